@@ -1,5 +1,5 @@
 use config::{Config, ConfigError, Environment, File};
-use serde::Deserialize;
+use serde::{Deserialize, Deserializer, Serialize};
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct AppConfig {
@@ -11,8 +11,14 @@ pub struct AppConfig {
     pub qdrant: QdrantConfig,
     pub llm: LlmConfig,
     pub features: FeatureFlags,
+    pub extraction: ExtractionConfig,
+    pub ocr: OcrConfig,
     pub crawler: CrawlerConfig,
     pub widget: WidgetConfig,
+    pub sharing: SharingConfig,
+    pub exports: ExportConfig,
+    pub rate_limit: ApiRateLimitConfig,
+    pub scheduler: SchedulerConfig,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -20,6 +26,55 @@ pub struct ServerConfig {
     pub host: String,
     pub port: u16,
     pub max_upload_size_mb: usize,
+    /// Largest body accepted by every route except document upload.
+    pub max_json_body_kb: usize,
+    /// Seconds in-flight background work may keep running after SIGTERM/SIGINT
+    /// before it is aborted and its records marked failed.
+    pub shutdown_grace_secs: u64,
+    /// Document and crawl embedding jobs processed at the same time.
+    pub worker_concurrency: usize,
+    /// Origins allowed to call the API from a browser; `*` allows any (development
+    /// only) and an empty list none. The widget API always allows any origin.
+    #[serde(default, deserialize_with = "list_or_comma_separated")]
+    pub cors_allowed_origins: Vec<String>,
+    /// Reverse proxies (CIDR ranges or addresses) whose `X-Forwarded-For` is
+    /// trusted when resolving client IPs. Empty uses the peer address as is.
+    #[serde(default, deserialize_with = "list_or_comma_separated")]
+    pub trusted_proxies: Vec<String>,
+    /// Plain text for reading in a terminal, or one JSON object per line.
+    pub log_format: LogFormat,
+}
+
+/// How log lines are written to stdout.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    Text,
+    /// `timestamp`, `level`, `target`, `message` and the event's own fields at
+    /// the top level of each line.
+    Json,
+}
+
+/// Accept a list from the config files or a comma-separated string from an env var.
+fn list_or_comma_separated<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum ListOrString {
+        List(Vec<String>),
+        String(String),
+    }
+
+    Ok(match ListOrString::deserialize(deserializer)? {
+        ListOrString::List(list) => list,
+        ListOrString::String(s) => s
+            .split(',')
+            .map(|item| item.trim().to_string())
+            .filter(|item| !item.is_empty())
+            .collect(),
+    })
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -27,9 +82,27 @@ pub struct AuthConfig {
     pub enabled: bool,
     pub jwt_secret: String,
     pub jwt_expiry_hours: i64,
+    /// Create the admin from `admin_*` on a first start, for automated
+    /// deployments. Otherwise the first visitor creates it through setup.
+    #[serde(default)]
+    pub seed_admin_from_env: bool,
     pub admin_email: String,
     pub admin_password: String,
     pub admin_username: String,
+    /// Secret used to encrypt organization API keys at rest. Falls back to
+    /// `jwt_secret` when empty; changing it makes stored keys unreadable.
+    #[serde(default)]
+    pub encryption_key: String,
+}
+
+impl AuthConfig {
+    pub fn encryption_secret(&self) -> &str {
+        if self.encryption_key.is_empty() {
+            &self.jwt_secret
+        } else {
+            &self.encryption_key
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -60,23 +133,141 @@ pub struct QdrantConfig {
     pub url: String,
     pub collection_name: String,
     pub vector_size: u64,
+    /// Dimensions of embedding models missing from the built-in table, or
+    /// corrections to it, e.g. `{ "my-embedder" = 768 }`.
+    #[serde(default)]
+    pub embedding_dimensions: std::collections::HashMap<String, u64>,
+    /// Start even when `vector_size` disagrees with the embedding model or the
+    /// existing collection. Also set by the `--skip-vector-validation` flag.
+    #[serde(default)]
+    pub skip_vector_validation: bool,
+    /// Points sent per upsert request; large documents are split into several.
+    pub upsert_batch_size: usize,
+    /// Characters of chunk content kept in each point's payload. The full
+    /// content stays in Postgres.
+    pub max_payload_content_chars: usize,
 }
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct LlmConfig {
     pub default_provider: String,
     pub default_model: String,
+    /// Provider used for embeddings; separate from `default_provider` because
+    /// some completion providers (e.g. Anthropic) have no embeddings API.
+    pub default_embedding_provider: String,
     pub default_embedding_model: String,
     pub default_system_prompt: String,
+    pub retrieval_mode: RetrievalMode,
+    pub rag_top_k: u64,
+    pub rag_min_score: f32,
+    pub rag_max_context_chars: usize,
+    pub rerank: RerankMode,
+    /// Replace the truncated first message with an LLM-written title after the first exchange.
+    pub auto_title_enabled: bool,
+    /// Model for generated titles, used with the conversation's provider; empty uses the chat model.
+    pub title_model: String,
+    /// Latest messages sent verbatim with each chat message; older ones are
+    /// folded into the conversation's summary.
+    pub history_recent_messages: usize,
+    /// Characters of system prompt, summary, history, knowledge base context
+    /// and message sent per chat message. The oldest recent messages are
+    /// dropped to fit.
+    pub history_max_chars: usize,
+    /// Cheap model per provider that writes conversation summaries, e.g.
+    /// `{ openai = "gpt-4o-mini" }`. Conversations with a provider missing
+    /// here aren't summarized.
+    #[serde(default)]
+    pub summary_models: std::collections::HashMap<String, String>,
+    /// Earlier user messages searched with a new one, so follow-ups like
+    /// "what about the second option?" find what they refer to. 0 searches
+    /// with the new message alone.
+    pub query_history_messages: usize,
+    /// Also search with the start of the conversation's summary.
+    pub query_include_summary: bool,
+    /// Have the summary model (or the chat model) rewrite follow-ups into a
+    /// standalone question before searching, instead of joining messages.
+    pub query_rewrite_enabled: bool,
+    /// What answers chat messages and embeds text.
+    pub backend: LlmBackend,
+    /// Seconds a completion call may take before it is abandoned.
+    pub request_timeout_secs: u64,
+    /// Seconds an embedding call may take before it is abandoned.
+    pub embedding_request_timeout_secs: u64,
+    /// Consecutive failed calls (timeouts, server and connection errors) after
+    /// which a provider's calls fail fast; 0 disables the breaker.
+    pub circuit_breaker_threshold: u32,
+    /// Seconds a tripped provider is left alone before one call probes it again.
+    pub circuit_breaker_cooldown_secs: u64,
+    /// Providers a chat reply is retried with, in order, when the chosen one
+    /// times out, rate limits or fails with a server error. Each answers with
+    /// its default chat model from the admin catalogue. Users can set their own
+    /// list. Embeddings never switch providers, as vectors from another model
+    /// can't be compared; retrieval is skipped instead when they fail.
+    #[serde(default, deserialize_with = "list_or_comma_separated")]
+    pub fallback_providers: Vec<String>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+/// How RAG context is retrieved: pure vector similarity, or vector search fused
+/// with Postgres full-text keyword search.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RetrievalMode {
+    Vector,
+    Hybrid,
+}
+
+/// `rig` calls the configured providers; `fake` echoes prompts and returns
+/// hash-based embeddings, for tests and development without API keys.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LlmBackend {
+    Rig,
+    Fake,
+}
+
+/// Optional reranking of retrieved chunks before they are cut down to `rag_top_k`.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RerankMode {
+    None,
+    Lexical,
+    Cohere,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct FeatureFlags {
     pub auth_enabled: bool,
     pub document_upload_enabled: bool,
     pub web_crawl_enabled: bool,
     pub admin_panel_enabled: bool,
     pub widget_enabled: bool,
+    /// Let users without their own key, and widgets without a key of their own,
+    /// fall back to the organization key for a provider.
+    pub allow_shared_api_keys: bool,
+    /// OCR scanned PDFs with `tesseract` instead of failing them. Requires
+    /// `tesseract` and poppler's `pdftoppm`/`pdfinfo` on the PATH.
+    pub ocr_enabled: bool,
+    /// Expose `/api/debug/*` to maintainers, e.g. to inspect what retrieval finds.
+    pub debug_endpoints_enabled: bool,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct ExtractionConfig {
+    /// Rows read from a CSV file or spreadsheet sheet; the rest is dropped with
+    /// a note in the extracted text.
+    pub max_table_rows: usize,
+}
+
+/// Limits for OCR of scanned PDFs (see `features.ocr_enabled`).
+#[derive(Debug, Deserialize, Clone)]
+pub struct OcrConfig {
+    /// Pages beyond this are not OCR'd.
+    pub max_pages: usize,
+    /// Time allowed to render and recognize a single page.
+    pub page_timeout_secs: u64,
+    /// Tesseract language(s), e.g. `eng` or `eng+deu`.
+    pub language: String,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -85,12 +276,80 @@ pub struct CrawlerConfig {
     pub max_depth: usize,
     pub request_timeout_secs: u64,
     pub user_agent: String,
+    /// Largest response accepted when a single page is ingested on its own.
+    pub max_page_size_mb: usize,
 }
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct WidgetConfig {
     pub enabled: bool,
     pub default_rate_limit: i32,
+    /// Days without a message after which a widget session is purged; 0 keeps them forever.
+    pub session_retention_days: u32,
+    /// Also delete the purged sessions' conversations instead of keeping them for the admin logs.
+    pub purge_session_conversations: bool,
+    /// Requests per minute one widget session may make to delete its conversations.
+    pub delete_requests_per_minute: u32,
+    /// Email admins when an embed key crosses 80% and 100% of a message cap.
+    pub message_cap_alert_emails: bool,
+    /// Transcripts one widget session may email per day.
+    pub transcript_emails_per_day: u32,
+    /// Transcripts visitors from one client IP may email per day, across sessions.
+    pub transcript_emails_per_ip_per_day: u32,
+    /// Transcripts all visitors of one embed key may email per day.
+    pub transcript_emails_per_embed_key_per_day: u32,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct SharingConfig {
+    /// How long a conversation link stays valid when the request doesn't say.
+    pub default_expiry_days: u32,
+    pub max_expiry_days: u32,
+    /// Requests per minute one client IP may make to shared conversation links.
+    pub public_requests_per_minute: u32,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct ExportConfig {
+    /// How long a finished personal data export can be downloaded before its
+    /// file is deleted.
+    pub expiry_hours: u32,
+}
+
+/// Per-user limits on the authenticated API, so a runaway script with a valid
+/// token can't run up LLM bills.
+#[derive(Debug, Deserialize, Clone)]
+pub struct ApiRateLimitConfig {
+    /// Requests per minute one user may make; 0 turns the limit off.
+    pub requests_per_minute: u32,
+    /// Requests per minute one user may make to endpoints that call a model,
+    /// such as sending a chat message; 0 turns the limit off.
+    pub llm_requests_per_minute: u32,
+    /// Don't limit admins.
+    pub exempt_admins: bool,
+}
+
+/// Periodic maintenance jobs run by [`crate::services::scheduler`].
+#[derive(Debug, Deserialize, Clone)]
+pub struct SchedulerConfig {
+    /// Up to this many seconds are added at random before each run, so
+    /// replicas started together don't run a job at the same moment.
+    pub jitter_secs: u64,
+    /// Settings per job name. Jobs without an entry are enabled.
+    #[serde(default)]
+    pub jobs: std::collections::HashMap<String, ScheduledJobConfig>,
+}
+
+impl SchedulerConfig {
+    pub fn is_enabled(&self, job: &str) -> bool {
+        self.jobs.get(job).is_none_or(|j| j.enabled)
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct ScheduledJobConfig {
+    /// A disabled job only runs when an admin triggers it.
+    pub enabled: bool,
 }
 
 impl AppConfig {
@@ -104,6 +363,144 @@ impl AppConfig {
             .build()?
             .try_deserialize()
     }
+
+    /// Check the loaded settings for values that would only fail later, and
+    /// in confusing ways. Run with `RUN_ENV=production`, sample credentials
+    /// from `config/default.toml` are refused as well.
+    pub fn validate(&self) -> Result<(), ConfigValidationError> {
+        let production = std::env::var("RUN_ENV").is_ok_and(|env| env == "production");
+        let violations = self.violations(production);
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigValidationError(violations))
+        }
+    }
+
+    fn violations(&self, production: bool) -> Vec<String> {
+        let mut v = Violations::default();
+
+        let secret = &self.auth.jwt_secret;
+        if secret.chars().count() < MIN_SECRET_CHARS {
+            v.add("auth.jwt_secret", format!("must be at least {MIN_SECRET_CHARS} characters"));
+        } else if distinct_chars(secret) < MIN_SECRET_DISTINCT_CHARS {
+            v.add("auth.jwt_secret", "is too predictable; use a random string");
+        } else if production && secret == SAMPLE_JWT_SECRET {
+            v.add("auth.jwt_secret", "is the sample value from config/default.toml");
+        }
+        if !self.auth.encryption_key.is_empty() && self.auth.encryption_key.chars().count() < MIN_SECRET_CHARS {
+            v.add("auth.encryption_key", format!("must be empty or at least {MIN_SECRET_CHARS} characters"));
+        }
+        // The admin password is only used to seed the first admin
+        let password = &self.auth.admin_password;
+        if self.auth.seed_admin_from_env {
+            if password.chars().count() < MIN_ADMIN_PASSWORD_CHARS {
+                v.add("auth.admin_password", format!("must be at least {MIN_ADMIN_PASSWORD_CHARS} characters"));
+            } else if production && SAMPLE_ADMIN_PASSWORDS.contains(&password.to_lowercase().as_str()) {
+                v.add("auth.admin_password", "must be changed from the default in production");
+            }
+        }
+        v.positive("auth.jwt_expiry_hours", self.auth.jwt_expiry_hours);
+
+        v.url("database.url", &self.database.url, &["postgres", "postgresql"]);
+        v.url("minio.endpoint", &self.minio.endpoint, &["http", "https"]);
+        v.url("qdrant.url", &self.qdrant.url, &["http", "https"]);
+        v.url("resend.frontend_url", &self.resend.frontend_url, &["http", "https"]);
+        v.non_empty("minio.bucket_name", &self.minio.bucket_name);
+        v.non_empty("qdrant.collection_name", &self.qdrant.collection_name);
+
+        v.positive("server.port", self.server.port);
+        v.positive("server.max_upload_size_mb", self.server.max_upload_size_mb);
+        v.positive("server.max_json_body_kb", self.server.max_json_body_kb);
+        v.positive("server.worker_concurrency", self.server.worker_concurrency);
+        v.positive("database.max_connections", self.database.max_connections);
+        v.positive("qdrant.vector_size", self.qdrant.vector_size);
+        v.positive("qdrant.upsert_batch_size", self.qdrant.upsert_batch_size);
+        v.positive("qdrant.max_payload_content_chars", self.qdrant.max_payload_content_chars);
+
+        v.non_empty("llm.default_provider", &self.llm.default_provider);
+        v.non_empty("llm.default_model", &self.llm.default_model);
+        v.non_empty("llm.default_embedding_provider", &self.llm.default_embedding_provider);
+        v.non_empty("llm.default_embedding_model", &self.llm.default_embedding_model);
+        v.positive("llm.rag_top_k", self.llm.rag_top_k);
+        v.positive("llm.rag_max_context_chars", self.llm.rag_max_context_chars);
+        v.positive("llm.history_max_chars", self.llm.history_max_chars);
+        v.positive("llm.request_timeout_secs", self.llm.request_timeout_secs);
+        v.positive("llm.embedding_request_timeout_secs", self.llm.embedding_request_timeout_secs);
+        if !self.llm.rag_min_score.is_finite() {
+            v.add("llm.rag_min_score", "must be a number");
+        }
+
+        v.positive("extraction.max_table_rows", self.extraction.max_table_rows);
+        v.positive("ocr.page_timeout_secs", self.ocr.page_timeout_secs);
+        v.positive("crawler.max_concurrent", self.crawler.max_concurrent);
+        v.positive("crawler.request_timeout_secs", self.crawler.request_timeout_secs);
+        v.positive("crawler.max_page_size_mb", self.crawler.max_page_size_mb);
+        v.positive("sharing.default_expiry_days", self.sharing.default_expiry_days);
+        if self.sharing.default_expiry_days > self.sharing.max_expiry_days {
+            v.add("sharing.default_expiry_days", "must not exceed sharing.max_expiry_days");
+        }
+        v.positive("exports.expiry_hours", self.exports.expiry_hours);
+
+        v.0
+    }
+}
+
+/// Shortest accepted `jwt_secret` (and `encryption_key`, when set).
+const MIN_SECRET_CHARS: usize = 32;
+
+/// Different characters a secret needs, so `aaaa…` or `1212…` don't pass.
+const MIN_SECRET_DISTINCT_CHARS: usize = 10;
+
+/// Setup enforces the same minimum for invited users.
+const MIN_ADMIN_PASSWORD_CHARS: usize = 8;
+
+const SAMPLE_JWT_SECRET: &str = "change-me-in-production-min-32-characters-long";
+
+/// Passwords refused for the seeded admin in production, compared lowercased.
+const SAMPLE_ADMIN_PASSWORDS: &[&str] = &["changeme123!", "changeme", "password", "admin123", "administrator"];
+
+fn distinct_chars(s: &str) -> usize {
+    s.chars().collect::<std::collections::HashSet<_>>().len()
+}
+
+/// Everything [`AppConfig::validate`] found wrong, one setting per line.
+#[derive(Debug, thiserror::Error)]
+#[error("Invalid configuration:\n  - {}", .0.join("\n  - "))]
+pub struct ConfigValidationError(pub Vec<String>);
+
+/// Collects violations, each naming the setting and the env var that sets it.
+#[derive(Default)]
+struct Violations(Vec<String>);
+
+impl Violations {
+    fn add(&mut self, key: &str, problem: impl std::fmt::Display) {
+        let env = format!("APP__{}", key.replace('.', "__").to_uppercase());
+        self.0.push(format!("{key} {problem} (set in config/*.toml or {env})"));
+    }
+
+    fn non_empty(&mut self, key: &str, value: &str) {
+        if value.trim().is_empty() {
+            self.add(key, "must not be empty");
+        }
+    }
+
+    fn positive<T: Default + PartialOrd>(&mut self, key: &str, value: T) {
+        if value <= T::default() {
+            self.add(key, "must be greater than 0");
+        }
+    }
+
+    fn url(&mut self, key: &str, value: &str, schemes: &[&str]) {
+        match url::Url::parse(value) {
+            Ok(url) if !schemes.contains(&url.scheme()) => {
+                self.add(key, format!("must be a {} URL, got '{}://…'", schemes.join(" or "), url.scheme()))
+            }
+            Ok(url) if url.host_str().is_none_or(str::is_empty) => self.add(key, "must include a host"),
+            Ok(_) => {}
+            Err(e) => self.add(key, format!("is not a valid URL ({e})")),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -126,8 +523,31 @@ mod tests {
 
         let config = config.unwrap();
         assert_eq!(config.server.port, 3000);
+        assert_eq!(config.server.shutdown_grace_secs, 30);
+        assert_eq!(config.server.worker_concurrency, 4);
         assert!(config.features.auth_enabled);
         assert!(config.features.document_upload_enabled);
+        assert!(config.features.allow_shared_api_keys);
+        assert!(!config.features.ocr_enabled);
+        assert_eq!(config.ocr.max_pages, 50);
+        assert_eq!(config.extraction.max_table_rows, 100_000);
+        assert_eq!(config.auth.encryption_secret(), config.auth.jwt_secret);
+        assert_eq!(config.llm.default_embedding_provider, "openai");
+        assert_eq!(config.llm.retrieval_mode, RetrievalMode::Vector);
+        assert_eq!(config.llm.rag_top_k, 5);
+        assert_eq!(config.llm.rerank, RerankMode::None);
+        assert!(config.llm.auto_title_enabled);
+        assert_eq!(config.llm.history_recent_messages, 20);
+        assert_eq!(config.llm.summary_models["openai"], "gpt-4o-mini");
+        assert_eq!(config.llm.query_history_messages, 1);
+        assert!(!config.llm.query_rewrite_enabled);
+        assert_eq!(config.llm.backend, LlmBackend::Rig);
+        assert_eq!(config.widget.session_retention_days, 90);
+        assert!(!config.widget.purge_session_conversations);
+        assert_eq!(config.sharing.default_expiry_days, 7);
+        assert_eq!(config.server.cors_allowed_origins, vec!["http://localhost:5173"]);
+        assert!(config.server.trusted_proxies.is_empty());
+        assert_eq!(config.server.log_format, LogFormat::Text);
     }
 
     #[test]
@@ -141,4 +561,161 @@ mod tests {
 
         unsafe { std::env::remove_var("APP__SERVER__PORT") };
     }
+
+    #[test]
+    fn test_rag_env_override() {
+        let _guard = ENV_LOCK.lock().unwrap();
+        unsafe { std::env::set_var("APP__LLM__RAG_TOP_K", "12") };
+        unsafe { std::env::set_var("APP__LLM__RAG_MIN_SCORE", "0.35") };
+        unsafe { std::env::set_var("RUN_ENV", "development") };
+
+        let config = AppConfig::load().unwrap();
+        assert_eq!(config.llm.rag_top_k, 12);
+        assert!((config.llm.rag_min_score - 0.35).abs() < f32::EPSILON);
+
+        unsafe { std::env::remove_var("APP__LLM__RAG_TOP_K") };
+        unsafe { std::env::remove_var("APP__LLM__RAG_MIN_SCORE") };
+    }
+
+    #[test]
+    fn test_cors_origins_env_override() {
+        let _guard = ENV_LOCK.lock().unwrap();
+        unsafe {
+            std::env::set_var(
+                "APP__SERVER__CORS_ALLOWED_ORIGINS",
+                "https://app.example.com, https://admin.example.com",
+            )
+        };
+        unsafe { std::env::set_var("RUN_ENV", "development") };
+
+        let config = AppConfig::load().unwrap();
+        assert_eq!(
+            config.server.cors_allowed_origins,
+            vec!["https://app.example.com", "https://admin.example.com"]
+        );
+
+        unsafe { std::env::set_var("APP__SERVER__CORS_ALLOWED_ORIGINS", "") };
+        let config = AppConfig::load().unwrap();
+        assert!(config.server.cors_allowed_origins.is_empty());
+
+        unsafe { std::env::remove_var("APP__SERVER__CORS_ALLOWED_ORIGINS") };
+    }
+
+    fn default_config() -> AppConfig {
+        let _guard = ENV_LOCK.lock().unwrap();
+        unsafe { std::env::set_var("RUN_ENV", "development") };
+        AppConfig::load().unwrap()
+    }
+
+    /// The settings `violations` complains about, without the explanations.
+    fn violated(config: &AppConfig, production: bool) -> Vec<String> {
+        config
+            .violations(production)
+            .iter()
+            .map(|v| v.split(' ').next().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_default_config_is_valid_for_development() {
+        let config = default_config();
+        assert!(config.violations(false).is_empty(), "{:?}", config.violations(false));
+    }
+
+    #[test]
+    fn test_production_refuses_sample_credentials() {
+        let mut config = default_config();
+        assert_eq!(violated(&config, true), ["auth.jwt_secret"]);
+        config.auth.seed_admin_from_env = true;
+        assert_eq!(violated(&config, true), ["auth.jwt_secret", "auth.admin_password"]);
+
+        config.auth.jwt_secret = "q8Zr2vN0xLp4Tb7Wm1Yc9Ks3Hd6Fg5Ja".to_string();
+        config.auth.admin_password = "Admin123".to_string();
+        assert_eq!(violated(&config, true), ["auth.admin_password"]);
+        config.auth.admin_password = "a long and unusual passphrase".to_string();
+        assert!(violated(&config, true).is_empty());
+    }
+
+    #[test]
+    fn test_secrets_need_length_and_variety() {
+        let mut config = default_config();
+        config.auth.jwt_secret = String::new();
+        assert_eq!(violated(&config, false), ["auth.jwt_secret"]);
+        config.auth.jwt_secret = "ab".repeat(20);
+        assert!(config.violations(false)[0].contains("too predictable"));
+        config.auth.jwt_secret = SAMPLE_JWT_SECRET.to_string();
+        config.auth.encryption_key = "short".to_string();
+        assert_eq!(violated(&config, false), ["auth.encryption_key"]);
+    }
+
+    #[test]
+    fn test_admin_password_needs_eight_characters() {
+        let mut config = default_config();
+        config.auth.admin_password = "admin".to_string();
+        // Only checked when it is used
+        assert!(violated(&config, false).is_empty());
+        config.auth.seed_admin_from_env = true;
+        assert_eq!(violated(&config, false), ["auth.admin_password"]);
+    }
+
+    #[test]
+    fn test_endpoints_must_be_urls_with_the_right_scheme() {
+        let mut config = default_config();
+        config.qdrant.url = "localhost:6334".to_string();
+        config.minio.endpoint = "ftp://minio:9000".to_string();
+        config.database.url = "http://db:5432/rag".to_string();
+        config.resend.frontend_url = "not a url".to_string();
+        assert_eq!(
+            violated(&config, false),
+            ["database.url", "minio.endpoint", "qdrant.url", "resend.frontend_url"]
+        );
+        assert!(config.violations(false)[1].contains("must be a http or https URL, got 'ftp://…'"));
+    }
+
+    #[test]
+    fn test_sizes_and_limits_must_be_positive() {
+        let mut config = default_config();
+        config.server.max_upload_size_mb = 0;
+        config.qdrant.vector_size = 0;
+        config.llm.rag_top_k = 0;
+        config.auth.jwt_expiry_hours = -1;
+        config.llm.rag_min_score = f32::NAN;
+        config.sharing.default_expiry_days = config.sharing.max_expiry_days + 1;
+        assert_eq!(
+            violated(&config, false),
+            [
+                "auth.jwt_expiry_hours",
+                "server.max_upload_size_mb",
+                "qdrant.vector_size",
+                "llm.rag_top_k",
+                "llm.rag_min_score",
+                "sharing.default_expiry_days"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_default_models_must_be_set() {
+        let mut config = default_config();
+        config.llm.default_provider = " ".to_string();
+        config.llm.default_embedding_model = String::new();
+        assert_eq!(
+            violated(&config, false),
+            ["llm.default_provider", "llm.default_embedding_model"]
+        );
+    }
+
+    #[test]
+    fn test_all_violations_are_reported_together() {
+        let mut config = default_config();
+        config.server.max_upload_size_mb = 0;
+        config.llm.default_model = String::new();
+        let message = ConfigValidationError(config.violations(false)).to_string();
+        assert_eq!(
+            message,
+            "Invalid configuration:\n  \
+             - server.max_upload_size_mb must be greater than 0 (set in config/*.toml or APP__SERVER__MAX_UPLOAD_SIZE_MB)\n  \
+             - llm.default_model must not be empty (set in config/*.toml or APP__LLM__DEFAULT_MODEL)"
+        );
+    }
 }
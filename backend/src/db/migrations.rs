@@ -1,7 +1,19 @@
 use anyhow::{Context, Result};
 use sqlx::PgPool;
 
+use crate::db::startup_lock::StartupLock;
+
+/// Bring the schema up to date. Replicas starting together take turns, so
+/// only one of them changes the schema at a time.
 pub async fn run_all(pool: &PgPool) -> Result<()> {
+    let lock = StartupLock::acquire(pool).await?;
+    tracing::info!("Running database migrations as replica {}", lock.replica());
+    let result = migrate(pool).await;
+    lock.release().await?;
+    result
+}
+
+async fn migrate(pool: &PgPool) -> Result<()> {
     create_users_table(pool).await?;
     create_user_invites_table(pool).await?;
     add_maintainer_role(pool).await?;
@@ -18,6 +30,52 @@ pub async fn run_all(pool: &PgPool) -> Result<()> {
     create_widget_sessions_table(pool).await?;
     add_widget_columns_to_conversations(pool).await?;
     add_custom_css_to_embed_keys(pool).await?;
+    add_embedding_columns_to_documents(pool).await?;
+    add_fulltext_index_to_document_chunks(pool).await?;
+    add_rag_overrides_to_embed_keys(pool).await?;
+    add_base_url_columns(pool).await?;
+    add_removed_at_to_admin_models(pool).await?;
+    create_admin_api_keys_table(pool).await?;
+    add_default_embedding_to_admin_providers(pool).await?;
+    create_jobs_table(pool).await?;
+    add_localizations_to_embed_keys(pool).await?;
+    add_title_is_custom_to_conversations(pool).await?;
+    add_preview_and_last_used_to_user_api_keys(pool).await?;
+    add_last_message_index_to_widget_sessions(pool).await?;
+    add_handoff_columns_to_embed_keys(pool).await?;
+    create_widget_handoffs_table(pool).await?;
+    add_sort_order_and_is_enabled_to_admin_models(pool).await?;
+    add_tags_to_documents(pool).await?;
+    create_collections_table(pool).await?;
+    create_crawl_schedules_table(pool).await?;
+    create_web_pages_table(pool).await?;
+    add_embed_key_index_to_conversations(pool).await?;
+    create_document_events_table(pool).await?;
+    create_conversation_shares_table(pool).await?;
+    add_chat_settings_to_conversations(pool).await?;
+    create_pending_vector_deletions_table(pool).await?;
+    create_webhook_tables(pool).await?;
+    create_impersonation_sessions_table(pool).await?;
+    add_generation_columns_to_messages(pool).await?;
+    add_message_caps_to_embed_keys(pool).await?;
+    add_archived_at_to_conversations(pool).await?;
+    add_theme_to_embed_keys(pool).await?;
+    create_data_exports_table(pool).await?;
+    add_summary_to_conversations(pool).await?;
+    create_rescan_runs_table(pool).await?;
+    add_avatar_key_to_embed_keys(pool).await?;
+    normalize_user_emails(pool).await?;
+    create_email_outbox_table(pool).await?;
+    add_deleted_at_to_messages(pool).await?;
+    add_versioning_to_documents(pool).await?;
+    create_scheduled_job_runs_table(pool).await?;
+    add_provider_fallbacks(pool).await?;
+    add_transcript_email_enabled_to_embed_keys(pool).await?;
+    create_org_settings_tables(pool).await?;
+    add_office_hours_to_embed_keys(pool).await?;
+    add_extraction_fingerprints(pool).await?;
+    add_context_chunks_to_messages(pool).await?;
+    add_leases(pool).await?;
     tracing::info!("Database migrations completed");
     Ok(())
 }
@@ -453,3 +511,956 @@ async fn add_custom_css_to_embed_keys(pool: &PgPool) -> Result<()> {
 
     Ok(())
 }
+
+async fn add_embedding_columns_to_documents(pool: &PgPool) -> Result<()> {
+    sqlx::query("ALTER TABLE documents ADD COLUMN IF NOT EXISTS embedding_model TEXT DEFAULT NULL")
+        .execute(pool)
+        .await
+        .context("Failed to add embedding_model to documents")?;
+
+    sqlx::query("ALTER TABLE documents ADD COLUMN IF NOT EXISTS vector_collection TEXT DEFAULT NULL")
+        .execute(pool)
+        .await
+        .context("Failed to add vector_collection to documents")?;
+
+    Ok(())
+}
+
+async fn add_fulltext_index_to_document_chunks(pool: &PgPool) -> Result<()> {
+    sqlx::query(
+        "CREATE INDEX IF NOT EXISTS idx_document_chunks_fts
+         ON document_chunks USING GIN (to_tsvector('english', content))",
+    )
+    .execute(pool)
+    .await
+    .context("Failed to create full-text index on document_chunks")?;
+
+    Ok(())
+}
+
+async fn add_rag_overrides_to_embed_keys(pool: &PgPool) -> Result<()> {
+    sqlx::query("ALTER TABLE embed_keys ADD COLUMN IF NOT EXISTS rag_top_k INTEGER DEFAULT NULL")
+        .execute(pool)
+        .await
+        .context("Failed to add rag_top_k to embed_keys")?;
+
+    sqlx::query("ALTER TABLE embed_keys ADD COLUMN IF NOT EXISTS rag_min_score REAL DEFAULT NULL")
+        .execute(pool)
+        .await
+        .context("Failed to add rag_min_score to embed_keys")?;
+
+    sqlx::query(
+        "ALTER TABLE embed_keys ADD COLUMN IF NOT EXISTS rag_max_context_chars INTEGER DEFAULT NULL",
+    )
+    .execute(pool)
+    .await
+    .context("Failed to add rag_max_context_chars to embed_keys")?;
+
+    Ok(())
+}
+
+async fn add_base_url_columns(pool: &PgPool) -> Result<()> {
+    sqlx::query("ALTER TABLE user_api_keys ADD COLUMN IF NOT EXISTS base_url TEXT DEFAULT NULL")
+        .execute(pool)
+        .await
+        .context("Failed to add base_url to user_api_keys")?;
+
+    sqlx::query("ALTER TABLE embed_keys ADD COLUMN IF NOT EXISTS base_url TEXT DEFAULT NULL")
+        .execute(pool)
+        .await
+        .context("Failed to add base_url to embed_keys")?;
+
+    Ok(())
+}
+
+async fn add_removed_at_to_admin_models(pool: &PgPool) -> Result<()> {
+    sqlx::query(
+        "ALTER TABLE admin_models ADD COLUMN IF NOT EXISTS removed_at TIMESTAMPTZ DEFAULT NULL",
+    )
+    .execute(pool)
+    .await
+    .context("Failed to add removed_at to admin_models")?;
+
+    Ok(())
+}
+
+async fn create_admin_api_keys_table(pool: &PgPool) -> Result<()> {
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS admin_api_keys (
+            provider TEXT PRIMARY KEY,
+            api_key_encrypted TEXT NOT NULL,
+            base_url TEXT,
+            updated_by TEXT REFERENCES users(id) ON DELETE SET NULL,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+            updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+        )",
+    )
+    .execute(pool)
+    .await
+    .context("Failed to create admin_api_keys table")?;
+
+    Ok(())
+}
+
+async fn add_default_embedding_to_admin_providers(pool: &PgPool) -> Result<()> {
+    sqlx::query(
+        "ALTER TABLE admin_providers
+         ADD COLUMN IF NOT EXISTS is_default_embedding BOOLEAN NOT NULL DEFAULT FALSE",
+    )
+    .execute(pool)
+    .await
+    .context("Failed to add is_default_embedding to admin_providers")?;
+
+    Ok(())
+}
+
+async fn create_jobs_table(pool: &PgPool) -> Result<()> {
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS jobs (
+            id TEXT PRIMARY KEY,
+            kind TEXT NOT NULL,
+            payload JSONB NOT NULL,
+            status TEXT NOT NULL CHECK(status IN ('queued', 'running', 'completed', 'dead'))
+                DEFAULT 'queued',
+            attempts INTEGER NOT NULL DEFAULT 0,
+            max_attempts INTEGER NOT NULL DEFAULT 3,
+            last_error TEXT,
+            next_run_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+            updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+        )",
+    )
+    .execute(pool)
+    .await
+    .context("Failed to create jobs table")?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_jobs_status_next_run ON jobs(status, next_run_at)")
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+async fn add_localizations_to_embed_keys(pool: &PgPool) -> Result<()> {
+    sqlx::query(
+        "ALTER TABLE embed_keys ADD COLUMN IF NOT EXISTS localizations JSONB NOT NULL DEFAULT '{}'",
+    )
+    .execute(pool)
+    .await
+    .context("Failed to add localizations to embed_keys")?;
+
+    Ok(())
+}
+
+async fn add_title_is_custom_to_conversations(pool: &PgPool) -> Result<()> {
+    sqlx::query(
+        "ALTER TABLE conversations ADD COLUMN IF NOT EXISTS title_is_custom BOOLEAN NOT NULL DEFAULT FALSE",
+    )
+    .execute(pool)
+    .await
+    .context("Failed to add title_is_custom to conversations")?;
+
+    Ok(())
+}
+
+async fn add_preview_and_last_used_to_user_api_keys(pool: &PgPool) -> Result<()> {
+    for statement in [
+        "ALTER TABLE user_api_keys ADD COLUMN IF NOT EXISTS key_preview TEXT NOT NULL DEFAULT ''",
+        "ALTER TABLE user_api_keys ADD COLUMN IF NOT EXISTS last_used_at TIMESTAMPTZ DEFAULT NULL",
+        // Same format as settings::key_preview
+        "UPDATE user_api_keys
+         SET key_preview = CASE WHEN char_length(api_key) >= 12
+                                THEN left(api_key, 4) || '...' || right(api_key, 4)
+                                ELSE '****' END
+         WHERE key_preview = ''",
+    ] {
+        sqlx::query(statement)
+            .execute(pool)
+            .await
+            .context("Failed to add key_preview and last_used_at to user_api_keys")?;
+    }
+
+    Ok(())
+}
+
+async fn add_last_message_index_to_widget_sessions(pool: &PgPool) -> Result<()> {
+    sqlx::query(
+        "CREATE INDEX IF NOT EXISTS idx_widget_sessions_last_message ON widget_sessions(last_message_at)",
+    )
+    .execute(pool)
+    .await
+    .context("Failed to index widget_sessions.last_message_at")?;
+
+    Ok(())
+}
+
+async fn add_handoff_columns_to_embed_keys(pool: &PgPool) -> Result<()> {
+    sqlx::query("ALTER TABLE embed_keys ADD COLUMN IF NOT EXISTS handoff_enabled BOOLEAN NOT NULL DEFAULT FALSE")
+        .execute(pool)
+        .await
+        .context("Failed to add handoff_enabled to embed_keys")?;
+
+    sqlx::query("ALTER TABLE embed_keys ADD COLUMN IF NOT EXISTS handoff_notification_email TEXT DEFAULT NULL")
+        .execute(pool)
+        .await
+        .context("Failed to add handoff_notification_email to embed_keys")?;
+
+    Ok(())
+}
+
+async fn create_widget_handoffs_table(pool: &PgPool) -> Result<()> {
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS widget_handoffs (
+            id TEXT PRIMARY KEY,
+            conversation_id TEXT NOT NULL REFERENCES conversations(id) ON DELETE CASCADE,
+            embed_key_id TEXT NOT NULL REFERENCES embed_keys(id) ON DELETE CASCADE,
+            session_id TEXT NOT NULL,
+            email TEXT NOT NULL,
+            message TEXT NOT NULL,
+            status TEXT NOT NULL CHECK(status IN ('open', 'closed')) DEFAULT 'open',
+            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+            updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+        )",
+    )
+    .execute(pool)
+    .await
+    .context("Failed to create widget_handoffs table")?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_widget_handoffs_status ON widget_handoffs(status, created_at)")
+        .execute(pool)
+        .await?;
+
+    sqlx::query(
+        "CREATE INDEX IF NOT EXISTS idx_widget_handoffs_session ON widget_handoffs(embed_key_id, session_id, created_at)",
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+async fn add_sort_order_and_is_enabled_to_admin_models(pool: &PgPool) -> Result<()> {
+    for statement in [
+        "ALTER TABLE admin_models ADD COLUMN IF NOT EXISTS sort_order INTEGER NOT NULL DEFAULT 0",
+        "ALTER TABLE admin_models ADD COLUMN IF NOT EXISTS is_enabled BOOLEAN NOT NULL DEFAULT TRUE",
+    ] {
+        sqlx::query(statement)
+            .execute(pool)
+            .await
+            .context("Failed to add sort_order and is_enabled to admin_models")?;
+    }
+
+    Ok(())
+}
+
+async fn add_tags_to_documents(pool: &PgPool) -> Result<()> {
+    for statement in [
+        "ALTER TABLE documents ADD COLUMN IF NOT EXISTS tags TEXT[] NOT NULL DEFAULT '{}'",
+        "CREATE INDEX IF NOT EXISTS idx_documents_tags ON documents USING GIN (tags)",
+    ] {
+        sqlx::query(statement)
+            .execute(pool)
+            .await
+            .context("Failed to add tags to documents")?;
+    }
+
+    Ok(())
+}
+
+async fn create_collections_table(pool: &PgPool) -> Result<()> {
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS collections (
+            id TEXT PRIMARY KEY,
+            name TEXT NOT NULL,
+            owner_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+        )",
+    )
+    .execute(pool)
+    .await
+    .context("Failed to create collections table")?;
+
+    // Deleting a collection keeps its documents, conversations and embed keys
+    for statement in [
+        "ALTER TABLE documents ADD COLUMN IF NOT EXISTS collection_id TEXT
+             REFERENCES collections(id) ON DELETE SET NULL",
+        "ALTER TABLE conversations ADD COLUMN IF NOT EXISTS collection_id TEXT
+             REFERENCES collections(id) ON DELETE SET NULL",
+        "ALTER TABLE embed_keys ADD COLUMN IF NOT EXISTS collection_id TEXT
+             REFERENCES collections(id) ON DELETE SET NULL",
+        "CREATE INDEX IF NOT EXISTS idx_documents_collection ON documents(collection_id)",
+    ] {
+        sqlx::query(statement)
+            .execute(pool)
+            .await
+            .context("Failed to add collection_id columns")?;
+    }
+
+    Ok(())
+}
+
+async fn create_crawl_schedules_table(pool: &PgPool) -> Result<()> {
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS crawl_schedules (
+            id TEXT PRIMARY KEY,
+            user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+            url TEXT NOT NULL,
+            crawl_type TEXT NOT NULL CHECK(crawl_type IN ('sitemap', 'full')),
+            frequency TEXT NOT NULL CHECK(frequency IN ('daily', 'weekly')),
+            next_run_at TIMESTAMPTZ NOT NULL,
+            last_job_id TEXT REFERENCES crawl_jobs(id) ON DELETE SET NULL,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+            updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+            UNIQUE(url, crawl_type)
+        )",
+    )
+    .execute(pool)
+    .await
+    .context("Failed to create crawl_schedules table")?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_crawl_schedules_next_run ON crawl_schedules(next_run_at)")
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+async fn create_web_pages_table(pool: &PgPool) -> Result<()> {
+    // Single pages are fetched through a lightweight crawl job of type 'page'
+    sqlx::query(
+        "DO $$
+        BEGIN
+            ALTER TABLE crawl_jobs DROP CONSTRAINT IF EXISTS crawl_jobs_crawl_type_check;
+            ALTER TABLE crawl_jobs ADD CONSTRAINT crawl_jobs_crawl_type_check
+                CHECK(crawl_type IN ('sitemap', 'full', 'page'));
+        END $$;",
+    )
+    .execute(pool)
+    .await
+    .context("Failed to allow page crawl jobs")?;
+
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS web_pages (
+            url TEXT PRIMARY KEY,
+            user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+            title TEXT,
+            vector_collection TEXT NOT NULL,
+            last_job_id TEXT REFERENCES crawl_jobs(id) ON DELETE SET NULL,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+            updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+        )",
+    )
+    .execute(pool)
+    .await
+    .context("Failed to create web_pages table")?;
+
+    Ok(())
+}
+
+async fn add_embed_key_index_to_conversations(pool: &PgPool) -> Result<()> {
+    // Embed key stats count each key's widget conversations
+    sqlx::query(
+        "CREATE INDEX IF NOT EXISTS idx_conversations_embed_key ON conversations(embed_key_id)
+         WHERE embed_key_id IS NOT NULL",
+    )
+    .execute(pool)
+    .await
+    .context("Failed to index conversations by embed key")?;
+
+    Ok(())
+}
+
+async fn create_document_events_table(pool: &PgPool) -> Result<()> {
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS document_events (
+            id BIGSERIAL PRIMARY KEY,
+            document_id TEXT NOT NULL REFERENCES documents(id) ON DELETE CASCADE,
+            level TEXT NOT NULL CHECK(level IN ('info', 'warn', 'error')),
+            message TEXT NOT NULL,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+        )",
+    )
+    .execute(pool)
+    .await
+    .context("Failed to create document_events table")?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_document_events_document ON document_events(document_id, id)")
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+async fn create_conversation_shares_table(pool: &PgPool) -> Result<()> {
+    // Only a hash of the share token is stored, like embed keys
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS conversation_shares (
+            id TEXT PRIMARY KEY,
+            token_hash TEXT NOT NULL UNIQUE,
+            conversation_id TEXT NOT NULL REFERENCES conversations(id) ON DELETE CASCADE,
+            created_by TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+            expires_at TIMESTAMPTZ NOT NULL,
+            revoked BOOLEAN NOT NULL DEFAULT FALSE,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+        )",
+    )
+    .execute(pool)
+    .await
+    .context("Failed to create conversation_shares table")?;
+
+    sqlx::query(
+        "CREATE INDEX IF NOT EXISTS idx_conversation_shares_conversation
+         ON conversation_shares(conversation_id)",
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+async fn add_chat_settings_to_conversations(pool: &PgPool) -> Result<()> {
+    // Per-conversation overrides of the user's preferences; NULL uses them
+    for column in ["provider", "model", "system_prompt"] {
+        sqlx::query(&format!("ALTER TABLE conversations ADD COLUMN IF NOT EXISTS {column} TEXT"))
+            .execute(pool)
+            .await
+            .with_context(|| format!("Failed to add {column} to conversations"))?;
+    }
+
+    Ok(())
+}
+
+async fn create_pending_vector_deletions_table(pool: &PgPool) -> Result<()> {
+    // Qdrant points whose chunk rows are gone but that couldn't be deleted yet
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS pending_vector_deletions (
+            id BIGSERIAL PRIMARY KEY,
+            collection TEXT NOT NULL,
+            point_ids TEXT[] NOT NULL,
+            attempts INTEGER NOT NULL DEFAULT 0,
+            last_error TEXT NOT NULL,
+            next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+        )",
+    )
+    .execute(pool)
+    .await
+    .context("Failed to create pending_vector_deletions table")?;
+
+    sqlx::query(
+        "CREATE INDEX IF NOT EXISTS idx_pending_vector_deletions_next_attempt
+         ON pending_vector_deletions(next_attempt_at)",
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+async fn create_webhook_tables(pool: &PgPool) -> Result<()> {
+    // An empty event list subscribes the webhook to every event
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS webhooks (
+            id TEXT PRIMARY KEY,
+            url TEXT NOT NULL,
+            secret_encrypted TEXT NOT NULL,
+            events TEXT[] NOT NULL DEFAULT '{}',
+            enabled BOOLEAN NOT NULL DEFAULT TRUE,
+            created_by TEXT REFERENCES users(id) ON DELETE SET NULL,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+            updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+        )",
+    )
+    .execute(pool)
+    .await
+    .context("Failed to create webhooks table")?;
+
+    // One row per attempt; attempts of the same event share a delivery_id
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS webhook_deliveries (
+            id BIGSERIAL PRIMARY KEY,
+            webhook_id TEXT NOT NULL REFERENCES webhooks(id) ON DELETE CASCADE,
+            delivery_id TEXT NOT NULL,
+            event TEXT NOT NULL,
+            attempt INTEGER NOT NULL,
+            status_code INTEGER,
+            success BOOLEAN NOT NULL,
+            error TEXT,
+            duration_ms BIGINT NOT NULL,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+        )",
+    )
+    .execute(pool)
+    .await
+    .context("Failed to create webhook_deliveries table")?;
+
+    sqlx::query(
+        "CREATE INDEX IF NOT EXISTS idx_webhook_deliveries_webhook
+         ON webhook_deliveries(webhook_id, id DESC)",
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+async fn create_impersonation_sessions_table(pool: &PgPool) -> Result<()> {
+    // Impersonation tokens name their session so ending it revokes them early
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS impersonation_sessions (
+            id TEXT PRIMARY KEY,
+            admin_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+            user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+            expires_at TIMESTAMPTZ NOT NULL,
+            ended_at TIMESTAMPTZ,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+        )",
+    )
+    .execute(pool)
+    .await
+    .context("Failed to create impersonation_sessions table")?;
+
+    sqlx::query(
+        "CREATE INDEX IF NOT EXISTS idx_impersonation_sessions_admin_user
+         ON impersonation_sessions(admin_id, user_id)",
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+async fn add_generation_columns_to_messages(pool: &PgPool) -> Result<()> {
+    // Which model wrote an assistant message; NULL on user messages and older replies
+    sqlx::query(
+        "ALTER TABLE messages
+            ADD COLUMN IF NOT EXISTS provider TEXT DEFAULT NULL,
+            ADD COLUMN IF NOT EXISTS model TEXT DEFAULT NULL,
+            ADD COLUMN IF NOT EXISTS prompt_tokens INTEGER DEFAULT NULL,
+            ADD COLUMN IF NOT EXISTS completion_tokens INTEGER DEFAULT NULL,
+            ADD COLUMN IF NOT EXISTS latency_ms INTEGER DEFAULT NULL",
+    )
+    .execute(pool)
+    .await
+    .context("Failed to add generation columns to messages")?;
+
+    Ok(())
+}
+
+async fn add_message_caps_to_embed_keys(pool: &PgPool) -> Result<()> {
+    // NULL leaves the key unlimited
+    sqlx::query(
+        "ALTER TABLE embed_keys
+            ADD COLUMN IF NOT EXISTS daily_message_limit INTEGER DEFAULT NULL,
+            ADD COLUMN IF NOT EXISTS monthly_message_limit INTEGER DEFAULT NULL",
+    )
+    .execute(pool)
+    .await
+    .context("Failed to add message caps to embed_keys")?;
+
+    // One counter per key and UTC day; a month's usage is the sum of its days
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS embed_key_daily_usage (
+            embed_key_id TEXT NOT NULL REFERENCES embed_keys(id) ON DELETE CASCADE,
+            day DATE NOT NULL,
+            message_count INTEGER NOT NULL DEFAULT 0,
+            PRIMARY KEY (embed_key_id, day)
+        )",
+    )
+    .execute(pool)
+    .await
+    .context("Failed to create embed_key_daily_usage table")?;
+
+    Ok(())
+}
+
+async fn add_archived_at_to_conversations(pool: &PgPool) -> Result<()> {
+    sqlx::query("ALTER TABLE conversations ADD COLUMN IF NOT EXISTS archived_at TIMESTAMPTZ DEFAULT NULL")
+        .execute(pool)
+        .await
+        .context("Failed to add archived_at to conversations")?;
+
+    Ok(())
+}
+
+async fn add_theme_to_embed_keys(pool: &PgPool) -> Result<()> {
+    // An empty theme uses the widget defaults, with primary_color as the primary color
+    sqlx::query("ALTER TABLE embed_keys ADD COLUMN IF NOT EXISTS theme JSONB NOT NULL DEFAULT '{}'")
+        .execute(pool)
+        .await
+        .context("Failed to add theme to embed_keys")?;
+
+    Ok(())
+}
+
+async fn create_data_exports_table(pool: &PgPool) -> Result<()> {
+    // No foreign key on user_id: a deleted user's export file is still removed
+    // when it expires
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS data_exports (
+            id TEXT PRIMARY KEY,
+            user_id TEXT NOT NULL,
+            format TEXT NOT NULL CHECK(format IN ('json', 'zip')),
+            status TEXT NOT NULL CHECK(status IN ('pending', 'ready', 'failed')) DEFAULT 'pending',
+            storage_key TEXT,
+            size_bytes BIGINT,
+            error_message TEXT,
+            expires_at TIMESTAMPTZ,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+            completed_at TIMESTAMPTZ
+        )",
+    )
+    .execute(pool)
+    .await
+    .context("Failed to create data_exports table")?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_data_exports_user ON data_exports(user_id, created_at DESC)")
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+async fn add_summary_to_conversations(pool: &PgPool) -> Result<()> {
+    // The summary covers the first summary_message_count messages
+    sqlx::query("ALTER TABLE conversations ADD COLUMN IF NOT EXISTS summary TEXT NOT NULL DEFAULT ''")
+        .execute(pool)
+        .await
+        .context("Failed to add summary to conversations")?;
+
+    sqlx::query(
+        "ALTER TABLE conversations ADD COLUMN IF NOT EXISTS summary_message_count INTEGER NOT NULL DEFAULT 0",
+    )
+    .execute(pool)
+    .await
+    .context("Failed to add summary_message_count to conversations")?;
+
+    Ok(())
+}
+
+async fn create_rescan_runs_table(pool: &PgPool) -> Result<()> {
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS rescan_runs (
+            id TEXT PRIMARY KEY,
+            started_by TEXT NOT NULL,
+            embedding_provider TEXT NOT NULL,
+            embedding_model TEXT NOT NULL,
+            status TEXT NOT NULL DEFAULT 'running' CHECK(status IN ('running', 'completed', 'interrupted')),
+            total INTEGER NOT NULL,
+            succeeded INTEGER NOT NULL DEFAULT 0,
+            failed INTEGER NOT NULL DEFAULT 0,
+            failed_document_ids TEXT[] NOT NULL DEFAULT '{}',
+            started_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+            finished_at TIMESTAMPTZ DEFAULT NULL
+        )",
+    )
+    .execute(pool)
+    .await
+    .context("Failed to create rescan_runs table")?;
+
+    // At most one rescan runs at a time
+    sqlx::query(
+        "CREATE UNIQUE INDEX IF NOT EXISTS idx_rescan_runs_running ON rescan_runs ((TRUE)) WHERE status = 'running'",
+    )
+    .execute(pool)
+    .await
+    .context("Failed to create rescan_runs running index")?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_rescan_runs_started ON rescan_runs(started_at DESC)")
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+async fn add_avatar_key_to_embed_keys(pool: &PgPool) -> Result<()> {
+    // Storage key of the widget's bot avatar; NULL uses the built-in icon
+    sqlx::query("ALTER TABLE embed_keys ADD COLUMN IF NOT EXISTS avatar_key TEXT DEFAULT NULL")
+        .execute(pool)
+        .await
+        .context("Failed to add avatar_key to embed_keys")?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_embed_keys_prefix ON embed_keys(key_prefix)")
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+async fn normalize_user_emails(pool: &PgPool) -> Result<()> {
+    // Emails are compared case-insensitively; accounts that only differ in
+    // case can't be merged automatically, so an admin has to resolve them
+    let duplicates: Vec<(String, String)> = sqlx::query_as(
+        "SELECT lower(trim(email)) AS email, string_agg(username, ', ' ORDER BY created_at) AS usernames
+         FROM users GROUP BY lower(trim(email)) HAVING COUNT(*) > 1 ORDER BY 1",
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to look for duplicate user emails")?;
+    if !duplicates.is_empty() {
+        let list = duplicates
+            .iter()
+            .map(|(email, usernames)| format!("{email} (users: {usernames})"))
+            .collect::<Vec<_>>()
+            .join("; ");
+        anyhow::bail!(
+            "Several accounts share an email when case is ignored: {list}. \
+             Change the email of all but one of each before starting the server."
+        );
+    }
+
+    let mut tx = pool.begin().await?;
+    sqlx::query("UPDATE users SET email = lower(trim(email)) WHERE email <> lower(trim(email))")
+        .execute(&mut *tx)
+        .await
+        .context("Failed to normalize user emails")?;
+    sqlx::query("UPDATE user_invites SET email = lower(trim(email)) WHERE email <> lower(trim(email))")
+        .execute(&mut *tx)
+        .await
+        .context("Failed to normalize invite emails")?;
+    sqlx::query("CREATE UNIQUE INDEX IF NOT EXISTS idx_users_email_lower ON users (lower(email))")
+        .execute(&mut *tx)
+        .await
+        .context("Failed to create case-insensitive email index")?;
+    tx.commit().await?;
+
+    Ok(())
+}
+
+async fn create_email_outbox_table(pool: &PgPool) -> Result<()> {
+    // Every email is written here before it is sent and retried from here
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS email_outbox (
+            id TEXT PRIMARY KEY,
+            recipient TEXT NOT NULL,
+            template TEXT NOT NULL,
+            payload JSONB NOT NULL DEFAULT '{}',
+            reference_id TEXT,
+            status TEXT NOT NULL CHECK(status IN ('pending', 'sent', 'failed')) DEFAULT 'pending',
+            attempts INTEGER NOT NULL DEFAULT 0,
+            last_error TEXT,
+            next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+            sent_at TIMESTAMPTZ
+        )",
+    )
+    .execute(pool)
+    .await
+    .context("Failed to create email_outbox table")?;
+
+    sqlx::query(
+        "CREATE INDEX IF NOT EXISTS idx_email_outbox_due
+         ON email_outbox(next_attempt_at) WHERE status = 'pending'",
+    )
+    .execute(pool)
+    .await?;
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_email_outbox_reference ON email_outbox(reference_id, created_at)")
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+async fn add_deleted_at_to_messages(pool: &PgPool) -> Result<()> {
+    // A deleted message keeps its row with the content wiped, so logs show the gap
+    sqlx::query("ALTER TABLE messages ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMPTZ DEFAULT NULL")
+        .execute(pool)
+        .await
+        .context("Failed to add deleted_at to messages")?;
+
+    Ok(())
+}
+
+async fn add_versioning_to_documents(pool: &PgPool) -> Result<()> {
+    // A re-upload under the same filename links to the document it replaces,
+    // which is superseded once the new version is ready
+    sqlx::query(
+        "DO $$
+        BEGIN
+            ALTER TABLE documents DROP CONSTRAINT IF EXISTS documents_status_check;
+            ALTER TABLE documents ADD CONSTRAINT documents_status_check
+                CHECK(status IN ('uploading', 'processing', 'ready', 'failed', 'superseded'));
+        END $$;",
+    )
+    .execute(pool)
+    .await
+    .context("Failed to add superseded status to documents")?;
+
+    sqlx::query(
+        "ALTER TABLE documents ADD COLUMN IF NOT EXISTS previous_version_id TEXT
+             REFERENCES documents(id) ON DELETE SET NULL",
+    )
+    .execute(pool)
+    .await
+    .context("Failed to add previous_version_id to documents")?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_documents_user_filename ON documents(user_id, original_filename)")
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+async fn create_scheduled_job_runs_table(pool: &PgPool) -> Result<()> {
+    // One row per periodic job with how its latest run went
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS scheduled_job_runs (
+            name TEXT PRIMARY KEY,
+            last_started_at TIMESTAMPTZ,
+            last_finished_at TIMESTAMPTZ,
+            last_duration_ms BIGINT,
+            last_succeeded BOOLEAN,
+            last_error TEXT,
+            last_error_at TIMESTAMPTZ,
+            runs BIGINT NOT NULL DEFAULT 0,
+            failures BIGINT NOT NULL DEFAULT 0
+        )",
+    )
+    .execute(pool)
+    .await
+    .context("Failed to create scheduled_job_runs table")?;
+
+    Ok(())
+}
+
+async fn add_provider_fallbacks(pool: &PgPool) -> Result<()> {
+    // Users may list providers to retry a reply with when theirs is down
+    sqlx::query(
+        "ALTER TABLE user_llm_preferences
+         ADD COLUMN IF NOT EXISTS fallback_providers TEXT[] NOT NULL DEFAULT '{}'",
+    )
+    .execute(pool)
+    .await
+    .context("Failed to add fallback_providers to user_llm_preferences")?;
+
+    // The provider that failed before a fallback answered
+    sqlx::query("ALTER TABLE messages ADD COLUMN IF NOT EXISTS fallback_from TEXT DEFAULT NULL")
+        .execute(pool)
+        .await
+        .context("Failed to add fallback_from to messages")?;
+
+    Ok(())
+}
+
+async fn add_transcript_email_enabled_to_embed_keys(pool: &PgPool) -> Result<()> {
+    // Off by default: site owners opt in to visitors emailing themselves transcripts
+    sqlx::query(
+        "ALTER TABLE embed_keys ADD COLUMN IF NOT EXISTS transcript_email_enabled BOOLEAN NOT NULL DEFAULT FALSE",
+    )
+    .execute(pool)
+    .await
+    .context("Failed to add transcript_email_enabled to embed_keys")?;
+
+    Ok(())
+}
+
+async fn create_org_settings_tables(pool: &PgPool) -> Result<()> {
+    // Organization-wide settings; the CHECK keeps it to a single row
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS org_settings (
+            id BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (id),
+            base_system_prompt TEXT NOT NULL DEFAULT '',
+            base_system_prompt_visible BOOLEAN NOT NULL DEFAULT FALSE,
+            updated_by TEXT,
+            updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+        )",
+    )
+    .execute(pool)
+    .await
+    .context("Failed to create org_settings table")?;
+
+    sqlx::query("INSERT INTO org_settings (id) VALUES (TRUE) ON CONFLICT (id) DO NOTHING")
+        .execute(pool)
+        .await
+        .context("Failed to seed org_settings")?;
+
+    // Every base system prompt that was replaced, for compliance review
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS org_system_prompt_history (
+            id TEXT PRIMARY KEY,
+            base_system_prompt TEXT NOT NULL,
+            set_by TEXT,
+            set_at TIMESTAMPTZ NOT NULL,
+            replaced_by TEXT,
+            replaced_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+        )",
+    )
+    .execute(pool)
+    .await
+    .context("Failed to create org_system_prompt_history table")?;
+
+    sqlx::query(
+        "CREATE INDEX IF NOT EXISTS idx_org_system_prompt_history_replaced_at
+         ON org_system_prompt_history(replaced_at DESC)",
+    )
+    .execute(pool)
+    .await
+    .context("Failed to index org_system_prompt_history.replaced_at")?;
+
+    Ok(())
+}
+
+async fn add_office_hours_to_embed_keys(pool: &PgPool) -> Result<()> {
+    // No schedule means always online, so existing widgets are unaffected
+    sqlx::query("ALTER TABLE embed_keys ADD COLUMN IF NOT EXISTS office_hours JSONB")
+        .execute(pool)
+        .await
+        .context("Failed to add office_hours to embed_keys")?;
+
+    sqlx::query("ALTER TABLE embed_keys ADD COLUMN IF NOT EXISTS offline_message TEXT NOT NULL DEFAULT ''")
+        .execute(pool)
+        .await
+        .context("Failed to add offline_message to embed_keys")?;
+
+    sqlx::query(
+        "ALTER TABLE embed_keys ADD COLUMN IF NOT EXISTS offline_behavior TEXT NOT NULL DEFAULT 'notice'
+         CHECK (offline_behavior IN ('notice', 'block'))",
+    )
+    .execute(pool)
+    .await
+    .context("Failed to add offline_behavior to embed_keys")?;
+
+    Ok(())
+}
+
+async fn add_extraction_fingerprints(pool: &PgPool) -> Result<()> {
+    // Unknown until a document is next processed, so the first rescan embeds everything
+    sqlx::query("ALTER TABLE documents ADD COLUMN IF NOT EXISTS extraction_fingerprint TEXT")
+        .execute(pool)
+        .await
+        .context("Failed to add extraction_fingerprint to documents")?;
+
+    sqlx::query("ALTER TABLE rescan_runs ADD COLUMN IF NOT EXISTS skipped INTEGER NOT NULL DEFAULT 0")
+        .execute(pool)
+        .await
+        .context("Failed to add skipped to rescan_runs")?;
+
+    Ok(())
+}
+
+async fn add_context_chunks_to_messages(pool: &PgPool) -> Result<()> {
+    // How many knowledge base chunks a reply was given; NULL on older replies
+    sqlx::query("ALTER TABLE messages ADD COLUMN IF NOT EXISTS context_chunks INTEGER DEFAULT NULL")
+        .execute(pool)
+        .await
+        .context("Failed to add context_chunks to messages")?;
+
+    Ok(())
+}
+
+async fn add_leases(pool: &PgPool) -> Result<()> {
+    // Which process is working on a running job or rescan, and until when; a
+    // lease that isn't renewed in time belongs to a process that died
+    for table in ["jobs", "rescan_runs"] {
+        sqlx::query(&format!(
+            "ALTER TABLE {table}
+                 ADD COLUMN IF NOT EXISTS locked_by TEXT DEFAULT NULL,
+                 ADD COLUMN IF NOT EXISTS locked_until TIMESTAMPTZ DEFAULT NULL"
+        ))
+        .execute(pool)
+        .await
+        .with_context(|| format!("Failed to add leases to {table}"))?;
+    }
+
+    Ok(())
+}
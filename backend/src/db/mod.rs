@@ -1,3 +1,5 @@
 pub mod connection;
 pub mod migrations;
 pub mod models;
+pub mod startup_lock;
+pub mod timestamp;
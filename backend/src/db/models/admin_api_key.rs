@@ -0,0 +1,127 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use sqlx::{PgPool, Row};
+
+use crate::db::models::settings::ProviderCredentials;
+use crate::services::secrets::SecretCipher;
+use chrono::{DateTime, Utc};
+
+/// An organization-wide provider key. The key itself is never serialized.
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct AdminApiKeyEntry {
+    pub provider: String,
+    pub base_url: Option<String>,
+    pub updated_by: Option<String>,
+    #[serde(with = "crate::db::timestamp")]
+    pub created_at: DateTime<Utc>,
+    #[serde(with = "crate::db::timestamp")]
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Organization API keys, encrypted at rest with the configured secret.
+#[derive(Clone)]
+pub struct AdminApiKeyRepository {
+    pool: PgPool,
+    cipher: SecretCipher,
+}
+
+const SELECT_COLS: &str = "provider, base_url, updated_by,
+     created_at,
+     updated_at";
+
+fn map_row(row: &sqlx::postgres::PgRow) -> AdminApiKeyEntry {
+    AdminApiKeyEntry {
+        provider: row.get("provider"),
+        base_url: row.get("base_url"),
+        updated_by: row.get("updated_by"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+    }
+}
+
+impl AdminApiKeyRepository {
+    pub fn new(pool: PgPool, cipher: SecretCipher) -> Self {
+        Self { pool, cipher }
+    }
+
+    pub async fn upsert(
+        &self,
+        provider: &str,
+        api_key: &str,
+        base_url: Option<&str>,
+        updated_by: &str,
+    ) -> Result<AdminApiKeyEntry> {
+        let encrypted = self.cipher.encrypt(api_key)?;
+
+        let row = sqlx::query(&format!(
+            "INSERT INTO admin_api_keys (provider, api_key_encrypted, base_url, updated_by)
+             VALUES ($1, $2, $3, $4)
+             ON CONFLICT (provider) DO UPDATE
+                 SET api_key_encrypted = $2, base_url = $3, updated_by = $4, updated_at = NOW()
+             RETURNING {SELECT_COLS}"
+        ))
+        .bind(provider)
+        .bind(&encrypted)
+        .bind(base_url)
+        .bind(updated_by)
+        .fetch_one(&self.pool)
+        .await
+        .context("Failed to upsert admin API key")?;
+
+        Ok(map_row(&row))
+    }
+
+    pub async fn find(&self, provider: &str) -> Result<Option<AdminApiKeyEntry>> {
+        let row = sqlx::query(&format!(
+            "SELECT {SELECT_COLS} FROM admin_api_keys WHERE provider = $1"
+        ))
+        .bind(provider)
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to query admin API key")?;
+
+        Ok(row.as_ref().map(map_row))
+    }
+
+    pub async fn list(&self) -> Result<Vec<AdminApiKeyEntry>> {
+        let rows = sqlx::query(&format!(
+            "SELECT {SELECT_COLS} FROM admin_api_keys ORDER BY provider"
+        ))
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to list admin API keys")?;
+
+        Ok(rows.iter().map(map_row).collect())
+    }
+
+    /// Decrypted credentials for a provider, if an organization key is configured.
+    pub async fn get_credentials(&self, provider: &str) -> Result<Option<ProviderCredentials>> {
+        let row = sqlx::query(
+            "SELECT api_key_encrypted, base_url FROM admin_api_keys WHERE provider = $1",
+        )
+        .bind(provider)
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to query admin API key")?;
+
+        row.map(|r| {
+            let encrypted: String = r.get("api_key_encrypted");
+            Ok(ProviderCredentials {
+                api_key: self.cipher.decrypt(&encrypted)?,
+                base_url: r.get("base_url"),
+            })
+        })
+        .transpose()
+    }
+
+    pub async fn delete(&self, provider: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM admin_api_keys WHERE provider = $1")
+            .bind(provider)
+            .execute(&self.pool)
+            .await
+            .context("Failed to delete admin API key")?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
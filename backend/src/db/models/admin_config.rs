@@ -1,9 +1,14 @@
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use sqlx::{PgPool, Row};
+use std::collections::HashSet;
 use uuid::Uuid;
 
-use crate::services::llm_provider;
+use crate::services::llm_provider::{self, ProviderInfo};
+use crate::services::config_transfer::{ImportPlan, NewEmbedKey};
+use crate::services::embedding::EmbeddingTarget;
+use crate::services::model_catalog::LiveModel;
+use chrono::{DateTime, Utc};
 
 #[derive(Debug, Clone, Serialize)]
 #[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
@@ -14,7 +19,10 @@ pub struct AdminProvider {
     pub enabled: bool,
     pub supports_completion: bool,
     pub supports_embeddings: bool,
-    pub created_at: String,
+    /// The organization-wide provider for embeddings (at most one).
+    pub is_default_embedding: bool,
+    #[serde(with = "crate::db::timestamp")]
+    pub created_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -26,7 +34,15 @@ pub struct AdminModel {
     pub display_name: String,
     pub model_type: String,
     pub is_default: bool,
-    pub created_at: String,
+    #[serde(with = "crate::db::timestamp")]
+    pub created_at: DateTime<Utc>,
+    /// Set when a sync no longer finds the model at the provider.
+    #[serde(with = "crate::db::timestamp::option")]
+    pub removed_at: Option<DateTime<Utc>>,
+    /// Position within the model type; lower sorts first.
+    pub sort_order: i32,
+    /// Disabled models stay configured but are hidden from users.
+    pub is_enabled: bool,
 }
 
 #[derive(Debug, Deserialize)]
@@ -37,6 +53,85 @@ pub struct AddModelRequest {
     pub model_type: String,
 }
 
+/// Fields left out are unchanged.
+#[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UpdateModelRequest {
+    pub display_name: Option<String>,
+    pub sort_order: Option<i32>,
+    pub is_enabled: Option<bool>,
+}
+
+#[derive(Debug, Default, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ModelSyncSummary {
+    pub added: Vec<String>,
+    pub restored: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+/// What seeding the built-in catalogue added; models are `provider/model`.
+#[derive(Debug, Default, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CatalogSyncSummary {
+    pub providers_added: Vec<String>,
+    pub models_added: Vec<String>,
+}
+
+/// An `admin_models` row as seen by the sync planner.
+#[derive(Debug, Clone)]
+pub struct ExistingModel {
+    pub model_id: String,
+    pub model_type: String,
+    pub removed: bool,
+}
+
+/// Work out which discovered models to insert, which previously removed ones to
+/// restore, and which active ones the provider no longer reports.
+pub fn plan_model_sync(existing: &[ExistingModel], discovered: &[LiveModel]) -> ModelSyncSummary {
+    let discovered_ids: HashSet<&str> = discovered.iter().map(|m| m.model_id.as_str()).collect();
+    let mut summary = ModelSyncSummary::default();
+
+    for model in discovered {
+        match existing
+            .iter()
+            .find(|e| e.model_id == model.model_id && e.model_type == model.model_type)
+        {
+            None => summary.added.push(model.model_id.clone()),
+            Some(e) if e.removed => summary.restored.push(model.model_id.clone()),
+            Some(_) => {}
+        }
+    }
+
+    summary.removed = existing
+        .iter()
+        .filter(|e| !e.removed && !discovered_ids.contains(e.model_id.as_str()))
+        .map(|e| e.model_id.clone())
+        .collect();
+
+    summary
+}
+
+const MODEL_COLS: &str = "id, provider_id, model_id, display_name, model_type, is_default,
+     created_at,
+     removed_at,
+     sort_order, is_enabled";
+
+fn map_model(row: &sqlx::postgres::PgRow) -> AdminModel {
+    AdminModel {
+        id: row.get("id"),
+        provider_id: row.get("provider_id"),
+        model_id: row.get("model_id"),
+        display_name: row.get("display_name"),
+        model_type: row.get("model_type"),
+        is_default: row.get("is_default"),
+        created_at: row.get("created_at"),
+        removed_at: row.get("removed_at"),
+        sort_order: row.get("sort_order"),
+        is_enabled: row.get("is_enabled"),
+    }
+}
+
 #[derive(Clone)]
 pub struct AdminConfigRepository {
     pool: PgPool,
@@ -47,83 +142,93 @@ impl AdminConfigRepository {
         Self { pool }
     }
 
-    pub async fn seed_defaults(&self) -> Result<()> {
-        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM admin_providers")
-            .fetch_one(&self.pool)
-            .await
-            .context("Failed to count admin_providers")?;
-
-        if count > 0 {
-            return Ok(());
-        }
+    /// Add any providers and models from the built-in catalogue that this install
+    /// doesn't have yet. Runs on every start.
+    pub async fn seed_defaults(&self) -> Result<CatalogSyncSummary> {
+        self.seed_catalog(&llm_provider::supported_providers()).await
+    }
 
+    /// Insert catalogue providers missing by `provider_id` and models missing by
+    /// `(provider_id, model_id, model_type)`. Existing rows are never modified, so
+    /// enabled flags, defaults, renames and custom models an admin set up survive.
+    pub async fn seed_catalog(&self, providers: &[ProviderInfo]) -> Result<CatalogSyncSummary> {
+        let mut tx = self.pool.begin().await.context("Failed to begin transaction")?;
         let now = chrono::Utc::now();
-        let providers = llm_provider::supported_providers();
+        let mut summary = CatalogSyncSummary::default();
 
-        for p in &providers {
-            let pid = Uuid::new_v4().to_string();
-            sqlx::query(
+        for p in providers {
+            let inserted: Option<String> = sqlx::query_scalar(
                 "INSERT INTO admin_providers
                      (id, provider_id, display_name, enabled, supports_completion, supports_embeddings, created_at)
-                 VALUES ($1, $2, $3, TRUE, $4, $5, $6)",
+                 VALUES ($1, $2, $3, TRUE, $4, $5, $6)
+                 ON CONFLICT (provider_id) DO NOTHING
+                 RETURNING id",
             )
-            .bind(&pid)
+            .bind(Uuid::new_v4().to_string())
             .bind(p.id)
             .bind(p.name)
             .bind(p.supports_completion)
             .bind(p.supports_embeddings)
             .bind(now)
-            .execute(&self.pool)
+            .fetch_optional(&mut *tx)
             .await
             .context("Failed to seed provider")?;
-
-            for m in p.completion_models {
-                let mid = Uuid::new_v4().to_string();
-                let is_default = m.id == p.default_model;
-                sqlx::query(
-                    "INSERT INTO admin_models
-                         (id, provider_id, model_id, display_name, model_type, is_default, created_at)
-                     VALUES ($1, $2, $3, $4, 'completion', $5, $6)",
-                )
-                .bind(&mid)
-                .bind(p.id)
-                .bind(m.id)
-                .bind(m.display_name)
-                .bind(is_default)
-                .bind(now)
-                .execute(&self.pool)
-                .await
-                .context("Failed to seed completion model")?;
+            let provider_added = inserted.is_some();
+            if provider_added {
+                summary.providers_added.push(p.id.to_string());
             }
 
-            for m in p.embedding_models {
-                let mid = Uuid::new_v4().to_string();
-                let is_default = p.default_embedding_model == Some(m.id);
-                sqlx::query(
-                    "INSERT INTO admin_models
-                         (id, provider_id, model_id, display_name, model_type, is_default, created_at)
-                     VALUES ($1, $2, $3, $4, 'embedding', $5, $6)",
-                )
-                .bind(&mid)
-                .bind(p.id)
-                .bind(m.id)
-                .bind(m.display_name)
-                .bind(is_default)
-                .bind(now)
-                .execute(&self.pool)
-                .await
-                .context("Failed to seed embedding model")?;
+            let catalogue = [
+                ("completion", p.completion_models, Some(p.default_model)),
+                ("embedding", p.embedding_models, p.default_embedding_model),
+            ];
+            for (model_type, models, default_model) in catalogue {
+                for m in models {
+                    // Providers already set up keep whatever defaults the admin chose
+                    let is_default = provider_added && default_model == Some(m.id);
+                    let inserted: Option<String> = sqlx::query_scalar(
+                        "INSERT INTO admin_models
+                             (id, provider_id, model_id, display_name, model_type, is_default, created_at)
+                         VALUES ($1, $2, $3, $4, $5, $6, $7)
+                         ON CONFLICT (provider_id, model_id, model_type) DO NOTHING
+                         RETURNING id",
+                    )
+                    .bind(Uuid::new_v4().to_string())
+                    .bind(p.id)
+                    .bind(m.id)
+                    .bind(m.display_name)
+                    .bind(model_type)
+                    .bind(is_default)
+                    .bind(now)
+                    .fetch_optional(&mut *tx)
+                    .await
+                    .context("Failed to seed model")?;
+                    if inserted.is_some() {
+                        summary.models_added.push(format!("{}/{}", p.id, m.id));
+                    }
+                }
             }
         }
 
-        tracing::info!("Seeded {} admin providers with default models", providers.len());
-        Ok(())
+        tx.commit().await.context("Failed to commit catalogue seeding")?;
+
+        if summary.providers_added.is_empty() && summary.models_added.is_empty() {
+            tracing::debug!("Provider catalogue is up to date");
+        } else {
+            tracing::info!(
+                "Seeded provider catalogue: providers added [{}], models added [{}]",
+                summary.providers_added.join(", "),
+                summary.models_added.join(", ")
+            );
+        }
+        Ok(summary)
     }
 
     pub async fn list_providers(&self) -> Result<Vec<AdminProvider>> {
         let rows = sqlx::query(
             "SELECT id, provider_id, display_name, enabled, supports_completion, supports_embeddings,
-                    to_char(created_at, 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') AS created_at
+                    is_default_embedding,
+                    created_at
              FROM admin_providers ORDER BY display_name",
         )
         .fetch_all(&self.pool)
@@ -139,6 +244,7 @@ impl AdminConfigRepository {
                 enabled: row.get("enabled"),
                 supports_completion: row.get("supports_completion"),
                 supports_embeddings: row.get("supports_embeddings"),
+                is_default_embedding: row.get("is_default_embedding"),
                 created_at: row.get("created_at"),
             })
             .collect();
@@ -151,6 +257,45 @@ impl AdminConfigRepository {
         Ok(all.into_iter().filter(|p| p.enabled).collect())
     }
 
+    pub async fn provider_exists(&self, provider_id: &str) -> Result<bool> {
+        let exists: bool =
+            sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM admin_providers WHERE provider_id = $1)")
+                .bind(provider_id)
+                .fetch_one(&self.pool)
+                .await
+                .context("Failed to query provider")?;
+
+        Ok(exists)
+    }
+
+    /// Whether `provider_id` exists and is enabled.
+    pub async fn is_provider_enabled(&self, provider_id: &str) -> Result<bool> {
+        let enabled: Option<bool> =
+            sqlx::query_scalar("SELECT enabled FROM admin_providers WHERE provider_id = $1")
+                .bind(provider_id)
+                .fetch_optional(&self.pool)
+                .await
+                .context("Failed to query provider")?;
+
+        Ok(enabled.unwrap_or(false))
+    }
+
+    /// True only for a catalogued completion model an admin has disabled; models
+    /// missing from the catalogue are left to the provider to accept or reject.
+    pub async fn is_model_disabled(&self, provider_id: &str, model_id: &str) -> Result<bool> {
+        let disabled: Option<bool> = sqlx::query_scalar(
+            "SELECT NOT is_enabled FROM admin_models
+             WHERE provider_id = $1 AND model_id = $2 AND model_type = 'completion'",
+        )
+        .bind(provider_id)
+        .bind(model_id)
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to query model")?;
+
+        Ok(disabled.unwrap_or(false))
+    }
+
     pub async fn toggle_provider(&self, provider_id: &str, enabled: bool) -> Result<()> {
         sqlx::query("UPDATE admin_providers SET enabled = $1 WHERE provider_id = $2")
             .bind(enabled)
@@ -162,31 +307,147 @@ impl AdminConfigRepository {
         Ok(())
     }
 
-    pub async fn list_models(&self, provider_id: &str) -> Result<Vec<AdminModel>> {
-        let rows = sqlx::query(
-            "SELECT id, provider_id, model_id, display_name, model_type, is_default,
-                    to_char(created_at, 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') AS created_at
-             FROM admin_models WHERE provider_id = $1 ORDER BY model_type, display_name",
+    /// Make `provider_id` the organization's embedding provider.
+    pub async fn set_default_embedding_provider(&self, provider_id: &str) -> Result<()> {
+        let mut tx = self.pool.begin().await.context("Failed to begin transaction")?;
+
+        let supports: Option<bool> = sqlx::query_scalar(
+            "SELECT supports_embeddings FROM admin_providers WHERE provider_id = $1",
+        )
+        .bind(provider_id)
+        .fetch_optional(&mut *tx)
+        .await
+        .context("Failed to query provider")?;
+
+        match supports {
+            None => anyhow::bail!("Provider not found"),
+            Some(false) => anyhow::bail!("Provider '{provider_id}' does not support embeddings"),
+            Some(true) => {}
+        }
+
+        sqlx::query("UPDATE admin_providers SET is_default_embedding = (provider_id = $1)")
+            .bind(provider_id)
+            .execute(&mut *tx)
+            .await
+            .context("Failed to set default embedding provider")?;
+
+        tx.commit().await.context("Failed to commit default embedding provider")?;
+        Ok(())
+    }
+
+    /// The admin-selected embedding provider with its default embedding model,
+    /// if one is set, enabled and has a default model.
+    pub async fn get_default_embedding(&self) -> Result<Option<EmbeddingTarget>> {
+        let row = sqlx::query(
+            "SELECT p.provider_id, m.model_id
+             FROM admin_providers p
+             JOIN admin_models m
+               ON m.provider_id = p.provider_id
+              AND m.model_type = 'embedding'
+              AND m.is_default
+              AND m.removed_at IS NULL
+             WHERE p.is_default_embedding AND p.enabled
+             LIMIT 1",
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to query default embedding provider")?;
+
+        Ok(row.map(|r| EmbeddingTarget {
+            provider: r.get("provider_id"),
+            model: r.get("model_id"),
+        }))
+    }
+
+    /// The default chat model of `provider_id`, if the provider is enabled and
+    /// its default model is available.
+    pub async fn get_default_completion_model(&self, provider_id: &str) -> Result<Option<String>> {
+        let model = sqlx::query_scalar(
+            "SELECT m.model_id FROM admin_models m
+             JOIN admin_providers p ON p.provider_id = m.provider_id
+             WHERE m.provider_id = $1 AND m.model_type = 'completion' AND m.is_default
+               AND m.is_enabled AND m.removed_at IS NULL AND p.enabled
+             LIMIT 1",
         )
         .bind(provider_id)
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to query default completion model")?;
+
+        Ok(model)
+    }
+
+    /// Enabled providers that offer `model_id` as an embedding model.
+    pub async fn find_embedding_model_providers(&self, model_id: &str) -> Result<Vec<String>> {
+        let providers = sqlx::query_scalar(
+            "SELECT m.provider_id FROM admin_models m
+             JOIN admin_providers p ON p.provider_id = m.provider_id
+             WHERE m.model_id = $1 AND m.model_type = 'embedding'
+               AND m.removed_at IS NULL AND p.enabled
+             ORDER BY m.provider_id",
+        )
+        .bind(model_id)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to look up embedding model providers")?;
+
+        Ok(providers)
+    }
+
+    pub async fn list_models(&self, provider_id: &str) -> Result<Vec<AdminModel>> {
+        let rows = sqlx::query(&format!(
+            "SELECT {MODEL_COLS} FROM admin_models WHERE provider_id = $1
+             ORDER BY model_type, sort_order, display_name"
+        ))
+        .bind(provider_id)
         .fetch_all(&self.pool)
         .await
         .context("Failed to list models")?;
 
-        let models = rows
-            .iter()
-            .map(|row| AdminModel {
-                id: row.get("id"),
-                provider_id: row.get("provider_id"),
-                model_id: row.get("model_id"),
-                display_name: row.get("display_name"),
-                model_type: row.get("model_type"),
-                is_default: row.get("is_default"),
-                created_at: row.get("created_at"),
-            })
-            .collect();
+        Ok(rows.iter().map(map_model).collect())
+    }
 
-        Ok(models)
+    pub async fn find_model(&self, id: &str) -> Result<Option<AdminModel>> {
+        let row = sqlx::query(&format!("SELECT {MODEL_COLS} FROM admin_models WHERE id = $1"))
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+            .context("Failed to query model")?;
+
+        Ok(row.as_ref().map(map_model))
+    }
+
+    /// Returns the updated model, or `None` if it doesn't exist.
+    pub async fn update_model(&self, id: &str, req: &UpdateModelRequest) -> Result<Option<AdminModel>> {
+        let row = sqlx::query(&format!(
+            "UPDATE admin_models
+             SET display_name = COALESCE($2, display_name),
+                 sort_order = COALESCE($3, sort_order),
+                 is_enabled = COALESCE($4, is_enabled)
+             WHERE id = $1
+             RETURNING {MODEL_COLS}"
+        ))
+        .bind(id)
+        .bind(&req.display_name)
+        .bind(req.sort_order)
+        .bind(req.is_enabled)
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to update model")?;
+
+        Ok(row.as_ref().map(map_model))
+    }
+
+    pub async fn list_all_models(&self) -> Result<Vec<AdminModel>> {
+        let rows = sqlx::query(&format!(
+            "SELECT {MODEL_COLS} FROM admin_models
+             ORDER BY provider_id, model_type, sort_order, display_name"
+        ))
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to list models")?;
+
+        Ok(rows.iter().map(map_model).collect())
     }
 
     pub async fn get_models_by_type(
@@ -198,13 +459,16 @@ impl AdminConfigRepository {
         Ok(all.into_iter().filter(|m| m.model_type == model_type).collect())
     }
 
-    pub async fn add_model(&self, provider_id: &str, req: &AddModelRequest) -> Result<AdminModel> {
+    /// Add a model by hand. `None` if the provider already has a model with
+    /// this ID and type, e.g. when the admin submits the form twice.
+    pub async fn add_model(&self, provider_id: &str, req: &AddModelRequest) -> Result<Option<AdminModel>> {
         let id = Uuid::new_v4().to_string();
         let now = chrono::Utc::now();
 
-        sqlx::query(
+        let inserted = sqlx::query(
             "INSERT INTO admin_models (id, provider_id, model_id, display_name, model_type, is_default, created_at)
-             VALUES ($1, $2, $3, $4, $5, FALSE, $6)",
+             VALUES ($1, $2, $3, $4, $5, FALSE, $6)
+             ON CONFLICT (provider_id, model_id, model_type) DO NOTHING",
         )
         .bind(&id)
         .bind(provider_id)
@@ -215,18 +479,254 @@ impl AdminConfigRepository {
         .execute(&self.pool)
         .await
         .context("Failed to add model")?;
+        if inserted.rows_affected() == 0 {
+            return Ok(None);
+        }
 
-        Ok(AdminModel {
+        Ok(Some(AdminModel {
             id,
             provider_id: provider_id.to_string(),
             model_id: req.model_id.clone(),
             display_name: req.display_name.clone(),
             model_type: req.model_type.clone(),
             is_default: false,
-            created_at: now.to_rfc3339(),
-        })
+            created_at: now,
+            removed_at: None,
+            sort_order: 0,
+            is_enabled: true,
+        }))
+    }
+
+    /// Bring `admin_models` in line with the models a provider currently reports.
+    /// Models the provider no longer lists are marked with `removed_at` rather than
+    /// deleted, so defaults and user preferences pointing at them stay intact.
+    pub async fn sync_models(
+        &self,
+        provider_id: &str,
+        discovered: &[LiveModel],
+    ) -> Result<ModelSyncSummary> {
+        let mut tx = self.pool.begin().await.context("Failed to begin transaction")?;
+
+        let rows = sqlx::query(
+            "SELECT model_id, model_type, removed_at IS NOT NULL AS removed
+             FROM admin_models WHERE provider_id = $1",
+        )
+        .bind(provider_id)
+        .fetch_all(&mut *tx)
+        .await
+        .context("Failed to load models for sync")?;
+
+        let existing: Vec<ExistingModel> = rows
+            .iter()
+            .map(|row| ExistingModel {
+                model_id: row.get("model_id"),
+                model_type: row.get("model_type"),
+                removed: row.get("removed"),
+            })
+            .collect();
+
+        let summary = plan_model_sync(&existing, discovered);
+        let now = chrono::Utc::now();
+
+        for model in discovered {
+            if !summary.added.contains(&model.model_id) {
+                continue;
+            }
+            sqlx::query(
+                "INSERT INTO admin_models (id, provider_id, model_id, display_name, model_type, is_default, created_at)
+                 VALUES ($1, $2, $3, $4, $5, FALSE, $6)
+                 ON CONFLICT (provider_id, model_id, model_type) DO NOTHING",
+            )
+            .bind(Uuid::new_v4().to_string())
+            .bind(provider_id)
+            .bind(&model.model_id)
+            .bind(&model.display_name)
+            .bind(&model.model_type)
+            .bind(now)
+            .execute(&mut *tx)
+            .await
+            .context("Failed to insert synced model")?;
+        }
+
+        sqlx::query(
+            "UPDATE admin_models SET removed_at = NULL
+             WHERE provider_id = $1 AND model_id = ANY($2)",
+        )
+        .bind(provider_id)
+        .bind(&summary.restored)
+        .execute(&mut *tx)
+        .await
+        .context("Failed to restore synced models")?;
+
+        sqlx::query(
+            "UPDATE admin_models SET removed_at = $3
+             WHERE provider_id = $1 AND model_id = ANY($2) AND removed_at IS NULL",
+        )
+        .bind(provider_id)
+        .bind(&summary.removed)
+        .bind(now)
+        .execute(&mut *tx)
+        .await
+        .context("Failed to mark removed models")?;
+
+        tx.commit().await.context("Failed to commit model sync")?;
+        Ok(summary)
+    }
+
+    /// Apply a configuration import in one transaction: provider flags, models
+    /// (inserted or updated) and embed keys. `new_embed_keys` come from
+    /// `plan.new_embed_keys` with their generated keys.
+    pub async fn apply_import(&self, plan: &ImportPlan, new_embed_keys: &[NewEmbedKey]) -> Result<()> {
+        let mut tx = self.pool.begin().await.context("Failed to begin transaction")?;
+
+        if plan.providers.iter().any(|p| p.is_default_embedding) {
+            sqlx::query("UPDATE admin_providers SET is_default_embedding = FALSE")
+                .execute(&mut *tx)
+                .await
+                .context("Failed to clear default embedding provider")?;
+        }
+        for p in &plan.providers {
+            sqlx::query(
+                "UPDATE admin_providers SET enabled = $2, is_default_embedding = $3 WHERE provider_id = $1",
+            )
+            .bind(&p.provider_id)
+            .bind(p.enabled)
+            .bind(p.is_default_embedding)
+            .execute(&mut *tx)
+            .await
+            .context("Failed to import provider")?;
+        }
+
+        let now = chrono::Utc::now();
+        for m in &plan.models {
+            if m.is_default {
+                sqlx::query(
+                    "UPDATE admin_models SET is_default = FALSE
+                     WHERE provider_id = $1 AND model_type = $2 AND model_id <> $3",
+                )
+                .bind(&m.provider_id)
+                .bind(&m.model_type)
+                .bind(&m.model_id)
+                .execute(&mut *tx)
+                .await
+                .context("Failed to clear default model")?;
+            }
+            sqlx::query(
+                "INSERT INTO admin_models
+                     (id, provider_id, model_id, display_name, model_type, is_default, sort_order, is_enabled, created_at)
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+                 ON CONFLICT (provider_id, model_id, model_type) DO UPDATE
+                 SET display_name = EXCLUDED.display_name, is_default = EXCLUDED.is_default,
+                     sort_order = EXCLUDED.sort_order, is_enabled = EXCLUDED.is_enabled",
+            )
+            .bind(Uuid::new_v4().to_string())
+            .bind(&m.provider_id)
+            .bind(&m.model_id)
+            .bind(&m.display_name)
+            .bind(&m.model_type)
+            .bind(m.is_default)
+            .bind(m.sort_order)
+            .bind(m.is_enabled)
+            .bind(now)
+            .execute(&mut *tx)
+            .await
+            .context("Failed to import model")?;
+        }
+
+        for k in &plan.updated_embed_keys {
+            sqlx::query(
+                "UPDATE embed_keys
+                 SET name = $2, allowed_domains = $3, system_prompt = $4, rate_limit = $5,
+                     widget_title = $6, primary_color = $7, greeting_message = $8, provider = $9,
+                     model = $10, base_url = $11, custom_css = $12, rag_top_k = $13,
+                     rag_min_score = $14, rag_max_context_chars = $15, localizations = $16,
+                     handoff_enabled = $17, handoff_notification_email = $18, is_active = $19,
+                     daily_message_limit = $20, monthly_message_limit = $21, theme = $22,
+                     transcript_email_enabled = $23, office_hours = $24, offline_message = $25,
+                     offline_behavior = $26, updated_at = NOW()
+                 WHERE id = $1",
+            )
+            .bind(&k.id)
+            .bind(&k.name)
+            .bind(&k.allowed_domains)
+            .bind(&k.system_prompt)
+            .bind(k.rate_limit)
+            .bind(&k.widget_title)
+            .bind(&k.primary_color)
+            .bind(&k.greeting_message)
+            .bind(&k.provider)
+            .bind(&k.model)
+            .bind(&k.base_url)
+            .bind(&k.custom_css)
+            .bind(k.rag_top_k)
+            .bind(k.rag_min_score)
+            .bind(k.rag_max_context_chars)
+            .bind(sqlx::types::Json(&k.localizations))
+            .bind(k.handoff_enabled)
+            .bind(&k.handoff_notification_email)
+            .bind(k.is_active)
+            .bind(k.daily_message_limit)
+            .bind(k.monthly_message_limit)
+            .bind(sqlx::types::Json(&k.theme))
+            .bind(k.transcript_email_enabled)
+            .bind(k.office_hours.as_ref().map(sqlx::types::Json))
+            .bind(&k.offline_message)
+            .bind(k.offline_behavior.as_str())
+            .execute(&mut *tx)
+            .await
+            .context("Failed to import embed key")?;
+        }
+
+        for new_key in new_embed_keys {
+            let k = &new_key.settings;
+            sqlx::query(
+                "INSERT INTO embed_keys (id, name, key_hash, key_prefix, allowed_domains, system_prompt,
+                    rate_limit, widget_title, primary_color, greeting_message, provider, model,
+                    api_key_encrypted, base_url, custom_css, rag_top_k, rag_min_score,
+                    rag_max_context_chars, localizations, handoff_enabled, handoff_notification_email,
+                    is_active, daily_message_limit, monthly_message_limit, theme, transcript_email_enabled,
+                    office_hours, offline_message, offline_behavior)
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, '', $13, $14, $15, $16, $17,
+                    $18, $19, $20, $21, $22, $23, $24, $25, $26, $27, $28)",
+            )
+            .bind(&k.id)
+            .bind(&k.name)
+            .bind(&new_key.key_hash)
+            .bind(&new_key.key_prefix)
+            .bind(&k.allowed_domains)
+            .bind(&k.system_prompt)
+            .bind(k.rate_limit)
+            .bind(&k.widget_title)
+            .bind(&k.primary_color)
+            .bind(&k.greeting_message)
+            .bind(&k.provider)
+            .bind(&k.model)
+            .bind(&k.base_url)
+            .bind(&k.custom_css)
+            .bind(k.rag_top_k)
+            .bind(k.rag_min_score)
+            .bind(k.rag_max_context_chars)
+            .bind(sqlx::types::Json(&k.localizations))
+            .bind(k.handoff_enabled)
+            .bind(&k.handoff_notification_email)
+            .bind(k.is_active)
+            .bind(k.daily_message_limit)
+            .bind(k.monthly_message_limit)
+            .bind(sqlx::types::Json(&k.theme))
+            .bind(k.transcript_email_enabled)
+            .bind(k.office_hours.as_ref().map(sqlx::types::Json))
+            .bind(&k.offline_message)
+            .bind(k.offline_behavior.as_str())
+            .execute(&mut *tx)
+            .await
+            .context("Failed to create imported embed key")?;
+        }
+
+        tx.commit().await.context("Failed to commit configuration import")?;
+        Ok(())
     }
 
+    /// Catalogue models come back on the next start; disable them to hide them for good.
     pub async fn remove_model(&self, model_id: &str) -> Result<()> {
         sqlx::query("DELETE FROM admin_models WHERE id = $1")
             .bind(model_id)
@@ -266,3 +766,54 @@ impl AdminConfigRepository {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn existing(model_id: &str, model_type: &str, removed: bool) -> ExistingModel {
+        ExistingModel {
+            model_id: model_id.to_string(),
+            model_type: model_type.to_string(),
+            removed,
+        }
+    }
+
+    fn live(model_id: &str, model_type: &str) -> LiveModel {
+        LiveModel {
+            model_id: model_id.to_string(),
+            display_name: model_id.to_string(),
+            model_type: model_type.to_string(),
+            in_catalog: false,
+        }
+    }
+
+    #[test]
+    fn test_plan_model_sync() {
+        let existing = vec![
+            existing("gpt-4o", "completion", false),
+            existing("gpt-4", "completion", false),
+            existing("o1-mini", "completion", true),
+            existing("gpt-3.5-turbo", "completion", true),
+        ];
+        let discovered = vec![
+            live("gpt-4o", "completion"),
+            live("o1-mini", "completion"),
+            live("gpt-4.1", "completion"),
+        ];
+
+        let summary = plan_model_sync(&existing, &discovered);
+        assert_eq!(summary.added, vec!["gpt-4.1"]);
+        assert_eq!(summary.restored, vec!["o1-mini"]);
+        // Already-removed models are not reported again
+        assert_eq!(summary.removed, vec!["gpt-4"]);
+    }
+
+    #[test]
+    fn test_plan_model_sync_empty_provider_list_removes_everything() {
+        let existing = vec![existing("llama3", "completion", false)];
+        let summary = plan_model_sync(&existing, &[]);
+        assert!(summary.added.is_empty());
+        assert_eq!(summary.removed, vec!["llama3"]);
+    }
+}
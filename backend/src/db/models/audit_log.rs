@@ -1,4 +1,5 @@
 use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
 use serde::Serialize;
 use sqlx::{PgPool, Row};
 use uuid::Uuid;
@@ -14,7 +15,22 @@ pub struct AuditLog {
     pub description: String,
     pub ip_address: Option<String>,
     pub metadata: serde_json::Value,
-    pub created_at: String,
+    #[serde(with = "crate::db::timestamp")]
+    pub created_at: DateTime<Utc>,
+}
+
+/// An audit event waiting to be written. `created_at` is when it happened,
+/// which can be a little before it reaches the database.
+#[derive(Debug, Clone)]
+pub struct AuditEntry {
+    pub user_id: Option<String>,
+    pub event_type: String,
+    pub resource_type: Option<String>,
+    pub resource_id: Option<String>,
+    pub description: String,
+    pub ip_address: Option<String>,
+    pub metadata: Option<serde_json::Value>,
+    pub created_at: DateTime<Utc>,
 }
 
 #[derive(Clone)]
@@ -27,35 +43,18 @@ impl AuditLogRepository {
         Self { pool }
     }
 
-    pub async fn create(
-        &self,
-        user_id: Option<&str>,
-        event_type: &str,
-        resource_type: Option<&str>,
-        resource_id: Option<&str>,
-        description: &str,
-        ip_address: Option<&str>,
-        metadata: Option<serde_json::Value>,
-    ) -> Result<()> {
-        let id = Uuid::new_v4().to_string();
-        let meta = metadata.unwrap_or(serde_json::json!({}));
-
-        sqlx::query(
-            "INSERT INTO audit_logs (id, user_id, event_type, resource_type, resource_id, description, ip_address, metadata)
-             VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
-        )
-        .bind(&id)
-        .bind(user_id)
-        .bind(event_type)
-        .bind(resource_type)
-        .bind(resource_id)
-        .bind(description)
-        .bind(ip_address)
-        .bind(&meta)
-        .execute(&self.pool)
-        .await
-        .context("Failed to create audit log")?;
+    pub async fn create(&self, entry: &AuditEntry) -> Result<()> {
+        insert(&self.pool, entry).await
+    }
 
+    /// Write several entries in one transaction, so a failure writes none of them
+    /// and the whole batch can be retried.
+    pub async fn create_batch(&self, entries: &[AuditEntry]) -> Result<()> {
+        let mut tx = self.pool.begin().await.context("Failed to start audit log transaction")?;
+        for entry in entries {
+            insert(&mut *tx, entry).await?;
+        }
+        tx.commit().await.context("Failed to commit audit logs")?;
         Ok(())
     }
 
@@ -71,7 +70,7 @@ impl AuditLogRepository {
         let mut query = String::from(
             "SELECT id, user_id, event_type, resource_type, resource_id, description,
                     ip_address, metadata,
-                    to_char(created_at, 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') AS created_at
+                    created_at
              FROM audit_logs WHERE 1=1",
         );
         let mut param_idx = 1u32;
@@ -170,3 +169,26 @@ impl AuditLogRepository {
         Ok(count)
     }
 }
+
+async fn insert<'e, E: sqlx::PgExecutor<'e>>(executor: E, entry: &AuditEntry) -> Result<()> {
+    let meta = entry.metadata.clone().unwrap_or(serde_json::json!({}));
+
+    sqlx::query(
+        "INSERT INTO audit_logs (id, user_id, event_type, resource_type, resource_id, description, ip_address, metadata, created_at)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
+    )
+    .bind(Uuid::new_v4().to_string())
+    .bind(&entry.user_id)
+    .bind(&entry.event_type)
+    .bind(&entry.resource_type)
+    .bind(&entry.resource_id)
+    .bind(&entry.description)
+    .bind(&entry.ip_address)
+    .bind(&meta)
+    .bind(entry.created_at)
+    .execute(executor)
+    .await
+    .context("Failed to create audit log")?;
+
+    Ok(())
+}
@@ -0,0 +1,105 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use sqlx::{PgPool, Row};
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+
+/// A named group of documents. Conversations and embed keys can be limited to
+/// one collection's knowledge.
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Collection {
+    pub id: String,
+    pub name: String,
+    pub owner_id: String,
+    pub document_count: i64,
+    #[serde(with = "crate::db::timestamp")]
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Clone)]
+pub struct CollectionRepository {
+    pool: PgPool,
+}
+
+const SELECT_COLS: &str = "c.id, c.name, c.owner_id,
+     (SELECT COUNT(*) FROM documents d WHERE d.collection_id = c.id) AS document_count,
+     c.created_at";
+
+fn map_row(row: &sqlx::postgres::PgRow) -> Collection {
+    Collection {
+        id: row.get("id"),
+        name: row.get("name"),
+        owner_id: row.get("owner_id"),
+        document_count: row.get("document_count"),
+        created_at: row.get("created_at"),
+    }
+}
+
+impl CollectionRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn create(&self, owner_id: &str, name: &str) -> Result<Collection> {
+        let id = Uuid::new_v4().to_string();
+        sqlx::query("INSERT INTO collections (id, name, owner_id) VALUES ($1, $2, $3)")
+            .bind(&id)
+            .bind(name)
+            .bind(owner_id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to create collection")?;
+
+        self.find_by_id(&id)
+            .await?
+            .context("Collection disappeared after insert")
+    }
+
+    /// Every collection, by name. Collections are shared across the organization
+    /// like the documents in them.
+    pub async fn list(&self) -> Result<Vec<Collection>> {
+        let rows = sqlx::query(&format!(
+            "SELECT {SELECT_COLS} FROM collections c ORDER BY LOWER(c.name), c.created_at"
+        ))
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to list collections")?;
+
+        Ok(rows.iter().map(map_row).collect())
+    }
+
+    pub async fn find_by_id(&self, id: &str) -> Result<Option<Collection>> {
+        let row = sqlx::query(&format!("SELECT {SELECT_COLS} FROM collections c WHERE c.id = $1"))
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+            .context("Failed to query collection")?;
+
+        Ok(row.as_ref().map(map_row))
+    }
+
+    /// Returns false if there is no such collection.
+    pub async fn rename(&self, id: &str, name: &str) -> Result<bool> {
+        let result = sqlx::query("UPDATE collections SET name = $1 WHERE id = $2")
+            .bind(name)
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to rename collection")?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Delete a collection. Its documents, conversations and embed keys are kept
+    /// and fall back to all knowledge. Returns false if there is no such collection.
+    pub async fn delete(&self, id: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM collections WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to delete collection")?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
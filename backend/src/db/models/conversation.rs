@@ -1,7 +1,8 @@
 use anyhow::{Context, Result};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use sqlx::{PgPool, Row};
 use uuid::Uuid;
+use chrono::{DateTime, Utc};
 
 #[derive(Debug, Clone, Serialize)]
 #[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
@@ -9,10 +10,58 @@ pub struct Conversation {
     pub id: String,
     pub user_id: String,
     pub title: String,
-    pub created_at: String,
-    pub updated_at: String,
+    /// Set when the user chose the title; generated titles never replace it.
+    pub title_is_custom: bool,
+    /// Retrieval is limited to this collection's documents when set.
+    pub collection_id: Option<String>,
+    #[serde(with = "crate::db::timestamp")]
+    pub created_at: DateTime<Utc>,
+    #[serde(with = "crate::db::timestamp")]
+    pub updated_at: DateTime<Utc>,
+    /// Set while the conversation is archived, which hides it from the default listing.
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub deleted_at: Option<String>,
+    #[serde(with = "crate::db::timestamp::option")]
+    pub archived_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(with = "crate::db::timestamp::option")]
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+/// Days a soft-deleted conversation is kept before it's purged for good.
+pub const DELETED_RETENTION_DAYS: i32 = 30;
+
+/// Condition on the `c` (conversations) alias matching conversations past
+/// their retention after soft deletion.
+fn expired_condition() -> String {
+    format!("c.deleted_at IS NOT NULL AND c.deleted_at < NOW() - make_interval(days => {DELETED_RETENTION_DAYS})")
+}
+
+/// What a purge of expired conversations removed, or would remove.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ConversationPurge {
+    pub conversations: u64,
+    pub messages: u64,
+    /// Widget sessions left without conversations and themselves inactive
+    /// for [`DELETED_RETENTION_DAYS`].
+    pub sessions: u64,
+}
+
+/// Characters of the latest message shown in a widget conversation list.
+pub const LAST_MESSAGE_PREVIEW_CHARS: i32 = 120;
+
+/// A widget visitor's conversation with enough of its latest message to tell
+/// threads apart.
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct WidgetConversationSummary {
+    #[serde(flatten)]
+    pub conversation: Conversation,
+    /// The start of the latest message; `None` before the first one.
+    pub last_message_preview: Option<String>,
+    /// `user` or `assistant`, for the latest message.
+    pub last_message_role: Option<String>,
+    pub message_count: i64,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -24,10 +73,158 @@ pub struct ConversationWithUser {
     pub email: String,
     pub title: String,
     pub message_count: i64,
-    pub created_at: String,
-    pub updated_at: String,
+    #[serde(with = "crate::db::timestamp")]
+    pub created_at: DateTime<Utc>,
+    #[serde(with = "crate::db::timestamp")]
+    pub updated_at: DateTime<Utc>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub deleted_at: Option<String>,
+    #[serde(with = "crate::db::timestamp::option")]
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+/// Which conversations admin log queries include, by soft-deletion state.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum DeletedFilter {
+    #[default]
+    All,
+    Active,
+    Deleted,
+}
+
+impl DeletedFilter {
+    /// Condition on the `c` (conversations) alias, to be ANDed into a WHERE clause.
+    fn condition(self) -> &'static str {
+        match self {
+            DeletedFilter::All => "TRUE",
+            DeletedFilter::Active => "c.deleted_at IS NULL",
+            DeletedFilter::Deleted => "c.deleted_at IS NOT NULL",
+        }
+    }
+}
+
+/// What admin log listings are ordered by.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum LogSort {
+    #[default]
+    UpdatedAt,
+    CreatedAt,
+    MessageCount,
+}
+
+impl LogSort {
+    fn column(self) -> &'static str {
+        match self {
+            LogSort::UpdatedAt => "c.updated_at",
+            LogSort::CreatedAt => "c.created_at",
+            LogSort::MessageCount => "message_count",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+impl SortOrder {
+    fn keyword(self) -> &'static str {
+        match self {
+            SortOrder::Asc => "ASC",
+            SortOrder::Desc => "DESC",
+        }
+    }
+}
+
+/// Filters and order of the admin conversation log listings.
+#[derive(Debug, Clone, Default)]
+pub struct LogFilter {
+    pub deleted: DeletedFilter,
+    /// Only conversations started at or after this time.
+    pub from: Option<chrono::DateTime<chrono::Utc>>,
+    /// Only conversations started at or before this time.
+    pub to: Option<chrono::DateTime<chrono::Utc>>,
+    /// Matched case-insensitively against the title and every message.
+    pub search: Option<String>,
+    pub sort: LogSort,
+    pub order: SortOrder,
+}
+
+impl LogFilter {
+    /// Conditions on the `c` (conversations) alias, to be ANDed into a WHERE
+    /// clause after `owner`'s, with their values appended to `binds`.
+    fn conditions(&self, owner: Option<(&str, &str)>, binds: &mut Vec<String>) -> String {
+        let mut conditions = vec![self.deleted.condition().to_string()];
+        if let Some((column, value)) = owner {
+            binds.push(value.to_string());
+            conditions.push(format!("{column} = ${}", binds.len()));
+        }
+        if let Some(from) = self.from {
+            binds.push(from.to_rfc3339());
+            conditions.push(format!("c.created_at >= ${}::timestamptz", binds.len()));
+        }
+        if let Some(to) = self.to {
+            binds.push(to.to_rfc3339());
+            conditions.push(format!("c.created_at <= ${}::timestamptz", binds.len()));
+        }
+        if let Some(search) = self.search.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+            binds.push(format!("%{}%", escape_like(search)));
+            let idx = binds.len();
+            conditions.push(format!(
+                "(c.title ILIKE ${idx}
+                  OR EXISTS (SELECT 1 FROM messages m WHERE m.conversation_id = c.id AND m.content ILIKE ${idx}))"
+            ));
+        }
+        conditions.join(" AND ")
+    }
+
+    fn order_by(&self) -> String {
+        format!("{} {}, c.id", self.sort.column(), self.order.keyword())
+    }
+}
+
+impl From<DeletedFilter> for LogFilter {
+    fn from(deleted: DeletedFilter) -> Self {
+        Self {
+            deleted,
+            ..Self::default()
+        }
+    }
+}
+
+/// Escape `%`, `_` and `\` so user input matches literally inside a LIKE pattern.
+fn escape_like(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
+
+/// Which of a user's conversations a listing includes, by archive state.
+/// Deleted conversations are never included.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum ArchivedFilter {
+    #[default]
+    Active,
+    Archived,
+    All,
+}
+
+impl ArchivedFilter {
+    /// Condition on the conversations table, to be ANDed into a WHERE clause.
+    fn condition(self) -> &'static str {
+        match self {
+            ArchivedFilter::Active => "archived_at IS NULL",
+            ArchivedFilter::Archived => "archived_at IS NOT NULL",
+            ArchivedFilter::All => "TRUE",
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -39,8 +236,14 @@ pub struct WidgetConversationLog {
     pub session_id: String,
     pub title: String,
     pub message_count: i64,
-    pub created_at: String,
-    pub updated_at: String,
+    #[serde(with = "crate::db::timestamp")]
+    pub created_at: DateTime<Utc>,
+    #[serde(with = "crate::db::timestamp")]
+    pub updated_at: DateTime<Utc>,
+    /// Set when the visitor cleared the conversation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(with = "crate::db::timestamp::option")]
+    pub deleted_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -50,7 +253,106 @@ pub struct Message {
     pub conversation_id: String,
     pub role: String,
     pub content: String,
-    pub created_at: String,
+    #[serde(with = "crate::db::timestamp")]
+    pub created_at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub generation: MessageGeneration,
+    /// Set when the user deleted the message; its content is then empty.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(with = "crate::db::timestamp::option")]
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+/// Messages after a cursor, oldest first.
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct MessagePage {
+    pub messages: Vec<Message>,
+    /// More messages follow the last one; ask again with it as the cursor.
+    pub has_more: bool,
+}
+
+const MESSAGE_COLS: &str = "id, conversation_id, role, content,
+     created_at,
+     provider, model, prompt_tokens, completion_tokens, latency_ms, fallback_from, context_chunks,
+     deleted_at";
+
+fn map_message(row: &sqlx::postgres::PgRow) -> Message {
+    Message {
+        id: row.get("id"),
+        conversation_id: row.get("conversation_id"),
+        role: row.get("role"),
+        content: row.get("content"),
+        created_at: row.get("created_at"),
+        generation: MessageGeneration {
+            provider: row.get("provider"),
+            model: row.get("model"),
+            prompt_tokens: row.get("prompt_tokens"),
+            completion_tokens: row.get("completion_tokens"),
+            latency_ms: row.get("latency_ms"),
+            fallback_from: row.get("fallback_from"),
+            context_chunks: row.get("context_chunks"),
+        },
+        deleted_at: row.get("deleted_at"),
+    }
+}
+
+/// How an assistant message was generated. Unset on user messages and on
+/// replies saved before it was recorded.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct MessageGeneration {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Set when the provider reports token usage.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompt_tokens: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub completion_tokens: Option<i32>,
+    /// Time the model took to answer.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<i32>,
+    /// The conversation's provider, when it failed and `provider` answered
+    /// as a fallback.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fallback_from: Option<String>,
+    /// Knowledge base chunks that went into the prompt; 0 means the reply
+    /// was written without retrieved context.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub context_chunks: Option<i32>,
+}
+
+/// Chat settings a conversation uses instead of the user's preferences. Unset
+/// fields fall back to them; widget conversations never have any.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ConversationSettings {
+    /// Set together with `model`.
+    pub provider: Option<String>,
+    pub model: Option<String>,
+    pub system_prompt: Option<String>,
+}
+
+impl ConversationSettings {
+    /// Trim the fields and treat blank ones as unset.
+    pub fn normalized(self) -> Self {
+        let clean = |v: Option<String>| v.map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+        Self {
+            provider: clean(self.provider),
+            model: clean(self.model),
+            system_prompt: clean(self.system_prompt),
+        }
+    }
+}
+
+/// A rolling summary of a conversation's older messages, sent in place of them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConversationSummary {
+    pub text: String,
+    /// How many of the conversation's first messages the summary covers.
+    pub message_count: usize,
 }
 
 #[derive(Clone)]
@@ -63,17 +365,26 @@ impl ConversationRepository {
         Self { pool }
     }
 
-    pub async fn create(&self, user_id: &str, title: &str) -> Result<Conversation> {
+    /// `title_is_custom` marks a title the user chose, which automatic titling never replaces.
+    pub async fn create(
+        &self,
+        user_id: &str,
+        title: &str,
+        title_is_custom: bool,
+        collection_id: Option<&str>,
+    ) -> Result<Conversation> {
         let id = Uuid::new_v4().to_string();
         let now = chrono::Utc::now();
 
         sqlx::query(
-            "INSERT INTO conversations (id, user_id, title, created_at, updated_at)
-             VALUES ($1, $2, $3, $4, $5)",
+            "INSERT INTO conversations (id, user_id, title, title_is_custom, collection_id, created_at, updated_at)
+             VALUES ($1, $2, $3, $4, $5, $6, $7)",
         )
         .bind(&id)
         .bind(user_id)
         .bind(title)
+        .bind(title_is_custom)
+        .bind(collection_id)
         .bind(now)
         .bind(now)
         .execute(&self.pool)
@@ -84,23 +395,30 @@ impl ConversationRepository {
             id,
             user_id: user_id.to_string(),
             title: title.to_string(),
-            created_at: now.to_rfc3339(),
-            updated_at: now.to_rfc3339(),
+            title_is_custom,
+            collection_id: collection_id.map(str::to_string),
+            created_at: now,
+            updated_at: now,
+            archived_at: None,
             deleted_at: None,
         })
     }
 
-    pub async fn list_by_user(&self, user_id: &str) -> Result<Vec<Conversation>> {
-        let rows = sqlx::query(
-            "SELECT id, user_id, title,
-                    to_char(created_at, 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') AS created_at,
-                    to_char(updated_at, 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') AS updated_at
-             FROM conversations WHERE user_id = $1 AND deleted_at IS NULL ORDER BY updated_at DESC",
-        )
-        .bind(user_id)
-        .fetch_all(&self.pool)
-        .await
-        .context("Failed to list conversations")?;
+    pub async fn list_by_user(&self, user_id: &str, archived: ArchivedFilter) -> Result<Vec<Conversation>> {
+        let sql = format!(
+            "SELECT id, user_id, title, title_is_custom, collection_id,
+                    created_at,
+                    updated_at,
+                    archived_at
+             FROM conversations WHERE user_id = $1 AND deleted_at IS NULL AND {}
+             ORDER BY updated_at DESC",
+            archived.condition()
+        );
+        let rows = sqlx::query(&sql)
+            .bind(user_id)
+            .fetch_all(&self.pool)
+            .await
+            .context("Failed to list conversations")?;
 
         let conversations = rows
             .iter()
@@ -108,8 +426,11 @@ impl ConversationRepository {
                 id: row.get("id"),
                 user_id: row.get("user_id"),
                 title: row.get("title"),
+                title_is_custom: row.get("title_is_custom"),
+                collection_id: row.get("collection_id"),
                 created_at: row.get("created_at"),
                 updated_at: row.get("updated_at"),
+                archived_at: row.get("archived_at"),
                 deleted_at: None,
             })
             .collect();
@@ -119,9 +440,10 @@ impl ConversationRepository {
 
     pub async fn get(&self, id: &str, user_id: &str) -> Result<Option<Conversation>> {
         let row = sqlx::query(
-            "SELECT id, user_id, title,
-                    to_char(created_at, 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') AS created_at,
-                    to_char(updated_at, 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') AS updated_at
+            "SELECT id, user_id, title, title_is_custom, collection_id,
+                    created_at,
+                    updated_at,
+                    archived_at
              FROM conversations WHERE id = $1 AND user_id = $2 AND deleted_at IS NULL",
         )
         .bind(id)
@@ -134,8 +456,11 @@ impl ConversationRepository {
             id: r.get("id"),
             user_id: r.get("user_id"),
             title: r.get("title"),
+            title_is_custom: r.get("title_is_custom"),
+            collection_id: r.get("collection_id"),
             created_at: r.get("created_at"),
             updated_at: r.get("updated_at"),
+            archived_at: r.get("archived_at"),
             deleted_at: None,
         }))
     }
@@ -153,28 +478,233 @@ impl ConversationRepository {
         Ok(())
     }
 
-    pub async fn hard_delete_expired(&self) -> Result<i64> {
+    /// Hide a conversation from the default listing; already archived ones keep
+    /// their original time. Returns false if the user has no such conversation.
+    pub async fn archive(&self, id: &str, user_id: &str) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE conversations SET archived_at = COALESCE(archived_at, NOW())
+             WHERE id = $1 AND user_id = $2 AND deleted_at IS NULL",
+        )
+        .bind(id)
+        .bind(user_id)
+        .execute(&self.pool)
+        .await
+        .context("Failed to archive conversation")?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Bring an archived conversation back to the default listing. Deleted
+    /// conversations stay deleted. Returns false if the user has no such conversation.
+    pub async fn unarchive(&self, id: &str, user_id: &str) -> Result<bool> {
         let result = sqlx::query(
-            "DELETE FROM conversations WHERE deleted_at IS NOT NULL AND deleted_at < NOW() - INTERVAL '30 days'",
+            "UPDATE conversations SET archived_at = NULL
+             WHERE id = $1 AND user_id = $2 AND deleted_at IS NULL",
         )
+        .bind(id)
+        .bind(user_id)
         .execute(&self.pool)
         .await
+        .context("Failed to unarchive conversation")?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// How much the next purge of expired conversations would remove.
+    pub async fn count_expired(&self) -> Result<ConversationPurge> {
+        let row = sqlx::query(&format!(
+            "SELECT COUNT(*) AS conversations,
+                    COALESCE(SUM((SELECT COUNT(*) FROM messages m WHERE m.conversation_id = c.id)), 0)::BIGINT AS messages
+             FROM conversations c
+             WHERE {}",
+            expired_condition()
+        ))
+        .fetch_one(&self.pool)
+        .await
+        .context("Failed to count expired conversations")?;
+
+        Ok(ConversationPurge {
+            conversations: row.get::<i64, _>("conversations") as u64,
+            messages: row.get::<i64, _>("messages") as u64,
+            sessions: 0,
+        })
+    }
+
+    /// Permanently delete up to `limit` conversations soft-deleted more than
+    /// [`DELETED_RETENTION_DAYS`] ago, oldest first. Messages, shares and
+    /// handoffs go with them by cascade.
+    pub async fn hard_delete_expired(&self, limit: i64) -> Result<ConversationPurge> {
+        let row = sqlx::query(&format!(
+            "WITH doomed AS (
+                 SELECT c.id FROM conversations c WHERE {expired} ORDER BY c.deleted_at LIMIT $1
+             ),
+             counted AS (
+                 SELECT COUNT(*) AS messages FROM messages WHERE conversation_id IN (SELECT id FROM doomed)
+             ),
+             deleted AS (
+                 DELETE FROM conversations WHERE id IN (SELECT id FROM doomed) RETURNING id
+             )
+             SELECT (SELECT COUNT(*) FROM deleted) AS conversations, (SELECT messages FROM counted) AS messages",
+            expired = expired_condition()
+        ))
+        .bind(limit)
+        .fetch_one(&self.pool)
+        .await
         .context("Failed to hard-delete expired conversations")?;
 
-        Ok(result.rows_affected() as i64)
+        Ok(ConversationPurge {
+            conversations: row.get::<i64, _>("conversations") as u64,
+            messages: row.get::<i64, _>("messages") as u64,
+            sessions: 0,
+        })
     }
 
-    pub async fn update_title(&self, id: &str, title: &str) -> Result<()> {
-        let now = chrono::Utc::now();
-        sqlx::query("UPDATE conversations SET title = $1, updated_at = $2 WHERE id = $3")
-            .bind(title)
-            .bind(now)
+    /// Soft-delete any conversation, regardless of owner (admin moderation).
+    /// Returns false if it doesn't exist or was already deleted.
+    pub async fn admin_soft_delete(&self, id: &str) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE conversations SET deleted_at = NOW() WHERE id = $1 AND deleted_at IS NULL",
+        )
+        .bind(id)
+        .execute(&self.pool)
+        .await
+        .context("Failed to soft-delete conversation")?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Permanently delete a conversation and its messages right away, skipping
+    /// the 30-day retention of soft-deleted conversations.
+    pub async fn hard_delete(&self, id: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM conversations WHERE id = $1")
             .bind(id)
             .execute(&self.pool)
             .await
-            .context("Failed to update title")?;
+            .context("Failed to hard-delete conversation")?;
 
-        Ok(())
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Set an automatically generated title. Titles the user set are left alone;
+    /// returns whether the title was changed.
+    pub async fn update_title(&self, id: &str, title: &str) -> Result<bool> {
+        let now = chrono::Utc::now();
+        let result = sqlx::query(
+            "UPDATE conversations SET title = $1, updated_at = $2 WHERE id = $3 AND NOT title_is_custom",
+        )
+        .bind(title)
+        .bind(now)
+        .bind(id)
+        .execute(&self.pool)
+        .await
+        .context("Failed to update title")?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Set a title chosen by the user; automatic titling won't replace it.
+    /// Returns false if the user has no such conversation.
+    pub async fn rename(&self, id: &str, user_id: &str, title: &str) -> Result<bool> {
+        let now = chrono::Utc::now();
+        let result = sqlx::query(
+            "UPDATE conversations SET title = $1, title_is_custom = TRUE, updated_at = $2
+             WHERE id = $3 AND user_id = $4 AND deleted_at IS NULL",
+        )
+        .bind(title)
+        .bind(now)
+        .bind(id)
+        .bind(user_id)
+        .execute(&self.pool)
+        .await
+        .context("Failed to rename conversation")?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Limit a conversation's retrieval to a collection, or lift the limit with
+    /// `None`. Returns false if the user has no such conversation.
+    pub async fn set_collection(&self, id: &str, user_id: &str, collection_id: Option<&str>) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE conversations SET collection_id = $1
+             WHERE id = $2 AND user_id = $3 AND deleted_at IS NULL",
+        )
+        .bind(collection_id)
+        .bind(id)
+        .bind(user_id)
+        .execute(&self.pool)
+        .await
+        .context("Failed to set conversation collection")?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn get_settings(&self, id: &str) -> Result<ConversationSettings> {
+        let row = sqlx::query(
+            "SELECT provider, model, system_prompt FROM conversations WHERE id = $1 AND source = 'app'",
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to query conversation settings")?;
+
+        Ok(row
+            .map(|r| ConversationSettings {
+                provider: r.get("provider"),
+                model: r.get("model"),
+                system_prompt: r.get("system_prompt"),
+            })
+            .unwrap_or_default())
+    }
+
+    /// Replace a user's conversation settings. Returns false if the conversation isn't theirs.
+    pub async fn set_settings(&self, id: &str, user_id: &str, settings: &ConversationSettings) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE conversations SET provider = $1, model = $2, system_prompt = $3
+             WHERE id = $4 AND user_id = $5 AND source = 'app' AND deleted_at IS NULL",
+        )
+        .bind(&settings.provider)
+        .bind(&settings.model)
+        .bind(&settings.system_prompt)
+        .bind(id)
+        .bind(user_id)
+        .execute(&self.pool)
+        .await
+        .context("Failed to update conversation settings")?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn get_summary(&self, id: &str) -> Result<ConversationSummary> {
+        let row = sqlx::query("SELECT summary, summary_message_count FROM conversations WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+            .context("Failed to query conversation summary")?;
+
+        Ok(row
+            .map(|r| ConversationSummary {
+                text: r.get("summary"),
+                message_count: r.get::<i32, _>("summary_message_count") as usize,
+            })
+            .unwrap_or_default())
+    }
+
+    /// Replace the summary, unless another update got there first since
+    /// `previous_count` was read. Returns whether it was saved.
+    pub async fn update_summary(&self, id: &str, summary: &ConversationSummary, previous_count: usize) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE conversations SET summary = $1, summary_message_count = $2
+             WHERE id = $3 AND summary_message_count = $4",
+        )
+        .bind(&summary.text)
+        .bind(summary.message_count as i32)
+        .bind(id)
+        .bind(previous_count as i32)
+        .execute(&self.pool)
+        .await
+        .context("Failed to update conversation summary")?;
+
+        Ok(result.rows_affected() > 0)
     }
 
     pub async fn touch(&self, id: &str) -> Result<()> {
@@ -189,24 +719,36 @@ impl ConversationRepository {
         Ok(())
     }
 
+    /// Save a message; `generation` records how an assistant reply was produced.
     pub async fn add_message(
         &self,
         conversation_id: &str,
         role: &str,
         content: &str,
+        generation: Option<&MessageGeneration>,
     ) -> Result<Message> {
         let id = Uuid::new_v4().to_string();
         let now = chrono::Utc::now();
+        let generation = generation.cloned().unwrap_or_default();
 
         sqlx::query(
-            "INSERT INTO messages (id, conversation_id, role, content, created_at)
-             VALUES ($1, $2, $3, $4, $5)",
+            "INSERT INTO messages (id, conversation_id, role, content, created_at,
+                                   provider, model, prompt_tokens, completion_tokens, latency_ms, fallback_from,
+                                   context_chunks)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)",
         )
         .bind(&id)
         .bind(conversation_id)
         .bind(role)
         .bind(content)
         .bind(now)
+        .bind(&generation.provider)
+        .bind(&generation.model)
+        .bind(generation.prompt_tokens)
+        .bind(generation.completion_tokens)
+        .bind(generation.latency_ms)
+        .bind(&generation.fallback_from)
+        .bind(generation.context_chunks)
         .execute(&self.pool)
         .await
         .context("Failed to add message")?;
@@ -216,33 +758,152 @@ impl ConversationRepository {
             conversation_id: conversation_id.to_string(),
             role: role.to_string(),
             content: content.to_string(),
-            created_at: now.to_rfc3339(),
+            created_at: now,
+            generation,
+            deleted_at: None,
         })
     }
 
+    /// The conversation's messages, without deleted ones.
     pub async fn get_messages(&self, conversation_id: &str) -> Result<Vec<Message>> {
-        let rows = sqlx::query(
-            "SELECT id, conversation_id, role, content,
-                    to_char(created_at, 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') AS created_at
-             FROM messages WHERE conversation_id = $1 ORDER BY created_at ASC",
-        )
+        self.query_messages(conversation_id, false).await
+    }
+
+    /// The conversation's messages with deleted ones left in place as empty
+    /// stubs, so a log shows where they were.
+    pub async fn get_messages_with_deleted(&self, conversation_id: &str) -> Result<Vec<Message>> {
+        self.query_messages(conversation_id, true).await
+    }
+
+    async fn query_messages(&self, conversation_id: &str, with_deleted: bool) -> Result<Vec<Message>> {
+        let rows = sqlx::query(&format!(
+            "SELECT {MESSAGE_COLS}
+             FROM messages WHERE conversation_id = $1 AND ($2 OR deleted_at IS NULL)
+             ORDER BY messages.created_at ASC, messages.id ASC"
+        ))
         .bind(conversation_id)
+        .bind(with_deleted)
         .fetch_all(&self.pool)
         .await
         .context("Failed to get messages")?;
 
-        let messages = rows
-            .iter()
-            .map(|row| Message {
-                id: row.get("id"),
-                conversation_id: row.get("conversation_id"),
-                role: row.get("role"),
-                content: row.get("content"),
-                created_at: row.get("created_at"),
-            })
-            .collect();
+        Ok(rows.iter().map(map_message).collect())
+    }
+
+    /// Up to `limit` messages that come after `after_id` (or from the start
+    /// without one), without deleted ones, and whether more follow. `None` if
+    /// `after_id` isn't a message of the conversation. A deleted message still
+    /// works as the cursor, so a polling client doesn't lose its place.
+    pub async fn get_messages_after(
+        &self,
+        conversation_id: &str,
+        after_id: Option<&str>,
+        limit: i64,
+    ) -> Result<Option<MessagePage>> {
+        let cursor = match after_id {
+            Some(after_id) => {
+                let row = sqlx::query("SELECT created_at FROM messages WHERE id = $1 AND conversation_id = $2")
+                    .bind(after_id)
+                    .bind(conversation_id)
+                    .fetch_optional(&self.pool)
+                    .await
+                    .context("Failed to find cursor message")?;
+                let Some(row) = row else {
+                    return Ok(None);
+                };
+                Some((row.get::<chrono::DateTime<chrono::Utc>, _>("created_at"), after_id))
+            }
+            None => None,
+        };
 
-        Ok(messages)
+        // One extra row tells whether there is another page
+        let rows = sqlx::query(&format!(
+            "SELECT {MESSAGE_COLS}
+             FROM messages
+             WHERE conversation_id = $1 AND deleted_at IS NULL
+               AND ($2::timestamptz IS NULL OR (created_at, id) > ($2, $3))
+             ORDER BY messages.created_at ASC, messages.id ASC
+             LIMIT $4"
+        ))
+        .bind(conversation_id)
+        .bind(cursor.map(|(created_at, _)| created_at))
+        .bind(cursor.map(|(_, id)| id))
+        .bind(limit + 1)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to get messages")?;
+
+        let mut messages: Vec<Message> = rows.iter().map(map_message).collect();
+        let has_more = messages.len() as i64 > limit;
+        messages.truncate(limit as usize);
+        Ok(Some(MessagePage { messages, has_more }))
+    }
+
+    /// Delete a message of the conversation, and with `cascade` the assistant
+    /// reply that follows it when it is a user message. The content is wiped
+    /// and the row kept as a stub. The conversation's summary is reset, as it
+    /// may repeat the content and counts messages that are now gone. Returns
+    /// the deleted message IDs, or `None` if there was no such message.
+    pub async fn delete_message(
+        &self,
+        conversation_id: &str,
+        id: &str,
+        cascade: bool,
+    ) -> Result<Option<Vec<String>>> {
+        let mut tx = self.pool.begin().await.context("Failed to begin transaction")?;
+
+        let Some(row) = sqlx::query(
+            "SELECT role, created_at FROM messages
+             WHERE id = $1 AND conversation_id = $2 AND deleted_at IS NULL
+             FOR UPDATE",
+        )
+        .bind(id)
+        .bind(conversation_id)
+        .fetch_optional(&mut *tx)
+        .await
+        .context("Failed to query message")?
+        else {
+            return Ok(None);
+        };
+
+        let mut ids = vec![id.to_string()];
+        let role: String = row.get("role");
+        if cascade && role == "user" {
+            let created_at: chrono::DateTime<chrono::Utc> = row.get("created_at");
+            let reply: Option<String> = sqlx::query_scalar(
+                "SELECT id FROM messages
+                 WHERE conversation_id = $1 AND deleted_at IS NULL AND created_at > $2 AND role = 'assistant'
+                   AND NOT EXISTS (
+                       SELECT 1 FROM messages u
+                       WHERE u.conversation_id = $1 AND u.deleted_at IS NULL AND u.role = 'user'
+                         AND u.created_at > $2 AND u.created_at < messages.created_at
+                   )
+                 ORDER BY created_at ASC
+                 LIMIT 1",
+            )
+            .bind(conversation_id)
+            .bind(created_at)
+            .fetch_optional(&mut *tx)
+            .await
+            .context("Failed to query message reply")?;
+            ids.extend(reply);
+        }
+
+        sqlx::query("UPDATE messages SET content = '', deleted_at = NOW() WHERE id = ANY($1)")
+            .bind(&ids)
+            .execute(&mut *tx)
+            .await
+            .context("Failed to delete message")?;
+        sqlx::query(
+            "UPDATE conversations SET summary = '', summary_message_count = 0, updated_at = NOW() WHERE id = $1",
+        )
+        .bind(conversation_id)
+        .execute(&mut *tx)
+        .await
+        .context("Failed to reset conversation summary")?;
+
+        tx.commit().await.context("Failed to commit transaction")?;
+        Ok(Some(ids))
     }
 
     // ── Admin log queries (unscoped) ─────────────────────────
@@ -250,51 +911,38 @@ impl ConversationRepository {
     pub async fn list_all(
         &self,
         user_id_filter: Option<&str>,
+        filter: &LogFilter,
         limit: i64,
         offset: i64,
     ) -> Result<Vec<ConversationWithUser>> {
-        let (query, bind_user_id);
-        if let Some(uid) = user_id_filter {
-            bind_user_id = Some(uid.to_string());
-            query = "SELECT c.id, c.user_id, u.username, u.email, c.title,
-                            (SELECT COUNT(*) FROM messages m WHERE m.conversation_id = c.id) AS message_count,
-                            to_char(c.created_at, 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') AS created_at,
-                            to_char(c.updated_at, 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') AS updated_at,
-                            to_char(c.deleted_at, 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') AS deleted_at
-                     FROM conversations c
-                     JOIN users u ON c.user_id = u.id
-                     WHERE c.user_id = $1 AND (c.source IS NULL OR c.source != 'widget')
-                     ORDER BY c.updated_at DESC
-                     LIMIT $2 OFFSET $3";
-        } else {
-            bind_user_id = None;
-            query = "SELECT c.id, c.user_id, u.username, u.email, c.title,
-                            (SELECT COUNT(*) FROM messages m WHERE m.conversation_id = c.id) AS message_count,
-                            to_char(c.created_at, 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') AS created_at,
-                            to_char(c.updated_at, 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') AS updated_at,
-                            to_char(c.deleted_at, 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') AS deleted_at
-                     FROM conversations c
-                     JOIN users u ON c.user_id = u.id
-                     WHERE (c.source IS NULL OR c.source != 'widget')
-                     ORDER BY c.updated_at DESC
-                     LIMIT $1 OFFSET $2";
-        }
+        let mut binds = Vec::new();
+        let conditions = filter.conditions(user_id_filter.map(|uid| ("c.user_id", uid)), &mut binds);
+        let query = format!(
+            "SELECT c.id, c.user_id, u.username, u.email, c.title,
+                    (SELECT COUNT(*) FROM messages m WHERE m.conversation_id = c.id) AS message_count,
+                    c.created_at,
+                    c.updated_at,
+                    c.deleted_at
+             FROM conversations c
+             JOIN users u ON c.user_id = u.id
+             WHERE (c.source IS NULL OR c.source != 'widget') AND {conditions}
+             ORDER BY {}
+             LIMIT ${} OFFSET ${}",
+            filter.order_by(),
+            binds.len() + 1,
+            binds.len() + 2
+        );
 
-        let rows = if let Some(ref uid) = bind_user_id {
-            sqlx::query(query)
-                .bind(uid)
-                .bind(limit)
-                .bind(offset)
-                .fetch_all(&self.pool)
-                .await
-        } else {
-            sqlx::query(query)
-                .bind(limit)
-                .bind(offset)
-                .fetch_all(&self.pool)
-                .await
+        let mut q = sqlx::query(&query);
+        for b in &binds {
+            q = q.bind(b);
         }
-        .context("Failed to list all conversations")?;
+        let rows = q
+            .bind(limit)
+            .bind(offset)
+            .fetch_all(&self.pool)
+            .await
+            .context("Failed to list all conversations")?;
 
         let conversations = rows
             .iter()
@@ -314,22 +962,19 @@ impl ConversationRepository {
         Ok(conversations)
     }
 
-    pub async fn count_all(&self, user_id_filter: Option<&str>) -> Result<i64> {
-        let count = if let Some(uid) = user_id_filter {
-            sqlx::query_scalar::<_, i64>(
-                "SELECT COUNT(*) FROM conversations WHERE user_id = $1 AND (source IS NULL OR source != 'widget')",
-            )
-            .bind(uid)
-            .fetch_one(&self.pool)
-            .await
-        } else {
-            sqlx::query_scalar::<_, i64>(
-                "SELECT COUNT(*) FROM conversations WHERE (source IS NULL OR source != 'widget')",
-            )
-            .fetch_one(&self.pool)
-            .await
+    pub async fn count_all(&self, user_id_filter: Option<&str>, filter: &LogFilter) -> Result<i64> {
+        let mut binds = Vec::new();
+        let conditions = filter.conditions(user_id_filter.map(|uid| ("c.user_id", uid)), &mut binds);
+        let query = format!(
+            "SELECT COUNT(*) FROM conversations c
+             WHERE (c.source IS NULL OR c.source != 'widget') AND {conditions}"
+        );
+
+        let mut q = sqlx::query_scalar::<_, i64>(&query);
+        for b in &binds {
+            q = q.bind(b);
         }
-        .context("Failed to count conversations")?;
+        let count = q.fetch_one(&self.pool).await.context("Failed to count conversations")?;
 
         Ok(count)
     }
@@ -339,55 +984,41 @@ impl ConversationRepository {
     pub async fn list_widget_conversations(
         &self,
         embed_key_id_filter: Option<&str>,
+        filter: &LogFilter,
         limit: i64,
         offset: i64,
     ) -> Result<Vec<WidgetConversationLog>> {
-        let (query, bind_ek_id);
-        if let Some(ek_id) = embed_key_id_filter {
-            bind_ek_id = Some(ek_id.to_string());
-            query = "SELECT c.id, c.embed_key_id,
-                            COALESCE(ek.name, 'Unknown') AS embed_key_name,
-                            COALESCE(c.session_id, '') AS session_id,
-                            c.title,
-                            (SELECT COUNT(*) FROM messages m WHERE m.conversation_id = c.id) AS message_count,
-                            to_char(c.created_at, 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') AS created_at,
-                            to_char(c.updated_at, 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') AS updated_at
-                     FROM conversations c
-                     LEFT JOIN embed_keys ek ON c.embed_key_id = ek.id
-                     WHERE c.source = 'widget' AND c.embed_key_id = $1
-                     ORDER BY c.updated_at DESC
-                     LIMIT $2 OFFSET $3";
-        } else {
-            bind_ek_id = None;
-            query = "SELECT c.id, c.embed_key_id,
-                            COALESCE(ek.name, 'Unknown') AS embed_key_name,
-                            COALESCE(c.session_id, '') AS session_id,
-                            c.title,
-                            (SELECT COUNT(*) FROM messages m WHERE m.conversation_id = c.id) AS message_count,
-                            to_char(c.created_at, 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') AS created_at,
-                            to_char(c.updated_at, 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') AS updated_at
-                     FROM conversations c
-                     LEFT JOIN embed_keys ek ON c.embed_key_id = ek.id
-                     WHERE c.source = 'widget'
-                     ORDER BY c.updated_at DESC
-                     LIMIT $1 OFFSET $2";
-        }
+        let mut binds = Vec::new();
+        let conditions = filter.conditions(embed_key_id_filter.map(|id| ("c.embed_key_id", id)), &mut binds);
+        let query = format!(
+            "SELECT c.id, c.embed_key_id,
+                    COALESCE(ek.name, 'Unknown') AS embed_key_name,
+                    COALESCE(c.session_id, '') AS session_id,
+                    c.title,
+                    (SELECT COUNT(*) FROM messages m WHERE m.conversation_id = c.id) AS message_count,
+                    c.created_at,
+                    c.updated_at,
+                    c.deleted_at
+             FROM conversations c
+             LEFT JOIN embed_keys ek ON c.embed_key_id = ek.id
+             WHERE c.source = 'widget' AND {conditions}
+             ORDER BY {}
+             LIMIT ${} OFFSET ${}",
+            filter.order_by(),
+            binds.len() + 1,
+            binds.len() + 2
+        );
 
-        let rows = if let Some(ref ek_id) = bind_ek_id {
-            sqlx::query(query)
-                .bind(ek_id)
-                .bind(limit)
-                .bind(offset)
-                .fetch_all(&self.pool)
-                .await
-        } else {
-            sqlx::query(query)
-                .bind(limit)
-                .bind(offset)
-                .fetch_all(&self.pool)
-                .await
+        let mut q = sqlx::query(&query);
+        for b in &binds {
+            q = q.bind(b);
         }
-        .context("Failed to list widget conversations")?;
+        let rows = q
+            .bind(limit)
+            .bind(offset)
+            .fetch_all(&self.pool)
+            .await
+            .context("Failed to list widget conversations")?;
 
         let conversations = rows
             .iter()
@@ -400,6 +1031,7 @@ impl ConversationRepository {
                 message_count: row.get("message_count"),
                 created_at: row.get("created_at"),
                 updated_at: row.get("updated_at"),
+                deleted_at: row.get("deleted_at"),
             })
             .collect();
 
@@ -409,32 +1041,28 @@ impl ConversationRepository {
     pub async fn count_widget_conversations(
         &self,
         embed_key_id_filter: Option<&str>,
+        filter: &LogFilter,
     ) -> Result<i64> {
-        let count = if let Some(ek_id) = embed_key_id_filter {
-            sqlx::query_scalar::<_, i64>(
-                "SELECT COUNT(*) FROM conversations WHERE source = 'widget' AND embed_key_id = $1",
-            )
-            .bind(ek_id)
-            .fetch_one(&self.pool)
-            .await
-        } else {
-            sqlx::query_scalar::<_, i64>(
-                "SELECT COUNT(*) FROM conversations WHERE source = 'widget'",
-            )
-            .fetch_one(&self.pool)
-            .await
+        let mut binds = Vec::new();
+        let conditions = filter.conditions(embed_key_id_filter.map(|id| ("c.embed_key_id", id)), &mut binds);
+        let query = format!("SELECT COUNT(*) FROM conversations c WHERE c.source = 'widget' AND {conditions}");
+
+        let mut q = sqlx::query_scalar::<_, i64>(&query);
+        for b in &binds {
+            q = q.bind(b);
         }
-        .context("Failed to count widget conversations")?;
+        let count = q.fetch_one(&self.pool).await.context("Failed to count widget conversations")?;
 
         Ok(count)
     }
 
     pub async fn get_by_id(&self, id: &str) -> Result<Option<Conversation>> {
         let row = sqlx::query(
-            "SELECT id, user_id, title,
-                    to_char(created_at, 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') AS created_at,
-                    to_char(updated_at, 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') AS updated_at,
-                    to_char(deleted_at, 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') AS deleted_at
+            "SELECT id, user_id, title, title_is_custom, collection_id,
+                    created_at,
+                    updated_at,
+                    archived_at,
+                    deleted_at
              FROM conversations WHERE id = $1",
         )
         .bind(id)
@@ -446,8 +1074,11 @@ impl ConversationRepository {
             id: r.get("id"),
             user_id: r.get("user_id"),
             title: r.get("title"),
+            title_is_custom: r.get("title_is_custom"),
+            collection_id: r.get("collection_id"),
             created_at: r.get("created_at"),
             updated_at: r.get("updated_at"),
+            archived_at: r.get("archived_at"),
             deleted_at: r.get("deleted_at"),
         }))
     }
@@ -481,8 +1112,11 @@ impl ConversationRepository {
             id,
             user_id: "__widget__".to_string(),
             title: title.to_string(),
-            created_at: now.to_rfc3339(),
-            updated_at: now.to_rfc3339(),
+            title_is_custom: false,
+            collection_id: None,
+            created_at: now,
+            updated_at: now,
+            archived_at: None,
             deleted_at: None,
         })
     }
@@ -494,9 +1128,9 @@ impl ConversationRepository {
         embed_key_id: &str,
     ) -> Result<Option<Conversation>> {
         let row = sqlx::query(
-            "SELECT id, user_id, title,
-                    to_char(created_at, 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') AS created_at,
-                    to_char(updated_at, 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') AS updated_at
+            "SELECT id, user_id, title, title_is_custom, collection_id,
+                    created_at,
+                    updated_at
              FROM conversations
              WHERE id = $1 AND session_id = $2 AND embed_key_id = $3
                AND source = 'widget' AND deleted_at IS NULL",
@@ -512,41 +1146,100 @@ impl ConversationRepository {
             id: r.get("id"),
             user_id: r.get("user_id"),
             title: r.get("title"),
+            title_is_custom: r.get("title_is_custom"),
+            collection_id: r.get("collection_id"),
             created_at: r.get("created_at"),
             updated_at: r.get("updated_at"),
+            archived_at: None,
             deleted_at: None,
         }))
     }
 
+    /// Soft-delete a visitor's conversation. Returns false if it isn't theirs
+    /// or is already deleted.
+    pub async fn soft_delete_widget(&self, id: &str, session_id: &str, embed_key_id: &str) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE conversations SET deleted_at = NOW()
+             WHERE id = $1 AND session_id = $2 AND embed_key_id = $3
+               AND source = 'widget' AND deleted_at IS NULL",
+        )
+        .bind(id)
+        .bind(session_id)
+        .bind(embed_key_id)
+        .execute(&self.pool)
+        .await
+        .context("Failed to soft-delete widget conversation")?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Soft-delete every conversation of a widget session. Returns how many.
+    pub async fn soft_delete_session(&self, session_id: &str, embed_key_id: &str) -> Result<u64> {
+        let result = sqlx::query(
+            "UPDATE conversations SET deleted_at = NOW()
+             WHERE session_id = $1 AND embed_key_id = $2
+               AND source = 'widget' AND deleted_at IS NULL",
+        )
+        .bind(session_id)
+        .bind(embed_key_id)
+        .execute(&self.pool)
+        .await
+        .context("Failed to soft-delete widget session conversations")?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// A widget session's conversations, most recently active first.
     pub async fn list_by_session(
         &self,
         session_id: &str,
         embed_key_id: &str,
-    ) -> Result<Vec<Conversation>> {
+    ) -> Result<Vec<WidgetConversationSummary>> {
         let rows = sqlx::query(
-            "SELECT id, user_id, title,
-                    to_char(created_at, 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') AS created_at,
-                    to_char(updated_at, 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') AS updated_at
-             FROM conversations
-             WHERE session_id = $1 AND embed_key_id = $2
-               AND source = 'widget' AND deleted_at IS NULL
-             ORDER BY updated_at DESC",
+            "SELECT c.id, c.user_id, c.title, c.title_is_custom, c.collection_id,
+                    c.created_at,
+                    c.updated_at,
+                    left(last.content, $3) AS last_message_preview,
+                    last.role AS last_message_role,
+                    counts.message_count
+             FROM conversations c
+             LEFT JOIN LATERAL (
+                 SELECT m.role, m.content FROM messages m
+                 WHERE m.conversation_id = c.id
+                 ORDER BY m.created_at DESC
+                 LIMIT 1
+             ) last ON TRUE
+             CROSS JOIN LATERAL (
+                 SELECT COUNT(*) AS message_count FROM messages m WHERE m.conversation_id = c.id
+             ) counts
+             WHERE c.session_id = $1 AND c.embed_key_id = $2
+               AND c.source = 'widget' AND c.deleted_at IS NULL
+             ORDER BY c.updated_at DESC",
         )
         .bind(session_id)
         .bind(embed_key_id)
+        .bind(LAST_MESSAGE_PREVIEW_CHARS)
         .fetch_all(&self.pool)
         .await
         .context("Failed to list widget conversations")?;
 
         Ok(rows
             .iter()
-            .map(|r| Conversation {
-                id: r.get("id"),
-                user_id: r.get("user_id"),
-                title: r.get("title"),
-                created_at: r.get("created_at"),
-                updated_at: r.get("updated_at"),
-                deleted_at: None,
+            .map(|r| WidgetConversationSummary {
+                conversation: Conversation {
+                    id: r.get("id"),
+                    user_id: r.get("user_id"),
+                    title: r.get("title"),
+                    title_is_custom: r.get("title_is_custom"),
+                    collection_id: r.get("collection_id"),
+                    created_at: r.get("created_at"),
+                    updated_at: r.get("updated_at"),
+                    archived_at: None,
+                    deleted_at: None,
+                },
+                last_message_preview: r.get("last_message_preview"),
+                last_message_role: r.get("last_message_role"),
+                message_count: r.get("message_count"),
             })
             .collect())
     }
@@ -0,0 +1,95 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use sqlx::{PgPool, Row};
+use chrono::{DateTime, Utc};
+
+/// A read-only public link to a conversation. The token itself is only shown
+/// when the link is created; the table keeps its hash.
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ConversationShare {
+    pub id: String,
+    pub conversation_id: String,
+    pub created_by: String,
+    #[serde(with = "crate::db::timestamp")]
+    pub expires_at: DateTime<Utc>,
+    pub revoked: bool,
+    #[serde(with = "crate::db::timestamp")]
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Clone)]
+pub struct ConversationShareRepository {
+    pool: PgPool,
+}
+
+impl ConversationShareRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn create(
+        &self,
+        token_hash: &str,
+        conversation_id: &str,
+        created_by: &str,
+        expires_in_days: u32,
+    ) -> Result<ConversationShare> {
+        let id = uuid::Uuid::new_v4().to_string();
+        let row = sqlx::query(
+            "INSERT INTO conversation_shares (id, token_hash, conversation_id, created_by, expires_at)
+             VALUES ($1, $2, $3, $4, NOW() + make_interval(days => $5))
+             RETURNING id, conversation_id, created_by, revoked,
+                       expires_at,
+                       created_at",
+        )
+        .bind(&id)
+        .bind(token_hash)
+        .bind(conversation_id)
+        .bind(created_by)
+        .bind(expires_in_days as i32)
+        .fetch_one(&self.pool)
+        .await
+        .context("Failed to create conversation share")?;
+
+        Ok(ConversationShare {
+            id: row.get("id"),
+            conversation_id: row.get("conversation_id"),
+            created_by: row.get("created_by"),
+            expires_at: row.get("expires_at"),
+            revoked: row.get("revoked"),
+            created_at: row.get("created_at"),
+        })
+    }
+
+    /// Revoke every link to a conversation. Returns how many were still active.
+    pub async fn revoke_all(&self, conversation_id: &str) -> Result<u64> {
+        let result = sqlx::query(
+            "UPDATE conversation_shares SET revoked = TRUE
+             WHERE conversation_id = $1 AND NOT revoked AND expires_at > NOW()",
+        )
+        .bind(conversation_id)
+        .execute(&self.pool)
+        .await
+        .context("Failed to revoke conversation shares")?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// The conversation a token opens, if the link is neither revoked nor
+    /// expired and the conversation hasn't been deleted.
+    pub async fn find_conversation(&self, token_hash: &str) -> Result<Option<String>> {
+        let row = sqlx::query(
+            "SELECT s.conversation_id FROM conversation_shares s
+             JOIN conversations c ON c.id = s.conversation_id
+             WHERE s.token_hash = $1 AND NOT s.revoked AND s.expires_at > NOW()
+               AND c.deleted_at IS NULL",
+        )
+        .bind(token_hash)
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to look up conversation share")?;
+
+        Ok(row.map(|r| r.get("conversation_id")))
+    }
+}
@@ -2,6 +2,7 @@ use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use sqlx::{PgPool, Row};
 use uuid::Uuid;
+use chrono::{DateTime, Utc};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
@@ -14,9 +15,12 @@ pub struct CrawlJob {
     pub pages_found: i64,
     pub pages_processed: i64,
     pub error_message: Option<String>,
-    pub created_at: String,
-    pub started_at: Option<String>,
-    pub completed_at: Option<String>,
+    #[serde(with = "crate::db::timestamp")]
+    pub created_at: DateTime<Utc>,
+    #[serde(with = "crate::db::timestamp::option")]
+    pub started_at: Option<DateTime<Utc>>,
+    #[serde(with = "crate::db::timestamp::option")]
+    pub completed_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Clone)]
@@ -55,7 +59,7 @@ impl CrawlJobRepository {
             pages_found: 0,
             pages_processed: 0,
             error_message: None,
-            created_at: now.to_rfc3339(),
+            created_at: now,
             started_at: None,
             completed_at: None,
         })
@@ -65,9 +69,9 @@ impl CrawlJobRepository {
         let row = sqlx::query(
             "SELECT id, user_id, url, crawl_type, status, pages_found, pages_processed,
                     error_message,
-                    to_char(created_at, 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') AS created_at,
-                    to_char(started_at, 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') AS started_at,
-                    to_char(completed_at, 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') AS completed_at
+                    created_at,
+                    started_at,
+                    completed_at
              FROM crawl_jobs WHERE id = $1",
         )
         .bind(id)
@@ -82,9 +86,9 @@ impl CrawlJobRepository {
         let rows = sqlx::query(
             "SELECT id, user_id, url, crawl_type, status, pages_found, pages_processed,
                     error_message,
-                    to_char(created_at, 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') AS created_at,
-                    to_char(started_at, 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') AS started_at,
-                    to_char(completed_at, 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') AS completed_at
+                    created_at,
+                    started_at,
+                    completed_at
              FROM crawl_jobs WHERE user_id = $1 ORDER BY created_at DESC",
         )
         .bind(user_id)
@@ -95,6 +99,27 @@ impl CrawlJobRepository {
         Ok(rows.into_iter().map(Self::map_row).collect())
     }
 
+    /// Mark crawl jobs left `pending` or `running` by a process that died as
+    /// failed, unless a queued job will still run them or a running one's lease
+    /// is still being renewed. Returns how many were updated.
+    pub async fn fail_interrupted(&self, error_message: &str) -> Result<u64> {
+        let result = sqlx::query(
+            "UPDATE crawl_jobs SET status = 'failed', error_message = $1, completed_at = NOW()
+             WHERE status IN ('pending', 'running')
+               AND NOT EXISTS (
+                   SELECT 1 FROM jobs
+                   WHERE (jobs.status = 'queued' OR (jobs.status = 'running' AND jobs.locked_until >= NOW()))
+                     AND jobs.payload->>'crawl_job_id' = crawl_jobs.id
+               )",
+        )
+        .bind(error_message)
+        .execute(&self.pool)
+        .await
+        .context("Failed to fail interrupted crawl jobs")?;
+
+        Ok(result.rows_affected())
+    }
+
     pub async fn update_status(
         &self,
         id: &str,
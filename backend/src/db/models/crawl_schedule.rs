@@ -0,0 +1,170 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use sqlx::{PgPool, Row};
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+
+/// How often a schedule can re-crawl its site.
+pub const SCHEDULE_FREQUENCIES: &[&str] = &["daily", "weekly"];
+
+/// SQL for the time between runs of the frequency in `expr`.
+fn interval_sql(expr: &str) -> String {
+    format!("CASE {expr} WHEN 'daily' THEN INTERVAL '1 day' ELSE INTERVAL '7 days' END")
+}
+
+/// A site re-crawled on a fixed frequency, keeping its pages fresh.
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CrawlSchedule {
+    pub id: String,
+    /// Whose embedding settings and keys the runs use.
+    pub user_id: String,
+    pub url: String,
+    pub crawl_type: String,
+    /// `daily` or `weekly`.
+    pub frequency: String,
+    #[serde(with = "crate::db::timestamp")]
+    pub next_run_at: DateTime<Utc>,
+    /// The run whose pages are currently indexed; the next successful run replaces them.
+    pub last_job_id: Option<String>,
+    #[serde(with = "crate::db::timestamp")]
+    pub created_at: DateTime<Utc>,
+    #[serde(with = "crate::db::timestamp")]
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Clone)]
+pub struct CrawlScheduleRepository {
+    pool: PgPool,
+}
+
+const SELECT_COLS: &str = "id, user_id, url, crawl_type, frequency, last_job_id,
+     next_run_at,
+     created_at,
+     updated_at";
+
+fn map_row(row: &sqlx::postgres::PgRow) -> CrawlSchedule {
+    CrawlSchedule {
+        id: row.get("id"),
+        user_id: row.get("user_id"),
+        url: row.get("url"),
+        crawl_type: row.get("crawl_type"),
+        frequency: row.get("frequency"),
+        next_run_at: row.get("next_run_at"),
+        last_job_id: row.get("last_job_id"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+    }
+}
+
+impl CrawlScheduleRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Schedule re-crawls of the site `last_job_id` crawled. A site has at most
+    /// one schedule per crawl type, so scheduling it again changes the frequency;
+    /// the next run is one interval from now.
+    pub async fn upsert(
+        &self,
+        user_id: &str,
+        url: &str,
+        crawl_type: &str,
+        frequency: &str,
+        last_job_id: &str,
+    ) -> Result<CrawlSchedule> {
+        let row = sqlx::query(&format!(
+            "INSERT INTO crawl_schedules (id, user_id, url, crawl_type, frequency, next_run_at, last_job_id)
+             VALUES ($1, $2, $3, $4, $5::TEXT, NOW() + {}, $6)
+             ON CONFLICT (url, crawl_type) DO UPDATE SET
+                 user_id = EXCLUDED.user_id,
+                 frequency = EXCLUDED.frequency,
+                 next_run_at = EXCLUDED.next_run_at,
+                 last_job_id = EXCLUDED.last_job_id,
+                 updated_at = NOW()
+             RETURNING {SELECT_COLS}",
+            interval_sql("$5::TEXT")
+        ))
+        .bind(Uuid::new_v4().to_string())
+        .bind(user_id)
+        .bind(url)
+        .bind(crawl_type)
+        .bind(frequency)
+        .bind(last_job_id)
+        .fetch_one(&self.pool)
+        .await
+        .context("Failed to save crawl schedule")?;
+
+        Ok(map_row(&row))
+    }
+
+    /// Stop re-crawling a site. Returns false if it had no schedule.
+    pub async fn delete_for(&self, url: &str, crawl_type: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM crawl_schedules WHERE url = $1 AND crawl_type = $2")
+            .bind(url)
+            .bind(crawl_type)
+            .execute(&self.pool)
+            .await
+            .context("Failed to delete crawl schedule")?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn find_by_id(&self, id: &str) -> Result<Option<CrawlSchedule>> {
+        let row = sqlx::query(&format!("SELECT {SELECT_COLS} FROM crawl_schedules WHERE id = $1"))
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+            .context("Failed to query crawl schedule")?;
+
+        Ok(row.as_ref().map(map_row))
+    }
+
+    /// All schedules, soonest run first.
+    pub async fn list_upcoming(&self) -> Result<Vec<CrawlSchedule>> {
+        let rows = sqlx::query(&format!(
+            "SELECT {SELECT_COLS} FROM crawl_schedules ORDER BY next_run_at, url"
+        ))
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to list crawl schedules")?;
+
+        Ok(rows.iter().map(map_row).collect())
+    }
+
+    /// Claim the schedules that are due and move their next run one interval
+    /// ahead. A schedule whose site still has a pending or running crawl is left
+    /// due until that crawl finishes, so runs of the same site never overlap.
+    pub async fn claim_due(&self) -> Result<Vec<CrawlSchedule>> {
+        let rows = sqlx::query(&format!(
+            "UPDATE crawl_schedules SET next_run_at = NOW() + {}, updated_at = NOW()
+             WHERE id IN (
+                 SELECT s.id FROM crawl_schedules s
+                 WHERE s.next_run_at <= NOW()
+                   AND NOT EXISTS (
+                       SELECT 1 FROM crawl_jobs j
+                       WHERE j.url = s.url AND j.status IN ('pending', 'running')
+                   )
+                 FOR UPDATE SKIP LOCKED
+             )
+             RETURNING {SELECT_COLS}",
+            interval_sql("frequency")
+        ))
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to claim due crawl schedules")?;
+
+        Ok(rows.iter().map(map_row).collect())
+    }
+
+    /// Record the run whose pages are now indexed for this schedule.
+    pub async fn set_last_job(&self, id: &str, job_id: &str) -> Result<()> {
+        sqlx::query("UPDATE crawl_schedules SET last_job_id = $1, updated_at = NOW() WHERE id = $2")
+            .bind(job_id)
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to update crawl schedule")?;
+        Ok(())
+    }
+}
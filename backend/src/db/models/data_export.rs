@@ -0,0 +1,216 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Row};
+use chrono::{DateTime, Utc};
+
+/// How an export's categories are packaged.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    /// One JSON document with a key per category.
+    #[default]
+    Json,
+    /// A zip archive with one JSON file per category.
+    Zip,
+}
+
+impl ExportFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Json => "application/json",
+            ExportFormat::Zip => "application/zip",
+        }
+    }
+}
+
+impl std::fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExportFormat::Json => write!(f, "json"),
+            ExportFormat::Zip => write!(f, "zip"),
+        }
+    }
+}
+
+impl TryFrom<&str> for ExportFormat {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> Result<Self> {
+        match value {
+            "json" => Ok(ExportFormat::Json),
+            "zip" => Ok(ExportFormat::Zip),
+            other => Err(anyhow::anyhow!("Invalid export format: {other}")),
+        }
+    }
+}
+
+/// A user's request for a copy of their data. The file is built by a
+/// background job and kept in object storage until `expires_at`.
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DataExport {
+    pub id: String,
+    pub user_id: String,
+    pub format: ExportFormat,
+    /// `pending`, `ready` or `failed`.
+    pub status: String,
+    #[serde(skip)]
+    pub storage_key: Option<String>,
+    pub size_bytes: Option<i64>,
+    pub error_message: Option<String>,
+    /// When the file is deleted; set once the export is ready.
+    #[serde(with = "crate::db::timestamp::option")]
+    pub expires_at: Option<DateTime<Utc>>,
+    #[serde(with = "crate::db::timestamp")]
+    pub created_at: DateTime<Utc>,
+    #[serde(with = "crate::db::timestamp::option")]
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+impl DataExport {
+    /// Ready and not yet expired. The sweeper deletes the file some time after
+    /// `expires_at`, so the row alone isn't enough.
+    pub fn is_downloadable(&self, now: chrono::DateTime<chrono::Utc>) -> bool {
+        self.status == "ready"
+            && self.storage_key.is_some()
+            && self.expires_at.is_some_and(|e| e > now)
+    }
+}
+
+const SELECT_COLS: &str = "id, user_id, format, status, storage_key, size_bytes, error_message,
+     expires_at,
+     created_at,
+     completed_at";
+
+fn map_row(row: &sqlx::postgres::PgRow) -> DataExport {
+    DataExport {
+        id: row.get("id"),
+        user_id: row.get("user_id"),
+        format: ExportFormat::try_from(row.get::<&str, _>("format")).unwrap_or_default(),
+        status: row.get("status"),
+        storage_key: row.get("storage_key"),
+        size_bytes: row.get("size_bytes"),
+        error_message: row.get("error_message"),
+        expires_at: row.get("expires_at"),
+        created_at: row.get("created_at"),
+        completed_at: row.get("completed_at"),
+    }
+}
+
+#[derive(Clone)]
+pub struct DataExportRepository {
+    pool: PgPool,
+}
+
+impl DataExportRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn create(&self, user_id: &str, format: ExportFormat) -> Result<DataExport> {
+        let row = sqlx::query(&format!(
+            "INSERT INTO data_exports (id, user_id, format)
+             VALUES ($1, $2, $3)
+             RETURNING {SELECT_COLS}"
+        ))
+        .bind(uuid::Uuid::new_v4().to_string())
+        .bind(user_id)
+        .bind(format.to_string())
+        .fetch_one(&self.pool)
+        .await
+        .context("Failed to create data export")?;
+
+        Ok(map_row(&row))
+    }
+
+    pub async fn find_by_id(&self, id: &str) -> Result<Option<DataExport>> {
+        let row = sqlx::query(&format!("SELECT {SELECT_COLS} FROM data_exports WHERE id = $1"))
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+            .context("Failed to get data export")?;
+
+        Ok(row.as_ref().map(map_row))
+    }
+
+    /// The user's export still being built, if any.
+    pub async fn find_pending(&self, user_id: &str) -> Result<Option<DataExport>> {
+        let row = sqlx::query(&format!(
+            "SELECT {SELECT_COLS} FROM data_exports
+             WHERE user_id = $1 AND status = 'pending'
+             ORDER BY data_exports.created_at DESC
+             LIMIT 1"
+        ))
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to find pending data export")?;
+
+        Ok(row.as_ref().map(map_row))
+    }
+
+    pub async fn mark_ready(
+        &self,
+        id: &str,
+        storage_key: &str,
+        size_bytes: i64,
+        expiry_hours: u32,
+    ) -> Result<Option<DataExport>> {
+        let row = sqlx::query(&format!(
+            "UPDATE data_exports
+             SET status = 'ready', storage_key = $2, size_bytes = $3, error_message = NULL,
+                 completed_at = NOW(), expires_at = NOW() + make_interval(hours => $4)
+             WHERE id = $1
+             RETURNING {SELECT_COLS}"
+        ))
+        .bind(id)
+        .bind(storage_key)
+        .bind(size_bytes)
+        .bind(expiry_hours as i32)
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to mark data export ready")?;
+
+        Ok(row.as_ref().map(map_row))
+    }
+
+    pub async fn mark_failed(&self, id: &str, error: &str) -> Result<()> {
+        sqlx::query(
+            "UPDATE data_exports SET status = 'failed', error_message = $2, completed_at = NOW()
+             WHERE id = $1",
+        )
+        .bind(id)
+        .bind(error)
+        .execute(&self.pool)
+        .await
+        .context("Failed to mark data export failed")?;
+        Ok(())
+    }
+
+    /// Exports past their expiry, oldest first, with their files still to delete.
+    pub async fn list_expired(&self, limit: i64) -> Result<Vec<DataExport>> {
+        let rows = sqlx::query(&format!(
+            "SELECT {SELECT_COLS} FROM data_exports
+             WHERE expires_at <= NOW()
+                OR (status = 'failed' AND completed_at <= NOW() - INTERVAL '7 days')
+             ORDER BY data_exports.created_at
+             LIMIT $1"
+        ))
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to list expired data exports")?;
+
+        Ok(rows.iter().map(map_row).collect())
+    }
+
+    pub async fn delete(&self, id: &str) -> Result<()> {
+        sqlx::query("DELETE FROM data_exports WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to delete data export")?;
+        Ok(())
+    }
+}
@@ -2,6 +2,7 @@ use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use sqlx::{PgPool, Row};
 use uuid::Uuid;
+use chrono::{DateTime, Utc};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Document {
@@ -14,8 +15,22 @@ pub struct Document {
     pub size_bytes: i64,
     pub status: DocumentStatus,
     pub error_message: Option<String>,
-    pub created_at: String,
-    pub processed_at: Option<String>,
+    #[serde(with = "crate::db::timestamp")]
+    pub created_at: DateTime<Utc>,
+    #[serde(with = "crate::db::timestamp::option")]
+    pub processed_at: Option<DateTime<Utc>>,
+    pub embedding_model: Option<String>,
+    pub vector_collection: Option<String>,
+    /// Lowercase labels used to organize documents and narrow retrieval.
+    pub tags: Vec<String>,
+    /// The collection the document is filed in, if any.
+    pub collection_id: Option<String>,
+    /// The document this one is a newer upload of, by the same user under the
+    /// same filename.
+    pub previous_version_id: Option<String>,
+    /// Hash of the extracted text and the chunking parameters as of the last
+    /// successful processing; a rescan skips the document while it matches.
+    pub extraction_fingerprint: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -26,6 +41,9 @@ pub enum DocumentStatus {
     Processing,
     Ready,
     Failed,
+    /// Replaced by a newer version that finished processing. Its chunks are
+    /// deleted and its status never changes again.
+    Superseded,
 }
 
 impl std::fmt::Display for DocumentStatus {
@@ -35,6 +53,7 @@ impl std::fmt::Display for DocumentStatus {
             DocumentStatus::Processing => write!(f, "processing"),
             DocumentStatus::Ready => write!(f, "ready"),
             DocumentStatus::Failed => write!(f, "failed"),
+            DocumentStatus::Superseded => write!(f, "superseded"),
         }
     }
 }
@@ -47,11 +66,28 @@ impl TryFrom<&str> for DocumentStatus {
             "processing" => Ok(DocumentStatus::Processing),
             "ready" => Ok(DocumentStatus::Ready),
             "failed" => Ok(DocumentStatus::Failed),
+            "superseded" => Ok(DocumentStatus::Superseded),
             other => Err(anyhow::anyhow!("Invalid document status: {other}")),
         }
     }
 }
 
+const SELECT_COLS: &str = "id, user_id, filename, original_filename, minio_key, content_type,
+     size_bytes, status, error_message,
+     created_at,
+     processed_at,
+     embedding_model, vector_collection, tags, collection_id, previous_version_id,
+     extraction_fingerprint";
+
+/// Walks from a document (`$1`) back through its previous versions, nearest
+/// first by `depth`.
+const PREVIOUS_VERSIONS: &str = "WITH RECURSIVE chain(id, depth) AS (
+         SELECT previous_version_id, 1 FROM documents WHERE id = $1 AND previous_version_id IS NOT NULL
+         UNION ALL
+         SELECT d.previous_version_id, c.depth + 1 FROM documents d JOIN chain c ON d.id = c.id
+         WHERE d.previous_version_id IS NOT NULL AND c.depth < 1000
+     )";
+
 #[derive(Clone)]
 pub struct DocumentRepository {
     pool: PgPool,
@@ -101,41 +137,83 @@ impl DocumentRepository {
             size_bytes,
             status: DocumentStatus::Uploading,
             error_message: None,
-            created_at: now.to_rfc3339(),
+            created_at: now,
             processed_at: None,
+            embedding_model: None,
+            vector_collection: None,
+            tags: Vec::new(),
+            collection_id: None,
+            previous_version_id: None,
+            extraction_fingerprint: None,
         })
     }
 
     pub async fn find_by_id(&self, id: &str) -> Result<Option<Document>> {
-        let row = sqlx::query(
-            "SELECT id, user_id, filename, original_filename, minio_key, content_type,
-                    size_bytes, status, error_message,
-                    to_char(created_at, 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') AS created_at,
-                    to_char(processed_at, 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') AS processed_at
-             FROM documents WHERE id = $1",
-        )
-        .bind(id)
-        .fetch_optional(&self.pool)
-        .await
-        .context("Failed to query document")?;
+        let row = sqlx::query(&format!("SELECT {SELECT_COLS} FROM documents WHERE id = $1"))
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+            .context("Failed to query document")?;
 
         row.map(|r| Self::map_row(&r)).transpose()
     }
 
-    pub async fn find_by_user(&self, user_id: &str) -> Result<Vec<Document>> {
-        let rows = sqlx::query(
-            "SELECT id, user_id, filename, original_filename, minio_key, content_type,
-                    size_bytes, status, error_message,
-                    to_char(created_at, 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') AS created_at,
-                    to_char(processed_at, 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') AS processed_at
-             FROM documents WHERE user_id = $1 ORDER BY created_at DESC",
-        )
+    /// The documents among `ids` that exist, in no particular order.
+    pub async fn find_by_ids(&self, ids: &[String]) -> Result<Vec<Document>> {
+        let rows = sqlx::query(&format!("SELECT {SELECT_COLS} FROM documents WHERE id = ANY($1)"))
+            .bind(ids)
+            .fetch_all(&self.pool)
+            .await
+            .context("Failed to query documents")?;
+
+        rows.iter().map(Self::map_row).collect()
+    }
+
+    /// A user's documents, newest first, optionally only those carrying `tag`.
+    /// Superseded versions are left out.
+    pub async fn find_by_user(&self, user_id: &str, tag: Option<&str>) -> Result<Vec<Document>> {
+        let rows = sqlx::query(&format!(
+            "SELECT {SELECT_COLS} FROM documents
+             WHERE user_id = $1 AND status <> 'superseded' AND ($2::TEXT IS NULL OR $2 = ANY(tags))
+             ORDER BY created_at DESC"
+        ))
         .bind(user_id)
+        .bind(tag)
         .fetch_all(&self.pool)
         .await
         .context("Failed to list documents")?;
 
-        rows.iter().map(|r| Self::map_row(r)).collect()
+        rows.iter().map(Self::map_row).collect()
+    }
+
+    pub async fn update_size(&self, id: &str, size_bytes: i64) -> Result<()> {
+        sqlx::query("UPDATE documents SET size_bytes = $1 WHERE id = $2")
+            .bind(size_bytes)
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to update document size")?;
+        Ok(())
+    }
+
+    pub async fn update_tags(&self, id: &str, tags: &[String]) -> Result<()> {
+        sqlx::query("UPDATE documents SET tags = $1 WHERE id = $2")
+            .bind(tags)
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to update document tags")?;
+        Ok(())
+    }
+
+    pub async fn update_collection(&self, id: &str, collection_id: Option<&str>) -> Result<()> {
+        sqlx::query("UPDATE documents SET collection_id = $1 WHERE id = $2")
+            .bind(collection_id)
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to update document collection")?;
+        Ok(())
     }
 
     pub async fn update_minio_key(&self, id: &str, minio_key: &str) -> Result<()> {
@@ -148,6 +226,35 @@ impl DocumentRepository {
         Ok(())
     }
 
+    /// Record which embedding model and Qdrant collection hold this document's vectors.
+    pub async fn update_embedding(
+        &self,
+        id: &str,
+        embedding_model: &str,
+        vector_collection: &str,
+    ) -> Result<()> {
+        sqlx::query("UPDATE documents SET embedding_model = $1, vector_collection = $2 WHERE id = $3")
+            .bind(embedding_model)
+            .bind(vector_collection)
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to update document embedding")?;
+        Ok(())
+    }
+
+    /// Record the fingerprint of the text the document's chunks were made from.
+    pub async fn set_extraction_fingerprint(&self, id: &str, fingerprint: &str) -> Result<()> {
+        sqlx::query("UPDATE documents SET extraction_fingerprint = $1 WHERE id = $2")
+            .bind(fingerprint)
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to set document extraction fingerprint")?;
+        Ok(())
+    }
+
+    /// Set the status, unless the document is superseded, which is final.
     pub async fn update_status(
         &self,
         id: &str,
@@ -163,7 +270,8 @@ impl DocumentRepository {
             };
 
         sqlx::query(
-            "UPDATE documents SET status = $1, error_message = $2, processed_at = $3 WHERE id = $4",
+            "UPDATE documents SET status = $1, error_message = $2, processed_at = $3
+             WHERE id = $4 AND status <> 'superseded'",
         )
         .bind(status.to_string())
         .bind(error_message)
@@ -176,19 +284,120 @@ impl DocumentRepository {
         Ok(())
     }
 
-    pub async fn find_all_ready(&self) -> Result<Vec<Document>> {
-        let rows = sqlx::query(
-            "SELECT id, user_id, filename, original_filename, minio_key, content_type,
-                    size_bytes, status, error_message,
-                    to_char(created_at, 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') AS created_at,
-                    to_char(processed_at, 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') AS processed_at
-             FROM documents WHERE status = 'ready' ORDER BY created_at DESC",
+    /// Mark a processed document ready. Returns false if a newer version
+    /// superseded it meanwhile, leaving it superseded.
+    pub async fn mark_ready(&self, id: &str) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE documents SET status = 'ready', error_message = NULL, processed_at = NOW()
+             WHERE id = $1 AND status <> 'superseded'",
         )
+        .bind(id)
+        .execute(&self.pool)
+        .await
+        .context("Failed to mark document ready")?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// The latest document `user_id` uploaded as `original_filename` that
+    /// isn't superseded, other than `exclude_id`: the one a new upload under
+    /// that name is the next version of.
+    pub async fn find_latest_version(
+        &self,
+        user_id: &str,
+        original_filename: &str,
+        exclude_id: &str,
+    ) -> Result<Option<Document>> {
+        let row = sqlx::query(&format!(
+            "SELECT {SELECT_COLS} FROM documents
+             WHERE user_id = $1 AND original_filename = $2 AND id <> $3 AND status <> 'superseded'
+             ORDER BY created_at DESC
+             LIMIT 1"
+        ))
+        .bind(user_id)
+        .bind(original_filename)
+        .bind(exclude_id)
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to query latest document version")?;
+
+        row.map(|r| Self::map_row(&r)).transpose()
+    }
+
+    pub async fn set_previous_version(&self, id: &str, previous_version_id: &str) -> Result<()> {
+        sqlx::query("UPDATE documents SET previous_version_id = $1 WHERE id = $2")
+            .bind(previous_version_id)
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to set previous document version")?;
+        Ok(())
+    }
+
+    /// The earlier versions of a document, newest first.
+    pub async fn find_previous_versions(&self, id: &str) -> Result<Vec<Document>> {
+        let rows = sqlx::query(&format!(
+            "{PREVIOUS_VERSIONS}
+             SELECT {SELECT_COLS} FROM documents JOIN chain USING (id) ORDER BY chain.depth"
+        ))
+        .bind(id)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to query previous document versions")?;
+
+        rows.iter().map(Self::map_row).collect()
+    }
+
+    /// Mark every earlier version of a document superseded. Returns those that
+    /// weren't already, whose chunks are now to be deleted.
+    pub async fn supersede_previous_versions(&self, id: &str) -> Result<Vec<Document>> {
+        let rows = sqlx::query(&format!(
+            "{PREVIOUS_VERSIONS}
+             UPDATE documents SET status = 'superseded', error_message = NULL
+             WHERE id IN (SELECT id FROM chain) AND status <> 'superseded'
+             RETURNING {SELECT_COLS}"
+        ))
+        .bind(id)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to supersede previous document versions")?;
+
+        rows.iter().map(Self::map_row).collect()
+    }
+
+    /// Mark documents left in `processing` by a process that died as failed:
+    /// those with no queued job and no running job or rescan whose lease is
+    /// still being renewed by another process. Returns how many were updated.
+    pub async fn fail_interrupted(&self, error_message: &str) -> Result<u64> {
+        let result = sqlx::query(
+            "UPDATE documents SET status = 'failed', error_message = $1, processed_at = NOW()
+             WHERE status = 'processing'
+               AND NOT EXISTS (
+                   SELECT 1 FROM jobs
+                   WHERE (jobs.status = 'queued' OR (jobs.status = 'running' AND jobs.locked_until >= NOW()))
+                     AND jobs.payload->>'document_id' = documents.id
+               )
+               AND NOT EXISTS (
+                   SELECT 1 FROM rescan_runs WHERE status = 'running' AND locked_until >= NOW()
+               )",
+        )
+        .bind(error_message)
+        .execute(&self.pool)
+        .await
+        .context("Failed to fail interrupted documents")?;
+
+        Ok(result.rows_affected())
+    }
+
+    pub async fn find_all_ready(&self) -> Result<Vec<Document>> {
+        let rows = sqlx::query(&format!(
+            "SELECT {SELECT_COLS} FROM documents WHERE status = 'ready' ORDER BY created_at DESC"
+        ))
         .fetch_all(&self.pool)
         .await
         .context("Failed to list ready documents")?;
 
-        rows.iter().map(|r| Self::map_row(r)).collect()
+        rows.iter().map(Self::map_row).collect()
     }
 
     pub async fn delete(&self, id: &str) -> Result<()> {
@@ -223,6 +432,22 @@ impl DocumentRepository {
             processed_at: row
                 .try_get("processed_at")
                 .context("Failed to get processed_at")?,
+            embedding_model: row
+                .try_get("embedding_model")
+                .context("Failed to get embedding_model")?,
+            vector_collection: row
+                .try_get("vector_collection")
+                .context("Failed to get vector_collection")?,
+            tags: row.try_get("tags").context("Failed to get tags")?,
+            collection_id: row
+                .try_get("collection_id")
+                .context("Failed to get collection_id")?,
+            previous_version_id: row
+                .try_get("previous_version_id")
+                .context("Failed to get previous_version_id")?,
+            extraction_fingerprint: row
+                .try_get("extraction_fingerprint")
+                .context("Failed to get extraction_fingerprint")?,
         })
     }
 }
@@ -2,6 +2,7 @@ use anyhow::{Context, Result};
 use serde::Serialize;
 use sqlx::{PgPool, Row};
 use uuid::Uuid;
+use chrono::{DateTime, Utc};
 
 #[derive(Debug, Clone, Serialize)]
 pub struct DocumentChunk {
@@ -11,7 +12,23 @@ pub struct DocumentChunk {
     pub chunk_index: i32,
     pub content: String,
     pub qdrant_point_id: String,
-    pub created_at: String,
+    #[serde(with = "crate::db::timestamp")]
+    pub created_at: DateTime<Utc>,
+}
+
+const SELECT_COLS: &str = "id, source_type, source_id, chunk_index, content, qdrant_point_id,
+    created_at AS created_at_fmt";
+
+fn map_row(row: &sqlx::postgres::PgRow) -> DocumentChunk {
+    DocumentChunk {
+        id: row.get("id"),
+        source_type: row.get("source_type"),
+        source_id: row.get("source_id"),
+        chunk_index: row.get("chunk_index"),
+        content: row.get("content"),
+        qdrant_point_id: row.get("qdrant_point_id"),
+        created_at: row.get("created_at_fmt"),
+    }
 }
 
 #[derive(Clone)]
@@ -24,10 +41,12 @@ impl DocumentChunkRepository {
         Self { pool }
     }
 
+    /// Insert a batch of chunk rows in one transaction, so a failure leaves none of them behind.
     pub async fn create_batch(
         &self,
         chunks: &[(String, String, i32, String, String)], // (source_type, source_id, chunk_index, content, qdrant_point_id)
     ) -> Result<()> {
+        let mut tx = self.pool.begin().await.context("Failed to start chunk transaction")?;
         for (source_type, source_id, chunk_index, content, qdrant_point_id) in chunks {
             let id = Uuid::new_v4().to_string();
             sqlx::query(
@@ -40,41 +59,106 @@ impl DocumentChunkRepository {
             .bind(chunk_index)
             .bind(content)
             .bind(qdrant_point_id)
-            .execute(&self.pool)
+            .execute(&mut *tx)
             .await
             .context("Failed to insert document chunk")?;
         }
+        tx.commit().await.context("Failed to commit document chunks")?;
 
         Ok(())
     }
 
-    pub async fn find_by_source(&self, source_type: &str, source_id: &str) -> Result<Vec<DocumentChunk>> {
-        let rows = sqlx::query(
-            "SELECT id, source_type, source_id, chunk_index, content, qdrant_point_id,
-                    to_char(created_at, 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') AS created_at
-             FROM document_chunks WHERE source_type = $1 AND source_id = $2
-             ORDER BY chunk_index ASC",
+    /// Number of leading chunks already stored for a source, i.e. where an
+    /// interrupted embedding run can resume. Returns 0 unless the stored chunks
+    /// are exactly `0..n`, since anything else can't be safely continued.
+    pub async fn resumable_prefix(&self, source_type: &str, source_id: &str) -> Result<usize> {
+        let row = sqlx::query(
+            "SELECT COUNT(*) AS count, COALESCE(MAX(chunk_index), -1) AS max_index
+             FROM document_chunks WHERE source_type = $1 AND source_id = $2",
         )
         .bind(source_type)
         .bind(source_id)
-        .fetch_all(&self.pool)
+        .fetch_one(&self.pool)
         .await
-        .context("Failed to find chunks by source")?;
-
-        let chunks = rows
-            .iter()
-            .map(|row| DocumentChunk {
-                id: row.get("id"),
-                source_type: row.get("source_type"),
-                source_id: row.get("source_id"),
-                chunk_index: row.get("chunk_index"),
-                content: row.get("content"),
-                qdrant_point_id: row.get("qdrant_point_id"),
-                created_at: row.get("created_at"),
-            })
-            .collect();
-
-        Ok(chunks)
+        .context("Failed to count stored chunks")?;
+
+        let count: i64 = row.get("count");
+        let max_index: i32 = row.get("max_index");
+        if count == i64::from(max_index) + 1 {
+            Ok(count as usize)
+        } else {
+            Ok(0)
+        }
+    }
+
+    pub async fn find_by_source(&self, source_type: &str, source_id: &str) -> Result<Vec<DocumentChunk>> {
+        let sql = format!(
+            "SELECT {SELECT_COLS} FROM document_chunks WHERE source_type = $1 AND source_id = $2
+             ORDER BY chunk_index ASC"
+        );
+        let rows = sqlx::query(&sql)
+            .bind(source_type)
+            .bind(source_id)
+            .fetch_all(&self.pool)
+            .await
+            .context("Failed to find chunks by source")?;
+
+        Ok(rows.iter().map(map_row).collect())
+    }
+
+    /// One page of a source's chunks in order, for inspecting what was stored.
+    pub async fn find_by_source_paged(
+        &self,
+        source_type: &str,
+        source_id: &str,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<DocumentChunk>> {
+        let sql = format!(
+            "SELECT {SELECT_COLS} FROM document_chunks WHERE source_type = $1 AND source_id = $2
+             ORDER BY chunk_index ASC
+             LIMIT $3 OFFSET $4"
+        );
+        let rows = sqlx::query(&sql)
+            .bind(source_type)
+            .bind(source_id)
+            .bind(limit)
+            .bind(offset)
+            .fetch_all(&self.pool)
+            .await
+            .context("Failed to page chunks by source")?;
+
+        Ok(rows.iter().map(map_row).collect())
+    }
+
+    pub async fn count_by_source(&self, source_type: &str, source_id: &str) -> Result<i64> {
+        sqlx::query_scalar("SELECT COUNT(*) FROM document_chunks WHERE source_type = $1 AND source_id = $2")
+            .bind(source_type)
+            .bind(source_id)
+            .fetch_one(&self.pool)
+            .await
+            .context("Failed to count chunks by source")
+    }
+
+    pub async fn find_by_index(
+        &self,
+        source_type: &str,
+        source_id: &str,
+        chunk_index: i32,
+    ) -> Result<Option<DocumentChunk>> {
+        let sql = format!(
+            "SELECT {SELECT_COLS} FROM document_chunks
+             WHERE source_type = $1 AND source_id = $2 AND chunk_index = $3"
+        );
+        let row = sqlx::query(&sql)
+            .bind(source_type)
+            .bind(source_id)
+            .bind(chunk_index)
+            .fetch_optional(&self.pool)
+            .await
+            .context("Failed to find chunk by index")?;
+
+        Ok(row.as_ref().map(map_row))
     }
 
     pub async fn delete_by_source(&self, source_type: &str, source_id: &str) -> Result<Vec<String>> {
@@ -96,29 +180,49 @@ impl DocumentChunkRepository {
             return Ok(Vec::new());
         }
 
-        let rows = sqlx::query(
-            "SELECT id, source_type, source_id, chunk_index, content, qdrant_point_id,
-                    to_char(created_at, 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') AS created_at
-             FROM document_chunks WHERE qdrant_point_id = ANY($1)",
-        )
-        .bind(point_ids)
-        .fetch_all(&self.pool)
-        .await
-        .context("Failed to find chunks by qdrant ids")?;
-
-        let chunks = rows
-            .iter()
-            .map(|row| DocumentChunk {
-                id: row.get("id"),
-                source_type: row.get("source_type"),
-                source_id: row.get("source_id"),
-                chunk_index: row.get("chunk_index"),
-                content: row.get("content"),
-                qdrant_point_id: row.get("qdrant_point_id"),
-                created_at: row.get("created_at"),
-            })
-            .collect();
-
-        Ok(chunks)
+        let sql = format!("SELECT {SELECT_COLS} FROM document_chunks WHERE qdrant_point_id = ANY($1)");
+        let rows = sqlx::query(&sql)
+            .bind(point_ids)
+            .fetch_all(&self.pool)
+            .await
+            .context("Failed to find chunks by qdrant ids")?;
+
+        Ok(rows.iter().map(map_row).collect())
+    }
+
+    /// Keyword search over chunk content using the `english` full-text index.
+    /// `tsquery` is passed to `websearch_to_tsquery`; results are ordered by rank.
+    /// Non-empty `tags` keeps only chunks of documents carrying any of them, and
+    /// `collection_id` only chunks of documents in that collection.
+    pub async fn search_fulltext(
+        &self,
+        tsquery: &str,
+        limit: i64,
+        tags: &[String],
+        collection_id: Option<&str>,
+    ) -> Result<Vec<DocumentChunk>> {
+        let sql = format!(
+            "SELECT {SELECT_COLS}
+             FROM document_chunks, websearch_to_tsquery('english', $1) query
+             WHERE to_tsvector('english', content) @@ query
+               AND ((cardinality($3::TEXT[]) = 0 AND $4::TEXT IS NULL) OR EXISTS (
+                   SELECT 1 FROM documents d
+                   WHERE source_type = 'document' AND d.id = source_id
+                     AND (cardinality($3::TEXT[]) = 0 OR d.tags && $3)
+                     AND ($4::TEXT IS NULL OR d.collection_id = $4)
+               ))
+             ORDER BY ts_rank_cd(to_tsvector('english', content), query) DESC
+             LIMIT $2"
+        );
+        let rows = sqlx::query(&sql)
+            .bind(tsquery)
+            .bind(limit)
+            .bind(tags)
+            .bind(collection_id)
+            .fetch_all(&self.pool)
+            .await
+            .context("Failed to run full-text chunk search")?;
+
+        Ok(rows.iter().map(map_row).collect())
     }
 }
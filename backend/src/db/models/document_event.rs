@@ -0,0 +1,106 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{PgPool, Row};
+
+/// Events kept per document; older ones are pruned as new ones are written.
+pub const EVENTS_PER_DOCUMENT: i64 = 200;
+
+/// One step of a document's processing, e.g. "Chunked into 42 chunks".
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DocumentEvent {
+    pub id: i64,
+    pub document_id: String,
+    /// `info`, `warn` or `error`.
+    pub level: String,
+    pub message: String,
+    #[serde(with = "crate::db::timestamp")]
+    pub created_at: DateTime<Utc>,
+}
+
+/// An event waiting to be written. `created_at` is when it happened, which can
+/// be a little before it reaches the database.
+#[derive(Debug, Clone)]
+pub struct NewDocumentEvent {
+    pub document_id: String,
+    pub level: &'static str,
+    pub message: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Clone)]
+pub struct DocumentEventRepository {
+    pool: PgPool,
+}
+
+impl DocumentEventRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Write several events in one transaction and prune the documents they
+    /// belong to back to [`EVENTS_PER_DOCUMENT`]. Events of documents deleted
+    /// in the meantime are skipped rather than failing the batch.
+    pub async fn create_batch(&self, events: &[NewDocumentEvent]) -> Result<()> {
+        let mut tx = self.pool.begin().await.context("Failed to start document event transaction")?;
+        for event in events {
+            sqlx::query(
+                "INSERT INTO document_events (document_id, level, message, created_at)
+                 SELECT $1, $2, $3, $4
+                 WHERE EXISTS (SELECT 1 FROM documents WHERE id = $1)",
+            )
+            .bind(&event.document_id)
+            .bind(event.level)
+            .bind(&event.message)
+            .bind(event.created_at)
+            .execute(&mut *tx)
+            .await
+            .context("Failed to insert document event")?;
+        }
+
+        let mut document_ids: Vec<&str> = events.iter().map(|e| e.document_id.as_str()).collect();
+        document_ids.sort_unstable();
+        document_ids.dedup();
+        sqlx::query(
+            "DELETE FROM document_events WHERE id IN (
+                SELECT id FROM (
+                    SELECT id, ROW_NUMBER() OVER (PARTITION BY document_id ORDER BY id DESC) AS n
+                    FROM document_events WHERE document_id = ANY($1)
+                ) ranked WHERE n > $2
+             )",
+        )
+        .bind(&document_ids)
+        .bind(EVENTS_PER_DOCUMENT)
+        .execute(&mut *tx)
+        .await
+        .context("Failed to prune document events")?;
+
+        tx.commit().await.context("Failed to commit document events")?;
+        Ok(())
+    }
+
+    /// A document's events, oldest first.
+    pub async fn list(&self, document_id: &str) -> Result<Vec<DocumentEvent>> {
+        let rows = sqlx::query(
+            "SELECT id, document_id, level, message,
+                    created_at
+             FROM document_events WHERE document_id = $1 ORDER BY id",
+        )
+        .bind(document_id)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to list document events")?;
+
+        Ok(rows
+            .iter()
+            .map(|row| DocumentEvent {
+                id: row.get("id"),
+                document_id: row.get("document_id"),
+                level: row.get("level"),
+                message: row.get("message"),
+                created_at: row.get("created_at"),
+            })
+            .collect())
+    }
+}
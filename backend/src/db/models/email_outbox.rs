@@ -0,0 +1,236 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Row};
+use std::collections::HashMap;
+use std::time::Duration;
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+
+/// Where an outbox email is in its delivery.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum EmailStatus {
+    /// Not sent yet, or waiting for a retry.
+    Pending,
+    Sent,
+    /// Ran out of attempts; `last_error` says why.
+    Failed,
+}
+
+impl std::fmt::Display for EmailStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EmailStatus::Pending => write!(f, "pending"),
+            EmailStatus::Sent => write!(f, "sent"),
+            EmailStatus::Failed => write!(f, "failed"),
+        }
+    }
+}
+
+impl TryFrom<&str> for EmailStatus {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> Result<Self> {
+        match value {
+            "pending" => Ok(EmailStatus::Pending),
+            "sent" => Ok(EmailStatus::Sent),
+            "failed" => Ok(EmailStatus::Failed),
+            other => Err(anyhow::anyhow!("Invalid email status: {other}")),
+        }
+    }
+}
+
+/// An email waiting in, or delivered through, the outbox. `template` and
+/// `payload` are what the sender renders it from.
+#[derive(Debug, Clone)]
+pub struct OutboxEmail {
+    pub id: String,
+    pub recipient: String,
+    pub template: String,
+    pub payload: serde_json::Value,
+    /// What the email is about, e.g. an invite ID, so its status can be shown there.
+    pub reference_id: Option<String>,
+    pub status: EmailStatus,
+    /// Send attempts made so far.
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub sent_at: Option<DateTime<Utc>>,
+}
+
+const SELECT_COLS: &str = "id, recipient, template, payload, reference_id, status, attempts, last_error,
+    created_at,
+    sent_at";
+
+fn map_row(r: &sqlx::postgres::PgRow) -> Result<OutboxEmail> {
+    let status: String = r.get("status");
+    Ok(OutboxEmail {
+        id: r.get("id"),
+        recipient: r.get("recipient"),
+        template: r.get("template"),
+        payload: r.get("payload"),
+        reference_id: r.get("reference_id"),
+        status: EmailStatus::try_from(status.as_str())?,
+        attempts: r.get("attempts"),
+        last_error: r.get("last_error"),
+        created_at: r.get("created_at"),
+        sent_at: r.get("sent_at"),
+    })
+}
+
+#[derive(Clone)]
+pub struct EmailOutboxRepository {
+    pool: PgPool,
+}
+
+impl EmailOutboxRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Queue an email. Its first attempt is due `delay` from now, which leaves
+    /// room for whoever queued it to send it right away.
+    pub async fn enqueue(
+        &self,
+        recipient: &str,
+        template: &str,
+        payload: &serde_json::Value,
+        reference_id: Option<&str>,
+        delay: Duration,
+    ) -> Result<OutboxEmail> {
+        let row = sqlx::query(&format!(
+            "INSERT INTO email_outbox (id, recipient, template, payload, reference_id, next_attempt_at)
+             VALUES ($1, $2, $3, $4, $5, NOW() + make_interval(secs => $6))
+             RETURNING {SELECT_COLS}"
+        ))
+        .bind(Uuid::new_v4().to_string())
+        .bind(recipient)
+        .bind(template)
+        .bind(payload)
+        .bind(reference_id)
+        .bind(delay.as_secs_f64())
+        .fetch_one(&self.pool)
+        .await
+        .context("Failed to queue email")?;
+
+        map_row(&row)
+    }
+
+    pub async fn find(&self, id: &str) -> Result<Option<OutboxEmail>> {
+        let row = sqlx::query(&format!("SELECT {SELECT_COLS} FROM email_outbox WHERE id = $1"))
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+            .context("Failed to query outbox email")?;
+
+        row.as_ref().map(map_row).transpose()
+    }
+
+    /// Pending emails whose attempt is due, oldest first. Each one's next
+    /// attempt is moved `lease` ahead, so a concurrent pass skips it while
+    /// this one sends it.
+    pub async fn claim_due(&self, limit: i64, lease: Duration) -> Result<Vec<OutboxEmail>> {
+        let rows = sqlx::query(&format!(
+            "UPDATE email_outbox SET next_attempt_at = NOW() + make_interval(secs => $2)
+             WHERE id IN (
+                 SELECT id FROM email_outbox
+                 WHERE status = 'pending' AND next_attempt_at <= NOW()
+                 ORDER BY created_at
+                 LIMIT $1
+                 FOR UPDATE SKIP LOCKED
+             )
+             RETURNING {SELECT_COLS}"
+        ))
+        .bind(limit)
+        .bind(lease.as_secs_f64())
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to claim due emails")?;
+
+        let mut emails = rows.iter().map(map_row).collect::<Result<Vec<_>>>()?;
+        emails.sort_by_key(|e| e.created_at);
+        Ok(emails)
+    }
+
+    pub async fn mark_sent(&self, id: &str) -> Result<()> {
+        sqlx::query(
+            "UPDATE email_outbox
+             SET status = 'sent', attempts = attempts + 1, last_error = NULL, sent_at = NOW()
+             WHERE id = $1",
+        )
+        .bind(id)
+        .execute(&self.pool)
+        .await
+        .context("Failed to mark email as sent")?;
+
+        Ok(())
+    }
+
+    /// Record a failed attempt and put the next one `delay` from now.
+    pub async fn retry_later(&self, id: &str, error: &str, delay: Duration) -> Result<()> {
+        sqlx::query(
+            "UPDATE email_outbox
+             SET attempts = attempts + 1, last_error = $1,
+                 next_attempt_at = NOW() + make_interval(secs => $2)
+             WHERE id = $3",
+        )
+        .bind(error)
+        .bind(delay.as_secs_f64())
+        .bind(id)
+        .execute(&self.pool)
+        .await
+        .context("Failed to reschedule email")?;
+
+        Ok(())
+    }
+
+    /// Record the last failed attempt; the email isn't tried again.
+    pub async fn mark_failed(&self, id: &str, error: &str) -> Result<()> {
+        sqlx::query(
+            "UPDATE email_outbox SET status = 'failed', attempts = attempts + 1, last_error = $1
+             WHERE id = $2",
+        )
+        .bind(error)
+        .bind(id)
+        .execute(&self.pool)
+        .await
+        .context("Failed to mark email as failed")?;
+
+        Ok(())
+    }
+
+    /// Status of the latest email about each of `reference_ids` that has one.
+    pub async fn latest_statuses(&self, reference_ids: &[String]) -> Result<HashMap<String, EmailStatus>> {
+        let rows = sqlx::query(
+            "SELECT DISTINCT ON (reference_id) reference_id, status FROM email_outbox
+             WHERE reference_id = ANY($1)
+             ORDER BY reference_id, created_at DESC",
+        )
+        .bind(reference_ids)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to query email statuses")?;
+
+        rows.iter()
+            .map(|r| {
+                let status: String = r.get("status");
+                Ok((r.get("reference_id"), EmailStatus::try_from(status.as_str())?))
+            })
+            .collect()
+    }
+
+    /// Delete sent emails older than `days`. Failed ones stay for inspection.
+    pub async fn purge_sent(&self, days: u32) -> Result<u64> {
+        let result = sqlx::query(
+            "DELETE FROM email_outbox
+             WHERE status = 'sent' AND sent_at < NOW() - make_interval(days => $1)",
+        )
+        .bind(days as i32)
+        .execute(&self.pool)
+        .await
+        .context("Failed to purge sent emails")?;
+
+        Ok(result.rows_affected())
+    }
+}
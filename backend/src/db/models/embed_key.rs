@@ -1,6 +1,10 @@
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use sqlx::{PgPool, Row};
+use std::collections::BTreeMap;
+
+use crate::services::widget_avatar;
+use chrono::{DateTime, Utc};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
@@ -13,19 +17,173 @@ pub struct EmbedKey {
     pub allowed_domains: Vec<String>,
     pub system_prompt: String,
     pub rate_limit: i32,
+    /// Messages all sessions together may send per UTC day and calendar month;
+    /// `None` is unlimited.
+    pub daily_message_limit: Option<i32>,
+    pub monthly_message_limit: Option<i32>,
     pub widget_title: String,
+    /// Shorthand for the theme's light primary color, kept in sync with it.
     pub primary_color: String,
+    pub theme: WidgetTheme,
     pub greeting_message: String,
     pub provider: String,
     pub model: String,
     #[serde(skip_serializing)]
     pub api_key_encrypted: String,
+    pub base_url: Option<String>,
+    /// Widget conversations and their messages, matching the widget logs.
     pub total_conversations: i64,
     pub total_messages: i64,
     pub custom_css: String,
+    /// Per-key overrides of the global RAG retrieval settings; `None` uses the config value.
+    pub rag_top_k: Option<i32>,
+    pub rag_min_score: Option<f32>,
+    pub rag_max_context_chars: Option<i32>,
+    /// Per-locale overrides keyed by normalized locale code (`de`, `pt-br`).
+    pub localizations: BTreeMap<String, WidgetLocalization>,
+    /// Let visitors leave their email for a person to follow up.
+    pub handoff_enabled: bool,
+    /// Where new handoffs are emailed; `None` only lists them in the admin panel.
+    pub handoff_notification_email: Option<String>,
+    /// Let visitors email themselves a transcript of their conversation.
+    pub transcript_email_enabled: bool,
+    /// When the widget is staffed; `None` is always online.
+    pub office_hours: Option<OfficeHours>,
+    /// Shown outside office hours; empty uses the widget's default.
+    pub offline_message: String,
+    pub offline_behavior: OfflineBehavior,
+    /// Retrieval is limited to this collection's documents when set.
+    pub collection_id: Option<String>,
+    /// Storage key of the uploaded bot avatar.
+    #[serde(skip_serializing)]
+    pub avatar_key: Option<String>,
+    /// Public URL of the avatar, relative to the API server.
+    pub avatar_url: Option<String>,
     pub is_active: bool,
-    pub created_at: String,
-    pub updated_at: String,
+    #[serde(with = "crate::db::timestamp")]
+    pub created_at: DateTime<Utc>,
+    #[serde(with = "crate::db::timestamp")]
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Texts shown to or sent for visitors of one locale. Unset fields fall back to
+/// the embed key's default.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct WidgetLocalization {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub widget_title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub greeting_message: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_prompt: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offline_message: Option<String>,
+}
+
+/// Weekly opening times of a widget, checked with
+/// [`crate::services::office_hours::is_open`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(deny_unknown_fields)]
+pub struct OfficeHours {
+    /// IANA time zone the times are in, e.g. `Europe/Berlin`.
+    pub timezone: String,
+    /// Opening ranges keyed by `mon` to `sun`; a missing day is closed.
+    #[serde(default)]
+    pub days: BTreeMap<String, Vec<OpeningRange>>,
+}
+
+/// `HH:MM` local times. An end at or before the start runs past midnight into
+/// the next day; `24:00` ends at midnight.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(deny_unknown_fields)]
+pub struct OpeningRange {
+    pub start: String,
+    pub end: String,
+}
+
+/// What the widget does outside office hours.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum OfflineBehavior {
+    /// Show the offline message but keep answering.
+    #[default]
+    Notice,
+    /// Refuse new messages; visitors can still leave their email.
+    Block,
+}
+
+impl OfflineBehavior {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            OfflineBehavior::Notice => "notice",
+            OfflineBehavior::Block => "block",
+        }
+    }
+}
+
+impl TryFrom<&str> for OfflineBehavior {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> Result<Self> {
+        match value {
+            "notice" => Ok(OfflineBehavior::Notice),
+            "block" => Ok(OfflineBehavior::Block),
+            other => Err(anyhow::anyhow!("Invalid offline behavior: {other}")),
+        }
+    }
+}
+
+/// Structured widget styling. Unset values use the widget's defaults, see
+/// [`crate::services::widget_theme::resolve`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(deny_unknown_fields)]
+pub struct WidgetTheme {
+    #[serde(default)]
+    pub light: WidgetThemeColors,
+    /// Colors used instead when the visitor's system prefers a dark scheme.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dark: Option<WidgetThemeColors>,
+    /// Corner radius of the panel and message bubbles, in pixels.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub border_radius: Option<u32>,
+    /// A CSS font family list such as `Inter, sans-serif`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub font_family: Option<String>,
+}
+
+/// Colors of one scheme of a [`WidgetTheme`], as hex, `rgb()` or `hsl()` colors.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(deny_unknown_fields)]
+pub struct WidgetThemeColors {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub primary: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub background: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    /// Defaults to the primary color.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_bubble: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_text: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub assistant_bubble: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub assistant_text: Option<String>,
+}
+
+/// Messages a key's widget sessions sent in the current UTC day and month.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct EmbedKeyUsage {
+    pub messages_today: i64,
+    pub messages_this_month: i64,
 }
 
 #[derive(Debug, Deserialize)]
@@ -35,38 +193,106 @@ pub struct UpdateEmbedKeyRequest {
     pub allowed_domains: Option<Vec<String>>,
     pub system_prompt: Option<String>,
     pub rate_limit: Option<i32>,
+    /// `null` removes the cap.
+    #[serde(default, deserialize_with = "super::double_option")]
+    pub daily_message_limit: Option<Option<i32>>,
+    #[serde(default, deserialize_with = "super::double_option")]
+    pub monthly_message_limit: Option<Option<i32>>,
     pub widget_title: Option<String>,
     pub primary_color: Option<String>,
+    /// Replaces the whole theme.
+    pub theme: Option<WidgetTheme>,
     pub greeting_message: Option<String>,
     pub provider: Option<String>,
     pub model: Option<String>,
     pub api_key: Option<String>,
+    #[serde(default, deserialize_with = "super::double_option")]
+    pub base_url: Option<Option<String>>,
     pub custom_css: Option<String>,
+    /// `null` clears the override so the global setting applies again.
+    #[serde(default, deserialize_with = "super::double_option")]
+    pub rag_top_k: Option<Option<i32>>,
+    #[serde(default, deserialize_with = "super::double_option")]
+    pub rag_min_score: Option<Option<f32>>,
+    #[serde(default, deserialize_with = "super::double_option")]
+    pub rag_max_context_chars: Option<Option<i32>>,
+    /// Replaces all localizations; locale codes must already be normalized.
+    pub localizations: Option<BTreeMap<String, WidgetLocalization>>,
+    pub handoff_enabled: Option<bool>,
+    #[serde(default, deserialize_with = "super::double_option")]
+    pub handoff_notification_email: Option<Option<String>>,
+    pub transcript_email_enabled: Option<bool>,
+    /// `null` removes the schedule so the widget is always online.
+    #[serde(default, deserialize_with = "super::double_option")]
+    pub office_hours: Option<Option<OfficeHours>>,
+    pub offline_message: Option<String>,
+    pub offline_behavior: Option<OfflineBehavior>,
+    /// `null` lets the widget search all documents again.
+    #[serde(default, deserialize_with = "super::double_option")]
+    pub collection_id: Option<Option<String>>,
 }
 
 const SELECT_COLS: &str =
     "id, name, key_hash, key_prefix, allowed_domains, system_prompt, rate_limit,
-     widget_title, primary_color, greeting_message, provider, model, api_key_encrypted,
-     custom_css, total_conversations, total_messages, is_active,
-     to_char(created_at, 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') as created_at_fmt,
-     to_char(updated_at, 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') as updated_at_fmt";
+     daily_message_limit, monthly_message_limit, widget_title, primary_color, theme, greeting_message,
+     provider, model, api_key_encrypted, base_url, custom_css, rag_top_k, rag_min_score, rag_max_context_chars, localizations,
+     handoff_enabled, handoff_notification_email, transcript_email_enabled, office_hours, offline_message, offline_behavior,
+     collection_id, avatar_key, is_active,
+     created_at as created_at_fmt,
+     updated_at as updated_at_fmt";
+
+/// Conversation and message counts of a key, counted the same way as the
+/// widget logs so the two always agree.
+const LIVE_STATS: &str = "(SELECT COUNT(*) FROM conversations c
+         WHERE c.source = 'widget' AND c.embed_key_id = embed_keys.id) AS total_conversations,
+     (SELECT COUNT(*) FROM messages m JOIN conversations c ON c.id = m.conversation_id
+         WHERE c.source = 'widget' AND c.embed_key_id = embed_keys.id) AS total_messages";
+
+/// The counts as of the last [`EmbedKeyRepository::refresh_stats`], for lookups
+/// on the widget's request path where counting would be wasted work.
+const CACHED_STATS: &str = "total_conversations, total_messages";
 
 fn map_row(row: &sqlx::postgres::PgRow) -> EmbedKey {
+    let key_prefix: String = row.get("key_prefix");
+    let avatar_key: Option<String> = row.get("avatar_key");
     EmbedKey {
         id: row.get("id"),
         name: row.get("name"),
         key_hash: row.get("key_hash"),
-        key_prefix: row.get("key_prefix"),
+        avatar_url: avatar_key.as_deref().map(|k| widget_avatar::url(&key_prefix, k)),
+        avatar_key,
+        key_prefix,
         allowed_domains: row.get("allowed_domains"),
         system_prompt: row.get("system_prompt"),
         rate_limit: row.get("rate_limit"),
+        daily_message_limit: row.get("daily_message_limit"),
+        monthly_message_limit: row.get("monthly_message_limit"),
         widget_title: row.get("widget_title"),
         primary_color: row.get("primary_color"),
+        theme: row.get::<sqlx::types::Json<WidgetTheme>, _>("theme").0,
         greeting_message: row.get("greeting_message"),
         provider: row.get("provider"),
         model: row.get("model"),
         api_key_encrypted: row.get("api_key_encrypted"),
+        base_url: row.get("base_url"),
         custom_css: row.get("custom_css"),
+        rag_top_k: row.get("rag_top_k"),
+        rag_min_score: row.get("rag_min_score"),
+        rag_max_context_chars: row.get("rag_max_context_chars"),
+        localizations: row
+            .get::<sqlx::types::Json<BTreeMap<String, WidgetLocalization>>, _>("localizations")
+            .0,
+        handoff_enabled: row.get("handoff_enabled"),
+        handoff_notification_email: row.get("handoff_notification_email"),
+        transcript_email_enabled: row.get("transcript_email_enabled"),
+        office_hours: row
+            .get::<Option<sqlx::types::Json<OfficeHours>>, _>("office_hours")
+            .map(|h| h.0),
+        offline_message: row.get("offline_message"),
+        // The column's CHECK constraint only admits known behaviors
+        offline_behavior: OfflineBehavior::try_from(row.get::<&str, _>("offline_behavior"))
+            .unwrap_or_default(),
+        collection_id: row.get("collection_id"),
         total_conversations: row.get("total_conversations"),
         total_messages: row.get("total_messages"),
         is_active: row.get("is_active"),
@@ -85,6 +311,7 @@ impl EmbedKeyRepository {
         Self { pool }
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub async fn create(
         &self,
         id: &str,
@@ -94,19 +321,37 @@ impl EmbedKeyRepository {
         allowed_domains: &[String],
         system_prompt: &str,
         rate_limit: i32,
+        daily_message_limit: Option<i32>,
+        monthly_message_limit: Option<i32>,
         widget_title: &str,
         primary_color: &str,
+        theme: &WidgetTheme,
         greeting_message: &str,
         provider: &str,
         model: &str,
         api_key_encrypted: &str,
+        base_url: Option<&str>,
         custom_css: &str,
+        rag_top_k: Option<i32>,
+        rag_min_score: Option<f32>,
+        rag_max_context_chars: Option<i32>,
+        localizations: &BTreeMap<String, WidgetLocalization>,
+        handoff_enabled: bool,
+        handoff_notification_email: Option<&str>,
+        transcript_email_enabled: bool,
+        office_hours: Option<&OfficeHours>,
+        offline_message: &str,
+        offline_behavior: OfflineBehavior,
     ) -> Result<EmbedKey> {
         let sql = format!(
             "INSERT INTO embed_keys (id, name, key_hash, key_prefix, allowed_domains, system_prompt, rate_limit,
-                widget_title, primary_color, greeting_message, provider, model, api_key_encrypted, custom_css)
-             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
-             RETURNING {SELECT_COLS}"
+                widget_title, primary_color, greeting_message, provider, model, api_key_encrypted, base_url,
+                custom_css, rag_top_k, rag_min_score, rag_max_context_chars, localizations,
+                handoff_enabled, handoff_notification_email, daily_message_limit, monthly_message_limit, theme,
+                transcript_email_enabled, office_hours, offline_message, offline_behavior)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19,
+                $20, $21, $22, $23, $24, $25, $26, $27, $28)
+             RETURNING {SELECT_COLS}, {LIVE_STATS}"
         );
         let row = sqlx::query(&sql)
             .bind(id)
@@ -122,7 +367,21 @@ impl EmbedKeyRepository {
             .bind(provider)
             .bind(model)
             .bind(api_key_encrypted)
+            .bind(base_url)
             .bind(custom_css)
+            .bind(rag_top_k)
+            .bind(rag_min_score)
+            .bind(rag_max_context_chars)
+            .bind(sqlx::types::Json(localizations))
+            .bind(handoff_enabled)
+            .bind(handoff_notification_email)
+            .bind(daily_message_limit)
+            .bind(monthly_message_limit)
+            .bind(sqlx::types::Json(theme))
+            .bind(transcript_email_enabled)
+            .bind(office_hours.map(sqlx::types::Json))
+            .bind(offline_message)
+            .bind(offline_behavior.as_str())
             .fetch_one(&self.pool)
             .await
             .context("Failed to create embed key")?;
@@ -130,8 +389,9 @@ impl EmbedKeyRepository {
         Ok(map_row(&row))
     }
 
+    /// Look up the key a widget request presents. Its stats are the cached counts.
     pub async fn find_by_hash(&self, key_hash: &str) -> Result<Option<EmbedKey>> {
-        let sql = format!("SELECT {SELECT_COLS} FROM embed_keys WHERE key_hash = $1");
+        let sql = format!("SELECT {SELECT_COLS}, {CACHED_STATS} FROM embed_keys WHERE key_hash = $1");
         let row = sqlx::query(&sql)
             .bind(key_hash)
             .fetch_optional(&self.pool)
@@ -142,7 +402,7 @@ impl EmbedKeyRepository {
     }
 
     pub async fn find_by_id(&self, id: &str) -> Result<Option<EmbedKey>> {
-        let sql = format!("SELECT {SELECT_COLS} FROM embed_keys WHERE id = $1");
+        let sql = format!("SELECT {SELECT_COLS}, {LIVE_STATS} FROM embed_keys WHERE id = $1");
         let row = sqlx::query(&sql)
             .bind(id)
             .fetch_optional(&self.pool)
@@ -153,7 +413,7 @@ impl EmbedKeyRepository {
     }
 
     pub async fn list_all(&self) -> Result<Vec<EmbedKey>> {
-        let sql = format!("SELECT {SELECT_COLS} FROM embed_keys ORDER BY created_at DESC");
+        let sql = format!("SELECT {SELECT_COLS}, {LIVE_STATS} FROM embed_keys ORDER BY created_at DESC");
         let rows = sqlx::query(&sql)
             .fetch_all(&self.pool)
             .await
@@ -167,8 +427,14 @@ impl EmbedKeyRepository {
         // since sqlx needs correct Rust types for PostgreSQL columns.
         enum BindVal {
             Text(String),
+            OptText(Option<String>),
             Int(i32),
+            Bool(bool),
+            OptInt(Option<i32>),
+            OptFloat(Option<f32>),
             TextArray(Vec<String>),
+            Json(serde_json::Value),
+            OptJson(Option<serde_json::Value>),
         }
 
         let mut sets = Vec::new();
@@ -195,6 +461,11 @@ impl EmbedKeyRepository {
             binds.push(BindVal::Text(primary_color.clone()));
             param_idx += 1;
         }
+        if let Some(ref theme) = req.theme {
+            sets.push(format!("theme = ${param_idx}"));
+            binds.push(BindVal::Json(serde_json::to_value(theme)?));
+            param_idx += 1;
+        }
         if let Some(ref greeting_message) = req.greeting_message {
             sets.push(format!("greeting_message = ${param_idx}"));
             binds.push(BindVal::Text(greeting_message.clone()));
@@ -215,6 +486,11 @@ impl EmbedKeyRepository {
             binds.push(BindVal::Text(api_key.clone()));
             param_idx += 1;
         }
+        if let Some(ref base_url) = req.base_url {
+            sets.push(format!("base_url = ${param_idx}"));
+            binds.push(BindVal::OptText(base_url.clone()));
+            param_idx += 1;
+        }
         if let Some(ref custom_css) = req.custom_css {
             sets.push(format!("custom_css = ${param_idx}"));
             binds.push(BindVal::Text(custom_css.clone()));
@@ -225,6 +501,71 @@ impl EmbedKeyRepository {
             binds.push(BindVal::Int(rate_limit));
             param_idx += 1;
         }
+        if let Some(daily_message_limit) = req.daily_message_limit {
+            sets.push(format!("daily_message_limit = ${param_idx}"));
+            binds.push(BindVal::OptInt(daily_message_limit));
+            param_idx += 1;
+        }
+        if let Some(monthly_message_limit) = req.monthly_message_limit {
+            sets.push(format!("monthly_message_limit = ${param_idx}"));
+            binds.push(BindVal::OptInt(monthly_message_limit));
+            param_idx += 1;
+        }
+        if let Some(rag_top_k) = req.rag_top_k {
+            sets.push(format!("rag_top_k = ${param_idx}"));
+            binds.push(BindVal::OptInt(rag_top_k));
+            param_idx += 1;
+        }
+        if let Some(rag_min_score) = req.rag_min_score {
+            sets.push(format!("rag_min_score = ${param_idx}"));
+            binds.push(BindVal::OptFloat(rag_min_score));
+            param_idx += 1;
+        }
+        if let Some(rag_max_context_chars) = req.rag_max_context_chars {
+            sets.push(format!("rag_max_context_chars = ${param_idx}"));
+            binds.push(BindVal::OptInt(rag_max_context_chars));
+            param_idx += 1;
+        }
+        if let Some(ref localizations) = req.localizations {
+            sets.push(format!("localizations = ${param_idx}"));
+            binds.push(BindVal::Json(serde_json::to_value(localizations)?));
+            param_idx += 1;
+        }
+        if let Some(handoff_enabled) = req.handoff_enabled {
+            sets.push(format!("handoff_enabled = ${param_idx}"));
+            binds.push(BindVal::Bool(handoff_enabled));
+            param_idx += 1;
+        }
+        if let Some(ref email) = req.handoff_notification_email {
+            sets.push(format!("handoff_notification_email = ${param_idx}"));
+            binds.push(BindVal::OptText(email.clone()));
+            param_idx += 1;
+        }
+        if let Some(transcript_email_enabled) = req.transcript_email_enabled {
+            sets.push(format!("transcript_email_enabled = ${param_idx}"));
+            binds.push(BindVal::Bool(transcript_email_enabled));
+            param_idx += 1;
+        }
+        if let Some(ref office_hours) = req.office_hours {
+            sets.push(format!("office_hours = ${param_idx}"));
+            binds.push(BindVal::OptJson(office_hours.as_ref().map(serde_json::to_value).transpose()?));
+            param_idx += 1;
+        }
+        if let Some(ref offline_message) = req.offline_message {
+            sets.push(format!("offline_message = ${param_idx}"));
+            binds.push(BindVal::Text(offline_message.clone()));
+            param_idx += 1;
+        }
+        if let Some(offline_behavior) = req.offline_behavior {
+            sets.push(format!("offline_behavior = ${param_idx}"));
+            binds.push(BindVal::Text(offline_behavior.as_str().to_string()));
+            param_idx += 1;
+        }
+        if let Some(ref collection_id) = req.collection_id {
+            sets.push(format!("collection_id = ${param_idx}"));
+            binds.push(BindVal::OptText(collection_id.clone()));
+            param_idx += 1;
+        }
         if let Some(ref domains) = req.allowed_domains {
             sets.push(format!("allowed_domains = ${param_idx}"));
             binds.push(BindVal::TextArray(domains.clone()));
@@ -239,15 +580,21 @@ impl EmbedKeyRepository {
         let set_clause = sets.join(", ");
 
         let sql = format!(
-            "UPDATE embed_keys SET {set_clause} WHERE id = $1 RETURNING {SELECT_COLS}"
+            "UPDATE embed_keys SET {set_clause} WHERE id = $1 RETURNING {SELECT_COLS}, {LIVE_STATS}"
         );
 
         let mut query = sqlx::query(&sql).bind(id);
         for bind in binds {
             match bind {
                 BindVal::Text(v) => query = query.bind(v),
+                BindVal::OptText(v) => query = query.bind(v),
                 BindVal::Int(v) => query = query.bind(v),
+                BindVal::Bool(v) => query = query.bind(v),
+                BindVal::OptInt(v) => query = query.bind(v),
+                BindVal::OptFloat(v) => query = query.bind(v),
                 BindVal::TextArray(v) => query = query.bind(v),
+                BindVal::Json(v) => query = query.bind(v),
+                BindVal::OptJson(v) => query = query.bind(v),
             }
         }
 
@@ -259,6 +606,40 @@ impl EmbedKeyRepository {
         Ok(row.as_ref().map(map_row))
     }
 
+    /// Point the key at a newly stored avatar. Returns the key and the storage
+    /// key of the avatar it replaced, to be deleted.
+    pub async fn set_avatar(&self, id: &str, avatar_key: &str) -> Result<Option<(EmbedKey, Option<String>)>> {
+        let row = sqlx::query(&format!(
+            "UPDATE embed_keys SET avatar_key = $2, updated_at = NOW()
+             FROM (SELECT avatar_key AS previous FROM embed_keys WHERE id = $1) AS old
+             WHERE id = $1
+             RETURNING {SELECT_COLS}, {LIVE_STATS}, old.previous"
+        ))
+        .bind(id)
+        .bind(avatar_key)
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to set embed key avatar")?;
+
+        Ok(row.map(|r| (map_row(&r), r.get("previous"))))
+    }
+
+    /// The storage key of the avatar of the active key with this prefix.
+    pub async fn find_avatar_by_prefix(&self, key_prefix: &str) -> Result<Option<String>> {
+        let key = sqlx::query_scalar::<_, String>(
+            "SELECT avatar_key FROM embed_keys
+             WHERE key_prefix = $1 AND is_active AND avatar_key IS NOT NULL
+             ORDER BY created_at
+             LIMIT 1",
+        )
+        .bind(key_prefix)
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to find embed key avatar")?;
+
+        Ok(key)
+    }
+
     pub async fn delete(&self, id: &str) -> Result<()> {
         sqlx::query("DELETE FROM embed_keys WHERE id = $1")
             .bind(id)
@@ -271,7 +652,7 @@ impl EmbedKeyRepository {
     pub async fn toggle(&self, id: &str) -> Result<Option<EmbedKey>> {
         let sql = format!(
             "UPDATE embed_keys SET is_active = NOT is_active, updated_at = NOW() WHERE id = $1
-             RETURNING {SELECT_COLS}"
+             RETURNING {SELECT_COLS}, {LIVE_STATS}"
         );
         let row = sqlx::query(&sql)
             .bind(id)
@@ -282,20 +663,83 @@ impl EmbedKeyRepository {
         Ok(row.as_ref().map(map_row))
     }
 
-    pub async fn increment_stats(&self, id: &str, conversations: i64, messages: i64) -> Result<()> {
-        sqlx::query(
-            "UPDATE embed_keys SET
-                total_conversations = total_conversations + $2,
-                total_messages = total_messages + $3,
-                updated_at = NOW()
-             WHERE id = $1"
+    /// Bring the cached conversation and message counts of every key up to date.
+    /// Returns how many keys had drifted.
+    pub async fn refresh_stats(&self) -> Result<u64> {
+        let result = sqlx::query(&format!(
+            "UPDATE embed_keys SET total_conversations = live.total_conversations,
+                 total_messages = live.total_messages
+             FROM (SELECT id, {LIVE_STATS} FROM embed_keys) AS live
+             WHERE embed_keys.id = live.id
+               AND (embed_keys.total_conversations, embed_keys.total_messages)
+                   IS DISTINCT FROM (live.total_conversations, live.total_messages)"
+        ))
+        .execute(&self.pool)
+        .await
+        .context("Failed to refresh embed key stats")?;
+        Ok(result.rows_affected())
+    }
+
+    /// Count one widget message against the key's daily and monthly caps and
+    /// return the usage including it. Returns `None`, without counting the
+    /// message, when it would exceed a cap.
+    pub async fn record_message(&self, key: &EmbedKey) -> Result<Option<EmbedKeyUsage>> {
+        let mut tx = self.pool.begin().await.context("Failed to begin transaction")?;
+
+        // The row lock on today's counter queues concurrent messages of the key
+        let messages_today = sqlx::query_scalar::<_, i64>(
+            "INSERT INTO embed_key_daily_usage (embed_key_id, day, message_count)
+             VALUES ($1, (NOW() AT TIME ZONE 'UTC')::date, 1)
+             ON CONFLICT (embed_key_id, day)
+             DO UPDATE SET message_count = embed_key_daily_usage.message_count + 1
+             RETURNING message_count::BIGINT",
+        )
+        .bind(&key.id)
+        .fetch_one(&mut *tx)
+        .await
+        .context("Failed to record embed key usage")?;
+
+        let messages_this_month = sqlx::query_scalar::<_, i64>(
+            "SELECT COALESCE(SUM(message_count), 0)::BIGINT FROM embed_key_daily_usage
+             WHERE embed_key_id = $1 AND day >= date_trunc('month', NOW() AT TIME ZONE 'UTC')::date",
+        )
+        .bind(&key.id)
+        .fetch_one(&mut *tx)
+        .await
+        .context("Failed to sum embed key usage")?;
+
+        let over = |used: i64, limit: Option<i32>| limit.is_some_and(|l| used > i64::from(l));
+        if over(messages_today, key.daily_message_limit)
+            || over(messages_this_month, key.monthly_message_limit)
+        {
+            tx.rollback().await.context("Failed to roll back embed key usage")?;
+            return Ok(None);
+        }
+
+        tx.commit().await.context("Failed to commit embed key usage")?;
+        Ok(Some(EmbedKeyUsage {
+            messages_today,
+            messages_this_month,
+        }))
+    }
+
+    pub async fn usage(&self, id: &str) -> Result<EmbedKeyUsage> {
+        let row = sqlx::query(
+            "SELECT COALESCE(SUM(message_count) FILTER (
+                        WHERE day = (NOW() AT TIME ZONE 'UTC')::date), 0)::BIGINT AS messages_today,
+                    COALESCE(SUM(message_count), 0)::BIGINT AS messages_this_month
+             FROM embed_key_daily_usage
+             WHERE embed_key_id = $1
+               AND day >= date_trunc('month', NOW() AT TIME ZONE 'UTC')::date",
         )
         .bind(id)
-        .bind(conversations)
-        .bind(messages)
-        .execute(&self.pool)
+        .fetch_one(&self.pool)
         .await
-        .context("Failed to increment embed key stats")?;
-        Ok(())
+        .context("Failed to get embed key usage")?;
+
+        Ok(EmbedKeyUsage {
+            messages_today: row.get("messages_today"),
+            messages_this_month: row.get("messages_this_month"),
+        })
     }
 }
@@ -0,0 +1,84 @@
+use anyhow::{Context, Result};
+use sqlx::{PgPool, Row};
+use std::time::Duration;
+
+/// An admin acting as another user. Tokens issued for it stop working once it
+/// ends or expires.
+#[derive(Debug, Clone)]
+pub struct ImpersonationSession {
+    pub id: String,
+    pub admin_id: String,
+    pub user_id: String,
+    /// Unix timestamp, used as the token's `exp`.
+    pub expires_at: i64,
+}
+
+#[derive(Clone)]
+pub struct ImpersonationRepository {
+    pool: PgPool,
+}
+
+impl ImpersonationRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn start(&self, admin_id: &str, user_id: &str, duration: Duration) -> Result<ImpersonationSession> {
+        let id = uuid::Uuid::new_v4().to_string();
+        let row = sqlx::query(
+            "INSERT INTO impersonation_sessions (id, admin_id, user_id, expires_at)
+             VALUES ($1, $2, $3, NOW() + make_interval(secs => $4))
+             RETURNING EXTRACT(EPOCH FROM expires_at)::BIGINT AS expires_at",
+        )
+        .bind(&id)
+        .bind(admin_id)
+        .bind(user_id)
+        .bind(duration.as_secs_f64())
+        .fetch_one(&self.pool)
+        .await
+        .context("Failed to start impersonation session")?;
+
+        Ok(ImpersonationSession {
+            id,
+            admin_id: admin_id.to_string(),
+            user_id: user_id.to_string(),
+            expires_at: row.get("expires_at"),
+        })
+    }
+
+    /// Whether session `id` of `admin_id` acting as `user_id` is still running.
+    pub async fn is_active(&self, id: &str, admin_id: &str, user_id: &str) -> Result<bool> {
+        let row = sqlx::query(
+            "SELECT EXISTS (
+                 SELECT 1 FROM impersonation_sessions
+                 WHERE id = $1 AND admin_id = $2 AND user_id = $3
+                   AND ended_at IS NULL AND expires_at > NOW()
+             ) AS active",
+        )
+        .bind(id)
+        .bind(admin_id)
+        .bind(user_id)
+        .fetch_one(&self.pool)
+        .await
+        .context("Failed to check impersonation session")?;
+
+        Ok(row.get("active"))
+    }
+
+    /// End every running session of `admin_id` acting as `user_id`. Returns how
+    /// many were running.
+    pub async fn end(&self, admin_id: &str, user_id: &str) -> Result<u64> {
+        let result = sqlx::query(
+            "UPDATE impersonation_sessions SET ended_at = NOW()
+             WHERE admin_id = $1 AND user_id = $2
+               AND ended_at IS NULL AND expires_at > NOW()",
+        )
+        .bind(admin_id)
+        .bind(user_id)
+        .execute(&self.pool)
+        .await
+        .context("Failed to end impersonation sessions")?;
+
+        Ok(result.rows_affected())
+    }
+}
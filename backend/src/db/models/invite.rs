@@ -4,6 +4,8 @@ use sqlx::{PgPool, Row};
 use uuid::Uuid;
 
 use super::user::UserRole;
+use crate::services::email::normalize_email;
+use chrono::{DateTime, Utc};
 
 #[derive(Debug, Clone, Serialize)]
 pub struct Invite {
@@ -13,8 +15,28 @@ pub struct Invite {
     pub role: UserRole,
     pub invited_by: String,
     pub used: bool,
-    pub expires_at: String,
-    pub created_at: String,
+    #[serde(with = "crate::db::timestamp")]
+    pub expires_at: DateTime<Utc>,
+    #[serde(with = "crate::db::timestamp")]
+    pub created_at: DateTime<Utc>,
+}
+
+const SELECT_COLS: &str = "id, email, token, role, invited_by, used,
+    expires_at,
+    created_at";
+
+fn map_row(r: &sqlx::postgres::PgRow) -> Result<Invite> {
+    let role_str: String = r.get("role");
+    Ok(Invite {
+        id: r.get("id"),
+        email: r.get("email"),
+        token: r.get("token"),
+        role: UserRole::try_from(role_str.as_str()).map_err(|e| anyhow::anyhow!("Invalid role: {e}"))?,
+        invited_by: r.get("invited_by"),
+        used: r.get::<bool, _>("used"),
+        expires_at: r.get("expires_at"),
+        created_at: r.get("created_at"),
+    })
 }
 
 #[derive(Clone)]
@@ -41,13 +63,14 @@ impl InviteRepository {
             .checked_add_signed(chrono::Duration::hours(expires_hours))
             .unwrap_or(now);
         let created_at = now;
+        let email = normalize_email(email);
 
         sqlx::query(
             "INSERT INTO user_invites (id, email, token, role, invited_by, used, expires_at, created_at)
              VALUES ($1, $2, $3, $4, $5, false, $6, $7)",
         )
         .bind(&id)
-        .bind(email)
+        .bind(&email)
         .bind(&token)
         .bind(role.to_string())
         .bind(invited_by)
@@ -59,46 +82,49 @@ impl InviteRepository {
 
         Ok(Invite {
             id,
-            email: email.to_string(),
+            email,
             token,
             role: role.clone(),
             invited_by: invited_by.to_string(),
             used: false,
-            expires_at: expires_at.to_rfc3339(),
-            created_at: created_at.to_rfc3339(),
+            expires_at,
+            created_at,
         })
     }
 
     pub async fn find_by_token(&self, token: &str) -> Result<Option<Invite>> {
-        let row = sqlx::query(
-            "SELECT id, email, token, role, invited_by, used,
-                    to_char(expires_at, 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') AS expires_at,
-                    to_char(created_at, 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') AS created_at
-             FROM user_invites WHERE token = $1",
-        )
-        .bind(token)
+        let row = sqlx::query(&format!("SELECT {SELECT_COLS} FROM user_invites WHERE token = $1"))
+            .bind(token)
+            .fetch_optional(&self.pool)
+            .await
+            .context("Failed to query invite by token")?;
+
+        row.as_ref().map(map_row).transpose()
+    }
+
+    pub async fn find_by_id(&self, id: &str) -> Result<Option<Invite>> {
+        let row = sqlx::query(&format!("SELECT {SELECT_COLS} FROM user_invites WHERE id = $1"))
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+            .context("Failed to query invite by id")?;
+
+        row.as_ref().map(map_row).transpose()
+    }
+
+    /// An unused, unexpired invite for `email`, in any case.
+    pub async fn find_pending_by_email(&self, email: &str) -> Result<Option<Invite>> {
+        let row = sqlx::query(&format!(
+            "SELECT {SELECT_COLS} FROM user_invites
+             WHERE lower(email) = $1 AND NOT used AND expires_at > NOW()
+             ORDER BY created_at DESC LIMIT 1"
+        ))
+        .bind(normalize_email(email))
         .fetch_optional(&self.pool)
         .await
-        .context("Failed to query invite by token")?;
-
-        let invite = row
-            .map(|r| -> Result<Invite> {
-                let role_str: String = r.get("role");
-                Ok(Invite {
-                    id: r.get("id"),
-                    email: r.get("email"),
-                    token: r.get("token"),
-                    role: UserRole::try_from(role_str.as_str())
-                        .map_err(|e| anyhow::anyhow!("Invalid role: {e}"))?,
-                    invited_by: r.get("invited_by"),
-                    used: r.get::<bool, _>("used"),
-                    expires_at: r.get("expires_at"),
-                    created_at: r.get("created_at"),
-                })
-            })
-            .transpose()?;
-
-        Ok(invite)
+        .context("Failed to query pending invite by email")?;
+
+        row.as_ref().map(map_row).transpose()
     }
 
     pub async fn mark_used(&self, token: &str) -> Result<()> {
@@ -111,34 +137,13 @@ impl InviteRepository {
     }
 
     pub async fn find_all(&self) -> Result<Vec<Invite>> {
-        let rows = sqlx::query(
-            "SELECT id, email, token, role, invited_by, used,
-                    to_char(expires_at, 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') AS expires_at,
-                    to_char(created_at, 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') AS created_at
-             FROM user_invites ORDER BY created_at DESC",
-        )
+        let rows = sqlx::query(&format!(
+            "SELECT {SELECT_COLS} FROM user_invites ORDER BY created_at DESC"
+        ))
         .fetch_all(&self.pool)
         .await
         .context("Failed to query invites")?;
 
-        let invites = rows
-            .into_iter()
-            .map(|r| {
-                let role_str: String = r.get("role");
-                Ok(Invite {
-                    id: r.get("id"),
-                    email: r.get("email"),
-                    token: r.get("token"),
-                    role: UserRole::try_from(role_str.as_str())
-                        .map_err(|e| anyhow::anyhow!("Invalid role: {e}"))?,
-                    invited_by: r.get("invited_by"),
-                    used: r.get::<bool, _>("used"),
-                    expires_at: r.get("expires_at"),
-                    created_at: r.get("created_at"),
-                })
-            })
-            .collect::<Result<Vec<_>>>()?;
-
-        Ok(invites)
+        rows.iter().map(map_row).collect()
     }
 }
@@ -0,0 +1,224 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use sqlx::{PgPool, Row};
+use std::time::Duration;
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+
+/// A row in the background job queue. `payload` never contains secrets: workers
+/// resolve credentials when the job runs.
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Job {
+    pub id: String,
+    pub kind: String,
+    pub payload: serde_json::Value,
+    /// `queued`, `running`, `completed` or `dead` (retries exhausted).
+    pub status: String,
+    pub attempts: i32,
+    pub max_attempts: i32,
+    pub last_error: Option<String>,
+    /// The process running the job, while it is `running`.
+    pub locked_by: Option<String>,
+    /// When the running job's lease expires unless `locked_by` renews it.
+    #[serde(with = "crate::db::timestamp::option")]
+    pub locked_until: Option<DateTime<Utc>>,
+    #[serde(with = "crate::db::timestamp")]
+    pub next_run_at: DateTime<Utc>,
+    #[serde(with = "crate::db::timestamp")]
+    pub created_at: DateTime<Utc>,
+    #[serde(with = "crate::db::timestamp")]
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Clone)]
+pub struct JobRepository {
+    pool: PgPool,
+}
+
+const SELECT_COLS: &str = "id, kind, payload, status, attempts, max_attempts, last_error,
+     locked_by, locked_until,
+     next_run_at,
+     created_at,
+     updated_at";
+
+fn map_row(row: &sqlx::postgres::PgRow) -> Job {
+    Job {
+        id: row.get("id"),
+        kind: row.get("kind"),
+        payload: row.get("payload"),
+        status: row.get("status"),
+        attempts: row.get("attempts"),
+        max_attempts: row.get("max_attempts"),
+        last_error: row.get("last_error"),
+        locked_by: row.get("locked_by"),
+        locked_until: row.get("locked_until"),
+        next_run_at: row.get("next_run_at"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+    }
+}
+
+impl JobRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn enqueue(
+        &self,
+        kind: &str,
+        payload: &serde_json::Value,
+        max_attempts: i32,
+    ) -> Result<Job> {
+        let row = sqlx::query(&format!(
+            "INSERT INTO jobs (id, kind, payload, max_attempts)
+             VALUES ($1, $2, $3, $4)
+             RETURNING {SELECT_COLS}"
+        ))
+        .bind(Uuid::new_v4().to_string())
+        .bind(kind)
+        .bind(payload)
+        .bind(max_attempts)
+        .fetch_one(&self.pool)
+        .await
+        .context("Failed to enqueue job")?;
+
+        Ok(map_row(&row))
+    }
+
+    /// Atomically take the oldest due job and mark it running, leased to
+    /// `owner` for `lease`. Concurrent workers never receive the same job.
+    pub async fn claim_next(&self, owner: &str, lease: Duration) -> Result<Option<Job>> {
+        let row = sqlx::query(&format!(
+            "UPDATE jobs SET status = 'running', attempts = attempts + 1,
+                 locked_by = $1, locked_until = NOW() + make_interval(secs => $2), updated_at = NOW()
+             WHERE id = (
+                 SELECT id FROM jobs
+                 WHERE status = 'queued' AND next_run_at <= NOW()
+                 ORDER BY next_run_at
+                 LIMIT 1
+                 FOR UPDATE SKIP LOCKED
+             )
+             RETURNING {SELECT_COLS}"
+        ))
+        .bind(owner)
+        .bind(lease.as_secs_f64())
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to claim job")?;
+
+        Ok(row.as_ref().map(map_row))
+    }
+
+    /// Extend `owner`'s lease on a running job. False if the job is no longer
+    /// running under that lease.
+    pub async fn renew_lease(&self, id: &str, owner: &str, lease: Duration) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE jobs SET locked_until = NOW() + make_interval(secs => $1)
+             WHERE id = $2 AND status = 'running' AND locked_by = $3",
+        )
+        .bind(lease.as_secs_f64())
+        .bind(id)
+        .bind(owner)
+        .execute(&self.pool)
+        .await
+        .context("Failed to renew job lease")?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn complete(&self, id: &str) -> Result<()> {
+        sqlx::query(
+            "UPDATE jobs SET status = 'completed', last_error = NULL,
+                 locked_by = NULL, locked_until = NULL, updated_at = NOW()
+             WHERE id = $1",
+        )
+        .bind(id)
+        .execute(&self.pool)
+        .await
+        .context("Failed to complete job")?;
+        Ok(())
+    }
+
+    /// Put a failed job back in the queue to run again after `delay`.
+    pub async fn retry_later(&self, id: &str, error: &str, delay: Duration) -> Result<()> {
+        sqlx::query(
+            "UPDATE jobs SET status = 'queued', last_error = $1,
+                 next_run_at = NOW() + make_interval(secs => $2),
+                 locked_by = NULL, locked_until = NULL, updated_at = NOW()
+             WHERE id = $3",
+        )
+        .bind(error)
+        .bind(delay.as_secs_f64())
+        .bind(id)
+        .execute(&self.pool)
+        .await
+        .context("Failed to reschedule job")?;
+        Ok(())
+    }
+
+    /// Move a job to the dead-letter state after its last attempt failed.
+    pub async fn mark_dead(&self, id: &str, error: &str) -> Result<()> {
+        sqlx::query(
+            "UPDATE jobs SET status = 'dead', last_error = $1,
+                 locked_by = NULL, locked_until = NULL, updated_at = NOW()
+             WHERE id = $2",
+        )
+        .bind(error)
+        .bind(id)
+        .execute(&self.pool)
+        .await
+        .context("Failed to dead-letter job")?;
+        Ok(())
+    }
+
+    /// Return a job interrupted by shutdown to the queue without using up an attempt.
+    pub async fn release(&self, id: &str) -> Result<()> {
+        sqlx::query(
+            "UPDATE jobs SET status = 'queued', attempts = GREATEST(attempts - 1, 0),
+                 next_run_at = NOW(), locked_by = NULL, locked_until = NULL, updated_at = NOW()
+             WHERE id = $1",
+        )
+        .bind(id)
+        .execute(&self.pool)
+        .await
+        .context("Failed to release job")?;
+        Ok(())
+    }
+
+    /// Requeue running jobs whose lease has expired, left behind by a process
+    /// that died; jobs other processes are still renewing are left alone. The
+    /// interrupted run counts as an attempt, so a job that keeps crashing the
+    /// server is dead-lettered. Returns how many jobs were requeued or dead-lettered.
+    pub async fn recover_expired(&self, error: &str) -> Result<u64> {
+        let result = sqlx::query(
+            "UPDATE jobs SET
+                 status = CASE WHEN attempts >= max_attempts THEN 'dead' ELSE 'queued' END,
+                 last_error = $1, next_run_at = NOW(),
+                 locked_by = NULL, locked_until = NULL, updated_at = NOW()
+             WHERE status = 'running' AND (locked_until IS NULL OR locked_until < NOW())",
+        )
+        .bind(error)
+        .execute(&self.pool)
+        .await
+        .context("Failed to recover running jobs")?;
+
+        Ok(result.rows_affected())
+    }
+
+    pub async fn list(&self, status: Option<&str>, limit: i64) -> Result<Vec<Job>> {
+        let rows = sqlx::query(&format!(
+            "SELECT {SELECT_COLS} FROM jobs
+             WHERE ($1::TEXT IS NULL OR status = $1)
+             ORDER BY updated_at DESC
+             LIMIT $2"
+        ))
+        .bind(status)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to list jobs")?;
+
+        Ok(rows.iter().map(map_row).collect())
+    }
+}
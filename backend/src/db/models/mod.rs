@@ -1,11 +1,36 @@
+pub mod admin_api_key;
 pub mod admin_config;
 pub mod audit_log;
+pub mod collection;
 pub mod conversation;
+pub mod conversation_share;
 pub mod crawl_job;
+pub mod crawl_schedule;
+pub mod data_export;
 pub mod document;
 pub mod document_chunk;
+pub mod document_event;
+pub mod email_outbox;
 pub mod embed_key;
+pub mod impersonation;
 pub mod invite;
+pub mod job;
+pub mod org_settings;
+pub mod pending_vector_deletion;
+pub mod rescan_run;
+pub mod scheduled_job_run;
 pub mod settings;
 pub mod user;
+pub mod web_page;
+pub mod webhook;
+pub mod widget_handoff;
 pub mod widget_session;
+
+/// Distinguish an absent field (`None`) from an explicit `null` (`Some(None)`).
+pub(crate) fn double_option<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: serde::Deserialize<'de>,
+{
+    serde::Deserialize::deserialize(deserializer).map(Some)
+}
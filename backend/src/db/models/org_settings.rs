@@ -0,0 +1,144 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use sqlx::{PgPool, Row};
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+
+/// Organization-wide settings, one row for the whole deployment.
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct OrgSettings {
+    /// Put before every user's and embed key's system prompt; users can't remove it.
+    pub base_system_prompt: String,
+    /// Whether users may read the base prompt, or only learn that there is one.
+    pub base_system_prompt_visible: bool,
+    pub updated_by: Option<String>,
+    #[serde(with = "crate::db::timestamp")]
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A base system prompt that was in effect until it was replaced.
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SystemPromptRevision {
+    pub id: String,
+    pub base_system_prompt: String,
+    pub set_by: Option<String>,
+    #[serde(with = "crate::db::timestamp")]
+    pub set_at: DateTime<Utc>,
+    pub replaced_by: Option<String>,
+    #[serde(with = "crate::db::timestamp")]
+    pub replaced_at: DateTime<Utc>,
+}
+
+const SELECT_COLS: &str = "base_system_prompt, base_system_prompt_visible, updated_by,
+    updated_at AS updated_at_fmt";
+
+fn map_row(r: &sqlx::postgres::PgRow) -> OrgSettings {
+    OrgSettings {
+        base_system_prompt: r.get("base_system_prompt"),
+        base_system_prompt_visible: r.get("base_system_prompt_visible"),
+        updated_by: r.get("updated_by"),
+        updated_at: r.get("updated_at_fmt"),
+    }
+}
+
+#[derive(Clone)]
+pub struct OrgSettingsRepository {
+    pool: PgPool,
+}
+
+impl OrgSettingsRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn get(&self) -> Result<OrgSettings> {
+        let sql = format!("SELECT {SELECT_COLS} FROM org_settings");
+        let row = sqlx::query(&sql)
+            .fetch_one(&self.pool)
+            .await
+            .context("Failed to get org settings")?;
+        Ok(map_row(&row))
+    }
+
+    /// The base system prompt, empty when none is set.
+    pub async fn base_system_prompt(&self) -> Result<String> {
+        sqlx::query_scalar("SELECT base_system_prompt FROM org_settings")
+            .fetch_one(&self.pool)
+            .await
+            .context("Failed to get base system prompt")
+    }
+
+    /// Replace the base system prompt, keeping the previous one in the history
+    /// when the text changes.
+    pub async fn set_base_system_prompt(&self, prompt: &str, visible: bool, user_id: &str) -> Result<OrgSettings> {
+        let mut tx = self.pool.begin().await.context("Failed to start transaction")?;
+
+        let current = sqlx::query(
+            "SELECT base_system_prompt, updated_by, updated_at FROM org_settings FOR UPDATE",
+        )
+        .fetch_one(&mut *tx)
+        .await
+        .context("Failed to lock org settings")?;
+        let previous: String = current.get("base_system_prompt");
+        if previous != prompt {
+            sqlx::query(
+                "INSERT INTO org_system_prompt_history (id, base_system_prompt, set_by, set_at, replaced_by)
+                 VALUES ($1, $2, $3, $4, $5)",
+            )
+            .bind(Uuid::new_v4().to_string())
+            .bind(&previous)
+            .bind(current.get::<Option<String>, _>("updated_by"))
+            .bind(current.get::<chrono::DateTime<chrono::Utc>, _>("updated_at"))
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await
+            .context("Failed to record previous base system prompt")?;
+        }
+
+        let sql = format!(
+            "UPDATE org_settings
+             SET base_system_prompt = $1, base_system_prompt_visible = $2, updated_by = $3, updated_at = NOW()
+             RETURNING {SELECT_COLS}"
+        );
+        let row = sqlx::query(&sql)
+            .bind(prompt)
+            .bind(visible)
+            .bind(user_id)
+            .fetch_one(&mut *tx)
+            .await
+            .context("Failed to update base system prompt")?;
+
+        tx.commit().await.context("Failed to commit base system prompt")?;
+        Ok(map_row(&row))
+    }
+
+    /// Replaced base system prompts, most recently replaced first.
+    pub async fn system_prompt_history(&self, limit: i64) -> Result<Vec<SystemPromptRevision>> {
+        let rows = sqlx::query(
+            "SELECT id, base_system_prompt, set_by,
+                    set_at AS set_at_fmt, replaced_by,
+                    replaced_at AS replaced_at_fmt
+             FROM org_system_prompt_history
+             ORDER BY replaced_at DESC
+             LIMIT $1",
+        )
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to list base system prompt history")?;
+
+        Ok(rows
+            .iter()
+            .map(|r| SystemPromptRevision {
+                id: r.get("id"),
+                base_system_prompt: r.get("base_system_prompt"),
+                set_by: r.get("set_by"),
+                set_at: r.get("set_at_fmt"),
+                replaced_by: r.get("replaced_by"),
+                replaced_at: r.get("replaced_at_fmt"),
+            })
+            .collect())
+    }
+}
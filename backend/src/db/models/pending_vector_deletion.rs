@@ -0,0 +1,101 @@
+use anyhow::{Context, Result};
+use sqlx::{PgPool, Row};
+use std::time::Duration;
+
+/// Vectors that couldn't be deleted from Qdrant when their chunks were, kept
+/// until a retry succeeds instead of being left orphaned in the collection.
+#[derive(Debug, Clone)]
+pub struct PendingVectorDeletion {
+    pub id: i64,
+    pub collection: String,
+    pub point_ids: Vec<String>,
+    /// Retries made so far; the first attempt happened before the record was queued.
+    pub attempts: i32,
+}
+
+#[derive(Clone)]
+pub struct PendingVectorDeletionRepository {
+    pool: PgPool,
+}
+
+impl PendingVectorDeletionRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn enqueue(&self, collection: &str, point_ids: &[String], error: &str) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO pending_vector_deletions (collection, point_ids, last_error)
+             VALUES ($1, $2, $3)",
+        )
+        .bind(collection)
+        .bind(point_ids)
+        .bind(error)
+        .execute(&self.pool)
+        .await
+        .context("Failed to queue vector deletion")?;
+
+        Ok(())
+    }
+
+    /// Deletions whose next attempt is due, oldest first.
+    pub async fn list_due(&self, limit: i64) -> Result<Vec<PendingVectorDeletion>> {
+        let rows = sqlx::query(
+            "SELECT id, collection, point_ids, attempts FROM pending_vector_deletions
+             WHERE next_attempt_at <= NOW()
+             ORDER BY id
+             LIMIT $1",
+        )
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to list pending vector deletions")?;
+
+        Ok(rows
+            .iter()
+            .map(|r| PendingVectorDeletion {
+                id: r.get("id"),
+                collection: r.get("collection"),
+                point_ids: r.get("point_ids"),
+                attempts: r.get("attempts"),
+            })
+            .collect())
+    }
+
+    /// Record a failed retry and put the next one `delay` from now.
+    pub async fn retry_later(&self, id: i64, error: &str, delay: Duration) -> Result<()> {
+        sqlx::query(
+            "UPDATE pending_vector_deletions
+             SET attempts = attempts + 1, last_error = $1,
+                 next_attempt_at = NOW() + make_interval(secs => $2)
+             WHERE id = $3",
+        )
+        .bind(error)
+        .bind(delay.as_secs_f64())
+        .bind(id)
+        .execute(&self.pool)
+        .await
+        .context("Failed to reschedule vector deletion")?;
+
+        Ok(())
+    }
+
+    pub async fn remove(&self, id: i64) -> Result<()> {
+        sqlx::query("DELETE FROM pending_vector_deletions WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to remove pending vector deletion")?;
+
+        Ok(())
+    }
+
+    pub async fn count(&self) -> Result<i64> {
+        let row = sqlx::query("SELECT COUNT(*) AS count FROM pending_vector_deletions")
+            .fetch_one(&self.pool)
+            .await
+            .context("Failed to count pending vector deletions")?;
+
+        Ok(row.get("count"))
+    }
+}
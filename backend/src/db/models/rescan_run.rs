@@ -0,0 +1,185 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use sqlx::{PgPool, Row};
+use chrono::{DateTime, Utc};
+use std::time::Duration;
+
+/// One re-embedding of every ready document, started by an admin. Counts are
+/// updated as each document finishes, so a running rescan reports progress.
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct RescanRun {
+    pub id: String,
+    pub started_by: String,
+    pub embedding_provider: String,
+    pub embedding_model: String,
+    /// `running`, `completed` or `interrupted` (by a shutdown or restart).
+    pub status: String,
+    pub total: i32,
+    /// Documents re-embedded.
+    pub succeeded: i32,
+    /// Documents whose text and chunking were unchanged, left as they were.
+    pub skipped: i32,
+    pub failed: i32,
+    /// Documents that failed; each carries its own error message.
+    pub failed_document_ids: Vec<String>,
+    #[serde(with = "crate::db::timestamp")]
+    pub started_at: DateTime<Utc>,
+    #[serde(with = "crate::db::timestamp::option")]
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+const SELECT_COLS: &str = "id, started_by, embedding_provider, embedding_model, status, total, succeeded, skipped, failed,
+     failed_document_ids,
+     started_at,
+     finished_at";
+
+fn map_row(row: &sqlx::postgres::PgRow) -> RescanRun {
+    RescanRun {
+        id: row.get("id"),
+        started_by: row.get("started_by"),
+        embedding_provider: row.get("embedding_provider"),
+        embedding_model: row.get("embedding_model"),
+        status: row.get("status"),
+        total: row.get("total"),
+        succeeded: row.get("succeeded"),
+        skipped: row.get("skipped"),
+        failed: row.get("failed"),
+        failed_document_ids: row.get("failed_document_ids"),
+        started_at: row.get("started_at"),
+        finished_at: row.get("finished_at"),
+    }
+}
+
+#[derive(Clone)]
+pub struct RescanRunRepository {
+    pool: PgPool,
+}
+
+impl RescanRunRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Record a new running rescan, leased to `owner` for `lease`. `None` if
+    /// another one is still running.
+    pub async fn start(
+        &self,
+        started_by: &str,
+        embedding_provider: &str,
+        embedding_model: &str,
+        total: usize,
+        owner: &str,
+        lease: Duration,
+    ) -> Result<Option<RescanRun>> {
+        let row = sqlx::query(&format!(
+            "INSERT INTO rescan_runs (id, started_by, embedding_provider, embedding_model, total,
+                 locked_by, locked_until)
+             VALUES ($1, $2, $3, $4, $5, $6, NOW() + make_interval(secs => $7))
+             ON CONFLICT DO NOTHING
+             RETURNING {SELECT_COLS}"
+        ))
+        .bind(uuid::Uuid::new_v4().to_string())
+        .bind(started_by)
+        .bind(embedding_provider)
+        .bind(embedding_model)
+        .bind(total as i32)
+        .bind(owner)
+        .bind(lease.as_secs_f64())
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to start rescan run")?;
+
+        Ok(row.as_ref().map(map_row))
+    }
+
+    /// The most recently started rescan, running or not.
+    pub async fn latest(&self) -> Result<Option<RescanRun>> {
+        let row = sqlx::query(&format!(
+            "SELECT {SELECT_COLS} FROM rescan_runs ORDER BY rescan_runs.started_at DESC LIMIT 1"
+        ))
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to get latest rescan run")?;
+
+        Ok(row.as_ref().map(map_row))
+    }
+
+    /// Extend `owner`'s lease on a running rescan. False if it is no longer running.
+    pub async fn renew_lease(&self, id: &str, owner: &str, lease: Duration) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE rescan_runs SET locked_until = NOW() + make_interval(secs => $1)
+             WHERE id = $2 AND status = 'running' AND locked_by = $3",
+        )
+        .bind(lease.as_secs_f64())
+        .bind(id)
+        .bind(owner)
+        .execute(&self.pool)
+        .await
+        .context("Failed to renew rescan lease")?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn record_success(&self, id: &str) -> Result<()> {
+        sqlx::query("UPDATE rescan_runs SET succeeded = succeeded + 1 WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to record rescanned document")?;
+        Ok(())
+    }
+
+    pub async fn record_skip(&self, id: &str) -> Result<()> {
+        sqlx::query("UPDATE rescan_runs SET skipped = skipped + 1 WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to record skipped document")?;
+        Ok(())
+    }
+
+    pub async fn record_failure(&self, id: &str, document_id: &str) -> Result<()> {
+        sqlx::query(
+            "UPDATE rescan_runs
+             SET failed = failed + 1, failed_document_ids = array_append(failed_document_ids, $2)
+             WHERE id = $1",
+        )
+        .bind(id)
+        .bind(document_id)
+        .execute(&self.pool)
+        .await
+        .context("Failed to record failed rescan document")?;
+        Ok(())
+    }
+
+    /// End a running rescan as `completed` or `interrupted`.
+    pub async fn finish(&self, id: &str, status: &str) -> Result<Option<RescanRun>> {
+        let row = sqlx::query(&format!(
+            "UPDATE rescan_runs SET status = $2, finished_at = NOW(), locked_by = NULL, locked_until = NULL
+             WHERE id = $1 AND status = 'running'
+             RETURNING {SELECT_COLS}"
+        ))
+        .bind(id)
+        .bind(status)
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to finish rescan run")?;
+
+        Ok(row.as_ref().map(map_row))
+    }
+
+    /// Mark a rescan left running by a process that died, its lease expired,
+    /// as interrupted. Returns how many were updated.
+    pub async fn fail_interrupted(&self) -> Result<u64> {
+        let result = sqlx::query(
+            "UPDATE rescan_runs SET status = 'interrupted', finished_at = NOW(), locked_by = NULL, locked_until = NULL
+             WHERE status = 'running' AND (locked_until IS NULL OR locked_until < NOW())",
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to mark interrupted rescan runs")?;
+
+        Ok(result.rows_affected())
+    }
+}
@@ -0,0 +1,102 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use sqlx::{PgPool, Row};
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+
+/// How the latest runs of a periodic job went. `last_error` is kept after
+/// later runs succeed, so a flaky job still shows what went wrong.
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ScheduledJobRun {
+    pub name: String,
+    #[serde(with = "crate::db::timestamp::option")]
+    pub last_started_at: Option<DateTime<Utc>>,
+    #[serde(with = "crate::db::timestamp::option")]
+    pub last_finished_at: Option<DateTime<Utc>>,
+    pub last_duration_ms: Option<i64>,
+    /// Whether the last finished run succeeded.
+    pub last_succeeded: Option<bool>,
+    pub last_error: Option<String>,
+    #[serde(with = "crate::db::timestamp::option")]
+    pub last_error_at: Option<DateTime<Utc>>,
+    pub runs: i64,
+    pub failures: i64,
+}
+
+const SELECT_COLS: &str = "name,
+    last_started_at,
+    last_finished_at,
+    last_duration_ms, last_succeeded, last_error,
+    last_error_at,
+    runs, failures";
+
+fn map_row(r: &sqlx::postgres::PgRow) -> ScheduledJobRun {
+    ScheduledJobRun {
+        name: r.get("name"),
+        last_started_at: r.get("last_started_at"),
+        last_finished_at: r.get("last_finished_at"),
+        last_duration_ms: r.get("last_duration_ms"),
+        last_succeeded: r.get("last_succeeded"),
+        last_error: r.get("last_error"),
+        last_error_at: r.get("last_error_at"),
+        runs: r.get("runs"),
+        failures: r.get("failures"),
+    }
+}
+
+#[derive(Clone)]
+pub struct ScheduledJobRunRepository {
+    pool: PgPool,
+}
+
+impl ScheduledJobRunRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn record_start(&self, name: &str) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO scheduled_job_runs (name, last_started_at) VALUES ($1, NOW())
+             ON CONFLICT (name) DO UPDATE SET last_started_at = NOW()",
+        )
+        .bind(name)
+        .execute(&self.pool)
+        .await
+        .context("Failed to record scheduled job start")?;
+        Ok(())
+    }
+
+    /// Record a finished run, failed if `error` is set.
+    pub async fn record_finish(&self, name: &str, duration: Duration, error: Option<&str>) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO scheduled_job_runs
+                 (name, last_finished_at, last_duration_ms, last_succeeded, last_error, last_error_at, runs, failures)
+             VALUES ($1, NOW(), $2, $3 IS NULL, $3, CASE WHEN $3 IS NULL THEN NULL ELSE NOW() END, 1,
+                     CASE WHEN $3 IS NULL THEN 0 ELSE 1 END)
+             ON CONFLICT (name) DO UPDATE SET
+                 last_finished_at = NOW(),
+                 last_duration_ms = EXCLUDED.last_duration_ms,
+                 last_succeeded = EXCLUDED.last_succeeded,
+                 last_error = COALESCE(EXCLUDED.last_error, scheduled_job_runs.last_error),
+                 last_error_at = COALESCE(EXCLUDED.last_error_at, scheduled_job_runs.last_error_at),
+                 runs = scheduled_job_runs.runs + 1,
+                 failures = scheduled_job_runs.failures + EXCLUDED.failures",
+        )
+        .bind(name)
+        .bind(duration.as_millis() as i64)
+        .bind(error)
+        .execute(&self.pool)
+        .await
+        .context("Failed to record scheduled job run")?;
+        Ok(())
+    }
+
+    pub async fn list(&self) -> Result<Vec<ScheduledJobRun>> {
+        let rows = sqlx::query(&format!("SELECT {SELECT_COLS} FROM scheduled_job_runs ORDER BY name"))
+            .fetch_all(&self.pool)
+            .await
+            .context("Failed to list scheduled job runs")?;
+        Ok(rows.iter().map(map_row).collect())
+    }
+}
@@ -3,12 +3,68 @@ use serde::{Deserialize, Serialize};
 use sqlx::{PgPool, Row};
 use uuid::Uuid;
 
+use crate::services::llm_provider;
+use chrono::{DateTime, Utc};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct ApiKeyEntry {
     pub id: String,
     pub provider: String,
-    pub created_at: String,
+    /// Custom endpoint for self-hosted or OpenAI-compatible servers.
+    pub base_url: Option<String>,
+    /// First and last four characters of the key, to tell keys apart.
+    pub key_preview: String,
+    /// When chat or embeddings last used the key, updated at most once per hour.
+    #[serde(with = "crate::db::timestamp::option")]
+    pub last_used_at: Option<DateTime<Utc>>,
+    #[serde(with = "crate::db::timestamp")]
+    pub created_at: DateTime<Utc>,
+}
+
+/// How stale `last_used_at` must be before another use is recorded.
+const LAST_USED_THROTTLE_SECS: f64 = 3600.0;
+
+const API_KEY_COLS: &str = "id, provider, base_url, key_preview,
+     last_used_at,
+     created_at";
+
+fn map_api_key_row(row: &sqlx::postgres::PgRow) -> ApiKeyEntry {
+    ApiKeyEntry {
+        id: row.get("id"),
+        provider: row.get("provider"),
+        base_url: row.get("base_url"),
+        key_preview: row.get("key_preview"),
+        last_used_at: row.get("last_used_at"),
+        created_at: row.get("created_at"),
+    }
+}
+
+/// A non-reversible preview such as `sk-p...9xQz`. Keys too short to reveal
+/// eight characters of are masked entirely.
+pub fn key_preview(api_key: &str) -> String {
+    let chars: Vec<char> = api_key.chars().collect();
+    if chars.len() < 12 {
+        return "****".to_string();
+    }
+    let head: String = chars[..4].iter().collect();
+    let tail: String = chars[chars.len() - 4..].iter().collect();
+    format!("{head}...{tail}")
+}
+
+/// Everything needed to build a provider client for a stored key.
+#[derive(Debug, Clone, Default)]
+pub struct ProviderCredentials {
+    pub api_key: String,
+    pub base_url: Option<String>,
+}
+
+impl ProviderCredentials {
+    /// Use the stored credentials, or empty ones when `provider` needs no key.
+    /// `None` means the user has to configure a key first.
+    pub fn or_keyless(stored: Option<Self>, provider: &str) -> Option<Self> {
+        stored.or_else(|| (!llm_provider::requires_api_key(provider)).then(Self::default))
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -18,6 +74,10 @@ pub struct LlmPreferences {
     pub preferred_model: String,
     pub preferred_embedding_model: String,
     pub system_prompt: String,
+    /// Providers to retry a reply with when the chosen one is down, in order.
+    /// Empty uses the organization's `llm.fallback_providers`.
+    #[serde(default)]
+    pub fallback_providers: Vec<String>,
 }
 
 #[derive(Clone)]
@@ -36,30 +96,49 @@ impl SettingsRepository {
         user_id: &str,
         provider: &str,
         api_key: &str,
+        base_url: Option<&str>,
     ) -> Result<ApiKeyEntry> {
         let id = Uuid::new_v4().to_string();
         let now = chrono::Utc::now();
 
-        let row = sqlx::query(
-            "INSERT INTO user_api_keys (id, user_id, provider, api_key, created_at)
-             VALUES ($1, $2, $3, $4, $5)
-             ON CONFLICT(user_id, provider) DO UPDATE SET api_key = $4, id = $1
-             RETURNING id, provider, to_char(created_at, 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') AS created_at",
-        )
+        // A replaced key starts out unused
+        let row = sqlx::query(&format!(
+            "INSERT INTO user_api_keys (id, user_id, provider, api_key, base_url, key_preview, created_at)
+             VALUES ($1, $2, $3, $4, $5, $6, $7)
+             ON CONFLICT(user_id, provider) DO UPDATE
+                 SET api_key = $4, base_url = $5, key_preview = $6, last_used_at = NULL, id = $1
+             RETURNING {API_KEY_COLS}"
+        ))
         .bind(&id)
         .bind(user_id)
         .bind(provider)
         .bind(api_key)
+        .bind(base_url)
+        .bind(key_preview(api_key))
         .bind(now)
         .fetch_one(&self.pool)
         .await
         .context("Failed to upsert API key")?;
 
-        Ok(ApiKeyEntry {
-            id: row.get("id"),
-            provider: row.get("provider"),
-            created_at: row.get("created_at"),
-        })
+        Ok(map_api_key_row(&row))
+    }
+
+    /// Record that the user's key for `provider` was used. Throttled to one
+    /// write per hour; returns whether `last_used_at` was updated.
+    pub async fn touch_api_key(&self, user_id: &str, provider: &str) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE user_api_keys SET last_used_at = NOW()
+             WHERE user_id = $1 AND provider = $2
+               AND (last_used_at IS NULL OR last_used_at < NOW() - make_interval(secs => $3))",
+        )
+        .bind(user_id)
+        .bind(provider)
+        .bind(LAST_USED_THROTTLE_SECS)
+        .execute(&self.pool)
+        .await
+        .context("Failed to record API key use")?;
+
+        Ok(result.rows_affected() > 0)
     }
 
     pub async fn get_api_key(&self, user_id: &str, provider: &str) -> Result<Option<String>> {
@@ -75,44 +154,36 @@ impl SettingsRepository {
         Ok(row.map(|r| r.get("api_key")))
     }
 
-    /// Find any API key for a provider (prefers admin users, then any user).
-    /// Used as a fallback when an embed key has no dedicated API key.
-    pub async fn get_any_api_key_for_provider(&self, provider: &str) -> Result<Option<String>> {
+    pub async fn get_credentials(
+        &self,
+        user_id: &str,
+        provider: &str,
+    ) -> Result<Option<ProviderCredentials>> {
         let row = sqlx::query(
-            "SELECT k.api_key FROM user_api_keys k
-             JOIN users u ON u.id = k.user_id
-             WHERE k.provider = $1
-             ORDER BY CASE u.role WHEN 'admin' THEN 0 ELSE 1 END
-             LIMIT 1",
+            "SELECT api_key, base_url FROM user_api_keys WHERE user_id = $1 AND provider = $2",
         )
+        .bind(user_id)
         .bind(provider)
         .fetch_optional(&self.pool)
         .await
-        .context("Failed to query fallback API key")?;
+        .context("Failed to query API key")?;
 
-        Ok(row.map(|r| r.get("api_key")))
+        Ok(row.map(|r| ProviderCredentials {
+            api_key: r.get("api_key"),
+            base_url: r.get("base_url"),
+        }))
     }
 
     pub async fn list_api_keys(&self, user_id: &str) -> Result<Vec<ApiKeyEntry>> {
-        let rows = sqlx::query(
-            "SELECT id, provider, to_char(created_at, 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') AS created_at
-             FROM user_api_keys WHERE user_id = $1 ORDER BY provider",
-        )
+        let rows = sqlx::query(&format!(
+            "SELECT {API_KEY_COLS} FROM user_api_keys WHERE user_id = $1 ORDER BY provider"
+        ))
         .bind(user_id)
         .fetch_all(&self.pool)
         .await
         .context("Failed to list API keys")?;
 
-        let entries = rows
-            .iter()
-            .map(|row| ApiKeyEntry {
-                id: row.get("id"),
-                provider: row.get("provider"),
-                created_at: row.get("created_at"),
-            })
-            .collect();
-
-        Ok(entries)
+        Ok(rows.iter().map(map_api_key_row).collect())
     }
 
     pub async fn delete_api_key(&self, user_id: &str, provider: &str) -> Result<()> {
@@ -129,7 +200,8 @@ impl SettingsRepository {
     // ── LLM Preferences ──────────────────────────────────────
     pub async fn get_preferences(&self, user_id: &str) -> Result<Option<LlmPreferences>> {
         let row = sqlx::query(
-            "SELECT preferred_provider, preferred_model, preferred_embedding_model, system_prompt
+            "SELECT preferred_provider, preferred_model, preferred_embedding_model, system_prompt,
+                    fallback_providers
              FROM user_llm_preferences WHERE user_id = $1",
         )
         .bind(user_id)
@@ -142,25 +214,29 @@ impl SettingsRepository {
             preferred_model: r.get("preferred_model"),
             preferred_embedding_model: r.get("preferred_embedding_model"),
             system_prompt: r.get("system_prompt"),
+            fallback_providers: r.get("fallback_providers"),
         }))
     }
 
     pub async fn set_preferences(&self, user_id: &str, prefs: &LlmPreferences) -> Result<()> {
         sqlx::query(
             "INSERT INTO user_llm_preferences
-                 (user_id, preferred_provider, preferred_model, preferred_embedding_model, system_prompt)
-             VALUES ($1, $2, $3, $4, $5)
+                 (user_id, preferred_provider, preferred_model, preferred_embedding_model, system_prompt,
+                  fallback_providers)
+             VALUES ($1, $2, $3, $4, $5, $6)
              ON CONFLICT(user_id) DO UPDATE SET
                  preferred_provider      = $2,
                  preferred_model         = $3,
                  preferred_embedding_model = $4,
-                 system_prompt           = $5",
+                 system_prompt           = $5,
+                 fallback_providers      = $6",
         )
         .bind(user_id)
         .bind(&prefs.preferred_provider)
         .bind(&prefs.preferred_model)
         .bind(&prefs.preferred_embedding_model)
         .bind(&prefs.system_prompt)
+        .bind(&prefs.fallback_providers)
         .execute(&self.pool)
         .await
         .context("Failed to upsert preferences")?;
@@ -168,3 +244,44 @@ impl SettingsRepository {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_or_keyless_for_ollama_without_stored_key() {
+        let creds = ProviderCredentials::or_keyless(None, "ollama").unwrap();
+        assert!(creds.api_key.is_empty());
+        assert!(creds.base_url.is_none());
+    }
+
+    #[test]
+    fn test_or_keyless_requires_key_for_other_providers() {
+        assert!(ProviderCredentials::or_keyless(None, "openai").is_none());
+
+        let stored = ProviderCredentials {
+            api_key: "sk-test".to_string(),
+            base_url: None,
+        };
+        let creds = ProviderCredentials::or_keyless(Some(stored), "openai").unwrap();
+        assert_eq!(creds.api_key, "sk-test");
+    }
+
+    #[test]
+    fn test_key_preview() {
+        assert_eq!(key_preview("sk-proj-abcdefghijklmn9xQz"), "sk-p...9xQz");
+        assert_eq!(key_preview("short-key"), "****");
+        assert_eq!(key_preview(""), "****");
+    }
+
+    #[test]
+    fn test_stored_ollama_base_url_is_kept() {
+        let stored = ProviderCredentials {
+            api_key: String::new(),
+            base_url: Some("http://gpu-box:11434".to_string()),
+        };
+        let creds = ProviderCredentials::or_keyless(Some(stored), "ollama").unwrap();
+        assert_eq!(creds.base_url.as_deref(), Some("http://gpu-box:11434"));
+    }
+}
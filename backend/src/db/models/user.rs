@@ -3,6 +3,14 @@ use serde::{Deserialize, Serialize};
 use sqlx::{PgPool, Row};
 use uuid::Uuid;
 
+use super::settings::LlmPreferences;
+use crate::services::email::normalize_email;
+use chrono::{DateTime, Utc};
+
+/// Advisory lock key held while creating the first admin ("ragadmn0"), so two
+/// setup requests can't both find the install empty.
+const FIRST_ADMIN_LOCK_KEY: i64 = 0x7261_6761_646d_6e30;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct User {
     pub id: String,
@@ -11,8 +19,10 @@ pub struct User {
     #[serde(skip_serializing)]
     pub password_hash: String,
     pub role: UserRole,
-    pub created_at: String,
-    pub updated_at: String,
+    #[serde(with = "crate::db::timestamp")]
+    pub created_at: DateTime<Utc>,
+    #[serde(with = "crate::db::timestamp")]
+    pub updated_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -25,6 +35,22 @@ pub enum UserRole {
 }
 
 impl UserRole {
+    /// Every role, least privileged first.
+    pub const ALL: [UserRole; 3] = [UserRole::User, UserRole::Maintainer, UserRole::Admin];
+
+    /// What the role may do, for display when choosing a role.
+    pub fn description(&self) -> &'static str {
+        match self {
+            UserRole::User => "Chat with the knowledge base and manage their own conversations and API keys.",
+            UserRole::Maintainer => {
+                "Everything a user can do, plus uploading documents, running crawls and managing embed keys."
+            }
+            UserRole::Admin => {
+                "Full access, including user management, organization settings and conversation logs."
+            }
+        }
+    }
+
     pub fn level(&self) -> u8 {
         match self {
             UserRole::User => 0,
@@ -61,6 +87,19 @@ impl TryFrom<&str> for UserRole {
     }
 }
 
+/// What the app shows about a user right after login, gathered in one query.
+#[derive(Debug, Clone)]
+pub struct UserOverview {
+    /// Conversations that are neither archived nor deleted.
+    pub conversations: i64,
+    pub documents: i64,
+    pub has_own_api_key: bool,
+    /// Any organization key exists, whether or not users may fall back to it.
+    pub has_organization_api_key: bool,
+    /// `None` until the user saves preferences.
+    pub preferences: Option<LlmPreferences>,
+}
+
 #[derive(Clone)]
 pub struct UserRepository {
     pool: PgPool,
@@ -80,6 +119,7 @@ impl UserRepository {
     ) -> Result<User> {
         let id = Uuid::new_v4().to_string();
         let now = chrono::Utc::now();
+        let email = normalize_email(email);
 
         sqlx::query(
             "INSERT INTO users (id, username, email, password_hash, role, created_at, updated_at)
@@ -87,7 +127,7 @@ impl UserRepository {
         )
         .bind(&id)
         .bind(username)
-        .bind(email)
+        .bind(&email)
         .bind(password_hash)
         .bind(role.to_string())
         .bind(now)
@@ -99,22 +139,70 @@ impl UserRepository {
         Ok(User {
             id,
             username: username.to_string(),
-            email: email.to_string(),
+            email,
             password_hash: password_hash.to_string(),
             role: role.clone(),
-            created_at: now.to_rfc3339(),
-            updated_at: now.to_rfc3339(),
+            created_at: now,
+            updated_at: now,
         })
     }
 
+    /// Create the admin account of a fresh install: only while there are no
+    /// accounts yet, and never failing on an account a concurrent request or
+    /// start created. Returns the new user, or `None` if there already was one.
+    pub async fn create_first_admin(&self, username: &str, email: &str, password_hash: &str) -> Result<Option<User>> {
+        let id = Uuid::new_v4().to_string();
+        let now = chrono::Utc::now();
+        let email = normalize_email(email);
+
+        let mut tx = self.pool.begin().await.context("Failed to start transaction")?;
+        // Checking and inserting under one lock: without it two requests could
+        // each see no accounts and create two admins with different names
+        sqlx::query("SELECT pg_advisory_xact_lock($1)")
+            .bind(FIRST_ADMIN_LOCK_KEY)
+            .execute(&mut *tx)
+            .await
+            .context("Failed to lock first admin creation")?;
+        let inserted = sqlx::query(
+            "INSERT INTO users (id, username, email, password_hash, role, created_at, updated_at)
+             SELECT $1, $2, $3, $4, $5, $6, $6
+             WHERE NOT EXISTS (SELECT 1 FROM users WHERE id <> '__widget__')
+             ON CONFLICT DO NOTHING",
+        )
+        .bind(&id)
+        .bind(username)
+        .bind(&email)
+        .bind(password_hash)
+        .bind(UserRole::Admin.to_string())
+        .bind(now)
+        .execute(&mut *tx)
+        .await
+        .context("Failed to insert admin user")?;
+        tx.commit().await.context("Failed to commit admin user")?;
+        if inserted.rows_affected() == 0 {
+            return Ok(None);
+        }
+
+        Ok(Some(User {
+            id,
+            username: username.to_string(),
+            email,
+            password_hash: password_hash.to_string(),
+            role: UserRole::Admin,
+            created_at: now,
+            updated_at: now,
+        }))
+    }
+
+    /// The account with `email`, in any case and with surrounding whitespace.
     pub async fn find_by_email(&self, email: &str) -> Result<Option<User>> {
         let row = sqlx::query(
             "SELECT id, username, email, password_hash, role,
-                    to_char(created_at, 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') AS created_at,
-                    to_char(updated_at, 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') AS updated_at
-             FROM users WHERE email = $1",
+                    created_at,
+                    updated_at
+             FROM users WHERE lower(email) = $1",
         )
-        .bind(email)
+        .bind(normalize_email(email))
         .fetch_optional(&self.pool)
         .await
         .context("Failed to query user by email")?;
@@ -125,8 +213,8 @@ impl UserRepository {
     pub async fn find_by_id(&self, id: &str) -> Result<Option<User>> {
         let row = sqlx::query(
             "SELECT id, username, email, password_hash, role,
-                    to_char(created_at, 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') AS created_at,
-                    to_char(updated_at, 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') AS updated_at
+                    created_at,
+                    updated_at
              FROM users WHERE id = $1",
         )
         .bind(id)
@@ -140,15 +228,23 @@ impl UserRepository {
     pub async fn find_all(&self) -> Result<Vec<User>> {
         let rows = sqlx::query(
             "SELECT id, username, email, password_hash, role,
-                    to_char(created_at, 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') AS created_at,
-                    to_char(updated_at, 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') AS updated_at
+                    created_at,
+                    updated_at
              FROM users ORDER BY created_at DESC",
         )
         .fetch_all(&self.pool)
         .await
         .context("Failed to query users")?;
 
-        rows.iter().map(|r| map_row(r)).collect()
+        rows.iter().map(map_row).collect()
+    }
+
+    pub async fn emails_by_role(&self, role: &UserRole) -> Result<Vec<String>> {
+        sqlx::query_scalar::<_, String>("SELECT email FROM users WHERE role = $1 ORDER BY created_at")
+            .bind(role.to_string())
+            .fetch_all(&self.pool)
+            .await
+            .context("Failed to query user emails by role")
     }
 
     pub async fn update_role(&self, id: &str, role: &UserRole) -> Result<()> {
@@ -175,14 +271,59 @@ impl UserRepository {
         Ok(())
     }
 
+    pub async fn overview(&self, user_id: &str) -> Result<UserOverview> {
+        let row = sqlx::query(
+            "SELECT
+                 (SELECT COUNT(*) FROM conversations
+                  WHERE user_id = $1 AND deleted_at IS NULL AND archived_at IS NULL) AS conversations,
+                 (SELECT COUNT(*) FROM documents WHERE user_id = $1) AS documents,
+                 EXISTS (SELECT 1 FROM user_api_keys WHERE user_id = $1) AS has_own_api_key,
+                 EXISTS (SELECT 1 FROM admin_api_keys) AS has_organization_api_key,
+                 p.preferred_provider, p.preferred_model, p.preferred_embedding_model, p.system_prompt,
+                 p.fallback_providers
+             FROM (SELECT 1) AS one
+             LEFT JOIN user_llm_preferences p ON p.user_id = $1",
+        )
+        .bind(user_id)
+        .fetch_one(&self.pool)
+        .await
+        .context("Failed to query user overview")?;
+
+        let preferred_provider: Option<String> = row.get("preferred_provider");
+        Ok(UserOverview {
+            conversations: row.get("conversations"),
+            documents: row.get("documents"),
+            has_own_api_key: row.get("has_own_api_key"),
+            has_organization_api_key: row.get("has_organization_api_key"),
+            preferences: preferred_provider.map(|preferred_provider| LlmPreferences {
+                preferred_provider,
+                preferred_model: row.get("preferred_model"),
+                preferred_embedding_model: row.get("preferred_embedding_model"),
+                system_prompt: row.get("system_prompt"),
+                fallback_providers: row.get("fallback_providers"),
+            }),
+        })
+    }
+
+    /// Accounts, not counting the system user widget conversations belong to.
     pub async fn count(&self) -> Result<i64> {
-        let count = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM users")
+        let count = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM users WHERE id <> '__widget__'")
             .fetch_one(&self.pool)
             .await
             .context("Failed to count users")?;
 
         Ok(count)
     }
+
+    pub async fn count_by_role(&self, role: &UserRole) -> Result<i64> {
+        let count = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM users WHERE role = $1")
+            .bind(role.to_string())
+            .fetch_one(&self.pool)
+            .await
+            .context("Failed to count users by role")?;
+
+        Ok(count)
+    }
 }
 
 fn map_row(row: &sqlx::postgres::PgRow) -> Result<User> {
@@ -0,0 +1,103 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use sqlx::{PgPool, Row};
+use chrono::{DateTime, Utc};
+
+/// A single page ingested on its own, outside a crawl. Its chunks are stored
+/// with source type `page` and the URL as source ID, so fetching it again
+/// replaces them.
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct WebPage {
+    pub url: String,
+    pub user_id: String,
+    pub title: Option<String>,
+    /// Qdrant collection holding the page's vectors.
+    pub vector_collection: String,
+    pub last_job_id: Option<String>,
+    #[serde(with = "crate::db::timestamp")]
+    pub created_at: DateTime<Utc>,
+    #[serde(with = "crate::db::timestamp")]
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Clone)]
+pub struct WebPageRepository {
+    pool: PgPool,
+}
+
+const SELECT_COLS: &str = "url, user_id, title, vector_collection, last_job_id,
+     created_at,
+     updated_at";
+
+fn map_row(row: &sqlx::postgres::PgRow) -> WebPage {
+    WebPage {
+        url: row.get("url"),
+        user_id: row.get("user_id"),
+        title: row.get("title"),
+        vector_collection: row.get("vector_collection"),
+        last_job_id: row.get("last_job_id"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+    }
+}
+
+impl WebPageRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Record the page as fetched by `job_id`, replacing any earlier record of
+    /// the same URL.
+    pub async fn upsert(
+        &self,
+        url: &str,
+        user_id: &str,
+        title: Option<&str>,
+        vector_collection: &str,
+        job_id: &str,
+    ) -> Result<WebPage> {
+        let row = sqlx::query(&format!(
+            "INSERT INTO web_pages (url, user_id, title, vector_collection, last_job_id)
+             VALUES ($1, $2, $3, $4, $5)
+             ON CONFLICT (url) DO UPDATE SET
+                 user_id = EXCLUDED.user_id,
+                 title = EXCLUDED.title,
+                 vector_collection = EXCLUDED.vector_collection,
+                 last_job_id = EXCLUDED.last_job_id,
+                 updated_at = NOW()
+             RETURNING {SELECT_COLS}"
+        ))
+        .bind(url)
+        .bind(user_id)
+        .bind(title)
+        .bind(vector_collection)
+        .bind(job_id)
+        .fetch_one(&self.pool)
+        .await
+        .context("Failed to save web page")?;
+
+        Ok(map_row(&row))
+    }
+
+    pub async fn find_by_url(&self, url: &str) -> Result<Option<WebPage>> {
+        let row = sqlx::query(&format!("SELECT {SELECT_COLS} FROM web_pages WHERE url = $1"))
+            .bind(url)
+            .fetch_optional(&self.pool)
+            .await
+            .context("Failed to query web page")?;
+
+        Ok(row.as_ref().map(map_row))
+    }
+
+    /// Returns false if the page was never ingested.
+    pub async fn delete(&self, url: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM web_pages WHERE url = $1")
+            .bind(url)
+            .execute(&self.pool)
+            .await
+            .context("Failed to delete web page")?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
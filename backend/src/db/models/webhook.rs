@@ -0,0 +1,267 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use sqlx::{PgPool, Row};
+
+use crate::services::secrets::SecretCipher;
+use chrono::{DateTime, Utc};
+
+/// An admin-registered endpoint that receives event notifications. The
+/// signing secret is never serialized after creation.
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Webhook {
+    pub id: String,
+    pub url: String,
+    /// Events the webhook receives; empty means all of them.
+    pub events: Vec<String>,
+    pub enabled: bool,
+    pub created_by: Option<String>,
+    #[serde(with = "crate::db::timestamp")]
+    pub created_at: DateTime<Utc>,
+    #[serde(with = "crate::db::timestamp")]
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Where and how to deliver one event, with the decrypted secret.
+#[derive(Debug, Clone)]
+pub struct WebhookTarget {
+    pub id: String,
+    pub url: String,
+    pub secret: String,
+}
+
+/// One attempt at delivering an event to a webhook.
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct WebhookDelivery {
+    pub id: i64,
+    pub webhook_id: String,
+    /// Shared by every attempt at delivering the same event.
+    pub delivery_id: String,
+    pub event: String,
+    pub attempt: i32,
+    /// Absent when no response was received.
+    pub status_code: Option<i32>,
+    pub success: bool,
+    pub error: Option<String>,
+    pub duration_ms: i64,
+    #[serde(with = "crate::db::timestamp")]
+    pub created_at: DateTime<Utc>,
+}
+
+/// A delivery attempt to record.
+pub struct NewWebhookDelivery<'a> {
+    pub webhook_id: &'a str,
+    pub delivery_id: &'a str,
+    pub event: &'a str,
+    pub attempt: u32,
+    pub status_code: Option<u16>,
+    pub success: bool,
+    pub error: Option<&'a str>,
+    pub duration_ms: i64,
+}
+
+/// Changes to a webhook; `None` leaves a field as it is.
+#[derive(Debug, Default)]
+pub struct WebhookUpdate {
+    pub url: Option<String>,
+    pub events: Option<Vec<String>>,
+    pub enabled: Option<bool>,
+    pub secret: Option<String>,
+}
+
+/// Webhooks and their delivery log, with secrets encrypted at rest.
+#[derive(Clone)]
+pub struct WebhookRepository {
+    pool: PgPool,
+    cipher: SecretCipher,
+}
+
+const SELECT_COLS: &str = "id, url, events, enabled, created_by,
+     created_at,
+     updated_at";
+
+fn map_row(row: &sqlx::postgres::PgRow) -> Webhook {
+    Webhook {
+        id: row.get("id"),
+        url: row.get("url"),
+        events: row.get("events"),
+        enabled: row.get("enabled"),
+        created_by: row.get("created_by"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+    }
+}
+
+impl WebhookRepository {
+    pub fn new(pool: PgPool, cipher: SecretCipher) -> Self {
+        Self { pool, cipher }
+    }
+
+    pub async fn create(
+        &self,
+        url: &str,
+        secret: &str,
+        events: &[String],
+        enabled: bool,
+        created_by: &str,
+    ) -> Result<Webhook> {
+        let id = uuid::Uuid::new_v4().to_string();
+        let encrypted = self.cipher.encrypt(secret)?;
+
+        let row = sqlx::query(&format!(
+            "INSERT INTO webhooks (id, url, secret_encrypted, events, enabled, created_by)
+             VALUES ($1, $2, $3, $4, $5, $6)
+             RETURNING {SELECT_COLS}"
+        ))
+        .bind(&id)
+        .bind(url)
+        .bind(&encrypted)
+        .bind(events)
+        .bind(enabled)
+        .bind(created_by)
+        .fetch_one(&self.pool)
+        .await
+        .context("Failed to create webhook")?;
+
+        Ok(map_row(&row))
+    }
+
+    pub async fn list(&self) -> Result<Vec<Webhook>> {
+        let rows = sqlx::query(&format!(
+            "SELECT {SELECT_COLS} FROM webhooks ORDER BY created_at"
+        ))
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to list webhooks")?;
+
+        Ok(rows.iter().map(map_row).collect())
+    }
+
+    pub async fn find(&self, id: &str) -> Result<Option<Webhook>> {
+        let row = sqlx::query(&format!("SELECT {SELECT_COLS} FROM webhooks WHERE id = $1"))
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+            .context("Failed to query webhook")?;
+
+        Ok(row.as_ref().map(map_row))
+    }
+
+    pub async fn update(&self, id: &str, update: &WebhookUpdate) -> Result<Option<Webhook>> {
+        let secret = update
+            .secret
+            .as_deref()
+            .map(|s| self.cipher.encrypt(s))
+            .transpose()?;
+
+        let row = sqlx::query(&format!(
+            "UPDATE webhooks
+             SET url = COALESCE($2, url),
+                 events = COALESCE($3, events),
+                 enabled = COALESCE($4, enabled),
+                 secret_encrypted = COALESCE($5, secret_encrypted),
+                 updated_at = NOW()
+             WHERE id = $1
+             RETURNING {SELECT_COLS}"
+        ))
+        .bind(id)
+        .bind(&update.url)
+        .bind(&update.events)
+        .bind(update.enabled)
+        .bind(&secret)
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to update webhook")?;
+
+        Ok(row.as_ref().map(map_row))
+    }
+
+    pub async fn delete(&self, id: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM webhooks WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to delete webhook")?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Enabled webhooks subscribed to `event`, with their secrets decrypted.
+    pub async fn targets_for(&self, event: &str) -> Result<Vec<WebhookTarget>> {
+        let rows = sqlx::query(
+            "SELECT id, url, secret_encrypted FROM webhooks
+             WHERE enabled AND (cardinality(events) = 0 OR $1 = ANY(events))",
+        )
+        .bind(event)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to list webhooks for event")?;
+
+        rows.iter()
+            .map(|r| {
+                let encrypted: String = r.get("secret_encrypted");
+                Ok(WebhookTarget {
+                    id: r.get("id"),
+                    url: r.get("url"),
+                    secret: self.cipher.decrypt(&encrypted)?,
+                })
+            })
+            .collect()
+    }
+
+    pub async fn record_delivery(&self, delivery: &NewWebhookDelivery<'_>) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO webhook_deliveries
+                 (webhook_id, delivery_id, event, attempt, status_code, success, error, duration_ms)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+        )
+        .bind(delivery.webhook_id)
+        .bind(delivery.delivery_id)
+        .bind(delivery.event)
+        .bind(delivery.attempt as i32)
+        .bind(delivery.status_code.map(i32::from))
+        .bind(delivery.success)
+        .bind(delivery.error)
+        .bind(delivery.duration_ms)
+        .execute(&self.pool)
+        .await
+        .context("Failed to record webhook delivery")?;
+
+        Ok(())
+    }
+
+    /// Most recent delivery attempts first.
+    pub async fn list_deliveries(&self, webhook_id: &str, limit: i64) -> Result<Vec<WebhookDelivery>> {
+        let rows = sqlx::query(
+            "SELECT id, webhook_id, delivery_id, event, attempt, status_code, success, error,
+                    duration_ms,
+                    created_at
+             FROM webhook_deliveries
+             WHERE webhook_id = $1
+             ORDER BY id DESC
+             LIMIT $2",
+        )
+        .bind(webhook_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to list webhook deliveries")?;
+
+        Ok(rows
+            .iter()
+            .map(|r| WebhookDelivery {
+                id: r.get("id"),
+                webhook_id: r.get("webhook_id"),
+                delivery_id: r.get("delivery_id"),
+                event: r.get("event"),
+                attempt: r.get("attempt"),
+                status_code: r.get("status_code"),
+                success: r.get("success"),
+                error: r.get("error"),
+                duration_ms: r.get("duration_ms"),
+                created_at: r.get("created_at"),
+            })
+            .collect())
+    }
+}
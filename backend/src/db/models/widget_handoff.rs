@@ -0,0 +1,157 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use sqlx::{PgPool, Row};
+use std::time::Duration;
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+
+/// Statuses a handoff can be in.
+pub const HANDOFF_STATUSES: &[&str] = &["open", "closed"];
+
+/// A widget visitor's request to be contacted by a person.
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct WidgetHandoff {
+    pub id: String,
+    pub conversation_id: String,
+    pub embed_key_id: String,
+    pub embed_key_name: String,
+    pub session_id: String,
+    pub email: String,
+    pub message: String,
+    /// `open` until someone has followed up, then `closed`.
+    pub status: String,
+    #[serde(with = "crate::db::timestamp")]
+    pub created_at: DateTime<Utc>,
+    #[serde(with = "crate::db::timestamp")]
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Clone)]
+pub struct WidgetHandoffRepository {
+    pool: PgPool,
+}
+
+const SELECT_COLS: &str = "h.id, h.conversation_id, h.embed_key_id, k.name AS embed_key_name,
+     h.session_id, h.email, h.message, h.status,
+     h.created_at,
+     h.updated_at";
+
+fn map_row(row: &sqlx::postgres::PgRow) -> WidgetHandoff {
+    WidgetHandoff {
+        id: row.get("id"),
+        conversation_id: row.get("conversation_id"),
+        embed_key_id: row.get("embed_key_id"),
+        embed_key_name: row.get("embed_key_name"),
+        session_id: row.get("session_id"),
+        email: row.get("email"),
+        message: row.get("message"),
+        status: row.get("status"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+    }
+}
+
+impl WidgetHandoffRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn create(
+        &self,
+        conversation_id: &str,
+        embed_key_id: &str,
+        session_id: &str,
+        email: &str,
+        message: &str,
+    ) -> Result<WidgetHandoff> {
+        let id = Uuid::new_v4().to_string();
+        sqlx::query(
+            "INSERT INTO widget_handoffs (id, conversation_id, embed_key_id, session_id, email, message)
+             VALUES ($1, $2, $3, $4, $5, $6)",
+        )
+        .bind(&id)
+        .bind(conversation_id)
+        .bind(embed_key_id)
+        .bind(session_id)
+        .bind(email)
+        .bind(message)
+        .execute(&self.pool)
+        .await
+        .context("Failed to create widget handoff")?;
+
+        self.find_by_id(&id)
+            .await?
+            .context("Widget handoff vanished after insert")
+    }
+
+    pub async fn find_by_id(&self, id: &str) -> Result<Option<WidgetHandoff>> {
+        let row = sqlx::query(&format!(
+            "SELECT {SELECT_COLS} FROM widget_handoffs h
+             JOIN embed_keys k ON k.id = h.embed_key_id
+             WHERE h.id = $1"
+        ))
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to query widget handoff")?;
+
+        Ok(row.as_ref().map(map_row))
+    }
+
+    /// Newest first, optionally only those in `status`.
+    pub async fn list(&self, status: Option<&str>, limit: i64) -> Result<Vec<WidgetHandoff>> {
+        let rows = sqlx::query(&format!(
+            "SELECT {SELECT_COLS} FROM widget_handoffs h
+             JOIN embed_keys k ON k.id = h.embed_key_id
+             WHERE ($1::TEXT IS NULL OR h.status = $1)
+             ORDER BY h.created_at DESC
+             LIMIT $2"
+        ))
+        .bind(status)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to list widget handoffs")?;
+
+        Ok(rows.iter().map(map_row).collect())
+    }
+
+    pub async fn update_status(&self, id: &str, status: &str) -> Result<Option<WidgetHandoff>> {
+        let result = sqlx::query(
+            "UPDATE widget_handoffs SET status = $1, updated_at = NOW() WHERE id = $2",
+        )
+        .bind(status)
+        .bind(id)
+        .execute(&self.pool)
+        .await
+        .context("Failed to update widget handoff status")?;
+
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+        self.find_by_id(id).await
+    }
+
+    /// Handoffs a widget session submitted within `window`, for rate limiting.
+    pub async fn count_recent_for_session(
+        &self,
+        embed_key_id: &str,
+        session_id: &str,
+        window: Duration,
+    ) -> Result<i64> {
+        let count = sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM widget_handoffs
+             WHERE embed_key_id = $1 AND session_id = $2
+               AND created_at > NOW() - make_interval(secs => $3)",
+        )
+        .bind(embed_key_id)
+        .bind(session_id)
+        .bind(window.as_secs_f64())
+        .fetch_one(&self.pool)
+        .await
+        .context("Failed to count recent widget handoffs")?;
+
+        Ok(count)
+    }
+}
@@ -2,14 +2,27 @@ use anyhow::{Context, Result};
 use serde::Serialize;
 use sqlx::PgPool;
 
+use crate::db::models::conversation::DELETED_RETENTION_DAYS;
+use chrono::{DateTime, Utc};
+
 #[derive(Debug, Clone, Serialize)]
 pub struct WidgetSession {
     pub id: String,
     pub embed_key_id: String,
     pub session_id: String,
     pub message_count: i32,
-    pub created_at: String,
-    pub last_message_at: String,
+    #[serde(with = "crate::db::timestamp")]
+    pub created_at: DateTime<Utc>,
+    #[serde(with = "crate::db::timestamp")]
+    pub last_message_at: DateTime<Utc>,
+}
+
+/// What a purge of inactive widget sessions removed.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct WidgetSessionPurge {
+    pub sessions: u64,
+    pub conversations: u64,
 }
 
 #[derive(Clone)]
@@ -24,13 +37,13 @@ impl WidgetSessionRepository {
 
     pub async fn get_or_create(&self, embed_key_id: &str, session_id: &str) -> Result<WidgetSession> {
         let id = uuid::Uuid::new_v4().to_string();
-        let row = sqlx::query_as::<_, (String, String, String, i32, String, String)>(
+        let row = sqlx::query_as::<_, (String, String, String, i32, DateTime<Utc>, DateTime<Utc>)>(
             "INSERT INTO widget_sessions (id, embed_key_id, session_id)
              VALUES ($1, $2, $3)
              ON CONFLICT (embed_key_id, session_id) DO UPDATE SET embed_key_id = widget_sessions.embed_key_id
              RETURNING id, embed_key_id, session_id, message_count,
-                to_char(created_at, 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"'),
-                to_char(last_message_at, 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"')"
+                created_at,
+                last_message_at"
         )
         .bind(&id)
         .bind(embed_key_id)
@@ -49,19 +62,42 @@ impl WidgetSessionRepository {
         })
     }
 
-    pub async fn increment_message_count(&self, embed_key_id: &str, session_id: &str) -> Result<i32> {
+    /// Counts a message against the session, or returns `None` without counting it
+    /// when the session has already sent `limit` messages.
+    pub async fn increment_message_count(
+        &self,
+        embed_key_id: &str,
+        session_id: &str,
+        limit: i32,
+    ) -> Result<Option<i32>> {
         let row = sqlx::query_as::<_, (i32,)>(
             "UPDATE widget_sessions SET message_count = message_count + 1, last_message_at = NOW()
-             WHERE embed_key_id = $1 AND session_id = $2
+             WHERE embed_key_id = $1 AND session_id = $2 AND message_count < $3
              RETURNING message_count"
         )
         .bind(embed_key_id)
         .bind(session_id)
-        .fetch_one(&self.pool)
+        .bind(limit)
+        .fetch_optional(&self.pool)
         .await
         .context("Failed to increment widget session message count")?;
 
-        Ok(row.0)
+        Ok(row.map(|r| r.0))
+    }
+
+    /// Give back a message counted by `increment_message_count` whose send was refused later.
+    pub async fn decrement_message_count(&self, embed_key_id: &str, session_id: &str) -> Result<()> {
+        sqlx::query(
+            "UPDATE widget_sessions SET message_count = GREATEST(message_count - 1, 0)
+             WHERE embed_key_id = $1 AND session_id = $2"
+        )
+        .bind(embed_key_id)
+        .bind(session_id)
+        .execute(&self.pool)
+        .await
+        .context("Failed to decrement widget session message count")?;
+
+        Ok(())
     }
 
     pub async fn get_message_count(&self, embed_key_id: &str, session_id: &str) -> Result<i32> {
@@ -79,4 +115,91 @@ impl WidgetSessionRepository {
 
         Ok(row.0)
     }
+
+    /// Delete sessions with no message in the last `retention_days` days. Their
+    /// conversations stay for the admin logs unless `delete_conversations` is set.
+    pub async fn purge_inactive(
+        &self,
+        retention_days: u32,
+        delete_conversations: bool,
+    ) -> Result<WidgetSessionPurge> {
+        let days = i32::try_from(retention_days).unwrap_or(i32::MAX);
+        let mut tx = self.pool.begin().await.context("Failed to begin transaction")?;
+
+        let conversations = if delete_conversations {
+            sqlx::query(
+                "DELETE FROM conversations c USING widget_sessions s
+                 WHERE c.source = 'widget'
+                   AND c.embed_key_id = s.embed_key_id AND c.session_id = s.session_id
+                   AND s.last_message_at < NOW() - make_interval(days => $1)",
+            )
+            .bind(days)
+            .execute(&mut *tx)
+            .await
+            .context("Failed to delete conversations of inactive widget sessions")?
+            .rows_affected()
+        } else {
+            0
+        };
+
+        let sessions = sqlx::query(
+            "DELETE FROM widget_sessions WHERE last_message_at < NOW() - make_interval(days => $1)",
+        )
+        .bind(days)
+        .execute(&mut *tx)
+        .await
+        .context("Failed to delete inactive widget sessions")?
+        .rows_affected();
+
+        tx.commit().await.context("Failed to commit widget session purge")?;
+
+        Ok(WidgetSessionPurge {
+            sessions,
+            conversations,
+        })
+    }
+
+    /// Stale sessions, inactive for `inactive_days`, with no conversations
+    /// left. Sessions whose only conversations are soft-deleted past their
+    /// retention count too, as the conversation purge is about to remove them.
+    fn orphaned_condition() -> String {
+        format!(
+            "s.last_message_at < NOW() - make_interval(days => $1)
+             AND NOT EXISTS (
+                 SELECT 1 FROM conversations c
+                 WHERE c.source = 'widget' AND c.embed_key_id = s.embed_key_id AND c.session_id = s.session_id
+                   AND (c.deleted_at IS NULL OR c.deleted_at >= NOW() - make_interval(days => {DELETED_RETENTION_DAYS}))
+             )"
+        )
+    }
+
+    pub async fn count_orphaned(&self, inactive_days: i32) -> Result<u64> {
+        let row = sqlx::query_as::<_, (i64,)>(&format!(
+            "SELECT COUNT(*) FROM widget_sessions s WHERE {}",
+            Self::orphaned_condition()
+        ))
+        .bind(inactive_days)
+        .fetch_one(&self.pool)
+        .await
+        .context("Failed to count orphaned widget sessions")?;
+
+        Ok(row.0 as u64)
+    }
+
+    /// Delete up to `limit` orphaned sessions (see [`Self::count_orphaned`]).
+    pub async fn delete_orphaned(&self, inactive_days: i32, limit: i64) -> Result<u64> {
+        let result = sqlx::query(&format!(
+            "DELETE FROM widget_sessions WHERE id IN (
+                 SELECT s.id FROM widget_sessions s WHERE {} LIMIT $2
+             )",
+            Self::orphaned_condition()
+        ))
+        .bind(inactive_days)
+        .bind(limit)
+        .execute(&self.pool)
+        .await
+        .context("Failed to delete orphaned widget sessions")?;
+
+        Ok(result.rows_affected())
+    }
 }
@@ -0,0 +1,66 @@
+use anyhow::{Context, Result};
+use sqlx::{Connection, PgConnection, PgPool};
+use std::time::Instant;
+
+/// Advisory lock key every replica takes before migrating or seeding ("ragpipe0").
+const STARTUP_LOCK_KEY: i64 = 0x7261_6770_6970_6530;
+
+/// A session-level Postgres advisory lock that lets one replica at a time run
+/// migrations and seeding while the others wait. It is held on a connection
+/// of its own, outside the pool, so it is released by [`StartupLock::release`]
+/// or, should the replica fail first, when that connection closes.
+pub struct StartupLock {
+    conn: PgConnection,
+    replica: String,
+}
+
+impl StartupLock {
+    /// Wait until no other replica holds the lock, then take it.
+    pub async fn acquire(pool: &PgPool) -> Result<Self> {
+        let mut conn = pool
+            .acquire()
+            .await
+            .context("Failed to get a connection for the startup lock")?
+            .detach();
+        let replica = replica_name();
+
+        let acquired: bool = sqlx::query_scalar("SELECT pg_try_advisory_lock($1)")
+            .bind(STARTUP_LOCK_KEY)
+            .fetch_one(&mut conn)
+            .await
+            .context("Failed to try the startup lock")?;
+        if !acquired {
+            tracing::info!("Replica {replica} is waiting for another replica to finish migrating and seeding");
+            let started = Instant::now();
+            sqlx::query("SELECT pg_advisory_lock($1)")
+                .bind(STARTUP_LOCK_KEY)
+                .execute(&mut conn)
+                .await
+                .context("Failed to wait for the startup lock")?;
+            tracing::info!("Replica {replica} got the startup lock after {}ms", started.elapsed().as_millis());
+        }
+
+        Ok(Self { conn, replica })
+    }
+
+    /// Name of the replica holding the lock, for logs.
+    pub fn replica(&self) -> &str {
+        &self.replica
+    }
+
+    pub async fn release(mut self) -> Result<()> {
+        sqlx::query("SELECT pg_advisory_unlock($1)")
+            .bind(STARTUP_LOCK_KEY)
+            .execute(&mut self.conn)
+            .await
+            .context("Failed to release the startup lock")?;
+        self.conn.close().await.context("Failed to close the startup lock connection")?;
+        Ok(())
+    }
+}
+
+/// This process as `host/pid`; in a container the host name is its ID.
+fn replica_name() -> String {
+    let host = std::env::var("HOSTNAME").unwrap_or_else(|_| "unknown-host".to_string());
+    format!("{host}/{}", std::process::id())
+}
@@ -0,0 +1,80 @@
+//! Serde helpers for timestamps read from `TIMESTAMPTZ` columns. They are
+//! written as RFC 3339 in UTC with milliseconds, e.g. `2026-10-17T04:01:22.123Z`,
+//! so clients can order events within the same second.
+//!
+//! ```text
+//! #[serde(with = "crate::db::timestamp")]
+//! pub created_at: DateTime<Utc>,
+//! #[serde(with = "crate::db::timestamp::option")]
+//! pub deleted_at: Option<DateTime<Utc>>,
+//! ```
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Deserializer, Serializer};
+
+/// The wire format of a timestamp.
+pub fn format(at: &DateTime<Utc>) -> String {
+    at.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+pub fn serialize<S: Serializer>(at: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&format(at))
+}
+
+/// Accepts any RFC 3339 timestamp, converting it to UTC.
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<DateTime<Utc>, D::Error> {
+    DateTime::<Utc>::deserialize(deserializer)
+}
+
+pub mod option {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(at: &Option<DateTime<Utc>>, serializer: S) -> Result<S::Ok, S::Error> {
+        match at {
+            Some(at) => super::serialize(at, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error> {
+        Option::<DateTime<Utc>>::deserialize(deserializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{FixedOffset, TimeZone};
+    use serde::Serialize;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Event {
+        #[serde(with = "super")]
+        at: DateTime<Utc>,
+        #[serde(with = "super::option")]
+        until: Option<DateTime<Utc>>,
+    }
+
+    #[test]
+    fn test_serializes_utc_with_milliseconds() {
+        let at = Utc.with_ymd_and_hms(2026, 10, 17, 4, 1, 22).unwrap() + chrono::Duration::microseconds(123_456);
+        let json = serde_json::to_value(Event { at, until: None }).unwrap();
+        assert_eq!(json, serde_json::json!({ "at": "2026-10-17T04:01:22.123Z", "until": null }));
+
+        // Whole seconds keep their milliseconds so every timestamp has the same shape
+        let whole = Utc.with_ymd_and_hms(2026, 1, 2, 3, 4, 5).unwrap();
+        assert_eq!(format(&whole), "2026-01-02T03:04:05.000Z");
+    }
+
+    #[test]
+    fn test_renders_other_offsets_in_utc() {
+        let kolkata = FixedOffset::east_opt(5 * 3600 + 1800).unwrap();
+        let local = kolkata.with_ymd_and_hms(2026, 10, 17, 9, 31, 22).unwrap();
+        assert_eq!(format(&local.with_timezone(&Utc)), "2026-10-17T04:01:22.000Z");
+
+        let parsed: Event =
+            serde_json::from_str(r#"{"at": "2026-10-17T09:31:22.5+05:30", "until": "2026-10-17T04:01:22Z"}"#).unwrap();
+        assert_eq!(format(&parsed.at), "2026-10-17T04:01:22.500Z");
+        assert_eq!(parsed.until, Some(Utc.with_ymd_and_hms(2026, 10, 17, 4, 1, 22).unwrap()));
+    }
+}
@@ -1,6 +1,10 @@
 use serde::{Deserialize, Serialize};
 
+use crate::db::models::email_outbox::EmailStatus;
+use crate::db::models::settings::LlmPreferences;
 use crate::db::models::user::{User, UserRole};
+use crate::middleware::auth::CurrentUser;
+use chrono::{DateTime, Utc};
 
 #[derive(Debug, Deserialize)]
 #[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
@@ -17,6 +21,23 @@ pub struct SetupRequest {
     pub password: String,
 }
 
+/// `GET /api/setup/status`.
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SetupStatusResponse {
+    /// No account exists yet, so the first admin can be created.
+    pub needs_setup: bool,
+}
+
+/// The first admin account of a fresh install.
+#[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CreateAdminRequest {
+    pub username: String,
+    pub email: String,
+    pub password: String,
+}
+
 #[derive(Debug, Deserialize)]
 #[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct InviteRequest {
@@ -38,8 +59,10 @@ pub struct UserResponse {
     pub username: String,
     pub email: String,
     pub role: UserRole,
-    pub created_at: String,
-    pub updated_at: String,
+    #[serde(with = "crate::db::timestamp")]
+    pub created_at: DateTime<Utc>,
+    #[serde(with = "crate::db::timestamp")]
+    pub updated_at: DateTime<Utc>,
 }
 
 impl From<User> for UserResponse {
@@ -55,6 +78,36 @@ impl From<User> for UserResponse {
     }
 }
 
+impl From<CurrentUser> for UserResponse {
+    fn from(user: CurrentUser) -> Self {
+        Self {
+            id: user.id,
+            username: user.username,
+            email: user.email,
+            role: user.role,
+            created_at: user.created_at,
+            updated_at: user.updated_at,
+        }
+    }
+}
+
+/// `GET /api/auth/me`: the user plus what the app needs to render its first
+/// screen without further requests.
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct MeResponse {
+    #[serde(flatten)]
+    pub user: UserResponse,
+    /// Conversations that are neither archived nor deleted.
+    pub conversation_count: i64,
+    /// The user's documents; only set for maintainers and admins.
+    pub document_count: Option<i64>,
+    /// The user has a provider key of their own, or may use an organization key.
+    pub has_api_key: bool,
+    /// The saved preferences, or the configured defaults.
+    pub preferences: LlmPreferences,
+}
+
 #[derive(Debug, Deserialize)]
 #[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct UpdateRoleRequest {
@@ -69,6 +122,31 @@ pub struct InviteResponse {
     pub role: UserRole,
     pub used: bool,
     pub setup_link: String,
-    pub expires_at: String,
-    pub created_at: String,
+    /// Delivery of the latest invite email; `null` for invites sent before
+    /// emails went through the outbox.
+    pub email_status: Option<EmailStatus>,
+    #[serde(with = "crate::db::timestamp")]
+    pub expires_at: DateTime<Utc>,
+    #[serde(with = "crate::db::timestamp")]
+    pub created_at: DateTime<Utc>,
+}
+
+/// A role an admin can assign, as listed by `GET /api/admin/roles`.
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct RoleInfo {
+    pub role: UserRole,
+    pub description: String,
+}
+
+/// A token for acting as another user, returned by
+/// `POST /api/admin/users/{user_id}/impersonate`.
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ImpersonationResponse {
+    pub token: String,
+    pub user: UserResponse,
+    pub impersonator_id: String,
+    #[serde(with = "crate::db::timestamp")]
+    pub expires_at: DateTime<Utc>,
 }
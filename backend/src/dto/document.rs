@@ -1,6 +1,8 @@
 use serde::Serialize;
 
 use crate::db::models::document::{Document, DocumentStatus};
+use crate::db::models::document_chunk::DocumentChunk;
+use chrono::{DateTime, Utc};
 
 #[derive(Debug, Serialize)]
 #[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
@@ -11,8 +13,15 @@ pub struct DocumentResponse {
     pub size_bytes: i64,
     pub status: DocumentStatus,
     pub error_message: Option<String>,
-    pub created_at: String,
-    pub processed_at: Option<String>,
+    #[serde(with = "crate::db::timestamp")]
+    pub created_at: DateTime<Utc>,
+    #[serde(with = "crate::db::timestamp::option")]
+    pub processed_at: Option<DateTime<Utc>>,
+    pub embedding_model: Option<String>,
+    pub tags: Vec<String>,
+    pub collection_id: Option<String>,
+    /// The earlier upload under the same filename this document replaces.
+    pub previous_version_id: Option<String>,
 }
 
 impl From<Document> for DocumentResponse {
@@ -26,6 +35,72 @@ impl From<Document> for DocumentResponse {
             error_message: doc.error_message,
             created_at: doc.created_at,
             processed_at: doc.processed_at,
+            embedding_model: doc.embedding_model,
+            tags: doc.tags,
+            collection_id: doc.collection_id,
+            previous_version_id: doc.previous_version_id,
         }
     }
 }
+
+/// An earlier upload of a document.
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DocumentVersion {
+    pub id: String,
+    pub size_bytes: i64,
+    pub status: DocumentStatus,
+    #[serde(with = "crate::db::timestamp")]
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<Document> for DocumentVersion {
+    fn from(doc: Document) -> Self {
+        Self {
+            id: doc.id,
+            size_bytes: doc.size_bytes,
+            status: doc.status,
+            created_at: doc.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DocumentDetailResponse {
+    #[serde(flatten)]
+    pub document: DocumentResponse,
+    /// Earlier uploads under the same filename, newest first.
+    pub versions: Vec<DocumentVersion>,
+    /// Chunks stored for retrieval; 0 until processing has stored some.
+    pub chunk_count: i64,
+}
+
+/// A stored chunk of a document, as it was embedded.
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DocumentChunkResponse {
+    pub chunk_index: i32,
+    pub content: String,
+    /// ID of the chunk's vector in Qdrant.
+    pub qdrant_point_id: String,
+}
+
+impl From<DocumentChunk> for DocumentChunkResponse {
+    fn from(chunk: DocumentChunk) -> Self {
+        Self {
+            chunk_index: chunk.chunk_index,
+            content: chunk.content,
+            qdrant_point_id: chunk.qdrant_point_id,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DocumentChunksResponse {
+    pub chunks: Vec<DocumentChunkResponse>,
+    pub total: i64,
+    pub page: i64,
+    pub per_page: i64,
+}
@@ -16,15 +16,47 @@ pub enum AppError {
     #[error("Validation error: {0}")]
     Validation(String),
 
+    /// The request clashes with existing data, such as a duplicate name.
+    #[error("{0}")]
+    Conflict(String),
+
+    /// Something that is only possible once has been done, such as creating
+    /// the first admin account.
+    #[error("{0}")]
+    Gone(String),
+
     #[error("Feature disabled: {0}")]
     FeatureDisabled(String),
 
     #[error("File too large. Maximum upload size is {0} MB")]
     PayloadTooLarge(usize),
 
+    #[error("Request body too large. Maximum size is {0} KB")]
+    RequestTooLarge(usize),
+
     #[error("Rate limit exceeded")]
     RateLimited,
 
+    /// A reply is still being generated in the conversation. Answered with
+    /// 409 and the `generation_in_progress` code.
+    #[error("A reply is still being generated in this conversation")]
+    GenerationInProgress,
+
+    /// A usage cap, such as an embed key's monthly messages, is used up.
+    /// Answered with 429 and the `quota_exceeded` code.
+    #[error("{0}")]
+    QuotaExceeded(String),
+
+    /// The widget is outside its office hours and set to refuse messages.
+    /// Answered with 403 and the `widget_offline` code; the message is the
+    /// key's offline message.
+    #[error("{0}")]
+    Offline(String),
+
+    /// A service the request depends on, such as the vector store, is down.
+    #[error("{0}")]
+    Unavailable(String),
+
     #[error("Internal error: {0}")]
     Internal(#[from] anyhow::Error),
 }
@@ -34,6 +66,10 @@ pub enum AppError {
 pub struct ErrorResponse {
     error: String,
     status: u16,
+    /// Tells apart errors that share a status, e.g. `quota_exceeded` from a rate limit
+    /// or `generation_in_progress` from other conflicts.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code: Option<&'static str>,
 }
 
 impl IntoResponse for AppError {
@@ -43,9 +79,17 @@ impl IntoResponse for AppError {
             AppError::Forbidden => (StatusCode::FORBIDDEN, self.to_string()),
             AppError::NotFound(msg) => (StatusCode::NOT_FOUND, msg.clone()),
             AppError::Validation(msg) => (StatusCode::BAD_REQUEST, msg.clone()),
-            AppError::FeatureDisabled(msg) => (StatusCode::FORBIDDEN, msg.clone()),
-            AppError::PayloadTooLarge(_) => (StatusCode::PAYLOAD_TOO_LARGE, self.to_string()),
-            AppError::RateLimited => (StatusCode::TOO_MANY_REQUESTS, self.to_string()),
+            AppError::Conflict(msg) => (StatusCode::CONFLICT, msg.clone()),
+            AppError::GenerationInProgress => (StatusCode::CONFLICT, self.to_string()),
+            AppError::Gone(msg) => (StatusCode::GONE, msg.clone()),
+            AppError::FeatureDisabled(msg) | AppError::Offline(msg) => (StatusCode::FORBIDDEN, msg.clone()),
+            AppError::PayloadTooLarge(_) | AppError::RequestTooLarge(_) => {
+                (StatusCode::PAYLOAD_TOO_LARGE, self.to_string())
+            }
+            AppError::RateLimited | AppError::QuotaExceeded(_) => {
+                (StatusCode::TOO_MANY_REQUESTS, self.to_string())
+            }
+            AppError::Unavailable(msg) => (StatusCode::SERVICE_UNAVAILABLE, msg.clone()),
             AppError::Internal(e) => {
                 tracing::error!("Internal error: {e}");
                 (
@@ -55,9 +99,16 @@ impl IntoResponse for AppError {
             }
         };
 
+        let code = match &self {
+            AppError::QuotaExceeded(_) => Some("quota_exceeded"),
+            AppError::GenerationInProgress => Some("generation_in_progress"),
+            AppError::Offline(_) => Some("widget_offline"),
+            _ => None,
+        };
         let body = axum::Json(ErrorResponse {
             error: message,
             status: status.as_u16(),
+            code,
         });
 
         (status, body).into_response()
@@ -1,34 +1,42 @@
 use anyhow::Context;
-use axum::{
-    extract::DefaultBodyLimit,
-    middleware as axum_mw,
-    routing::{delete, get, post, put},
-    Router,
-};
-use axum::http::HeaderName;
-use tower_http::cors::{Any, CorsLayer};
+use axum::{middleware as axum_mw, Router};
+use std::time::Duration;
 use tower_http::services::ServeDir;
+use tower_http::request_id::{MakeRequestUuid, PropagateRequestIdLayer, RequestId, SetRequestIdLayer};
 use tower_http::trace::TraceLayer;
+use tracing_subscriber::util::SubscriberInitExt;
 use tracing_subscriber::EnvFilter;
 
 use rag_backend::config::AppConfig;
-use rag_backend::db::models::user::UserRole;
+use rag_backend::db::startup_lock::StartupLock;
 use rag_backend::db::{connection, migrations};
-use rag_backend::middleware::auth::auth_middleware;
-use rag_backend::middleware::embed_auth::embed_auth_middleware;
-use rag_backend::routes::{admin, admin_audit, admin_config, admin_embed, admin_logs, auth, chat, crawl, documents, health, settings, widget};
-use rag_backend::services::auth_service;
+use rag_backend::middleware::body_limit;
+use rag_backend::middleware::client_ip::parse_proxy_entry;
+use rag_backend::middleware::cors::{api_cors_layer, OriginPolicy};
+use rag_backend::routes;
+use rag_backend::services::{
+    auth_service, conversation_purge, crawl_scheduler, data_export, email_outbox, embed_key_check, jobs,
+    logging, scheduler, vector_cleanup,
+};
 use rag_backend::services::storage::StorageService;
+use rag_backend::services::tasks::{self, RESTART_ERROR};
 use rag_backend::services::vector::VectorService;
 use rag_backend::state::AppState;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    tracing_subscriber::fmt()
-        .with_env_filter(EnvFilter::try_from_default_env().unwrap_or_else(|_| "info".into()))
-        .init();
-
-    let config = AppConfig::load().context("Failed to load configuration")?;
+    // Loaded first so the log format can come from it
+    let mut config = AppConfig::load().context("Failed to load configuration")?;
+    logging::subscriber(
+        config.server.log_format,
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| "info".into()),
+        std::io::stdout,
+    )
+    .init();
+    config.validate()?;
+    if std::env::args().any(|arg| arg == "--skip-vector-validation") {
+        config.qdrant.skip_vector_validation = true;
+    }
     tracing::info!(
         "Configuration loaded (env: {})",
         std::env::var("RUN_ENV").unwrap_or_else(|_| "development".into())
@@ -47,175 +55,73 @@ async fn main() -> anyhow::Result<()> {
         .context("Failed to initialize MinIO storage")?;
     tracing::info!("MinIO storage initialized");
 
-    let vector_service = VectorService::new(&config.qdrant)
+    let vector_service = VectorService::new(&config.qdrant, &config.llm.default_embedding_model)
         .await
         .context("Failed to initialize Qdrant vector service")?;
     tracing::info!("Qdrant vector service initialized");
 
     let state = AppState::new(config.clone(), db_pool, storage, vector_service);
 
-    // Seed admin account on first boot
-    seed_admin(&state).await?;
+    // Replicas starting together seed one at a time; each step is safe to repeat
+    let startup_lock = StartupLock::acquire(&state.db).await?;
+    tracing::info!("Seeding defaults as replica {}", startup_lock.replica());
+
+    // Seed admin account on first boot, unless the first visitor creates it
+    if state.config.auth.seed_admin_from_env {
+        seed_admin(&state).await?;
+    } else if state.user_repo.count().await? == 0 {
+        tracing::info!("No accounts yet: open the app to create the admin account");
+    }
 
     // Seed widget system user for anonymous widget conversations
     seed_widget_user(&state).await?;
 
-    // Seed default provider/model catalogue
+    // Seed providers and models added to the catalogue since the last start
     state
         .admin_config_repo
         .seed_defaults()
         .await
         .context("Failed to seed admin config defaults")?;
+    startup_lock.release().await?;
 
-    // Spawn background task to purge soft-deleted conversations older than 30 days
-    {
-        let conversation_repo = state.conversation_repo.clone();
-        tokio::spawn(async move {
-            let mut interval = tokio::time::interval(std::time::Duration::from_secs(24 * 60 * 60));
-            loop {
-                interval.tick().await;
-                match conversation_repo.hard_delete_expired().await {
-                    Ok(count) if count > 0 => {
-                        tracing::info!("Purged {count} expired soft-deleted conversations");
-                    }
-                    Ok(_) => {}
-                    Err(e) => {
-                        tracing::error!("Failed to purge expired conversations: {e}");
-                    }
-                }
-            }
-        });
+    // Work whose lease lapsed was lost when the process running it stopped
+    recover_interrupted(&state).await?;
+
+    jobs::start_worker(state.clone());
+    crawl_scheduler::start(state.clone());
+    vector_cleanup::start(state.clone());
+    data_export::start(state.clone());
+    email_outbox::start(state.clone());
+
+    register_scheduled_jobs(&state);
+    scheduler::start(state.clone());
+
+    let origin_policy = OriginPolicy::from_config(&config.server.cors_allowed_origins);
+    if origin_policy == OriginPolicy::Any {
+        tracing::warn!("CORS: API allows any origin; restrict server.cors_allowed_origins in production");
+    } else {
+        tracing::info!("CORS: API allows {origin_policy}; widget API allows any origin");
     }
 
-    let cors = CorsLayer::new()
-        .allow_origin(Any)
-        .allow_methods(Any)
-        .allow_headers([
-            axum::http::header::CONTENT_TYPE,
-            axum::http::header::AUTHORIZATION,
-            HeaderName::from_static("x-embed-key"),
-            HeaderName::from_static("x-session-id"),
-        ]);
-
-    let public_routes = Router::new()
-        .route("/api/health", get(health::health_check))
-        .route("/api/auth/login", post(auth::login))
-        .route("/api/auth/setup", post(auth::setup));
-
-    let protected_routes = Router::new()
-        // Auth
-        .route("/api/auth/me", get(auth::me))
-        // Conversations
-        .route("/api/conversations", get(chat::list_conversations).post(chat::create_conversation))
-        .route("/api/conversations/{id}", get(chat::get_conversation).delete(chat::delete_conversation))
-        .route(
-            "/api/conversations/{id}/messages",
-            post(chat::send_message),
-        )
-        // Documents
-        .route("/api/documents", get(documents::list).post(documents::upload))
-        .route("/api/documents/limits", get(documents::upload_limits))
-        .route("/api/documents/{id}", get(documents::get_document).delete(documents::delete_document))
-        .route("/api/documents/rescan", post(documents::rescan))
-        // Crawl
-        .route("/api/crawl", get(crawl::list_crawl_jobs).post(crawl::start_crawl))
-        .route("/api/crawl/{id}", get(crawl::get_crawl_job))
-        // Settings (user-facing — only admin-enabled providers/models)
-        .route("/api/settings/providers", get(settings::list_providers))
-        .route(
-            "/api/settings/providers/{provider_id}/models",
-            get(settings::list_models_for_provider),
-        )
-        .route("/api/settings/api-keys", get(settings::list_api_keys))
-        .route(
-            "/api/settings/api-keys/{provider}",
-            put(settings::set_api_key).delete(settings::delete_api_key),
-        )
-        .route("/api/settings/preferences", get(settings::get_preferences).put(settings::update_preferences))
-        // Admin — User management
-        .route("/api/admin/users", get(admin::list_users))
-        .route(
-            "/api/admin/users/{user_id}/role",
-            put(admin::update_user_role),
-        )
-        .route(
-            "/api/admin/users/{user_id}",
-            delete(admin::delete_user),
-        )
-        .route("/api/admin/invites", get(admin::list_invites).post(admin::invite_user))
-        // Admin — Logs
-        .route("/api/admin/logs", get(admin_logs::list_conversation_logs))
-        .route(
-            "/api/admin/logs/{id}",
-            get(admin_logs::get_conversation_log),
-        )
-        .route(
-            "/api/admin/widget-logs",
-            get(admin_logs::list_widget_logs),
-        )
-        // Admin — Provider / model config
-        .route(
-            "/api/admin/config/providers",
-            get(admin_config::list_providers),
-        )
-        .route(
-            "/api/admin/config/providers/{provider_id}/toggle",
-            put(admin_config::toggle_provider),
-        )
-        .route(
-            "/api/admin/config/providers/{provider_id}/models",
-            get(admin_config::list_models).post(admin_config::add_model),
-        )
-        .route(
-            "/api/admin/config/models/{model_id}",
-            delete(admin_config::remove_model),
-        )
-        .route(
-            "/api/admin/config/models/{model_id}/default",
-            put(admin_config::set_default_model),
-        )
-        // Admin — Audit logs
-        .route(
-            "/api/admin/audit-logs",
-            get(admin_audit::list_audit_logs),
-        )
-        // Admin — Embed keys
-        .route("/api/admin/embed-keys", get(admin_embed::list_keys).post(admin_embed::create_key))
-        .route(
-            "/api/admin/embed-keys/{id}",
-            get(admin_embed::get_key)
-                .put(admin_embed::update_key)
-                .delete(admin_embed::delete_key),
-        )
-        .route(
-            "/api/admin/embed-keys/{id}/toggle",
-            put(admin_embed::toggle_key),
-        )
-        .layer(axum_mw::from_fn_with_state(
-            state.clone(),
-            auth_middleware,
-        ));
-
-    let widget_routes = Router::new()
-        .route("/api/widget/config", get(widget::get_config))
-        .route(
-            "/api/widget/conversations",
-            get(widget::list_conversations).post(widget::create_conversation),
-        )
-        .route(
-            "/api/widget/conversations/{id}/messages",
-            get(widget::get_messages).post(widget::send_message),
-        )
-        .layer(axum_mw::from_fn_with_state(
-            state.clone(),
-            embed_auth_middleware,
-        ));
+    for entry in &config.server.trusted_proxies {
+        if parse_proxy_entry(entry).is_none() {
+            tracing::warn!("Ignoring invalid trusted proxy '{entry}'");
+        }
+    }
+    if config.server.trusted_proxies.is_empty() {
+        tracing::info!("No trusted proxies; client IPs are taken from the connection");
+    } else {
+        tracing::info!(
+            "Trusting X-Forwarded-For from {}",
+            config.server.trusted_proxies.join(", ")
+        );
+    }
 
     #[allow(unused_mut)]
     let mut app = Router::new()
-        .merge(public_routes)
-        .merge(widget_routes)
-        .merge(protected_routes)
+        .merge(routes::api_routes(&state))
+        .layer(api_cors_layer(&origin_policy))
+        .merge(routes::widget_routes(&state))
         .nest_service("/static", ServeDir::new("static"));
 
     #[cfg(feature = "openapi")]
@@ -225,17 +131,37 @@ async fn main() -> anyhow::Result<()> {
         let openapi = rag_backend::openapi::ApiDoc::openapi();
         app = app
             .merge(Redoc::with_url("/api/docs", openapi.clone()))
-            .route("/api/openapi.json", get({
+            .route("/api/openapi.json", axum::routing::get({
                 let spec = openapi;
                 move || async move { axum::Json(spec) }
             }));
         tracing::info!("OpenAPI docs available at /api/docs");
     }
 
+    let background_tasks = state.tasks.clone();
     let app = app
-        .layer(DefaultBodyLimit::max(config.server.max_upload_size_mb * 1024 * 1024))
-        .layer(cors)
-        .layer(TraceLayer::new_for_http())
+        .layer(body_limit::json_limit(&config.server))
+        .layer(axum_mw::from_fn_with_state(
+            config.server.max_json_body_kb,
+            body_limit::json_rejections,
+        ))
+        // Every request gets an `x-request-id`, echoed in the response and
+        // recorded on its span so errors logged while serving it can be found
+        .layer(PropagateRequestIdLayer::x_request_id())
+        .layer(TraceLayer::new_for_http().make_span_with(|request: &axum::http::Request<_>| {
+            let request_id = request
+                .extensions()
+                .get::<RequestId>()
+                .and_then(|id| id.header_value().to_str().ok())
+                .unwrap_or_default();
+            tracing::info_span!(
+                "request",
+                method = %request.method(),
+                uri = %request.uri(),
+                request_id,
+            )
+        }))
+        .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid))
         .with_state(state);
 
     let addr = format!("{}:{}", config.server.host, config.server.port);
@@ -245,10 +171,124 @@ async fn main() -> anyhow::Result<()> {
         .await
         .context("Failed to bind to address")?;
 
-    axum::serve(listener, app)
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+    )
+        .with_graceful_shutdown(tasks::shutdown_signal())
         .await
         .context("Server error")?;
 
+    background_tasks
+        .shutdown(Duration::from_secs(config.server.shutdown_grace_secs))
+        .await;
+    tracing::info!("Shutdown complete");
+
+    Ok(())
+}
+
+/// Periodic maintenance, run by the scheduler shortly after startup and then
+/// every interval.
+fn register_scheduled_jobs(state: &AppState) {
+    const DAILY: Duration = Duration::from_secs(24 * 60 * 60);
+
+    // Take over work from replicas that stopped while this one keeps running
+    state.scheduler.register("interrupted_work", jobs::LEASE, |state| async move {
+        recover_interrupted(&state).await
+    });
+
+    // Soft-deleted conversations older than 30 days
+    state.scheduler.register("conversation_purge", DAILY, |state| async move {
+        conversation_purge::purge(&state, conversation_purge::BATCH_SIZE).await?;
+        Ok(())
+    });
+
+    // Widget sessions past their retention
+    state.scheduler.register("widget_session_purge", DAILY, |state| async move {
+        let widget_config = &state.config.widget;
+        if widget_config.session_retention_days == 0 {
+            return Ok(());
+        }
+        let purged = state
+            .widget_session_repo
+            .purge_inactive(
+                widget_config.session_retention_days,
+                widget_config.purge_session_conversations,
+            )
+            .await?;
+        if purged.sessions > 0 {
+            tracing::info!(
+                "Purged {} inactive widget sessions and {} of their conversations",
+                purged.sessions,
+                purged.conversations
+            );
+        }
+        Ok(())
+    });
+
+    state.scheduler.register("email_outbox_purge", DAILY, |state| async move {
+        let purged = state
+            .email_outbox
+            .repo()
+            .purge_sent(email_outbox::SENT_RETENTION_DAYS)
+            .await?;
+        if purged > 0 {
+            tracing::info!("Purged {purged} sent emails from the outbox");
+        }
+        Ok(())
+    });
+
+    // Warn about embed keys whose provider or model can no longer answer
+    state.scheduler.register("embed_key_check", DAILY, |state| async move {
+        embed_key_check::log_issues(&state).await;
+        Ok(())
+    });
+
+    // Keep the cached embed key stats close to the counts the admin panel shows
+    state
+        .scheduler
+        .register("embed_key_stats", Duration::from_secs(10 * 60), |state| async move {
+            state.embed_key_repo.refresh_stats().await?;
+            Ok(())
+        });
+}
+
+/// Recover work left behind by a process that stopped, found by its expired
+/// lease; work other replicas are still running keeps its lease and is left alone.
+async fn recover_interrupted(state: &AppState) -> anyhow::Result<()> {
+    // Jobs that were running go back in the queue, so their documents stay processing
+    let jobs = state
+        .jobs
+        .repo()
+        .recover_expired("Interrupted by a server restart")
+        .await
+        .context("Failed to recover interrupted jobs")?;
+    if jobs > 0 {
+        tracing::warn!("Requeued {jobs} jobs whose server stopped running them");
+    }
+
+    let documents = state
+        .document_repo
+        .fail_interrupted(RESTART_ERROR)
+        .await
+        .context("Failed to recover interrupted documents")?;
+    let crawls = state
+        .crawl_repo
+        .fail_interrupted(RESTART_ERROR)
+        .await
+        .context("Failed to recover interrupted crawl jobs")?;
+
+    if documents > 0 || crawls > 0 {
+        tracing::warn!(
+            "Marked {documents} documents and {crawls} crawl jobs whose server stopped running them as failed"
+        );
+    }
+
+    // Its documents were marked failed above; a new rescan can start
+    if state.rescan_run_repo.fail_interrupted().await.context("Failed to recover interrupted rescans")? > 0 {
+        tracing::warn!("Marked a rescan whose server stopped running it as interrupted");
+    }
+
     Ok(())
 }
 
@@ -257,7 +297,7 @@ async fn seed_widget_user(state: &AppState) -> anyhow::Result<()> {
     sqlx::query(
         "INSERT INTO users (id, username, email, password_hash, role)
          VALUES ('__widget__', 'widget', 'widget@system.internal', '__no_login__', 'user')
-         ON CONFLICT (id) DO NOTHING"
+         ON CONFLICT DO NOTHING"
     )
     .execute(&state.db)
     .await
@@ -274,15 +314,13 @@ async fn seed_admin(state: &AppState) -> anyhow::Result<()> {
     let password_hash = auth_service::hash_password(&state.config.auth.admin_password)
         .context("Failed to hash admin password")?;
 
-    state
+    let seeded = state
         .user_repo
-        .create(
-            &state.config.auth.admin_username,
-            &state.config.auth.admin_email,
-            &password_hash,
-            &UserRole::Admin,
-        )
+        .create_first_admin(&state.config.auth.admin_username, &state.config.auth.admin_email, &password_hash)
         .await?;
+    if seeded.is_none() {
+        return Ok(());
+    }
 
     tracing::info!(
         "Admin account seeded: {} ({})",
@@ -7,7 +7,11 @@ use axum::{
 use jsonwebtoken::{decode, DecodingKey, Validation};
 use serde::{Deserialize, Serialize};
 
+use crate::db::models::user::{User, UserRole};
+use crate::middleware::client_ip::ClientIp;
+use crate::services::audit;
 use crate::state::AppState;
+use chrono::{DateTime, Utc};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Claims {
@@ -15,6 +19,49 @@ pub struct Claims {
     pub username: String,
     pub role: String,
     pub exp: usize,
+    /// Admin acting as this user, when the token was issued by impersonation.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub imp: Option<String>,
+    /// Impersonation session the token belongs to; ending it revokes the token.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jti: Option<String>,
+}
+
+/// The signed-in user as stored, loaded by [`auth_middleware`] on every
+/// request so handlers needn't query it again.
+#[derive(Debug, Clone)]
+pub struct CurrentUser {
+    pub id: String,
+    pub username: String,
+    pub email: String,
+    pub role: UserRole,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<User> for CurrentUser {
+    fn from(user: User) -> Self {
+        Self {
+            id: user.id,
+            username: user.username,
+            email: user.email,
+            role: user.role,
+            created_at: user.created_at,
+            updated_at: user.updated_at,
+        }
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for CurrentUser {
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<CurrentUser>()
+            .cloned()
+            .ok_or(StatusCode::UNAUTHORIZED)
+    }
 }
 
 impl<S: Send + Sync> FromRequestParts<S> for Claims {
@@ -41,19 +88,98 @@ pub async fn auth_middleware(
             username: "anonymous".to_string(),
             role: "admin".to_string(),
             exp: usize::MAX,
+            imp: None,
+            jti: None,
         };
+        req.extensions_mut().insert(CurrentUser {
+            id: default_claims.sub.clone(),
+            username: default_claims.username.clone(),
+            email: String::new(),
+            role: UserRole::Admin,
+            created_at: Default::default(),
+            updated_at: Default::default(),
+        });
         req.extensions_mut().insert(default_claims);
         return Ok(next.run(req).await);
     }
 
     let token = extract_token(&req).ok_or(StatusCode::UNAUTHORIZED)?;
-    let claims = validate_token(&token, &state.config.auth.jwt_secret)
+    let mut claims = validate_token(&token, &state.config.auth.jwt_secret)
         .map_err(|_| StatusCode::UNAUTHORIZED)?;
 
+    // Tokens outlive deletions and role changes, so the stored user decides
+    let user = state
+        .user_cache
+        .find_by_id(&claims.sub)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to load user: {e:#}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+    claims.role = user.role.to_string();
+    claims.username = user.username.clone();
+
+    let mut req = match &claims.imp {
+        Some(admin_id) => check_impersonation(&state, &claims, admin_id, req).await?,
+        None => req,
+    };
+
+    req.extensions_mut().insert(CurrentUser::from(user));
     req.extensions_mut().insert(claims);
     Ok(next.run(req).await)
 }
 
+/// Let an impersonated request through only while its session runs and away
+/// from sensitive routes, recording it with both identities.
+async fn check_impersonation(
+    state: &AppState,
+    claims: &Claims,
+    admin_id: &str,
+    req: Request,
+) -> Result<Request, StatusCode> {
+    let session = claims.jti.as_deref().ok_or(StatusCode::UNAUTHORIZED)?;
+    let active = state
+        .impersonation_repo
+        .is_active(session, admin_id, &claims.sub)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to check impersonation session: {e:#}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    if !active {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    let (mut parts, body) = req.into_parts();
+    let ip = ClientIp::from_request_parts(&mut parts, state).await.ok().map(|ip| ip.to_string());
+    let blocked = blocked_while_impersonating(parts.uri.path());
+    audit::log(
+        &state.audit,
+        Some(admin_id),
+        if blocked { "admin.impersonation.blocked" } else { "admin.impersonation.request" },
+        Some("user"),
+        Some(&claims.sub),
+        &format!("{} {} as '{}'", parts.method, parts.uri.path(), claims.username),
+        ip.as_deref(),
+        Some(serde_json::json!({ "impersonator_id": admin_id, "user_id": claims.sub, "session_id": session })),
+    );
+    if blocked {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    Ok(Request::from_parts(parts, body))
+}
+
+/// Routes an impersonated session may not use: account management, API keys,
+/// personal data exports and administration.
+fn blocked_while_impersonating(path: &str) -> bool {
+    path.starts_with("/api/admin/")
+        || path.starts_with("/api/settings/api-keys")
+        || path.starts_with("/api/settings/export")
+        || (path.starts_with("/api/auth/") && path != "/api/auth/me")
+}
+
 fn extract_token(req: &Request) -> Option<String> {
     req.headers()
         .get(header::AUTHORIZATION)
@@ -76,13 +202,17 @@ fn validate_token(
 
 // ── Role-based access helpers ────────────────────────────────
 
-use crate::db::models::user::UserRole;
 use crate::errors::AppError;
 
 impl Claims {
     pub fn parsed_role(&self) -> Result<UserRole, AppError> {
         UserRole::try_from(self.role.as_str()).map_err(|_| AppError::Forbidden)
     }
+
+    /// The admin acting as this user, if the request is impersonated.
+    pub fn impersonator(&self) -> Option<&str> {
+        self.imp.as_deref()
+    }
 }
 
 pub fn require_role(claims: &Claims, minimum: UserRole) -> Result<(), AppError> {
@@ -102,16 +232,10 @@ pub fn require_maintainer(claims: &Claims) -> Result<(), AppError> {
     require_role(claims, UserRole::Maintainer)
 }
 
-pub fn extract_ip(headers: &axum::http::HeaderMap) -> Option<String> {
-    headers
-        .get("x-forwarded-for")
-        .and_then(|v| v.to_str().ok())
-        .and_then(|s| s.split(',').next())
-        .map(|s| s.trim().to_string())
-        .or_else(|| {
-            headers
-                .get("x-real-ip")
-                .and_then(|v| v.to_str().ok())
-                .map(|s| s.trim().to_string())
-        })
+/// Allow the resource's owner, or an admin acting on anyone's.
+pub fn ensure_owner_or_admin(resource_user_id: &str, claims: &Claims) -> Result<(), AppError> {
+    if resource_user_id == claims.sub {
+        return Ok(());
+    }
+    require_admin(claims)
 }
@@ -0,0 +1,159 @@
+use axum::extract::{multipart::MultipartError, DefaultBodyLimit, Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+
+use crate::config::ServerConfig;
+use crate::errors::AppError;
+use crate::services::widget_avatar;
+
+/// Room in an upload's body for the multipart framing and the form fields sent
+/// with the file, on top of `server.max_upload_size_mb`.
+const UPLOAD_FORM_OVERHEAD_BYTES: usize = 1024 * 1024;
+
+/// Body limit for every route but document upload. Bodies are read only up to
+/// the limit, so oversized requests are rejected without being buffered.
+pub fn json_limit(config: &ServerConfig) -> DefaultBodyLimit {
+    DefaultBodyLimit::max(config.max_json_body_kb * 1024)
+}
+
+/// Body limit for the document upload route, which overrides [`json_limit`].
+/// The upload handler enforces the file size itself as the file streams in.
+pub fn upload_limit(config: &ServerConfig) -> DefaultBodyLimit {
+    DefaultBodyLimit::max(config.max_upload_size_mb * 1024 * 1024 + UPLOAD_FORM_OVERHEAD_BYTES)
+}
+
+/// Body limit for embed key avatar uploads, with room for the multipart framing.
+pub fn avatar_limit() -> DefaultBodyLimit {
+    DefaultBodyLimit::max(widget_avatar::MAX_AVATAR_BYTES + 64 * 1024)
+}
+
+/// Turn axum's plain-text 413, sent when an extractor hits the body limit, into
+/// the JSON error every other failure uses. The state is `server.max_json_body_kb`.
+pub async fn json_rejections(State(max_kb): State<usize>, req: Request, next: Next) -> Response {
+    let response = next.run(req).await;
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"));
+
+    if response.status() == StatusCode::PAYLOAD_TOO_LARGE && !is_json {
+        return AppError::RequestTooLarge(max_kb).into_response();
+    }
+    response
+}
+
+/// A failure reading an upload's multipart body. Hitting the upload body limit
+/// is reported as the file being too large rather than as malformed data.
+pub fn multipart_error(e: MultipartError, max_upload_mb: usize) -> AppError {
+    if e.status() == StatusCode::PAYLOAD_TOO_LARGE {
+        AppError::PayloadTooLarge(max_upload_mb)
+    } else {
+        AppError::Validation(format!("Invalid multipart data: {e}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::routes::chat::SendMessageRequest;
+    use axum::body::{to_bytes, Body};
+    use axum::extract::Multipart;
+    use axum::middleware::from_fn_with_state;
+    use axum::routing::post;
+    use axum::{Json, Router};
+    use tower::ServiceExt;
+
+    fn server_config() -> ServerConfig {
+        ServerConfig {
+            host: "127.0.0.1".to_string(),
+            port: 0,
+            max_upload_size_mb: 1,
+            max_json_body_kb: 1,
+            shutdown_grace_secs: 0,
+            worker_concurrency: 1,
+            cors_allowed_origins: vec![],
+            trusted_proxies: vec![],
+            log_format: crate::config::LogFormat::Text,
+        }
+    }
+
+    /// The limits as `main.rs` layers them, around handlers with the same
+    /// extractors as `send_message` and `upload`.
+    fn app(config: &ServerConfig) -> Router {
+        let max_upload_mb = config.max_upload_size_mb;
+        Router::new()
+            .route(
+                "/api/conversations/{id}/messages",
+                post(|Json(payload): Json<SendMessageRequest>| async move { payload.message }),
+            )
+            .route(
+                "/api/documents",
+                post(move |mut multipart: Multipart| async move {
+                    let mut size = 0;
+                    while let Some(mut field) =
+                        multipart.next_field().await.map_err(|e| multipart_error(e, max_upload_mb))?
+                    {
+                        while let Some(chunk) =
+                            field.chunk().await.map_err(|e| multipart_error(e, max_upload_mb))?
+                        {
+                            size += chunk.len();
+                        }
+                    }
+                    Ok::<_, AppError>(size.to_string())
+                })
+                .layer(upload_limit(config)),
+            )
+            .layer(json_limit(config))
+            .layer(from_fn_with_state(config.max_json_body_kb, json_rejections))
+    }
+
+    async fn send(app: Router, uri: &str, content_type: &str, body: Vec<u8>) -> (StatusCode, serde_json::Value) {
+        let request = Request::builder()
+            .method("POST")
+            .uri(uri)
+            .header(header::CONTENT_TYPE, content_type)
+            .body(Body::from(body))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or(serde_json::Value::Null))
+    }
+
+    fn multipart_body(file_len: usize) -> Vec<u8> {
+        let mut body = b"--boundary\r\nContent-Disposition: form-data; name=\"file\"; filename=\"a.txt\"\r\nContent-Type: text/plain\r\n\r\n".to_vec();
+        body.extend(std::iter::repeat_n(b'a', file_len));
+        body.extend_from_slice(b"\r\n--boundary--\r\n");
+        body
+    }
+
+    #[tokio::test]
+    async fn test_oversized_message_is_a_json_413() {
+        let config = server_config();
+        let small = serde_json::to_vec(&serde_json::json!({ "message": "hello" })).unwrap();
+        let (status, _) = send(app(&config), "/api/conversations/c1/messages", "application/json", small).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let huge = serde_json::to_vec(&serde_json::json!({ "message": "x".repeat(2048) })).unwrap();
+        let (status, body) = send(app(&config), "/api/conversations/c1/messages", "application/json", huge).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(body["status"], 413);
+        assert_eq!(body["error"], "Request body too large. Maximum size is 1 KB");
+    }
+
+    #[tokio::test]
+    async fn test_oversized_upload_is_a_json_413() {
+        let config = server_config();
+        let content_type = "multipart/form-data; boundary=boundary";
+        // Uploads aren't held to the much smaller JSON limit
+        let (status, _) = send(app(&config), "/api/documents", content_type, multipart_body(512 * 1024)).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, body) = send(app(&config), "/api/documents", content_type, multipart_body(3 * 1024 * 1024)).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(body["status"], 413);
+        assert_eq!(body["error"], "File too large. Maximum upload size is 1 MB");
+    }
+}
@@ -0,0 +1,150 @@
+use axum::{
+    extract::{ConnectInfo, FromRequestParts},
+    http::{request::Parts, StatusCode},
+};
+use ipnet::IpNet;
+use std::net::{IpAddr, SocketAddr};
+
+use crate::state::AppState;
+
+/// The address of the client that made the request. Behind reverse proxies listed
+/// in `server.trusted_proxies` it comes from `X-Forwarded-For`; otherwise it is
+/// the peer address, so clients can't spoof it by sending the header themselves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientIp(pub IpAddr);
+
+impl std::fmt::Display for ClientIp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl FromRequestParts<AppState> for ClientIp {
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        let ConnectInfo(peer) = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .copied()
+            .ok_or_else(|| {
+                tracing::error!("ClientIp used without ConnectInfo; serve with into_make_service_with_connect_info");
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+
+        let trusted = parse_trusted_proxies(&state.config.server.trusted_proxies);
+        let forwarded_for = parts
+            .headers
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .collect::<Vec<_>>()
+            .join(",");
+
+        Ok(ClientIp(resolve_client_ip(peer.ip(), &forwarded_for, &trusted)))
+    }
+}
+
+/// Parse `server.trusted_proxies` entries as CIDR ranges or single addresses.
+/// Invalid entries are skipped; `main` warns about them at startup.
+pub fn parse_trusted_proxies(entries: &[String]) -> Vec<IpNet> {
+    entries.iter().filter_map(|e| parse_proxy_entry(e)).collect()
+}
+
+pub fn parse_proxy_entry(entry: &str) -> Option<IpNet> {
+    let entry = entry.trim();
+    entry
+        .parse::<IpNet>()
+        .ok()
+        .or_else(|| entry.parse::<IpAddr>().ok().map(IpNet::from))
+}
+
+/// Walk `X-Forwarded-For` from the right for as long as the hops are trusted
+/// proxies. The first untrusted hop is the client; a malformed hop ends the walk
+/// at the last trusted address.
+pub fn resolve_client_ip(peer: IpAddr, forwarded_for: &str, trusted: &[IpNet]) -> IpAddr {
+    let is_trusted = |ip: &IpAddr| trusted.iter().any(|net| net.contains(ip));
+
+    let mut client = peer;
+    if !is_trusted(&client) {
+        return client;
+    }
+
+    for hop in forwarded_for.rsplit(',').map(str::trim).filter(|h| !h.is_empty()) {
+        let Ok(ip) = hop.parse::<IpAddr>() else {
+            break;
+        };
+        client = ip;
+        if !is_trusted(&client) {
+            break;
+        }
+    }
+    client
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    fn proxies(entries: &[&str]) -> Vec<IpNet> {
+        parse_trusted_proxies(&entries.iter().map(|e| e.to_string()).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn test_parse_trusted_proxies() {
+        let nets = proxies(&["10.0.0.0/8", " 192.168.1.5 ", "::1", "not-an-ip", "10.0.0.0/99"]);
+        assert_eq!(nets.len(), 3);
+        assert!(nets[1].contains(&ip("192.168.1.5")));
+        assert!(!nets[1].contains(&ip("192.168.1.6")));
+    }
+
+    #[test]
+    fn test_untrusted_peer_ignores_spoofed_header() {
+        let trusted = proxies(&["10.0.0.0/8"]);
+        assert_eq!(
+            resolve_client_ip(ip("203.0.113.7"), "1.2.3.4", &trusted),
+            ip("203.0.113.7")
+        );
+        // Without any trusted proxies the header is never read
+        assert_eq!(resolve_client_ip(ip("10.0.0.2"), "1.2.3.4", &[]), ip("10.0.0.2"));
+    }
+
+    #[test]
+    fn test_one_trusted_proxy() {
+        let trusted = proxies(&["10.0.0.0/8"]);
+        // The client may prepend anything; only the hop the proxy appended counts
+        assert_eq!(
+            resolve_client_ip(ip("10.0.0.2"), "1.2.3.4, 198.51.100.20", &trusted),
+            ip("198.51.100.20")
+        );
+        // A trusted proxy that didn't forward anything is the client itself
+        assert_eq!(resolve_client_ip(ip("10.0.0.2"), "", &trusted), ip("10.0.0.2"));
+    }
+
+    #[test]
+    fn test_two_trusted_proxies() {
+        let trusted = proxies(&["10.0.0.0/8", "172.16.0.1"]);
+        assert_eq!(
+            resolve_client_ip(ip("10.0.0.2"), "1.2.3.4, 198.51.100.20, 172.16.0.1", &trusted),
+            ip("198.51.100.20")
+        );
+        // A malformed hop stops the walk at the last trusted address
+        assert_eq!(
+            resolve_client_ip(ip("10.0.0.2"), "198.51.100.20, garbage, 172.16.0.1", &trusted),
+            ip("172.16.0.1")
+        );
+    }
+
+    #[test]
+    fn test_ipv6_proxies() {
+        let trusted = proxies(&["fd00::/8"]);
+        assert_eq!(
+            resolve_client_ip(ip("fd00::1"), "2001:db8::42", &trusted),
+            ip("2001:db8::42")
+        );
+    }
+}
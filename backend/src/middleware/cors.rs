@@ -0,0 +1,182 @@
+use axum::http::{header, HeaderName, HeaderValue, Method};
+use std::fmt;
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+
+const EMBED_KEY_HEADER: HeaderName = HeaderName::from_static("x-embed-key");
+const SESSION_ID_HEADER: HeaderName = HeaderName::from_static("x-session-id");
+
+/// Origins allowed to call the authenticated API, from `server.cors_allowed_origins`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum OriginPolicy {
+    /// `*` was configured: any origin may call the API. Meant for development.
+    Any,
+    /// Only these exact origins; an empty list rejects every cross-origin request.
+    Exact(Vec<HeaderValue>),
+}
+
+impl OriginPolicy {
+    /// Entries are compared exactly, so a trailing `/` is dropped. Entries that
+    /// aren't valid header values are skipped with a warning.
+    pub fn from_config(origins: &[String]) -> Self {
+        if origins.iter().any(|o| o.trim() == "*") {
+            return OriginPolicy::Any;
+        }
+
+        let exact = origins
+            .iter()
+            .map(|o| o.trim().trim_end_matches('/'))
+            .filter(|o| !o.is_empty())
+            .filter_map(|o| match HeaderValue::from_str(o) {
+                Ok(value) => Some(value),
+                Err(_) => {
+                    tracing::warn!("Ignoring invalid CORS origin '{o}'");
+                    None
+                }
+            })
+            .collect();
+        OriginPolicy::Exact(exact)
+    }
+}
+
+impl fmt::Display for OriginPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OriginPolicy::Any => write!(f, "any origin"),
+            OriginPolicy::Exact(origins) if origins.is_empty() => write!(f, "no cross-origin requests"),
+            OriginPolicy::Exact(origins) => {
+                let origins: Vec<&str> = origins.iter().filter_map(|o| o.to_str().ok()).collect();
+                write!(f, "{}", origins.join(", "))
+            }
+        }
+    }
+}
+
+/// CORS for the authenticated API and the public auth routes.
+pub fn api_cors_layer(policy: &OriginPolicy) -> CorsLayer {
+    let allow_origin = match policy {
+        OriginPolicy::Any => AllowOrigin::any(),
+        OriginPolicy::Exact(origins) => AllowOrigin::list(origins.clone()),
+    };
+
+    CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods([
+            Method::GET,
+            Method::POST,
+            Method::PUT,
+            Method::PATCH,
+            Method::DELETE,
+        ])
+        .allow_headers([header::CONTENT_TYPE, header::AUTHORIZATION])
+}
+
+/// CORS for the widget API, which is embedded on arbitrary sites. Access is
+/// controlled by the embed key's allowed domains instead. Visitors delete
+/// their own conversations, hence `DELETE`.
+pub fn widget_cors_layer() -> CorsLayer {
+    CorsLayer::new()
+        .allow_origin(Any)
+        .allow_methods([Method::GET, Method::POST, Method::DELETE])
+        .allow_headers([header::CONTENT_TYPE, EMBED_KEY_HEADER, SESSION_ID_HEADER])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::{Request, Response};
+    use axum::routing::post;
+    use axum::Router;
+    use tower::ServiceExt;
+
+    fn origins(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
+
+    async fn preflight(layer: CorsLayer, origin: &str) -> Response<Body> {
+        preflight_for(layer, origin, Method::POST).await
+    }
+
+    async fn preflight_for(layer: CorsLayer, origin: &str, method: Method) -> Response<Body> {
+        let app = Router::new()
+            .route("/api/test", post(|| async { "ok" }).delete(|| async { "ok" }))
+            .layer(layer);
+        let request = Request::builder()
+            .method(Method::OPTIONS)
+            .uri("/api/test")
+            .header(header::ORIGIN, origin)
+            .header(header::ACCESS_CONTROL_REQUEST_METHOD, method.as_str())
+            .body(Body::empty())
+            .unwrap();
+        app.oneshot(request).await.unwrap()
+    }
+
+    fn allowed_origin(response: &Response<Body>) -> Option<&str> {
+        response
+            .headers()
+            .get(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+            .and_then(|v| v.to_str().ok())
+    }
+
+    #[test]
+    fn test_origin_policy_from_config() {
+        assert_eq!(OriginPolicy::from_config(&[]), OriginPolicy::Exact(vec![]));
+        assert_eq!(
+            OriginPolicy::from_config(&origins(&["https://app.example.com", "*"])),
+            OriginPolicy::Any
+        );
+        assert_eq!(
+            OriginPolicy::from_config(&origins(&[" https://app.example.com/ ", "", "bad\norigin"])),
+            OriginPolicy::Exact(vec![HeaderValue::from_static("https://app.example.com")])
+        );
+    }
+
+    #[test]
+    fn test_origin_policy_display() {
+        assert_eq!(OriginPolicy::from_config(&[]).to_string(), "no cross-origin requests");
+        assert_eq!(OriginPolicy::from_config(&origins(&["*"])).to_string(), "any origin");
+        assert_eq!(
+            OriginPolicy::from_config(&origins(&["https://a.example", "https://b.example"])).to_string(),
+            "https://a.example, https://b.example"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_api_layer_allows_configured_origins_only() {
+        let policy = OriginPolicy::from_config(&origins(&["https://app.example.com"]));
+
+        let response = preflight(api_cors_layer(&policy), "https://app.example.com").await;
+        assert_eq!(allowed_origin(&response), Some("https://app.example.com"));
+
+        let response = preflight(api_cors_layer(&policy), "https://evil.example").await;
+        assert_eq!(allowed_origin(&response), None);
+    }
+
+    #[tokio::test]
+    async fn test_api_layer_empty_denies_and_wildcard_allows() {
+        let response = preflight(api_cors_layer(&OriginPolicy::from_config(&[])), "https://app.example.com").await;
+        assert_eq!(allowed_origin(&response), None);
+
+        let any = OriginPolicy::from_config(&origins(&["*"]));
+        let response = preflight(api_cors_layer(&any), "https://anywhere.example").await;
+        assert_eq!(allowed_origin(&response), Some("*"));
+    }
+
+    #[tokio::test]
+    async fn test_widget_layer_allows_any_origin() {
+        let response = preflight(widget_cors_layer(), "https://customer-site.example").await;
+        assert_eq!(allowed_origin(&response), Some("*"));
+        let headers = response.headers()[header::ACCESS_CONTROL_ALLOW_HEADERS].to_str().unwrap();
+        assert!(headers.contains("x-embed-key"));
+        assert!(!headers.contains("authorization"));
+    }
+
+    #[tokio::test]
+    async fn test_widget_layer_allows_deleting_conversations() {
+        let response = preflight_for(widget_cors_layer(), "https://customer-site.example", Method::DELETE).await;
+        assert_eq!(allowed_origin(&response), Some("*"));
+        let methods = response.headers()[header::ACCESS_CONTROL_ALLOW_METHODS].to_str().unwrap();
+        assert!(methods.contains("DELETE"), "{methods}");
+        assert!(!methods.contains("PUT"), "{methods}");
+    }
+}
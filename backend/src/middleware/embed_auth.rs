@@ -47,9 +47,9 @@ pub async fn embed_auth_middleware(
     // Hash the key
     let key_hash = hash_key(&raw_key);
 
-    // Look up in database
+    // Look up the key, cached for busy widgets
     let embed_key = state
-        .embed_key_repo
+        .embed_key_cache
         .find_by_hash(&key_hash)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
@@ -1,2 +1,6 @@
 pub mod auth;
+pub mod body_limit;
+pub mod client_ip;
+pub mod cors;
 pub mod embed_auth;
+pub mod rate_limit;
@@ -0,0 +1,47 @@
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderValue},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::time::Duration;
+
+use crate::errors::AppError;
+use crate::middleware::auth::Claims;
+use crate::services::rate_limit::TokenBucketLimiter;
+use crate::state::AppState;
+
+/// Limit each user's requests to the authenticated API. Runs after
+/// [`auth_middleware`](crate::middleware::auth::auth_middleware).
+pub async fn api_rate_limit(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    limit(&state, &state.api_limiter, req, next).await
+}
+
+/// Limit each user's requests to endpoints that call a model, on top of
+/// [`api_rate_limit`].
+pub async fn llm_rate_limit(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    limit(&state, &state.llm_limiter, req, next).await
+}
+
+async fn limit(state: &AppState, limiter: &TokenBucketLimiter<String>, req: Request, next: Next) -> Response {
+    let Some(claims) = req.extensions().get::<Claims>() else {
+        return next.run(req).await;
+    };
+    if state.config.rate_limit.exempt_admins && claims.role == "admin" {
+        return next.run(req).await;
+    }
+    match limiter.acquire(claims.sub.clone()) {
+        Ok(()) => next.run(req).await,
+        Err(wait) => rate_limited(wait),
+    }
+}
+
+/// A 429 telling the client to retry after `wait`, in whole seconds.
+fn rate_limited(wait: Duration) -> Response {
+    let seconds = wait.as_secs_f64().ceil().max(1.0) as u64;
+    let mut response = AppError::RateLimited.into_response();
+    response
+        .headers_mut()
+        .insert(header::RETRY_AFTER, HeaderValue::from(seconds));
+    response
+}
@@ -1,30 +1,89 @@
 use utoipa::openapi::security::{Http, HttpAuthScheme, ApiKey, ApiKeyValue, SecurityScheme};
 use utoipa::{Modify, OpenApi};
 
-use crate::db::models::admin_config::{AddModelRequest, AdminModel, AdminProvider};
+use crate::config::FeatureFlags;
+use crate::db::models::admin_api_key::AdminApiKeyEntry;
+use crate::db::models::admin_config::{
+    AddModelRequest, AdminModel, AdminProvider, CatalogSyncSummary, ModelSyncSummary,
+    UpdateModelRequest,
+};
 use crate::db::models::audit_log::AuditLog;
-use crate::db::models::conversation::{Conversation, ConversationWithUser, Message};
+use crate::db::models::collection::Collection;
+use crate::db::models::conversation::{
+    ArchivedFilter, Conversation, ConversationPurge, ConversationSettings, ConversationWithUser, DeletedFilter,
+    LogSort, Message, MessageGeneration, MessagePage, SortOrder, WidgetConversationLog, WidgetConversationSummary,
+};
+use crate::db::models::conversation_share::ConversationShare;
 use crate::db::models::crawl_job::CrawlJob;
+use crate::db::models::crawl_schedule::CrawlSchedule;
 use crate::db::models::document::DocumentStatus;
-use crate::db::models::embed_key::{EmbedKey, UpdateEmbedKeyRequest};
+use crate::db::models::rescan_run::RescanRun;
+use crate::db::models::document_event::DocumentEvent;
+use crate::db::models::embed_key::{
+    EmbedKey, EmbedKeyUsage, OfficeHours, OfflineBehavior, OpeningRange, UpdateEmbedKeyRequest,
+    WidgetLocalization, WidgetTheme, WidgetThemeColors,
+};
+use crate::db::models::widget_handoff::WidgetHandoff;
+use crate::db::models::widget_session::WidgetSessionPurge;
+use crate::db::models::job::Job;
+use crate::db::models::org_settings::{OrgSettings, SystemPromptRevision};
+use crate::db::models::scheduled_job_run::ScheduledJobRun;
+use crate::db::models::webhook::{Webhook, WebhookDelivery};
+use crate::db::models::data_export::{DataExport, ExportFormat};
+use crate::db::models::email_outbox::EmailStatus;
 use crate::db::models::settings::{ApiKeyEntry, LlmPreferences};
 use crate::db::models::user::UserRole;
 use crate::dto::auth::{
-    AuthResponse, InviteRequest, InviteResponse, LoginRequest, SetupRequest, UpdateRoleRequest,
-    UserResponse,
+    AuthResponse, CreateAdminRequest, ImpersonationResponse, InviteRequest, InviteResponse, LoginRequest, MeResponse,
+    RoleInfo, SetupRequest, SetupStatusResponse, UpdateRoleRequest, UserResponse,
+};
+use crate::dto::document::{
+    DocumentChunkResponse, DocumentChunksResponse, DocumentDetailResponse, DocumentResponse, DocumentVersion,
 };
-use crate::dto::document::DocumentResponse;
 use crate::errors::ErrorResponse;
 use crate::routes::admin_audit::AuditLogsResponse;
-use crate::routes::admin_config::ToggleRequest;
-use crate::routes::admin_embed::{CreateEmbedKeyRequest, CreateEmbedKeyResponse};
-use crate::routes::admin_logs::{LogDetailResponse, LogsResponse};
-use crate::routes::chat::{ConversationWithMessages, CreateConversationRequest, SendMessageRequest};
-use crate::routes::crawl::StartCrawlRequest;
-use crate::routes::settings::SetApiKeyRequest;
+use crate::routes::admin_config::{
+    ConfigImportResponse, CreatedEmbedKey, SystemPromptResponse, ToggleRequest, UpdateSystemPromptRequest,
+};
+use crate::routes::admin_embed::{
+    CreateEmbedKeyRequest, CreateEmbedKeyResponse, EmbedKeyDetail, EmbedKeyListItem, UpdateHandoffStatusRequest,
+};
+use crate::routes::admin_jobs::ScheduledJobStatus;
+use crate::routes::admin_logs::{LogDetailResponse, LogsResponse, WidgetLogsResponse};
+use crate::routes::admin_maintenance::PurgeConversationsResponse;
+use crate::routes::admin_webhooks::{
+    CreateWebhookRequest, CreateWebhookResponse, UpdateWebhookRequest,
+};
+use crate::routes::capabilities::{Capabilities, PublicCapabilities};
+use crate::routes::chat::{
+    ConversationChatSettings, ConversationWithMessages, CreateConversationRequest, DeleteMessageResponse,
+    RenameConversationRequest, SendMessageRequest,
+};
+use crate::routes::crawl::{IngestPageRequest, SetScheduleRequest, StartCrawlRequest};
+use crate::routes::collections::CollectionRequest;
+use crate::routes::debug::{RetrievalDebugRequest, RetrievalDebugResponse, RetrievedChunk};
+use crate::routes::documents::{
+    BulkAction, BulkDocumentsRequest, BulkDocumentsResponse, BulkItemResult, BulkItemStatus, RescanRequest, SetCollectionRequest,
+    UpdateTagsRequest,
+};
+use crate::routes::settings::{
+    ApiKeyStatus, ApiKeyTestResponse, DataExportResponse, PreferencesResponse, SetApiKeyRequest,
+    StartExportRequest, TestApiKeyRequest,
+};
+use crate::routes::shares::{CreateShareRequest, CreateShareResponse, SharedConversation, SharedMessage};
+use crate::routes::utils::{EstimateTokensRequest, EstimateTokensResponse};
 use crate::routes::widget::{
-    CreateWidgetConversationRequest, WidgetConfigResponse, WidgetSendMessageRequest,
+    ClearConversationsResponse, CreateWidgetConversationRequest, HandoffRequest, TranscriptEmailRequest,
+    WidgetConfigResponse, WidgetFeatures, WidgetMessages, WidgetSendMessageRequest,
+};
+use crate::services::chat_service::EffectiveChatSettings;
+use crate::services::credentials::KeySource;
+use crate::services::config_transfer::{
+    ChangeAction, ConfigChange, ConfigDocument, EmbedKeySettings, ModelSettings, ProviderSettings,
 };
+use crate::services::model_catalog::LiveModel;
+use crate::services::tokens::EstimationMethod;
+use crate::services::widget_theme::{ResolvedThemeColors, ResolvedWidgetTheme};
 
 struct SecurityAddon;
 
@@ -55,51 +114,113 @@ impl Modify for SecurityAddon {
         crate::routes::health::health_check,
         crate::routes::auth::login,
         crate::routes::auth::setup,
+        crate::routes::setup::status,
+        crate::routes::capabilities::public_capabilities,
+        crate::routes::setup::create_admin,
         // Auth (protected)
         crate::routes::auth::me,
+        crate::routes::capabilities::capabilities,
         // Conversations
         crate::routes::chat::create_conversation,
         crate::routes::chat::list_conversations,
         crate::routes::chat::get_conversation,
+        crate::routes::chat::rename_conversation,
         crate::routes::chat::delete_conversation,
+        crate::routes::chat::archive_conversation,
+        crate::routes::chat::unarchive_conversation,
+        crate::routes::chat::update_conversation_settings,
         crate::routes::chat::send_message,
+        crate::routes::chat::delete_message,
+        crate::routes::shares::create_share,
+        crate::routes::shares::revoke_shares,
+        crate::routes::shares::get_shared,
         // Documents
         crate::routes::documents::upload_limits,
         crate::routes::documents::upload,
         crate::routes::documents::list,
         crate::routes::documents::get_document,
+        crate::routes::documents::list_events,
+        crate::routes::documents::list_chunks,
+        crate::routes::documents::get_chunk,
         crate::routes::documents::delete_document,
+        crate::routes::documents::update_tags,
+        crate::routes::documents::set_collection,
+        crate::routes::collections::list,
+        crate::routes::collections::create,
+        crate::routes::collections::rename,
+        crate::routes::collections::delete,
+        crate::routes::documents::reprocess,
         crate::routes::documents::rescan,
+        crate::routes::documents::rescan_status,
+        crate::routes::documents::bulk,
         // Crawl
         crate::routes::crawl::start_crawl,
         crate::routes::crawl::list_crawl_jobs,
         crate::routes::crawl::get_crawl_job,
+        crate::routes::crawl::set_schedule,
+        // Debug
+        crate::routes::debug::retrieval,
+        // Utils
+        crate::routes::utils::estimate_tokens,
+        crate::routes::crawl::list_schedules,
+        crate::routes::crawl::ingest_page,
+        crate::routes::crawl::delete_page,
         // Settings
         crate::routes::settings::list_providers,
         crate::routes::settings::list_models_for_provider,
+        crate::routes::settings::list_live_models,
         crate::routes::settings::list_api_keys,
+        crate::routes::settings::api_key_status,
         crate::routes::settings::set_api_key,
         crate::routes::settings::delete_api_key,
+        crate::routes::settings::test_api_key,
         crate::routes::settings::get_preferences,
         crate::routes::settings::update_preferences,
+        crate::routes::settings::start_export,
+        crate::routes::settings::get_export,
+        crate::routes::settings::download_export,
         // Admin — Users
         crate::routes::admin::list_users,
+        crate::routes::admin::list_roles,
         crate::routes::admin::update_user_role,
         crate::routes::admin::delete_user,
+        crate::routes::admin::impersonate_user,
+        crate::routes::admin::end_impersonation,
         crate::routes::admin::invite_user,
         crate::routes::admin::list_invites,
+        crate::routes::admin::resend_invite_email,
         // Admin — Logs
         crate::routes::admin_logs::list_conversation_logs,
         crate::routes::admin_logs::get_conversation_log,
+        crate::routes::admin_logs::delete_conversation_log,
+        crate::routes::admin_logs::list_widget_logs,
         // Admin — Config
         crate::routes::admin_config::list_providers,
         crate::routes::admin_config::toggle_provider,
+        crate::routes::admin_config::set_default_embedding_provider,
         crate::routes::admin_config::list_models,
         crate::routes::admin_config::add_model,
+        crate::routes::admin_config::sync_models,
+        crate::routes::admin_config::update_model,
+        crate::routes::admin_config::sync_catalog,
+        crate::routes::admin_config::export_config,
+        crate::routes::admin_config::import_config,
         crate::routes::admin_config::remove_model,
         crate::routes::admin_config::set_default_model,
+        crate::routes::admin_config::list_api_keys,
+        crate::routes::admin_config::get_api_key,
+        crate::routes::admin_config::set_api_key,
+        crate::routes::admin_config::delete_api_key,
+        crate::routes::admin_config::get_system_prompt,
+        crate::routes::admin_config::set_system_prompt,
         // Admin — Audit
         crate::routes::admin_audit::list_audit_logs,
+        // Admin — Jobs
+        crate::routes::admin_jobs::list_jobs,
+        crate::routes::admin_jobs::list_scheduled_jobs,
+        crate::routes::admin_jobs::run_scheduled_job,
+        // Admin — Maintenance
+        crate::routes::admin_maintenance::purge_conversations,
         // Admin — Embed keys
         crate::routes::admin_embed::create_key,
         crate::routes::admin_embed::list_keys,
@@ -107,34 +228,80 @@ impl Modify for SecurityAddon {
         crate::routes::admin_embed::update_key,
         crate::routes::admin_embed::delete_key,
         crate::routes::admin_embed::toggle_key,
+        crate::routes::admin_embed::test_key,
+        crate::routes::admin_embed::upload_avatar,
+        crate::routes::admin_embed::purge_widget_sessions,
+        crate::routes::admin_embed::list_handoffs,
+        crate::routes::admin_embed::update_handoff_status,
+        // Admin — Webhooks
+        crate::routes::admin_webhooks::list_webhooks,
+        crate::routes::admin_webhooks::create_webhook,
+        crate::routes::admin_webhooks::update_webhook,
+        crate::routes::admin_webhooks::delete_webhook,
+        crate::routes::admin_webhooks::list_deliveries,
         // Widget
         crate::routes::widget::get_config,
         crate::routes::widget::create_conversation,
         crate::routes::widget::list_conversations,
+        crate::routes::widget::delete_conversation,
+        crate::routes::widget::clear_conversations,
         crate::routes::widget::get_messages,
         crate::routes::widget::send_message,
+        crate::routes::widget::request_handoff,
+        crate::routes::widget::email_transcript,
+        crate::routes::widget::get_avatar,
     ),
     components(
         schemas(
             // Auth
-            LoginRequest, SetupRequest, AuthResponse, UserResponse, UserRole,
-            InviteRequest, InviteResponse, UpdateRoleRequest,
+            LoginRequest, SetupRequest, SetupStatusResponse, CreateAdminRequest, AuthResponse, UserResponse, MeResponse, UserRole,
+            InviteRequest, InviteResponse, EmailStatus, UpdateRoleRequest, RoleInfo, ImpersonationResponse,
+            // Capabilities
+            Capabilities, PublicCapabilities, FeatureFlags,
             // Conversations
-            Conversation, Message, ConversationWithMessages, ConversationWithUser,
-            CreateConversationRequest, SendMessageRequest,
+            Conversation, Message, MessageGeneration, MessagePage, ConversationWithMessages, ConversationWithUser,
+            WidgetConversationSummary, WidgetMessages,
+            ArchivedFilter, DeletedFilter, LogSort, SortOrder,
+            ConversationSettings, ConversationChatSettings, EffectiveChatSettings,
+            CreateConversationRequest, RenameConversationRequest, SendMessageRequest, DeleteMessageResponse,
+            ConversationShare, CreateShareRequest, CreateShareResponse, SharedConversation, SharedMessage,
             // Documents
-            DocumentResponse, DocumentStatus,
+            DocumentResponse, DocumentDetailResponse, DocumentVersion, DocumentStatus, DocumentEvent, UpdateTagsRequest,
+            DocumentChunkResponse, DocumentChunksResponse,
+            BulkAction, BulkDocumentsRequest, BulkDocumentsResponse, BulkItemResult, BulkItemStatus, SetCollectionRequest,
+            RescanRequest, RescanRun,
+            // Collections
+            Collection, CollectionRequest,
             // Crawl
-            CrawlJob, StartCrawlRequest,
+            CrawlJob, StartCrawlRequest, CrawlSchedule, SetScheduleRequest, IngestPageRequest,
+            // Debug
+            RetrievalDebugRequest, RetrievalDebugResponse, RetrievedChunk,
+            EstimateTokensRequest, EstimateTokensResponse, EstimationMethod,
             // Settings
-            AdminProvider, AdminModel, AddModelRequest, ToggleRequest,
-            ApiKeyEntry, LlmPreferences, SetApiKeyRequest,
+            AdminProvider, AdminModel, AddModelRequest, UpdateModelRequest, ToggleRequest,
+            ModelSyncSummary, CatalogSyncSummary, LiveModel,
+            ConfigDocument, ProviderSettings, ModelSettings, EmbedKeySettings, ConfigChange,
+            ChangeAction, ConfigImportResponse, CreatedEmbedKey,
+            ApiKeyEntry, LlmPreferences, PreferencesResponse, SetApiKeyRequest, TestApiKeyRequest, ApiKeyTestResponse,
+            ApiKeyStatus, KeySource, AdminApiKeyEntry,
+            StartExportRequest, DataExportResponse, DataExport, ExportFormat,
+            OrgSettings, SystemPromptRevision, SystemPromptResponse, UpdateSystemPromptRequest,
             // Admin logs
             LogsResponse, LogDetailResponse, AuditLogsResponse, AuditLog,
+            WidgetLogsResponse, WidgetConversationLog,
+            // Jobs
+            Job, ScheduledJobStatus, ScheduledJobRun,
+            // Maintenance
+            ConversationPurge, PurgeConversationsResponse,
             // Embed keys
-            EmbedKey, UpdateEmbedKeyRequest, CreateEmbedKeyRequest, CreateEmbedKeyResponse,
+            EmbedKey, EmbedKeyDetail, EmbedKeyListItem, EmbedKeyUsage, UpdateEmbedKeyRequest, CreateEmbedKeyRequest,
+            CreateEmbedKeyResponse, WidgetLocalization, WidgetSessionPurge, WidgetHandoff, UpdateHandoffStatusRequest,
+            WidgetTheme, WidgetThemeColors, OfficeHours, OpeningRange, OfflineBehavior,
+            // Webhooks
+            Webhook, WebhookDelivery, CreateWebhookRequest, CreateWebhookResponse, UpdateWebhookRequest,
             // Widget
-            WidgetConfigResponse, CreateWidgetConversationRequest, WidgetSendMessageRequest,
+            WidgetConfigResponse, WidgetFeatures, CreateWidgetConversationRequest, WidgetSendMessageRequest,
+            ClearConversationsResponse, HandoffRequest, TranscriptEmailRequest, ResolvedWidgetTheme, ResolvedThemeColors,
             // Errors
             ErrorResponse,
         )
@@ -142,14 +309,21 @@ impl Modify for SecurityAddon {
     tags(
         (name = "Health", description = "Health check"),
         (name = "Auth", description = "Authentication and account setup"),
+        (name = "Capabilities", description = "Enabled features and what the current user can do"),
         (name = "Chat", description = "Conversations and messages"),
         (name = "Documents", description = "Document upload and management"),
+        (name = "Collections", description = "Groups of documents that conversations can be limited to"),
         (name = "Crawl", description = "Web crawling"),
+        (name = "Debug", description = "Retrieval inspection for tuning RAG (maintainers, when enabled)"),
+        (name = "Utils", description = "Helpers for composing messages, such as token estimates"),
         (name = "Settings", description = "User settings, API keys, and LLM preferences"),
         (name = "Admin - Users", description = "User and invite management (admin only)"),
         (name = "Admin - Logs", description = "Conversation and audit log viewing (admin only)"),
         (name = "Admin - Config", description = "Provider and model configuration (admin only)"),
         (name = "Admin - Embed", description = "Embed key management (admin only)"),
+        (name = "Admin - Jobs", description = "Background job queue (admin only)"),
+        (name = "Admin - Maintenance", description = "Data retention tasks run on demand (admin only)"),
+        (name = "Admin - Webhooks", description = "Outgoing event notifications (admin only)"),
         (name = "Widget", description = "Embeddable chat widget API"),
     )
 )]
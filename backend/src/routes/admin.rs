@@ -1,13 +1,21 @@
 use axum::{
     extract::{Path, State},
+    http::StatusCode,
     Json,
 };
+use std::time::Duration;
 
+use crate::db::models::email_outbox::EmailStatus;
 use crate::db::models::invite::Invite;
-use crate::dto::auth::{InviteRequest, InviteResponse, UpdateRoleRequest, UserResponse};
+use crate::db::models::user::UserRole;
+use crate::dto::auth::{
+    ImpersonationResponse, InviteRequest, InviteResponse, RoleInfo, UpdateRoleRequest, UserResponse,
+};
 use crate::errors::AppError;
 use crate::middleware::auth::{require_admin, Claims};
-use crate::services::audit;
+use crate::services::email::normalize_email;
+use crate::services::email_outbox::EmailMessage;
+use crate::services::{audit, auth_service};
 use crate::state::AppState;
 
 #[cfg_attr(feature = "openapi", utoipa::path(get, path = "/api/admin/users", tag = "Admin - Users", security(("bearer_auth" = [])), responses((status = 200, body = Vec<UserResponse>))))]
@@ -22,6 +30,37 @@ pub async fn list_users(
     Ok(Json(responses))
 }
 
+#[cfg_attr(feature = "openapi", utoipa::path(get, path = "/api/admin/roles", tag = "Admin - Users", security(("bearer_auth" = [])), responses((status = 200, body = Vec<RoleInfo>))))]
+pub async fn list_roles(claims: Claims) -> Result<Json<Vec<RoleInfo>>, AppError> {
+    require_admin(&claims)?;
+
+    let roles = UserRole::ALL
+        .into_iter()
+        .map(|role| RoleInfo {
+            description: role.description().to_string(),
+            role,
+        })
+        .collect();
+    Ok(Json(roles))
+}
+
+/// Refuse to demote (`new_role` other than admin) or delete (`new_role` of `None`)
+/// the only remaining admin, which would leave nobody able to manage the instance.
+fn ensure_admin_remains(
+    current_role: &UserRole,
+    new_role: Option<&UserRole>,
+    admin_count: i64,
+) -> Result<(), AppError> {
+    let removes_admin = *current_role == UserRole::Admin && new_role != Some(&UserRole::Admin);
+    if removes_admin && admin_count <= 1 {
+        let action = if new_role.is_some() { "demote" } else { "delete" };
+        return Err(AppError::Validation(format!(
+            "Cannot {action} the last admin. Promote another user to admin first."
+        )));
+    }
+    Ok(())
+}
+
 #[cfg_attr(feature = "openapi", utoipa::path(put, path = "/api/admin/users/{user_id}/role", tag = "Admin - Users", security(("bearer_auth" = [])), params(("user_id" = String, Path, description = "User ID")), request_body = UpdateRoleRequest, responses((status = 200, body = UserResponse))))]
 pub async fn update_user_role(
     State(state): State<AppState>,
@@ -37,10 +76,19 @@ pub async fn update_user_role(
         ));
     }
 
+    let target = state
+        .user_repo
+        .find_by_id(&user_id)
+        .await?
+        .ok_or(AppError::NotFound("User not found".to_string()))?;
+    let admin_count = state.user_repo.count_by_role(&UserRole::Admin).await?;
+    ensure_admin_remains(&target.role, Some(&payload.role), admin_count)?;
+
     state.user_repo.update_role(&user_id, &payload.role).await?;
+    state.invalidate_user(&user_id).await;
 
-    audit::log(
-        &state.audit_log_repo,
+    audit::log_critical(
+        &state.audit,
         Some(&claims.sub),
         "admin.update_role",
         Some("user"),
@@ -48,7 +96,8 @@ pub async fn update_user_role(
         &format!("Updated user role to '{}'", payload.role),
         None,
         None,
-    );
+    )
+    .await?;
 
     let user = state
         .user_repo
@@ -73,16 +122,19 @@ pub async fn delete_user(
         ));
     }
 
-    state
+    let target = state
         .user_repo
         .find_by_id(&user_id)
         .await?
         .ok_or(AppError::NotFound("User not found".to_string()))?;
+    let admin_count = state.user_repo.count_by_role(&UserRole::Admin).await?;
+    ensure_admin_remains(&target.role, None, admin_count)?;
 
     state.user_repo.delete(&user_id).await?;
+    state.invalidate_user(&user_id).await;
 
-    audit::log(
-        &state.audit_log_repo,
+    audit::log_critical(
+        &state.audit,
         Some(&claims.sub),
         "admin.delete_user",
         Some("user"),
@@ -90,11 +142,89 @@ pub async fn delete_user(
         "Deleted user",
         None,
         None,
-    );
+    )
+    .await?;
 
     Ok(())
 }
 
+/// How long an impersonation lasts unless ended sooner.
+const IMPERSONATION_DURATION: Duration = Duration::from_secs(30 * 60);
+
+#[cfg_attr(feature = "openapi", utoipa::path(post, path = "/api/admin/users/{user_id}/impersonate", tag = "Admin - Users", security(("bearer_auth" = [])), params(("user_id" = String, Path, description = "User ID")), responses((status = 200, body = ImpersonationResponse), (status = 400, description = "The user is an admin or yourself"))))]
+pub async fn impersonate_user(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(user_id): Path<String>,
+) -> Result<Json<ImpersonationResponse>, AppError> {
+    require_admin(&claims)?;
+
+    if claims.sub == user_id {
+        return Err(AppError::Validation("Cannot impersonate yourself".to_string()));
+    }
+    let target = state
+        .user_repo
+        .find_by_id(&user_id)
+        .await?
+        .ok_or(AppError::NotFound("User not found".to_string()))?;
+    if target.role == UserRole::Admin {
+        return Err(AppError::Validation("Admins cannot be impersonated".to_string()));
+    }
+
+    let session = state
+        .impersonation_repo
+        .start(&claims.sub, &user_id, IMPERSONATION_DURATION)
+        .await?;
+    let token = auth_service::generate_impersonation_jwt(&target, &session, &state.config.auth)
+        .map_err(AppError::Internal)?;
+
+    audit::log_critical(
+        &state.audit,
+        Some(&claims.sub),
+        "admin.impersonation.start",
+        Some("user"),
+        Some(&user_id),
+        &format!("Started impersonating '{}'", target.username),
+        None,
+        Some(serde_json::json!({ "session_id": session.id })),
+    )
+    .await?;
+
+    let expires_at = chrono::DateTime::from_timestamp(session.expires_at, 0).unwrap_or_default();
+    Ok(Json(ImpersonationResponse {
+        token,
+        user: target.into(),
+        impersonator_id: claims.sub,
+        expires_at,
+    }))
+}
+
+#[cfg_attr(feature = "openapi", utoipa::path(delete, path = "/api/admin/users/{user_id}/impersonate", tag = "Admin - Users", security(("bearer_auth" = [])), params(("user_id" = String, Path, description = "User ID")), responses((status = 204))))]
+pub async fn end_impersonation(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(user_id): Path<String>,
+) -> Result<StatusCode, AppError> {
+    require_admin(&claims)?;
+
+    let ended = state.impersonation_repo.end(&claims.sub, &user_id).await?;
+    if ended > 0 {
+        audit::log_critical(
+            &state.audit,
+            Some(&claims.sub),
+            "admin.impersonation.end",
+            Some("user"),
+            Some(&user_id),
+            "Stopped impersonating user",
+            None,
+            None,
+        )
+        .await?;
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
 #[cfg_attr(feature = "openapi", utoipa::path(post, path = "/api/admin/invites", tag = "Admin - Users", security(("bearer_auth" = [])), request_body = InviteRequest, responses((status = 200, body = InviteResponse))))]
 pub async fn invite_user(
     State(state): State<AppState>,
@@ -103,24 +233,31 @@ pub async fn invite_user(
 ) -> Result<Json<InviteResponse>, AppError> {
     require_admin(&claims)?;
 
-    if payload.email.trim().is_empty() || !payload.email.contains('@') {
+    let email = normalize_email(&payload.email);
+    if email.is_empty() || !email.contains('@') {
         return Err(AppError::Validation("Valid email is required".to_string()));
     }
 
-    if state.user_repo.find_by_email(&payload.email).await?.is_some() {
+    if state.user_repo.find_by_email(&email).await?.is_some() {
         return Err(AppError::Validation(
             "A user with this email already exists".to_string(),
         ));
     }
 
+    if state.invite_repo.find_pending_by_email(&email).await?.is_some() {
+        return Err(AppError::Conflict(
+            "A pending invite for this email already exists".to_string(),
+        ));
+    }
+
     let invite = state
         .invite_repo
-        .create(&payload.email, &payload.role, &claims.sub, 48)
+        .create(&email, &payload.role, &claims.sub, 48)
         .await
         .map_err(AppError::Internal)?;
 
-    audit::log(
-        &state.audit_log_repo,
+    audit::log_critical(
+        &state.audit,
         Some(&claims.sub),
         "admin.invite",
         Some("invite"),
@@ -128,20 +265,72 @@ pub async fn invite_user(
         &format!("Invited '{}' with role '{}'", invite.email, invite.role),
         None,
         None,
-    );
-
-    // Send invite email (non-blocking - don't fail the request if email fails)
-    let email_service = state.email.clone();
-    let email = invite.email.clone();
-    let token = invite.token.clone();
-    tokio::spawn(async move {
-        if let Err(e) = email_service.send_invite(&email, &token).await {
-            tracing::error!("Failed to send invite email to {email}: {e}");
+    )
+    .await?;
+
+    // Sent in the background and retried; the invite list shows how it went
+    let email_status = match queue_invite_email(&state, &invite).await {
+        Ok(status) => Some(status),
+        Err(e) => {
+            tracing::error!("Failed to queue invite email to {}: {e:#}", invite.email);
+            None
         }
-    });
+    };
+
+    let frontend_url = &state.config.resend.frontend_url;
+    Ok(Json(invite_to_response(invite, frontend_url, email_status)))
+}
+
+async fn queue_invite_email(state: &AppState, invite: &Invite) -> anyhow::Result<EmailStatus> {
+    let message = EmailMessage::Invite {
+        token: invite.token.clone(),
+    };
+    let email = state
+        .email_outbox
+        .enqueue(&invite.email, message, Some(&invite.id))
+        .await?;
+    Ok(email.status)
+}
+
+/// Queue the invite email again, e.g. after it failed or got lost.
+#[cfg_attr(feature = "openapi", utoipa::path(post, path = "/api/admin/invites/{id}/resend-email", tag = "Admin - Users", security(("bearer_auth" = [])), params(("id" = String, Path, description = "Invite ID")), responses((status = 200, body = InviteResponse), (status = 400, description = "Invite already used or expired"), (status = 404, description = "Invite not found"))))]
+pub async fn resend_invite_email(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(id): Path<String>,
+) -> Result<Json<InviteResponse>, AppError> {
+    require_admin(&claims)?;
+
+    let invite = state
+        .invite_repo
+        .find_by_id(&id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Invite not found".to_string()))?;
+    if invite.used {
+        return Err(AppError::Validation("This invite has already been used".to_string()));
+    }
+    if invite.expires_at < chrono::Utc::now() {
+        return Err(AppError::Validation(
+            "This invite has expired; invite the user again".to_string(),
+        ));
+    }
+
+    let email_status = queue_invite_email(&state, &invite).await.map_err(AppError::Internal)?;
+
+    audit::log_critical(
+        &state.audit,
+        Some(&claims.sub),
+        "admin.invite_resend",
+        Some("invite"),
+        Some(&invite.id),
+        &format!("Resent the invite email to '{}'", invite.email),
+        None,
+        None,
+    )
+    .await?;
 
     let frontend_url = &state.config.resend.frontend_url;
-    Ok(Json(invite_to_response(invite, frontend_url)))
+    Ok(Json(invite_to_response(invite, frontend_url, Some(email_status))))
 }
 
 #[cfg_attr(feature = "openapi", utoipa::path(get, path = "/api/admin/invites", tag = "Admin - Users", security(("bearer_auth" = [])), responses((status = 200, body = Vec<InviteResponse>))))]
@@ -157,14 +346,19 @@ pub async fn list_invites(
         .find_all()
         .await
         .map_err(AppError::Internal)?;
+    let ids: Vec<String> = invites.iter().map(|i| i.id.clone()).collect();
+    let mut email_statuses = state.email_outbox.repo().latest_statuses(&ids).await?;
     let responses: Vec<InviteResponse> = invites
         .into_iter()
-        .map(|i| invite_to_response(i, &frontend_url))
+        .map(|i| {
+            let email_status = email_statuses.remove(&i.id);
+            invite_to_response(i, &frontend_url, email_status)
+        })
         .collect();
     Ok(Json(responses))
 }
 
-fn invite_to_response(invite: Invite, frontend_url: &str) -> InviteResponse {
+fn invite_to_response(invite: Invite, frontend_url: &str, email_status: Option<EmailStatus>) -> InviteResponse {
     let setup_link = format!("{}/setup?token={}", frontend_url, invite.token);
     InviteResponse {
         id: invite.id,
@@ -172,7 +366,32 @@ fn invite_to_response(invite: Invite, frontend_url: &str) -> InviteResponse {
         role: invite.role,
         used: invite.used,
         setup_link,
+        email_status,
         expires_at: invite.expires_at,
         created_at: invite.created_at,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_last_admin_cannot_be_demoted_or_deleted() {
+        let demote = ensure_admin_remains(&UserRole::Admin, Some(&UserRole::Maintainer), 1);
+        assert!(matches!(demote, Err(AppError::Validation(msg)) if msg.contains("demote the last admin")));
+
+        let delete = ensure_admin_remains(&UserRole::Admin, None, 1);
+        assert!(matches!(delete, Err(AppError::Validation(msg)) if msg.contains("delete the last admin")));
+    }
+
+    #[test]
+    fn test_admin_changes_allowed_while_another_admin_remains() {
+        assert!(ensure_admin_remains(&UserRole::Admin, Some(&UserRole::User), 2).is_ok());
+        assert!(ensure_admin_remains(&UserRole::Admin, None, 2).is_ok());
+        // Keeping the role, or changing non-admins, never removes an admin
+        assert!(ensure_admin_remains(&UserRole::Admin, Some(&UserRole::Admin), 1).is_ok());
+        assert!(ensure_admin_remains(&UserRole::Maintainer, Some(&UserRole::User), 1).is_ok());
+        assert!(ensure_admin_remains(&UserRole::User, None, 1).is_ok());
+    }
+}
@@ -1,12 +1,24 @@
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     Json,
 };
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
-use crate::db::models::admin_config::{AddModelRequest, AdminModel, AdminProvider};
+use crate::db::models::admin_api_key::AdminApiKeyEntry;
+use crate::db::models::org_settings::{OrgSettings, SystemPromptRevision};
+use crate::db::models::admin_config::{
+    AddModelRequest, AdminModel, AdminProvider, CatalogSyncSummary, ModelSyncSummary,
+    UpdateModelRequest,
+};
 use crate::errors::AppError;
 use crate::middleware::auth::{require_admin, Claims};
+use crate::routes::admin_embed::{
+    generate_key, validate_localizations, validate_message_limit, validate_notification_email,
+    validate_office_hours, validate_rag_params, validate_theme,
+};
+use crate::routes::settings::SetApiKeyRequest;
+use crate::services::config_transfer::{self, ConfigChange, ConfigDocument, NewEmbedKey};
+use crate::services::{audit, llm_provider, provider_api};
 use crate::state::AppState;
 
 #[cfg_attr(feature = "openapi", utoipa::path(get, path = "/api/admin/config/providers", tag = "Admin - Config", security(("bearer_auth" = [])), responses((status = 200, body = Vec<AdminProvider>))))]
@@ -40,6 +52,33 @@ pub async fn toggle_provider(
     Ok(())
 }
 
+#[cfg_attr(feature = "openapi", utoipa::path(put, path = "/api/admin/config/providers/{provider_id}/default-embedding", tag = "Admin - Config", security(("bearer_auth" = [])), params(("provider_id" = String, Path, description = "Provider ID")), responses((status = 200))))]
+pub async fn set_default_embedding_provider(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(provider_id): Path<String>,
+) -> Result<(), AppError> {
+    require_admin(&claims)?;
+    state
+        .admin_config_repo
+        .set_default_embedding_provider(&provider_id)
+        .await
+        .map_err(|e| AppError::Validation(e.to_string()))?;
+
+    audit::log(
+        &state.audit,
+        Some(&claims.sub),
+        "admin.config.default_embedding_provider",
+        Some("provider"),
+        Some(&provider_id),
+        &format!("Set '{provider_id}' as the default embedding provider"),
+        None,
+        None,
+    );
+
+    Ok(())
+}
+
 #[cfg_attr(feature = "openapi", utoipa::path(get, path = "/api/admin/config/providers/{provider_id}/models", tag = "Admin - Config", security(("bearer_auth" = [])), params(("provider_id" = String, Path, description = "Provider ID")), responses((status = 200, body = Vec<AdminModel>))))]
 pub async fn list_models(
     State(state): State<AppState>,
@@ -51,7 +90,7 @@ pub async fn list_models(
     Ok(Json(models))
 }
 
-#[cfg_attr(feature = "openapi", utoipa::path(post, path = "/api/admin/config/providers/{provider_id}/models", tag = "Admin - Config", security(("bearer_auth" = [])), params(("provider_id" = String, Path, description = "Provider ID")), request_body = AddModelRequest, responses((status = 200, body = AdminModel))))]
+#[cfg_attr(feature = "openapi", utoipa::path(post, path = "/api/admin/config/providers/{provider_id}/models", tag = "Admin - Config", security(("bearer_auth" = [])), params(("provider_id" = String, Path, description = "Provider ID")), request_body = AddModelRequest, responses((status = 200, body = AdminModel), (status = 404, description = "Provider not found"), (status = 409, description = "The provider already has this model"))))]
 pub async fn add_model(
     State(state): State<AppState>,
     claims: Claims,
@@ -63,16 +102,330 @@ pub async fn add_model(
     if payload.model_id.trim().is_empty() {
         return Err(AppError::Validation("Model ID is required".to_string()));
     }
+    validate_display_name(&payload.display_name)?;
     if payload.model_type != "completion" && payload.model_type != "embedding" {
         return Err(AppError::Validation(
             "model_type must be 'completion' or 'embedding'".to_string(),
         ));
     }
 
+    if !state.admin_config_repo.provider_exists(&provider_id).await? {
+        return Err(AppError::NotFound(format!("Provider '{provider_id}' not found")));
+    }
+
     let model = state
         .admin_config_repo
         .add_model(&provider_id, &payload)
+        .await?
+        .ok_or_else(|| {
+            AppError::Conflict(format!(
+                "Model '{}' is already configured as a {} model for this provider",
+                payload.model_id, payload.model_type
+            ))
+        })?;
+    Ok(Json(model))
+}
+
+#[cfg_attr(feature = "openapi", utoipa::path(post, path = "/api/admin/config/providers/{provider_id}/models/sync", tag = "Admin - Config", security(("bearer_auth" = [])), params(("provider_id" = String, Path, description = "Provider ID")), responses((status = 200, body = ModelSyncSummary))))]
+pub async fn sync_models(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(provider_id): Path<String>,
+) -> Result<Json<ModelSyncSummary>, AppError> {
+    require_admin(&claims)?;
+
+    if !provider_api::supports_live_models(&provider_id) {
+        return Err(AppError::Validation(format!(
+            "Live model discovery is not supported for provider '{provider_id}'"
+        )));
+    }
+
+    // Prefer the organization key, then the admin's own
+    let credentials = match state.admin_api_key_repo.get_credentials(&provider_id).await? {
+        Some(c) => Some(c),
+        None => state
+            .credentials
+            .resolve(&claims.sub, &provider_id)
+            .await?
+            .map(|r| r.credentials),
+    };
+    let api_key = credentials.as_ref().map(|c| c.api_key.clone()).unwrap_or_default();
+    let base_url = credentials.and_then(|c| c.base_url);
+
+    if api_key.is_empty() && llm_provider::requires_api_key(&provider_id) {
+        return Err(AppError::Validation(format!(
+            "No API key configured for provider '{provider_id}'"
+        )));
+    }
+
+    let models = state
+        .model_catalog
+        .refresh(&provider_id, &api_key, base_url.as_deref())
+        .await
+        .map_err(|e| AppError::Validation(format!("Failed to fetch models: {e}")))?;
+
+    // An empty list is far more likely a misconfigured endpoint than a provider with no models
+    if models.is_empty() {
+        return Err(AppError::Validation(
+            "Provider returned no models; nothing was synced".to_string(),
+        ));
+    }
+
+    let summary = state
+        .admin_config_repo
+        .sync_models(&provider_id, &models)
         .await?;
+
+    audit::log(
+        &state.audit,
+        Some(&claims.sub),
+        "admin.config.sync_models",
+        Some("provider"),
+        Some(&provider_id),
+        &format!(
+            "Synced models for '{provider_id}': {} added, {} restored, {} removed",
+            summary.added.len(),
+            summary.restored.len(),
+            summary.removed.len()
+        ),
+        None,
+        None,
+    );
+
+    Ok(Json(summary))
+}
+
+/// Add providers and models from the built-in catalogue that are missing here,
+/// as happens on every start. Existing configuration is left alone.
+#[cfg_attr(feature = "openapi", utoipa::path(post, path = "/api/admin/config/sync-catalog", tag = "Admin - Config", security(("bearer_auth" = [])), responses((status = 200, body = CatalogSyncSummary))))]
+pub async fn sync_catalog(
+    State(state): State<AppState>,
+    claims: Claims,
+) -> Result<Json<CatalogSyncSummary>, AppError> {
+    require_admin(&claims)?;
+
+    let summary = state.admin_config_repo.seed_defaults().await?;
+
+    audit::log(
+        &state.audit,
+        Some(&claims.sub),
+        "admin.config.sync_catalog",
+        None,
+        None,
+        &format!(
+            "Synced provider catalogue: {} providers and {} models added",
+            summary.providers_added.len(),
+            summary.models_added.len()
+        ),
+        None,
+        None,
+    );
+
+    Ok(Json(summary))
+}
+
+// ── Export / import ─────────────────────────────────────────
+async fn current_document(state: &AppState) -> Result<ConfigDocument, AppError> {
+    let providers = state.admin_config_repo.list_providers().await?;
+    let models = state.admin_config_repo.list_all_models().await?;
+    let embed_keys = state.embed_key_repo.list_all().await?;
+    Ok(config_transfer::build_document(
+        &providers,
+        &models,
+        &embed_keys,
+        &state.config.features,
+    ))
+}
+
+/// Providers, models, embed keys and feature flags as a versioned document.
+/// API keys and embed key secrets are left out.
+#[cfg_attr(feature = "openapi", utoipa::path(get, path = "/api/admin/config/export", tag = "Admin - Config", security(("bearer_auth" = [])), responses((status = 200, body = ConfigDocument))))]
+pub async fn export_config(
+    State(state): State<AppState>,
+    claims: Claims,
+) -> Result<Json<ConfigDocument>, AppError> {
+    require_admin(&claims)?;
+    let document = current_document(&state).await?;
+
+    audit::log(
+        &state.audit,
+        Some(&claims.sub),
+        "admin.config.export",
+        None,
+        None,
+        "Exported configuration",
+        None,
+        None,
+    );
+
+    Ok(Json(document))
+}
+
+#[derive(Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams))]
+pub struct ImportQuery {
+    /// Only report what would change.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CreatedEmbedKey {
+    pub id: String,
+    pub name: String,
+    /// Shown once; the export never contains embed key secrets.
+    pub raw_key: String,
+}
+
+#[derive(Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ConfigImportResponse {
+    pub dry_run: bool,
+    pub changes: Vec<ConfigChange>,
+    pub warnings: Vec<String>,
+    pub created_embed_keys: Vec<CreatedEmbedKey>,
+}
+
+/// Apply the checks the individual endpoints do, normalizing values in place.
+fn validate_document(document: &mut ConfigDocument) -> Result<(), AppError> {
+    for model in &mut document.models {
+        validate_display_name(&model.display_name)?;
+        model.display_name = model.display_name.trim().to_string();
+    }
+    for key in &mut document.embed_keys {
+        key.name = key.name.trim().to_string();
+        if key.name.is_empty() {
+            return Err(AppError::Validation(format!("Embed key '{}' has no name", key.id)));
+        }
+        key.base_url = key
+            .base_url
+            .as_deref()
+            .filter(|u| !u.trim().is_empty())
+            .map(llm_provider::validate_base_url)
+            .transpose()
+            .map_err(|e| AppError::Validation(e.to_string()))?;
+        key.localizations = validate_localizations(std::mem::take(&mut key.localizations))?;
+        key.handoff_notification_email =
+            validate_notification_email(key.handoff_notification_email.take())?;
+        validate_message_limit("daily_message_limit", key.daily_message_limit)?;
+        validate_message_limit("monthly_message_limit", key.monthly_message_limit)?;
+        validate_rag_params(key.rag_top_k, key.rag_min_score, key.rag_max_context_chars)?;
+        (key.theme, key.primary_color) = validate_theme(std::mem::take(&mut key.theme), &key.primary_color)?;
+        key.office_hours = validate_office_hours(key.office_hours.take())?;
+        key.offline_message = key.offline_message.trim().to_string();
+    }
+    Ok(())
+}
+
+/// Apply an export from `GET /api/admin/config/export` in one transaction.
+/// Entries missing from the document are left alone. With `dry_run=true` only
+/// the changes are returned.
+#[cfg_attr(feature = "openapi", utoipa::path(post, path = "/api/admin/config/import", tag = "Admin - Config", security(("bearer_auth" = [])), params(ImportQuery), request_body = ConfigDocument, responses((status = 200, body = ConfigImportResponse), (status = 400, description = "Invalid document or newer schema version"))))]
+pub async fn import_config(
+    State(state): State<AppState>,
+    claims: Claims,
+    Query(query): Query<ImportQuery>,
+    Json(body): Json<serde_json::Value>,
+) -> Result<Json<ConfigImportResponse>, AppError> {
+    require_admin(&claims)?;
+
+    let mut incoming = config_transfer::parse_document(body).map_err(AppError::Validation)?;
+    validate_document(&mut incoming)?;
+    let current = current_document(&state).await?;
+    let plan = config_transfer::plan_import(&current, &incoming).map_err(AppError::Validation)?;
+
+    let mut created_embed_keys = Vec::new();
+    if !query.dry_run && !plan.changes.is_empty() {
+        let mut new_embed_keys = Vec::new();
+        for settings in &plan.new_embed_keys {
+            let (raw_key, key_hash, key_prefix) = generate_key();
+            created_embed_keys.push(CreatedEmbedKey {
+                id: settings.id.clone(),
+                name: settings.name.clone(),
+                raw_key,
+            });
+            new_embed_keys.push(NewEmbedKey { settings: settings.clone(), key_hash, key_prefix });
+        }
+        state.admin_config_repo.apply_import(&plan, &new_embed_keys).await?;
+        state.embed_key_cache.clear().await;
+
+        audit::log(
+            &state.audit,
+            Some(&claims.sub),
+            "admin.config.import",
+            None,
+            None,
+            &format!(
+                "Imported configuration (schema version {}): {} changes",
+                incoming.schema_version,
+                plan.changes.len()
+            ),
+            None,
+            None,
+        );
+    }
+
+    Ok(Json(ConfigImportResponse {
+        dry_run: query.dry_run,
+        changes: plan.changes,
+        warnings: plan.warnings,
+        created_embed_keys,
+    }))
+}
+
+const MAX_MODEL_DISPLAY_NAME_CHARS: usize = 100;
+
+fn validate_display_name(name: &str) -> Result<(), AppError> {
+    if name.trim().is_empty() {
+        return Err(AppError::Validation("Display name is required".to_string()));
+    }
+    if name.chars().count() > MAX_MODEL_DISPLAY_NAME_CHARS {
+        return Err(AppError::Validation(format!(
+            "Display name must be at most {MAX_MODEL_DISPLAY_NAME_CHARS} characters"
+        )));
+    }
+    Ok(())
+}
+
+#[cfg_attr(feature = "openapi", utoipa::path(put, path = "/api/admin/config/models/{model_id}", tag = "Admin - Config", security(("bearer_auth" = [])), params(("model_id" = String, Path, description = "Model ID")), request_body = UpdateModelRequest, responses((status = 200, body = AdminModel), (status = 404, description = "Model not found"))))]
+pub async fn update_model(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(model_id): Path<String>,
+    Json(mut payload): Json<UpdateModelRequest>,
+) -> Result<Json<AdminModel>, AppError> {
+    require_admin(&claims)?;
+
+    if let Some(name) = &payload.display_name {
+        validate_display_name(name)?;
+        payload.display_name = Some(name.trim().to_string());
+    }
+
+    let model = state
+        .admin_config_repo
+        .update_model(&model_id, &payload)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Model not found".to_string()))?;
+
+    audit::log(
+        &state.audit,
+        Some(&claims.sub),
+        "admin.config.update_model",
+        Some("model"),
+        Some(&model.id),
+        &format!(
+            "Updated model '{}' ({}): display name '{}', sort order {}, {}",
+            model.model_id,
+            model.provider_id,
+            model.display_name,
+            model.sort_order,
+            if model.is_enabled { "enabled" } else { "disabled" }
+        ),
+        None,
+        None,
+    );
+
     Ok(Json(model))
 }
 
@@ -97,3 +450,200 @@ pub async fn set_default_model(
     state.admin_config_repo.set_default_model(&model_id).await?;
     Ok(())
 }
+
+// ── Organization API keys ───────────────────────────────────
+#[cfg_attr(feature = "openapi", utoipa::path(get, path = "/api/admin/config/api-keys", tag = "Admin - Config", security(("bearer_auth" = [])), responses((status = 200, body = Vec<AdminApiKeyEntry>))))]
+pub async fn list_api_keys(
+    State(state): State<AppState>,
+    claims: Claims,
+) -> Result<Json<Vec<AdminApiKeyEntry>>, AppError> {
+    require_admin(&claims)?;
+    let keys = state.admin_api_key_repo.list().await?;
+    Ok(Json(keys))
+}
+
+#[cfg_attr(feature = "openapi", utoipa::path(get, path = "/api/admin/config/api-keys/{provider}", tag = "Admin - Config", security(("bearer_auth" = [])), params(("provider" = String, Path, description = "Provider name")), responses((status = 200, body = AdminApiKeyEntry))))]
+pub async fn get_api_key(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(provider): Path<String>,
+) -> Result<Json<AdminApiKeyEntry>, AppError> {
+    require_admin(&claims)?;
+    let key = state
+        .admin_api_key_repo
+        .find(&provider)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Organization API key not found".to_string()))?;
+    Ok(Json(key))
+}
+
+#[cfg_attr(feature = "openapi", utoipa::path(put, path = "/api/admin/config/api-keys/{provider}", tag = "Admin - Config", security(("bearer_auth" = [])), params(("provider" = String, Path, description = "Provider name")), request_body = SetApiKeyRequest, responses((status = 200, body = AdminApiKeyEntry))))]
+pub async fn set_api_key(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(provider): Path<String>,
+    Json(payload): Json<SetApiKeyRequest>,
+) -> Result<Json<AdminApiKeyEntry>, AppError> {
+    require_admin(&claims)?;
+
+    if payload.api_key.trim().is_empty() && llm_provider::requires_api_key(&provider) {
+        return Err(AppError::Validation("API key cannot be empty".to_string()));
+    }
+
+    let base_url = payload
+        .base_url
+        .as_deref()
+        .filter(|u| !u.trim().is_empty())
+        .map(llm_provider::validate_base_url)
+        .transpose()
+        .map_err(|e| AppError::Validation(e.to_string()))?;
+
+    let entry = state
+        .admin_api_key_repo
+        .upsert(&provider, payload.api_key.trim(), base_url.as_deref(), &claims.sub)
+        .await?;
+
+    audit::log(
+        &state.audit,
+        Some(&claims.sub),
+        "admin.config.update_api_key",
+        Some("admin_api_key"),
+        Some(&provider),
+        &format!("Updated organization API key for provider '{provider}'"),
+        None,
+        None,
+    );
+
+    Ok(Json(entry))
+}
+
+#[cfg_attr(feature = "openapi", utoipa::path(delete, path = "/api/admin/config/api-keys/{provider}", tag = "Admin - Config", security(("bearer_auth" = [])), params(("provider" = String, Path, description = "Provider name")), responses((status = 200))))]
+pub async fn delete_api_key(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(provider): Path<String>,
+) -> Result<(), AppError> {
+    require_admin(&claims)?;
+
+    if !state.admin_api_key_repo.delete(&provider).await? {
+        return Err(AppError::NotFound("Organization API key not found".to_string()));
+    }
+
+    audit::log(
+        &state.audit,
+        Some(&claims.sub),
+        "admin.config.delete_api_key",
+        Some("admin_api_key"),
+        Some(&provider),
+        &format!("Deleted organization API key for provider '{provider}'"),
+        None,
+        None,
+    );
+
+    Ok(())
+}
+
+// ── Base system prompt ──────────────────────────────────────
+const MAX_BASE_SYSTEM_PROMPT_CHARS: usize = 20_000;
+
+/// Replaced base prompts listed with the current one.
+const SYSTEM_PROMPT_HISTORY_LIMIT: i64 = 50;
+
+#[derive(Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SystemPromptResponse {
+    #[serde(flatten)]
+    pub settings: OrgSettings,
+    /// Earlier base prompts, most recently replaced first.
+    pub history: Vec<SystemPromptRevision>,
+}
+
+#[derive(Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UpdateSystemPromptRequest {
+    /// Empty removes the base prompt.
+    pub base_system_prompt: String,
+    /// Let users read the base prompt; unchanged when absent.
+    pub visible: Option<bool>,
+}
+
+async fn system_prompt_response(state: &AppState, settings: OrgSettings) -> Result<SystemPromptResponse, AppError> {
+    let history = state
+        .org_settings_repo
+        .system_prompt_history(SYSTEM_PROMPT_HISTORY_LIMIT)
+        .await?;
+    Ok(SystemPromptResponse { settings, history })
+}
+
+#[cfg_attr(feature = "openapi", utoipa::path(get, path = "/api/admin/config/system-prompt", tag = "Admin - Config", security(("bearer_auth" = [])), responses((status = 200, body = SystemPromptResponse))))]
+pub async fn get_system_prompt(
+    State(state): State<AppState>,
+    claims: Claims,
+) -> Result<Json<SystemPromptResponse>, AppError> {
+    require_admin(&claims)?;
+    let settings = state.org_settings_repo.get().await?;
+    Ok(Json(system_prompt_response(&state, settings).await?))
+}
+
+/// Set the organization's base system prompt, which comes before every user's
+/// and embed key's own prompt. The one it replaces is kept in the history.
+#[cfg_attr(feature = "openapi", utoipa::path(put, path = "/api/admin/config/system-prompt", tag = "Admin - Config", security(("bearer_auth" = [])), request_body = UpdateSystemPromptRequest, responses((status = 200, body = SystemPromptResponse))))]
+pub async fn set_system_prompt(
+    State(state): State<AppState>,
+    claims: Claims,
+    Json(payload): Json<UpdateSystemPromptRequest>,
+) -> Result<Json<SystemPromptResponse>, AppError> {
+    require_admin(&claims)?;
+
+    let prompt = payload.base_system_prompt.trim();
+    if prompt.chars().count() > MAX_BASE_SYSTEM_PROMPT_CHARS {
+        return Err(AppError::Validation(format!(
+            "Base system prompt must be at most {MAX_BASE_SYSTEM_PROMPT_CHARS} characters"
+        )));
+    }
+    let visible = match payload.visible {
+        Some(visible) => visible,
+        None => state.org_settings_repo.get().await?.base_system_prompt_visible,
+    };
+
+    let settings = state
+        .org_settings_repo
+        .set_base_system_prompt(prompt, visible, &claims.sub)
+        .await?;
+
+    audit::log_critical(
+        &state.audit,
+        Some(&claims.sub),
+        "admin.config.system_prompt",
+        Some("org_settings"),
+        None,
+        &if prompt.is_empty() {
+            "Removed the base system prompt".to_string()
+        } else {
+            format!(
+                "Set the base system prompt ({} characters, {} to users)",
+                prompt.chars().count(),
+                if visible { "visible" } else { "hidden" }
+            )
+        },
+        None,
+        None,
+    )
+    .await?;
+
+    Ok(Json(system_prompt_response(&state, settings).await?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_display_name() {
+        assert!(validate_display_name("GPT-4o").is_ok());
+        assert!(validate_display_name(&"é".repeat(100)).is_ok());
+        assert!(validate_display_name("").is_err());
+        assert!(validate_display_name("   ").is_err());
+        assert!(validate_display_name(&"a".repeat(101)).is_err());
+    }
+}
@@ -1,15 +1,27 @@
 use axum::{
-    extract::{Path, State},
+    extract::{multipart::MultipartError, Multipart, Path, Query, State},
+    http::StatusCode,
     Json,
 };
 use rand::Rng;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
-use crate::db::models::embed_key::{EmbedKey, UpdateEmbedKeyRequest};
+use crate::db::models::embed_key::{
+    EmbedKey, EmbedKeyUsage, OfficeHours, OfflineBehavior, UpdateEmbedKeyRequest, WidgetLocalization,
+    WidgetTheme,
+};
+use crate::db::models::widget_handoff::{WidgetHandoff, HANDOFF_STATUSES};
+use crate::db::models::widget_session::WidgetSessionPurge;
 use crate::errors::AppError;
 use crate::middleware::auth::{require_admin, Claims};
 use crate::middleware::embed_auth::hash_key;
-use crate::services::audit;
+use crate::routes::collections::require_collection;
+use crate::routes::settings::{run_key_test, ApiKeyTestResponse};
+use crate::services::email::looks_like_email;
+use crate::services::{
+    audit, embed_key_check, llm_provider, locale, office_hours, widget_avatar, widget_theme,
+};
 use crate::state::AppState;
 
 #[derive(Deserialize)]
@@ -21,10 +33,17 @@ pub struct CreateEmbedKeyRequest {
     #[serde(default)]
     pub system_prompt: String,
     pub rate_limit: Option<i32>,
+    /// Messages all sessions together may send per UTC day; unlimited when omitted.
+    pub daily_message_limit: Option<i32>,
+    /// Messages all sessions together may send per calendar month; unlimited when omitted.
+    pub monthly_message_limit: Option<i32>,
     #[serde(default = "default_widget_title")]
     pub widget_title: String,
+    /// Shorthand for `theme.light.primary`, used when the theme doesn't set it.
     #[serde(default = "default_primary_color")]
     pub primary_color: String,
+    #[serde(default)]
+    pub theme: WidgetTheme,
     #[serde(default = "default_greeting")]
     pub greeting_message: String,
     #[serde(default)]
@@ -33,15 +52,162 @@ pub struct CreateEmbedKeyRequest {
     pub model: String,
     #[serde(default)]
     pub api_key: String,
+    pub base_url: Option<String>,
     #[serde(default)]
     pub custom_css: String,
+    pub rag_top_k: Option<i32>,
+    pub rag_min_score: Option<f32>,
+    pub rag_max_context_chars: Option<i32>,
+    /// Per-locale overrides, e.g. `{"de": {"greeting_message": "Hallo!"}}`.
+    #[serde(default)]
+    pub localizations: BTreeMap<String, WidgetLocalization>,
+    #[serde(default)]
+    pub handoff_enabled: bool,
+    pub handoff_notification_email: Option<String>,
+    #[serde(default)]
+    pub transcript_email_enabled: bool,
+    /// When the widget is staffed, e.g.
+    /// `{"timezone": "Europe/Berlin", "days": {"mon": [{"start": "09:00", "end": "17:00"}]}}`.
+    /// Always online when omitted.
+    pub office_hours: Option<OfficeHours>,
+    #[serde(default)]
+    pub offline_message: String,
+    #[serde(default)]
+    pub offline_behavior: OfflineBehavior,
+}
+
+/// Trim the handoff notification address; blank means no notifications.
+pub(crate) fn validate_notification_email(email: Option<String>) -> Result<Option<String>, AppError> {
+    let Some(email) = email.map(|e| e.trim().to_string()).filter(|e| !e.is_empty()) else {
+        return Ok(None);
+    };
+    if !looks_like_email(&email) {
+        return Err(AppError::Validation(format!(
+            "'{email}' is not a valid handoff notification email"
+        )));
+    }
+    Ok(Some(email))
+}
+
+pub(crate) fn validate_office_hours(hours: Option<OfficeHours>) -> Result<Option<OfficeHours>, AppError> {
+    hours
+        .map(office_hours::validate)
+        .transpose()
+        .map_err(|e| AppError::Validation(e.to_string()))
+}
+
+/// Message caps must allow at least one message; `None` is unlimited.
+pub(crate) fn validate_message_limit(field: &str, limit: Option<i32>) -> Result<Option<i32>, AppError> {
+    match limit {
+        Some(limit) if limit < 1 => Err(AppError::Validation(format!(
+            "{field} must be at least 1, or null for no limit"
+        ))),
+        _ => Ok(limit),
+    }
+}
+
+/// Retrieval overrides must be usable by the search; `None` falls back to the global defaults.
+pub(crate) fn validate_rag_params(
+    top_k: Option<i32>,
+    min_score: Option<f32>,
+    max_context_chars: Option<i32>,
+) -> Result<(), AppError> {
+    if let Some(top_k) = top_k.filter(|k| !(1..=50).contains(k)) {
+        return Err(AppError::Validation(format!(
+            "rag_top_k must be between 1 and 50, got {top_k}"
+        )));
+    }
+    if let Some(min_score) = min_score.filter(|s| !(0.0..=1.0).contains(s)) {
+        return Err(AppError::Validation(format!(
+            "rag_min_score must be between 0.0 and 1.0, got {min_score}"
+        )));
+    }
+    if let Some(max_chars) = max_context_chars.filter(|c| !(1..=200_000).contains(c)) {
+        return Err(AppError::Validation(format!(
+            "rag_max_context_chars must be between 1 and 200000, got {max_chars}"
+        )));
+    }
+    Ok(())
+}
+
+fn validate_primary_color(color: &str) -> Result<String, AppError> {
+    let color = color.trim();
+    if !widget_theme::is_valid_color(color) {
+        return Err(AppError::Validation(format!(
+            "primary_color '{color}' is not a hex, rgb() or hsl() color"
+        )));
+    }
+    Ok(color.to_string())
+}
+
+/// Validate a theme and keep it in sync with `primary_color`, the shorthand for
+/// its light primary color. A primary color set in the theme itself wins.
+pub(crate) fn validate_theme(
+    theme: WidgetTheme,
+    primary_color: &str,
+) -> Result<(WidgetTheme, String), AppError> {
+    let mut theme = widget_theme::validate(theme).map_err(|e| AppError::Validation(e.to_string()))?;
+    let primary_color = match theme.light.primary.clone() {
+        Some(primary) => primary,
+        None => validate_primary_color(primary_color)?,
+    };
+    theme.light.primary = Some(primary_color.clone());
+    Ok((theme, primary_color))
+}
+
+/// Normalize locale codes and drop blank texts so they fall back to the defaults.
+pub(crate) fn validate_localizations(
+    localizations: BTreeMap<String, WidgetLocalization>,
+) -> Result<BTreeMap<String, WidgetLocalization>, AppError> {
+    let mut validated = BTreeMap::new();
+    for (code, localization) in localizations {
+        let normalized = locale::normalize_locale(&code).ok_or_else(|| {
+            AppError::Validation(format!(
+                "Invalid locale code '{code}'. Use a language tag such as 'de' or 'pt-BR'"
+            ))
+        })?;
+        let non_blank = |text: Option<String>| text.filter(|t| !t.trim().is_empty());
+        let localization = WidgetLocalization {
+            widget_title: non_blank(localization.widget_title),
+            greeting_message: non_blank(localization.greeting_message),
+            system_prompt: non_blank(localization.system_prompt),
+            offline_message: non_blank(localization.offline_message),
+        };
+        if validated.insert(normalized.clone(), localization).is_some() {
+            return Err(AppError::Validation(format!(
+                "Locale '{normalized}' is listed more than once"
+            )));
+        }
+    }
+    Ok(validated)
+}
+
+/// A new random embed key with its hash and display prefix.
+pub(crate) fn generate_key() -> (String, String, String) {
+    // Scoped so the non-Send RNG is dropped before any await
+    let raw_key = {
+        let mut rng = rand::rng();
+        let mut key_bytes = [0u8; 32];
+        rng.fill(&mut key_bytes);
+        format!(
+            "ek_{}",
+            key_bytes
+                .iter()
+                .map(|b| format!("{b:02x}"))
+                .collect::<String>()
+        )
+    };
+
+    let key_hash = hash_key(&raw_key);
+    let key_prefix = raw_key[..11.min(raw_key.len())].to_string(); // "ek_" + first 8 hex chars
+    (raw_key, key_hash, key_prefix)
 }
 
 fn default_widget_title() -> String {
     "Chat with us".to_string()
 }
 fn default_primary_color() -> String {
-    "#2563eb".to_string()
+    widget_theme::DEFAULT_PRIMARY_COLOR.to_string()
 }
 fn default_greeting() -> String {
     "Hello! How can I help you?".to_string()
@@ -66,22 +232,25 @@ pub async fn create_key(
         return Err(AppError::Validation("Name is required".to_string()));
     }
 
-    // Generate cryptographically random key (scoped to avoid Send issue)
-    let raw_key = {
-        let mut rng = rand::rng();
-        let mut key_bytes = [0u8; 32];
-        rng.fill(&mut key_bytes);
-        format!(
-            "ek_{}",
-            key_bytes
-                .iter()
-                .map(|b| format!("{b:02x}"))
-                .collect::<String>()
-        )
-    };
+    let (raw_key, key_hash, key_prefix) = generate_key();
 
-    let key_hash = hash_key(&raw_key);
-    let key_prefix = &raw_key[..11.min(raw_key.len())]; // "ek_" + first 8 hex chars
+    let base_url = payload
+        .base_url
+        .as_deref()
+        .filter(|u| !u.trim().is_empty())
+        .map(llm_provider::validate_base_url)
+        .transpose()
+        .map_err(|e| AppError::Validation(e.to_string()))?;
+
+    let localizations = validate_localizations(payload.localizations)?;
+    let handoff_notification_email = validate_notification_email(payload.handoff_notification_email)?;
+    let daily_message_limit = validate_message_limit("daily_message_limit", payload.daily_message_limit)?;
+    let monthly_message_limit =
+        validate_message_limit("monthly_message_limit", payload.monthly_message_limit)?;
+    let (theme, primary_color) = validate_theme(payload.theme, &payload.primary_color)?;
+    let office_hours = validate_office_hours(payload.office_hours)?;
+    validate_rag_params(payload.rag_top_k, payload.rag_min_score, payload.rag_max_context_chars)?;
+    embed_key_check::validate_model_choice(&state, &payload.provider, &payload.model).await?;
 
     let id = uuid::Uuid::new_v4().to_string();
     let rate_limit = payload
@@ -94,22 +263,36 @@ pub async fn create_key(
             &id,
             payload.name.trim(),
             &key_hash,
-            key_prefix,
+            &key_prefix,
             &payload.allowed_domains,
             &payload.system_prompt,
             rate_limit,
+            daily_message_limit,
+            monthly_message_limit,
             &payload.widget_title,
-            &payload.primary_color,
+            &primary_color,
+            &theme,
             &payload.greeting_message,
             &payload.provider,
             &payload.model,
             &payload.api_key,
+            base_url.as_deref(),
             &payload.custom_css,
+            payload.rag_top_k,
+            payload.rag_min_score,
+            payload.rag_max_context_chars,
+            &localizations,
+            payload.handoff_enabled,
+            handoff_notification_email.as_deref(),
+            payload.transcript_email_enabled,
+            office_hours.as_ref(),
+            payload.offline_message.trim(),
+            payload.offline_behavior,
         )
         .await?;
 
-    audit::log(
-        &state.audit_log_repo,
+    audit::log_critical(
+        &state.audit,
         Some(&claims.sub),
         "admin.embed_key.create",
         Some("embed_key"),
@@ -117,6 +300,15 @@ pub async fn create_key(
         &format!("Created embed key '{}'", payload.name.trim()),
         None,
         None,
+    )
+    .await?;
+    state.webhooks.emit(
+        "embed_key.created",
+        serde_json::json!({
+            "embed_key_id": embed_key.id,
+            "name": embed_key.name,
+            "key_prefix": embed_key.key_prefix,
+        }),
     );
 
     Ok(Json(CreateEmbedKeyResponse {
@@ -125,29 +317,53 @@ pub async fn create_key(
     }))
 }
 
-#[cfg_attr(feature = "openapi", utoipa::path(get, path = "/api/admin/embed-keys", tag = "Admin - Embed", security(("bearer_auth" = [])), responses((status = 200, body = Vec<EmbedKey>))))]
+/// An embed key with what would keep its widget from answering, such as a
+/// provider disabled since the key was set up.
+#[derive(Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct EmbedKeyListItem {
+    #[serde(flatten)]
+    pub embed_key: EmbedKey,
+    pub issues: Vec<String>,
+}
+
+#[cfg_attr(feature = "openapi", utoipa::path(get, path = "/api/admin/embed-keys", tag = "Admin - Embed", security(("bearer_auth" = [])), responses((status = 200, body = Vec<EmbedKeyListItem>))))]
 pub async fn list_keys(
     State(state): State<AppState>,
     claims: Claims,
-) -> Result<Json<Vec<EmbedKey>>, AppError> {
+) -> Result<Json<Vec<EmbedKeyListItem>>, AppError> {
     require_admin(&claims)?;
-    let keys = state.embed_key_repo.list_all().await?;
+    let keys = embed_key_check::check_all(&state)
+        .await?
+        .into_iter()
+        .map(|(embed_key, issues)| EmbedKeyListItem { embed_key, issues })
+        .collect();
     Ok(Json(keys))
 }
 
-#[cfg_attr(feature = "openapi", utoipa::path(get, path = "/api/admin/embed-keys/{id}", tag = "Admin - Embed", security(("bearer_auth" = [])), params(("id" = String, Path, description = "Embed key ID")), responses((status = 200, body = EmbedKey))))]
+/// An embed key with the messages counted against its caps so far.
+#[derive(Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct EmbedKeyDetail {
+    #[serde(flatten)]
+    pub embed_key: EmbedKey,
+    pub usage: EmbedKeyUsage,
+}
+
+#[cfg_attr(feature = "openapi", utoipa::path(get, path = "/api/admin/embed-keys/{id}", tag = "Admin - Embed", security(("bearer_auth" = [])), params(("id" = String, Path, description = "Embed key ID")), responses((status = 200, body = EmbedKeyDetail))))]
 pub async fn get_key(
     State(state): State<AppState>,
     claims: Claims,
     Path(id): Path<String>,
-) -> Result<Json<EmbedKey>, AppError> {
+) -> Result<Json<EmbedKeyDetail>, AppError> {
     require_admin(&claims)?;
-    let key = state
+    let embed_key = state
         .embed_key_repo
         .find_by_id(&id)
         .await?
         .ok_or_else(|| AppError::NotFound("Embed key not found".to_string()))?;
-    Ok(Json(key))
+    let usage = state.embed_key_repo.usage(&id).await?;
+    Ok(Json(EmbedKeyDetail { embed_key, usage }))
 }
 
 #[cfg_attr(feature = "openapi", utoipa::path(put, path = "/api/admin/embed-keys/{id}", tag = "Admin - Embed", security(("bearer_auth" = [])), params(("id" = String, Path, description = "Embed key ID")), request_body = UpdateEmbedKeyRequest, responses((status = 200, body = EmbedKey))))]
@@ -155,18 +371,99 @@ pub async fn update_key(
     State(state): State<AppState>,
     claims: Claims,
     Path(id): Path<String>,
-    Json(payload): Json<UpdateEmbedKeyRequest>,
+    Json(mut payload): Json<UpdateEmbedKeyRequest>,
 ) -> Result<Json<EmbedKey>, AppError> {
     require_admin(&claims)?;
 
+    if let Some(Some(ref base_url)) = payload.base_url {
+        payload.base_url = if base_url.trim().is_empty() {
+            Some(None)
+        } else {
+            Some(Some(
+                llm_provider::validate_base_url(base_url)
+                    .map_err(|e| AppError::Validation(e.to_string()))?,
+            ))
+        };
+    }
+
+    if let Some(limit) = payload.daily_message_limit {
+        validate_message_limit("daily_message_limit", limit)?;
+    }
+    if let Some(limit) = payload.monthly_message_limit {
+        validate_message_limit("monthly_message_limit", limit)?;
+    }
+    validate_rag_params(
+        payload.rag_top_k.flatten(),
+        payload.rag_min_score.flatten(),
+        payload.rag_max_context_chars.flatten(),
+    )?;
+
+    if let Some(localizations) = payload.localizations.take() {
+        payload.localizations = Some(validate_localizations(localizations)?);
+    }
+
+    if payload.provider.is_some() || payload.model.is_some() {
+        let existing = state
+            .embed_key_repo
+            .find_by_id(&id)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Embed key not found".to_string()))?;
+        embed_key_check::validate_model_choice(
+            &state,
+            payload.provider.as_deref().unwrap_or(&existing.provider),
+            payload.model.as_deref().unwrap_or(&existing.model),
+        )
+        .await?;
+    }
+
+    if payload.theme.is_some() || payload.primary_color.is_some() {
+        let existing = state
+            .embed_key_repo
+            .find_by_id(&id)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Embed key not found".to_string()))?;
+        let (theme, primary_color) = match payload.theme.take() {
+            Some(theme) => validate_theme(
+                theme,
+                payload.primary_color.as_deref().unwrap_or(&existing.primary_color),
+            )?,
+            None => {
+                // Only the shorthand changed, so it replaces the stored theme's primary
+                let mut theme = existing.theme;
+                theme.light.primary = None;
+                validate_theme(theme, payload.primary_color.as_deref().unwrap_or_default())?
+            }
+        };
+        payload.theme = Some(theme);
+        payload.primary_color = Some(primary_color);
+    }
+
+    if let Some(email) = payload.handoff_notification_email.take() {
+        payload.handoff_notification_email = Some(validate_notification_email(email)?);
+    }
+
+    if let Some(hours) = payload.office_hours.take() {
+        payload.office_hours = Some(validate_office_hours(hours)?);
+    }
+    if let Some(message) = payload.offline_message.take() {
+        payload.offline_message = Some(message.trim().to_string());
+    }
+
+    if let Some(collection_id) = payload.collection_id.take() {
+        let collection_id = collection_id.filter(|c| !c.is_empty());
+        require_collection(&state, collection_id.as_deref()).await?;
+        payload.collection_id = Some(collection_id);
+    }
+
     let key = state
         .embed_key_repo
         .update(&id, &payload)
         .await?
         .ok_or_else(|| AppError::NotFound("Embed key not found".to_string()))?;
+    state.invalidate_embed_key(&id).await;
 
     audit::log(
-        &state.audit_log_repo,
+        &state.audit,
         Some(&claims.sub),
         "admin.embed_key.update",
         Some("embed_key"),
@@ -179,7 +476,7 @@ pub async fn update_key(
     Ok(Json(key))
 }
 
-#[cfg_attr(feature = "openapi", utoipa::path(delete, path = "/api/admin/embed-keys/{id}", tag = "Admin - Embed", security(("bearer_auth" = [])), params(("id" = String, Path, description = "Embed key ID")), responses((status = 200))))]
+#[cfg_attr(feature = "openapi", utoipa::path(delete, path = "/api/admin/embed-keys/{id}", tag = "Admin - Embed", security(("bearer_auth" = [])), params(("id" = String, Path, description = "Embed key ID")), responses((status = 200), (status = 404, description = "Embed key not found"))))]
 pub async fn delete_key(
     State(state): State<AppState>,
     claims: Claims,
@@ -187,10 +484,23 @@ pub async fn delete_key(
 ) -> Result<(), AppError> {
     require_admin(&claims)?;
 
+    let key = state
+        .embed_key_repo
+        .find_by_id(&id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Embed key not found".to_string()))?;
+    if let Some(avatar_key) = &key.avatar_key {
+        state
+            .storage
+            .delete(avatar_key)
+            .await
+            .map_err(AppError::Internal)?;
+    }
     state.embed_key_repo.delete(&id).await?;
+    state.invalidate_embed_key(&id).await;
 
-    audit::log(
-        &state.audit_log_repo,
+    audit::log_critical(
+        &state.audit,
         Some(&claims.sub),
         "admin.embed_key.delete",
         Some("embed_key"),
@@ -198,7 +508,11 @@ pub async fn delete_key(
         "Deleted embed key",
         None,
         None,
-    );
+    )
+    .await?;
+    state
+        .webhooks
+        .emit("embed_key.deleted", serde_json::json!({ "embed_key_id": id }));
 
     Ok(())
 }
@@ -216,6 +530,7 @@ pub async fn toggle_key(
         .toggle(&id)
         .await?
         .ok_or_else(|| AppError::NotFound("Embed key not found".to_string()))?;
+    state.invalidate_embed_key(&id).await;
 
     let action = if key.is_active {
         "Activated"
@@ -224,7 +539,7 @@ pub async fn toggle_key(
     };
 
     audit::log(
-        &state.audit_log_repo,
+        &state.audit,
         Some(&claims.sub),
         "admin.embed_key.toggle",
         Some("embed_key"),
@@ -236,3 +551,257 @@ pub async fn toggle_key(
 
     Ok(Json(key))
 }
+
+/// Replace the key's bot avatar with the uploaded `file`: a PNG, JPEG or SVG
+/// of at most 512 KB. SVGs are stored without scripts or external references.
+#[cfg_attr(feature = "openapi", utoipa::path(post, path = "/api/admin/embed-keys/{id}/avatar", tag = "Admin - Embed", security(("bearer_auth" = [])), params(("id" = String, Path, description = "Embed key ID")), request_body(content_type = "multipart/form-data", description = "Image in the `file` field"), responses((status = 200, body = EmbedKey), (status = 400, description = "Not a PNG, JPEG or SVG image"), (status = 413, description = "Image too large"))))]
+pub async fn upload_avatar(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(id): Path<String>,
+    mut multipart: Multipart,
+) -> Result<Json<EmbedKey>, AppError> {
+    require_admin(&claims)?;
+
+    state
+        .embed_key_repo
+        .find_by_id(&id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Embed key not found".to_string()))?;
+
+    let too_large = || AppError::RequestTooLarge(widget_avatar::MAX_AVATAR_BYTES / 1024);
+    let read_error = |e: MultipartError| {
+        if e.status() == StatusCode::PAYLOAD_TOO_LARGE {
+            too_large()
+        } else {
+            AppError::Validation(format!("Invalid multipart data: {e}"))
+        }
+    };
+    let mut field = loop {
+        match multipart.next_field().await.map_err(read_error)? {
+            Some(field) if field.name() == Some("file") => break field,
+            Some(_) => continue,
+            None => return Err(AppError::Validation("No file provided".to_string())),
+        }
+    };
+    let content_type = field.content_type().unwrap_or_default().to_string();
+    let mut data = Vec::new();
+    while let Some(chunk) = field.chunk().await.map_err(read_error)? {
+        if data.len() + chunk.len() > widget_avatar::MAX_AVATAR_BYTES {
+            return Err(too_large());
+        }
+        data.extend_from_slice(&chunk);
+    }
+
+    let (format, data) = widget_avatar::prepare(&content_type, &data).map_err(AppError::Validation)?;
+    let avatar_key = widget_avatar::storage_key(&id, format);
+    state
+        .storage
+        .upload(&avatar_key, data, format.content_type())
+        .await
+        .map_err(AppError::Internal)?;
+
+    let Some((key, previous)) = state.embed_key_repo.set_avatar(&id, &avatar_key).await? else {
+        // Deleted while the image was uploading
+        if let Err(e) = state.storage.delete(&avatar_key).await {
+            tracing::warn!("Failed to delete avatar {avatar_key} of a deleted embed key: {e:#}");
+        }
+        return Err(AppError::NotFound("Embed key not found".to_string()));
+    };
+    state.invalidate_embed_key(&id).await;
+    if let Some(previous) = previous
+        && let Err(e) = state.storage.delete(&previous).await
+    {
+        tracing::warn!("Failed to delete replaced avatar {previous}: {e:#}");
+    }
+
+    audit::log(
+        &state.audit,
+        Some(&claims.sub),
+        "admin.embed_key.avatar",
+        Some("embed_key"),
+        Some(&id),
+        &format!("Uploaded {} avatar for embed key '{}'", format.extension().to_uppercase(), key.name),
+        None,
+        None,
+    );
+
+    Ok(Json(key))
+}
+
+#[cfg_attr(feature = "openapi", utoipa::path(post, path = "/api/admin/embed-keys/{id}/test", tag = "Admin - Embed", security(("bearer_auth" = [])), params(("id" = String, Path, description = "Embed key ID")), responses((status = 200, body = ApiKeyTestResponse))))]
+pub async fn test_key(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(id): Path<String>,
+) -> Result<Json<ApiKeyTestResponse>, AppError> {
+    require_admin(&claims)?;
+
+    let key = state
+        .embed_key_repo
+        .find_by_id(&id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Embed key not found".to_string()))?;
+
+    // Resolve credentials the same way the widget does at chat time
+    let provider = if key.provider.is_empty() {
+        state.config.llm.default_provider.clone()
+    } else {
+        key.provider.clone()
+    };
+
+    let (api_key, base_url) = if !key.api_key_encrypted.is_empty() {
+        (key.api_key_encrypted.clone(), key.base_url.clone())
+    } else {
+        let fallback = state
+            .credentials
+            .resolve_shared(&provider)
+            .await?
+            .map(|r| r.credentials);
+        let api_key = fallback.as_ref().map(|c| c.api_key.clone()).unwrap_or_default();
+        (api_key, key.base_url.clone().or(fallback.and_then(|c| c.base_url)))
+    };
+
+    if api_key.is_empty() && llm_provider::requires_api_key(&provider) {
+        return Err(AppError::Validation(format!(
+            "No API key configured for provider '{provider}'"
+        )));
+    }
+
+    let response = run_key_test(&provider, &api_key, base_url.as_deref()).await;
+
+    audit::log(
+        &state.audit,
+        Some(&claims.sub),
+        "admin.embed_key.test",
+        Some("embed_key"),
+        Some(&id),
+        &format!(
+            "Tested provider connection for embed key '{}': {}",
+            key.name,
+            if response.success { "success" } else { "failed" }
+        ),
+        None,
+        None,
+    );
+
+    Ok(Json(response))
+}
+
+/// Purge widget sessions past `widget.session_retention_days` now instead of
+/// waiting for the daily run.
+#[cfg_attr(feature = "openapi", utoipa::path(post, path = "/api/admin/widget-sessions/purge", tag = "Admin - Embed", security(("bearer_auth" = [])), responses((status = 200, body = WidgetSessionPurge))))]
+pub async fn purge_widget_sessions(
+    State(state): State<AppState>,
+    claims: Claims,
+) -> Result<Json<WidgetSessionPurge>, AppError> {
+    require_admin(&claims)?;
+
+    let config = &state.config.widget;
+    if config.session_retention_days == 0 {
+        return Err(AppError::Validation(
+            "Widget session retention is disabled (widget.session_retention_days = 0)".to_string(),
+        ));
+    }
+
+    let purged = state
+        .widget_session_repo
+        .purge_inactive(config.session_retention_days, config.purge_session_conversations)
+        .await?;
+    tracing::info!(
+        "Purged {} inactive widget sessions and {} of their conversations",
+        purged.sessions,
+        purged.conversations
+    );
+
+    audit::log(
+        &state.audit,
+        Some(&claims.sub),
+        "admin.widget_sessions.purge",
+        Some("widget_session"),
+        None,
+        &format!(
+            "Purged {} widget sessions inactive for {} days and {} conversations",
+            purged.sessions, config.session_retention_days, purged.conversations
+        ),
+        None,
+        None,
+    );
+
+    Ok(Json(purged))
+}
+
+// ── Human handoffs ───────────────────────────────────────
+
+#[derive(Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams))]
+pub struct HandoffsQuery {
+    /// `open` or `closed`; all handoffs when omitted.
+    pub status: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UpdateHandoffStatusRequest {
+    pub status: String,
+}
+
+fn validate_handoff_status(status: &str) -> Result<(), AppError> {
+    if HANDOFF_STATUSES.contains(&status) {
+        Ok(())
+    } else {
+        Err(AppError::Validation(format!(
+            "Invalid status '{status}'. Must be one of: {}",
+            HANDOFF_STATUSES.join(", ")
+        )))
+    }
+}
+
+/// Handoff requests left by widget visitors, newest first.
+#[cfg_attr(feature = "openapi", utoipa::path(get, path = "/api/admin/widget-handoffs", tag = "Admin - Embed", security(("bearer_auth" = [])), params(HandoffsQuery), responses((status = 200, body = Vec<WidgetHandoff>))))]
+pub async fn list_handoffs(
+    State(state): State<AppState>,
+    claims: Claims,
+    Query(query): Query<HandoffsQuery>,
+) -> Result<Json<Vec<WidgetHandoff>>, AppError> {
+    require_admin(&claims)?;
+
+    let status = query.status.as_deref().filter(|s| !s.is_empty());
+    if let Some(status) = status {
+        validate_handoff_status(status)?;
+    }
+
+    let handoffs = state.widget_handoff_repo.list(status, 200).await?;
+    Ok(Json(handoffs))
+}
+
+/// Mark a handoff as followed up (`closed`) or reopen it.
+#[cfg_attr(feature = "openapi", utoipa::path(put, path = "/api/admin/widget-handoffs/{id}/status", tag = "Admin - Embed", security(("bearer_auth" = [])), params(("id" = String, Path, description = "Handoff ID")), request_body = UpdateHandoffStatusRequest, responses((status = 200, body = WidgetHandoff))))]
+pub async fn update_handoff_status(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(id): Path<String>,
+    Json(payload): Json<UpdateHandoffStatusRequest>,
+) -> Result<Json<WidgetHandoff>, AppError> {
+    require_admin(&claims)?;
+    validate_handoff_status(&payload.status)?;
+
+    let handoff = state
+        .widget_handoff_repo
+        .update_status(&id, &payload.status)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Handoff not found".to_string()))?;
+
+    audit::log(
+        &state.audit,
+        Some(&claims.sub),
+        "admin.widget_handoff.update",
+        Some("widget_handoff"),
+        Some(&id),
+        &format!("Marked handoff from {} as {}", handoff.email, handoff.status),
+        None,
+        None,
+    );
+
+    Ok(Json(handoff))
+}
@@ -0,0 +1,136 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::db::models::job::Job;
+use crate::db::models::scheduled_job_run::ScheduledJobRun;
+use crate::errors::AppError;
+use crate::middleware::auth::{require_admin, Claims};
+use crate::services::audit;
+use crate::services::scheduler::{self, ScheduledJob};
+use crate::state::AppState;
+
+const JOB_STATUSES: &[&str] = &["queued", "running", "completed", "dead"];
+
+#[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams))]
+pub struct JobsQuery {
+    /// `queued`, `running`, `completed` or `dead`. All statuses when omitted.
+    pub status: Option<String>,
+    pub limit: Option<i64>,
+}
+
+#[cfg_attr(feature = "openapi", utoipa::path(get, path = "/api/admin/jobs", tag = "Admin - Jobs", security(("bearer_auth" = [])), params(JobsQuery), responses((status = 200, body = Vec<Job>))))]
+pub async fn list_jobs(
+    State(state): State<AppState>,
+    claims: Claims,
+    Query(query): Query<JobsQuery>,
+) -> Result<Json<Vec<Job>>, AppError> {
+    require_admin(&claims)?;
+
+    if let Some(status) = &query.status
+        && !JOB_STATUSES.contains(&status.as_str())
+    {
+        return Err(AppError::Validation(format!(
+            "status must be one of: {}",
+            JOB_STATUSES.join(", ")
+        )));
+    }
+
+    let limit = query.limit.unwrap_or(50).clamp(1, 500);
+    let jobs = state
+        .jobs
+        .repo()
+        .list(query.status.as_deref(), limit)
+        .await?;
+
+    Ok(Json(jobs))
+}
+
+/// A periodic job and how its latest runs went.
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ScheduledJobStatus {
+    pub name: String,
+    pub interval_secs: u64,
+    /// Disabled jobs only run when triggered.
+    pub enabled: bool,
+    pub running: bool,
+    /// Absent until the job has run once.
+    pub last_run: Option<ScheduledJobRun>,
+}
+
+fn job_status(state: &AppState, job: &ScheduledJob, last_run: Option<ScheduledJobRun>) -> ScheduledJobStatus {
+    ScheduledJobStatus {
+        name: job.name().to_string(),
+        interval_secs: job.interval().as_secs(),
+        enabled: state.config.scheduler.is_enabled(job.name()),
+        running: job.is_running(),
+        last_run,
+    }
+}
+
+#[cfg_attr(feature = "openapi", utoipa::path(get, path = "/api/admin/jobs/scheduled", tag = "Admin - Jobs", security(("bearer_auth" = [])), responses((status = 200, body = Vec<ScheduledJobStatus>))))]
+pub async fn list_scheduled_jobs(
+    State(state): State<AppState>,
+    claims: Claims,
+) -> Result<Json<Vec<ScheduledJobStatus>>, AppError> {
+    require_admin(&claims)?;
+
+    let mut runs = state.scheduled_job_run_repo.list().await?;
+    let jobs = state
+        .scheduler
+        .jobs()
+        .iter()
+        .map(|job| {
+            let last_run = runs
+                .iter()
+                .position(|r| r.name == job.name())
+                .map(|i| runs.swap_remove(i));
+            job_status(&state, job, last_run)
+        })
+        .collect();
+
+    Ok(Json(jobs))
+}
+
+/// Start a run of a periodic job now, even if it is disabled. Answers once
+/// the run has started; its outcome shows in the job list.
+#[cfg_attr(feature = "openapi", utoipa::path(post, path = "/api/admin/jobs/scheduled/{name}/run", tag = "Admin - Jobs", security(("bearer_auth" = [])), params(("name" = String, Path, description = "Job name")), responses((status = 202, body = ScheduledJobStatus), (status = 404, description = "No such job"), (status = 409, description = "Job is already running"))))]
+pub async fn run_scheduled_job(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(name): Path<String>,
+) -> Result<(StatusCode, Json<ScheduledJobStatus>), AppError> {
+    require_admin(&claims)?;
+
+    let job = state
+        .scheduler
+        .find(&name)
+        .ok_or_else(|| AppError::NotFound(format!("No scheduled job named '{name}'")))?;
+    if !scheduler::run_now(&state, job.clone()) {
+        return Err(AppError::Conflict(format!("Scheduled job '{name}' is already running")));
+    }
+
+    audit::log(
+        &state.audit,
+        Some(&claims.sub),
+        "admin.scheduled_job.run",
+        Some("scheduled_job"),
+        Some(&name),
+        &format!("Started scheduled job {name}"),
+        None,
+        None,
+    );
+
+    let last_run = state
+        .scheduled_job_run_repo
+        .list()
+        .await?
+        .into_iter()
+        .find(|r| r.name == name);
+    Ok((StatusCode::ACCEPTED, Json(job_status(&state, &job, last_run))))
+}
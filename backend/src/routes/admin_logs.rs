@@ -2,21 +2,77 @@ use axum::{
     extract::{Path, Query, State},
     Json,
 };
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
 
-use crate::db::models::conversation::{ConversationWithUser, Message, WidgetConversationLog};
+use crate::db::models::conversation::{
+    ConversationWithUser, DeletedFilter, LogFilter, LogSort, Message, SortOrder, WidgetConversationLog,
+};
 use crate::errors::AppError;
 use crate::middleware::auth::{require_admin, Claims};
+use crate::services::audit;
 use crate::state::AppState;
 
 #[derive(Debug, Deserialize)]
 #[cfg_attr(feature = "openapi", derive(utoipa::IntoParams))]
 pub struct LogsQuery {
     pub user_id: Option<String>,
+    /// `all` (default), `active` or `deleted`.
+    #[serde(default)]
+    pub deleted: DeletedFilter,
+    /// Conversations started on or after this RFC 3339 time or `YYYY-MM-DD` date.
+    pub from: Option<String>,
+    /// Conversations started on or before this RFC 3339 time or `YYYY-MM-DD` date.
+    pub to: Option<String>,
+    /// Text to find in conversation titles or messages.
+    pub q: Option<String>,
+    /// `updated_at` (default), `created_at` or `message_count`.
+    #[serde(default)]
+    pub sort: LogSort,
+    /// `desc` (default) or `asc`.
+    #[serde(default)]
+    pub order: SortOrder,
     pub page: Option<i64>,
     pub per_page: Option<i64>,
 }
 
+/// A `from`/`to` bound: an RFC 3339 time, or a date meaning the start of the
+/// day for `from` and its end for `to`.
+fn parse_time_bound(name: &str, value: Option<&str>, end_of_day: bool) -> Result<Option<DateTime<Utc>>, AppError> {
+    let Some(value) = value.map(str::trim).filter(|v| !v.is_empty()) else {
+        return Ok(None);
+    };
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(Some(time.with_timezone(&Utc)));
+    }
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d").map_err(|_| {
+        AppError::Validation(format!("'{name}' must be an RFC 3339 time or a YYYY-MM-DD date"))
+    })?;
+    let time = if end_of_day {
+        date.and_hms_micro_opt(23, 59, 59, 999_999)
+    } else {
+        date.and_hms_opt(0, 0, 0)
+    };
+    Ok(time.map(|t| t.and_utc()))
+}
+
+/// A filter for the `deleted` state and `from`/`to` range of a listing.
+fn time_filter(deleted: DeletedFilter, from: Option<&str>, to: Option<&str>) -> Result<LogFilter, AppError> {
+    let from = parse_time_bound("from", from, false)?;
+    let to = parse_time_bound("to", to, true)?;
+    if let (Some(from), Some(to)) = (from, to)
+        && from > to
+    {
+        return Err(AppError::Validation("'from' must not be after 'to'".to_string()));
+    }
+    Ok(LogFilter {
+        deleted,
+        from,
+        to,
+        ..LogFilter::default()
+    })
+}
+
 #[derive(Debug, Serialize)]
 #[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct LogsResponse {
@@ -38,14 +94,17 @@ pub async fn list_conversation_logs(
     let per_page = query.per_page.unwrap_or(25).clamp(1, 100);
     let offset = (page - 1) * per_page;
     let user_id_filter = query.user_id.as_deref();
+    let filter = LogFilter {
+        search: query.q,
+        sort: query.sort,
+        order: query.order,
+        ..time_filter(query.deleted, query.from.as_deref(), query.to.as_deref())?
+    };
 
-    let total = state
-        .conversation_repo
-        .count_all(user_id_filter)
-        .await?;
+    let total = state.conversation_repo.count_all(user_id_filter, &filter).await?;
     let conversations = state
         .conversation_repo
-        .list_all(user_id_filter, per_page, offset)
+        .list_all(user_id_filter, &filter, per_page, offset)
         .await?;
 
     Ok(Json(LogsResponse {
@@ -62,8 +121,13 @@ pub struct LogDetailResponse {
     pub id: String,
     pub user_id: String,
     pub title: String,
-    pub created_at: String,
-    pub updated_at: String,
+    #[serde(with = "crate::db::timestamp")]
+    pub created_at: DateTime<Utc>,
+    #[serde(with = "crate::db::timestamp")]
+    pub updated_at: DateTime<Utc>,
+    /// Every message in order; deleted ones are stubs with `deleted_at` set.
+    /// Replies carry how they were generated: provider, model, token usage,
+    /// latency and how many knowledge base chunks they were given.
     pub messages: Vec<Message>,
 }
 
@@ -81,7 +145,7 @@ pub async fn get_conversation_log(
         .await?
         .ok_or_else(|| AppError::NotFound("Conversation not found".to_string()))?;
 
-    let messages = state.conversation_repo.get_messages(&id).await?;
+    let messages = state.conversation_repo.get_messages_with_deleted(&id).await?;
 
     Ok(Json(LogDetailResponse {
         id: conv.id,
@@ -93,12 +157,69 @@ pub async fn get_conversation_log(
     }))
 }
 
+#[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams))]
+pub struct DeleteLogQuery {
+    /// Delete permanently now instead of soft-deleting.
+    #[serde(default)]
+    pub hard: bool,
+}
+
+/// Delete a conversation as an admin. Soft-deleted conversations are purged
+/// after 30 days; `hard=true` removes it and its messages immediately, for
+/// removal requests that can't wait.
+#[cfg_attr(feature = "openapi", utoipa::path(delete, path = "/api/admin/logs/{id}", tag = "Admin - Logs", security(("bearer_auth" = [])), params(("id" = String, Path, description = "Conversation ID"), DeleteLogQuery), responses((status = 200))))]
+pub async fn delete_conversation_log(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(id): Path<String>,
+    Query(query): Query<DeleteLogQuery>,
+) -> Result<(), AppError> {
+    require_admin(&claims)?;
+
+    let conv = state
+        .conversation_repo
+        .get_by_id(&id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Conversation not found".to_string()))?;
+
+    let (event_type, description) = if query.hard {
+        state.conversation_repo.hard_delete(&id).await?;
+        ("admin.conversation_hard_delete", "Permanently deleted")
+    } else {
+        if !state.conversation_repo.admin_soft_delete(&id).await? {
+            return Err(AppError::Validation("Conversation is already deleted".to_string()));
+        }
+        ("admin.conversation_delete", "Deleted")
+    };
+
+    audit::log(
+        &state.audit,
+        Some(&claims.sub),
+        event_type,
+        Some("conversation"),
+        Some(&id),
+        &format!("{description} conversation '{}' of user {}", conv.title, conv.user_id),
+        None,
+        None,
+    );
+
+    Ok(())
+}
+
 // ── Widget logs ──────────────────────────────────────────
 
 #[derive(Debug, Deserialize)]
 #[cfg_attr(feature = "openapi", derive(utoipa::IntoParams))]
 pub struct WidgetLogsQuery {
     pub embed_key_id: Option<String>,
+    /// `all` (default), `active` or `deleted`.
+    #[serde(default)]
+    pub deleted: DeletedFilter,
+    /// Conversations started on or after this RFC 3339 time or `YYYY-MM-DD` date.
+    pub from: Option<String>,
+    /// Conversations started on or before this RFC 3339 time or `YYYY-MM-DD` date.
+    pub to: Option<String>,
     pub page: Option<i64>,
     pub per_page: Option<i64>,
 }
@@ -124,14 +245,15 @@ pub async fn list_widget_logs(
     let per_page = query.per_page.unwrap_or(25).clamp(1, 100);
     let offset = (page - 1) * per_page;
     let embed_key_id_filter = query.embed_key_id.as_deref();
+    let filter = time_filter(query.deleted, query.from.as_deref(), query.to.as_deref())?;
 
     let total = state
         .conversation_repo
-        .count_widget_conversations(embed_key_id_filter)
+        .count_widget_conversations(embed_key_id_filter, &filter)
         .await?;
     let conversations = state
         .conversation_repo
-        .list_widget_conversations(embed_key_id_filter, per_page, offset)
+        .list_widget_conversations(embed_key_id_filter, &filter, per_page, offset)
         .await?;
 
     Ok(Json(WidgetLogsResponse {
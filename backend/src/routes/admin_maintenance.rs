@@ -0,0 +1,60 @@
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::db::models::conversation::ConversationPurge;
+use crate::errors::AppError;
+use crate::middleware::auth::{require_admin, Claims};
+use crate::services::{audit, conversation_purge};
+use crate::state::AppState;
+
+#[derive(Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams))]
+pub struct PurgeQuery {
+    /// Only report what would be removed.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct PurgeConversationsResponse {
+    pub dry_run: bool,
+    #[serde(flatten)]
+    pub purged: ConversationPurge,
+}
+
+/// Run the daily purge of expired soft-deleted conversations now. With
+/// `dry_run=true` nothing is deleted and the counts are what it would remove.
+#[cfg_attr(feature = "openapi", utoipa::path(post, path = "/api/admin/maintenance/purge-conversations", tag = "Admin - Maintenance", security(("bearer_auth" = [])), params(PurgeQuery), responses((status = 200, body = PurgeConversationsResponse))))]
+pub async fn purge_conversations(
+    State(state): State<AppState>,
+    claims: Claims,
+    Query(query): Query<PurgeQuery>,
+) -> Result<Json<PurgeConversationsResponse>, AppError> {
+    require_admin(&claims)?;
+
+    if query.dry_run {
+        let purged = conversation_purge::preview(&state).await?;
+        return Ok(Json(PurgeConversationsResponse { dry_run: true, purged }));
+    }
+
+    let purged = conversation_purge::purge(&state, conversation_purge::BATCH_SIZE).await?;
+    audit::log(
+        &state.audit,
+        Some(&claims.sub),
+        "admin.conversations.purge",
+        Some("conversation"),
+        None,
+        &format!(
+            "Purged {} expired conversations with {} messages and {} orphaned widget sessions",
+            purged.conversations, purged.messages, purged.sessions
+        ),
+        None,
+        None,
+    );
+
+    Ok(Json(PurgeConversationsResponse { dry_run: false, purged }))
+}
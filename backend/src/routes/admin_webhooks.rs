@@ -0,0 +1,256 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::db::models::webhook::{Webhook, WebhookDelivery, WebhookUpdate};
+use crate::errors::AppError;
+use crate::middleware::auth::{require_admin, Claims};
+use crate::services::webhook::WEBHOOK_EVENTS;
+use crate::services::{audit, llm_provider};
+use crate::state::AppState;
+
+#[derive(Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CreateWebhookRequest {
+    pub url: String,
+    /// Events to receive; all of them when empty.
+    #[serde(default)]
+    pub events: Vec<String>,
+    /// Signing secret; one is generated when omitted.
+    pub secret: Option<String>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+#[derive(Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UpdateWebhookRequest {
+    pub url: Option<String>,
+    pub events: Option<Vec<String>>,
+    pub enabled: Option<bool>,
+    /// Replaces the signing secret.
+    pub secret: Option<String>,
+}
+
+#[derive(Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CreateWebhookResponse {
+    pub webhook: Webhook,
+    /// Shown only once; receivers verify `X-Webhook-Signature` with it.
+    pub secret: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams))]
+pub struct DeliveriesQuery {
+    pub limit: Option<i64>,
+}
+
+fn validate_url(url: &str) -> Result<String, AppError> {
+    // Same rules as provider base URLs: http(s) with a host
+    llm_provider::validate_base_url(url)
+        .map(|_| url.trim().to_string())
+        .map_err(|e| AppError::Validation(e.to_string().replace("Base URL", "Webhook URL")))
+}
+
+/// Reject unknown events and drop duplicates, keeping the given order.
+fn validate_events(events: Vec<String>) -> Result<Vec<String>, AppError> {
+    let mut validated: Vec<String> = Vec::new();
+    for event in events {
+        let event = event.trim().to_string();
+        if !WEBHOOK_EVENTS.contains(&event.as_str()) {
+            return Err(AppError::Validation(format!(
+                "Unknown event '{event}'. Use one of: {}",
+                WEBHOOK_EVENTS.join(", ")
+            )));
+        }
+        if !validated.contains(&event) {
+            validated.push(event);
+        }
+    }
+    Ok(validated)
+}
+
+fn validate_secret(secret: String) -> Result<String, AppError> {
+    if secret.trim().len() < 16 {
+        return Err(AppError::Validation(
+            "Webhook secret must be at least 16 characters".to_string(),
+        ));
+    }
+    Ok(secret.trim().to_string())
+}
+
+fn generate_secret() -> String {
+    let mut bytes = [0u8; 32];
+    rand::rng().fill(&mut bytes);
+    format!("whsec_{}", bytes.iter().map(|b| format!("{b:02x}")).collect::<String>())
+}
+
+#[cfg_attr(feature = "openapi", utoipa::path(get, path = "/api/admin/webhooks", tag = "Admin - Webhooks", security(("bearer_auth" = [])), responses((status = 200, body = Vec<Webhook>))))]
+pub async fn list_webhooks(
+    State(state): State<AppState>,
+    claims: Claims,
+) -> Result<Json<Vec<Webhook>>, AppError> {
+    require_admin(&claims)?;
+    Ok(Json(state.webhooks.repo().list().await?))
+}
+
+#[cfg_attr(feature = "openapi", utoipa::path(post, path = "/api/admin/webhooks", tag = "Admin - Webhooks", security(("bearer_auth" = [])), request_body = CreateWebhookRequest, responses((status = 200, body = CreateWebhookResponse))))]
+pub async fn create_webhook(
+    State(state): State<AppState>,
+    claims: Claims,
+    Json(payload): Json<CreateWebhookRequest>,
+) -> Result<Json<CreateWebhookResponse>, AppError> {
+    require_admin(&claims)?;
+
+    let url = validate_url(&payload.url)?;
+    let events = validate_events(payload.events)?;
+    let secret = match payload.secret {
+        Some(secret) => validate_secret(secret)?,
+        None => generate_secret(),
+    };
+
+    let webhook = state
+        .webhooks
+        .repo()
+        .create(&url, &secret, &events, payload.enabled, &claims.sub)
+        .await?;
+
+    audit::log_critical(
+        &state.audit,
+        Some(&claims.sub),
+        "admin.webhook.create",
+        Some("webhook"),
+        Some(&webhook.id),
+        &format!("Created webhook for {url}"),
+        None,
+        None,
+    )
+    .await?;
+
+    Ok(Json(CreateWebhookResponse { webhook, secret }))
+}
+
+#[cfg_attr(feature = "openapi", utoipa::path(put, path = "/api/admin/webhooks/{id}", tag = "Admin - Webhooks", security(("bearer_auth" = [])), params(("id" = String, Path, description = "Webhook ID")), request_body = UpdateWebhookRequest, responses((status = 200, body = Webhook))))]
+pub async fn update_webhook(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(id): Path<String>,
+    Json(payload): Json<UpdateWebhookRequest>,
+) -> Result<Json<Webhook>, AppError> {
+    require_admin(&claims)?;
+
+    let update = WebhookUpdate {
+        url: payload.url.as_deref().map(validate_url).transpose()?,
+        events: payload.events.map(validate_events).transpose()?,
+        enabled: payload.enabled,
+        secret: payload.secret.map(validate_secret).transpose()?,
+    };
+    let rotated = update.secret.is_some();
+
+    let webhook = state
+        .webhooks
+        .repo()
+        .update(&id, &update)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Webhook not found".to_string()))?;
+
+    audit::log(
+        &state.audit,
+        Some(&claims.sub),
+        "admin.webhook.update",
+        Some("webhook"),
+        Some(&id),
+        &format!(
+            "Updated webhook for {}{}",
+            webhook.url,
+            if rotated { " and replaced its secret" } else { "" }
+        ),
+        None,
+        None,
+    );
+
+    Ok(Json(webhook))
+}
+
+#[cfg_attr(feature = "openapi", utoipa::path(delete, path = "/api/admin/webhooks/{id}", tag = "Admin - Webhooks", security(("bearer_auth" = [])), params(("id" = String, Path, description = "Webhook ID")), responses((status = 204), (status = 404))))]
+pub async fn delete_webhook(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(id): Path<String>,
+) -> Result<StatusCode, AppError> {
+    require_admin(&claims)?;
+
+    if !state.webhooks.repo().delete(&id).await? {
+        return Err(AppError::NotFound("Webhook not found".to_string()));
+    }
+
+    audit::log_critical(
+        &state.audit,
+        Some(&claims.sub),
+        "admin.webhook.delete",
+        Some("webhook"),
+        Some(&id),
+        "Deleted webhook",
+        None,
+        None,
+    )
+    .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg_attr(feature = "openapi", utoipa::path(get, path = "/api/admin/webhooks/{id}/deliveries", tag = "Admin - Webhooks", security(("bearer_auth" = [])), params(("id" = String, Path, description = "Webhook ID"), DeliveriesQuery), responses((status = 200, body = Vec<WebhookDelivery>))))]
+pub async fn list_deliveries(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(id): Path<String>,
+    Query(query): Query<DeliveriesQuery>,
+) -> Result<Json<Vec<WebhookDelivery>>, AppError> {
+    require_admin(&claims)?;
+
+    if state.webhooks.repo().find(&id).await?.is_none() {
+        return Err(AppError::NotFound("Webhook not found".to_string()));
+    }
+
+    let limit = query.limit.unwrap_or(50).clamp(1, 500);
+    Ok(Json(state.webhooks.repo().list_deliveries(&id, limit).await?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_events() {
+        assert_eq!(
+            validate_events(vec![
+                "crawl.completed".to_string(),
+                " document.ready ".to_string(),
+                "crawl.completed".to_string(),
+            ])
+            .unwrap(),
+            vec!["crawl.completed", "document.ready"]
+        );
+        assert!(validate_events(vec!["document.deleted".to_string()]).is_err());
+        assert!(validate_events(Vec::new()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_validate_url() {
+        assert_eq!(
+            validate_url(" https://hooks.example.com/rag ").unwrap(),
+            "https://hooks.example.com/rag"
+        );
+        assert!(validate_url("ftp://hooks.example.com").is_err());
+        assert!(validate_url("not a url").is_err());
+    }
+}
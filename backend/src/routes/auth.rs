@@ -1,16 +1,18 @@
 use axum::{extract::State, Json};
-use axum::http::HeaderMap;
 
-use crate::dto::auth::{AuthResponse, LoginRequest, SetupRequest, UserResponse};
+use crate::db::models::user::UserRole;
+use crate::dto::auth::{AuthResponse, LoginRequest, MeResponse, SetupRequest};
 use crate::errors::AppError;
-use crate::middleware::auth::{extract_ip, Claims};
+use crate::middleware::auth::CurrentUser;
+use crate::middleware::client_ip::ClientIp;
 use crate::services::{audit, auth_service};
+use crate::routes::settings::preferences_or_defaults;
 use crate::state::AppState;
 
 #[cfg_attr(feature = "openapi", utoipa::path(post, path = "/api/auth/login", tag = "Auth", request_body = LoginRequest, responses((status = 200, body = AuthResponse), (status = 400, description = "Invalid credentials"))))]
 pub async fn login(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    client_ip: ClientIp,
     Json(payload): Json<LoginRequest>,
 ) -> Result<Json<AuthResponse>, AppError> {
     let user = state
@@ -36,17 +38,18 @@ pub async fn login(
     )
     .map_err(AppError::Internal)?;
 
-    let ip = extract_ip(&headers);
-    audit::log(
-        &state.audit_log_repo,
+    let ip = client_ip.to_string();
+    audit::log_critical(
+        &state.audit,
         Some(&user.id),
         "auth.login",
         None,
         None,
         &format!("User '{}' logged in", user.username),
-        ip.as_deref(),
+        Some(&ip),
         None,
-    );
+    )
+    .await?;
 
     Ok(Json(AuthResponse {
         token,
@@ -57,10 +60,10 @@ pub async fn login(
 #[cfg_attr(feature = "openapi", utoipa::path(post, path = "/api/auth/setup", tag = "Auth", request_body = SetupRequest, responses((status = 200, body = AuthResponse), (status = 400, description = "Validation error"))))]
 pub async fn setup(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    client_ip: ClientIp,
     Json(payload): Json<SetupRequest>,
 ) -> Result<Json<AuthResponse>, AppError> {
-    validate_setup(&payload)?;
+    validate_credentials(&payload.username, &payload.password)?;
 
     let invite = state
         .invite_repo
@@ -75,10 +78,7 @@ pub async fn setup(
         ));
     }
 
-    let expires = chrono::DateTime::parse_from_rfc3339(&invite.expires_at)
-        .map_err(|_| AppError::Internal(anyhow::anyhow!("Invalid expiry date")))?;
-
-    if chrono::Utc::now() > expires {
+    if chrono::Utc::now() > invite.expires_at {
         return Err(AppError::Validation("This invite has expired".to_string()));
     }
 
@@ -111,17 +111,18 @@ pub async fn setup(
     )
     .map_err(AppError::Internal)?;
 
-    let ip = extract_ip(&headers);
-    audit::log(
-        &state.audit_log_repo,
+    let ip = client_ip.to_string();
+    audit::log_critical(
+        &state.audit,
         Some(&user.id),
         "auth.setup",
         None,
         None,
         &format!("User '{}' completed account setup", user.username),
-        ip.as_deref(),
+        Some(&ip),
         None,
-    );
+    )
+    .await?;
 
     Ok(Json(AuthResponse {
         token,
@@ -129,27 +130,33 @@ pub async fn setup(
     }))
 }
 
-#[cfg_attr(feature = "openapi", utoipa::path(get, path = "/api/auth/me", tag = "Auth", security(("bearer_auth" = [])), responses((status = 200, body = UserResponse))))]
+#[cfg_attr(feature = "openapi", utoipa::path(get, path = "/api/auth/me", tag = "Auth", security(("bearer_auth" = [])), responses((status = 200, body = MeResponse))))]
 pub async fn me(
     State(state): State<AppState>,
-    claims: Claims,
-) -> Result<Json<UserResponse>, AppError> {
-    let user = state
-        .user_repo
-        .find_by_id(&claims.sub)
-        .await?
-        .ok_or(AppError::NotFound("User not found".to_string()))?;
-
-    Ok(Json(user.into()))
+    user: CurrentUser,
+) -> Result<Json<MeResponse>, AppError> {
+    let overview = state.user_repo.overview(&user.id).await?;
+
+    let document_count = user.role.is_at_least(&UserRole::Maintainer).then_some(overview.documents);
+    let has_api_key = overview.has_own_api_key
+        || (state.config.features.allow_shared_api_keys && overview.has_organization_api_key);
+    Ok(Json(MeResponse {
+        user: user.into(),
+        conversation_count: overview.conversations,
+        document_count,
+        has_api_key,
+        preferences: preferences_or_defaults(&state.config.llm, overview.preferences),
+    }))
 }
 
-fn validate_setup(req: &SetupRequest) -> Result<(), AppError> {
-    if req.username.trim().is_empty() || req.username.len() < 3 {
+/// Check the username and password someone chose for their account.
+pub(crate) fn validate_credentials(username: &str, password: &str) -> Result<(), AppError> {
+    if username.trim().is_empty() || username.len() < 3 {
         return Err(AppError::Validation(
             "Username must be at least 3 characters".to_string(),
         ));
     }
-    if req.password.len() < 8 {
+    if password.len() < 8 {
         return Err(AppError::Validation(
             "Password must be at least 8 characters".to_string(),
         ));
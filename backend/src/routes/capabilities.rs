@@ -0,0 +1,78 @@
+use axum::{extract::State, Json};
+use serde::Serialize;
+
+use crate::config::FeatureFlags;
+use crate::db::models::user::UserRole;
+use crate::errors::AppError;
+use crate::middleware::auth::Claims;
+use crate::services::text_extract::SUPPORTED_EXTENSIONS;
+use crate::state::AppState;
+
+/// What this server offers the signed-in user, so the UI can hide what would
+/// only fail with a disabled feature or a missing role.
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Capabilities {
+    /// The server's feature flags, the same for every user.
+    pub features: FeatureFlags,
+    /// Upload is enabled and the user is at least a maintainer.
+    pub can_upload_documents: bool,
+    /// Crawling is enabled and the user is at least a maintainer.
+    pub can_crawl: bool,
+    /// Debug endpoints are enabled and the user is at least a maintainer.
+    pub can_debug_retrieval: bool,
+    pub max_upload_size_mb: usize,
+    /// File extensions accepted for upload, without the dot.
+    pub supported_extensions: Vec<String>,
+    /// Some enabled provider supports embeddings, so documents can be indexed.
+    pub embeddings_available: bool,
+    /// The user has stored at least one API key of their own.
+    pub has_api_key: bool,
+}
+
+/// What the login page needs before anyone signs in.
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct PublicCapabilities {
+    pub auth_enabled: bool,
+    /// No account exists yet, so the first admin can be created through setup.
+    pub setup_open: bool,
+}
+
+#[cfg_attr(feature = "openapi", utoipa::path(get, path = "/api/capabilities", tag = "Capabilities", security(("bearer_auth" = [])), responses((status = 200, body = Capabilities))))]
+pub async fn capabilities(
+    State(state): State<AppState>,
+    claims: Claims,
+) -> Result<Json<Capabilities>, AppError> {
+    let features = state.config.features.clone();
+    let maintainer = claims.parsed_role()?.is_at_least(&UserRole::Maintainer);
+
+    let embeddings_available = state
+        .admin_config_repo
+        .get_enabled_providers()
+        .await?
+        .iter()
+        .any(|p| p.supports_embeddings);
+    let has_api_key = !state.settings_repo.list_api_keys(&claims.sub).await?.is_empty();
+
+    Ok(Json(Capabilities {
+        can_upload_documents: maintainer && features.document_upload_enabled,
+        can_crawl: maintainer && features.web_crawl_enabled,
+        can_debug_retrieval: maintainer && features.debug_endpoints_enabled,
+        features,
+        max_upload_size_mb: state.config.server.max_upload_size_mb,
+        supported_extensions: SUPPORTED_EXTENSIONS.iter().map(|ext| ext.to_string()).collect(),
+        embeddings_available,
+        has_api_key,
+    }))
+}
+
+#[cfg_attr(feature = "openapi", utoipa::path(get, path = "/api/capabilities/public", tag = "Capabilities", responses((status = 200, body = PublicCapabilities))))]
+pub async fn public_capabilities(
+    State(state): State<AppState>,
+) -> Result<Json<PublicCapabilities>, AppError> {
+    Ok(Json(PublicCapabilities {
+        auth_enabled: state.config.features.auth_enabled,
+        setup_open: state.user_repo.count().await? == 0,
+    }))
+}
@@ -1,18 +1,23 @@
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     response::sse::{Event, Sse},
     Json,
 };
 use futures::stream::Stream;
-use rig::completion::Prompt;
 use serde::{Deserialize, Serialize};
 use std::convert::Infallible;
-use tokio_stream::StreamExt;
 
-use crate::db::models::conversation::{Conversation, Message};
+use crate::db::models::conversation::{
+    ArchivedFilter, Conversation, ConversationSettings, Message, MessagePage,
+};
 use crate::errors::AppError;
 use crate::middleware::auth::Claims;
-use crate::services::{audit, llm_provider};
+use crate::routes::collections::require_collection;
+use crate::routes::settings::validate_chat_model;
+use crate::services::chat_service::{self, ChatRequestContext, EffectiveChatSettings};
+use crate::services::llm_provider::ModelRef;
+use crate::services::vector::SearchFilter;
+use crate::services::{audit, sse, titles};
 use crate::state::AppState;
 
 // ── Conversations CRUD ──────────────────────────────────────
@@ -21,6 +26,11 @@ use crate::state::AppState;
 #[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct CreateConversationRequest {
     pub title: Option<String>,
+    /// Limit retrieval to this collection's documents.
+    pub collection_id: Option<String>,
+    /// Chat settings for this conversation instead of the user's preferences.
+    #[serde(flatten)]
+    pub settings: ConversationSettings,
 }
 
 #[cfg_attr(feature = "openapi", utoipa::path(post, path = "/api/conversations", tag = "Chat", security(("bearer_auth" = [])), request_body = CreateConversationRequest, responses((status = 200, body = Conversation))))]
@@ -29,15 +39,26 @@ pub async fn create_conversation(
     claims: Claims,
     Json(payload): Json<CreateConversationRequest>,
 ) -> Result<Json<Conversation>, AppError> {
-    let title = payload
-        .title
-        .filter(|t| !t.trim().is_empty())
-        .unwrap_or_else(|| "New Chat".to_string());
-
-    let conv = state.conversation_repo.create(&claims.sub, &title).await?;
+    let custom_title = payload.title.filter(|t| !t.trim().is_empty());
+    let collection_id = payload.collection_id.filter(|c| !c.is_empty());
+    require_collection(&state, collection_id.as_deref()).await?;
+    let settings = payload.settings.normalized();
+    validate_settings(&state, &settings).await?;
+    let conv = state
+        .conversation_repo
+        .create(
+            &claims.sub,
+            custom_title.as_deref().unwrap_or(titles::DEFAULT_TITLE),
+            custom_title.is_some(),
+            collection_id.as_deref(),
+        )
+        .await?;
+    if settings != ConversationSettings::default() {
+        state.conversation_repo.set_settings(&conv.id, &claims.sub, &settings).await?;
+    }
 
     audit::log(
-        &state.audit_log_repo,
+        &state.audit,
         Some(&claims.sub),
         "chat.create",
         Some("conversation"),
@@ -50,28 +71,104 @@ pub async fn create_conversation(
     Ok(Json(conv))
 }
 
-#[cfg_attr(feature = "openapi", utoipa::path(get, path = "/api/conversations", tag = "Chat", security(("bearer_auth" = [])), responses((status = 200, body = Vec<Conversation>))))]
+#[derive(Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams))]
+pub struct ListConversationsQuery {
+    /// `active` (default), `archived` or `all`.
+    #[serde(default)]
+    pub filter: ArchivedFilter,
+}
+
+#[cfg_attr(feature = "openapi", utoipa::path(get, path = "/api/conversations", tag = "Chat", security(("bearer_auth" = [])), params(ListConversationsQuery), responses((status = 200, body = Vec<Conversation>))))]
 pub async fn list_conversations(
     State(state): State<AppState>,
     claims: Claims,
+    Query(query): Query<ListConversationsQuery>,
 ) -> Result<Json<Vec<Conversation>>, AppError> {
-    let convs = state.conversation_repo.list_by_user(&claims.sub).await?;
+    let convs = state.conversation_repo.list_by_user(&claims.sub, query.filter).await?;
     Ok(Json(convs))
 }
 
+/// A conversation's own chat settings and what it answers with after fallbacks.
+#[derive(Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ConversationChatSettings {
+    pub overrides: ConversationSettings,
+    pub effective: EffectiveChatSettings,
+    /// Set when an override or preference is skipped, e.g. a disabled model.
+    pub warnings: Vec<String>,
+}
+
+async fn chat_settings(state: &AppState, user_id: &str, id: &str) -> Result<ConversationChatSettings, AppError> {
+    let overrides = state.conversation_repo.get_settings(id).await?;
+    let (effective, warnings) = chat_service::resolve_chat_settings(state, user_id, &overrides).await?;
+    Ok(ConversationChatSettings {
+        overrides,
+        effective,
+        warnings,
+    })
+}
+
+/// A provider and model must be chosen together and be enabled in the catalogue.
+async fn validate_settings(state: &AppState, settings: &ConversationSettings) -> Result<(), AppError> {
+    validate_chat_model(
+        state,
+        settings.provider.as_deref().unwrap_or_default(),
+        settings.model.as_deref().unwrap_or_default(),
+    )
+    .await
+}
+
 #[derive(Serialize)]
 #[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct ConversationWithMessages {
     #[serde(flatten)]
     pub conversation: Conversation,
+    pub settings: ConversationChatSettings,
     pub messages: Vec<Message>,
+    /// Only when paging with `after` or `limit`: more messages follow.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub has_more: Option<bool>,
+}
+
+const DEFAULT_MESSAGE_PAGE: i64 = 50;
+const MAX_MESSAGE_PAGE: i64 = 200;
+
+/// Fetch only the messages after one a client already has, for polling.
+/// Without either parameter the whole history is returned.
+#[derive(Debug, Default, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams))]
+pub struct MessagesQuery {
+    /// ID of the last message the client has; only later ones are returned.
+    pub after: Option<String>,
+    /// Most messages to return (default 50, at most 200).
+    pub limit: Option<i64>,
 }
 
-#[cfg_attr(feature = "openapi", utoipa::path(get, path = "/api/conversations/{id}", tag = "Chat", security(("bearer_auth" = [])), params(("id" = String, Path, description = "Conversation ID")), responses((status = 200, body = ConversationWithMessages))))]
+/// The page of messages `query` asks for, or `None` for the whole history.
+pub(crate) async fn message_page(
+    state: &AppState,
+    conversation_id: &str,
+    query: &MessagesQuery,
+) -> Result<Option<MessagePage>, AppError> {
+    if query.after.is_none() && query.limit.is_none() {
+        return Ok(None);
+    }
+    let limit = query.limit.unwrap_or(DEFAULT_MESSAGE_PAGE).clamp(1, MAX_MESSAGE_PAGE);
+    let page = state
+        .conversation_repo
+        .get_messages_after(conversation_id, query.after.as_deref(), limit)
+        .await?
+        .ok_or_else(|| AppError::Validation("after is not a message of this conversation".to_string()))?;
+    Ok(Some(page))
+}
+
+#[cfg_attr(feature = "openapi", utoipa::path(get, path = "/api/conversations/{id}", tag = "Chat", security(("bearer_auth" = [])), params(("id" = String, Path, description = "Conversation ID"), MessagesQuery), responses((status = 200, body = ConversationWithMessages), (status = 400, description = "Unknown after cursor"))))]
 pub async fn get_conversation(
     State(state): State<AppState>,
     claims: Claims,
     Path(id): Path<String>,
+    Query(query): Query<MessagesQuery>,
 ) -> Result<Json<ConversationWithMessages>, AppError> {
     let conv = state
         .conversation_repo
@@ -79,14 +176,111 @@ pub async fn get_conversation(
         .await?
         .ok_or_else(|| AppError::NotFound("Conversation not found".to_string()))?;
 
-    let messages = state.conversation_repo.get_messages(&id).await?;
+    let settings = chat_settings(&state, &claims.sub, &id).await?;
+    let (messages, has_more) = match message_page(&state, &id, &query).await? {
+        Some(page) => (page.messages, Some(page.has_more)),
+        None => (state.conversation_repo.get_messages(&id).await?, None),
+    };
 
     Ok(Json(ConversationWithMessages {
         conversation: conv,
+        settings,
         messages,
+        has_more,
     }))
 }
 
+/// Replace a conversation's model and system prompt overrides. Omitted or
+/// blank fields go back to following the user's preferences.
+#[cfg_attr(feature = "openapi", utoipa::path(put, path = "/api/conversations/{id}/settings", tag = "Chat", security(("bearer_auth" = [])), params(("id" = String, Path, description = "Conversation ID")), request_body = ConversationSettings, responses((status = 200, body = ConversationChatSettings), (status = 400, description = "Provider or model not available"))))]
+pub async fn update_conversation_settings(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(id): Path<String>,
+    Json(payload): Json<ConversationSettings>,
+) -> Result<Json<ConversationChatSettings>, AppError> {
+    let settings = payload.normalized();
+    validate_settings(&state, &settings).await?;
+    if !state.conversation_repo.set_settings(&id, &claims.sub, &settings).await? {
+        return Err(AppError::NotFound("Conversation not found".to_string()));
+    }
+
+    audit::log(
+        &state.audit,
+        Some(&claims.sub),
+        "chat.update_settings",
+        Some("conversation"),
+        Some(&id),
+        "Updated conversation chat settings",
+        None,
+        None,
+    );
+
+    Ok(Json(chat_settings(&state, &claims.sub, &id).await?))
+}
+
+#[derive(Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct RenameConversationRequest {
+    pub title: Option<String>,
+    /// `null` lets the conversation search all documents again.
+    #[serde(default, deserialize_with = "crate::db::models::double_option")]
+    pub collection_id: Option<Option<String>>,
+}
+
+/// Rename a conversation and/or change the collection it retrieves from.
+#[cfg_attr(feature = "openapi", utoipa::path(patch, path = "/api/conversations/{id}", tag = "Chat", security(("bearer_auth" = [])), params(("id" = String, Path, description = "Conversation ID")), request_body = RenameConversationRequest, responses((status = 200, body = Conversation))))]
+pub async fn rename_conversation(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(id): Path<String>,
+    Json(payload): Json<RenameConversationRequest>,
+) -> Result<Json<Conversation>, AppError> {
+    let title = payload.title.as_deref().map(str::trim);
+    if title.is_some_and(str::is_empty) {
+        return Err(AppError::Validation("Title cannot be empty".to_string()));
+    }
+    let collection_id = payload
+        .collection_id
+        .map(|c| c.filter(|c| !c.is_empty()));
+    if let Some(ref collection_id) = collection_id {
+        require_collection(&state, collection_id.as_deref()).await?;
+    }
+
+    if let Some(title) = title {
+        if !state.conversation_repo.rename(&id, &claims.sub, title).await? {
+            return Err(AppError::NotFound("Conversation not found".to_string()));
+        }
+
+        audit::log(
+            &state.audit,
+            Some(&claims.sub),
+            "chat.rename",
+            Some("conversation"),
+            Some(&id),
+            &format!("Renamed conversation to '{title}'"),
+            None,
+            None,
+        );
+    }
+
+    if let Some(collection_id) = collection_id
+        && !state
+            .conversation_repo
+            .set_collection(&id, &claims.sub, collection_id.as_deref())
+            .await?
+    {
+        return Err(AppError::NotFound("Conversation not found".to_string()));
+    }
+
+    let conv = state
+        .conversation_repo
+        .get(&id, &claims.sub)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Conversation not found".to_string()))?;
+    Ok(Json(conv))
+}
+
 #[cfg_attr(feature = "openapi", utoipa::path(delete, path = "/api/conversations/{id}", tag = "Chat", security(("bearer_auth" = [])), params(("id" = String, Path, description = "Conversation ID")), responses((status = 200))))]
 pub async fn delete_conversation(
     State(state): State<AppState>,
@@ -96,7 +290,7 @@ pub async fn delete_conversation(
     state.conversation_repo.soft_delete(&id, &claims.sub).await?;
 
     audit::log(
-        &state.audit_log_repo,
+        &state.audit,
         Some(&claims.sub),
         "chat.delete",
         Some("conversation"),
@@ -109,15 +303,127 @@ pub async fn delete_conversation(
     Ok(())
 }
 
+#[derive(Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams))]
+pub struct DeleteMessageQuery {
+    /// Also delete the assistant reply to a deleted user message.
+    #[serde(default)]
+    pub cascade: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DeleteMessageResponse {
+    /// The message and, with `cascade`, its reply.
+    pub deleted_ids: Vec<String>,
+}
+
+/// Delete one message, e.g. one a secret was pasted into. Its content is
+/// wiped; admin logs show an empty stub where it was.
+#[cfg_attr(feature = "openapi", utoipa::path(delete, path = "/api/conversations/{id}/messages/{message_id}", tag = "Chat", security(("bearer_auth" = [])), params(("id" = String, Path, description = "Conversation ID"), ("message_id" = String, Path, description = "Message ID"), DeleteMessageQuery), responses((status = 200, body = DeleteMessageResponse), (status = 404, description = "Conversation or message not found"))))]
+pub async fn delete_message(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path((conversation_id, id)): Path<(String, String)>,
+    Query(query): Query<DeleteMessageQuery>,
+) -> Result<Json<DeleteMessageResponse>, AppError> {
+    state
+        .conversation_repo
+        .get(&conversation_id, &claims.sub)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Conversation not found".to_string()))?;
+
+    let deleted_ids = state
+        .conversation_repo
+        .delete_message(&conversation_id, &id, query.cascade)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Message not found".to_string()))?;
+
+    audit::log(
+        &state.audit,
+        Some(&claims.sub),
+        "chat.message_delete",
+        Some("message"),
+        Some(&id),
+        "Deleted message",
+        None,
+        Some(serde_json::json!({ "conversation_id": conversation_id, "deleted_ids": deleted_ids })),
+    );
+
+    Ok(Json(DeleteMessageResponse { deleted_ids }))
+}
+
+/// Hide a conversation from the default listing without deleting it.
+#[cfg_attr(feature = "openapi", utoipa::path(post, path = "/api/conversations/{id}/archive", tag = "Chat", security(("bearer_auth" = [])), params(("id" = String, Path, description = "Conversation ID")), responses((status = 200, body = Conversation))))]
+pub async fn archive_conversation(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(id): Path<String>,
+) -> Result<Json<Conversation>, AppError> {
+    if !state.conversation_repo.archive(&id, &claims.sub).await? {
+        return Err(AppError::NotFound("Conversation not found".to_string()));
+    }
+
+    audit::log(
+        &state.audit,
+        Some(&claims.sub),
+        "chat.archive",
+        Some("conversation"),
+        Some(&id),
+        "Archived conversation",
+        None,
+        None,
+    );
+
+    let conv = state
+        .conversation_repo
+        .get(&id, &claims.sub)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Conversation not found".to_string()))?;
+    Ok(Json(conv))
+}
+
+#[cfg_attr(feature = "openapi", utoipa::path(post, path = "/api/conversations/{id}/unarchive", tag = "Chat", security(("bearer_auth" = [])), params(("id" = String, Path, description = "Conversation ID")), responses((status = 200, body = Conversation))))]
+pub async fn unarchive_conversation(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(id): Path<String>,
+) -> Result<Json<Conversation>, AppError> {
+    if !state.conversation_repo.unarchive(&id, &claims.sub).await? {
+        return Err(AppError::NotFound("Conversation not found".to_string()));
+    }
+
+    audit::log(
+        &state.audit,
+        Some(&claims.sub),
+        "chat.unarchive",
+        Some("conversation"),
+        Some(&id),
+        "Unarchived conversation",
+        None,
+        None,
+    );
+
+    let conv = state
+        .conversation_repo
+        .get(&id, &claims.sub)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Conversation not found".to_string()))?;
+    Ok(Json(conv))
+}
+
 // ── Send Message (with LLM + persistence) ───────────────────
 
 #[derive(Deserialize)]
 #[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct SendMessageRequest {
     pub message: String,
+    /// Only retrieve context from documents carrying at least one of these tags.
+    #[serde(default)]
+    pub tags: Option<Vec<String>>,
 }
 
-#[cfg_attr(feature = "openapi", utoipa::path(post, path = "/api/conversations/{id}/messages", tag = "Chat", security(("bearer_auth" = [])), params(("id" = String, Path, description = "Conversation ID")), request_body = SendMessageRequest, responses((status = 200, description = "SSE stream of assistant response"))))]
+#[cfg_attr(feature = "openapi", utoipa::path(post, path = "/api/conversations/{id}/messages", tag = "Chat", security(("bearer_auth" = [])), params(("id" = String, Path, description = "Conversation ID")), request_body = SendMessageRequest, responses((status = 200, description = "SSE stream of the assistant response: `warning` events (`{\"warning\": \"...\"}`) may come first, `token` events carry reply text, then a `done` event (data `[DONE]`) ends it, or an `error` event with `{\"error\": \"...\"}` replaces it. Comment pings are sent every 15 seconds while the reply is generated."), (status = 409, description = "`generation_in_progress`: a reply to an earlier message is still being generated"))))]
 pub async fn send_message(
     State(state): State<AppState>,
     claims: Claims,
@@ -127,6 +433,7 @@ pub async fn send_message(
     if payload.message.trim().is_empty() {
         return Err(AppError::Validation("Message cannot be empty".to_string()));
     }
+    let tags = crate::routes::documents::normalize_tags(payload.tags.as_deref().unwrap_or_default())?;
 
     // Verify conversation belongs to user
     let conv = state
@@ -135,14 +442,27 @@ pub async fn send_message(
         .await?
         .ok_or_else(|| AppError::NotFound("Conversation not found".to_string()))?;
 
+    // One send at a time, so exchanges from two tabs can't interleave
+    let guard = state
+        .conversation_locks
+        .try_lock(&conversation_id)
+        .ok_or(AppError::GenerationInProgress)?;
+
+    // Picking an archived conversation back up returns it to the sidebar
+    if conv.archived_at.is_some() {
+        state.conversation_repo.unarchive(&conversation_id, &claims.sub).await?;
+    }
+
+    let history = state.conversation_repo.get_messages(&conversation_id).await?;
+
     // Persist user message
     state
         .conversation_repo
-        .add_message(&conversation_id, "user", &payload.message)
+        .add_message(&conversation_id, "user", &payload.message, None)
         .await?;
 
     audit::log(
-        &state.audit_log_repo,
+        &state.audit,
         Some(&claims.sub),
         "chat.message",
         Some("conversation"),
@@ -152,143 +472,70 @@ pub async fn send_message(
         None,
     );
 
-    // Auto-title on first message
-    if conv.title == "New Chat" {
-        let title: String = payload.message.chars().take(50).collect();
+    // Title the conversation from the first message until a generated title replaces it
+    let first_exchange = !conv.title_is_custom && conv.title == titles::DEFAULT_TITLE;
+    if first_exchange {
         let _ = state
             .conversation_repo
-            .update_title(&conversation_id, &title)
+            .update_title(&conversation_id, &titles::fallback_title(&payload.message))
             .await;
     }
 
-    // Resolve provider/model from user preferences
-    let prefs = state.settings_repo.get_preferences(&claims.sub).await?;
-
-    let provider_name = prefs
-        .as_ref()
-        .map(|p| p.preferred_provider.clone())
-        .unwrap_or_else(|| state.config.llm.default_provider.clone());
-
-    let model_name = prefs
-        .as_ref()
-        .map(|p| p.preferred_model.clone())
-        .unwrap_or_else(|| state.config.llm.default_model.clone());
-
-    let system_prompt = prefs
-        .as_ref()
-        .map(|p| p.system_prompt.clone())
-        .filter(|s| !s.is_empty())
-        .unwrap_or_else(|| state.config.llm.default_system_prompt.clone());
-
-    // Get API key
-    let api_key = state
-        .settings_repo
-        .get_api_key(&claims.sub, &provider_name)
-        .await?
-        .ok_or_else(|| {
-            AppError::Validation(format!(
-                "No API key configured for provider '{provider_name}'. Add one in Settings."
-            ))
-        })?;
-
-    // RAG context retrieval: embed the user's message and search for relevant chunks
-    let mut rag_context = String::new();
-
-    let embedding_provider = prefs
-        .as_ref()
-        .map(|p| p.preferred_provider.clone())
-        .unwrap_or_else(|| state.config.llm.default_provider.clone());
-    let embedding_model_name = prefs
-        .as_ref()
-        .map(|p| p.preferred_embedding_model.clone())
-        .unwrap_or_else(|| state.config.llm.default_embedding_model.clone());
-
-    let embedding_api_key = state
-        .settings_repo
-        .get_api_key(&claims.sub, &embedding_provider)
-        .await
-        .ok()
-        .flatten();
-
-    if let Some(ref emb_key) = embedding_api_key {
-        if let Ok(emb_client) =
-            llm_provider::create_embeddings_client(&embedding_provider, emb_key)
-        {
-            let emb_model = rig::client::embeddings::EmbeddingsClientDyn::embedding_model(
-                emb_client.as_ref(),
-                &embedding_model_name,
-            );
-
-            match emb_model.embed_text(&payload.message).await {
-                Ok(query_embedding) => {
-                    match state.vector_service.search(query_embedding.vec, 5).await {
-                        Ok(results) if !results.is_empty() => {
-                            let context_parts: Vec<String> = results
-                                .iter()
-                                .filter(|r| !r.content.is_empty())
-                                .map(|r| r.content.clone())
-                                .collect();
-
-                            if !context_parts.is_empty() {
-                                rag_context = format!(
-                                    "\n\nUse the following context from the knowledge base to help answer the user's question. If the context is not relevant, you may ignore it.\n\n---\n{}\n---\n",
-                                    context_parts.join("\n\n")
-                                );
-                            }
-                        }
-                        Ok(_) => {}
-                        Err(e) => {
-                            tracing::warn!("RAG search failed: {e}");
-                        }
+    let filter = SearchFilter {
+        tags,
+        collection_id: conv.collection_id.clone(),
+    };
+    let (ctx, warnings) =
+        ChatRequestContext::for_user(&state, &claims.sub, &conversation_id, history, filter).await?;
+    let query = chat_service::retrieval_query(&state, &ctx, &payload.message).await;
+    let context = chat_service::retrieve_context(&state, &ctx.scope, &query).await;
+
+    // Title the conversation from the first exchange once the reply is saved
+    let title_request = (first_exchange && state.config.llm.auto_title_enabled).then(|| {
+        let title_model = match state.config.llm.title_model.as_str() {
+            "" => ctx.model.clone(),
+            model => model.to_string(),
+        };
+        (ctx.provider.clone(), ctx.credentials.clone(), title_model)
+    });
+    let repo = state.conversation_repo.clone();
+    let tasks = state.tasks.clone();
+    let backend = state.completion_backend.clone();
+    let message = payload.message.clone();
+    let after_reply = move |response: &str| {
+        let Some((provider_name, credentials, title_model)) = title_request else {
+            return;
+        };
+        let response = response.to_string();
+        tasks.spawn(async move {
+            let model = ModelRef {
+                provider: &provider_name,
+                model: &title_model,
+                api_key: &credentials.api_key,
+                base_url: credentials.base_url.as_deref(),
+            };
+            let result = titles::generate_title(backend.as_ref(), model, &message, &response).await;
+            match result {
+                Ok(title) => {
+                    if let Err(e) = repo.update_title(&conversation_id, &title).await {
+                        tracing::warn!("Failed to save title for conversation {conversation_id}: {e:#}");
                     }
                 }
                 Err(e) => {
-                    tracing::warn!("Failed to embed query for RAG: {e}");
+                    tracing::warn!("Title generation failed for conversation {conversation_id}: {e:#}");
                 }
             }
-        }
-    }
-
-    // Build final system prompt with RAG context
-    let final_system_prompt = format!("{system_prompt}{rag_context}");
-
-    // Create completion client via rig
-    let completion_client =
-        llm_provider::create_completion_client(&provider_name, &api_key)
-            .map_err(AppError::Internal)?;
-
-    let agent = completion_client
-        .agent(&model_name)
-        .preamble(&final_system_prompt)
-        .build();
-
-    let message = payload.message.clone();
-
-    // Get LLM response
-    let response = agent
-        .prompt(&message)
-        .await
-        .map_err(|e| AppError::Internal(anyhow::anyhow!("LLM error: {e}")))?;
-
-    // Persist assistant message
-    state
-        .conversation_repo
-        .add_message(&conversation_id, "assistant", &response)
-        .await?;
-
-    // Update conversation timestamp
-    let _ = state.conversation_repo.touch(&conversation_id).await;
-
-    // Stream response as SSE
-    let words: Vec<String> = response
-        .split_inclusive(' ')
-        .map(|s| s.to_string())
-        .collect();
-
-    let stream = tokio_stream::iter(words)
-        .throttle(std::time::Duration::from_millis(20))
-        .map(|word| Ok(Event::default().data(word)))
-        .chain(tokio_stream::once(Ok(Event::default().data("[DONE]"))));
-
-    Ok(Sse::new(stream))
+        });
+    };
+
+    let stream = chat_service::generate_reply(
+        state.clone(),
+        ctx,
+        guard,
+        payload.message,
+        context,
+        warnings,
+        after_reply,
+    )?;
+    Ok(Sse::new(stream).keep_alive(sse::keep_alive()))
 }
@@ -0,0 +1,158 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use serde::Deserialize;
+
+use crate::db::models::collection::Collection;
+use crate::errors::AppError;
+use crate::middleware::auth::{ensure_owner_or_admin, require_maintainer, Claims};
+use crate::services::audit;
+use crate::state::AppState;
+
+/// Longest collection name, in characters.
+const MAX_COLLECTION_NAME_CHARS: usize = 100;
+
+#[derive(Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CollectionRequest {
+    pub name: String,
+}
+
+fn validate_name(name: &str) -> Result<&str, AppError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(AppError::Validation("Collection name cannot be empty".to_string()));
+    }
+    if name.chars().count() > MAX_COLLECTION_NAME_CHARS {
+        return Err(AppError::Validation(format!(
+            "Collection name must be at most {MAX_COLLECTION_NAME_CHARS} characters"
+        )));
+    }
+    Ok(name)
+}
+
+/// Reject a `collection_id` that doesn't name an existing collection.
+pub(crate) async fn require_collection(state: &AppState, collection_id: Option<&str>) -> Result<(), AppError> {
+    if let Some(id) = collection_id
+        && state.collection_repo.find_by_id(id).await?.is_none()
+    {
+        return Err(AppError::Validation(format!("Collection '{id}' does not exist")));
+    }
+    Ok(())
+}
+
+/// Only the collection's owner or an admin may change it.
+async fn find_owned(state: &AppState, claims: &Claims, id: &str) -> Result<Collection, AppError> {
+    let collection = state
+        .collection_repo
+        .find_by_id(id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Collection not found".to_string()))?;
+
+    ensure_owner_or_admin(&collection.owner_id, claims)?;
+    Ok(collection)
+}
+
+/// Collections are listed to every user so they can pick one for a conversation.
+#[cfg_attr(feature = "openapi", utoipa::path(get, path = "/api/collections", tag = "Collections", security(("bearer_auth" = [])), responses((status = 200, body = Vec<Collection>))))]
+pub async fn list(
+    State(state): State<AppState>,
+    _claims: Claims,
+) -> Result<Json<Vec<Collection>>, AppError> {
+    Ok(Json(state.collection_repo.list().await?))
+}
+
+#[cfg_attr(feature = "openapi", utoipa::path(post, path = "/api/collections", tag = "Collections", security(("bearer_auth" = [])), request_body = CollectionRequest, responses((status = 200, body = Collection))))]
+pub async fn create(
+    State(state): State<AppState>,
+    claims: Claims,
+    Json(payload): Json<CollectionRequest>,
+) -> Result<Json<Collection>, AppError> {
+    require_maintainer(&claims)?;
+    let name = validate_name(&payload.name)?;
+    let collection = state.collection_repo.create(&claims.sub, name).await?;
+
+    audit::log(
+        &state.audit,
+        Some(&claims.sub),
+        "collection.create",
+        Some("collection"),
+        Some(&collection.id),
+        &format!("Created collection '{name}'"),
+        None,
+        None,
+    );
+
+    Ok(Json(collection))
+}
+
+#[cfg_attr(feature = "openapi", utoipa::path(put, path = "/api/collections/{id}", tag = "Collections", security(("bearer_auth" = [])), params(("id" = String, Path, description = "Collection ID")), request_body = CollectionRequest, responses((status = 200, body = Collection))))]
+pub async fn rename(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(id): Path<String>,
+    Json(payload): Json<CollectionRequest>,
+) -> Result<Json<Collection>, AppError> {
+    require_maintainer(&claims)?;
+    let name = validate_name(&payload.name)?;
+    let collection = find_owned(&state, &claims, &id).await?;
+    state.collection_repo.rename(&id, name).await?;
+
+    audit::log(
+        &state.audit,
+        Some(&claims.sub),
+        "collection.rename",
+        Some("collection"),
+        Some(&id),
+        &format!("Renamed collection '{}' to '{name}'", collection.name),
+        None,
+        None,
+    );
+
+    let collection = state
+        .collection_repo
+        .find_by_id(&id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Collection not found".to_string()))?;
+    Ok(Json(collection))
+}
+
+/// Delete a collection. Its documents are kept, just no longer filed in it, and
+/// conversations and embed keys limited to it search all documents again.
+#[cfg_attr(feature = "openapi", utoipa::path(delete, path = "/api/collections/{id}", tag = "Collections", security(("bearer_auth" = [])), params(("id" = String, Path, description = "Collection ID")), responses((status = 204))))]
+pub async fn delete(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(id): Path<String>,
+) -> Result<StatusCode, AppError> {
+    require_maintainer(&claims)?;
+    let collection = find_owned(&state, &claims, &id).await?;
+    state.collection_repo.delete(&id).await?;
+
+    audit::log(
+        &state.audit,
+        Some(&claims.sub),
+        "collection.delete",
+        Some("collection"),
+        Some(&id),
+        &format!("Deleted collection '{}'", collection.name),
+        None,
+        None,
+    );
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_name() {
+        assert_eq!(validate_name("  Handbook ").unwrap(), "Handbook");
+        assert!(validate_name("   ").is_err());
+        assert!(validate_name(&"x".repeat(MAX_COLLECTION_NAME_CHARS + 1)).is_err());
+    }
+}
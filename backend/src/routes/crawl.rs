@@ -1,16 +1,25 @@
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
+    http::StatusCode,
     Json,
 };
 use serde::Deserialize;
 use std::sync::Arc;
 
 use crate::db::models::crawl_job::CrawlJob;
+use crate::db::models::crawl_schedule::{CrawlSchedule, SCHEDULE_FREQUENCIES};
 use crate::db::models::document_chunk::DocumentChunkRepository;
+use crate::db::models::web_page::WebPage;
 use crate::errors::AppError;
-use crate::middleware::auth::{require_maintainer, Claims};
+use crate::middleware::auth::{ensure_owner_or_admin, require_maintainer, Claims};
+use crate::routes::documents::{require_embedding_key, store_chunk_batch};
 use crate::services::audit;
-use crate::services::vector::VectorService;
+use crate::services::chunking::{self, DEFAULT_CHUNK_OVERLAP, DEFAULT_CHUNK_SIZE};
+use crate::services::embedding::{embed_in_batches, EmbeddingTarget, EMBED_BATCH_SIZE};
+use crate::services::jobs::{JobPayload, PermanentFailure};
+use crate::services::llm_provider::{EmbeddingBackend, ModelRef};
+use crate::services::retry::RetryPolicy;
+use crate::services::vector::{ChunkLabels, VectorService};
 use crate::state::AppState;
 
 #[derive(Debug, Deserialize)]
@@ -47,21 +56,14 @@ pub async fn start_crawl(
         .map_err(|_| AppError::Validation("Invalid URL".to_string()))?;
 
     // Require an embedding API key before starting the crawl
-    let embedding_provider = state.config.llm.default_provider.clone();
-    let api_key = state
-        .settings_repo
-        .get_api_key(&claims.sub, &embedding_provider)
-        .await
-        .ok()
-        .flatten()
-        .unwrap_or_default();
-
-    if api_key.is_empty() {
-        return Err(AppError::Validation(format!(
-            "No API key configured for embedding provider '{}'. Add one in Settings before crawling.",
-            embedding_provider
-        )));
-    }
+    let embedding = state.embedding.resolve_for_user(&claims.sub).await?;
+    let api_key = embedding.api_key();
+    let EmbeddingTarget {
+        provider: embedding_provider,
+        model: embedding_model,
+    } = embedding.target;
+
+    require_embedding_key(&embedding_provider, &api_key, "crawling")?;
 
     let job = state
         .crawl_repo
@@ -69,7 +71,7 @@ pub async fn start_crawl(
         .await?;
 
     audit::log(
-        &state.audit_log_repo,
+        &state.audit,
         Some(&claims.sub),
         "crawl.start",
         Some("crawl_job"),
@@ -79,69 +81,23 @@ pub async fn start_crawl(
         None,
     );
 
-    // Spawn background crawl task
-    let job_id = job.id.clone();
-    let url = payload.url.clone();
-    let crawl_type = payload.crawl_type.clone();
-    let crawler = state.crawler.clone();
-    let crawl_repo = state.crawl_repo.clone();
-    let vector_service = state.vector_service.clone();
-    let chunk_repo = state.chunk_repo.clone();
-    let embedding_model = state.config.llm.default_embedding_model.clone();
-
-    tokio::spawn(async move {
-        // Update to running
-        let _ = crawl_repo
-            .update_status(&job_id, "running", None, None, None)
+    // Queue the crawl; the worker moves the job to running/completed/failed
+    let queued = JobPayload::CrawlEmbedding {
+        crawl_job_id: job.id.clone(),
+        user_id: claims.sub.clone(),
+        url: payload.url.clone(),
+        crawl_type: payload.crawl_type.clone(),
+        embedding_provider,
+        embedding_model,
+        schedule_id: None,
+    };
+    if let Err(e) = state.jobs.enqueue(&queued).await {
+        let _ = state
+            .crawl_repo
+            .update_status(&job.id, "failed", None, None, Some("Failed to queue crawl"))
             .await;
-
-        let result = match crawl_type.as_str() {
-            "sitemap" => {
-                run_crawl(
-                    &crawler,
-                    &crawl_repo,
-                    &job_id,
-                    &url,
-                    true,
-                    &vector_service,
-                    &chunk_repo,
-                    &embedding_provider,
-                    &embedding_model,
-                    &api_key,
-                )
-                .await
-            }
-            "full" => {
-                run_crawl(
-                    &crawler,
-                    &crawl_repo,
-                    &job_id,
-                    &url,
-                    false,
-                    &vector_service,
-                    &chunk_repo,
-                    &embedding_provider,
-                    &embedding_model,
-                    &api_key,
-                )
-                .await
-            }
-            _ => Err(anyhow::anyhow!("Invalid crawl type")),
-        };
-
-        match result {
-            Ok(()) => {
-                tracing::info!("Crawl job {job_id} completed");
-            }
-            Err(e) => {
-                let msg = format!("{e:#}");
-                let _ = crawl_repo
-                    .update_status(&job_id, "failed", None, None, Some(&msg))
-                    .await;
-                tracing::error!("Crawl job {job_id} failed: {msg}");
-            }
-        }
-    });
+        return Err(AppError::Internal(e));
+    }
 
     Ok(Json(job))
 }
@@ -159,9 +115,7 @@ pub async fn get_crawl_job(
         .await?
         .ok_or_else(|| AppError::NotFound("Crawl job not found".to_string()))?;
 
-    if job.user_id != claims.sub && claims.role != "admin" {
-        return Err(AppError::Forbidden);
-    }
+    ensure_owner_or_admin(&job.user_id, &claims)?;
 
     Ok(Json(job))
 }
@@ -176,7 +130,276 @@ pub async fn list_crawl_jobs(
     Ok(Json(jobs))
 }
 
-async fn run_crawl(
+#[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SetScheduleRequest {
+    /// `none`, `daily` or `weekly`.
+    pub schedule: String,
+}
+
+/// Re-crawl the site of a crawl job daily or weekly, or stop with `none`. Each
+/// successful run replaces the pages of the run before it. Returns the schedule,
+/// or `null` once it is removed.
+#[cfg_attr(feature = "openapi", utoipa::path(put, path = "/api/crawl/{id}/schedule", tag = "Crawl", security(("bearer_auth" = [])), params(("id" = String, Path, description = "Crawl job ID")), request_body = SetScheduleRequest, responses((status = 200, body = Option<CrawlSchedule>))))]
+pub async fn set_schedule(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(id): Path<String>,
+    Json(payload): Json<SetScheduleRequest>,
+) -> Result<Json<Option<CrawlSchedule>>, AppError> {
+    require_maintainer(&claims)?;
+
+    if !state.config.features.web_crawl_enabled {
+        return Err(AppError::FeatureDisabled("Web crawling".to_string()));
+    }
+
+    let schedule = payload.schedule.trim();
+    if schedule != "none" && !SCHEDULE_FREQUENCIES.contains(&schedule) {
+        return Err(AppError::Validation(
+            "schedule must be 'none', 'daily' or 'weekly'".to_string(),
+        ));
+    }
+
+    let job = state
+        .crawl_repo
+        .find_by_id(&id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Crawl job not found".to_string()))?;
+
+    ensure_owner_or_admin(&job.user_id, &claims)?;
+    if job.crawl_type == "page" {
+        return Err(AppError::Validation(
+            "Single pages can't be scheduled; fetch them again instead".to_string(),
+        ));
+    }
+
+    let saved = if schedule == "none" {
+        if !state.crawl_schedule_repo.delete_for(&job.url, &job.crawl_type).await? {
+            return Ok(Json(None));
+        }
+        None
+    } else {
+        Some(
+            state
+                .crawl_schedule_repo
+                .upsert(&claims.sub, &job.url, &job.crawl_type, schedule, &job.id)
+                .await?,
+        )
+    };
+
+    audit::log(
+        &state.audit,
+        Some(&claims.sub),
+        "crawl.schedule",
+        Some("crawl_job"),
+        Some(&id),
+        &match saved {
+            Some(_) => format!("Scheduled {schedule} {} crawls of {}", job.crawl_type, job.url),
+            None => format!("Stopped scheduled {} crawls of {}", job.crawl_type, job.url),
+        },
+        None,
+        None,
+    );
+
+    Ok(Json(saved))
+}
+
+/// Scheduled crawls, soonest run first.
+#[cfg_attr(feature = "openapi", utoipa::path(get, path = "/api/crawl/schedules", tag = "Crawl", security(("bearer_auth" = [])), responses((status = 200, body = Vec<CrawlSchedule>))))]
+pub async fn list_schedules(
+    State(state): State<AppState>,
+    claims: Claims,
+) -> Result<Json<Vec<CrawlSchedule>>, AppError> {
+    require_maintainer(&claims)?;
+    Ok(Json(state.crawl_schedule_repo.list_upcoming().await?))
+}
+
+#[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct IngestPageRequest {
+    pub url: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams))]
+pub struct PageQuery {
+    pub url: String,
+}
+
+/// Only absolute http(s) URLs with a host can be fetched as a single page.
+fn validate_page_url(url: &str) -> Result<String, AppError> {
+    let parsed = url::Url::parse(url.trim()).map_err(|_| AppError::Validation("Invalid URL".to_string()))?;
+    if !matches!(parsed.scheme(), "http" | "https") || parsed.host_str().is_none() {
+        return Err(AppError::Validation("URL must use http or https".to_string()));
+    }
+    Ok(parsed.to_string())
+}
+
+/// Fetch a single page into the knowledge base without crawling its site.
+/// Runs as a one-page crawl job; fetching a URL again replaces its chunks.
+#[cfg_attr(feature = "openapi", utoipa::path(post, path = "/api/crawl/page", tag = "Crawl", security(("bearer_auth" = [])), request_body = IngestPageRequest, responses((status = 200, body = CrawlJob))))]
+pub async fn ingest_page(
+    State(state): State<AppState>,
+    claims: Claims,
+    Json(payload): Json<IngestPageRequest>,
+) -> Result<Json<CrawlJob>, AppError> {
+    require_maintainer(&claims)?;
+
+    if !state.config.features.web_crawl_enabled {
+        return Err(AppError::FeatureDisabled("Web crawling".to_string()));
+    }
+
+    let url = validate_page_url(&payload.url)?;
+
+    let embedding = state.embedding.resolve_for_user(&claims.sub).await?;
+    let api_key = embedding.api_key();
+    let EmbeddingTarget {
+        provider: embedding_provider,
+        model: embedding_model,
+    } = embedding.target;
+
+    require_embedding_key(&embedding_provider, &api_key, "crawling")?;
+
+    let job = state.crawl_repo.create(&claims.sub, &url, "page").await?;
+
+    audit::log(
+        &state.audit,
+        Some(&claims.sub),
+        "crawl.page",
+        Some("crawl_job"),
+        Some(&job.id),
+        &format!("Started fetching page {url}"),
+        None,
+        None,
+    );
+
+    let queued = JobPayload::PageEmbedding {
+        crawl_job_id: job.id.clone(),
+        user_id: claims.sub.clone(),
+        url,
+        embedding_provider,
+        embedding_model,
+    };
+    if let Err(e) = state.jobs.enqueue(&queued).await {
+        let _ = state
+            .crawl_repo
+            .update_status(&job.id, "failed", None, None, Some("Failed to queue page"))
+            .await;
+        return Err(AppError::Internal(e));
+    }
+
+    Ok(Json(job))
+}
+
+/// Remove a page ingested with `POST /api/crawl/page` and its chunks.
+#[cfg_attr(feature = "openapi", utoipa::path(delete, path = "/api/crawl/page", tag = "Crawl", security(("bearer_auth" = [])), params(PageQuery), responses((status = 204))))]
+pub async fn delete_page(
+    State(state): State<AppState>,
+    claims: Claims,
+    Query(query): Query<PageQuery>,
+) -> Result<StatusCode, AppError> {
+    require_maintainer(&claims)?;
+    let url = validate_page_url(&query.url)?;
+    let page = state
+        .web_page_repo
+        .find_by_url(&url)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Page not found".to_string()))?;
+
+    delete_page_chunks(&state, &page).await?;
+    state.web_page_repo.delete(&url).await?;
+
+    audit::log(
+        &state.audit,
+        Some(&claims.sub),
+        "crawl.page_delete",
+        Some("web_page"),
+        Some(&url),
+        &format!("Deleted page {url}"),
+        None,
+        None,
+    );
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn delete_page_chunks(state: &AppState, page: &WebPage) -> anyhow::Result<()> {
+    let point_ids = state.chunk_repo.delete_by_source("page", &page.url).await?;
+    if let Err(e) = state
+        .vector_cleanup
+        .delete_points(&page.vector_collection, point_ids)
+        .await
+    {
+        tracing::error!("Failed to delete vectors for page {}, queued for retry: {e}", page.url);
+    }
+    Ok(())
+}
+
+/// Fetch, chunk and embed one page for a `page` crawl job. The page's earlier
+/// chunks, including any a failed attempt left behind, are replaced.
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn run_page(
+    state: &AppState,
+    job_id: &str,
+    user_id: &str,
+    url: &str,
+    embedding_provider: &str,
+    embedding_model: &str,
+    api_key: &str,
+    base_url: Option<&str>,
+) -> anyhow::Result<()> {
+    if api_key.is_empty() && crate::services::llm_provider::requires_api_key(embedding_provider) {
+        anyhow::bail!("No API key configured for embedding provider '{embedding_provider}'");
+    }
+
+    state
+        .crawl_repo
+        .update_status(job_id, "running", Some(1), Some(0), None)
+        .await?;
+
+    let page = state.crawler.fetch_page(url).await?;
+    if page.content.is_empty() {
+        return Err(PermanentFailure("Page has no text content".to_string()).into());
+    }
+
+    if let Some(previous) = state.web_page_repo.find_by_url(url).await? {
+        delete_page_chunks(state, &previous).await?;
+    }
+    // Record the collection before storing so a failed attempt's chunks can be found
+    state
+        .web_page_repo
+        .upsert(
+            url,
+            user_id,
+            page.title.as_deref(),
+            &state.vector_service.collection_for_model(embedding_model),
+            job_id,
+        )
+        .await?;
+
+    embed_crawled_pages(
+        std::slice::from_ref(&page),
+        "page",
+        url,
+        &state.vector_service,
+        &state.chunk_repo,
+        state.embedding_backend.as_ref(),
+        embedding_provider,
+        embedding_model,
+        api_key,
+        base_url,
+    )
+    .await?;
+
+    state
+        .crawl_repo
+        .update_status(job_id, "completed", None, Some(1), None)
+        .await?;
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn run_crawl(
     crawler: &crate::services::crawler::CrawlerService,
     crawl_repo: &crate::db::models::crawl_job::CrawlJobRepository,
     job_id: &str,
@@ -184,9 +407,11 @@ async fn run_crawl(
     is_sitemap: bool,
     vector_service: &Arc<VectorService>,
     chunk_repo: &DocumentChunkRepository,
+    embedding_backend: &dyn EmbeddingBackend,
     embedding_provider: &str,
     embedding_model: &str,
     api_key: &str,
+    base_url: Option<&str>,
 ) -> anyhow::Result<()> {
     let urls = if is_sitemap {
         crawler.crawl_sitemap(url).await?
@@ -204,19 +429,22 @@ async fn run_crawl(
     let processed = successful_pages.len() as i64;
 
     // Embed page content
-    if api_key.is_empty() {
+    if api_key.is_empty() && crate::services::llm_provider::requires_api_key(embedding_provider) {
         anyhow::bail!("No API key configured for embedding provider '{embedding_provider}'");
     }
 
     if !successful_pages.is_empty() {
         embed_crawled_pages(
             &successful_pages,
+            "crawl_page",
             job_id,
             vector_service,
             chunk_repo,
+            embedding_backend,
             embedding_provider,
             embedding_model,
             api_key,
+            base_url,
         )
         .await?;
     }
@@ -227,28 +455,32 @@ async fn run_crawl(
     Ok(())
 }
 
+/// Chunk and embed crawled pages, storing every chunk under one source.
+#[allow(clippy::too_many_arguments)]
 async fn embed_crawled_pages(
     pages: &[crate::services::crawler::CrawledPage],
-    job_id: &str,
+    source_type: &str,
+    source_id: &str,
     vector_service: &Arc<VectorService>,
     chunk_repo: &DocumentChunkRepository,
+    embedding_backend: &dyn EmbeddingBackend,
     embedding_provider: &str,
     embedding_model_name: &str,
     api_key: &str,
+    base_url: Option<&str>,
 ) -> anyhow::Result<()> {
-    let embeddings_client =
-        crate::services::llm_provider::create_embeddings_client(embedding_provider, api_key)?;
-
-    let model = rig::client::embeddings::EmbeddingsClientDyn::embedding_model(
-        embeddings_client.as_ref(),
-        embedding_model_name,
-    );
+    let embedder = embedding_backend.embedder(ModelRef {
+        provider: embedding_provider,
+        model: embedding_model_name,
+        api_key,
+        base_url,
+    })?;
 
     let mut all_chunks: Vec<String> = Vec::new();
     let mut chunk_metadata: Vec<(usize, i32)> = Vec::new(); // (page_index, chunk_index)
 
     for (page_idx, page) in pages.iter().enumerate() {
-        let chunks = crate::services::text_extract::chunk_text(&page.content, 200, 30);
+        let chunks = chunking::chunk_text(&page.content, DEFAULT_CHUNK_SIZE, DEFAULT_CHUNK_OVERLAP);
         for (chunk_idx, chunk) in chunks.into_iter().enumerate() {
             all_chunks.push(chunk);
             chunk_metadata.push((page_idx, chunk_idx as i32));
@@ -259,50 +491,57 @@ async fn embed_crawled_pages(
         return Ok(());
     }
 
-    // Embed in batches to avoid API limits
-    let batch_size = 100;
-    let mut qdrant_data = Vec::new();
-    let mut db_data = Vec::new();
-
-    for batch_start in (0..all_chunks.len()).step_by(batch_size) {
-        let batch_end = (batch_start + batch_size).min(all_chunks.len());
-        let batch: Vec<String> = all_chunks[batch_start..batch_end].to_vec();
-
-        let embeddings = model
-            .embed_texts(batch)
-            .await
-            .map_err(|e| anyhow::anyhow!("Embedding error: {e}"))?;
-
-        for (i, embedding) in embeddings.iter().enumerate() {
-            let global_idx = batch_start + i;
-            let point_id = uuid::Uuid::new_v4().to_string();
-
-            qdrant_data.push((
-                point_id.clone(),
-                embedding.vec.clone(),
-                all_chunks[global_idx].clone(),
-            ));
-            db_data.push((
-                "crawl_page".to_string(),
-                job_id.to_string(),
-                chunk_metadata[global_idx].1,
-                all_chunks[global_idx].clone(),
-                point_id,
-            ));
-        }
-    }
-
-    // Upsert to Qdrant
-    vector_service.upsert_chunks(qdrant_data).await?;
-
-    // Save to database
-    chunk_repo.create_batch(&db_data).await?;
+    // Crawled pages carry no document labels
+    let labels = ChunkLabels::default();
+
+    // Embed in batches to avoid API limits, storing each batch as it completes
+    embed_in_batches(
+        &all_chunks,
+        0,
+        EMBED_BATCH_SIZE,
+        RetryPolicy::default(),
+        |batch| embedder.embed_texts(batch),
+        |start, embeddings| {
+            let batch = embeddings
+                .into_iter()
+                .enumerate()
+                .map(|(i, e)| (chunk_metadata[start + i].1, all_chunks[start + i].clone(), e))
+                .collect();
+            store_chunk_batch(
+                vector_service,
+                chunk_repo,
+                embedding_model_name,
+                source_type,
+                source_id,
+                &labels,
+                batch,
+            )
+        },
+    )
+    .await?;
 
     tracing::info!(
-        "Crawl job {job_id}: embedded {} chunks from {} pages into Qdrant",
+        "{source_type} {source_id}: embedded {} chunks from {} pages into Qdrant",
         all_chunks.len(),
         pages.len()
     );
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_page_url() {
+        assert_eq!(
+            validate_page_url(" https://example.com/docs ").unwrap(),
+            "https://example.com/docs"
+        );
+        assert_eq!(validate_page_url("http://example.com").unwrap(), "http://example.com/");
+        assert!(validate_page_url("ftp://example.com/file").is_err());
+        assert!(validate_page_url("file:///etc/passwd").is_err());
+        assert!(validate_page_url("not a url").is_err());
+    }
+}
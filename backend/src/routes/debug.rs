@@ -0,0 +1,197 @@
+use axum::{extract::State, Json};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::config::RetrievalMode;
+use crate::errors::AppError;
+use crate::middleware::auth::{require_maintainer, Claims};
+use crate::routes::collections::require_collection;
+use crate::routes::documents::normalize_tags;
+use crate::services::audit;
+use crate::services::chat_service::{self, RetrievalScope};
+use crate::services::vector::SearchFilter;
+use crate::state::AppState;
+
+/// Most chunks a debug query may ask for.
+const MAX_DEBUG_TOP_K: u64 = 100;
+
+#[derive(Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct RetrievalDebugRequest {
+    pub query: String,
+    /// Overrides `llm.rag_top_k` for this query.
+    pub top_k: Option<u64>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub collection_id: Option<String>,
+}
+
+/// What retrieval returned for a query and what would reach the model.
+#[derive(Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct RetrievalDebugResponse {
+    pub query: String,
+    pub embedding_provider: String,
+    pub embedding_model: String,
+    /// Qdrant collection searched.
+    pub collection: String,
+    /// `vector` or `hybrid`.
+    pub retrieval_mode: String,
+    pub reranked: bool,
+    pub top_k: u64,
+    pub min_score: f32,
+    pub max_context_chars: usize,
+    /// Ranked as the prompt would see them.
+    pub chunks: Vec<RetrievedChunk>,
+    /// Appended to the system prompt verbatim; empty when nothing qualified.
+    pub context: String,
+}
+
+#[derive(Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct RetrievedChunk {
+    /// 1-based position after reranking.
+    pub rank: usize,
+    pub point_id: String,
+    /// Ranking score: cosine similarity, RRF score for hybrid search, or the reranker's score.
+    pub score: f32,
+    /// Cosine similarity, when the chunk came from the vector query.
+    pub vector_score: Option<f32>,
+    /// Whether the chunk passed `min_score` and fit the context budget.
+    pub included: bool,
+    pub content: String,
+    /// `document`, `crawl_page` or `page`; absent if the chunk row is gone.
+    pub source_type: Option<String>,
+    pub source_id: Option<String>,
+    pub chunk_index: Option<i32>,
+    /// Document filename or crawled URL.
+    pub source_name: Option<String>,
+}
+
+#[cfg_attr(feature = "openapi", utoipa::path(post, path = "/api/debug/retrieval", tag = "Debug", security(("bearer_auth" = [])), request_body = RetrievalDebugRequest, responses((status = 200, body = RetrievalDebugResponse), (status = 403, description = "Not a maintainer, or debug endpoints are disabled"), (status = 503, description = "Embedding or search failed"))))]
+pub async fn retrieval(
+    State(state): State<AppState>,
+    claims: Claims,
+    Json(payload): Json<RetrievalDebugRequest>,
+) -> Result<Json<RetrievalDebugResponse>, AppError> {
+    require_maintainer(&claims)?;
+
+    if !state.config.features.debug_endpoints_enabled {
+        return Err(AppError::FeatureDisabled("Debug endpoints".to_string()));
+    }
+
+    let query = payload.query.trim();
+    if query.is_empty() {
+        return Err(AppError::Validation("Query is required".to_string()));
+    }
+    let collection_id = payload.collection_id.filter(|c| !c.is_empty());
+    require_collection(&state, collection_id.as_deref()).await?;
+
+    let filter = SearchFilter {
+        tags: normalize_tags(&payload.tags)?,
+        collection_id,
+    };
+    let mut scope = RetrievalScope::for_user(&state, &claims.sub, filter).await?;
+    if let Some(top_k) = payload.top_k {
+        scope.params.top_k = top_k.clamp(1, MAX_DEBUG_TOP_K);
+    }
+
+    let trace = chat_service::trace_retrieval(&state, &scope, query).await?;
+    let chunks = describe_chunks(&state, &trace).await?;
+
+    audit::log(
+        &state.audit,
+        Some(&claims.sub),
+        "debug.retrieval",
+        None,
+        None,
+        &format!(
+            "Debugged retrieval: {} chunks found, {} in context",
+            chunks.len(),
+            trace.included.len()
+        ),
+        None,
+        Some(serde_json::json!({ "query": query, "collection": scope.collection })),
+    );
+
+    Ok(Json(RetrievalDebugResponse {
+        query: query.to_string(),
+        embedding_provider: scope.embedding.target.provider,
+        embedding_model: scope.embedding.target.model,
+        collection: scope.collection,
+        retrieval_mode: match state.config.llm.retrieval_mode {
+            RetrievalMode::Vector => "vector",
+            RetrievalMode::Hybrid => "hybrid",
+        }
+        .to_string(),
+        reranked: state.reranker.is_enabled(),
+        top_k: scope.params.top_k,
+        min_score: scope.params.min_score,
+        max_context_chars: scope.params.max_context_chars,
+        chunks,
+        context: trace.context,
+    }))
+}
+
+/// Attach each result's chunk row and the name of the document or page it came from.
+async fn describe_chunks(
+    state: &AppState,
+    trace: &chat_service::RetrievalTrace,
+) -> Result<Vec<RetrievedChunk>, AppError> {
+    let point_ids: Vec<String> = trace.results.iter().map(|r| r.point_id.clone()).collect();
+    let rows: HashMap<String, _> = state
+        .chunk_repo
+        .find_by_qdrant_ids(&point_ids)
+        .await?
+        .into_iter()
+        .map(|c| (c.qdrant_point_id.clone(), c))
+        .collect();
+
+    let document_ids: Vec<String> = rows
+        .values()
+        .filter(|c| c.source_type == "document")
+        .map(|c| c.source_id.clone())
+        .collect();
+    let mut names: HashMap<String, String> = state
+        .document_repo
+        .find_by_ids(&document_ids)
+        .await?
+        .into_iter()
+        .map(|d| (d.id, d.original_filename))
+        .collect();
+    for chunk in rows.values() {
+        match chunk.source_type.as_str() {
+            // Single pages are stored under their URL
+            "page" => {
+                names.insert(chunk.source_id.clone(), chunk.source_id.clone());
+            }
+            "crawl_page" if !names.contains_key(&chunk.source_id) => {
+                if let Some(job) = state.crawl_repo.find_by_id(&chunk.source_id).await? {
+                    names.insert(job.id, job.url);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(trace
+        .results
+        .iter()
+        .enumerate()
+        .map(|(i, result)| {
+            let row = rows.get(&result.point_id);
+            RetrievedChunk {
+                rank: i + 1,
+                point_id: result.point_id.clone(),
+                score: result.score,
+                vector_score: result.vector_score,
+                included: trace.included.contains(&i),
+                content: result.content.clone(),
+                source_type: row.map(|c| c.source_type.clone()),
+                source_id: row.map(|c| c.source_id.clone()),
+                chunk_index: row.map(|c| c.chunk_index),
+                source_name: row.and_then(|c| names.get(&c.source_id).cloned()),
+            }
+        })
+        .collect())
+}
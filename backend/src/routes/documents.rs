@@ -1,18 +1,37 @@
 use axum::{
-    extract::{Multipart, Path, State},
+    extract::{multipart::Field, Multipart, Path, Query, State},
     Json,
 };
-use futures::FutureExt;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::sync::Arc;
 
-use crate::db::models::document::DocumentStatus;
+use crate::config::OcrConfig;
+use crate::db::models::document::{Document, DocumentStatus};
 use crate::db::models::document_chunk::DocumentChunkRepository;
-use crate::dto::document::DocumentResponse;
+use crate::db::models::document_event::DocumentEvent;
+use crate::db::models::rescan_run::RescanRun;
+use crate::db::models::settings::ProviderCredentials;
+use crate::dto::document::{
+    DocumentChunkResponse, DocumentChunksResponse, DocumentDetailResponse, DocumentResponse,
+};
 use crate::errors::AppError;
-use crate::middleware::auth::{require_admin, require_maintainer, Claims};
+use crate::middleware::body_limit::multipart_error;
+use crate::middleware::auth::{ensure_owner_or_admin, require_admin, require_maintainer, Claims};
+use crate::routes::collections::require_collection;
 use crate::services::audit;
+use crate::services::chunking::{self, DEFAULT_CHUNK_OVERLAP, DEFAULT_CHUNK_SIZE};
+use crate::services::credentials::KeySource;
+use crate::services::document_events::DocumentEventLog;
+use crate::services::embedding::{embed_in_batches, EmbeddingTarget, EMBED_BATCH_SIZE};
+use crate::services::jobs::{keep_leased, JobPayload, PermanentFailure, LEASE};
+use crate::services::llm_provider::{EmbeddingBackend, ModelRef};
+use crate::services::retry::RetryPolicy;
 use crate::services::storage::StorageService;
-use crate::services::vector::VectorService;
+use crate::services::text_extract;
+use crate::services::tasks::SHUTDOWN_ERROR;
+use crate::services::vector::{ChunkLabels, VectorService};
+use crate::services::vector_cleanup::VectorCleanup;
 use crate::state::AppState;
 
 #[cfg_attr(feature = "openapi", utoipa::path(get, path = "/api/documents/limits", tag = "Documents", security(("bearer_auth" = [])), responses((status = 200, description = "Upload size limits", content_type = "application/json"))))]
@@ -24,10 +43,22 @@ pub async fn upload_limits(
     }))
 }
 
-#[cfg_attr(feature = "openapi", utoipa::path(post, path = "/api/documents", tag = "Documents", security(("bearer_auth" = [])), request_body(content_type = "multipart/form-data", description = "File upload"), responses((status = 200, body = DocumentResponse), (status = 413, description = "File too large"))))]
+#[derive(Debug, Default, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams))]
+pub struct UploadQuery {
+    /// Keep the upload as a separate document even if one with the same
+    /// filename exists, instead of making it that document's next version.
+    #[serde(default)]
+    pub new_document: bool,
+}
+
+/// Upload a document. A file named like one of the user's documents becomes
+/// its next version: the earlier one is superseded once this one is ready.
+#[cfg_attr(feature = "openapi", utoipa::path(post, path = "/api/documents", tag = "Documents", security(("bearer_auth" = [])), params(UploadQuery), request_body(content_type = "multipart/form-data", description = "File upload"), responses((status = 200, body = DocumentResponse), (status = 413, description = "File too large"), (status = 503, description = "Vector store unavailable"))))]
 pub async fn upload(
     State(state): State<AppState>,
     claims: Claims,
+    Query(query): Query<UploadQuery>,
     mut multipart: Multipart,
 ) -> Result<Json<DocumentResponse>, AppError> {
     require_maintainer(&claims)?;
@@ -37,60 +68,73 @@ pub async fn upload(
     }
 
     // Require an embedding API key before accepting the upload
-    let embedding_provider = state.config.llm.default_provider.clone();
-    let api_key = state
-        .settings_repo
-        .get_api_key(&claims.sub, &embedding_provider)
-        .await
-        .ok()
-        .flatten()
-        .unwrap_or_default();
-
-    if api_key.is_empty() {
-        return Err(AppError::Validation(format!(
-            "No API key configured for embedding provider '{}'. Add one in Settings before uploading.",
-            embedding_provider
-        )));
+    let embedding = state.embedding.resolve_for_user(&claims.sub).await?;
+    let api_key = embedding.api_key();
+    let EmbeddingTarget {
+        provider: embedding_provider,
+        model: embedding_model,
+    } = embedding.target;
+
+    require_embedding_key(&embedding_provider, &api_key, "uploading")?;
+
+    // Refuse the file up front rather than failing once it is stored and queued
+    if let Err(e) = state.vector_service.check_health().await {
+        tracing::warn!("Rejecting upload, vector store health check failed: {e}");
+        return Err(AppError::Unavailable(
+            "The vector store is unavailable, so documents can't be processed right now. Try again later."
+                .to_string(),
+        ));
     }
 
-    let max_file_size = state.config.server.max_upload_size_mb * 1024 * 1024;
+    let max_upload_mb = state.config.server.max_upload_size_mb;
+    let max_file_size = max_upload_mb * 1024 * 1024;
 
-    let field = multipart
-        .next_field()
-        .await
-        .map_err(|e| AppError::Validation(format!("Invalid multipart data: {e}")))?
-        .ok_or_else(|| AppError::Validation("No file provided".to_string()))?;
+    // Form fields may come before or after the file
+    let mut fields = UploadFields::default();
+    let mut field = loop {
+        let field = multipart
+            .next_field()
+            .await
+            .map_err(|e| multipart_error(e, max_upload_mb))?
+            .ok_or_else(|| AppError::Validation("No file provided".to_string()))?;
+        if field.file_name().is_some() || !field.name().is_some_and(|n| UploadFields::NAMES.contains(&n)) {
+            break field;
+        }
+        fields.read(field, max_upload_mb).await?;
+    };
 
     let original_filename = field
         .file_name()
         .unwrap_or("unnamed.txt")
         .to_string();
 
-    let content_type = field
+    let declared_type = field
         .content_type()
         .unwrap_or("application/octet-stream")
         .to_string();
 
-    if !crate::services::text_extract::is_supported(&content_type, &original_filename) {
+    // Identify the file from its first bytes; browsers often send a generic or wrong type
+    let mut head = Vec::with_capacity(text_extract::SNIFF_LEN);
+    while head.len() < text_extract::SNIFF_LEN
+        && let Some(chunk) = field
+            .chunk()
+            .await
+            .map_err(|e| multipart_error(e, max_upload_mb))?
+    {
+        head.extend_from_slice(&chunk);
+    }
+
+    let content_type = text_extract::sniff_content_type(&head, &declared_type, &original_filename).to_string();
+    if !text_extract::is_supported_type(&content_type) {
         return Err(AppError::Validation(format!(
-            "Unsupported file type. Supported: PDF, DOCX, XLSX, XML, CSV, TXT, MD"
+            "Unsupported file type: the content is {content_type} (uploaded as {declared_type}). Supported: PDF, DOCX, XLSX, XML, CSV, TXT, MD"
         )));
     }
-
-    let data = field
-        .bytes()
-        .await
-        .map_err(|e| AppError::Validation(format!("Failed to read file: {e}")))?;
-
-    if data.len() > max_file_size {
-        return Err(AppError::PayloadTooLarge(
-            state.config.server.max_upload_size_mb,
-        ));
+    if content_type != declared_type {
+        tracing::info!("Upload '{original_filename}' declared as {declared_type}, detected {content_type}");
     }
 
-    let size_bytes = data.len() as i64;
-
-    // Create document record first
+    // Create document record first; its id is part of the storage key
     let doc = state
         .document_repo
         .create(
@@ -98,7 +142,7 @@ pub async fn upload(
             &original_filename,
             "", // placeholder, will update after generating key
             &content_type,
-            size_bytes,
+            0, // updated once the whole file has been received
         )
         .await?;
 
@@ -110,25 +154,87 @@ pub async fn upload(
         .update_minio_key(&doc.id, &minio_key)
         .await?;
 
-    tracing::info!(
-        "Document {}: uploading {} bytes to MinIO (key={})",
-        doc.id,
-        data.len(),
-        minio_key
-    );
+    tracing::info!("Document {}: streaming upload to MinIO (key={})", doc.id, minio_key);
 
-    // Upload to MinIO
-    let storage = state.storage.clone();
-    let upload_data = data.to_vec();
-    let ct = content_type.clone();
-    let key = minio_key.clone();
+    // Stream the file to MinIO part by part, enforcing the size limit as it arrives
+    let mut upload = state.storage.start_upload(&minio_key, &content_type);
+    let mut size_bytes = head.len();
+    let streamed: Result<(), AppError> = async {
+        if size_bytes > max_file_size {
+            return Err(AppError::PayloadTooLarge(max_upload_mb));
+        }
+        upload.write(&head).await?;
 
-    storage
-        .upload(&key, upload_data, &ct)
-        .await
-        .map_err(|e| AppError::Internal(e))?;
+        while let Some(chunk) = field
+            .chunk()
+            .await
+            .map_err(|e| multipart_error(e, max_upload_mb))?
+        {
+            size_bytes += chunk.len();
+            if size_bytes > max_file_size {
+                return Err(AppError::PayloadTooLarge(max_upload_mb));
+            }
+            upload.write(&chunk).await?;
+        }
+        Ok(())
+    }
+    .await;
 
-    tracing::info!("Document {}: uploaded to MinIO successfully", doc.id);
+    drop(field);
+
+    // Form fields are validated before the object is kept, so a bad value discards the upload
+    let streamed = async {
+        streamed?;
+        fields.read_remaining(&mut multipart, max_upload_mb).await?;
+        fields.validate(&state).await
+    }
+    .await;
+
+    let stored = match streamed {
+        Ok(fields) => upload.finish().await.map(|()| fields).map_err(AppError::Internal),
+        Err(e) => {
+            upload.abort().await;
+            Err(e)
+        }
+    };
+    let fields = match stored {
+        Ok(fields) => fields,
+        Err(e) => {
+            if let Err(cleanup) = state.document_repo.delete(&doc.id).await {
+                tracing::error!("Failed to remove record of aborted upload {}: {cleanup:#}", doc.id);
+            }
+            return Err(e);
+        }
+    };
+
+    state
+        .document_repo
+        .update_size(&doc.id, size_bytes as i64)
+        .await?;
+
+    tracing::info!("Document {}: uploaded {size_bytes} bytes to MinIO", doc.id);
+
+    if !fields.tags.is_empty() {
+        state.document_repo.update_tags(&doc.id, &fields.tags).await?;
+    }
+    if let Some(ref collection_id) = fields.collection_id {
+        state
+            .document_repo
+            .update_collection(&doc.id, Some(collection_id))
+            .await?;
+    }
+
+    if !query.new_document
+        && let Some(previous) = state
+            .document_repo
+            .find_latest_version(&claims.sub, &original_filename, &doc.id)
+            .await?
+    {
+        state.document_repo.set_previous_version(&doc.id, &previous.id).await?;
+        state
+            .document_events
+            .info(&doc.id, format!("Uploaded as a new version of {}", previous.id));
+    }
 
     // Update status to processing
     state
@@ -136,64 +242,23 @@ pub async fn upload(
         .update_status(&doc.id, &DocumentStatus::Processing, None)
         .await?;
 
-    // Spawn background processing task
-    let doc_id = doc.id.clone();
-    let doc_repo = state.document_repo.clone();
-    let storage_clone = state.storage.clone();
-    let vector_service = state.vector_service.clone();
-    let chunk_repo = state.chunk_repo.clone();
-    let embedding_model = state.config.llm.default_embedding_model.clone();
-    let file_content_type = content_type.clone();
-    let file_name = original_filename.clone();
-
-    tracing::info!("Document {}: spawning background processing task", doc.id);
-
-    tokio::spawn(async move {
-        tracing::info!("Document {doc_id}: background task started");
-        let result = std::panic::AssertUnwindSafe(process_document(
-            &storage_clone,
-            &key,
-            &doc_id,
-            &file_content_type,
-            &file_name,
-            &vector_service,
-            &chunk_repo,
-            &embedding_provider,
-            &embedding_model,
-            &api_key,
-        ))
-        .catch_unwind()
-        .await;
-
-        match result {
-            Ok(Ok(())) => {
-                let _ = doc_repo
-                    .update_status(&doc_id, &DocumentStatus::Ready, None)
-                    .await;
-                tracing::info!("Document {doc_id} processed successfully");
-            }
-            Ok(Err(e)) => {
-                let msg = format!("{e:#}");
-                let _ = doc_repo
-                    .update_status(&doc_id, &DocumentStatus::Failed, Some(&msg))
-                    .await;
-                tracing::error!("Document {doc_id} processing failed: {msg}");
-            }
-            Err(_panic) => {
-                let _ = doc_repo
-                    .update_status(
-                        &doc_id,
-                        &DocumentStatus::Failed,
-                        Some("Internal error: document processing panicked"),
-                    )
-                    .await;
-                tracing::error!("Document {doc_id} processing panicked");
-            }
-        }
-    });
+    // Queue embedding; the worker moves the document to ready/failed
+    let job = JobPayload::DocumentEmbedding {
+        document_id: doc.id.clone(),
+        user_id: claims.sub.clone(),
+        embedding_provider,
+        embedding_model,
+    };
+    if let Err(e) = state.jobs.enqueue(&job).await {
+        let _ = state
+            .document_repo
+            .update_status(&doc.id, &DocumentStatus::Failed, Some("Failed to queue processing"))
+            .await;
+        return Err(AppError::Internal(e));
+    }
 
     audit::log(
-        &state.audit_log_repo,
+        &state.audit,
         Some(&claims.sub),
         "document.upload",
         Some("document"),
@@ -213,22 +278,113 @@ pub async fn upload(
     Ok(Json(updated_doc.into()))
 }
 
-#[cfg_attr(feature = "openapi", utoipa::path(get, path = "/api/documents", tag = "Documents", security(("bearer_auth" = [])), responses((status = 200, body = Vec<DocumentResponse>))))]
+/// Most tags a document can carry.
+const MAX_TAGS: usize = 20;
+/// Longest tag, in characters.
+const MAX_TAG_CHARS: usize = 50;
+
+/// Trim, lowercase and deduplicate tags, splitting comma-separated values.
+/// Empty entries are dropped.
+pub(crate) fn normalize_tags(raw: &[String]) -> Result<Vec<String>, AppError> {
+    let mut tags: Vec<String> = Vec::new();
+    for tag in raw.iter().flat_map(|t| t.split(',')) {
+        let tag = tag.trim().to_lowercase();
+        if tag.is_empty() || tags.contains(&tag) {
+            continue;
+        }
+        if tag.chars().count() > MAX_TAG_CHARS {
+            return Err(AppError::Validation(format!(
+                "Tag '{tag}' is longer than {MAX_TAG_CHARS} characters"
+            )));
+        }
+        tags.push(tag);
+    }
+    if tags.len() > MAX_TAGS {
+        return Err(AppError::Validation(format!(
+            "A document can have at most {MAX_TAGS} tags"
+        )));
+    }
+    Ok(tags)
+}
+
+async fn read_text_field(field: Field<'_>, max_upload_mb: usize) -> Result<String, AppError> {
+    field.text().await.map_err(|e| multipart_error(e, max_upload_mb))
+}
+
+/// Form fields sent alongside an uploaded file.
+#[derive(Default)]
+struct UploadFields {
+    tags: Vec<String>,
+    collection_id: Option<String>,
+}
+
+impl UploadFields {
+    const NAMES: &[&str] = &["tags", "collection_id"];
+
+    async fn read(&mut self, field: Field<'_>, max_upload_mb: usize) -> Result<(), AppError> {
+        match field.name() {
+            Some("tags") => self.tags.push(read_text_field(field, max_upload_mb).await?),
+            Some("collection_id") => {
+                let id = read_text_field(field, max_upload_mb).await?;
+                self.collection_id = Some(id.trim().to_string()).filter(|id| !id.is_empty());
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Read the fields sent after the file, ignoring unknown ones.
+    async fn read_remaining(&mut self, multipart: &mut Multipart, max_upload_mb: usize) -> Result<(), AppError> {
+        while let Some(field) = multipart
+            .next_field()
+            .await
+            .map_err(|e| multipart_error(e, max_upload_mb))?
+        {
+            if field.file_name().is_none() {
+                self.read(field, max_upload_mb).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn validate(self, state: &AppState) -> Result<Self, AppError> {
+        require_collection(state, self.collection_id.as_deref()).await?;
+        Ok(Self {
+            tags: normalize_tags(&self.tags)?,
+            collection_id: self.collection_id,
+        })
+    }
+}
+
+#[derive(Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams))]
+pub struct ListDocumentsQuery {
+    /// Only list documents carrying this tag.
+    pub tag: Option<String>,
+}
+
+#[cfg_attr(feature = "openapi", utoipa::path(get, path = "/api/documents", tag = "Documents", security(("bearer_auth" = [])), params(ListDocumentsQuery), responses((status = 200, body = Vec<DocumentResponse>))))]
 pub async fn list(
     State(state): State<AppState>,
     claims: Claims,
+    Query(query): Query<ListDocumentsQuery>,
 ) -> Result<Json<Vec<DocumentResponse>>, AppError> {
     require_maintainer(&claims)?;
-    let docs = state.document_repo.find_by_user(&claims.sub).await?;
+    let tag = query
+        .tag
+        .map(|t| t.trim().to_lowercase())
+        .filter(|t| !t.is_empty());
+    let docs = state.document_repo.find_by_user(&claims.sub, tag.as_deref()).await?;
     Ok(Json(docs.into_iter().map(|d| d.into()).collect()))
 }
 
-#[cfg_attr(feature = "openapi", utoipa::path(get, path = "/api/documents/{id}", tag = "Documents", security(("bearer_auth" = [])), params(("id" = String, Path, description = "Document ID")), responses((status = 200, body = DocumentResponse))))]
+/// A document with its earlier versions.
+#[cfg_attr(feature = "openapi", utoipa::path(get, path = "/api/documents/{id}", tag = "Documents", security(("bearer_auth" = [])), params(("id" = String, Path, description = "Document ID")), responses((status = 200, body = DocumentDetailResponse))))]
 pub async fn get_document(
     State(state): State<AppState>,
     claims: Claims,
     Path(id): Path<String>,
-) -> Result<Json<DocumentResponse>, AppError> {
+) -> Result<Json<DocumentDetailResponse>, AppError> {
     require_maintainer(&claims)?;
     let doc = state
         .document_repo
@@ -236,11 +392,102 @@ pub async fn get_document(
         .await?
         .ok_or_else(|| AppError::NotFound("Document not found".to_string()))?;
 
-    if doc.user_id != claims.sub && claims.role != "admin" {
-        return Err(AppError::Forbidden);
-    }
+    ensure_owner_or_admin(&doc.user_id, &claims)?;
+
+    let versions = state.document_repo.find_previous_versions(&doc.id).await?;
+    let chunk_count = state.chunk_repo.count_by_source("document", &doc.id).await?;
+    Ok(Json(DocumentDetailResponse {
+        document: doc.into(),
+        versions: versions.into_iter().map(Into::into).collect(),
+        chunk_count,
+    }))
+}
+
+#[derive(Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams))]
+pub struct ListChunksQuery {
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+}
+
+/// The chunks stored for a document, in order, to check what retrieval can
+/// actually find.
+#[cfg_attr(feature = "openapi", utoipa::path(get, path = "/api/documents/{id}/chunks", tag = "Documents", security(("bearer_auth" = [])), params(("id" = String, Path, description = "Document ID"), ListChunksQuery), responses((status = 200, body = DocumentChunksResponse))))]
+pub async fn list_chunks(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(id): Path<String>,
+    Query(query): Query<ListChunksQuery>,
+) -> Result<Json<DocumentChunksResponse>, AppError> {
+    require_maintainer(&claims)?;
+    let doc = state
+        .document_repo
+        .find_by_id(&id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Document not found".to_string()))?;
+
+    ensure_owner_or_admin(&doc.user_id, &claims)?;
+
+    let page = query.page.unwrap_or(1).max(1);
+    let per_page = query.per_page.unwrap_or(20).clamp(1, 100);
+    let offset = (page - 1) * per_page;
+
+    let total = state.chunk_repo.count_by_source("document", &doc.id).await?;
+    let chunks = state
+        .chunk_repo
+        .find_by_source_paged("document", &doc.id, per_page, offset)
+        .await?;
+
+    Ok(Json(DocumentChunksResponse {
+        chunks: chunks.into_iter().map(Into::into).collect(),
+        total,
+        page,
+        per_page,
+    }))
+}
+
+#[cfg_attr(feature = "openapi", utoipa::path(get, path = "/api/documents/{id}/chunks/{chunk_index}", tag = "Documents", security(("bearer_auth" = [])), params(("id" = String, Path, description = "Document ID"), ("chunk_index" = i32, Path, description = "Position of the chunk, from 0")), responses((status = 200, body = DocumentChunkResponse), (status = 404, description = "Document or chunk not found"))))]
+pub async fn get_chunk(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path((id, chunk_index)): Path<(String, i32)>,
+) -> Result<Json<DocumentChunkResponse>, AppError> {
+    require_maintainer(&claims)?;
+    let doc = state
+        .document_repo
+        .find_by_id(&id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Document not found".to_string()))?;
+
+    ensure_owner_or_admin(&doc.user_id, &claims)?;
+
+    let chunk = state
+        .chunk_repo
+        .find_by_index("document", &doc.id, chunk_index)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Chunk not found".to_string()))?;
+    Ok(Json(chunk.into()))
+}
+
+/// The document's processing log, oldest first. Only the most recent events
+/// are kept.
+#[cfg_attr(feature = "openapi", utoipa::path(get, path = "/api/documents/{id}/events", tag = "Documents", security(("bearer_auth" = [])), params(("id" = String, Path, description = "Document ID")), responses((status = 200, body = Vec<DocumentEvent>))))]
+pub async fn list_events(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<DocumentEvent>>, AppError> {
+    require_maintainer(&claims)?;
+    let doc = state
+        .document_repo
+        .find_by_id(&id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Document not found".to_string()))?;
 
-    Ok(Json(doc.into()))
+    ensure_owner_or_admin(&doc.user_id, &claims)?;
+
+    let events = state.document_event_repo.list(&doc.id).await?;
+    Ok(Json(events))
 }
 
 #[cfg_attr(feature = "openapi", utoipa::path(delete, path = "/api/documents/{id}", tag = "Documents", security(("bearer_auth" = [])), params(("id" = String, Path, description = "Document ID")), responses((status = 204))))]
@@ -256,17 +503,10 @@ pub async fn delete_document(
         .await?
         .ok_or_else(|| AppError::NotFound("Document not found".to_string()))?;
 
-    if doc.user_id != claims.sub && claims.role != "admin" {
-        return Err(AppError::Forbidden);
-    }
+    ensure_owner_or_admin(&doc.user_id, &claims)?;
 
     // Delete vectors from Qdrant and chunk records
-    let point_ids = state.chunk_repo.delete_by_source("document", &id).await?;
-    if !point_ids.is_empty() {
-        if let Err(e) = state.vector_service.delete_points(point_ids).await {
-            tracing::error!("Failed to delete vectors for document {id}: {e}");
-        }
-    }
+    delete_document_chunks(&state.vector_cleanup, &state.chunk_repo, &doc).await?;
 
     // Delete from MinIO (skip if key was never set)
     if !doc.minio_key.is_empty() {
@@ -274,14 +514,14 @@ pub async fn delete_document(
             .storage
             .delete(&doc.minio_key)
             .await
-            .map_err(|e| AppError::Internal(e))?;
+            .map_err(AppError::Internal)?;
     }
 
     // Delete record
     state.document_repo.delete(&id).await?;
 
     audit::log(
-        &state.audit_log_repo,
+        &state.audit,
         Some(&claims.sub),
         "document.delete",
         Some("document"),
@@ -294,77 +534,552 @@ pub async fn delete_document(
     Ok(axum::http::StatusCode::NO_CONTENT)
 }
 
-/// Rescan all documents: re-extract, re-chunk, and re-embed into the vector database.
-#[cfg_attr(feature = "openapi", utoipa::path(post, path = "/api/documents/rescan", tag = "Documents", security(("bearer_auth" = [])), responses((status = 200, description = "Rescan started"))))]
-pub async fn rescan(
+#[derive(Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UpdateTagsRequest {
+    pub tags: Vec<String>,
+}
+
+/// Replace a document's tags.
+#[cfg_attr(feature = "openapi", utoipa::path(put, path = "/api/documents/{id}/tags", tag = "Documents", security(("bearer_auth" = [])), params(("id" = String, Path, description = "Document ID")), request_body = UpdateTagsRequest, responses((status = 200, body = DocumentResponse))))]
+pub async fn update_tags(
     State(state): State<AppState>,
     claims: Claims,
-) -> Result<Json<serde_json::Value>, AppError> {
-    require_admin(&claims)?;
+    Path(id): Path<String>,
+    Json(payload): Json<UpdateTagsRequest>,
+) -> Result<Json<DocumentResponse>, AppError> {
+    require_maintainer(&claims)?;
+    let doc = state
+        .document_repo
+        .find_by_id(&id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Document not found".to_string()))?;
 
-    // Require an embedding API key before rescanning
-    let embedding_provider = state.config.llm.default_provider.clone();
-    let api_key = state
-        .settings_repo
-        .get_api_key(&claims.sub, &embedding_provider)
-        .await
-        .ok()
-        .flatten()
-        .unwrap_or_default();
+    ensure_owner_or_admin(&doc.user_id, &claims)?;
+
+    let tags = normalize_tags(&payload.tags)?;
+    state.document_repo.update_tags(&id, &tags).await?;
+
+    relabel_chunks(&state, &id).await?;
+
+    audit::log(
+        &state.audit,
+        Some(&claims.sub),
+        "document.update_tags",
+        Some("document"),
+        Some(&id),
+        &format!("Set tags of '{}' to [{}]", doc.original_filename, tags.join(", ")),
+        None,
+        None,
+    );
+
+    let updated_doc = state
+        .document_repo
+        .find_by_id(&id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Document not found".to_string()))?;
+
+    Ok(Json(updated_doc.into()))
+}
+
+#[derive(Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SetCollectionRequest {
+    /// `null` takes the document out of its collection.
+    pub collection_id: Option<String>,
+}
+
+/// File a document in a collection, or take it out of one.
+#[cfg_attr(feature = "openapi", utoipa::path(put, path = "/api/documents/{id}/collection", tag = "Documents", security(("bearer_auth" = [])), params(("id" = String, Path, description = "Document ID")), request_body = SetCollectionRequest, responses((status = 200, body = DocumentResponse))))]
+pub async fn set_collection(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(id): Path<String>,
+    Json(payload): Json<SetCollectionRequest>,
+) -> Result<Json<DocumentResponse>, AppError> {
+    require_maintainer(&claims)?;
+    let doc = state
+        .document_repo
+        .find_by_id(&id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Document not found".to_string()))?;
+
+    ensure_owner_or_admin(&doc.user_id, &claims)?;
+
+    let collection_id = payload.collection_id.filter(|c| !c.is_empty());
+    require_collection(&state, collection_id.as_deref()).await?;
+    state
+        .document_repo
+        .update_collection(&id, collection_id.as_deref())
+        .await?;
+    relabel_chunks(&state, &id).await?;
+
+    audit::log(
+        &state.audit,
+        Some(&claims.sub),
+        "document.set_collection",
+        Some("document"),
+        Some(&id),
+        &match collection_id {
+            Some(ref c) => format!("Moved '{}' to collection {c}", doc.original_filename),
+            None => format!("Removed '{}' from its collection", doc.original_filename),
+        },
+        None,
+        None,
+    );
+
+    let updated_doc = state
+        .document_repo
+        .find_by_id(&id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Document not found".to_string()))?;
+
+    Ok(Json(updated_doc.into()))
+}
+
+/// Most documents one bulk request may name.
+const MAX_BULK_IDS: usize = 500;
+
+/// MinIO deletions a bulk delete runs at once.
+const BULK_STORAGE_CONCURRENCY: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum BulkAction {
+    Delete,
+    Retag,
+}
+
+#[derive(Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct BulkDocumentsRequest {
+    pub action: BulkAction,
+    pub ids: Vec<String>,
+    /// The new tags for `retag`, replacing each document's current ones.
+    pub tags: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum BulkItemStatus {
+    Ok,
+    NotFound,
+    Forbidden,
+    Error,
+}
+
+#[derive(Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct BulkItemResult {
+    pub id: String,
+    pub status: BulkItemStatus,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct BulkDocumentsResponse {
+    /// One result per requested ID, in request order.
+    pub results: Vec<BulkItemResult>,
+}
+
+/// Delete or re-tag several documents at once. Every ID is checked before
+/// anything changes; IDs that don't exist or belong to someone else are
+/// reported and skipped rather than failing the request.
+#[cfg_attr(feature = "openapi", utoipa::path(post, path = "/api/documents/bulk", tag = "Documents", security(("bearer_auth" = [])), request_body = BulkDocumentsRequest, responses((status = 200, body = BulkDocumentsResponse))))]
+pub async fn bulk(
+    State(state): State<AppState>,
+    claims: Claims,
+    Json(payload): Json<BulkDocumentsRequest>,
+) -> Result<Json<BulkDocumentsResponse>, AppError> {
+    require_maintainer(&claims)?;
 
-    if api_key.is_empty() {
+    let mut ids = payload.ids;
+    let mut seen = std::collections::HashSet::new();
+    ids.retain(|id| seen.insert(id.clone()));
+    if ids.is_empty() {
+        return Err(AppError::Validation("No document IDs given".to_string()));
+    }
+    if ids.len() > MAX_BULK_IDS {
         return Err(AppError::Validation(format!(
-            "No API key configured for embedding provider '{}'. Add one in Settings before rescanning.",
-            embedding_provider
+            "At most {MAX_BULK_IDS} documents can be changed at once"
         )));
     }
+    let tags = match (payload.action, payload.tags) {
+        (BulkAction::Retag, Some(tags)) => normalize_tags(&tags)?,
+        (BulkAction::Retag, None) => {
+            return Err(AppError::Validation("Tags are required to retag documents".to_string()));
+        }
+        (BulkAction::Delete, _) => Vec::new(),
+    };
 
-    let docs = state.document_repo.find_all_ready().await?;
-    let total = docs.len();
+    let mut docs: HashMap<String, Document> = state
+        .document_repo
+        .find_by_ids(&ids)
+        .await?
+        .into_iter()
+        .map(|d| (d.id.clone(), d))
+        .collect();
+
+    let mut results: HashMap<String, BulkItemResult> = HashMap::new();
+    let mut allowed = Vec::new();
+    for id in &ids {
+        let status = match docs.remove(id) {
+            None => BulkItemStatus::NotFound,
+            Some(doc) if ensure_owner_or_admin(&doc.user_id, &claims).is_err() => BulkItemStatus::Forbidden,
+            Some(doc) => {
+                allowed.push(doc);
+                continue;
+            }
+        };
+        results.insert(id.clone(), BulkItemResult { id: id.clone(), status, error: None });
+    }
 
-    let vector_service = state.vector_service.clone();
-    let chunk_repo = state.chunk_repo.clone();
-    let storage = state.storage.clone();
-    let embedding_model = state.config.llm.default_embedding_model.clone();
+    let outcomes = match payload.action {
+        BulkAction::Delete => bulk_delete(&state, allowed).await,
+        BulkAction::Retag => bulk_retag(&state, allowed, &tags).await,
+    };
+    let mut changed = Vec::new();
+    for (id, outcome) in outcomes {
+        let (status, error) = match outcome {
+            Ok(()) => {
+                changed.push(id.clone());
+                (BulkItemStatus::Ok, None)
+            }
+            Err(e) => {
+                tracing::error!("Bulk {:?} failed for document {id}: {e:#}", payload.action);
+                (BulkItemStatus::Error, Some(format!("{e:#}")))
+            }
+        };
+        results.insert(id.clone(), BulkItemResult { id, status, error });
+    }
 
-    tokio::spawn(async move {
-        tracing::info!("Starting rescan of {total} documents");
+    if !changed.is_empty() {
+        let (event_type, description) = match payload.action {
+            BulkAction::Delete => ("document.bulk_delete", format!("Deleted {} documents", changed.len())),
+            BulkAction::Retag => (
+                "document.bulk_update_tags",
+                format!("Set tags of {} documents to [{}]", changed.len(), tags.join(", ")),
+            ),
+        };
+        audit::log(
+            &state.audit,
+            Some(&claims.sub),
+            event_type,
+            Some("document"),
+            None,
+            &description,
+            None,
+            Some(serde_json::json!({ "ids": changed })),
+        );
+    }
 
-        for doc in docs {
-            // Delete existing chunks for this document
-            let old_point_ids = chunk_repo.delete_by_source("document", &doc.id).await.unwrap_or_default();
-            if !old_point_ids.is_empty() {
-                let _ = vector_service.delete_points(old_point_ids).await;
-            }
+    let results = ids.iter().filter_map(|id| results.remove(id)).collect();
+    Ok(Json(BulkDocumentsResponse { results }))
+}
 
-            // Re-process
-            if let Err(e) = process_document(
-                &storage,
-                &doc.minio_key,
-                &doc.id,
-                &doc.content_type,
-                &doc.original_filename,
-                &vector_service,
-                &chunk_repo,
-                &embedding_provider,
-                &embedding_model,
-                &api_key,
-            )
-            .await
-            {
-                tracing::error!("Rescan failed for document {}: {e:#}", doc.id);
+/// Delete documents with one Qdrant request per collection for all their
+/// vectors and a bounded number of MinIO deletions at a time. A document whose
+/// file can't be deleted keeps its record, like a single delete.
+async fn bulk_delete(state: &AppState, docs: Vec<Document>) -> Vec<(String, anyhow::Result<()>)> {
+    let mut outcomes = Vec::with_capacity(docs.len());
+    let mut points_by_collection: HashMap<String, Vec<String>> = HashMap::new();
+    let mut cleared = Vec::with_capacity(docs.len());
+    for doc in docs {
+        match state.chunk_repo.delete_by_source("document", &doc.id).await {
+            Ok(point_ids) => {
+                let collection = doc
+                    .vector_collection
+                    .clone()
+                    .unwrap_or_else(|| state.vector_service.default_collection().to_string());
+                points_by_collection.entry(collection).or_default().extend(point_ids);
+                cleared.push(doc);
             }
+            Err(e) => outcomes.push((doc.id, Err(e))),
         }
+    }
 
-        tracing::info!("Rescan completed for {total} documents");
-    });
+    // The chunk rows are gone, so vectors Qdrant doesn't delete are queued for a retry
+    for (collection, point_ids) in points_by_collection {
+        let count = point_ids.len();
+        if let Err(e) = state.vector_cleanup.delete_points(&collection, point_ids).await {
+            tracing::error!(
+                "Failed to delete {count} vectors of bulk-deleted documents from '{collection}', queued for retry: {e:#}"
+            );
+        }
+    }
+
+    let mut files = tokio::task::JoinSet::new();
+    let mut task_docs = HashMap::new();
+    let mut pending = cleared.into_iter();
+    loop {
+        while files.len() < BULK_STORAGE_CONCURRENCY {
+            let Some(doc) = pending.next() else { break };
+            let storage = state.storage.clone();
+            let task = files.spawn(async move {
+                if doc.minio_key.is_empty() {
+                    Ok(())
+                } else {
+                    storage.delete(&doc.minio_key).await
+                }
+            });
+            task_docs.insert(task.id(), doc.id);
+        }
+        let Some(joined) = files.join_next_with_id().await else { break };
+        let (task, result) = match joined {
+            Ok((task, result)) => (task, result),
+            Err(e) => (e.id(), Err(anyhow::anyhow!("File deletion failed: {e}"))),
+        };
+        let Some(id) = task_docs.remove(&task) else { continue };
+        let result = match result {
+            Ok(()) => state.document_repo.delete(&id).await,
+            Err(e) => Err(e),
+        };
+        outcomes.push((id, result));
+    }
+
+    outcomes
+}
+
+async fn bulk_retag(state: &AppState, docs: Vec<Document>, tags: &[String]) -> Vec<(String, anyhow::Result<()>)> {
+    let mut outcomes = Vec::with_capacity(docs.len());
+    for doc in docs {
+        let result = match state.document_repo.update_tags(&doc.id, tags).await {
+            Ok(()) => relabel_chunks(state, &doc.id).await.map_err(anyhow::Error::from),
+            Err(e) => Err(e),
+        };
+        outcomes.push((doc.id, result));
+    }
+    outcomes
+}
+
+/// Copy a document's current tags and collection onto its chunks in Qdrant, so
+/// filtered search sees the change without re-embedding. A Qdrant failure is
+/// logged; the next reprocess writes the labels again.
+async fn relabel_chunks(state: &AppState, id: &str) -> Result<(), AppError> {
+    let doc = state
+        .document_repo
+        .find_by_id(id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Document not found".to_string()))?;
+    let point_ids: Vec<String> = state
+        .chunk_repo
+        .find_by_source("document", id)
+        .await?
+        .into_iter()
+        .map(|c| c.qdrant_point_id)
+        .collect();
+    let collection = doc
+        .vector_collection
+        .clone()
+        .unwrap_or_else(|| state.vector_service.default_collection().to_string());
+    if let Err(e) = state
+        .vector_service
+        .set_labels(&collection, point_ids, &chunk_labels(&doc))
+        .await
+    {
+        tracing::error!("Failed to relabel vectors of document {id}: {e:#}");
+    }
+    Ok(())
+}
+
+/// Queue a document for processing again. A failed document resumes after the
+/// chunks its last attempt stored if it is still on the same embedding model;
+/// otherwise its chunks are cleared and it is embedded from scratch.
+#[cfg_attr(feature = "openapi", utoipa::path(post, path = "/api/documents/{id}/reprocess", tag = "Documents", security(("bearer_auth" = [])), params(("id" = String, Path, description = "Document ID")), responses((status = 200, body = DocumentResponse))))]
+pub async fn reprocess(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(id): Path<String>,
+) -> Result<Json<DocumentResponse>, AppError> {
+    require_maintainer(&claims)?;
+    let doc = state
+        .document_repo
+        .find_by_id(&id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Document not found".to_string()))?;
+
+    ensure_owner_or_admin(&doc.user_id, &claims)?;
+    if doc.status == DocumentStatus::Processing {
+        return Err(AppError::Validation(
+            "Document is already being processed".to_string(),
+        ));
+    }
+    if doc.status == DocumentStatus::Superseded {
+        return Err(AppError::Validation(
+            "Document has been replaced by a newer version".to_string(),
+        ));
+    }
+
+    let embedding = state.embedding.resolve_for_user(&claims.sub).await?;
+    let api_key = embedding.api_key();
+    let EmbeddingTarget {
+        provider: embedding_provider,
+        model: embedding_model,
+    } = embedding.target;
+
+    require_embedding_key(&embedding_provider, &api_key, "reprocessing")?;
+
+    // Only a failed run on the same model can be resumed
+    if doc.status == DocumentStatus::Ready
+        || doc.embedding_model.as_deref() != Some(embedding_model.as_str())
+    {
+        delete_document_chunks(&state.vector_cleanup, &state.chunk_repo, &doc).await?;
+    }
+
+    state
+        .document_repo
+        .update_status(&doc.id, &DocumentStatus::Processing, None)
+        .await?;
+
+    let job = JobPayload::DocumentEmbedding {
+        document_id: doc.id.clone(),
+        user_id: claims.sub.clone(),
+        embedding_provider,
+        embedding_model,
+    };
+    if let Err(e) = state.jobs.enqueue(&job).await {
+        let _ = state
+            .document_repo
+            .update_status(&doc.id, &DocumentStatus::Failed, Some("Failed to queue processing"))
+            .await;
+        return Err(AppError::Internal(e));
+    }
 
     audit::log(
-        &state.audit_log_repo,
+        &state.audit,
         Some(&claims.sub),
-        "document.rescan",
+        "document.reprocess",
+        Some("document"),
+        Some(&doc.id),
+        &format!("Reprocessing document '{}'", doc.original_filename),
         None,
         None,
+    );
+
+    let updated_doc = state
+        .document_repo
+        .find_by_id(&doc.id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Document not found".to_string()))?;
+
+    Ok(Json(updated_doc.into()))
+}
+
+#[derive(Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams))]
+pub struct RescanQuery {
+    /// Embedding provider to re-embed with (defaults to the configured provider).
+    pub embedding_provider: Option<String>,
+    /// Embedding model to re-embed with (defaults to the configured model).
+    pub embedding_model: Option<String>,
+    /// Re-embed documents whose text and chunking haven't changed too.
+    pub force: Option<bool>,
+}
+
+/// Optional body of a rescan; its fields take precedence over the query parameters.
+#[derive(Debug, Default, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct RescanRequest {
+    pub embedding_provider: Option<String>,
+    pub embedding_model: Option<String>,
+    /// Spend only the admin's own key (`user`) or only the organization key
+    /// (`organization`). By default the admin's key is used if they have one.
+    pub key_source: Option<KeySource>,
+    pub force: Option<bool>,
+}
+
+/// Rescan all documents: re-extract, re-chunk, and re-embed into the vector database.
+/// Passing a different embedding model migrates every document into that model's collection.
+/// A document whose extracted text and chunking parameters are unchanged since
+/// it was embedded with the target model is skipped, unless `force` is set.
+///
+/// Documents are processed one at a time: each is `processing` while it's
+/// re-embedded, then `ready` or `failed` with the reason, and a failure doesn't
+/// stop the rest. `GET /api/admin/rescan/status` reports progress.
+#[cfg_attr(feature = "openapi", utoipa::path(post, path = "/api/documents/rescan", tag = "Documents", security(("bearer_auth" = [])), params(RescanQuery), request_body(content = Option<RescanRequest>), responses((status = 200, description = "Rescan started"), (status = 409, description = "A rescan is already running"))))]
+pub async fn rescan(
+    State(state): State<AppState>,
+    claims: Claims,
+    Query(query): Query<RescanQuery>,
+    body: Option<Json<RescanRequest>>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    require_admin(&claims)?;
+    let Json(body) = body.unwrap_or_default();
+
+    // Rescans re-embed every document, so start from the organization default
+    // rather than the admin's personal preference.
+    let default_target = state.embedding.default_target().await?;
+    let target = EmbeddingTarget {
+        provider: body
+            .embedding_provider
+            .or(query.embedding_provider)
+            .filter(|p| !p.is_empty())
+            .unwrap_or(default_target.provider),
+        model: body
+            .embedding_model
+            .or(query.embedding_model)
+            .filter(|m| !m.is_empty())
+            .unwrap_or(default_target.model),
+    };
+
+    // Require an embedding API key before rescanning
+    let credentials = match body.key_source {
+        None => state.embedding.credentials_for(Some(&claims.sub), &target).await?,
+        Some(KeySource::Keyless) => {
+            return Err(AppError::Validation(
+                "key_source must be 'user' or 'organization'".to_string(),
+            ));
+        }
+        Some(source) => {
+            let resolved = state
+                .credentials
+                .resolve_from(&claims.sub, &target.provider, source)
+                .await?
+                .ok_or_else(|| {
+                    let whose = if source == KeySource::User { "You have no" } else { "There is no organization" };
+                    AppError::Validation(format!(
+                        "{whose} API key for embedding provider '{}'",
+                        target.provider
+                    ))
+                })?;
+            Some(resolved.credentials)
+        }
+    };
+    let api_key = credentials
+        .as_ref()
+        .map(|c| c.api_key.clone())
+        .unwrap_or_default();
+    require_embedding_key(&target.provider, &api_key, "rescanning")?;
+
+    let docs = state.document_repo.find_all_ready().await?;
+    let total = docs.len();
+    let run = state
+        .rescan_run_repo
+        .start(&claims.sub, &target.provider, &target.model, total, state.jobs.owner(), LEASE)
+        .await?
+        .ok_or_else(|| AppError::Conflict("A rescan is already running".to_string()))?;
+    let run_id = run.id.clone();
+
+    let force = body.force.or(query.force).unwrap_or(false);
+    let credentials = credentials.unwrap_or_default();
+    let background = state.clone();
+    state.tasks.spawn(async move {
+        let renew = || background.rescan_run_repo.renew_lease(&run.id, background.jobs.owner(), LEASE);
+        tokio::select! {
+            () = run_rescan(&background, &run, docs, &target, &credentials, force) => {}
+            never = keep_leased(format!("rescan {}", run.id), renew) => match never {},
+        }
+    });
+
+    audit::log(
+        &state.audit,
+        Some(&claims.sub),
+        "document.rescan",
+        Some("rescan"),
+        Some(&run_id),
         &format!("Started rescan of {total} documents"),
         None,
         None,
@@ -373,101 +1088,532 @@ pub async fn rescan(
     Ok(Json(serde_json::json!({
         "message": format!("Rescan started for {total} documents"),
         "total": total,
+        "run_id": run_id,
     })))
 }
 
-async fn process_document(
+/// Re-embed `docs` one at a time, recording each outcome on the document and
+/// the run, then log a summary. Unless `force` is set, a document already on
+/// the target model whose extraction fingerprint is unchanged is skipped. A
+/// shutdown ends the run as interrupted.
+async fn run_rescan(
+    state: &AppState,
+    run: &RescanRun,
+    docs: Vec<Document>,
+    target: &EmbeddingTarget,
+    credentials: &ProviderCredentials,
+    force: bool,
+) {
+    let (runs, doc_repo, events) = (&state.rescan_run_repo, &state.document_repo, &state.document_events);
+    let total = docs.len();
+    let max_table_rows = state.config.extraction.max_table_rows;
+    let ocr = state.config.features.ocr_enabled.then(|| state.config.ocr.clone());
+    tracing::info!("Starting rescan of {total} documents (model={}, force={force})", target.model);
+
+    let fail = async |doc: &Document, error: String| {
+        tracing::error!("Rescan failed for document {}: {error}", doc.id);
+        events.error(&doc.id, format!("Rescan failed: {error}"));
+        if let Err(e) = doc_repo.update_status(&doc.id, &DocumentStatus::Failed, Some(&error)).await {
+            tracing::error!("Failed to mark document {} failed: {e:#}", doc.id);
+        }
+        if let Err(e) = runs.record_failure(&run.id, &doc.id).await {
+            tracing::error!("Failed to record rescan failure of document {}: {e:#}", doc.id);
+        }
+    };
+
+    let mut status = "completed";
+    for (done, doc) in docs.into_iter().enumerate() {
+        if state.tasks.stopping().is_cancelled() {
+            tracing::warn!("Rescan stopped by shutdown after {done} of {total} documents");
+            status = "interrupted";
+            break;
+        }
+
+        // Extract first: if the text and chunking haven't changed since the
+        // document was embedded with this model, its chunks are still current
+        let extraction = extract_chunks(
+            &state.storage,
+            &doc.minio_key,
+            &doc.id,
+            &doc.content_type,
+            &doc.original_filename,
+            max_table_rows,
+            ocr.as_ref(),
+            events,
+        );
+        let extracted = match state.tasks.run_until_aborted(extraction).await {
+            Some(Ok(extracted)) => extracted,
+            Some(Err(e)) => {
+                // Its old chunks no longer match the file, so they go too
+                if let Err(e) = delete_document_chunks(&state.vector_cleanup, &state.chunk_repo, &doc).await {
+                    tracing::error!("Failed to clear chunks of document {}: {e:#}", doc.id);
+                }
+                fail(&doc, format!("{e:#}")).await;
+                continue;
+            }
+            None => {
+                // Nothing was changed yet, so the document keeps its chunks
+                tracing::warn!("Rescan aborted by shutdown at document {}", doc.id);
+                status = "interrupted";
+                break;
+            }
+        };
+        let unchanged = doc.embedding_model.as_deref() == Some(target.model.as_str())
+            && doc.extraction_fingerprint.as_deref() == Some(extracted.fingerprint.as_str());
+        if unchanged && !force {
+            tracing::info!("Rescan skipped document {}: text and chunking unchanged", doc.id);
+            events.info(&doc.id, "Rescan skipped: text and chunking unchanged");
+            if let Err(e) = runs.record_skip(&run.id).await {
+                tracing::error!("Failed to record skipped document {}: {e:#}", doc.id);
+            }
+            continue;
+        }
+
+        if let Err(e) = doc_repo.update_status(&doc.id, &DocumentStatus::Processing, None).await {
+            fail(&doc, format!("{e:#}")).await;
+            continue;
+        }
+        // Delete existing chunks for this document from whichever collection holds them
+        if let Err(e) = delete_document_chunks(&state.vector_cleanup, &state.chunk_repo, &doc).await {
+            fail(&doc, format!("Could not clear old chunks: {e:#}")).await;
+            continue;
+        }
+        events.info(&doc.id, format!("Rescanning with embedding model {}", target.model));
+
+        // Re-embed, keeping the document's tags and collection
+        let labels = chunk_labels(&doc);
+        let work = embed_chunks(
+            &extracted.chunks,
+            &doc.id,
+            &labels,
+            &state.vector_service,
+            &state.chunk_repo,
+            state.embedding_backend.as_ref(),
+            &target.provider,
+            &target.model,
+            &credentials.api_key,
+            credentials.base_url.as_deref(),
+            0,
+            events,
+        );
+        match state.tasks.run_until_aborted(work).await {
+            Some(Ok(collection)) => {
+                let saved = async {
+                    doc_repo.update_embedding(&doc.id, &target.model, &collection).await?;
+                    doc_repo.set_extraction_fingerprint(&doc.id, &extracted.fingerprint).await?;
+                    if !doc_repo.mark_ready(&doc.id).await? {
+                        // Superseded during the rescan; its new chunks must go too
+                        if let Some(doc) = doc_repo.find_by_id(&doc.id).await? {
+                            delete_document_chunks(&state.vector_cleanup, &state.chunk_repo, &doc).await?;
+                        }
+                    }
+                    anyhow::Ok(())
+                };
+                match saved.await {
+                    Ok(()) => {
+                        if let Err(e) = runs.record_success(&run.id).await {
+                            tracing::error!("Failed to record rescan of document {}: {e:#}", doc.id);
+                        }
+                    }
+                    Err(e) => fail(&doc, format!("{e:#}")).await,
+                }
+            }
+            Some(Err(e)) => fail(&doc, format!("{e:#}")).await,
+            None => {
+                // Its old chunks are already gone, so flag it for a retry
+                let _ = doc_repo
+                    .update_status(&doc.id, &DocumentStatus::Failed, Some(SHUTDOWN_ERROR))
+                    .await;
+                let _ = runs.record_failure(&run.id, &doc.id).await;
+                tracing::warn!("Rescan aborted by shutdown at document {}", doc.id);
+                status = "interrupted";
+                break;
+            }
+        }
+    }
+
+    let finished = match runs.finish(&run.id, status).await {
+        Ok(Some(finished)) => finished,
+        Ok(None) => return,
+        Err(e) => {
+            tracing::error!("Failed to finish rescan {}: {e:#}", run.id);
+            return;
+        }
+    };
+    let summary = format!(
+        "Rescan {status}: {} of {total} documents re-embedded, {} unchanged, {} failed",
+        finished.succeeded, finished.skipped, finished.failed
+    );
+    tracing::info!("{summary}");
+    audit::log(
+        &state.audit,
+        Some(&run.started_by),
+        "document.rescan.complete",
+        Some("rescan"),
+        Some(&run.id),
+        &summary,
+        None,
+        None,
+    );
+}
+
+/// The latest rescan's progress or outcome, with the documents that failed;
+/// `null` if no rescan has run.
+#[cfg_attr(feature = "openapi", utoipa::path(get, path = "/api/admin/rescan/status", tag = "Documents", security(("bearer_auth" = [])), responses((status = 200, body = Option<RescanRun>))))]
+pub async fn rescan_status(
+    State(state): State<AppState>,
+    claims: Claims,
+) -> Result<Json<Option<RescanRun>>, AppError> {
+    require_admin(&claims)?;
+    Ok(Json(state.rescan_run_repo.latest().await?))
+}
+
+/// Reject a request that needs embeddings when the user has no key for a provider
+/// that requires one. Key-less providers such as Ollama always pass.
+pub(crate) fn require_embedding_key(
+    embedding_provider: &str,
+    api_key: &str,
+    action: &str,
+) -> Result<(), AppError> {
+    if api_key.is_empty() && crate::services::llm_provider::requires_api_key(embedding_provider) {
+        return Err(AppError::Validation(format!(
+            "No API key configured for embedding provider '{embedding_provider}'. Add one in Settings before {action}."
+        )));
+    }
+    Ok(())
+}
+
+/// Remove a document's chunk rows and their vectors from whichever collection
+/// holds them. A Qdrant failure is logged and the vectors queued for a retry
+/// rather than returned, since the rows that point at them are already gone.
+pub(crate) async fn delete_document_chunks(
+    vector_cleanup: &VectorCleanup,
+    chunk_repo: &DocumentChunkRepository,
+    doc: &Document,
+) -> anyhow::Result<()> {
+    let collection = doc
+        .vector_collection
+        .clone()
+        .unwrap_or_else(|| vector_cleanup.vector_service().default_collection().to_string());
+    let point_ids = chunk_repo.delete_by_source("document", &doc.id).await?;
+    if let Err(e) = vector_cleanup.delete_points(&collection, point_ids).await {
+        tracing::error!("Failed to delete vectors for document {}, queued for retry: {e}", doc.id);
+    }
+    Ok(())
+}
+
+/// Supersede the earlier versions of a document that just became ready and
+/// delete their chunks, so retrieval only finds the new version.
+pub(crate) async fn supersede_previous_versions(state: &AppState, doc: &Document) -> anyhow::Result<()> {
+    if doc.previous_version_id.is_none() {
+        return Ok(());
+    }
+    for old in state.document_repo.supersede_previous_versions(&doc.id).await? {
+        delete_document_chunks(&state.vector_cleanup, &state.chunk_repo, &old).await?;
+        state.document_events.info(&old.id, format!("Superseded by newer version {}", doc.id));
+        tracing::info!("Document {} superseded by newer version {}", old.id, doc.id);
+    }
+    Ok(())
+}
+
+/// Labels copied from a document onto each of its chunks.
+pub(crate) fn chunk_labels(doc: &Document) -> ChunkLabels {
+    ChunkLabels {
+        tags: doc.tags.clone(),
+        collection_id: doc.collection_id.clone(),
+    }
+}
+
+/// A document's text split into chunks, with the fingerprint of the text and
+/// the chunking parameters that produced them.
+pub(crate) struct ExtractedChunks {
+    pub chunks: Vec<String>,
+    pub fingerprint: String,
+}
+
+/// What processing a document produced.
+pub(crate) struct ProcessedDocument {
+    /// The collection holding the document's vectors.
+    pub collection: String,
+    pub fingerprint: String,
+}
+
+/// Extract, chunk and embed a document, storing each batch as soon as it is
+/// embedded. `resume_from` skips chunks a previous attempt already stored.
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn process_document(
     storage: &StorageService,
     minio_key: &str,
     doc_id: &str,
     content_type: &str,
     filename: &str,
+    labels: &ChunkLabels,
     vector_service: &Arc<VectorService>,
     chunk_repo: &DocumentChunkRepository,
+    embedding_backend: &dyn EmbeddingBackend,
     embedding_provider: &str,
     embedding_model: &str,
     api_key: &str,
-) -> anyhow::Result<()> {
+    base_url: Option<&str>,
+    resume_from: usize,
+    max_table_rows: usize,
+    ocr: Option<&OcrConfig>,
+    events: &DocumentEventLog,
+) -> anyhow::Result<ProcessedDocument> {
+    let extracted =
+        extract_chunks(storage, minio_key, doc_id, content_type, filename, max_table_rows, ocr, events).await?;
+    let collection = embed_chunks(
+        &extracted.chunks,
+        doc_id,
+        labels,
+        vector_service,
+        chunk_repo,
+        embedding_backend,
+        embedding_provider,
+        embedding_model,
+        api_key,
+        base_url,
+        resume_from,
+        events,
+    )
+    .await?;
+    Ok(ProcessedDocument {
+        collection,
+        fingerprint: extracted.fingerprint,
+    })
+}
+
+/// Download a document, extract its text, running OCR on a scanned PDF if
+/// enabled, and chunk it.
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn extract_chunks(
+    storage: &StorageService,
+    minio_key: &str,
+    doc_id: &str,
+    content_type: &str,
+    filename: &str,
+    max_table_rows: usize,
+    ocr: Option<&OcrConfig>,
+    events: &DocumentEventLog,
+) -> anyhow::Result<ExtractedChunks> {
     tracing::info!("Document {doc_id}: downloading from MinIO (key={minio_key})");
     let file_bytes = storage.download(minio_key).await?;
     tracing::info!(
         "Document {doc_id}: downloaded {} bytes, extracting text (type={content_type}, file={filename})",
         file_bytes.len()
     );
-
-    let text = crate::services::text_extract::extract_text(&file_bytes, content_type, filename).await?;
+    events.info(doc_id, format!("Downloaded {} bytes", file_bytes.len()));
+
+    let mut text =
+        text_extract::extract_text(&file_bytes, content_type, filename, max_table_rows).await?;
+    let head = &file_bytes[..file_bytes.len().min(text_extract::SNIFF_LEN)];
+    let detected_type = text_extract::sniff_content_type(head, content_type, filename);
+
+    // Scanned PDFs have (almost) no text layer; OCR them or fail with a clear reason
+    if detected_type == "application/pdf" {
+        let pages = text_extract::pdf_page_count(&file_bytes).await.unwrap_or(1);
+        if text_extract::looks_like_scanned_pdf(&text, pages) {
+            let Some(ocr) = ocr else {
+                return Err(PermanentFailure(
+                    "PDF appears to be scanned images; no extractable text".to_string(),
+                )
+                .into());
+            };
+            tracing::info!("Document {doc_id}: {pages}-page PDF has no text layer, running OCR");
+            events.info(doc_id, format!("{pages}-page PDF has no text layer, running OCR"));
+            text = text_extract::ocr_pdf(&file_bytes, pages, ocr).await?;
+            if text_extract::looks_like_scanned_pdf(&text, pages.min(ocr.max_pages)) {
+                return Err(PermanentFailure(
+                    "PDF appears to be scanned images and OCR found no readable text".to_string(),
+                )
+                .into());
+            }
+        }
+    }
     tracing::info!(
         "Document {doc_id}: extracted {} chars of text, chunking...",
         text.len()
     );
+    events.info(doc_id, format!("Extracted {} characters of text", text.chars().count()));
+
+    // Tables are chunked by whole rows so a row's cells stay together
+    let tabular = text_extract::is_tabular(detected_type);
+    let chunks = if tabular {
+        text_extract::chunk_rows(&text, DEFAULT_CHUNK_SIZE)
+    } else {
+        chunking::chunk_text(&text, DEFAULT_CHUNK_SIZE, DEFAULT_CHUNK_OVERLAP)
+    };
+    let fingerprint = chunking::extraction_fingerprint(&text, DEFAULT_CHUNK_SIZE, DEFAULT_CHUNK_OVERLAP, tabular);
+    if !chunks.is_empty() {
+        events.info(doc_id, format!("Split into {} chunks", chunks.len()));
+    }
+
+    Ok(ExtractedChunks { chunks, fingerprint })
+}
 
-    let chunks = crate::services::text_extract::chunk_text(&text, 200, 30);
+/// Embed a document's chunks, storing each batch as soon as it is embedded.
+/// `resume_from` skips chunks a previous attempt already stored. Returns the
+/// collection holding the document's vectors.
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn embed_chunks(
+    chunks: &[String],
+    doc_id: &str,
+    labels: &ChunkLabels,
+    vector_service: &Arc<VectorService>,
+    chunk_repo: &DocumentChunkRepository,
+    embedding_backend: &dyn EmbeddingBackend,
+    embedding_provider: &str,
+    embedding_model: &str,
+    api_key: &str,
+    base_url: Option<&str>,
+    resume_from: usize,
+    events: &DocumentEventLog,
+) -> anyhow::Result<String> {
+    let collection = vector_service.collection_for_model(embedding_model);
 
     if chunks.is_empty() {
         tracing::warn!("Document {doc_id}: no text chunks produced — nothing to embed");
-        return Ok(());
+        events.warn(doc_id, "No text chunks produced, nothing to embed");
+        return Ok(collection);
     }
 
+    if resume_from > 0 {
+        tracing::info!(
+            "Document {doc_id}: resuming after {resume_from} of {} chunks already embedded",
+            chunks.len()
+        );
+        events.info(
+            doc_id,
+            format!("Resuming after {resume_from} of {} chunks already embedded", chunks.len()),
+        );
+    }
     tracing::info!("Document {doc_id}: produced {} chunks, starting embedding with provider={embedding_provider} model={embedding_model}", chunks.len());
 
-    if api_key.is_empty() {
+    if api_key.is_empty() && crate::services::llm_provider::requires_api_key(embedding_provider) {
         anyhow::bail!("No API key configured for embedding provider '{embedding_provider}'");
     }
 
-    let embeddings_client =
-        crate::services::llm_provider::create_embeddings_client(embedding_provider, api_key)?;
+    let embedder = embedding_backend.embedder(ModelRef {
+        provider: embedding_provider,
+        model: embedding_model,
+        api_key,
+        base_url,
+    })?;
+
+    embed_in_batches(
+        chunks,
+        resume_from,
+        EMBED_BATCH_SIZE,
+        RetryPolicy::default(),
+        |batch| embedder.embed_texts(batch),
+        |start, embeddings| {
+            let end = start + embeddings.len();
+            let batch = embeddings
+                .into_iter()
+                .enumerate()
+                .map(|(i, e)| ((start + i) as i32, chunks[start + i].clone(), e))
+                .collect();
+            let total = chunks.len();
+            async move {
+                store_chunk_batch(vector_service, chunk_repo, embedding_model, "document", doc_id, labels, batch)
+                    .await?;
+                events.info(doc_id, format!("Embedded and stored chunks {}-{end} of {total}", start + 1));
+                Ok(())
+            }
+        },
+    )
+    .await?;
 
-    let model = rig::client::embeddings::EmbeddingsClientDyn::embedding_model(
-        embeddings_client.as_ref(),
-        embedding_model,
+    tracing::info!(
+        "Document {doc_id}: embedded {} chunks into Qdrant collection '{collection}'",
+        chunks.len()
+    );
+    events.info(
+        doc_id,
+        format!("Upserted {} chunks into collection '{collection}'", chunks.len()),
     );
 
-    let batch_size = 100;
-    let total_batches = (chunks.len() + batch_size - 1) / batch_size;
-    let mut qdrant_data = Vec::with_capacity(chunks.len());
-    let mut db_data = Vec::with_capacity(chunks.len());
+    Ok(collection)
+}
 
-    for (batch_num, batch_start) in (0..chunks.len()).step_by(batch_size).enumerate() {
-        let batch_end = (batch_start + batch_size).min(chunks.len());
-        let batch: Vec<String> = chunks[batch_start..batch_end].to_vec();
+/// Upsert one embedded batch to Qdrant, with `labels` in each point's payload,
+/// and record its chunk rows. If the rows
+/// can't be written the batch's points are deleted again, so a retry never
+/// leaves vectors behind that no chunk row refers to.
+pub(crate) async fn store_chunk_batch(
+    vector_service: &VectorService,
+    chunk_repo: &DocumentChunkRepository,
+    embedding_model: &str,
+    source_type: &str,
+    source_id: &str,
+    labels: &ChunkLabels,
+    batch: Vec<(i32, String, Vec<f64>)>, // (chunk_index, content, embedding)
+) -> anyhow::Result<()> {
+    // The collection is created on demand with the dimension the model actually produced
+    let vector_size = batch.first().map(|(_, _, v)| v.len() as u64).unwrap_or_default();
+    let collection = vector_service
+        .ensure_model_collection(embedding_model, vector_size)
+        .await?;
 
-        tracing::info!(
-            "Document {doc_id}: embedding batch {}/{} ({} chunks)",
-            batch_num + 1,
-            total_batches,
-            batch.len()
-        );
+    let mut points = Vec::with_capacity(batch.len());
+    let mut rows = Vec::with_capacity(batch.len());
+    for (chunk_index, content, embedding) in batch {
+        let point_id = uuid::Uuid::new_v4().to_string();
+        points.push((point_id.clone(), embedding, content.clone()));
+        rows.push((
+            source_type.to_string(),
+            source_id.to_string(),
+            chunk_index,
+            content,
+            point_id,
+        ));
+    }
+    let point_ids: Vec<String> = points.iter().map(|(id, _, _)| id.clone()).collect();
 
-        let embeddings = model
-            .embed_texts(batch)
-            .await
-            .map_err(|e| anyhow::anyhow!("Embedding error on batch {}: {e}", batch_num + 1))?;
-
-        for (i, embedding) in embeddings.iter().enumerate() {
-            let global_idx = batch_start + i;
-            let point_id = uuid::Uuid::new_v4().to_string();
-
-            qdrant_data.push((point_id.clone(), embedding.vec.clone(), chunks[global_idx].clone()));
-            db_data.push((
-                "document".to_string(),
-                doc_id.to_string(),
-                global_idx as i32,
-                chunks[global_idx].clone(),
-                point_id,
-            ));
+    vector_service.upsert_chunks(&collection, points, labels).await?;
+    if let Err(e) = chunk_repo.create_batch(&rows).await {
+        if let Err(cleanup) = vector_service.delete_points(&collection, point_ids).await {
+            tracing::error!("Failed to remove vectors of an unsaved {source_type} batch: {cleanup}");
         }
+        return Err(e);
     }
 
-    tracing::info!("Document {doc_id}: upserting {} vectors to Qdrant", qdrant_data.len());
-    vector_service.upsert_chunks(qdrant_data).await?;
+    Ok(())
+}
 
-    tracing::info!("Document {doc_id}: saving {} chunk records to database", db_data.len());
-    chunk_repo.create_batch(&db_data).await?;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    tracing::info!(
-        "Document {doc_id}: embedded {} chunks into Qdrant",
-        chunks.len()
-    );
+    #[test]
+    fn test_require_embedding_key_allows_keyless_ollama() {
+        assert!(require_embedding_key("ollama", "", "uploading").is_ok());
+        assert!(require_embedding_key("openai", "sk-test", "uploading").is_ok());
+    }
 
-    Ok(())
+    #[test]
+    fn test_require_embedding_key_rejects_missing_key() {
+        match require_embedding_key("openai", "", "crawling") {
+            Err(AppError::Validation(msg)) => {
+                assert!(msg.contains("'openai'"));
+                assert!(msg.ends_with("before crawling."));
+            }
+            other => panic!("expected validation error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_normalize_tags() {
+        let raw = vec![" HR ".to_string(), "policy, hr,,".to_string(), "".to_string()];
+        assert_eq!(normalize_tags(&raw).unwrap(), vec!["hr", "policy"]);
+        assert!(normalize_tags(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_normalize_tags_rejects_long_or_too_many() {
+        assert!(normalize_tags(&["x".repeat(MAX_TAG_CHARS + 1)]).is_err());
+        assert!(normalize_tags(&["é".repeat(MAX_TAG_CHARS)]).is_ok());
+        let many: Vec<String> = (0..=MAX_TAGS).map(|i| format!("tag{i}")).collect();
+        assert!(normalize_tags(&many).is_err());
+    }
 }
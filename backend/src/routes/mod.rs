@@ -1,12 +1,301 @@
+use axum::{
+    middleware as axum_mw,
+    routing::{delete, get, post, put},
+    Router,
+};
+
+use crate::middleware::auth::auth_middleware;
+use crate::middleware::body_limit;
+use crate::middleware::cors::widget_cors_layer;
+use crate::middleware::embed_auth::embed_auth_middleware;
+use crate::middleware::rate_limit::{api_rate_limit, llm_rate_limit};
+use crate::state::AppState;
+
 pub mod admin;
 pub mod admin_audit;
 pub mod admin_config;
 pub mod admin_embed;
+pub mod admin_jobs;
 pub mod admin_logs;
+pub mod admin_maintenance;
+pub mod admin_webhooks;
 pub mod auth;
+pub mod capabilities;
 pub mod chat;
+pub mod collections;
 pub mod crawl;
+pub mod debug;
 pub mod documents;
 pub mod health;
 pub mod settings;
+pub mod setup;
+pub mod shares;
+pub mod utils;
 pub mod widget;
+
+/// The JSON API: public routes, and everything behind `auth_middleware`. Each
+/// handler checks the role it needs, so this is the full surface a token can reach.
+pub fn api_routes(state: &AppState) -> Router<AppState> {
+    let public_routes = Router::new()
+        .route("/api/health", get(health::health_check))
+        .route("/api/auth/login", post(auth::login))
+        .route("/api/auth/setup", post(auth::setup))
+        .route("/api/capabilities/public", get(capabilities::public_capabilities))
+        .route("/api/setup/status", get(setup::status))
+        .route("/api/setup/admin", post(setup::create_admin))
+        .route("/api/shared/{token}", get(shares::get_shared));
+
+    // Endpoints that call a model also count against the stricter LLM limit
+    let llm_limit = || axum_mw::from_fn_with_state(state.clone(), llm_rate_limit);
+    let protected_routes = Router::new()
+        // Auth
+        .route("/api/auth/me", get(auth::me))
+        .route("/api/capabilities", get(capabilities::capabilities))
+        // Conversations
+        .route("/api/conversations", get(chat::list_conversations).post(chat::create_conversation))
+        .route("/api/conversations/{id}", get(chat::get_conversation).patch(chat::rename_conversation).delete(chat::delete_conversation))
+        .route(
+            "/api/conversations/{id}/messages",
+            post(chat::send_message).layer(llm_limit()),
+        )
+        .route("/api/conversations/{id}/messages/{message_id}", delete(chat::delete_message))
+        .route(
+            "/api/conversations/{id}/settings",
+            put(chat::update_conversation_settings),
+        )
+        .route("/api/conversations/{id}/archive", post(chat::archive_conversation))
+        .route("/api/conversations/{id}/unarchive", post(chat::unarchive_conversation))
+        .route(
+            "/api/conversations/{id}/share",
+            post(shares::create_share).delete(shares::revoke_shares),
+        )
+        // Documents
+        .route(
+            "/api/documents",
+            get(documents::list)
+                .post(documents::upload)
+                .layer(body_limit::upload_limit(&state.config.server)),
+        )
+        .route("/api/documents/limits", get(documents::upload_limits))
+        .route("/api/documents/{id}", get(documents::get_document).delete(documents::delete_document))
+        .route("/api/documents/{id}/events", get(documents::list_events))
+        .route("/api/documents/{id}/chunks", get(documents::list_chunks))
+        .route("/api/documents/{id}/chunks/{chunk_index}", get(documents::get_chunk))
+        .route("/api/documents/{id}/reprocess", post(documents::reprocess))
+        .route("/api/documents/{id}/tags", put(documents::update_tags))
+        .route("/api/documents/{id}/collection", put(documents::set_collection))
+        .route("/api/collections", get(collections::list).post(collections::create))
+        .route("/api/collections/{id}", put(collections::rename).delete(collections::delete))
+        .route("/api/documents/rescan", post(documents::rescan))
+        .route("/api/documents/bulk", post(documents::bulk))
+        // Crawl
+        .route("/api/crawl", get(crawl::list_crawl_jobs).post(crawl::start_crawl))
+        .route("/api/crawl/schedules", get(crawl::list_schedules))
+        .route("/api/crawl/page", post(crawl::ingest_page).delete(crawl::delete_page))
+        .route("/api/crawl/{id}", get(crawl::get_crawl_job))
+        .route("/api/crawl/{id}/schedule", put(crawl::set_schedule))
+        // Debug
+        .route("/api/debug/retrieval", post(debug::retrieval).layer(llm_limit()))
+        // Utils
+        .route("/api/utils/estimate-tokens", post(utils::estimate_tokens))
+        // Settings (user-facing — only admin-enabled providers/models)
+        .route("/api/settings/providers", get(settings::list_providers))
+        .route(
+            "/api/settings/providers/{provider_id}/models",
+            get(settings::list_models_for_provider),
+        )
+        .route(
+            "/api/settings/providers/{provider_id}/models/live",
+            get(settings::list_live_models),
+        )
+        .route("/api/settings/api-keys", get(settings::list_api_keys))
+        .route("/api/settings/api-keys/status", get(settings::api_key_status))
+        .route(
+            "/api/settings/api-keys/{provider}",
+            put(settings::set_api_key).delete(settings::delete_api_key),
+        )
+        .route(
+            "/api/settings/api-keys/{provider}/test",
+            post(settings::test_api_key),
+        )
+        .route("/api/settings/preferences", get(settings::get_preferences).put(settings::update_preferences))
+        .route("/api/settings/export", post(settings::start_export))
+        .route("/api/settings/export/{id}", get(settings::get_export))
+        .route("/api/settings/export/{id}/download", get(settings::download_export))
+        // Admin — User management
+        .route("/api/admin/users", get(admin::list_users))
+        .route("/api/admin/roles", get(admin::list_roles))
+        .route(
+            "/api/admin/users/{user_id}/role",
+            put(admin::update_user_role),
+        )
+        .route(
+            "/api/admin/users/{user_id}",
+            delete(admin::delete_user),
+        )
+        .route(
+            "/api/admin/users/{user_id}/impersonate",
+            post(admin::impersonate_user).delete(admin::end_impersonation),
+        )
+        .route("/api/admin/invites", get(admin::list_invites).post(admin::invite_user))
+        .route("/api/admin/invites/{id}/resend-email", post(admin::resend_invite_email))
+        // Admin — Logs
+        .route("/api/admin/logs", get(admin_logs::list_conversation_logs))
+        .route(
+            "/api/admin/logs/{id}",
+            get(admin_logs::get_conversation_log).delete(admin_logs::delete_conversation_log),
+        )
+        .route(
+            "/api/admin/widget-logs",
+            get(admin_logs::list_widget_logs),
+        )
+        // Admin — Jobs
+        .route("/api/admin/jobs", get(admin_jobs::list_jobs))
+        .route("/api/admin/jobs/scheduled", get(admin_jobs::list_scheduled_jobs))
+        .route("/api/admin/jobs/scheduled/{name}/run", post(admin_jobs::run_scheduled_job))
+        .route("/api/admin/rescan/status", get(documents::rescan_status))
+        // Admin — Maintenance
+        .route(
+            "/api/admin/maintenance/purge-conversations",
+            post(admin_maintenance::purge_conversations),
+        )
+        // Admin — Provider / model config
+        .route(
+            "/api/admin/config/providers",
+            get(admin_config::list_providers),
+        )
+        .route(
+            "/api/admin/config/providers/{provider_id}/toggle",
+            put(admin_config::toggle_provider),
+        )
+        .route(
+            "/api/admin/config/providers/{provider_id}/default-embedding",
+            put(admin_config::set_default_embedding_provider),
+        )
+        .route(
+            "/api/admin/config/providers/{provider_id}/models",
+            get(admin_config::list_models).post(admin_config::add_model),
+        )
+        .route(
+            "/api/admin/config/providers/{provider_id}/models/sync",
+            post(admin_config::sync_models),
+        )
+        .route("/api/admin/config/sync-catalog", post(admin_config::sync_catalog))
+        .route("/api/admin/config/export", get(admin_config::export_config))
+        .route("/api/admin/config/import", post(admin_config::import_config))
+        .route(
+            "/api/admin/config/system-prompt",
+            get(admin_config::get_system_prompt).put(admin_config::set_system_prompt),
+        )
+        .route(
+            "/api/admin/config/api-keys",
+            get(admin_config::list_api_keys),
+        )
+        .route(
+            "/api/admin/config/api-keys/{provider}",
+            get(admin_config::get_api_key)
+                .put(admin_config::set_api_key)
+                .delete(admin_config::delete_api_key),
+        )
+        .route(
+            "/api/admin/config/models/{model_id}",
+            put(admin_config::update_model).delete(admin_config::remove_model),
+        )
+        .route(
+            "/api/admin/config/models/{model_id}/default",
+            put(admin_config::set_default_model),
+        )
+        // Admin — Audit logs
+        .route(
+            "/api/admin/audit-logs",
+            get(admin_audit::list_audit_logs),
+        )
+        // Admin — Embed keys
+        .route("/api/admin/embed-keys", get(admin_embed::list_keys).post(admin_embed::create_key))
+        .route(
+            "/api/admin/embed-keys/{id}",
+            get(admin_embed::get_key)
+                .put(admin_embed::update_key)
+                .delete(admin_embed::delete_key),
+        )
+        .route(
+            "/api/admin/embed-keys/{id}/toggle",
+            put(admin_embed::toggle_key),
+        )
+        .route(
+            "/api/admin/embed-keys/{id}/test",
+            post(admin_embed::test_key).layer(llm_limit()),
+        )
+        .route(
+            "/api/admin/embed-keys/{id}/avatar",
+            post(admin_embed::upload_avatar).layer(body_limit::avatar_limit()),
+        )
+        .route(
+            "/api/admin/widget-sessions/purge",
+            post(admin_embed::purge_widget_sessions),
+        )
+        .route(
+            "/api/admin/widget-handoffs",
+            get(admin_embed::list_handoffs),
+        )
+        .route(
+            "/api/admin/widget-handoffs/{id}/status",
+            put(admin_embed::update_handoff_status),
+        )
+        // Admin — Webhooks
+        .route(
+            "/api/admin/webhooks",
+            get(admin_webhooks::list_webhooks).post(admin_webhooks::create_webhook),
+        )
+        .route(
+            "/api/admin/webhooks/{id}",
+            put(admin_webhooks::update_webhook).delete(admin_webhooks::delete_webhook),
+        )
+        .route(
+            "/api/admin/webhooks/{id}/deliveries",
+            get(admin_webhooks::list_deliveries),
+        )
+        .layer(axum_mw::from_fn_with_state(state.clone(), api_rate_limit))
+        .layer(axum_mw::from_fn_with_state(
+            state.clone(),
+            auth_middleware,
+        ));
+
+    public_routes.merge(protected_routes)
+}
+
+/// The embeddable widget's API, authenticated by embed key and open to any origin.
+pub fn widget_routes(state: &AppState) -> Router<AppState> {
+    Router::new()
+        .route("/api/widget/config", get(widget::get_config))
+        .route(
+            "/api/widget/conversations",
+            get(widget::list_conversations)
+                .post(widget::create_conversation)
+                .delete(widget::clear_conversations),
+        )
+        .route(
+            "/api/widget/conversations/{id}",
+            delete(widget::delete_conversation),
+        )
+        .route(
+            "/api/widget/conversations/{id}/messages",
+            get(widget::get_messages).post(widget::send_message),
+        )
+        .route(
+            "/api/widget/conversations/{id}/handoff",
+            post(widget::request_handoff),
+        )
+        .route(
+            "/api/widget/conversations/{id}/email-transcript",
+            post(widget::email_transcript),
+        )
+        .layer(axum_mw::from_fn_with_state(
+            state.clone(),
+            embed_auth_middleware,
+        ))
+        // Loaded by <img> tags, so without a key
+        .route("/api/widget/avatar/{key_prefix}", get(widget::get_avatar))
+        // Outside embed auth so preflight requests are answered without a key
+        .layer(widget_cors_layer())
+}
@@ -1,14 +1,22 @@
 use axum::{
     extract::{Path, State},
+    http::header,
+    response::{IntoResponse, Response},
     Json,
 };
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
 
+use crate::config::LlmConfig;
 use crate::db::models::admin_config::{AdminModel, AdminProvider};
+use crate::db::models::data_export::{DataExport, ExportFormat};
 use crate::db::models::settings::{ApiKeyEntry, LlmPreferences};
 use crate::errors::AppError;
-use crate::middleware::auth::Claims;
-use crate::services::audit;
+use crate::middleware::auth::{require_admin, Claims};
+use crate::services::credentials::KeySource;
+use crate::services::jobs::JobPayload;
+use crate::services::model_catalog::LiveModel;
+use crate::services::{audit, llm_provider, provider_api};
 use crate::state::AppState;
 
 // ── Providers (user-facing, only admin-enabled) ─────────────
@@ -26,6 +34,47 @@ pub async fn list_models_for_provider(
     Path(provider_id): Path<String>,
 ) -> Result<Json<Vec<AdminModel>>, AppError> {
     let models = state.admin_config_repo.list_models(&provider_id).await?;
+    Ok(Json(
+        models
+            .into_iter()
+            .filter(|m| m.removed_at.is_none() && m.is_enabled)
+            .collect(),
+    ))
+}
+
+#[cfg_attr(feature = "openapi", utoipa::path(get, path = "/api/settings/providers/{provider_id}/models/live", tag = "Settings", security(("bearer_auth" = [])), params(("provider_id" = String, Path, description = "Provider ID")), responses((status = 200, body = Vec<LiveModel>))))]
+pub async fn list_live_models(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(provider_id): Path<String>,
+) -> Result<Json<Vec<LiveModel>>, AppError> {
+    if !provider_api::supports_live_models(&provider_id) {
+        return Err(AppError::Validation(format!(
+            "Live model discovery is not supported for provider '{provider_id}'"
+        )));
+    }
+
+    let resolved = state.credentials.resolve(&claims.sub, &provider_id).await?;
+    let from_user = resolved.as_ref().is_some_and(|r| r.source == KeySource::User);
+    let credentials = resolved.map(|r| r.credentials);
+    let api_key = credentials.as_ref().map(|c| c.api_key.clone()).unwrap_or_default();
+    let base_url = credentials.and_then(|c| c.base_url);
+    if let Some(url) = base_url.as_deref().filter(|_| from_user) {
+        check_user_base_url(&claims, url).await?;
+    }
+
+    if api_key.is_empty() && llm_provider::requires_api_key(&provider_id) {
+        return Err(AppError::Validation(format!(
+            "No API key configured for provider '{provider_id}'"
+        )));
+    }
+
+    let models = state
+        .model_catalog
+        .get_or_fetch(&provider_id, &api_key, base_url.as_deref())
+        .await
+        .map_err(|e| AppError::Validation(format!("Failed to fetch models: {e}")))?;
+
     Ok(Json(models))
 }
 
@@ -34,6 +83,8 @@ pub async fn list_models_for_provider(
 #[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct SetApiKeyRequest {
     pub api_key: String,
+    /// Optional custom endpoint, e.g. a remote Ollama host or an OpenAI-compatible gateway.
+    pub base_url: Option<String>,
 }
 
 #[cfg_attr(feature = "openapi", utoipa::path(get, path = "/api/settings/api-keys", tag = "Settings", security(("bearer_auth" = [])), responses((status = 200, body = Vec<ApiKeyEntry>))))]
@@ -45,6 +96,42 @@ pub async fn list_api_keys(
     Ok(Json(keys))
 }
 
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ApiKeyStatus {
+    pub provider: String,
+    /// Which key chat and embeddings will use; `None` means no usable key.
+    pub source: Option<KeySource>,
+    /// True when the user has no key of their own and the organization key is used.
+    pub using_organization_key: bool,
+}
+
+/// Per enabled provider, report whether the user's own key, the organization
+/// key or no key at all will be used.
+#[cfg_attr(feature = "openapi", utoipa::path(get, path = "/api/settings/api-keys/status", tag = "Settings", security(("bearer_auth" = [])), responses((status = 200, body = Vec<ApiKeyStatus>))))]
+pub async fn api_key_status(
+    State(state): State<AppState>,
+    claims: Claims,
+) -> Result<Json<Vec<ApiKeyStatus>>, AppError> {
+    let providers = state.admin_config_repo.get_enabled_providers().await?;
+
+    let mut statuses = Vec::with_capacity(providers.len());
+    for provider in providers {
+        let source = state
+            .credentials
+            .resolve(&claims.sub, &provider.provider_id)
+            .await?
+            .map(|r| r.source);
+        statuses.push(ApiKeyStatus {
+            provider: provider.provider_id,
+            source,
+            using_organization_key: source == Some(KeySource::Organization),
+        });
+    }
+
+    Ok(Json(statuses))
+}
+
 #[cfg_attr(feature = "openapi", utoipa::path(put, path = "/api/settings/api-keys/{provider}", tag = "Settings", security(("bearer_auth" = [])), params(("provider" = String, Path, description = "Provider name")), request_body = SetApiKeyRequest, responses((status = 200, body = ApiKeyEntry))))]
 pub async fn set_api_key(
     State(state): State<AppState>,
@@ -52,16 +139,28 @@ pub async fn set_api_key(
     Path(provider): Path<String>,
     Json(payload): Json<SetApiKeyRequest>,
 ) -> Result<Json<ApiKeyEntry>, AppError> {
-    if payload.api_key.trim().is_empty() {
+    if payload.api_key.trim().is_empty() && llm_provider::requires_api_key(&provider) {
         return Err(AppError::Validation("API key cannot be empty".to_string()));
     }
 
+    let base_url = payload
+        .base_url
+        .as_deref()
+        .filter(|u| !u.trim().is_empty())
+        .map(llm_provider::validate_base_url)
+        .transpose()
+        .map_err(|e| AppError::Validation(e.to_string()))?;
+    if let Some(url) = &base_url {
+        check_user_base_url(&claims, url).await?;
+    }
+
     let entry = state
         .settings_repo
-        .set_api_key(&claims.sub, &provider, &payload.api_key).await?;
+        .set_api_key(&claims.sub, &provider, &payload.api_key, base_url.as_deref())
+        .await?;
 
-    audit::log(
-        &state.audit_log_repo,
+    audit::log_critical(
+        &state.audit,
         Some(&claims.sub),
         "settings.update_key",
         Some("api_key"),
@@ -69,7 +168,8 @@ pub async fn set_api_key(
         &format!("Updated API key for provider '{provider}'"),
         None,
         None,
-    );
+    )
+    .await?;
 
     Ok(Json(entry))
 }
@@ -82,8 +182,8 @@ pub async fn delete_api_key(
 ) -> Result<(), AppError> {
     state.settings_repo.delete_api_key(&claims.sub, &provider).await?;
 
-    audit::log(
-        &state.audit_log_repo,
+    audit::log_critical(
+        &state.audit,
         Some(&claims.sub),
         "settings.delete_key",
         Some("api_key"),
@@ -91,42 +191,282 @@ pub async fn delete_api_key(
         &format!("Deleted API key for provider '{provider}'"),
         None,
         None,
-    );
+    )
+    .await?;
 
     Ok(())
 }
 
+#[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TestApiKeyRequest {
+    /// Key to test. When omitted, the stored key for the provider is used.
+    #[serde(default)]
+    pub api_key: Option<String>,
+    #[serde(default)]
+    pub base_url: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ApiKeyTestResponse {
+    pub success: bool,
+    /// "OK" on success, otherwise the provider's (sanitized) error message.
+    pub message: String,
+    pub latency_ms: u64,
+}
+
+/// Users other than admins may only point their keys at public hosts, so the
+/// server can't be used to reach its own network.
+async fn check_user_base_url(claims: &Claims, base_url: &str) -> Result<(), AppError> {
+    if require_admin(claims).is_ok() {
+        return Ok(());
+    }
+    llm_provider::ensure_public_base_url(base_url)
+        .await
+        .map_err(|e| AppError::Validation(e.to_string()))
+}
+
+/// Run a provider connection test and time it. Failures are reported in the
+/// response body rather than as an HTTP error.
+pub async fn run_key_test(
+    provider: &str,
+    api_key: &str,
+    base_url: Option<&str>,
+) -> ApiKeyTestResponse {
+    let started = Instant::now();
+    let result = provider_api::test_connection(provider, api_key, base_url).await;
+    let latency_ms = started.elapsed().as_millis() as u64;
+
+    match result {
+        Ok(()) => ApiKeyTestResponse {
+            success: true,
+            message: "OK".to_string(),
+            latency_ms,
+        },
+        Err(e) => ApiKeyTestResponse {
+            success: false,
+            message: e.to_string(),
+            latency_ms,
+        },
+    }
+}
+
+#[cfg_attr(feature = "openapi", utoipa::path(post, path = "/api/settings/api-keys/{provider}/test", tag = "Settings", security(("bearer_auth" = [])), params(("provider" = String, Path, description = "Provider name")), request_body = TestApiKeyRequest, responses((status = 200, body = ApiKeyTestResponse))))]
+pub async fn test_api_key(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(provider): Path<String>,
+    Json(payload): Json<TestApiKeyRequest>,
+) -> Result<Json<ApiKeyTestResponse>, AppError> {
+    let stored = state.settings_repo.get_credentials(&claims.sub, &provider).await?;
+
+    let api_key = payload
+        .api_key
+        .filter(|k| !k.trim().is_empty())
+        .or_else(|| stored.as_ref().map(|c| c.api_key.clone()))
+        .unwrap_or_default();
+
+    let base_url = match payload.base_url.as_deref().filter(|u| !u.trim().is_empty()) {
+        Some(url) => Some(
+            llm_provider::validate_base_url(url)
+                .map_err(|e| AppError::Validation(e.to_string()))?,
+        ),
+        None => stored.and_then(|c| c.base_url),
+    };
+    if let Some(url) = &base_url {
+        check_user_base_url(&claims, url).await?;
+    }
+
+    if api_key.is_empty() && llm_provider::requires_api_key(&provider) {
+        return Err(AppError::Validation(format!(
+            "No API key supplied or stored for provider '{provider}'"
+        )));
+    }
+
+    let response = run_key_test(&provider, &api_key, base_url.as_deref()).await;
+
+    audit::log(
+        &state.audit,
+        Some(&claims.sub),
+        "settings.test_key",
+        Some("api_key"),
+        Some(&provider),
+        &format!(
+            "Tested API key for provider '{provider}': {}",
+            if response.success { "success" } else { "failed" }
+        ),
+        None,
+        None,
+    );
+
+    Ok(Json(response))
+}
+
 // ── LLM Preferences ─────────────────────────────────────────
-#[cfg_attr(feature = "openapi", utoipa::path(get, path = "/api/settings/preferences", tag = "Settings", security(("bearer_auth" = [])), responses((status = 200, body = LlmPreferences))))]
+#[derive(Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct PreferencesResponse {
+    #[serde(flatten)]
+    pub preferences: LlmPreferences,
+    /// Whether the organization puts a base prompt before `system_prompt`,
+    /// which stays in effect whatever the user's prompt says.
+    pub has_base_system_prompt: bool,
+    /// The base prompt, if the organization lets users read it.
+    pub base_system_prompt: Option<String>,
+}
+
+#[cfg_attr(feature = "openapi", utoipa::path(get, path = "/api/settings/preferences", tag = "Settings", security(("bearer_auth" = [])), responses((status = 200, body = PreferencesResponse))))]
 pub async fn get_preferences(
     State(state): State<AppState>,
     claims: Claims,
-) -> Result<Json<LlmPreferences>, AppError> {
-    let prefs = state
-        .settings_repo
-        .get_preferences(&claims.sub).await?
-        .unwrap_or_else(|| LlmPreferences {
-            preferred_provider: state.config.llm.default_provider.clone(),
-            preferred_model: state.config.llm.default_model.clone(),
-            preferred_embedding_model: state.config.llm.default_embedding_model.clone(),
-            system_prompt: state.config.llm.default_system_prompt.clone(),
-        });
+) -> Result<Json<PreferencesResponse>, AppError> {
+    let prefs = state.settings_repo.get_preferences(&claims.sub).await?;
+    let org = state.org_settings_repo.get().await?;
+    let has_base_system_prompt = !org.base_system_prompt.is_empty();
+    Ok(Json(PreferencesResponse {
+        preferences: preferences_or_defaults(&state.config.llm, prefs),
+        has_base_system_prompt,
+        base_system_prompt: (has_base_system_prompt && org.base_system_prompt_visible)
+            .then_some(org.base_system_prompt),
+    }))
+}
 
-    Ok(Json(prefs))
+/// The user's saved preferences, or the configured defaults when they have none.
+pub fn preferences_or_defaults(config: &LlmConfig, saved: Option<LlmPreferences>) -> LlmPreferences {
+    saved.unwrap_or_else(|| LlmPreferences {
+        preferred_provider: config.default_provider.clone(),
+        preferred_model: config.default_model.clone(),
+        preferred_embedding_model: config.default_embedding_model.clone(),
+        system_prompt: config.default_system_prompt.clone(),
+        fallback_providers: Vec::new(),
+    })
 }
 
-#[cfg_attr(feature = "openapi", utoipa::path(put, path = "/api/settings/preferences", tag = "Settings", security(("bearer_auth" = [])), request_body = LlmPreferences, responses((status = 200, body = LlmPreferences))))]
+/// Check preferences against the admin catalogue: the provider must be enabled
+/// and offer chat, the model must be one of its active, enabled completion models, and
+/// the embedding model (optional) must be offered by an enabled provider.
+/// An empty provider and model mean "use the organization default".
+fn check_preferences(
+    prefs: &LlmPreferences,
+    providers: &[AdminProvider],
+    provider_models: &[AdminModel],
+    embedding_model_providers: &[String],
+) -> Result<(), String> {
+    check_chat_model(&prefs.preferred_provider, &prefs.preferred_model, providers, provider_models)?;
+    for provider_id in &prefs.fallback_providers {
+        let provider = providers
+            .iter()
+            .find(|p| &p.provider_id == provider_id)
+            .ok_or_else(|| format!("Unknown fallback provider '{provider_id}'"))?;
+        if !provider.enabled || !provider.supports_completion {
+            return Err(format!(
+                "Provider '{}' can't be used as a fallback",
+                provider.display_name
+            ));
+        }
+    }
+
+    let embedding_model = prefs.preferred_embedding_model.as_str();
+    if !embedding_model.is_empty() && embedding_model_providers.is_empty() {
+        return Err(format!(
+            "Embedding model '{embedding_model}' is not offered by any enabled provider"
+        ));
+    }
+
+    Ok(())
+}
+
+/// Check a chat provider and model choice against the admin catalogue. An
+/// empty provider and model mean "use the default".
+pub(crate) fn check_chat_model(
+    provider_id: &str,
+    model_id: &str,
+    providers: &[AdminProvider],
+    provider_models: &[AdminModel],
+) -> Result<(), String> {
+    if provider_id.is_empty() {
+        if !model_id.is_empty() {
+            return Err(format!("Choose the provider for model '{model_id}'"));
+        }
+        return Ok(());
+    }
+
+    let provider = providers
+        .iter()
+        .find(|p| p.provider_id == provider_id)
+        .ok_or_else(|| format!("Unknown provider '{provider_id}'"))?;
+    if !provider.enabled {
+        return Err(format!(
+            "Provider '{}' has been disabled by an administrator",
+            provider.display_name
+        ));
+    }
+    if !provider.supports_completion {
+        return Err(format!("Provider '{}' does not offer chat models", provider.display_name));
+    }
+
+    if model_id.is_empty() {
+        return Err(format!("Choose a model for provider '{}'", provider.display_name));
+    }
+    let model = provider_models
+        .iter()
+        .find(|m| m.model_id == model_id && m.removed_at.is_none())
+        .ok_or_else(|| {
+            format!("Model '{model_id}' is not available for provider '{}'", provider.display_name)
+        })?;
+    if model.model_type != "completion" {
+        return Err(format!("'{model_id}' is an {} model, not a chat model", model.model_type));
+    }
+    if !model.is_enabled {
+        return Err(format!(
+            "Model '{}' has been disabled by an administrator",
+            model.display_name
+        ));
+    }
+    Ok(())
+}
+
+/// [`check_chat_model`] against the current catalogue.
+pub(crate) async fn validate_chat_model(state: &AppState, provider_id: &str, model_id: &str) -> Result<(), AppError> {
+    let providers = state.admin_config_repo.list_providers().await?;
+    let provider_models = if provider_id.is_empty() {
+        Vec::new()
+    } else {
+        state.admin_config_repo.list_models(provider_id).await?
+    };
+    check_chat_model(provider_id, model_id, &providers, &provider_models).map_err(AppError::Validation)
+}
+
+#[cfg_attr(feature = "openapi", utoipa::path(put, path = "/api/settings/preferences", tag = "Settings", security(("bearer_auth" = [])), request_body = LlmPreferences, responses((status = 200, body = LlmPreferences), (status = 400, description = "Provider or model not available"))))]
 pub async fn update_preferences(
     State(state): State<AppState>,
     claims: Claims,
     Json(payload): Json<LlmPreferences>,
 ) -> Result<Json<LlmPreferences>, AppError> {
+    let providers = state.admin_config_repo.list_providers().await?;
+    let provider_models = if payload.preferred_provider.is_empty() {
+        Vec::new()
+    } else {
+        state.admin_config_repo.list_models(&payload.preferred_provider).await?
+    };
+    let embedding_model_providers = if payload.preferred_embedding_model.is_empty() {
+        Vec::new()
+    } else {
+        state
+            .admin_config_repo
+            .find_embedding_model_providers(&payload.preferred_embedding_model)
+            .await?
+    };
+    check_preferences(&payload, &providers, &provider_models, &embedding_model_providers)
+        .map_err(AppError::Validation)?;
+
     state
         .settings_repo
         .set_preferences(&claims.sub, &payload).await?;
 
     audit::log(
-        &state.audit_log_repo,
+        &state.audit,
         Some(&claims.sub),
         "settings.update_preferences",
         None,
@@ -138,3 +478,192 @@ pub async fn update_preferences(
 
     Ok(Json(payload))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn provider(provider_id: &str, enabled: bool, supports_completion: bool) -> AdminProvider {
+        AdminProvider {
+            id: provider_id.to_string(),
+            provider_id: provider_id.to_string(),
+            display_name: provider_id.to_uppercase(),
+            enabled,
+            supports_completion,
+            supports_embeddings: true,
+            is_default_embedding: false,
+            created_at: Default::default(),
+        }
+    }
+
+    fn model(model_id: &str, model_type: &str, removed: bool) -> AdminModel {
+        AdminModel {
+            id: model_id.to_string(),
+            provider_id: "openai".to_string(),
+            model_id: model_id.to_string(),
+            display_name: model_id.to_string(),
+            model_type: model_type.to_string(),
+            is_default: false,
+            created_at: Default::default(),
+            removed_at: removed.then(Default::default),
+            sort_order: 0,
+            is_enabled: true,
+        }
+    }
+
+    fn prefs(provider: &str, model: &str, embedding: &str) -> LlmPreferences {
+        LlmPreferences {
+            preferred_provider: provider.to_string(),
+            preferred_model: model.to_string(),
+            preferred_embedding_model: embedding.to_string(),
+            system_prompt: String::new(),
+            fallback_providers: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_check_preferences() {
+        let providers = [
+            provider("openai", true, true),
+            provider("anthropic", false, true),
+            provider("voyage", true, false),
+        ];
+        let models = [
+            model("gpt-4o", "completion", false),
+            model("gpt-3.5-turbo", "completion", true),
+            model("text-embedding-3-small", "embedding", false),
+            AdminModel { is_enabled: false, ..model("gpt-4", "completion", false) },
+        ];
+        let embedders = ["openai".to_string()];
+        let check = |p: &LlmPreferences, embedders: &[String]| {
+            check_preferences(p, &providers, &models, embedders)
+        };
+
+        assert!(check(&prefs("openai", "gpt-4o", "text-embedding-3-small"), &embedders).is_ok());
+        assert!(check(&prefs("", "", ""), &[]).is_ok());
+
+        let err = |p: LlmPreferences, embedders: &[String]| check(&p, embedders).unwrap_err();
+        assert_eq!(err(prefs("mistral", "x", ""), &[]), "Unknown provider 'mistral'");
+        assert!(err(prefs("anthropic", "claude", ""), &[]).contains("disabled"));
+        assert!(err(prefs("voyage", "x", ""), &[]).contains("does not offer chat models"));
+        assert!(err(prefs("openai", "", ""), &[]).starts_with("Choose a model"));
+        assert!(err(prefs("openai", "gpt-9", ""), &[]).contains("not available"));
+        // Models removed at the provider are no longer selectable
+        assert!(err(prefs("openai", "gpt-3.5-turbo", ""), &[]).contains("not available"));
+        assert!(err(prefs("openai", "text-embedding-3-small", ""), &[]).contains("not a chat model"));
+        assert!(err(prefs("openai", "gpt-4", ""), &[]).contains("disabled"));
+        assert!(err(prefs("", "gpt-4o", ""), &[]).starts_with("Choose the provider"));
+        assert!(err(prefs("openai", "gpt-4o", "made-up-embedder"), &[]).contains("not offered"));
+    }
+}
+
+// ── Personal data export ─────────────────────────────────────
+#[derive(Debug, Default, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct StartExportRequest {
+    #[serde(default)]
+    pub format: ExportFormat,
+}
+
+#[derive(Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DataExportResponse {
+    #[serde(flatten)]
+    pub export: DataExport,
+    /// Where to fetch the file while the export is ready and unexpired.
+    pub download_url: Option<String>,
+}
+
+impl From<DataExport> for DataExportResponse {
+    fn from(export: DataExport) -> Self {
+        let download_url = export
+            .is_downloadable(chrono::Utc::now())
+            .then(|| format!("/api/settings/export/{}/download", export.id));
+        Self { export, download_url }
+    }
+}
+
+/// The current user's export, or 404 so other users' exports can't be probed.
+async fn find_own_export(state: &AppState, claims: &Claims, id: &str) -> Result<DataExport, AppError> {
+    state
+        .data_export_repo
+        .find_by_id(id)
+        .await?
+        .filter(|e| e.user_id == claims.sub)
+        .ok_or_else(|| AppError::NotFound("Export not found".to_string()))
+}
+
+/// Start assembling everything held about the current user. An export already
+/// in progress is returned instead of starting another.
+#[cfg_attr(feature = "openapi", utoipa::path(post, path = "/api/settings/export", tag = "Settings", security(("bearer_auth" = [])), request_body = StartExportRequest, responses((status = 200, body = DataExportResponse))))]
+pub async fn start_export(
+    State(state): State<AppState>,
+    claims: Claims,
+    payload: Option<Json<StartExportRequest>>,
+) -> Result<Json<DataExportResponse>, AppError> {
+    if let Some(pending) = state.data_export_repo.find_pending(&claims.sub).await? {
+        return Ok(Json(pending.into()));
+    }
+
+    let format = payload.map(|Json(p)| p.format).unwrap_or_default();
+    let export = state.data_export_repo.create(&claims.sub, format).await?;
+    let payload = JobPayload::DataExport {
+        export_id: export.id.clone(),
+        user_id: claims.sub.clone(),
+    };
+    if let Err(e) = state.jobs.enqueue(&payload).await {
+        state.data_export_repo.mark_failed(&export.id, &format!("{e:#}")).await?;
+        return Err(e.into());
+    }
+
+    audit::log(
+        &state.audit,
+        Some(&claims.sub),
+        "settings.export.request",
+        Some("data_export"),
+        Some(&export.id),
+        &format!("Requested a personal data export ({format})"),
+        None,
+        None,
+    );
+
+    Ok(Json(export.into()))
+}
+
+#[cfg_attr(feature = "openapi", utoipa::path(get, path = "/api/settings/export/{id}", tag = "Settings", security(("bearer_auth" = [])), params(("id" = String, Path, description = "Export ID")), responses((status = 200, body = DataExportResponse))))]
+pub async fn get_export(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(id): Path<String>,
+) -> Result<Json<DataExportResponse>, AppError> {
+    let export = find_own_export(&state, &claims, &id).await?;
+    Ok(Json(export.into()))
+}
+
+#[cfg_attr(feature = "openapi", utoipa::path(get, path = "/api/settings/export/{id}/download", tag = "Settings", security(("bearer_auth" = [])), params(("id" = String, Path, description = "Export ID")), responses((status = 200, description = "The export file"), (status = 404, description = "Not ready, expired or not found"))))]
+pub async fn download_export(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(id): Path<String>,
+) -> Result<Response, AppError> {
+    let export = find_own_export(&state, &claims, &id).await?;
+    let Some(key) = export
+        .storage_key
+        .as_deref()
+        .filter(|_| export.is_downloadable(chrono::Utc::now()))
+    else {
+        return Err(AppError::NotFound("Export is not ready or has expired".to_string()));
+    };
+
+    let bytes = state.storage.download(key).await?;
+    let date = export.created_at.format("%Y-%m-%d");
+    let filename = format!("data-export-{date}.{}", export.format);
+    Ok((
+        [
+            (header::CONTENT_TYPE, export.format.content_type().to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{filename}\"")),
+        ],
+        bytes,
+    )
+        .into_response())
+}
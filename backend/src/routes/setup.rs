@@ -0,0 +1,67 @@
+use axum::{extract::State, Json};
+
+use crate::dto::auth::{AuthResponse, CreateAdminRequest, SetupStatusResponse};
+use crate::errors::AppError;
+use crate::middleware::client_ip::ClientIp;
+use crate::routes::auth::validate_credentials;
+use crate::services::email::looks_like_email;
+use crate::services::{audit, auth_service};
+use crate::state::AppState;
+
+const ALREADY_SET_UP: &str = "Setup has already been completed";
+
+#[cfg_attr(feature = "openapi", utoipa::path(get, path = "/api/setup/status", tag = "Auth", responses((status = 200, body = SetupStatusResponse))))]
+pub async fn status(State(state): State<AppState>) -> Result<Json<SetupStatusResponse>, AppError> {
+    Ok(Json(SetupStatusResponse {
+        needs_setup: state.user_repo.count().await? == 0,
+    }))
+}
+
+/// Create the admin account of a fresh install and sign them in. Only works
+/// while there are no accounts; after that, and for a request that loses the
+/// race to another, it answers 410.
+#[cfg_attr(feature = "openapi", utoipa::path(post, path = "/api/setup/admin", tag = "Auth", request_body = CreateAdminRequest, responses((status = 200, body = AuthResponse), (status = 400, description = "Validation error"), (status = 410, description = "Setup has already been completed"))))]
+pub async fn create_admin(
+    State(state): State<AppState>,
+    client_ip: ClientIp,
+    Json(payload): Json<CreateAdminRequest>,
+) -> Result<Json<AuthResponse>, AppError> {
+    // Checked first so a finished install never spends time hashing passwords
+    if state.user_repo.count().await? > 0 {
+        return Err(AppError::Gone(ALREADY_SET_UP.to_string()));
+    }
+    let username = payload.username.trim();
+    validate_credentials(username, &payload.password)?;
+    let email = payload.email.trim();
+    if !looks_like_email(email) {
+        return Err(AppError::Validation("A valid email address is required".to_string()));
+    }
+
+    let password_hash = auth_service::hash_password(&payload.password).map_err(AppError::Internal)?;
+    let user = state
+        .user_repo
+        .create_first_admin(username, email, &password_hash)
+        .await?
+        .ok_or_else(|| AppError::Gone(ALREADY_SET_UP.to_string()))?;
+
+    let token = auth_service::generate_jwt(&user.id, &user.username, &user.role.to_string(), &state.config.auth)
+        .map_err(AppError::Internal)?;
+
+    let ip = client_ip.to_string();
+    audit::log_critical(
+        &state.audit,
+        Some(&user.id),
+        "auth.bootstrap",
+        Some("user"),
+        Some(&user.id),
+        &format!("First admin '{}' created through setup", user.username),
+        Some(&ip),
+        None,
+    )
+    .await?;
+
+    Ok(Json(AuthResponse {
+        token,
+        user: user.into(),
+    }))
+}
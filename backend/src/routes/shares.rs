@@ -0,0 +1,181 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::db::models::conversation_share::ConversationShare;
+use crate::errors::AppError;
+use crate::middleware::auth::Claims;
+use crate::middleware::client_ip::ClientIp;
+use crate::middleware::embed_auth::hash_key;
+use crate::services::audit;
+use crate::state::AppState;
+use chrono::{DateTime, Utc};
+
+#[derive(Deserialize, Default)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CreateShareRequest {
+    /// Days until the link stops working; `sharing.default_expiry_days` when omitted.
+    pub expires_in_days: Option<u32>,
+}
+
+#[derive(Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CreateShareResponse {
+    pub share: ConversationShare,
+    /// The link to hand out. Its token is not stored and can't be shown again.
+    pub url: String,
+    pub token: String,
+}
+
+/// A shared conversation as anyone with the link sees it. It carries no user
+/// or conversation identifiers.
+#[derive(Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SharedConversation {
+    pub title: String,
+    #[serde(with = "crate::db::timestamp")]
+    pub created_at: DateTime<Utc>,
+    pub messages: Vec<SharedMessage>,
+}
+
+#[derive(Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SharedMessage {
+    pub role: String,
+    pub content: String,
+    #[serde(with = "crate::db::timestamp")]
+    pub created_at: DateTime<Utc>,
+}
+
+fn generate_token() -> String {
+    let mut rng = rand::rng();
+    let mut bytes = [0u8; 32];
+    rng.fill(&mut bytes);
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Create a read-only public link to one of your conversations.
+#[cfg_attr(feature = "openapi", utoipa::path(post, path = "/api/conversations/{id}/share", tag = "Chat", security(("bearer_auth" = [])), params(("id" = String, Path, description = "Conversation ID")), request_body = CreateShareRequest, responses((status = 200, body = CreateShareResponse))))]
+pub async fn create_share(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(id): Path<String>,
+    payload: Option<Json<CreateShareRequest>>,
+) -> Result<Json<CreateShareResponse>, AppError> {
+    let payload = payload.map(|Json(p)| p).unwrap_or_default();
+    let config = &state.config.sharing;
+    let days = payload.expires_in_days.unwrap_or(config.default_expiry_days);
+    if days == 0 || days > config.max_expiry_days {
+        return Err(AppError::Validation(format!(
+            "Links can expire after 1 to {} days",
+            config.max_expiry_days
+        )));
+    }
+
+    let conv = state
+        .conversation_repo
+        .get(&id, &claims.sub)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Conversation not found".to_string()))?;
+
+    let token = generate_token();
+    let share = state
+        .share_repo
+        .create(&hash_key(&token), &conv.id, &claims.sub, days)
+        .await?;
+
+    audit::log_critical(
+        &state.audit,
+        Some(&claims.sub),
+        "chat.share.create",
+        Some("conversation"),
+        Some(&conv.id),
+        &format!("Shared conversation '{}' for {days} days", conv.title),
+        None,
+        Some(serde_json::json!({ "share_id": share.id })),
+    )
+    .await?;
+
+    let url = format!(
+        "{}/shared/{token}",
+        state.config.resend.frontend_url.trim_end_matches('/')
+    );
+    Ok(Json(CreateShareResponse { share, url, token }))
+}
+
+/// Revoke every public link to one of your conversations.
+#[cfg_attr(feature = "openapi", utoipa::path(delete, path = "/api/conversations/{id}/share", tag = "Chat", security(("bearer_auth" = [])), params(("id" = String, Path, description = "Conversation ID")), responses((status = 204))))]
+pub async fn revoke_shares(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(id): Path<String>,
+) -> Result<axum::http::StatusCode, AppError> {
+    let conv = state
+        .conversation_repo
+        .get(&id, &claims.sub)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Conversation not found".to_string()))?;
+
+    let revoked = state.share_repo.revoke_all(&conv.id).await?;
+
+    audit::log_critical(
+        &state.audit,
+        Some(&claims.sub),
+        "chat.share.revoke",
+        Some("conversation"),
+        Some(&conv.id),
+        &format!("Revoked {revoked} links to conversation '{}'", conv.title),
+        None,
+        None,
+    )
+    .await?;
+
+    Ok(axum::http::StatusCode::NO_CONTENT)
+}
+
+/// Read a shared conversation. Public; an unknown, revoked or expired token is
+/// not found.
+#[cfg_attr(feature = "openapi", utoipa::path(get, path = "/api/shared/{token}", tag = "Chat", params(("token" = String, Path, description = "Share token")), responses((status = 200, body = SharedConversation), (status = 404, description = "Link unknown, revoked or expired"), (status = 429, description = "Too many requests"))))]
+pub async fn get_shared(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+    client_ip: ClientIp,
+) -> Result<Json<SharedConversation>, AppError> {
+    if !state.share_limiter.allow(client_ip.0) {
+        return Err(AppError::RateLimited);
+    }
+
+    let not_found = || AppError::NotFound("Shared conversation not found".to_string());
+    let conversation_id = state
+        .share_repo
+        .find_conversation(&hash_key(&token))
+        .await?
+        .ok_or_else(not_found)?;
+    let conv = state
+        .conversation_repo
+        .get_by_id(&conversation_id)
+        .await?
+        .ok_or_else(not_found)?;
+
+    let messages = state
+        .conversation_repo
+        .get_messages(&conv.id)
+        .await?
+        .into_iter()
+        .filter(|m| m.role == "user" || m.role == "assistant")
+        .map(|m| SharedMessage {
+            role: m.role,
+            content: m.content,
+            created_at: m.created_at,
+        })
+        .collect();
+
+    Ok(Json(SharedConversation {
+        title: conv.title,
+        created_at: conv.created_at,
+        messages,
+    }))
+}
@@ -0,0 +1,80 @@
+use axum::{extract::State, Json};
+use serde::{Deserialize, Serialize};
+
+use crate::db::models::conversation::ConversationSettings;
+use crate::errors::AppError;
+use crate::middleware::auth::Claims;
+use crate::services::chat_service;
+use crate::services::llm_provider;
+use crate::services::tokens::{self, EstimationMethod, ModelTokens};
+use crate::state::AppState;
+
+#[derive(Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct EstimateTokensRequest {
+    pub text: String,
+    /// Defaults to the model the user's new conversations answer with.
+    pub provider: Option<String>,
+    /// Defaults to the provider's default model.
+    pub model: Option<String>,
+}
+
+#[derive(Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct EstimateTokensResponse {
+    pub tokens: usize,
+    pub method: EstimationMethod,
+    pub provider: String,
+    pub model: String,
+    /// Tokens the model accepts, prompt and reply together.
+    pub context_window: usize,
+}
+
+/// Estimate how many tokens a text is for a model, so long input can be
+/// flagged or cut before it is sent.
+#[cfg_attr(feature = "openapi", utoipa::path(post, path = "/api/utils/estimate-tokens", tag = "Utils", security(("bearer_auth" = [])), request_body = EstimateTokensRequest, responses((status = 200, body = EstimateTokensResponse), (status = 400, description = "A model without its provider, or a provider without a default model"))))]
+pub async fn estimate_tokens(
+    State(state): State<AppState>,
+    claims: Claims,
+    Json(payload): Json<EstimateTokensRequest>,
+) -> Result<Json<EstimateTokensResponse>, AppError> {
+    let choice = ConversationSettings {
+        provider: payload.provider,
+        model: payload.model,
+        system_prompt: None,
+    }
+    .normalized();
+    let (provider, model) = match (choice.provider, choice.model) {
+        (Some(provider), Some(model)) => (provider, model),
+        (Some(provider), None) => {
+            let model = llm_provider::supported_providers()
+                .iter()
+                .find(|p| p.id.eq_ignore_ascii_case(&provider))
+                .map(|p| p.default_model.to_string())
+                .ok_or_else(|| AppError::Validation(format!("Choose a model for provider '{provider}'")))?;
+            (provider, model)
+        }
+        (None, Some(model)) => {
+            return Err(AppError::Validation(format!("Choose the provider for model '{model}'")));
+        }
+        (None, None) => {
+            let (settings, _) =
+                chat_service::resolve_chat_settings(&state, &claims.sub, &ConversationSettings::default()).await?;
+            (settings.provider, settings.model)
+        }
+    };
+
+    let (tokens, method) = tokens::estimate(&provider, &model, &payload.text);
+    let context_window = ModelTokens {
+        provider: &provider,
+        model: &model,
+    }
+    .context_window();
+    Ok(Json(EstimateTokensResponse {
+        tokens,
+        method,
+        provider,
+        model,
+        context_window,
+    }))
+}
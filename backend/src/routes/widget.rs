@@ -1,46 +1,197 @@
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
     response::sse::{Event, Sse},
+    response::{IntoResponse, Response},
     Json,
 };
 use futures::stream::Stream;
-use rig::completion::Prompt;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::convert::Infallible;
-use tokio_stream::StreamExt;
+use std::time::Duration;
 
-use crate::db::models::conversation::{Conversation, Message};
+use crate::db::models::conversation::{
+    Conversation, Message, MessageGeneration, MessagePage, WidgetConversationSummary,
+};
+use crate::db::models::embed_key::{EmbedKey, OfflineBehavior, WidgetLocalization};
+use crate::db::models::user::UserRole;
+use crate::db::models::widget_handoff::WidgetHandoff;
 use crate::errors::AppError;
+use crate::middleware::client_ip::ClientIp;
 use crate::middleware::embed_auth::EmbedContext;
-use crate::services::{audit, llm_provider};
+use crate::routes::chat::{message_page, MessagesQuery};
+use crate::services::chat_service::{self, ChatRequestContext};
+use crate::services::email::{looks_like_email, normalize_email};
+use crate::services::email_outbox::EmailMessage;
+use crate::services::widget_avatar::{self, AvatarFormat};
+use crate::services::widget_theme::{self, ResolvedWidgetTheme};
+use crate::services::{audit, locale, office_hours, sse, transcript};
 use crate::state::AppState;
 
 #[derive(Serialize)]
 #[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct WidgetConfigResponse {
     pub widget_title: String,
+    /// The theme's light primary color, for loaders that predate themes.
     pub primary_color: String,
+    /// The key's theme with every unset value filled in from the defaults.
+    pub theme: ResolvedWidgetTheme,
     pub greeting_message: String,
     pub custom_css: String,
+    /// Locale whose overrides were applied; `None` when the defaults are used.
+    pub locale: Option<String>,
+    /// Whether visitors can leave their email for a person to follow up.
+    pub handoff_enabled: bool,
+    /// Bot avatar image, relative to the API server; `None` uses the built-in icon.
+    pub avatar_url: Option<String>,
+    pub features: WidgetFeatures,
+    /// Messages a session may send.
+    pub rate_limit: i32,
+    /// Messages this session has sent over its lifetime, never above `rate_limit`;
+    /// once it reaches `rate_limit` further messages are refused.
+    pub messages_used_in_window: i32,
+    /// Whether it is within the key's office hours; always true without a schedule.
+    pub is_online: bool,
+    /// Shown while offline.
+    pub offline_message: String,
+    /// With `block`, messages sent while offline are refused.
+    pub offline_behavior: OfflineBehavior,
+}
+
+const DEFAULT_OFFLINE_MESSAGE: &str =
+    "We're away right now. Leave a message and we'll get back to you.";
+
+/// Optional widget features enabled for the embed key.
+#[derive(Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct WidgetFeatures {
+    /// Visitors can leave their email for a person to follow up.
+    pub handoff: bool,
+    /// Visitors can email themselves a transcript of their conversation.
+    pub transcript_email: bool,
+}
+
+#[derive(Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams))]
+pub struct LocaleQuery {
+    /// Visitor locale such as `de` or `pt-BR`; takes precedence over `Accept-Language`.
+    pub lang: Option<String>,
+}
+
+/// The embed key's localization for the visitor's locale, with the locale it matched.
+fn visitor_localization<'a>(
+    embed_key: &'a EmbedKey,
+    query: &LocaleQuery,
+    headers: &HeaderMap,
+) -> Option<(&'a str, &'a WidgetLocalization)> {
+    let accept_language = headers
+        .get(header::ACCEPT_LANGUAGE)
+        .and_then(|v| v.to_str().ok());
+    let requested = locale::requested_locales(query.lang.as_deref(), accept_language);
+    locale::pick(&embed_key.localizations, &requested)
 }
 
-#[cfg_attr(feature = "openapi", utoipa::path(get, path = "/api/widget/config", tag = "Widget", security(("embed_key" = [])), responses((status = 200, body = WidgetConfigResponse))))]
+/// The key's offline message in the visitor's locale, or the built-in one.
+fn offline_message(embed_key: &EmbedKey, localized: Option<(&str, &WidgetLocalization)>) -> String {
+    localized
+        .and_then(|(_, l)| l.offline_message.clone())
+        .or_else(|| Some(embed_key.offline_message.clone()).filter(|m| !m.is_empty()))
+        .unwrap_or_else(|| DEFAULT_OFFLINE_MESSAGE.to_string())
+}
+
+#[cfg_attr(feature = "openapi", utoipa::path(get, path = "/api/widget/config", tag = "Widget", security(("embed_key" = [])), params(LocaleQuery), responses((status = 200, body = WidgetConfigResponse))))]
 pub async fn get_config(
     State(state): State<AppState>,
     ctx: EmbedContext,
+    Query(query): Query<LocaleQuery>,
+    headers: HeaderMap,
 ) -> Result<Json<WidgetConfigResponse>, AppError> {
     if !state.config.features.widget_enabled {
         return Err(AppError::FeatureDisabled("Widget".to_string()));
     }
 
+    let key = &ctx.embed_key;
+    let messages_used = state
+        .widget_session_repo
+        .get_message_count(&key.id, &ctx.session_id)
+        .await?;
+    let localized = visitor_localization(key, &query, &headers);
+    let text = |pick: fn(&WidgetLocalization) -> &Option<String>, default: &String| {
+        localized
+            .and_then(|(_, l)| pick(l).clone())
+            .unwrap_or_else(|| default.clone())
+    };
+
+    let theme = widget_theme::resolve(&key.theme, &key.primary_color);
+
     Ok(Json(WidgetConfigResponse {
-        widget_title: ctx.embed_key.widget_title,
-        primary_color: ctx.embed_key.primary_color,
-        greeting_message: ctx.embed_key.greeting_message,
-        custom_css: ctx.embed_key.custom_css,
+        widget_title: text(|l| &l.widget_title, &key.widget_title),
+        primary_color: theme.light.primary.clone(),
+        theme,
+        greeting_message: text(|l| &l.greeting_message, &key.greeting_message),
+        custom_css: key.custom_css.clone(),
+        locale: localized.map(|(code, _)| code.to_string()),
+        handoff_enabled: key.handoff_enabled,
+        avatar_url: key.avatar_url.clone(),
+        features: WidgetFeatures {
+            handoff: key.handoff_enabled,
+            transcript_email: key.transcript_email_enabled,
+        },
+        rate_limit: key.rate_limit,
+        messages_used_in_window: messages_used.min(key.rate_limit),
+        is_online: office_hours::is_online(key, chrono::Utc::now()),
+        offline_message: offline_message(key, localized),
+        offline_behavior: key.offline_behavior,
     }))
 }
 
+#[derive(Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams))]
+pub struct AvatarQuery {
+    /// Upload the URL was issued for, from `avatar_url`.
+    pub v: Option<String>,
+}
+
+/// The bot avatar of the active embed key with this prefix. Requests for the
+/// current upload may be cached indefinitely, as a new upload changes the URL.
+#[cfg_attr(feature = "openapi", utoipa::path(get, path = "/api/widget/avatar/{key_prefix}", tag = "Widget", params(("key_prefix" = String, Path, description = "Embed key prefix"), AvatarQuery), responses((status = 200, description = "PNG, JPEG or SVG image"), (status = 404, description = "No avatar for this key"))))]
+pub async fn get_avatar(
+    State(state): State<AppState>,
+    Path(key_prefix): Path<String>,
+    Query(query): Query<AvatarQuery>,
+) -> Result<Response, AppError> {
+    if !state.config.features.widget_enabled {
+        return Err(AppError::FeatureDisabled("Widget".to_string()));
+    }
+
+    let not_found = || AppError::NotFound("Avatar not found".to_string());
+    let avatar_key = state
+        .embed_key_repo
+        .find_avatar_by_prefix(&key_prefix)
+        .await?
+        .ok_or_else(not_found)?;
+    let format = AvatarFormat::from_storage_key(&avatar_key).ok_or_else(not_found)?;
+    let data = state.storage.download(&avatar_key).await.map_err(AppError::Internal)?;
+
+    let cache_control = if query.v.as_deref() == Some(widget_avatar::version(&avatar_key)) {
+        "public, max-age=31536000, immutable"
+    } else {
+        "public, max-age=300"
+    };
+    Ok((
+        [
+            (header::CONTENT_TYPE, format.content_type()),
+            (header::CACHE_CONTROL, cache_control),
+            (header::X_CONTENT_TYPE_OPTIONS, "nosniff"),
+            // SVGs are sanitized on upload; this keeps one opened directly inert too
+            (header::CONTENT_SECURITY_POLICY, "default-src 'none'; style-src 'unsafe-inline'"),
+        ],
+        data,
+    )
+        .into_response())
+}
+
 #[derive(Deserialize)]
 #[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct CreateWidgetConversationRequest {
@@ -73,21 +224,14 @@ pub async fn create_conversation(
         .create_widget(&ctx.embed_key.id, &ctx.session_id, &title)
         .await?;
 
-    // Increment conversation stats (fire-and-forget)
-    let repo = state.embed_key_repo.clone();
-    let key_id = ctx.embed_key.id.clone();
-    tokio::spawn(async move {
-        let _ = repo.increment_stats(&key_id, 1, 0).await;
-    });
-
     Ok(Json(conv))
 }
 
-#[cfg_attr(feature = "openapi", utoipa::path(get, path = "/api/widget/conversations", tag = "Widget", security(("embed_key" = [])), responses((status = 200, body = Vec<Conversation>))))]
+#[cfg_attr(feature = "openapi", utoipa::path(get, path = "/api/widget/conversations", tag = "Widget", security(("embed_key" = [])), responses((status = 200, body = Vec<WidgetConversationSummary>))))]
 pub async fn list_conversations(
     State(state): State<AppState>,
     ctx: EmbedContext,
-) -> Result<Json<Vec<Conversation>>, AppError> {
+) -> Result<Json<Vec<WidgetConversationSummary>>, AppError> {
     if !state.config.features.widget_enabled {
         return Err(AppError::FeatureDisabled("Widget".to_string()));
     }
@@ -100,12 +244,24 @@ pub async fn list_conversations(
     Ok(Json(convs))
 }
 
-#[cfg_attr(feature = "openapi", utoipa::path(get, path = "/api/widget/conversations/{id}/messages", tag = "Widget", security(("embed_key" = [])), params(("id" = String, Path, description = "Conversation ID")), responses((status = 200, body = Vec<Message>))))]
+/// The whole history as a list, or with `after` or `limit` one page of it.
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(untagged)]
+pub enum WidgetMessages {
+    All(Vec<Message>),
+    Page(MessagePage),
+}
+
+/// A conversation's messages. Polling clients pass the last message they have
+/// as `after` to receive only newer ones.
+#[cfg_attr(feature = "openapi", utoipa::path(get, path = "/api/widget/conversations/{id}/messages", tag = "Widget", security(("embed_key" = [])), params(("id" = String, Path, description = "Conversation ID"), MessagesQuery), responses((status = 200, body = WidgetMessages), (status = 400, description = "Unknown after cursor"))))]
 pub async fn get_messages(
     State(state): State<AppState>,
     ctx: EmbedContext,
     Path(conversation_id): Path<String>,
-) -> Result<Json<Vec<Message>>, AppError> {
+    Query(query): Query<MessagesQuery>,
+) -> Result<Json<WidgetMessages>, AppError> {
     if !state.config.features.widget_enabled {
         return Err(AppError::FeatureDisabled("Widget".to_string()));
     }
@@ -117,12 +273,112 @@ pub async fn get_messages(
         .await?
         .ok_or_else(|| AppError::NotFound("Conversation not found".to_string()))?;
 
-    let messages = state
+    let mut response = match message_page(&state, &conversation_id, &query).await? {
+        Some(page) => WidgetMessages::Page(page),
+        None => WidgetMessages::All(state.conversation_repo.get_messages(&conversation_id).await?),
+    };
+    let messages = match &mut response {
+        WidgetMessages::All(messages) => messages,
+        WidgetMessages::Page(page) => &mut page.messages,
+    };
+    // Which model answered is for owners and admins, not visitors
+    for message in messages {
+        message.generation = MessageGeneration::default();
+    }
+
+    Ok(Json(response))
+}
+
+/// Count a deletion request against the session's budget.
+fn check_delete_limit(state: &AppState, ctx: &EmbedContext, client_ip: &ClientIp) -> Result<(), AppError> {
+    if state
+        .widget_delete_limiter
+        .allow((ctx.embed_key.id.clone(), ctx.session_id.clone()))
+    {
+        return Ok(());
+    }
+    tracing::warn!(
+        embed_key_id = %ctx.embed_key.id,
+        session_id = %ctx.session_id,
+        client_ip = %client_ip,
+        "Widget session reached its deletion limit"
+    );
+    Err(AppError::RateLimited)
+}
+
+/// Delete one of the visitor's conversations. It disappears for the visitor at
+/// once and stays in the admin logs, marked deleted, until the purge.
+#[cfg_attr(feature = "openapi", utoipa::path(delete, path = "/api/widget/conversations/{id}", tag = "Widget", security(("embed_key" = [])), params(("id" = String, Path, description = "Conversation ID")), responses((status = 204), (status = 404, description = "Not a conversation of this session"), (status = 429, description = "Too many deletions"))))]
+pub async fn delete_conversation(
+    State(state): State<AppState>,
+    ctx: EmbedContext,
+    Path(conversation_id): Path<String>,
+    client_ip: ClientIp,
+) -> Result<StatusCode, AppError> {
+    if !state.config.features.widget_enabled {
+        return Err(AppError::FeatureDisabled("Widget".to_string()));
+    }
+    check_delete_limit(&state, &ctx, &client_ip)?;
+
+    if !state
         .conversation_repo
-        .get_messages(&conversation_id)
+        .soft_delete_widget(&conversation_id, &ctx.session_id, &ctx.embed_key.id)
+        .await?
+    {
+        return Err(AppError::NotFound("Conversation not found".to_string()));
+    }
+
+    audit::log(
+        &state.audit,
+        None,
+        "widget.delete",
+        Some("conversation"),
+        Some(&conversation_id),
+        "Visitor deleted a widget conversation",
+        Some(&client_ip.to_string()),
+        None,
+    );
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ClearConversationsResponse {
+    pub deleted: u64,
+}
+
+/// Delete all of the visitor's conversations with this embed key.
+#[cfg_attr(feature = "openapi", utoipa::path(delete, path = "/api/widget/conversations", tag = "Widget", security(("embed_key" = [])), responses((status = 200, body = ClearConversationsResponse), (status = 429, description = "Too many deletions"))))]
+pub async fn clear_conversations(
+    State(state): State<AppState>,
+    ctx: EmbedContext,
+    client_ip: ClientIp,
+) -> Result<Json<ClearConversationsResponse>, AppError> {
+    if !state.config.features.widget_enabled {
+        return Err(AppError::FeatureDisabled("Widget".to_string()));
+    }
+    check_delete_limit(&state, &ctx, &client_ip)?;
+
+    let deleted = state
+        .conversation_repo
+        .soft_delete_session(&ctx.session_id, &ctx.embed_key.id)
         .await?;
 
-    Ok(Json(messages))
+    if deleted > 0 {
+        audit::log(
+            &state.audit,
+            None,
+            "widget.clear",
+            Some("embed_key"),
+            Some(&ctx.embed_key.id),
+            &format!("Visitor deleted {deleted} widget conversations"),
+            Some(&client_ip.to_string()),
+            None,
+        );
+    }
+
+    Ok(Json(ClearConversationsResponse { deleted }))
 }
 
 #[derive(Deserialize)]
@@ -131,11 +387,14 @@ pub struct WidgetSendMessageRequest {
     pub message: String,
 }
 
-#[cfg_attr(feature = "openapi", utoipa::path(post, path = "/api/widget/conversations/{id}/messages", tag = "Widget", security(("embed_key" = [])), params(("id" = String, Path, description = "Conversation ID")), request_body = WidgetSendMessageRequest, responses((status = 200, description = "SSE stream of assistant response"))))]
+#[cfg_attr(feature = "openapi", utoipa::path(post, path = "/api/widget/conversations/{id}/messages", tag = "Widget", security(("embed_key" = [])), params(("id" = String, Path, description = "Conversation ID"), LocaleQuery), request_body = WidgetSendMessageRequest, responses((status = 200, description = "SSE stream of the assistant response, with the same `token` / `done` / `error` events as the chat API"), (status = 403, description = "`widget_offline`: outside office hours with `offline_behavior` `block`; the error is the offline message"), (status = 409, description = "`generation_in_progress`: a reply to an earlier message is still being generated"), (status = 429, description = "Session message limit reached, or `quota_exceeded` when the embed key's daily or monthly cap is used up"))))]
 pub async fn send_message(
     State(state): State<AppState>,
     ctx: EmbedContext,
     Path(conversation_id): Path<String>,
+    Query(locale_query): Query<LocaleQuery>,
+    client_ip: ClientIp,
+    headers: HeaderMap,
     Json(payload): Json<WidgetSendMessageRequest>,
 ) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, AppError> {
     if !state.config.features.widget_enabled {
@@ -146,6 +405,14 @@ pub async fn send_message(
         return Err(AppError::Validation("Message cannot be empty".to_string()));
     }
 
+    // Visitors can still leave their email through the handoff form
+    if ctx.embed_key.offline_behavior == OfflineBehavior::Block
+        && !office_hours::is_online(&ctx.embed_key, chrono::Utc::now())
+    {
+        let localized = visitor_localization(&ctx.embed_key, &locale_query, &headers);
+        return Err(AppError::Offline(offline_message(&ctx.embed_key, localized)));
+    }
+
     // Verify conversation belongs to this session + embed key
     state
         .conversation_repo
@@ -153,155 +420,385 @@ pub async fn send_message(
         .await?
         .ok_or_else(|| AppError::NotFound("Conversation not found".to_string()))?;
 
+    // One send at a time, so exchanges from two tabs can't interleave
+    let guard = state
+        .conversation_locks
+        .try_lock(&conversation_id)
+        .ok_or(AppError::GenerationInProgress)?;
+
     // Rate limit check
-    let msg_count = state
+    let counted = state
         .widget_session_repo
-        .increment_message_count(&ctx.embed_key.id, &ctx.session_id)
+        .increment_message_count(&ctx.embed_key.id, &ctx.session_id, ctx.embed_key.rate_limit)
         .await?;
 
-    if msg_count > ctx.embed_key.rate_limit {
+    if counted.is_none() {
+        tracing::warn!(
+            embed_key_id = %ctx.embed_key.id,
+            session_id = %ctx.session_id,
+            client_ip = %client_ip,
+            "Widget session reached its message limit"
+        );
         return Err(AppError::RateLimited);
     }
 
+    if let Err(e) = enforce_message_caps(&state, &ctx.embed_key, &client_ip).await {
+        // Refused by the key's cap, so the send doesn't use up the session's messages
+        state
+            .widget_session_repo
+            .decrement_message_count(&ctx.embed_key.id, &ctx.session_id)
+            .await?;
+        return Err(e);
+    }
+
+    let history = state.conversation_repo.get_messages(&conversation_id).await?;
+
     // Persist user message
     state
         .conversation_repo
-        .add_message(&conversation_id, "user", &payload.message)
+        .add_message(&conversation_id, "user", &payload.message, None)
         .await?;
 
-    // Resolve provider/model from embed key config or system defaults
-    let provider_name = if ctx.embed_key.provider.is_empty() {
-        state.config.llm.default_provider.clone()
-    } else {
-        ctx.embed_key.provider.clone()
+    // A prompt for the visitor's locale lets the bot answer in their language
+    let localized_prompt = visitor_localization(&ctx.embed_key, &locale_query, &headers)
+        .and_then(|(_, l)| l.system_prompt.clone());
+    let chat = ChatRequestContext::for_widget(
+        &state,
+        &ctx.embed_key,
+        &conversation_id,
+        localized_prompt,
+        history,
+    )
+    .await?;
+    let query = chat_service::retrieval_query(&state, &chat, &payload.message).await;
+    let context = chat_service::retrieve_context(&state, &chat.scope, &query).await;
+
+    let audit_queue = state.audit.clone();
+    let after_reply = move |_: &str| {
+        audit::log(
+            &audit_queue,
+            None,
+            "widget.message",
+            Some("conversation"),
+            Some(&conversation_id),
+            "Widget chat message",
+            Some(&client_ip.to_string()),
+            None,
+        );
     };
 
-    let model_name = if ctx.embed_key.model.is_empty() {
-        state.config.llm.default_model.clone()
-    } else {
-        ctx.embed_key.model.clone()
-    };
+    let stream = chat_service::generate_reply(
+        state.clone(),
+        chat,
+        guard,
+        payload.message,
+        context,
+        Vec::new(),
+        after_reply,
+    )?;
+    Ok(Sse::new(stream).keep_alive(sse::keep_alive()))
+}
 
-    let api_key = if !ctx.embed_key.api_key_encrypted.is_empty() {
-        ctx.embed_key.api_key_encrypted.clone()
-    } else {
-        // Fall back to any configured API key for this provider
-        state
-            .settings_repo
-            .get_any_api_key_for_provider(&provider_name)
-            .await?
-            .ok_or_else(|| {
-                AppError::Validation(
-                    "No API key configured for this provider. Contact the administrator."
-                        .to_string(),
-                )
-            })?
+/// Shares of a message cap at which admins are alerted.
+const CAP_ALERT_PERCENTS: [u8; 2] = [80, 100];
+
+/// The alert share `used` messages just reached. Every count is seen by one
+/// request only, so each share alerts once per day or month.
+fn reached_cap_alert(used: i64, limit: i32) -> Option<u8> {
+    // The smallest count at or above the share; 100% comes first for tiny caps
+    CAP_ALERT_PERCENTS
+        .into_iter()
+        .rev()
+        .find(|&percent| used == (i64::from(limit) * i64::from(percent) + 99) / 100)
+}
+
+/// Count the message against the embed key's daily and monthly caps, which
+/// hold across sessions, and alert admins as a cap fills up.
+async fn enforce_message_caps(
+    state: &AppState,
+    embed_key: &EmbedKey,
+    client_ip: &ClientIp,
+) -> Result<(), AppError> {
+    let Some(usage) = state.embed_key_repo.record_message(embed_key).await? else {
+        tracing::warn!(
+            embed_key_id = %embed_key.id,
+            client_ip = %client_ip,
+            "Embed key reached its message cap"
+        );
+        return Err(AppError::QuotaExceeded(
+            "This chat has reached its message limit. Please try again later.".to_string(),
+        ));
     };
 
-    let system_prompt = if ctx.embed_key.system_prompt.is_empty() {
-        state.config.llm.default_system_prompt.clone()
-    } else {
-        ctx.embed_key.system_prompt.clone()
+    let caps = [
+        ("daily", usage.messages_today, embed_key.daily_message_limit),
+        ("monthly", usage.messages_this_month, embed_key.monthly_message_limit),
+    ];
+    for (period, used, limit) in caps {
+        let Some(limit) = limit else { continue };
+        let Some(percent) = reached_cap_alert(used, limit) else { continue };
+        alert_message_cap(state, embed_key, period, used, limit, percent).await;
+    }
+    Ok(())
+}
+
+async fn alert_message_cap(
+    state: &AppState,
+    embed_key: &EmbedKey,
+    period: &'static str,
+    used: i64,
+    limit: i32,
+    percent: u8,
+) {
+    audit::log(
+        &state.audit,
+        None,
+        "widget.message_cap",
+        Some("embed_key"),
+        Some(&embed_key.id),
+        &format!(
+            "Embed key '{}' used {percent}% of its {period} message cap ({used} of {limit})",
+            embed_key.name
+        ),
+        None,
+        None,
+    );
+
+    if !state.config.widget.message_cap_alert_emails {
+        return;
+    }
+    let admins = match state.user_repo.emails_by_role(&UserRole::Admin).await {
+        Ok(admins) => admins,
+        Err(e) => {
+            tracing::error!("Failed to look up admins for a message cap alert: {e:#}");
+            return;
+        }
     };
+    for to in admins {
+        let message = EmailMessage::MessageCapAlert {
+            widget_name: embed_key.name.clone(),
+            period: period.to_string(),
+            used,
+            limit,
+            percent,
+        };
+        if let Err(e) = state.email_outbox.enqueue(&to, message, Some(&embed_key.id)).await {
+            tracing::error!("Failed to queue message cap alert: {e:#}");
+        }
+    }
+}
+
+// ── Human handoff ────────────────────────────────────────
+
+/// Handoffs a session may submit per `HANDOFF_WINDOW`.
+const HANDOFF_LIMIT: i64 = 3;
+const HANDOFF_WINDOW: Duration = Duration::from_secs(24 * 60 * 60);
+const MAX_HANDOFF_MESSAGE_CHARS: usize = 5000;
 
-    // RAG context retrieval
-    let mut rag_context = String::new();
+#[derive(Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct HandoffRequest {
+    pub email: String,
+    #[serde(default)]
+    pub message: String,
+}
+
+/// Leave an email address for a person to follow up on the conversation.
+#[cfg_attr(feature = "openapi", utoipa::path(post, path = "/api/widget/conversations/{id}/handoff", tag = "Widget", security(("embed_key" = [])), params(("id" = String, Path, description = "Conversation ID")), request_body = HandoffRequest, responses((status = 200, body = WidgetHandoff))))]
+pub async fn request_handoff(
+    State(state): State<AppState>,
+    ctx: EmbedContext,
+    Path(conversation_id): Path<String>,
+    client_ip: ClientIp,
+    Json(payload): Json<HandoffRequest>,
+) -> Result<Json<WidgetHandoff>, AppError> {
+    if !state.config.features.widget_enabled {
+        return Err(AppError::FeatureDisabled("Widget".to_string()));
+    }
+    if !ctx.embed_key.handoff_enabled {
+        return Err(AppError::FeatureDisabled("Human handoff".to_string()));
+    }
 
-    let embedding_model_name = state.config.llm.default_embedding_model.clone();
+    let email = payload.email.trim();
+    if !looks_like_email(email) {
+        return Err(AppError::Validation("A valid email address is required".to_string()));
+    }
+    let message = payload.message.trim();
+    if message.chars().count() > MAX_HANDOFF_MESSAGE_CHARS {
+        return Err(AppError::Validation(format!(
+            "Message must be at most {MAX_HANDOFF_MESSAGE_CHARS} characters"
+        )));
+    }
 
-    if let Ok(emb_client) = llm_provider::create_embeddings_client(&provider_name, &api_key) {
-        let emb_model = rig::client::embeddings::EmbeddingsClientDyn::embedding_model(
-            emb_client.as_ref(),
-            &embedding_model_name,
+    // Verify conversation belongs to this session + embed key
+    state
+        .conversation_repo
+        .get_widget(&conversation_id, &ctx.session_id, &ctx.embed_key.id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Conversation not found".to_string()))?;
+
+    let recent = state
+        .widget_handoff_repo
+        .count_recent_for_session(&ctx.embed_key.id, &ctx.session_id, HANDOFF_WINDOW)
+        .await?;
+    if recent >= HANDOFF_LIMIT {
+        tracing::warn!(
+            embed_key_id = %ctx.embed_key.id,
+            session_id = %ctx.session_id,
+            client_ip = %client_ip,
+            "Widget session reached its handoff limit"
         );
+        return Err(AppError::RateLimited);
+    }
+
+    let handoff = state
+        .widget_handoff_repo
+        .create(&conversation_id, &ctx.embed_key.id, &ctx.session_id, email, message)
+        .await?;
 
-        match emb_model.embed_text(&payload.message).await {
-            Ok(query_embedding) => {
-                match state.vector_service.search(query_embedding.vec, 5).await {
-                    Ok(results) if !results.is_empty() => {
-                        let context_parts: Vec<String> = results
-                            .iter()
-                            .filter(|r| !r.content.is_empty())
-                            .map(|r| r.content.clone())
-                            .collect();
-
-                        if !context_parts.is_empty() {
-                            rag_context = format!(
-                                "\n\nUse the following context from the knowledge base to help answer the user's question. If the context is not relevant, you may ignore it.\n\n---\n{}\n---\n",
-                                context_parts.join("\n\n")
-                            );
-                        }
-                    }
-                    Ok(_) => {}
-                    Err(e) => {
-                        tracing::warn!("Widget RAG search failed: {e}");
-                    }
-                }
-            }
-            Err(e) => {
-                tracing::warn!("Widget failed to embed query for RAG: {e}");
-            }
+    if let Some(to) = &ctx.embed_key.handoff_notification_email {
+        let message = EmailMessage::HandoffNotification {
+            widget_name: ctx.embed_key.name.clone(),
+            visitor_email: handoff.email.clone(),
+            message: handoff.message.clone(),
+        };
+        if let Err(e) = state.email_outbox.enqueue(to, message, Some(&handoff.id)).await {
+            tracing::error!("Failed to queue handoff notification: {e:#}");
         }
     }
 
-    // Build final system prompt
-    let final_system_prompt = format!("{system_prompt}{rag_context}");
+    audit::log(
+        &state.audit,
+        None,
+        "widget.handoff",
+        Some("conversation"),
+        Some(&conversation_id),
+        &format!("Visitor of embed key '{}' asked for a person", ctx.embed_key.name),
+        Some(&client_ip.to_string()),
+        None,
+    );
+
+    Ok(Json(handoff))
+}
 
-    // Create completion client
-    let completion_client = llm_provider::create_completion_client(&provider_name, &api_key)
-        .map_err(AppError::Internal)?;
+// ── Transcript emails ────────────────────────────────────
 
-    let agent = completion_client
-        .agent(&model_name)
-        .preamble(&final_system_prompt)
-        .build();
+#[derive(Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TranscriptEmailRequest {
+    pub email: String,
+}
 
-    let message = payload.message.clone();
+/// How a visitor's address appears in the audit log: hashed, so sends can be
+/// correlated without keeping the address itself.
+fn email_fingerprint(email: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(normalize_email(email).as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+/// Email the visitor a transcript of the conversation, branded with the
+/// widget's title. Only for embed keys that allow it.
+#[cfg_attr(feature = "openapi", utoipa::path(post, path = "/api/widget/conversations/{id}/email-transcript", tag = "Widget", security(("embed_key" = [])), params(("id" = String, Path, description = "Conversation ID")), request_body = TranscriptEmailRequest, responses((status = 202, description = "Transcript queued for sending"), (status = 404, description = "Not a conversation of this session"), (status = 429, description = "Too many transcript emails"))))]
+pub async fn email_transcript(
+    State(state): State<AppState>,
+    ctx: EmbedContext,
+    Path(conversation_id): Path<String>,
+    client_ip: ClientIp,
+    Json(payload): Json<TranscriptEmailRequest>,
+) -> Result<StatusCode, AppError> {
+    if !state.config.features.widget_enabled {
+        return Err(AppError::FeatureDisabled("Widget".to_string()));
+    }
+    if !ctx.embed_key.transcript_email_enabled {
+        return Err(AppError::FeatureDisabled("Transcript emails".to_string()));
+    }
 
-    // Get LLM response
-    let response = agent
-        .prompt(&message)
-        .await
-        .map_err(|e| AppError::Internal(anyhow::anyhow!("LLM error: {e}")))?;
+    let email = payload.email.trim();
+    if !looks_like_email(email) {
+        return Err(AppError::Validation("A valid email address is required".to_string()));
+    }
 
-    // Persist assistant message
-    state
+    // Verify conversation belongs to this session + embed key
+    let conversation = state
         .conversation_repo
-        .add_message(&conversation_id, "assistant", &response)
-        .await?;
-
-    // Update conversation timestamp
-    let _ = state.conversation_repo.touch(&conversation_id).await;
+        .get_widget(&conversation_id, &ctx.session_id, &ctx.embed_key.id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Conversation not found".to_string()))?;
 
-    // Fire-and-forget: audit log + stats
-    let audit_repo = state.audit_log_repo.clone();
-    let embed_key_repo = state.embed_key_repo.clone();
-    let key_id = ctx.embed_key.id.clone();
-    let conv_id = conversation_id.clone();
-    tokio::spawn(async move {
-        audit::log(
-            &audit_repo,
-            None,
-            "widget.message",
-            Some("conversation"),
-            Some(&conv_id),
-            "Widget chat message",
-            None,
-            None,
+    // Per session, then per client IP and per embed key so fresh sessions can't
+    // be used to send mail to arbitrary addresses; each is only counted once
+    // the narrower ones have allowed the email
+    let limit_reached = if !state
+        .transcript_email_limiter
+        .allow((ctx.embed_key.id.clone(), ctx.session_id.clone()))
+    {
+        Some("Widget session reached its transcript email limit")
+    } else if !state.transcript_email_ip_limiter.allow(client_ip.0) {
+        Some("Client IP reached its transcript email limit")
+    } else if !state.transcript_email_key_limiter.allow(ctx.embed_key.id.clone()) {
+        Some("Embed key reached its daily transcript email limit")
+    } else {
+        None
+    };
+    if let Some(reason) = limit_reached {
+        tracing::warn!(
+            embed_key_id = %ctx.embed_key.id,
+            session_id = %ctx.session_id,
+            client_ip = %client_ip,
+            "{reason}"
         );
-        let _ = embed_key_repo.increment_stats(&key_id, 0, 2).await;
-    });
+        return Err(AppError::RateLimited);
+    }
+
+    let messages = state.conversation_repo.get_messages(&conversation_id).await?;
+    let widget_title = ctx.embed_key.widget_title.clone();
+    let message = EmailMessage::ConversationTranscript {
+        transcript: transcript::render_markdown(&conversation.title, &messages, &widget_title),
+        widget_title,
+    };
+    state
+        .email_outbox
+        .enqueue(email, message, Some(&conversation_id))
+        .await?;
 
-    // Stream response as SSE
-    let words: Vec<String> = response
-        .split_inclusive(' ')
-        .map(|s| s.to_string())
-        .collect();
+    audit::log(
+        &state.audit,
+        None,
+        "widget.transcript_email",
+        Some("conversation"),
+        Some(&conversation_id),
+        &format!("Visitor of embed key '{}' emailed themselves a transcript", ctx.embed_key.name),
+        Some(&client_ip.to_string()),
+        Some(serde_json::json!({ "email_sha256": email_fingerprint(email) })),
+    );
+
+    Ok(StatusCode::ACCEPTED)
+}
 
-    let stream = tokio_stream::iter(words)
-        .throttle(std::time::Duration::from_millis(20))
-        .map(|word| Ok(Event::default().data(word)))
-        .chain(tokio_stream::once(Ok(Event::default().data("[DONE]"))));
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_email_fingerprint_ignores_case_and_whitespace() {
+        let fingerprint = email_fingerprint("visitor@example.com");
+        assert_eq!(fingerprint.len(), 64);
+        assert!(!fingerprint.contains("visitor"));
+        assert_eq!(email_fingerprint(" Visitor@Example.COM "), fingerprint);
+        assert_ne!(email_fingerprint("other@example.com"), fingerprint);
+    }
 
-    Ok(Sse::new(stream))
+    #[test]
+    fn test_reached_cap_alert() {
+        assert_eq!(reached_cap_alert(7, 10), None);
+        assert_eq!(reached_cap_alert(8, 10), Some(80));
+        assert_eq!(reached_cap_alert(9, 10), None);
+        assert_eq!(reached_cap_alert(10, 10), Some(100));
+        // 80% of 7 rounds up to 6
+        assert_eq!(reached_cap_alert(5, 7), None);
+        assert_eq!(reached_cap_alert(6, 7), Some(80));
+        // Both shares of a cap of one are its only message
+        assert_eq!(reached_cap_alert(1, 1), Some(100));
+    }
 }
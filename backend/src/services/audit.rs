@@ -1,7 +1,64 @@
-use crate::db::models::audit_log::AuditLogRepository;
+use anyhow::Result;
 
+use crate::db::models::audit_log::{AuditEntry, AuditLogRepository};
+use crate::services::batch_writer::BatchQueue;
+use crate::services::tasks::BackgroundTasks;
+
+/// Entries waiting to be written before new ones are dropped.
+const QUEUE_CAPACITY: usize = 10_000;
+
+/// Audit log writer. [`log`] queues an entry for a background task that writes
+/// in batches and retries failures; [`log_critical`] writes before returning.
+#[derive(Clone)]
+pub struct AuditQueue {
+    queue: BatchQueue<AuditEntry>,
+    repo: AuditLogRepository,
+}
+
+impl AuditQueue {
+    /// Start the writer. On shutdown it writes what is already queued and stops;
+    /// entries logged after that are counted as lost.
+    pub fn start(repo: AuditLogRepository, tasks: &BackgroundTasks) -> Self {
+        let writer_repo = repo.clone();
+        let queue = BatchQueue::start("audit log entries", QUEUE_CAPACITY, tasks, move |batch| {
+            let repo = writer_repo.clone();
+            async move { repo.create_batch(&batch).await }
+        });
+        Self { queue, repo }
+    }
+
+    /// Entries dropped because the queue was full or their writes kept failing.
+    pub fn lost(&self) -> u64 {
+        self.queue.lost()
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn entry(
+    user_id: Option<&str>,
+    event_type: &str,
+    resource_type: Option<&str>,
+    resource_id: Option<&str>,
+    description: &str,
+    ip_address: Option<&str>,
+    metadata: Option<serde_json::Value>,
+) -> AuditEntry {
+    AuditEntry {
+        user_id: user_id.map(str::to_string),
+        event_type: event_type.to_string(),
+        resource_type: resource_type.map(str::to_string),
+        resource_id: resource_id.map(str::to_string),
+        description: description.to_string(),
+        ip_address: ip_address.map(str::to_string),
+        metadata,
+        created_at: chrono::Utc::now(),
+    }
+}
+
+/// Queue an audit entry without waiting for it to be written.
+#[allow(clippy::too_many_arguments)]
 pub fn log(
-    repo: &AuditLogRepository,
+    queue: &AuditQueue,
     user_id: Option<&str>,
     event_type: &str,
     resource_type: Option<&str>,
@@ -10,28 +67,45 @@ pub fn log(
     ip_address: Option<&str>,
     metadata: Option<serde_json::Value>,
 ) {
-    let repo = repo.clone();
-    let user_id = user_id.map(|s| s.to_string());
-    let event_type = event_type.to_string();
-    let resource_type = resource_type.map(|s| s.to_string());
-    let resource_id = resource_id.map(|s| s.to_string());
-    let description = description.to_string();
-    let ip_address = ip_address.map(|s| s.to_string());
-
-    tokio::spawn(async move {
-        if let Err(e) = repo
-            .create(
-                user_id.as_deref(),
-                &event_type,
-                resource_type.as_deref(),
-                resource_id.as_deref(),
-                &description,
-                ip_address.as_deref(),
-                metadata,
-            )
-            .await
-        {
-            tracing::error!("Failed to write audit log: {e}");
+    queue
+        .queue
+        .push(entry(user_id, event_type, resource_type, resource_id, description, ip_address, metadata));
+}
+
+/// Write an audit entry before returning, for security-sensitive events such
+/// as logins, role changes and new keys. The action should fail if this does.
+#[allow(clippy::too_many_arguments)]
+pub async fn log_critical(
+    queue: &AuditQueue,
+    user_id: Option<&str>,
+    event_type: &str,
+    resource_type: Option<&str>,
+    resource_id: Option<&str>,
+    description: &str,
+    ip_address: Option<&str>,
+    metadata: Option<serde_json::Value>,
+) -> Result<()> {
+    queue
+        .repo
+        .create(&entry(user_id, event_type, resource_type, resource_id, description, ip_address, metadata))
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_full_queue_counts_lost_entries() {
+        let pool = sqlx::PgPool::connect_lazy("postgres://localhost/unused").unwrap();
+        let (queue, _rx) = BatchQueue::channel("audit log entries", 2);
+        let queue = AuditQueue {
+            queue,
+            repo: AuditLogRepository::new(pool),
+        };
+        for _ in 0..5 {
+            log(&queue, None, "test.event", None, None, "entry", None, None);
         }
-    });
+        assert_eq!(queue.lost(), 3);
+    }
 }
@@ -6,6 +6,8 @@ use argon2::{
 use jsonwebtoken::{encode, EncodingKey, Header};
 
 use crate::config::AuthConfig;
+use crate::db::models::impersonation::ImpersonationSession;
+use crate::db::models::user::User;
 use crate::middleware::auth::Claims;
 
 pub fn hash_password(password: &str) -> Result<String> {
@@ -44,14 +46,37 @@ pub fn generate_jwt(
         username: username.to_string(),
         role: role.to_string(),
         exp: expiration,
+        imp: None,
+        jti: None,
     };
 
-    let token = encode(
+    encode_claims(&claims, config)
+}
+
+/// A token that lets the admin in `session` act as `user` until the session
+/// ends or expires.
+pub fn generate_impersonation_jwt(
+    user: &User,
+    session: &ImpersonationSession,
+    config: &AuthConfig,
+) -> Result<String> {
+    let claims = Claims {
+        sub: user.id.clone(),
+        username: user.username.clone(),
+        role: user.role.to_string(),
+        exp: session.expires_at as usize,
+        imp: Some(session.admin_id.clone()),
+        jti: Some(session.id.clone()),
+    };
+
+    encode_claims(&claims, config)
+}
+
+fn encode_claims(claims: &Claims, config: &AuthConfig) -> Result<String> {
+    encode(
         &Header::default(),
-        &claims,
+        claims,
         &EncodingKey::from_secret(config.jwt_secret.as_bytes()),
     )
-    .context("Failed to generate JWT")?;
-
-    Ok(token)
+    .context("Failed to generate JWT")
 }
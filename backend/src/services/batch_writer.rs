@@ -0,0 +1,284 @@
+use anyhow::Result;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+
+use crate::services::tasks::BackgroundTasks;
+
+/// Largest batch written in one transaction.
+const MAX_BATCH: usize = 100;
+
+/// How long an item waits for its batch to fill before it is written anyway.
+const FLUSH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Attempts to write a batch before its items are given up on.
+const MAX_WRITE_ATTEMPTS: u32 = 5;
+
+/// Delay before retrying a failed batch; doubles with every further attempt.
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+
+/// Log every this many lost items, so a full queue doesn't flood the logs.
+const LOST_LOG_EVERY: u64 = 100;
+
+/// Queue for records that are written in the background, such as audit log
+/// entries. A writer task hands them to a write function in batches and
+/// retries failed batches, so callers never wait on the database.
+pub struct BatchQueue<T> {
+    tx: mpsc::Sender<T>,
+    lost: Arc<AtomicU64>,
+    /// What the items are, for log messages ("audit log entries").
+    name: &'static str,
+}
+
+impl<T> Clone for BatchQueue<T> {
+    fn clone(&self) -> Self {
+        Self {
+            tx: self.tx.clone(),
+            lost: self.lost.clone(),
+            name: self.name,
+        }
+    }
+}
+
+impl<T: Clone + Send + 'static> BatchQueue<T> {
+    /// Start the writer. On shutdown it writes what is already queued and stops;
+    /// items pushed after that are counted as lost.
+    pub fn start<W, F>(name: &'static str, capacity: usize, tasks: &BackgroundTasks, write: W) -> Self
+    where
+        W: FnMut(Vec<T>) -> F + Send + 'static,
+        F: Future<Output = Result<()>> + Send + 'static,
+    {
+        let (queue, rx) = Self::channel(name, capacity);
+        tasks.spawn(run_writer(rx, tasks.stopping().clone(), queue.lost.clone(), name, write));
+        queue
+    }
+
+    /// A queue whose items are received from the returned channel rather than
+    /// by a writer task.
+    pub(crate) fn channel(name: &'static str, capacity: usize) -> (Self, mpsc::Receiver<T>) {
+        let (tx, rx) = mpsc::channel(capacity);
+        let queue = Self {
+            tx,
+            lost: Arc::new(AtomicU64::new(0)),
+            name,
+        };
+        (queue, rx)
+    }
+
+    /// Items dropped because the queue was full or their writes kept failing.
+    pub fn lost(&self) -> u64 {
+        self.lost.load(Ordering::Relaxed)
+    }
+
+    /// Queue an item without waiting; it is dropped if the queue is full.
+    pub fn push(&self, item: T) {
+        if let Err(e) = self.tx.try_send(item) {
+            let reason = match e {
+                mpsc::error::TrySendError::Full(_) => "queue full",
+                mpsc::error::TrySendError::Closed(_) => "writer stopped",
+            };
+            record_lost(&self.lost, 1, self.name, reason);
+        }
+    }
+}
+
+fn record_lost(lost: &AtomicU64, count: u64, name: &str, reason: &str) {
+    let before = lost.fetch_add(count, Ordering::Relaxed);
+    let total = before + count;
+    if before / LOST_LOG_EVERY != total / LOST_LOG_EVERY || before == 0 {
+        tracing::error!("Lost {count} {name}: {reason}; {total} lost since startup");
+    }
+}
+
+/// Receive items and hand them to `write` in batches of up to [`MAX_BATCH`],
+/// waiting at most [`FLUSH_INTERVAL`] for a batch to fill.
+pub(crate) async fn run_writer<T, W, F>(
+    mut rx: mpsc::Receiver<T>,
+    stopping: CancellationToken,
+    lost: Arc<AtomicU64>,
+    name: &'static str,
+    mut write: W,
+) where
+    T: Clone,
+    W: FnMut(Vec<T>) -> F,
+    F: Future<Output = Result<()>>,
+{
+    loop {
+        let first = tokio::select! {
+            item = rx.recv() => item,
+            _ = stopping.cancelled() => None,
+        };
+        let Some(first) = first else { break };
+
+        let mut batch = vec![first];
+        let deadline = Instant::now() + FLUSH_INTERVAL;
+        while batch.len() < MAX_BATCH {
+            tokio::select! {
+                item = rx.recv() => match item {
+                    Some(item) => batch.push(item),
+                    None => break,
+                },
+                _ = tokio::time::sleep_until(deadline) => break,
+            }
+        }
+        write_with_retry(&mut write, batch, &lost, name).await;
+    }
+
+    // Write whatever was queued before shutdown
+    rx.close();
+    let mut batch = Vec::new();
+    while let Ok(item) = rx.try_recv() {
+        batch.push(item);
+        if batch.len() == MAX_BATCH {
+            write_with_retry(&mut write, std::mem::take(&mut batch), &lost, name).await;
+        }
+    }
+    if !batch.is_empty() {
+        write_with_retry(&mut write, batch, &lost, name).await;
+    }
+    tracing::info!("Writer for {name} stopped");
+}
+
+async fn write_with_retry<T, W, F>(write: &mut W, batch: Vec<T>, lost: &AtomicU64, name: &str)
+where
+    T: Clone,
+    W: FnMut(Vec<T>) -> F,
+    F: Future<Output = Result<()>>,
+{
+    let mut attempt = 1;
+    loop {
+        match write(batch.clone()).await {
+            Ok(()) => return,
+            Err(e) if attempt >= MAX_WRITE_ATTEMPTS => {
+                record_lost(lost, batch.len() as u64, name, &format!("{e:#}"));
+                return;
+            }
+            Err(e) => {
+                let delay = RETRY_BASE_DELAY * 2u32.pow(attempt - 1);
+                tracing::warn!(
+                    "Failed to write {} {name} (attempt {attempt}), retrying in {delay:?}: {e:#}",
+                    batch.len()
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Run the writer over `items`, recording the batches it writes. `failures`
+    /// makes that many writes fail first.
+    async fn write_all(items: usize, failures: usize) -> (Vec<usize>, usize, u64) {
+        let (tx, rx) = mpsc::channel(1000);
+        for n in 0..items {
+            tx.send(n).await.unwrap();
+        }
+        drop(tx);
+
+        let batches = Arc::new(Mutex::new(Vec::new()));
+        let attempts = Arc::new(Mutex::new(0));
+        let lost = Arc::new(AtomicU64::new(0));
+        let (written, tried) = (batches.clone(), attempts.clone());
+        run_writer(rx, CancellationToken::new(), lost.clone(), "test items", move |batch| {
+            let (written, tried) = (written.clone(), tried.clone());
+            async move {
+                let mut tried = tried.lock().unwrap();
+                *tried += 1;
+                if *tried <= failures {
+                    anyhow::bail!("database unavailable");
+                }
+                written.lock().unwrap().push(batch.len());
+                Ok(())
+            }
+        })
+        .await;
+
+        let batches = batches.lock().unwrap().clone();
+        let attempts = *attempts.lock().unwrap();
+        (batches, attempts, lost.load(Ordering::Relaxed))
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_writes_in_batches() {
+        let (batches, attempts, lost) = write_all(250, 0).await;
+        assert_eq!(batches, vec![100, 100, 50]);
+        assert_eq!((attempts, lost), (3, 0));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_partial_batch_is_written_after_the_flush_interval() {
+        let (tx, rx) = mpsc::channel(10);
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let sink = written.clone();
+        let writer = tokio::spawn(run_writer(rx, CancellationToken::new(), Arc::default(), "test items", move |batch: Vec<u32>| {
+            let sink = sink.clone();
+            async move {
+                sink.lock().unwrap().push(batch.len());
+                Ok(())
+            }
+        }));
+
+        tx.send(0).await.unwrap();
+        tx.send(1).await.unwrap();
+        tokio::time::sleep(FLUSH_INTERVAL / 2).await;
+        assert!(written.lock().unwrap().is_empty());
+        tokio::time::sleep(FLUSH_INTERVAL).await;
+        assert_eq!(*written.lock().unwrap(), vec![2]);
+
+        drop(tx);
+        writer.await.unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_failed_batches_are_retried() {
+        let (batches, attempts, lost) = write_all(3, 2).await;
+        assert_eq!(batches, vec![3]);
+        assert_eq!((attempts, lost), (3, 0));
+
+        // A batch that never goes through is counted as lost
+        let (batches, attempts, lost) = write_all(3, usize::MAX).await;
+        assert!(batches.is_empty());
+        assert_eq!((attempts, lost), (MAX_WRITE_ATTEMPTS as usize, 3));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_queue_flushes_on_shutdown() {
+        let (tx, rx) = mpsc::channel(10);
+        let stopping = CancellationToken::new();
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let sink = written.clone();
+        for n in 0..3u32 {
+            tx.send(n).await.unwrap();
+        }
+        stopping.cancel();
+        run_writer(rx, stopping, Arc::default(), "test items", move |batch| {
+            let sink = sink.clone();
+            async move {
+                sink.lock().unwrap().push(batch.len());
+                Ok(())
+            }
+        })
+        .await;
+        assert_eq!(*written.lock().unwrap(), vec![3]);
+        // Nothing can be queued once the writer has stopped
+        assert!(tx.try_send(3).is_err());
+    }
+
+    #[test]
+    fn test_full_queue_counts_lost_items() {
+        let (queue, _rx) = BatchQueue::channel("test items", 2);
+        for n in 0..5u32 {
+            queue.push(n);
+        }
+        assert_eq!(queue.lost(), 3);
+    }
+}
@@ -0,0 +1,900 @@
+use axum::response::sse::Event;
+use futures::stream::Stream;
+use serde::Serialize;
+use rig::completion::Message as PromptMessage;
+use std::convert::Infallible;
+use std::time::Instant;
+use tracing::Instrument;
+
+use crate::db::models::conversation::{ConversationSettings, ConversationSummary, Message, MessageGeneration};
+use crate::db::models::embed_key::EmbedKey;
+use crate::db::models::settings::ProviderCredentials;
+use crate::errors::AppError;
+use crate::services::chunk_search::{self, RetrievalParams};
+use crate::services::conversation_locks::ConversationGuard;
+use crate::services::embed_key_check;
+use crate::services::embedding::ResolvedEmbedding;
+use crate::services::history::{self, Trimmed};
+use crate::services::llm_provider::{ChatRequest, ModelRef};
+use crate::services::provider_failure::{provider_name, ProviderFailure};
+use crate::services::query_expansion;
+use crate::services::rerank::RerankService;
+use crate::services::sse;
+use crate::services::tokens::ModelTokens;
+use crate::services::vector::{SearchFilter, SearchResult};
+use crate::state::AppState;
+
+/// Where a chat request comes from, which decides the wording of errors shown
+/// to whoever is chatting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChatChannel {
+    /// A signed-in user of the app.
+    App,
+    /// An anonymous visitor using an embedded widget.
+    Widget,
+}
+
+impl ChatChannel {
+    pub(crate) fn unavailable_message(self) -> &'static str {
+        match self {
+            ChatChannel::App => "The model failed to respond. Please try again.",
+            ChatChannel::Widget => "The assistant is unavailable right now. Please try again.",
+        }
+    }
+
+    fn save_failed_message(self) -> &'static str {
+        match self {
+            ChatChannel::App => "Failed to save the reply. Please try again.",
+            ChatChannel::Widget => "Something went wrong. Please try again.",
+        }
+    }
+}
+
+/// What knowledge base content a chat request may draw on, and how much of it.
+#[derive(Debug, Clone)]
+pub struct RetrievalScope {
+    /// Model the query is embedded with; its collection is searched.
+    pub embedding: ResolvedEmbedding,
+    pub collection: String,
+    pub filter: SearchFilter,
+    pub params: RetrievalParams,
+    /// User whose Cohere key reranks results; `None` uses the organization key.
+    pub user_id: Option<String>,
+}
+
+impl RetrievalScope {
+    /// What a signed-in user's chat searches: the collection for their embedding
+    /// model, or the organization default if nothing has been embedded with it yet.
+    pub async fn for_user(state: &AppState, user_id: &str, filter: SearchFilter) -> Result<Self, AppError> {
+        let preferred = state.embedding.target_for_user(user_id).await?;
+        let preferred_collection = state.vector_service.collection_for_model(&preferred.model);
+        let (target, collection) = if state
+            .vector_service
+            .collection_exists(&preferred_collection)
+            .await
+            .unwrap_or(false)
+        {
+            (preferred, preferred_collection)
+        } else {
+            tracing::debug!(
+                "No collection for embedding model '{}', falling back to default",
+                preferred.model
+            );
+            let default = state.embedding.default_target().await?;
+            let collection = state.vector_service.collection_for_model(&default.model);
+            (default, collection)
+        };
+        let embedding_credentials = state
+            .embedding
+            .credentials_for(Some(user_id), &target)
+            .await
+            .ok()
+            .flatten();
+
+        Ok(Self {
+            embedding: ResolvedEmbedding {
+                target,
+                credentials: embedding_credentials,
+            },
+            collection,
+            filter,
+            params: RetrievalParams::from_config(&state.config.llm),
+            user_id: Some(user_id.to_string()),
+        })
+    }
+}
+
+/// Everything needed to answer one message in a conversation.
+#[derive(Debug, Clone)]
+pub struct ChatRequestContext {
+    pub channel: ChatChannel,
+    pub conversation_id: String,
+    pub provider: String,
+    pub model: String,
+    pub credentials: ProviderCredentials,
+    /// Providers to retry the reply with, in order, when `provider` is down.
+    pub fallback_providers: Vec<String>,
+    /// Signed-in user whose keys fallback providers are called with; `None`
+    /// for widget visitors, who use the organization's.
+    pub user_id: Option<String>,
+    pub system_prompt: String,
+    /// Earlier messages of the conversation, oldest first, without the new one.
+    pub history: Vec<Message>,
+    /// Summary of the messages before the recent ones sent verbatim.
+    pub summary: ConversationSummary,
+    pub scope: RetrievalScope,
+}
+
+/// The model and system prompt a user's conversation actually answers with.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct EffectiveChatSettings {
+    pub provider: String,
+    pub model: String,
+    pub system_prompt: String,
+}
+
+/// Why a chosen provider or model can't be used, if an admin has disabled it.
+async fn unavailable_model(state: &AppState, provider: &str, model: &str) -> Result<Option<String>, AppError> {
+    if !state.admin_config_repo.is_provider_enabled(provider).await? {
+        return Ok(Some(format!("provider '{provider}'")));
+    }
+    if state.admin_config_repo.is_model_disabled(provider, model).await? {
+        return Ok(Some(format!("model '{model}'")));
+    }
+    Ok(None)
+}
+
+/// Settings for a user's conversation: its own overrides first, then the
+/// user's preferences, then the configured defaults. A choice an admin has
+/// since disabled is skipped with a warning for the user.
+pub async fn resolve_chat_settings(
+    state: &AppState,
+    user_id: &str,
+    overrides: &ConversationSettings,
+) -> Result<(EffectiveChatSettings, Vec<String>), AppError> {
+    let prefs = state.settings_repo.get_preferences(user_id).await?;
+    let mut warnings = Vec::new();
+
+    let mut chosen = None;
+    if let (Some(provider), Some(model)) = (&overrides.provider, &overrides.model) {
+        match unavailable_model(state, provider, model).await? {
+            None => chosen = Some((provider.clone(), model.clone())),
+            Some(what) => {
+                tracing::warn!("A conversation of user {user_id} uses disabled {what}, ignoring it");
+                warnings.push(format!(
+                    "This conversation's {what} is no longer available, so another model \
+                     answered instead. Choose another in the conversation settings."
+                ));
+            }
+        }
+    }
+    if chosen.is_none()
+        && let Some(p) = prefs.as_ref().filter(|p| !p.preferred_provider.is_empty())
+    {
+        match unavailable_model(state, &p.preferred_provider, &p.preferred_model).await? {
+            None => chosen = Some((p.preferred_provider.clone(), p.preferred_model.clone())),
+            Some(what) => {
+                tracing::warn!("User {user_id} prefers disabled {what}, using the default");
+                warnings.push(format!(
+                    "Your preferred {what} is no longer available, so the default model \
+                     answered instead. Choose another in Settings."
+                ));
+            }
+        }
+    }
+    let (provider, model) = chosen.unwrap_or_else(|| {
+        (
+            state.config.llm.default_provider.clone(),
+            state.config.llm.default_model.clone(),
+        )
+    });
+
+    let system_prompt = overrides
+        .system_prompt
+        .clone()
+        .or_else(|| prefs.map(|p| p.system_prompt).filter(|s| !s.is_empty()))
+        .unwrap_or_else(|| state.config.llm.default_system_prompt.clone());
+
+    let settings = EffectiveChatSettings {
+        provider,
+        model,
+        system_prompt,
+    };
+    Ok((settings, warnings))
+}
+
+/// The system prompt a reply is generated with: the organization's base prompt,
+/// which users can't remove, then the user's or embed key's own prompt.
+pub fn compose_system_prompt(base: &str, prompt: &str) -> String {
+    match (base.trim(), prompt.trim()) {
+        ("", _) => prompt.to_string(),
+        (base, "") => base.to_string(),
+        (base, prompt) => format!("{base}\n\n{prompt}"),
+    }
+}
+
+impl ChatRequestContext {
+    /// Context for a signed-in user: their preferred model, system prompt and
+    /// keys. Also returns warnings for the user, such as a preferred model
+    /// having been disabled.
+    pub async fn for_user(
+        state: &AppState,
+        user_id: &str,
+        conversation_id: &str,
+        history: Vec<Message>,
+        filter: SearchFilter,
+    ) -> Result<(Self, Vec<String>), AppError> {
+        let overrides = state.conversation_repo.get_settings(conversation_id).await?;
+        let (settings, warnings) = resolve_chat_settings(state, user_id, &overrides).await?;
+        let EffectiveChatSettings {
+            provider,
+            model,
+            system_prompt,
+        } = settings;
+        let base_prompt = state.org_settings_repo.base_system_prompt().await?;
+        let system_prompt = compose_system_prompt(&base_prompt, &system_prompt);
+
+        let credentials = state
+            .credentials
+            .resolve_for_use(user_id, &provider)
+            .await?
+            .map(|r| r.credentials)
+            .ok_or_else(|| {
+                AppError::Validation(format!(
+                    "No API key configured for provider '{provider}'. Add one in Settings."
+                ))
+            })?;
+
+        let preferred_fallbacks = state
+            .settings_repo
+            .get_preferences(user_id)
+            .await?
+            .map(|p| p.fallback_providers)
+            .unwrap_or_default();
+        let fallback_providers = fallback_order(&provider, &preferred_fallbacks, &state.config.llm.fallback_providers);
+
+        let scope = RetrievalScope::for_user(state, user_id, filter).await?;
+        let summary = state.conversation_repo.get_summary(conversation_id).await?;
+
+        let context = Self {
+            channel: ChatChannel::App,
+            conversation_id: conversation_id.to_string(),
+            provider,
+            model,
+            credentials,
+            fallback_providers,
+            user_id: Some(user_id.to_string()),
+            system_prompt,
+            history,
+            summary,
+            scope,
+        };
+        Ok((context, warnings))
+    }
+
+    /// Context for a widget visitor: the embed key's model, key and prompt,
+    /// falling back to the organization defaults. `localized_prompt` is the
+    /// prompt for the visitor's locale, if the embed key has one.
+    pub async fn for_widget(
+        state: &AppState,
+        embed_key: &EmbedKey,
+        conversation_id: &str,
+        localized_prompt: Option<String>,
+        history: Vec<Message>,
+    ) -> Result<Self, AppError> {
+        let (provider, model) = embed_key_check::effective_model(&state.config.llm, &embed_key.provider, &embed_key.model);
+
+        let credentials = if !embed_key.api_key_encrypted.is_empty() {
+            ProviderCredentials {
+                api_key: embed_key.api_key_encrypted.clone(),
+                base_url: embed_key.base_url.clone(),
+            }
+        } else {
+            // Fall back to the organization key for this provider
+            let fallback = state
+                .credentials
+                .resolve_shared(&provider)
+                .await?
+                .map(|r| r.credentials)
+                .ok_or_else(|| {
+                    AppError::Validation(
+                        "No API key configured for this provider. Contact the administrator."
+                            .to_string(),
+                    )
+                })?;
+            ProviderCredentials {
+                api_key: fallback.api_key,
+                base_url: embed_key.base_url.clone().or(fallback.base_url),
+            }
+        };
+
+        let system_prompt = match localized_prompt {
+            Some(prompt) => prompt,
+            None if embed_key.system_prompt.is_empty() => state.config.llm.default_system_prompt.clone(),
+            None => embed_key.system_prompt.clone(),
+        };
+        let base_prompt = state.org_settings_repo.base_system_prompt().await?;
+        let system_prompt = compose_system_prompt(&base_prompt, &system_prompt);
+
+        // Retrieval embeds with the organization's embedding provider: the key's
+        // own provider may have no embeddings API (Anthropic, Groq). An embed key
+        // with its own key for the embedding provider uses it for retrieval too.
+        let mut embedding = state.embedding.resolve_shared().await?;
+        if embedding.target.provider == provider && !embed_key.api_key_encrypted.is_empty() {
+            embedding.credentials = Some(credentials.clone());
+        }
+        state.embedding.note_widget_embedding(&embed_key.id, &embedding);
+        let collection = state.vector_service.collection_for_model(&embedding.target.model);
+        let summary = state.conversation_repo.get_summary(conversation_id).await?;
+
+        Ok(Self {
+            channel: ChatChannel::Widget,
+            conversation_id: conversation_id.to_string(),
+            fallback_providers: fallback_order(&provider, &[], &state.config.llm.fallback_providers),
+            provider,
+            model,
+            credentials,
+            user_id: None,
+            system_prompt,
+            history,
+            summary,
+            scope: RetrievalScope {
+                embedding,
+                collection,
+                filter: SearchFilter {
+                    collection_id: embed_key.collection_id.clone(),
+                    ..SearchFilter::default()
+                },
+                params: RetrievalParams::from_config(&state.config.llm).with_embed_key_overrides(embed_key),
+                user_id: None,
+            },
+        })
+    }
+}
+
+/// Providers to retry a reply with, in order: the user's own list, or else
+/// the organization's, without the provider that failed.
+fn fallback_order(primary: &str, preferred: &[String], configured: &[String]) -> Vec<String> {
+    let list = if preferred.is_empty() { configured } else { preferred };
+    let mut order: Vec<String> = Vec::with_capacity(list.len());
+    for provider in list {
+        if provider != primary && !order.contains(provider) {
+            order.push(provider.clone());
+        }
+    }
+    order
+}
+
+/// A provider answering in place of the conversation's own.
+struct Fallback {
+    provider: String,
+    model: String,
+    credentials: ProviderCredentials,
+}
+
+/// The first of `providers` that is enabled, has a default chat model and a
+/// key the request may use: `user_id`'s own or shared keys, or only the
+/// organization's for widget visitors.
+async fn resolve_fallback(state: &AppState, user_id: Option<&str>, providers: &[String]) -> Option<Fallback> {
+    for provider in providers {
+        let Some(model) = state
+            .admin_config_repo
+            .get_default_completion_model(provider)
+            .await
+            .ok()
+            .flatten()
+        else {
+            tracing::debug!("Skipping fallback provider {provider}: disabled or without a default chat model");
+            continue;
+        };
+        let resolved = match user_id {
+            Some(user_id) => state.credentials.resolve_for_use(user_id, provider).await,
+            None => state.credentials.resolve_shared(provider).await,
+        };
+        let Some(resolved) = resolved.ok().flatten() else {
+            tracing::debug!("Skipping fallback provider {provider}: no API key");
+            continue;
+        };
+        return Some(Fallback {
+            provider: provider.clone(),
+            model,
+            credentials: resolved.credentials,
+        });
+    }
+    None
+}
+
+/// What to search the knowledge base with for `message`. A follow-up is
+/// rewritten into a standalone question when `llm.query_rewrite_enabled` is on,
+/// or else joined with earlier user messages (and the summary) as configured.
+/// The first message of a conversation is searched as is.
+pub async fn retrieval_query(state: &AppState, ctx: &ChatRequestContext, message: &str) -> String {
+    if ctx.history.is_empty() {
+        return message.to_string();
+    }
+    let config = &state.config.llm;
+
+    let mut query = None;
+    if config.query_rewrite_enabled {
+        let model = history::summary_model(config, &ctx.provider).unwrap_or(&ctx.model);
+        let rewritten = query_expansion::rewrite_query(
+            state.completion_backend.as_ref(),
+            ModelRef {
+                provider: &ctx.provider,
+                model,
+                api_key: &ctx.credentials.api_key,
+                base_url: ctx.credentials.base_url.as_deref(),
+            },
+            message,
+            &ctx.history,
+            &ctx.summary,
+        )
+        .await;
+        match rewritten {
+            Ok(rewritten) => query = Some(rewritten),
+            Err(e) => tracing::warn!(
+                "Query rewrite failed in conversation {}, joining messages instead: {e:#}",
+                ctx.conversation_id
+            ),
+        }
+    }
+    let query = query.unwrap_or_else(|| {
+        query_expansion::expanded_query(
+            message,
+            &ctx.history,
+            &ctx.summary,
+            config.query_history_messages,
+            config.query_include_summary,
+        )
+    });
+    tracing::debug!(
+        "Retrieval query in conversation {}: {message:?} searched as {query:?}",
+        ctx.conversation_id
+    );
+    query
+}
+
+/// Knowledge base chunks for `query`, best first. Empty when nothing relevant
+/// is found or retrieval fails; a failed search never stops the reply. Unlike
+/// completions, a failed embedding is not retried with a fallback provider:
+/// its vectors couldn't be compared with the collection's.
+pub async fn retrieve_context(state: &AppState, scope: &RetrievalScope, query: &str) -> Vec<String> {
+    // Without an embedding key there is nothing to search with; not worth a warning
+    if scope.embedding.credentials.is_none() {
+        return Vec::new();
+    }
+    let results = match search_chunks(state, scope, query).await {
+        Ok(results) => results,
+        Err(e) => {
+            tracing::warn!("RAG retrieval failed: {e}");
+            return Vec::new();
+        }
+    };
+    let cohere_key = cohere_key(state, scope, &results).await;
+    assemble_context(&state.reranker, query, results, &scope.params, cohere_key.as_deref()).await
+}
+
+/// Every step of retrieving context for one query, as shown by the debug endpoint.
+#[derive(Debug, Clone)]
+pub struct RetrievalTrace {
+    /// Search results after reranking, best first.
+    pub results: Vec<SearchResult>,
+    /// Positions in `results` of the chunks that went into the context.
+    pub included: Vec<usize>,
+    pub context: String,
+}
+
+/// Run retrieval like [`retrieve_context`], but keep the ranked results and
+/// report failures instead of falling back to no context.
+pub async fn trace_retrieval(state: &AppState, scope: &RetrievalScope, query: &str) -> Result<RetrievalTrace, AppError> {
+    let results = search_chunks(state, scope, query).await?;
+    let cohere_key = cohere_key(state, scope, &results).await;
+    let (results, included) =
+        rank_and_select(&state.reranker, query, results, &scope.params, cohere_key.as_deref()).await;
+    let context_parts: Vec<String> = included.iter().map(|&i| results[i].content.clone()).collect();
+    Ok(RetrievalTrace {
+        context: chunk_search::format_rag_context(&context_parts),
+        results,
+        included,
+    })
+}
+
+/// Embed `query` and search the scope's collection for candidate chunks.
+async fn search_chunks(state: &AppState, scope: &RetrievalScope, query: &str) -> Result<Vec<SearchResult>, AppError> {
+    let embedding = &scope.embedding;
+    let credentials = embedding.credentials.as_ref().ok_or_else(|| {
+        AppError::Validation(format!(
+            "No API key configured for embedding provider '{}'",
+            embedding.target.provider
+        ))
+    })?;
+    let embedder = state
+        .embedding_backend
+        .embedder(ModelRef {
+            provider: &embedding.target.provider,
+            model: &embedding.target.model,
+            api_key: &credentials.api_key,
+            base_url: credentials.base_url.as_deref(),
+        })
+        .map_err(|e| AppError::Validation(e.to_string()))?;
+
+    let query_embedding = embedder
+        .embed_texts(vec![query.to_string()])
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to embed the query with {}/{}: {e:#}", embedding.target.provider, embedding.target.model);
+            ProviderFailure::classify(&e).into_app_error(ChatChannel::App, &embedding.target.provider, &embedding.target.model)
+        })?
+        .into_iter()
+        .next()
+        .ok_or_else(|| AppError::Unavailable("The embedding model returned no vector".to_string()))?;
+
+    state
+        .chunk_search
+        .search(
+            &scope.collection,
+            query,
+            query_embedding,
+            state.reranker.candidate_count(scope.params.top_k),
+            &scope.filter,
+        )
+        .await
+        .map_err(|e| AppError::Unavailable(format!("Search failed: {e:#}")))
+}
+
+/// The Cohere key to rerank `results` with, when the reranker needs one.
+async fn cohere_key(state: &AppState, scope: &RetrievalScope, results: &[SearchResult]) -> Option<String> {
+    if results.is_empty() || !state.reranker.needs_cohere_key() {
+        return None;
+    }
+    let resolved = match &scope.user_id {
+        Some(user_id) => state.credentials.resolve_for_use(user_id, "cohere").await,
+        None => state.credentials.resolve_shared("cohere").await,
+    };
+    resolved.ok().flatten().map(|r| r.credentials.api_key)
+}
+
+/// Rerank search results and keep the best that fit the context budget, best first.
+pub async fn assemble_context(
+    reranker: &RerankService,
+    query: &str,
+    results: Vec<SearchResult>,
+    params: &RetrievalParams,
+    cohere_key: Option<&str>,
+) -> Vec<String> {
+    let (results, included) = rank_and_select(reranker, query, results, params, cohere_key).await;
+    included.into_iter().map(|i| results[i].content.clone()).collect()
+}
+
+/// Reranked results and the positions of those that fit the context.
+async fn rank_and_select(
+    reranker: &RerankService,
+    query: &str,
+    results: Vec<SearchResult>,
+    params: &RetrievalParams,
+    cohere_key: Option<&str>,
+) -> (Vec<SearchResult>, Vec<usize>) {
+    if results.is_empty() {
+        return (results, Vec::new());
+    }
+
+    let results = reranker
+        .rerank(query, results, params.top_k as usize, cohere_key)
+        .await;
+    let included = chunk_search::select_context_indices(&results, params);
+    tracing::debug!(
+        "RAG included {}/{} retrieved chunks",
+        included.len(),
+        results.len()
+    );
+    (results, included)
+}
+
+/// Stored messages as chat history for the model. Only user and assistant
+/// messages are sent.
+fn prompt_history(history: &[Message]) -> Vec<PromptMessage> {
+    history
+        .iter()
+        .filter_map(|m| match m.role.as_str() {
+            "user" => Some(PromptMessage::user(m.content.clone())),
+            "assistant" => Some(PromptMessage::assistant(m.content.clone())),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The request for `message` to `provider`/`model`, with the prompt trimmed to
+/// fit that model's context window, and how many `context` chunks it kept.
+fn fit_request(
+    state: &AppState,
+    ctx: &ChatRequestContext,
+    provider: &str,
+    model: &str,
+    context: &[String],
+    message: &str,
+) -> (ChatRequest, usize) {
+    let tokens = ModelTokens { provider, model };
+    let (parts, trimmed) = history::fit_context_window(
+        &state.config.llm,
+        tokens,
+        &ctx.system_prompt,
+        &ctx.summary,
+        context,
+        message,
+        &ctx.history,
+    );
+    if trimmed != Trimmed::default() {
+        tracing::warn!(
+            "Prompt for conversation {} exceeded the {} token window of {provider}/{model}; left out {} knowledge base chunks and {} messages",
+            ctx.conversation_id,
+            tokens.context_window(),
+            trimmed.context_chunks,
+            trimmed.messages
+        );
+    }
+    let request = ChatRequest {
+        preamble: parts.preamble,
+        history: prompt_history(parts.history),
+        prompt: message.to_string(),
+    };
+    (request, context.len() - trimmed.context_chunks)
+}
+
+/// Answer `message` with the conversation summary and knowledge base `context`
+/// appended to the system prompt and trimmed to the model's context window.
+/// Saves the reply to the conversation and streams it as the chat event
+/// contract (see [`sse::reply_stream`]). `after_reply` runs once the reply is
+/// saved, as does the summary update.
+///
+/// When the model times out, rate limits or fails with a server error, the
+/// reply is retried once with the first usable of `ctx.fallback_providers`,
+/// with the prompt fitted again to the fallback model's context window.
+/// The saved reply records which provider answered, and app users get a
+/// notice event saying so.
+///
+/// The reply is generated inside the stream so keep-alive pings flow while the
+/// model works. `guard` is held until the reply is saved or abandoned.
+pub fn generate_reply<F>(
+    state: AppState,
+    ctx: ChatRequestContext,
+    guard: ConversationGuard,
+    message: String,
+    context: Vec<String>,
+    warnings: Vec<String>,
+    after_reply: F,
+) -> Result<impl Stream<Item = Result<Event, Infallible>>, AppError>
+where
+    F: FnOnce(&str) + Send + 'static,
+{
+    let model = state
+        .completion_backend
+        .chat_model(ModelRef {
+            provider: &ctx.provider,
+            model: &ctx.model,
+            api_key: &ctx.credentials.api_key,
+            base_url: ctx.credentials.base_url.as_deref(),
+        })
+        .map_err(|e| {
+            tracing::error!("Failed to set up {}/{} for conversation {}: {e:#}", ctx.provider, ctx.model, ctx.conversation_id);
+            ProviderFailure::classify(&e).into_app_error(ctx.channel, &ctx.provider, &ctx.model)
+        })?;
+    let (request, mut context_chunks) = fit_request(&state, &ctx, &ctx.provider, &ctx.model, &context, &message);
+    // The new message and its reply join the history
+    let total_messages = ctx.history.len() + 2;
+    let (channel, conversation_id) = (ctx.channel, ctx.conversation_id.clone());
+    let (mut provider, mut model_id, mut credentials) = (ctx.provider.clone(), ctx.model.clone(), ctx.credentials.clone());
+
+    let reply = async move {
+        let _guard = guard;
+        let mut started = Instant::now();
+        let mut fallback_from = None;
+        let mut notices = Vec::new();
+        let reply = match model.chat(request).await {
+            Ok(reply) => reply,
+            Err(e) => {
+                tracing::error!("LLM error in {channel:?} conversation {conversation_id}: {e:#}");
+                let failed = ProviderFailure::classify(&e).message(channel, &provider, &model_id);
+                if !ProviderFailure::warrants_fallback(&e) {
+                    return Err(failed);
+                }
+                let Some(fallback) = resolve_fallback(&state, ctx.user_id.as_deref(), &ctx.fallback_providers).await else {
+                    return Err(failed);
+                };
+                tracing::warn!(
+                    "Retrying conversation {conversation_id} with fallback {}/{} after {provider} failed",
+                    fallback.provider,
+                    fallback.model
+                );
+                // The fallback's context window may be smaller, so the prompt is fitted again
+                let (request, kept) = fit_request(&state, &ctx, &fallback.provider, &fallback.model, &context, &message);
+                context_chunks = kept;
+                started = Instant::now();
+                let retried = match state.completion_backend.chat_model(ModelRef {
+                    provider: &fallback.provider,
+                    model: &fallback.model,
+                    api_key: &fallback.credentials.api_key,
+                    base_url: fallback.credentials.base_url.as_deref(),
+                }) {
+                    Ok(model) => model.chat(request).await,
+                    Err(e) => Err(e),
+                };
+                let reply = retried.map_err(|e| {
+                    tracing::error!(
+                        "Fallback {}/{} failed too in conversation {conversation_id}: {e:#}",
+                        fallback.provider,
+                        fallback.model
+                    );
+                    failed
+                })?;
+                if channel == ChatChannel::App {
+                    notices.push(format!(
+                        "{} is not responding, so {} ({}) answered instead.",
+                        provider_name(&provider),
+                        provider_name(&fallback.provider),
+                        fallback.model
+                    ));
+                }
+                fallback_from = Some(std::mem::replace(&mut provider, fallback.provider));
+                model_id = fallback.model;
+                credentials = fallback.credentials;
+                reply
+            }
+        };
+        let generation = MessageGeneration {
+            provider: Some(provider.clone()),
+            model: Some(model_id),
+            prompt_tokens: reply.usage.and_then(|u| i32::try_from(u.prompt_tokens).ok()),
+            completion_tokens: reply.usage.and_then(|u| i32::try_from(u.completion_tokens).ok()),
+            latency_ms: i32::try_from(started.elapsed().as_millis()).ok(),
+            fallback_from,
+            context_chunks: i32::try_from(context_chunks).ok(),
+        };
+        let response = reply.text;
+
+        state
+            .conversation_repo
+            .add_message(&conversation_id, "assistant", &response, Some(&generation))
+            .await
+            .map_err(|e| {
+                tracing::error!("Failed to save reply in {channel:?} conversation {conversation_id}: {e:#}");
+                channel.save_failed_message().to_string()
+            })?;
+
+        let _ = state.conversation_repo.touch(&conversation_id).await;
+
+        history::update_summary_later(&state, &conversation_id, &provider, &credentials, &ctx.summary, total_messages);
+        after_reply(&response);
+        Ok(sse::Reply {
+            text: response,
+            notices,
+        })
+    }
+    // The reply outlives the handler; keep logging under the request's span and id
+    .instrument(tracing::Span::current());
+
+    Ok(sse::reply_stream(warnings, reply))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RerankMode;
+    use crate::services::provider_guard::CircuitBreakers;
+    use std::time::Duration;
+
+    fn result(point_id: &str, score: f32, content: &str) -> SearchResult {
+        SearchResult {
+            point_id: point_id.to_string(),
+            score,
+            vector_score: Some(score),
+            content: content.to_string(),
+        }
+    }
+
+    fn params(top_k: u64, min_score: f32, max_context_chars: usize) -> RetrievalParams {
+        RetrievalParams {
+            top_k,
+            min_score,
+            max_context_chars,
+        }
+    }
+
+    fn message(role: &str, content: &str) -> Message {
+        Message {
+            id: String::new(),
+            conversation_id: "c1".to_string(),
+            role: role.to_string(),
+            content: content.to_string(),
+            created_at: Default::default(),
+            generation: MessageGeneration::default(),
+            deleted_at: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_context_keeps_ranked_chunks_above_threshold() {
+        let reranker = RerankService::new(RerankMode::None, CircuitBreakers::new(0, Duration::ZERO));
+        let results = vec![
+            result("a", 0.9, "Refunds take five days."),
+            result("b", 0.2, "Unrelated chunk."),
+            result("c", 0.8, "Refunds go to the original card."),
+        ];
+        let context = assemble_context(&reranker, "refunds", results, &params(5, 0.5, 1000), None).await;
+        assert_eq!(context, ["Refunds take five days.", "Refunds go to the original card."]);
+    }
+
+    #[tokio::test]
+    async fn test_context_respects_top_k_and_budget() {
+        let reranker = RerankService::new(RerankMode::None, CircuitBreakers::new(0, Duration::ZERO));
+        let results = vec![
+            result("a", 0.9, "first"),
+            result("b", 0.9, "a chunk too long for the budget"),
+            result("c", 0.9, "third"),
+            result("d", 0.9, "fourth"),
+        ];
+        let context = assemble_context(&reranker, "q", results, &params(3, 0.0, 15), None).await;
+        assert_eq!(context, ["first", "third"]);
+    }
+
+    #[tokio::test]
+    async fn test_context_is_empty_without_results() {
+        let reranker = RerankService::new(RerankMode::Lexical, CircuitBreakers::new(0, Duration::ZERO));
+        assert!(assemble_context(&reranker, "q", Vec::new(), &params(5, 0.0, 1000), None).await.is_empty());
+
+        let below_threshold = vec![result("a", 0.1, "barely related")];
+        assert!(
+            assemble_context(&reranker, "q", below_threshold, &params(5, 0.5, 1000), None)
+                .await
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_context_is_reranked_before_selection() {
+        let reranker = RerankService::new(RerankMode::Lexical, CircuitBreakers::new(0, Duration::ZERO));
+        let results = vec![
+            result("a", 0.9, "Shipping is free over fifty dollars."),
+            result("b", 0.8, "Refund requests are handled within a week."),
+        ];
+        let context = assemble_context(&reranker, "refund requests", results, &params(1, 0.0, 1000), None).await;
+        assert_eq!(context.len(), 1);
+        assert!(context[0].contains("Refund requests"));
+    }
+
+    #[test]
+    fn test_fallback_order_prefers_the_users_list_and_skips_the_primary() {
+        let list = |providers: &[&str]| providers.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+        let configured = list(&["anthropic", "openai"]);
+        assert_eq!(fallback_order("openai", &[], &configured), list(&["anthropic"]));
+        assert_eq!(
+            fallback_order("openai", &list(&["groq", "openai", "groq", "mistral"]), &configured),
+            list(&["groq", "mistral"])
+        );
+        assert!(fallback_order("openai", &[], &[]).is_empty());
+    }
+
+    #[test]
+    fn test_compose_system_prompt_puts_the_base_first() {
+        assert_eq!(
+            compose_system_prompt("Never give legal advice.", "You are a helpful assistant."),
+            "Never give legal advice.\n\nYou are a helpful assistant."
+        );
+        assert_eq!(compose_system_prompt("", "You are a helpful assistant."), "You are a helpful assistant.");
+        // Clearing the user's prompt leaves the base in effect
+        assert_eq!(compose_system_prompt(" Never give legal advice.\n", ""), "Never give legal advice.");
+    }
+
+    #[test]
+    fn test_prompt_history_keeps_user_and_assistant_messages() {
+        let history = vec![
+            message("user", "Hi"),
+            message("assistant", "Hello!"),
+            message("system", "internal note"),
+        ];
+        assert_eq!(
+            prompt_history(&history),
+            vec![PromptMessage::user("Hi"), PromptMessage::assistant("Hello!")]
+        );
+    }
+}
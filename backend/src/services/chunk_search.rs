@@ -0,0 +1,326 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::config::{LlmConfig, RetrievalMode};
+use crate::db::models::document_chunk::DocumentChunkRepository;
+use crate::db::models::embed_key::EmbedKey;
+use crate::services::vector::{SearchFilter, SearchResult, VectorService};
+
+/// Constant `k` from the reciprocal rank fusion paper; dampens the weight of top ranks.
+const RRF_K: f32 = 60.0;
+
+/// Retrieves RAG context chunks, either straight from Qdrant or by fusing the
+/// Qdrant results with a Postgres full-text query over `document_chunks`.
+pub struct ChunkSearchService {
+    vector_service: Arc<VectorService>,
+    chunk_repo: DocumentChunkRepository,
+    mode: RetrievalMode,
+}
+
+impl ChunkSearchService {
+    pub fn new(
+        vector_service: Arc<VectorService>,
+        chunk_repo: DocumentChunkRepository,
+        mode: RetrievalMode,
+    ) -> Self {
+        Self {
+            vector_service,
+            chunk_repo,
+            mode,
+        }
+    }
+
+    /// Results are restricted to chunks matching `filter`.
+    pub async fn search(
+        &self,
+        collection: &str,
+        query: &str,
+        query_embedding: Vec<f64>,
+        top_k: u64,
+        filter: &SearchFilter,
+    ) -> Result<Vec<SearchResult>> {
+        if self.mode == RetrievalMode::Vector {
+            return self
+                .vector_service
+                .search(collection, query_embedding, top_k, filter)
+                .await
+                .map_err(Into::into);
+        }
+
+        let tsquery = fulltext_query(query);
+        let keyword_search = async {
+            if tsquery.is_empty() {
+                return Ok(Vec::new());
+            }
+            self.chunk_repo
+                .search_fulltext(&tsquery, top_k as i64, &filter.tags, filter.collection_id.as_deref())
+                .await
+        };
+
+        let (vector_results, keyword_results) = tokio::join!(
+            self.vector_service.search(collection, query_embedding, top_k, filter),
+            keyword_search,
+        );
+
+        let vector_results = vector_results?;
+        let keyword_results: Vec<SearchResult> = match keyword_results {
+            Ok(chunks) => chunks
+                .into_iter()
+                .map(|c| SearchResult {
+                    point_id: c.qdrant_point_id,
+                    score: 0.0,
+                    vector_score: None,
+                    content: c.content,
+                })
+                .collect(),
+            Err(e) => {
+                tracing::warn!("Full-text chunk search failed, using vector results only: {e:#}");
+                Vec::new()
+            }
+        };
+
+        Ok(reciprocal_rank_fusion(
+            vec![vector_results, keyword_results],
+            top_k as usize,
+        ))
+    }
+}
+
+/// Limits applied when turning search results into prompt context.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetrievalParams {
+    pub top_k: u64,
+    /// Minimum cosine similarity; keyword-only hybrid matches carry no cosine score and are kept.
+    pub min_score: f32,
+    pub max_context_chars: usize,
+}
+
+impl RetrievalParams {
+    pub fn from_config(llm: &LlmConfig) -> Self {
+        Self {
+            top_k: llm.rag_top_k,
+            min_score: llm.rag_min_score,
+            max_context_chars: llm.rag_max_context_chars,
+        }
+    }
+
+    /// Apply the widget's per-embed-key overrides. Non-positive counts are ignored.
+    pub fn with_embed_key_overrides(mut self, key: &EmbedKey) -> Self {
+        if let Some(top_k) = key.rag_top_k.filter(|v| *v > 0) {
+            self.top_k = top_k as u64;
+        }
+        if let Some(min_score) = key.rag_min_score {
+            self.min_score = min_score;
+        }
+        if let Some(max_chars) = key.rag_max_context_chars.filter(|v| *v > 0) {
+            self.max_context_chars = max_chars as usize;
+        }
+        self
+    }
+}
+
+/// Pick the chunk texts that go into the prompt: drop results below the score
+/// threshold, then keep whole chunks in rank order while they fit the character budget.
+pub fn select_context_chunks(results: &[SearchResult], params: &RetrievalParams) -> Vec<String> {
+    select_context_indices(results, params)
+        .into_iter()
+        .map(|i| results[i].content.clone())
+        .collect()
+}
+
+/// Positions in `results` of the chunks [`select_context_chunks`] keeps.
+pub fn select_context_indices(results: &[SearchResult], params: &RetrievalParams) -> Vec<usize> {
+    let mut selected = Vec::new();
+    let mut used_chars = 0usize;
+
+    for (index, result) in results.iter().enumerate() {
+        if result.content.is_empty() {
+            continue;
+        }
+
+        if let Some(score) = result.vector_score
+            && score < params.min_score
+        {
+            tracing::debug!(
+                "RAG chunk {} excluded: score {score:.3} below min_score {:.3}",
+                result.point_id,
+                params.min_score
+            );
+            continue;
+        }
+
+        let chunk_chars = result.content.chars().count();
+        let separator = if selected.is_empty() { 0 } else { 2 };
+        if used_chars + separator + chunk_chars > params.max_context_chars {
+            tracing::debug!(
+                "RAG chunk {} excluded: {chunk_chars} chars would exceed context budget ({used_chars}/{} used)",
+                result.point_id,
+                params.max_context_chars
+            );
+            continue;
+        }
+
+        tracing::debug!(
+            "RAG chunk {} included: score {:.3}, {chunk_chars} chars",
+            result.point_id,
+            result.score
+        );
+        used_chars += separator + chunk_chars;
+        selected.push(index);
+    }
+
+    selected
+}
+
+/// Wrap selected chunks in the instruction block appended to the system prompt.
+pub fn format_rag_context(chunks: &[String]) -> String {
+    if chunks.is_empty() {
+        return String::new();
+    }
+
+    format!(
+        "\n\nUse the following context from the knowledge base to help answer the user's question. If the context is not relevant, you may ignore it.\n\n---\n{}\n---\n",
+        chunks.join("\n\n")
+    )
+}
+
+/// Merge several ranked result lists with reciprocal rank fusion. Each result's
+/// `score` is replaced by its fused score; `vector_score` is kept when any list
+/// carried one.
+pub fn reciprocal_rank_fusion(lists: Vec<Vec<SearchResult>>, top_k: usize) -> Vec<SearchResult> {
+    let mut fused: HashMap<String, SearchResult> = HashMap::new();
+
+    for list in lists {
+        for (rank, result) in list.into_iter().enumerate() {
+            let contribution = 1.0 / (RRF_K + rank as f32 + 1.0);
+            fused
+                .entry(result.point_id.clone())
+                .and_modify(|existing| {
+                    existing.score += contribution;
+                    if existing.vector_score.is_none() {
+                        existing.vector_score = result.vector_score;
+                    }
+                })
+                .or_insert(SearchResult {
+                    score: contribution,
+                    ..result
+                });
+        }
+    }
+
+    let mut results: Vec<SearchResult> = fused.into_values().collect();
+    results.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| a.point_id.cmp(&b.point_id))
+    });
+    results.truncate(top_k);
+    results
+}
+
+/// Turn a free-text question into a `websearch_to_tsquery` expression that
+/// matches any of its terms. Identifiers such as `ERR-4021` or `SKU_88.B` are
+/// kept whole so the exact token can match.
+pub fn fulltext_query(text: &str) -> String {
+    text.split(|c: char| !(c.is_alphanumeric() || matches!(c, '-' | '_' | '.')))
+        .map(|term| term.trim_matches(|c: char| matches!(c, '-' | '_' | '.')))
+        .filter(|term| !term.is_empty() && !term.eq_ignore_ascii_case("or"))
+        .collect::<Vec<_>>()
+        .join(" or ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(id: &str, score: f32) -> SearchResult {
+        SearchResult {
+            point_id: id.to_string(),
+            score,
+            vector_score: Some(score),
+            content: format!("content {id}"),
+        }
+    }
+
+    fn keyword(id: &str) -> SearchResult {
+        SearchResult {
+            point_id: id.to_string(),
+            score: 0.0,
+            vector_score: None,
+            content: format!("content {id}"),
+        }
+    }
+
+    fn params(min_score: f32, max_context_chars: usize) -> RetrievalParams {
+        RetrievalParams {
+            top_k: 5,
+            min_score,
+            max_context_chars,
+        }
+    }
+
+    #[test]
+    fn test_select_context_applies_min_score() {
+        let results = vec![result("a", 0.9), result("b", 0.2), keyword("c")];
+        let chunks = select_context_chunks(&results, &params(0.5, 1000));
+        assert_eq!(chunks, vec!["content a", "content c"]);
+    }
+
+    #[test]
+    fn test_select_context_cuts_at_chunk_boundaries() {
+        // Each chunk is 9 chars; two chunks plus separator need 20.
+        let results = vec![result("a", 0.9), result("b", 0.8), result("c", 0.7)];
+        let chunks = select_context_chunks(&results, &params(0.0, 20));
+        assert_eq!(chunks, vec!["content a", "content b"]);
+
+        let chunks = select_context_chunks(&results, &params(0.0, 19));
+        assert_eq!(chunks, vec!["content a"]);
+    }
+
+    #[test]
+    fn test_format_rag_context_empty() {
+        assert_eq!(format_rag_context(&[]), "");
+        assert!(format_rag_context(&["x".to_string()]).contains("---\nx\n---"));
+    }
+
+    #[test]
+    fn test_fulltext_query_keeps_identifiers() {
+        assert_eq!(
+            fulltext_query("What does ERR-4021 mean?"),
+            "What or does or ERR-4021 or mean"
+        );
+        assert_eq!(fulltext_query("sku_88.B, please"), "sku_88.B or please");
+        assert_eq!(fulltext_query("-negated 'quoted' OR"), "negated or quoted");
+        assert_eq!(fulltext_query("?!"), "");
+    }
+
+    #[test]
+    fn test_rrf_surfaces_keyword_only_match() {
+        // The chunk containing the exact identifier is missed by the vector query
+        // but ranked first by keyword search; fusion must pull it into the top results.
+        let vector = vec![result("a", 0.82), result("b", 0.80), result("c", 0.79)];
+        let keywords = vec![keyword("err-chunk")];
+
+        let fused = reciprocal_rank_fusion(vec![vector, keywords], 3);
+        let ids: Vec<&str> = fused.iter().map(|r| r.point_id.as_str()).collect();
+
+        assert!(ids.contains(&"err-chunk"), "fused results: {ids:?}");
+        assert_eq!(fused.len(), 3);
+    }
+
+    #[test]
+    fn test_rrf_rewards_agreement() {
+        let vector = vec![result("a", 0.9), result("b", 0.8)];
+        let keywords = vec![keyword("b"), keyword("c")];
+
+        let fused = reciprocal_rank_fusion(vec![vector, keywords], 5);
+
+        assert_eq!(fused[0].point_id, "b");
+        assert_eq!(fused[0].vector_score, Some(0.8));
+        let expected = 1.0 / (RRF_K + 2.0) + 1.0 / (RRF_K + 1.0);
+        assert!((fused[0].score - expected).abs() < 1e-6);
+        assert_eq!(fused.iter().find(|r| r.point_id == "c").unwrap().vector_score, None);
+    }
+}
@@ -0,0 +1,116 @@
+use sha2::{Digest, Sha256};
+
+/// Words per chunk when splitting documents and crawled pages.
+pub const DEFAULT_CHUNK_SIZE: usize = 200;
+/// Words each chunk repeats from the end of the previous one.
+pub const DEFAULT_CHUNK_OVERLAP: usize = 30;
+
+/// Split text into chunks of at most `chunk_size` words, each starting with the
+/// last `overlap` words of the previous chunk. A `chunk_size` of 0 is treated
+/// as 1 and `overlap` is clamped below `chunk_size`, so every chunk moves
+/// forward by at least one word.
+pub fn chunk_text(text: &str, chunk_size: usize, overlap: usize) -> Vec<String> {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    if words.is_empty() {
+        return Vec::new();
+    }
+
+    let chunk_size = chunk_size.max(1);
+    let step = chunk_size - overlap.min(chunk_size - 1);
+
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    loop {
+        let end = (start + chunk_size).min(words.len());
+        chunks.push(words[start..end].join(" "));
+        if end == words.len() {
+            return chunks;
+        }
+        start += step;
+    }
+}
+
+/// Hex SHA-256 of extracted text together with the parameters that chunked
+/// it. Two extractions with the same fingerprint produce the same chunks, so
+/// a rescan can leave such a document's embeddings alone.
+pub fn extraction_fingerprint(text: &str, chunk_size: usize, overlap: usize, tabular: bool) -> String {
+    let mode = if tabular { "rows" } else { "words" };
+    let mut hasher = Sha256::new();
+    hasher.update(format!("{mode}:{chunk_size}:{overlap}\n"));
+    hasher.update(text.as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numbered_words(count: usize) -> String {
+        (0..count).map(|i| format!("w{i}")).collect::<Vec<_>>().join(" ")
+    }
+
+    #[test]
+    fn test_chunk_text_basic() {
+        let chunks = chunk_text(&numbered_words(10), 4, 1);
+        assert_eq!(chunks, vec!["w0 w1 w2 w3", "w3 w4 w5 w6", "w6 w7 w8 w9"]);
+    }
+
+    #[test]
+    fn test_chunk_text_empty() {
+        assert!(chunk_text("", 30, 5).is_empty());
+        assert!(chunk_text(" \n\t ", 30, 5).is_empty());
+    }
+
+    #[test]
+    fn test_chunk_text_properties() {
+        for word_count in [1, 2, 5, 29, 30, 31, 100, 257] {
+            let text = numbered_words(word_count);
+            let words: Vec<&str> = text.split_whitespace().collect();
+            for chunk_size in [1, 2, 3, 7, 30, 200] {
+                for overlap in [0, 1, 2, 5, 29, 30, 200] {
+                    let case = format!("{word_count} words, size {chunk_size}, overlap {overlap}");
+                    let chunks = chunk_text(&text, chunk_size, overlap);
+                    let chunks: Vec<Vec<&str>> = chunks.iter().map(|c| c.split_whitespace().collect()).collect();
+
+                    assert!(chunks.iter().all(|c| !c.is_empty() && c.len() <= chunk_size), "{case}");
+                    // Together the chunks cover the text in order, ending at its last word
+                    assert_eq!(chunks[0][0], words[0], "{case}");
+                    assert_eq!(chunks.last().unwrap().last(), words.last(), "{case}");
+
+                    let overlap = overlap.min(chunk_size - 1);
+                    for pair in chunks.windows(2) {
+                        let (previous, next) = (&pair[0], &pair[1]);
+                        assert_eq!(previous.len(), chunk_size, "{case}");
+                        assert_eq!(previous[chunk_size - overlap..], next[..overlap], "{case}");
+                        // No word is skipped between chunks
+                        let previous_end = words.iter().position(|w| w == previous.last().unwrap()).unwrap();
+                        let next_start = words.iter().position(|w| *w == next[0]).unwrap();
+                        assert_eq!(next_start, previous_end + 1 - overlap, "{case}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_chunk_text_degenerate_sizes_terminate() {
+        let text = numbered_words(5);
+        // Overlap as large as the chunk is clamped so each chunk moves one word
+        assert_eq!(chunk_text(&text, 2, 2), vec!["w0 w1", "w1 w2", "w2 w3", "w3 w4"]);
+        assert_eq!(chunk_text(&text, 2, 10).len(), 4);
+        assert_eq!(chunk_text(&text, 0, 0), vec!["w0", "w1", "w2", "w3", "w4"]);
+    }
+
+    #[test]
+    fn test_extraction_fingerprint_covers_text_and_parameters() {
+        let text = numbered_words(50);
+        let base = extraction_fingerprint(&text, 200, 30, false);
+        assert_eq!(base.len(), 64);
+        assert_eq!(base, extraction_fingerprint(&text, 200, 30, false));
+
+        assert_ne!(base, extraction_fingerprint(&numbered_words(51), 200, 30, false));
+        assert_ne!(base, extraction_fingerprint(&text, 100, 30, false));
+        assert_ne!(base, extraction_fingerprint(&text, 200, 20, false));
+        assert_ne!(base, extraction_fingerprint(&text, 200, 30, true));
+    }
+}
@@ -0,0 +1,473 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+
+use crate::config::FeatureFlags;
+use crate::db::models::admin_config::{AdminModel, AdminProvider};
+use crate::db::models::embed_key::{
+    EmbedKey, OfficeHours, OfflineBehavior, WidgetLocalization, WidgetTheme,
+};
+
+/// Version of the export format. Bump it whenever older servers would misread a
+/// document; they refuse versions newer than their own.
+pub const CONFIG_SCHEMA_VERSION: u32 = 1;
+
+/// Organization configuration that can be copied between installs. Secrets such
+/// as provider API keys and embed key hashes are never included.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ConfigDocument {
+    pub schema_version: u32,
+    #[serde(default)]
+    pub exported_at: String,
+    #[serde(default)]
+    pub providers: Vec<ProviderSettings>,
+    #[serde(default)]
+    pub models: Vec<ModelSettings>,
+    #[serde(default)]
+    pub embed_keys: Vec<EmbedKeySettings>,
+    /// Feature flags of the exporting server. They are set in its config file or
+    /// environment, so an import only reports differences.
+    #[serde(default)]
+    pub features: BTreeMap<String, bool>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ProviderSettings {
+    pub provider_id: String,
+    pub enabled: bool,
+    pub is_default_embedding: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ModelSettings {
+    pub provider_id: String,
+    pub model_id: String,
+    pub model_type: String,
+    pub display_name: String,
+    pub is_default: bool,
+    pub sort_order: i32,
+    pub is_enabled: bool,
+}
+
+/// An embed key without its key hash or provider API key. Keys created by an
+/// import get a fresh key.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct EmbedKeySettings {
+    pub id: String,
+    pub name: String,
+    pub allowed_domains: Vec<String>,
+    pub system_prompt: String,
+    pub rate_limit: i32,
+    /// Absent in exports made before message caps existed.
+    #[serde(default)]
+    pub daily_message_limit: Option<i32>,
+    #[serde(default)]
+    pub monthly_message_limit: Option<i32>,
+    pub widget_title: String,
+    pub primary_color: String,
+    /// Absent in exports made before widget themes existed.
+    #[serde(default)]
+    pub theme: WidgetTheme,
+    pub greeting_message: String,
+    pub provider: String,
+    pub model: String,
+    pub base_url: Option<String>,
+    pub custom_css: String,
+    pub rag_top_k: Option<i32>,
+    pub rag_min_score: Option<f32>,
+    pub rag_max_context_chars: Option<i32>,
+    pub localizations: BTreeMap<String, WidgetLocalization>,
+    pub handoff_enabled: bool,
+    pub handoff_notification_email: Option<String>,
+    /// Absent in exports made before transcript emails existed.
+    #[serde(default)]
+    pub transcript_email_enabled: bool,
+    /// Absent in exports made before office hours existed.
+    #[serde(default)]
+    pub office_hours: Option<OfficeHours>,
+    #[serde(default)]
+    pub offline_message: String,
+    #[serde(default)]
+    pub offline_behavior: OfflineBehavior,
+    pub is_active: bool,
+}
+
+impl From<&AdminProvider> for ProviderSettings {
+    fn from(p: &AdminProvider) -> Self {
+        ProviderSettings {
+            provider_id: p.provider_id.clone(),
+            enabled: p.enabled,
+            is_default_embedding: p.is_default_embedding,
+        }
+    }
+}
+
+impl From<&AdminModel> for ModelSettings {
+    fn from(m: &AdminModel) -> Self {
+        ModelSettings {
+            provider_id: m.provider_id.clone(),
+            model_id: m.model_id.clone(),
+            model_type: m.model_type.clone(),
+            display_name: m.display_name.clone(),
+            is_default: m.is_default,
+            sort_order: m.sort_order,
+            is_enabled: m.is_enabled,
+        }
+    }
+}
+
+impl From<&EmbedKey> for EmbedKeySettings {
+    fn from(k: &EmbedKey) -> Self {
+        EmbedKeySettings {
+            id: k.id.clone(),
+            name: k.name.clone(),
+            allowed_domains: k.allowed_domains.clone(),
+            system_prompt: k.system_prompt.clone(),
+            rate_limit: k.rate_limit,
+            daily_message_limit: k.daily_message_limit,
+            monthly_message_limit: k.monthly_message_limit,
+            widget_title: k.widget_title.clone(),
+            primary_color: k.primary_color.clone(),
+            theme: k.theme.clone(),
+            greeting_message: k.greeting_message.clone(),
+            provider: k.provider.clone(),
+            model: k.model.clone(),
+            base_url: k.base_url.clone(),
+            custom_css: k.custom_css.clone(),
+            rag_top_k: k.rag_top_k,
+            rag_min_score: k.rag_min_score,
+            rag_max_context_chars: k.rag_max_context_chars,
+            localizations: k.localizations.clone(),
+            handoff_enabled: k.handoff_enabled,
+            handoff_notification_email: k.handoff_notification_email.clone(),
+            transcript_email_enabled: k.transcript_email_enabled,
+            office_hours: k.office_hours.clone(),
+            offline_message: k.offline_message.clone(),
+            offline_behavior: k.offline_behavior,
+            is_active: k.is_active,
+        }
+    }
+}
+
+pub fn build_document(
+    providers: &[AdminProvider],
+    models: &[AdminModel],
+    embed_keys: &[EmbedKey],
+    features: &FeatureFlags,
+) -> ConfigDocument {
+    ConfigDocument {
+        schema_version: CONFIG_SCHEMA_VERSION,
+        exported_at: crate::db::timestamp::format(&chrono::Utc::now()),
+        providers: providers.iter().map(ProviderSettings::from).collect(),
+        models: models.iter().map(ModelSettings::from).collect(),
+        embed_keys: embed_keys.iter().map(EmbedKeySettings::from).collect(),
+        features: serde_json::to_value(features)
+            .and_then(serde_json::from_value)
+            .unwrap_or_default(),
+    }
+}
+
+/// Parse an uploaded document, checking `schema_version` before anything else so
+/// a document from a newer server fails with a clear message.
+pub fn parse_document(value: serde_json::Value) -> Result<ConfigDocument, String> {
+    let version = value
+        .get("schema_version")
+        .and_then(|v| v.as_u64())
+        .filter(|v| *v > 0)
+        .ok_or_else(|| "Not a configuration export: schema_version is missing".to_string())?;
+    if version > u64::from(CONFIG_SCHEMA_VERSION) {
+        return Err(format!(
+            "This export uses schema version {version}, but this server only reads up to \
+             version {CONFIG_SCHEMA_VERSION}. Upgrade the server before importing it."
+        ));
+    }
+    serde_json::from_value(value).map_err(|e| format!("Invalid configuration export: {e}"))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeAction {
+    Create,
+    Update,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ConfigChange {
+    /// `provider`, `model` or `embed_key`.
+    pub section: String,
+    /// The provider ID, `provider/model (type)`, or the embed key's name and ID.
+    pub key: String,
+    pub action: ChangeAction,
+    /// Fields that differ from this install; empty when creating.
+    pub fields: Vec<String>,
+}
+
+/// What importing a document would change. Only entries that differ are kept.
+#[derive(Debug, Default)]
+pub struct ImportPlan {
+    pub changes: Vec<ConfigChange>,
+    pub warnings: Vec<String>,
+    pub providers: Vec<ProviderSettings>,
+    pub models: Vec<ModelSettings>,
+    pub updated_embed_keys: Vec<EmbedKeySettings>,
+    pub new_embed_keys: Vec<EmbedKeySettings>,
+}
+
+/// An embed key the import creates, with its freshly generated key.
+pub struct NewEmbedKey {
+    pub settings: EmbedKeySettings,
+    pub key_hash: String,
+    pub key_prefix: String,
+}
+
+/// Compare `incoming` with the `current` configuration. Anything missing from
+/// `incoming` is left alone; providers must already exist here, since they
+/// come from the built-in catalogue.
+pub fn plan_import(current: &ConfigDocument, incoming: &ConfigDocument) -> Result<ImportPlan, String> {
+    let mut plan = ImportPlan::default();
+
+    let mut seen = HashSet::new();
+    for p in &incoming.providers {
+        if !seen.insert(p.provider_id.as_str()) {
+            return Err(format!("Provider '{}' is listed more than once", p.provider_id));
+        }
+        let existing = current
+            .providers
+            .iter()
+            .find(|c| c.provider_id == p.provider_id)
+            .ok_or_else(|| format!("Unknown provider '{}'", p.provider_id))?;
+        plan.update("provider", &p.provider_id, existing, p, |plan| plan.providers.push(p.clone()));
+    }
+    if incoming.providers.iter().filter(|p| p.is_default_embedding).count() > 1 {
+        return Err("Only one provider can be the default embedding provider".to_string());
+    }
+
+    let mut seen = HashSet::new();
+    let mut defaults = HashSet::new();
+    for m in &incoming.models {
+        let key = format!("{}/{} ({})", m.provider_id, m.model_id, m.model_type);
+        if m.model_type != "completion" && m.model_type != "embedding" {
+            return Err(format!("{key}: model_type must be 'completion' or 'embedding'"));
+        }
+        if !current.providers.iter().any(|p| p.provider_id == m.provider_id) {
+            return Err(format!("{key}: unknown provider '{}'", m.provider_id));
+        }
+        if !seen.insert(key.clone()) {
+            return Err(format!("Model {key} is listed more than once"));
+        }
+        if m.is_default && !defaults.insert((m.provider_id.as_str(), m.model_type.as_str())) {
+            return Err(format!(
+                "Provider '{}' has more than one default {} model",
+                m.provider_id, m.model_type
+            ));
+        }
+        let existing = current.models.iter().find(|c| {
+            c.provider_id == m.provider_id && c.model_id == m.model_id && c.model_type == m.model_type
+        });
+        match existing {
+            Some(existing) => plan.update("model", &key, existing, m, |plan| plan.models.push(m.clone())),
+            None => {
+                plan.create("model", key);
+                plan.models.push(m.clone());
+            }
+        }
+    }
+
+    let mut seen = HashSet::new();
+    for k in &incoming.embed_keys {
+        if !seen.insert(k.id.as_str()) {
+            return Err(format!("Embed key '{}' is listed more than once", k.id));
+        }
+        let key = format!("{} ({})", k.name, k.id);
+        match current.embed_keys.iter().find(|c| c.id == k.id) {
+            Some(existing) => plan.update("embed_key", &key, existing, k, |plan| {
+                plan.updated_embed_keys.push(k.clone())
+            }),
+            None => {
+                plan.create("embed_key", key);
+                plan.new_embed_keys.push(k.clone());
+            }
+        }
+    }
+
+    for (flag, wanted) in &incoming.features {
+        if let Some(actual) = current.features.get(flag).filter(|actual| *actual != wanted) {
+            plan.warnings.push(format!(
+                "Feature '{flag}' is {actual} here but {wanted} in the export; \
+                 feature flags are set in the server configuration"
+            ));
+        }
+    }
+
+    Ok(plan)
+}
+
+impl ImportPlan {
+    fn create(&mut self, section: &str, key: String) {
+        self.changes.push(ConfigChange {
+            section: section.to_string(),
+            key,
+            action: ChangeAction::Create,
+            fields: Vec::new(),
+        });
+    }
+
+    /// Record an update if any field differs, calling `keep` to queue the entry.
+    fn update<T: Serialize>(
+        &mut self,
+        section: &str,
+        key: &str,
+        current: &T,
+        incoming: &T,
+        keep: impl FnOnce(&mut Self),
+    ) {
+        let fields = changed_fields(current, incoming);
+        if fields.is_empty() {
+            return;
+        }
+        self.changes.push(ConfigChange {
+            section: section.to_string(),
+            key: key.to_string(),
+            action: ChangeAction::Update,
+            fields,
+        });
+        keep(self);
+    }
+}
+
+/// Names of the top-level fields whose serialized values differ.
+fn changed_fields<T: Serialize>(current: &T, incoming: &T) -> Vec<String> {
+    let (Ok(serde_json::Value::Object(current)), Ok(serde_json::Value::Object(incoming))) =
+        (serde_json::to_value(current), serde_json::to_value(incoming))
+    else {
+        return Vec::new();
+    };
+    incoming
+        .iter()
+        .filter(|(field, value)| current.get(field.as_str()) != Some(value))
+        .map(|(field, _)| field.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn provider(provider_id: &str, enabled: bool) -> ProviderSettings {
+        ProviderSettings {
+            provider_id: provider_id.to_string(),
+            enabled,
+            is_default_embedding: false,
+        }
+    }
+
+    fn model(model_id: &str, display_name: &str, is_default: bool) -> ModelSettings {
+        ModelSettings {
+            provider_id: "openai".to_string(),
+            model_id: model_id.to_string(),
+            model_type: "completion".to_string(),
+            display_name: display_name.to_string(),
+            is_default,
+            sort_order: 0,
+            is_enabled: true,
+        }
+    }
+
+    fn document(providers: Vec<ProviderSettings>, models: Vec<ModelSettings>) -> ConfigDocument {
+        ConfigDocument {
+            schema_version: CONFIG_SCHEMA_VERSION,
+            exported_at: String::new(),
+            providers,
+            models,
+            embed_keys: Vec::new(),
+            features: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_parse_document_checks_schema_version() {
+        let doc = document(vec![provider("openai", true)], Vec::new());
+        let parsed = parse_document(serde_json::to_value(&doc).unwrap()).unwrap();
+        assert_eq!(parsed, doc);
+
+        let newer = serde_json::json!({
+            "schema_version": CONFIG_SCHEMA_VERSION + 1,
+            "providers": "changed",
+        });
+        assert!(parse_document(newer).unwrap_err().contains("Upgrade the server"));
+        assert!(parse_document(serde_json::json!({ "providers": [] })).unwrap_err().contains("missing"));
+        assert!(parse_document(serde_json::json!({ "schema_version": 1, "models": 5 }))
+            .unwrap_err()
+            .starts_with("Invalid configuration export"));
+    }
+
+    #[test]
+    fn test_plan_import_lists_only_differences() {
+        let current = document(
+            vec![provider("openai", true), provider("anthropic", true)],
+            vec![model("gpt-4o", "GPT-4o", true)],
+        );
+        let incoming = document(
+            vec![provider("openai", true), provider("anthropic", false)],
+            vec![model("gpt-4o", "Flagship", true), model("ft:acme", "Acme", false)],
+        );
+
+        let plan = plan_import(&current, &incoming).unwrap();
+        let summary: Vec<(&str, &str, ChangeAction, Vec<String>)> = plan
+            .changes
+            .iter()
+            .map(|c| (c.section.as_str(), c.key.as_str(), c.action, c.fields.clone()))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("provider", "anthropic", ChangeAction::Update, vec!["enabled".to_string()]),
+                ("model", "openai/gpt-4o (completion)", ChangeAction::Update, vec!["display_name".to_string()]),
+                ("model", "openai/ft:acme (completion)", ChangeAction::Create, vec![]),
+            ]
+        );
+        assert_eq!(plan.providers.len(), 1);
+        assert_eq!(plan.models.len(), 2);
+
+        assert!(plan_import(&current, &current).unwrap().changes.is_empty());
+    }
+
+    #[test]
+    fn test_plan_import_rejects_inconsistent_documents() {
+        let current = document(vec![provider("openai", true)], Vec::new());
+        let plan = |providers, models| plan_import(&current, &document(providers, models));
+
+        assert_eq!(plan(vec![provider("mistral", true)], vec![]).unwrap_err(), "Unknown provider 'mistral'");
+        assert!(plan(vec![provider("openai", true), provider("openai", false)], vec![])
+            .unwrap_err()
+            .contains("more than once"));
+        assert!(plan(vec![], vec![model("a", "A", true), model("b", "B", true)])
+            .unwrap_err()
+            .contains("more than one default"));
+        let mut bad_type = model("a", "A", false);
+        bad_type.model_type = "vision".to_string();
+        assert!(plan(vec![], vec![bad_type]).unwrap_err().contains("model_type"));
+    }
+
+    #[test]
+    fn test_plan_import_warns_about_feature_differences() {
+        let mut current = document(Vec::new(), Vec::new());
+        current.features = BTreeMap::from([
+            ("widget_enabled".to_string(), true),
+            ("ocr_enabled".to_string(), false),
+        ]);
+        let mut incoming = current.clone();
+        incoming.features.insert("widget_enabled".to_string(), false);
+        incoming.features.insert("future_flag".to_string(), true);
+
+        let plan = plan_import(&current, &incoming).unwrap();
+        assert!(plan.changes.is_empty());
+        assert_eq!(plan.warnings.len(), 1);
+        assert!(plan.warnings[0].starts_with("Feature 'widget_enabled' is true here"));
+    }
+}
@@ -0,0 +1,61 @@
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+
+/// Conversations with a reply being generated. A send claims its conversation
+/// before loading the history and keeps it until the reply is saved, so two
+/// tabs can't interleave their exchanges; the second send is refused rather
+/// than queued. Claims are per process, like the rate limiters.
+#[derive(Clone, Default)]
+pub struct ConversationLocks {
+    active: Arc<Mutex<HashSet<String>>>,
+}
+
+impl ConversationLocks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Claim `conversation_id` until the guard is dropped; `None` while
+    /// another send holds it.
+    pub fn try_lock(&self, conversation_id: &str) -> Option<ConversationGuard> {
+        let mut active = self.active.lock().unwrap_or_else(|e| e.into_inner());
+        if !active.insert(conversation_id.to_string()) {
+            return None;
+        }
+        Some(ConversationGuard {
+            active: self.active.clone(),
+            conversation_id: conversation_id.to_string(),
+        })
+    }
+}
+
+/// A claimed conversation, released on drop: once the reply is saved, or
+/// when it fails or the client goes away.
+pub struct ConversationGuard {
+    active: Arc<Mutex<HashSet<String>>>,
+    conversation_id: String,
+}
+
+impl Drop for ConversationGuard {
+    fn drop(&mut self) {
+        let mut active = self.active.lock().unwrap_or_else(|e| e.into_inner());
+        active.remove(&self.conversation_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_try_lock_refuses_until_released() {
+        let locks = ConversationLocks::new();
+        let guard = locks.try_lock("conv-1").unwrap();
+        assert!(locks.try_lock("conv-1").is_none());
+        // Other conversations are independent
+        assert!(locks.try_lock("conv-2").is_some());
+
+        drop(guard);
+        assert!(locks.try_lock("conv-1").is_some());
+    }
+}
@@ -0,0 +1,50 @@
+use anyhow::Result;
+
+use crate::db::models::conversation::{ConversationPurge, DELETED_RETENTION_DAYS};
+use crate::state::AppState;
+
+/// Rows deleted per statement, so a backlog is worked off in short
+/// transactions rather than one long one.
+pub const BATCH_SIZE: i64 = 1000;
+
+/// What [`purge`] would remove right now.
+pub async fn preview(state: &AppState) -> Result<ConversationPurge> {
+    let mut preview = state.conversation_repo.count_expired().await?;
+    preview.sessions = state.widget_session_repo.count_orphaned(DELETED_RETENTION_DAYS).await?;
+    Ok(preview)
+}
+
+/// Permanently delete conversations soft-deleted more than
+/// [`DELETED_RETENTION_DAYS`] ago, then the stale widget sessions they leave
+/// behind, `batch_size` rows at a time.
+pub async fn purge(state: &AppState, batch_size: i64) -> Result<ConversationPurge> {
+    let mut purged = ConversationPurge::default();
+    loop {
+        let batch = state.conversation_repo.hard_delete_expired(batch_size).await?;
+        purged.conversations += batch.conversations;
+        purged.messages += batch.messages;
+        if (batch.conversations as i64) < batch_size {
+            break;
+        }
+    }
+    loop {
+        let sessions = state
+            .widget_session_repo
+            .delete_orphaned(DELETED_RETENTION_DAYS, batch_size)
+            .await?;
+        purged.sessions += sessions;
+        if (sessions as i64) < batch_size {
+            break;
+        }
+    }
+
+    if purged != ConversationPurge::default() {
+        tracing::info!(
+            "Purged {} expired conversations with {} messages and {} orphaned widget sessions",
+            purged.conversations,
+            purged.messages,
+            purged.sessions
+        );
+    }
+    Ok(purged)
+}
@@ -0,0 +1,143 @@
+use anyhow::Result;
+use std::time::Duration;
+
+use crate::db::models::crawl_schedule::CrawlSchedule;
+use crate::services::audit;
+use crate::services::embedding::EmbeddingTarget;
+use crate::services::jobs::JobPayload;
+use crate::state::AppState;
+
+/// How often the scheduler looks for due crawl schedules.
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Start the loop that queues a new crawl for every schedule that is due.
+/// Nothing runs while web crawling is disabled; schedules simply stay due.
+pub fn start(state: AppState) {
+    let stopping = state.tasks.stopping().clone();
+    state.tasks.clone().spawn(async move {
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = stopping.cancelled() => break,
+            }
+            if !state.config.features.web_crawl_enabled {
+                continue;
+            }
+            match state.crawl_schedule_repo.claim_due().await {
+                Ok(due) => {
+                    for schedule in due {
+                        if let Err(e) = start_run(&state, &schedule).await {
+                            tracing::error!("Scheduled crawl of {} failed to start: {e:#}", schedule.url);
+                        }
+                    }
+                }
+                Err(e) => tracing::error!("Failed to check crawl schedules: {e:#}"),
+            }
+        }
+        tracing::info!("Crawl scheduler stopped");
+    });
+}
+
+/// Create and queue the crawl job for one due schedule, using the embedding
+/// settings of the user who owns it.
+async fn start_run(state: &AppState, schedule: &CrawlSchedule) -> Result<()> {
+    let embedding = state.embedding.resolve_for_user(&schedule.user_id).await?;
+    let api_key = embedding.api_key();
+    let EmbeddingTarget {
+        provider: embedding_provider,
+        model: embedding_model,
+    } = embedding.target;
+
+    let job = state
+        .crawl_repo
+        .create(&schedule.user_id, &schedule.url, &schedule.crawl_type)
+        .await?;
+
+    audit::log(
+        &state.audit,
+        None,
+        "crawl.scheduled_run",
+        Some("crawl_job"),
+        Some(&job.id),
+        &format!(
+            "Started {} {} crawl of {} (schedule {})",
+            schedule.frequency, schedule.crawl_type, schedule.url, schedule.id
+        ),
+        None,
+        Some(serde_json::json!({ "schedule_id": schedule.id, "user_id": schedule.user_id })),
+    );
+
+    if api_key.is_empty() && crate::services::llm_provider::requires_api_key(&embedding_provider) {
+        let msg = format!("No API key configured for embedding provider '{embedding_provider}'");
+        state
+            .crawl_repo
+            .update_status(&job.id, "failed", None, None, Some(&msg))
+            .await?;
+        anyhow::bail!(msg);
+    }
+
+    let queued = JobPayload::CrawlEmbedding {
+        crawl_job_id: job.id.clone(),
+        user_id: schedule.user_id.clone(),
+        url: schedule.url.clone(),
+        crawl_type: schedule.crawl_type.clone(),
+        embedding_provider,
+        embedding_model,
+        schedule_id: Some(schedule.id.clone()),
+    };
+    if let Err(e) = state.jobs.enqueue(&queued).await {
+        let _ = state
+            .crawl_repo
+            .update_status(&job.id, "failed", None, None, Some("Failed to queue crawl"))
+            .await;
+        return Err(e);
+    }
+
+    tracing::info!("Queued scheduled crawl {} of {}", job.id, schedule.url);
+    Ok(())
+}
+
+/// After a scheduled run succeeds, drop the pages of the run it replaces so the
+/// site isn't indexed twice, and make the new run the schedule's last one.
+/// Failures are logged; the stale pages are then replaced by the next run.
+pub(crate) async fn replace_previous_run(
+    state: &AppState,
+    schedule_id: &str,
+    crawl_job_id: &str,
+    embedding_model: &str,
+) {
+    let previous = match state.crawl_schedule_repo.find_by_id(schedule_id).await {
+        Ok(Some(schedule)) => schedule.last_job_id,
+        // The schedule was removed while the run was in progress
+        Ok(None) => None,
+        Err(e) => {
+            tracing::error!("Failed to load crawl schedule {schedule_id}: {e:#}");
+            return;
+        }
+    };
+
+    if let Some(previous) = previous.filter(|p| p != crawl_job_id) {
+        match state.chunk_repo.delete_by_source("crawl_page", &previous).await {
+            Ok(point_ids) if !point_ids.is_empty() => {
+                let collection = state.vector_service.collection_for_model(embedding_model);
+                if let Err(e) = state.vector_cleanup.delete_points(&collection, point_ids).await {
+                    tracing::error!("Failed to delete vectors of replaced crawl {previous}, queued for retry: {e:#}");
+                }
+            }
+            Ok(_) => {}
+            Err(e) => {
+                tracing::error!("Failed to delete chunks of replaced crawl {previous}: {e:#}");
+                return;
+            }
+        }
+    }
+
+    if let Err(e) = state
+        .crawl_schedule_repo
+        .set_last_job(schedule_id, crawl_job_id)
+        .await
+    {
+        tracing::error!("Failed to record run {crawl_job_id} on crawl schedule {schedule_id}: {e:#}");
+    }
+}
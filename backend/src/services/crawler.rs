@@ -6,6 +6,7 @@ use tokio::sync::Semaphore;
 use url::Url;
 
 use crate::config::CrawlerConfig;
+use crate::services::jobs::PermanentFailure;
 
 pub struct CrawlerService {
     client: reqwest::Client,
@@ -83,15 +84,15 @@ impl CrawlerService {
             let link_selector = Selector::parse("a[href]").unwrap();
 
             for element in doc.select(&link_selector) {
-                if let Some(href) = element.value().attr("href") {
-                    if let Ok(resolved) = base.join(href) {
-                        let resolved_str = resolved.to_string();
-                        if resolved.host_str() == base.host_str()
-                            && !visited.contains(&resolved_str)
-                            && !resolved_str.contains('#')
-                        {
-                            to_visit.push(resolved_str);
-                        }
+                if let Some(href) = element.value().attr("href")
+                    && let Ok(resolved) = base.join(href)
+                {
+                    let resolved_str = resolved.to_string();
+                    if resolved.host_str() == base.host_str()
+                        && !visited.contains(&resolved_str)
+                        && !resolved_str.contains('#')
+                    {
+                        to_visit.push(resolved_str);
                     }
                 }
             }
@@ -131,6 +132,41 @@ impl CrawlerService {
         results
     }
 
+    /// Fetch and extract one page, refusing responses larger than
+    /// `max_page_size_mb` and error statuses.
+    pub async fn fetch_page(&self, url: &str) -> Result<CrawledPage> {
+        let max_bytes = self.config.max_page_size_mb * 1024 * 1024;
+        let too_large = || {
+            PermanentFailure(format!(
+                "Page is larger than the {} MB limit",
+                self.config.max_page_size_mb
+            ))
+        };
+
+        let mut response = self
+            .client
+            .get(url)
+            .send()
+            .await
+            .context("Failed to fetch page")?
+            .error_for_status()
+            .context("Page returned an error status")?;
+        if response.content_length().is_some_and(|len| len > max_bytes as u64) {
+            return Err(too_large().into());
+        }
+
+        // The declared length can be missing or wrong, so count while reading
+        let mut body = Vec::new();
+        while let Some(chunk) = response.chunk().await.context("Failed to read page body")? {
+            body.extend_from_slice(&chunk);
+            if body.len() > max_bytes {
+                return Err(too_large().into());
+            }
+        }
+
+        Ok(parse_page(url, &String::from_utf8_lossy(&body)))
+    }
+
     async fn fetch_html(&self, url: &str) -> Result<String> {
         self.client
             .get(url)
@@ -153,7 +189,11 @@ async fn fetch_and_extract(client: &reqwest::Client, url: &str) -> Result<Crawle
         .await
         .context("Failed to read page body")?;
 
-    let doc = Html::parse_document(&body);
+    Ok(parse_page(url, &body))
+}
+
+fn parse_page(url: &str, body: &str) -> CrawledPage {
+    let doc = Html::parse_document(body);
 
     let title = Selector::parse("title")
         .ok()
@@ -163,11 +203,11 @@ async fn fetch_and_extract(client: &reqwest::Client, url: &str) -> Result<Crawle
 
     let content = extract_text_content(&doc);
 
-    Ok(CrawledPage {
+    CrawledPage {
         url: url.to_string(),
         title,
         content,
-    })
+    }
 }
 
 fn extract_text_content(doc: &Html) -> String {
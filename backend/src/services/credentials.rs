@@ -0,0 +1,218 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::db::models::admin_api_key::AdminApiKeyRepository;
+use crate::db::models::settings::{ProviderCredentials, SettingsRepository};
+use crate::db::models::user::{UserRepository, UserRole};
+use crate::services::llm_provider;
+use crate::services::tasks::BackgroundTasks;
+
+/// Where a resolved provider key came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum KeySource {
+    /// The user's own key from Settings.
+    User,
+    /// The organization key configured by an admin.
+    Organization,
+    /// The provider needs no key (e.g. a local Ollama).
+    Keyless,
+}
+
+#[derive(Debug, Clone)]
+pub struct ResolvedCredentials {
+    pub credentials: ProviderCredentials,
+    pub source: KeySource,
+}
+
+/// Picks the API key to use for a provider: the user's own key first, then the
+/// organization key when `features.allow_shared_api_keys` is on.
+#[derive(Clone)]
+pub struct CredentialResolver {
+    settings_repo: SettingsRepository,
+    admin_keys: AdminApiKeyRepository,
+    users: UserRepository,
+    allow_shared: bool,
+    tasks: BackgroundTasks,
+}
+
+impl CredentialResolver {
+    pub fn new(
+        settings_repo: SettingsRepository,
+        admin_keys: AdminApiKeyRepository,
+        users: UserRepository,
+        allow_shared: bool,
+        tasks: BackgroundTasks,
+    ) -> Self {
+        Self {
+            settings_repo,
+            admin_keys,
+            users,
+            allow_shared,
+            tasks,
+        }
+    }
+
+    /// Like `resolve`, for credentials about to be used for a chat or embedding
+    /// call. Records the use on the user's own key without waiting for the write.
+    /// A non-admin's base URL is checked again, since its host may resolve
+    /// differently than when it was saved.
+    pub async fn resolve_for_use(
+        &self,
+        user_id: &str,
+        provider: &str,
+    ) -> Result<Option<ResolvedCredentials>> {
+        let resolved = self.resolve(user_id, provider).await?;
+        if let Some(base_url) = resolved
+            .as_ref()
+            .filter(|r| r.source == KeySource::User)
+            .and_then(|r| r.credentials.base_url.as_deref())
+        {
+            self.ensure_allowed_base_url(user_id, provider, base_url).await?;
+        }
+        if resolved.as_ref().is_some_and(|r| r.source == KeySource::User) {
+            let repo = self.settings_repo.clone();
+            let user_id = user_id.to_string();
+            let provider = provider.to_string();
+            self.tasks.spawn(async move {
+                if let Err(e) = repo.touch_api_key(&user_id, &provider).await {
+                    tracing::warn!("Failed to record API key use: {e:#}");
+                }
+            });
+        }
+        Ok(resolved)
+    }
+
+    async fn ensure_allowed_base_url(&self, user_id: &str, provider: &str, base_url: &str) -> Result<()> {
+        let is_admin = self
+            .users
+            .find_by_id(user_id)
+            .await?
+            .is_some_and(|u| u.role == UserRole::Admin);
+        if is_admin {
+            return Ok(());
+        }
+        llm_provider::ensure_public_base_url(base_url).await.map_err(|e| {
+            tracing::warn!("Refusing base URL of user {user_id} for provider '{provider}': {e:#}");
+            anyhow::anyhow!("The base URL for provider '{provider}' is not allowed: {e}")
+        })
+    }
+
+    /// Credentials for `user_id` talking to `provider`, or `None` if no usable key exists.
+    pub async fn resolve(
+        &self,
+        user_id: &str,
+        provider: &str,
+    ) -> Result<Option<ResolvedCredentials>> {
+        let user = self.settings_repo.get_credentials(user_id, provider).await?;
+        let organization = if user.is_none() && self.allow_shared {
+            self.admin_keys.get_credentials(provider).await?
+        } else {
+            None
+        };
+
+        let resolved = pick_credentials(user, organization, provider);
+        match &resolved {
+            Some(r) => tracing::debug!(
+                "Using {:?} API key for provider '{provider}' (user {user_id})",
+                r.source
+            ),
+            None => {
+                tracing::debug!("No API key available for provider '{provider}' (user {user_id})")
+            }
+        }
+        Ok(resolved)
+    }
+
+    /// Credentials from one source only, for an admin choosing which key a bulk
+    /// job spends. Key-less providers resolve whatever the source.
+    pub async fn resolve_from(
+        &self,
+        user_id: &str,
+        provider: &str,
+        source: KeySource,
+    ) -> Result<Option<ResolvedCredentials>> {
+        let (user, organization) = match source {
+            KeySource::User => (self.settings_repo.get_credentials(user_id, provider).await?, None),
+            KeySource::Organization => (None, self.admin_keys.get_credentials(provider).await?),
+            KeySource::Keyless => (None, None),
+        };
+        Ok(pick_credentials(user, organization, provider))
+    }
+
+    /// Credentials for requests made on behalf of the organization rather than
+    /// a user (the embeddable widget): the organization key when
+    /// `features.allow_shared_api_keys` is on. User keys are never used here.
+    pub async fn resolve_shared(&self, provider: &str) -> Result<Option<ResolvedCredentials>> {
+        let organization = if self.allow_shared {
+            self.admin_keys.get_credentials(provider).await?
+        } else {
+            None
+        };
+
+        let resolved = pick_credentials(None, organization, provider);
+        match &resolved {
+            Some(r) => tracing::debug!("Using {:?} API key for provider '{provider}' (shared)", r.source),
+            None => tracing::debug!("No shared API key available for provider '{provider}'"),
+        }
+        Ok(resolved)
+    }
+}
+
+/// Precedence: user key, organization key, then empty credentials for key-less providers.
+fn pick_credentials(
+    user: Option<ProviderCredentials>,
+    organization: Option<ProviderCredentials>,
+    provider: &str,
+) -> Option<ResolvedCredentials> {
+    if let Some(credentials) = user {
+        return Some(ResolvedCredentials {
+            credentials,
+            source: KeySource::User,
+        });
+    }
+    if let Some(credentials) = organization {
+        return Some(ResolvedCredentials {
+            credentials,
+            source: KeySource::Organization,
+        });
+    }
+    ProviderCredentials::or_keyless(None, provider).map(|credentials| ResolvedCredentials {
+        credentials,
+        source: KeySource::Keyless,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn creds(key: &str) -> Option<ProviderCredentials> {
+        Some(ProviderCredentials {
+            api_key: key.to_string(),
+            base_url: None,
+        })
+    }
+
+    #[test]
+    fn test_user_key_wins() {
+        let resolved = pick_credentials(creds("user"), creds("org"), "openai").unwrap();
+        assert_eq!(resolved.source, KeySource::User);
+        assert_eq!(resolved.credentials.api_key, "user");
+    }
+
+    #[test]
+    fn test_falls_back_to_organization_key() {
+        let resolved = pick_credentials(None, creds("org"), "openai").unwrap();
+        assert_eq!(resolved.source, KeySource::Organization);
+        assert_eq!(resolved.credentials.api_key, "org");
+    }
+
+    #[test]
+    fn test_keyless_and_missing() {
+        let resolved = pick_credentials(None, None, "ollama").unwrap();
+        assert_eq!(resolved.source, KeySource::Keyless);
+        assert!(pick_credentials(None, None, "openai").is_none());
+    }
+}
@@ -0,0 +1,263 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::io::Write;
+use std::time::Duration;
+
+use crate::db::models::conversation::{ArchivedFilter, Conversation, Message};
+use crate::db::models::data_export::ExportFormat;
+use crate::db::models::document::Document;
+use crate::dto::auth::UserResponse;
+use crate::services::audit;
+use crate::services::storage::StreamingUpload;
+use crate::state::AppState;
+use chrono::{DateTime, Utc};
+
+/// How often expired export files are deleted.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Expired exports deleted per sweep.
+const SWEEP_BATCH: i64 = 100;
+
+/// An uploaded document's metadata. The file itself is not included.
+#[derive(Serialize)]
+struct ExportedDocument {
+    id: String,
+    filename: String,
+    content_type: String,
+    size_bytes: i64,
+    status: String,
+    tags: Vec<String>,
+    collection_id: Option<String>,
+    embedding_model: Option<String>,
+    #[serde(with = "crate::db::timestamp")]
+    created_at: DateTime<Utc>,
+    #[serde(with = "crate::db::timestamp::option")]
+    processed_at: Option<DateTime<Utc>>,
+}
+
+impl From<Document> for ExportedDocument {
+    fn from(doc: Document) -> Self {
+        Self {
+            id: doc.id,
+            filename: doc.original_filename,
+            content_type: doc.content_type,
+            size_bytes: doc.size_bytes,
+            status: doc.status.to_string(),
+            tags: doc.tags,
+            collection_id: doc.collection_id,
+            embedding_model: doc.embedding_model,
+            created_at: doc.created_at,
+            processed_at: doc.processed_at,
+        }
+    }
+}
+
+/// Which providers the user stored a key for. The key and its preview are left out.
+#[derive(Serialize)]
+struct ExportedApiKey {
+    provider: String,
+    #[serde(with = "crate::db::timestamp")]
+    created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+struct ExportedConversation {
+    #[serde(flatten)]
+    conversation: Conversation,
+    messages: Vec<Message>,
+}
+
+/// Categories small enough to hold in memory, in the order they're written.
+/// Conversations follow, written one at a time.
+async fn collect_sections(state: &AppState, user_id: &str) -> Result<Vec<(&'static str, serde_json::Value)>> {
+    let user = state
+        .user_repo
+        .find_by_id(user_id)
+        .await?
+        .context("The user no longer exists")?;
+    let preferences = state.settings_repo.get_preferences(user_id).await?;
+    let api_keys: Vec<ExportedApiKey> = state
+        .settings_repo
+        .list_api_keys(user_id)
+        .await?
+        .into_iter()
+        .map(|k| ExportedApiKey {
+            provider: k.provider,
+            created_at: k.created_at,
+        })
+        .collect();
+    let documents: Vec<ExportedDocument> = state
+        .document_repo
+        .find_by_user(user_id, None)
+        .await?
+        .into_iter()
+        .map(ExportedDocument::from)
+        .collect();
+    let crawl_jobs = state.crawl_repo.find_by_user(user_id).await?;
+
+    Ok(vec![
+        ("profile", serde_json::to_value(UserResponse::from(user))?),
+        ("preferences", serde_json::to_value(preferences)?),
+        ("api_keys", serde_json::to_value(api_keys)?),
+        ("documents", serde_json::to_value(documents)?),
+        ("crawl_jobs", serde_json::to_value(crawl_jobs)?),
+    ])
+}
+
+/// Build the export's file, store it and mark the export ready.
+pub async fn run(state: &AppState, export_id: &str) -> Result<()> {
+    let Some(export) = state.data_export_repo.find_by_id(export_id).await? else {
+        tracing::info!("Data export {export_id} was deleted before it ran");
+        return Ok(());
+    };
+
+    let sections = collect_sections(state, &export.user_id).await?;
+    let conversations = state
+        .conversation_repo
+        .list_by_user(&export.user_id, ArchivedFilter::All)
+        .await?;
+    let key = format!("exports/{}/{}.{}", export.user_id, export.id, export.format);
+
+    let size = match export.format {
+        ExportFormat::Json => write_json(state, &key, sections, conversations).await?,
+        ExportFormat::Zip => write_zip(state, &key, sections, conversations).await?,
+    };
+
+    state
+        .data_export_repo
+        .mark_ready(&export.id, &key, size as i64, state.config.exports.expiry_hours)
+        .await?;
+    audit::log(
+        &state.audit,
+        Some(&export.user_id),
+        "settings.export.complete",
+        Some("data_export"),
+        Some(&export.id),
+        &format!("Personal data export finished ({size} bytes)"),
+        None,
+        None,
+    );
+    tracing::info!("Data export {} ready ({size} bytes)", export.id);
+    Ok(())
+}
+
+async fn load_conversation(state: &AppState, conversation: Conversation) -> Result<ExportedConversation> {
+    let messages = state.conversation_repo.get_messages(&conversation.id).await?;
+    Ok(ExportedConversation { conversation, messages })
+}
+
+/// Stream one JSON document to storage, a conversation at a time. Returns its size.
+async fn write_json(
+    state: &AppState,
+    key: &str,
+    sections: Vec<(&'static str, serde_json::Value)>,
+    conversations: Vec<Conversation>,
+) -> Result<usize> {
+    let mut upload = state.storage.start_upload(key, ExportFormat::Json.content_type());
+    match write_json_body(state, &mut upload, sections, conversations).await {
+        Ok(size) => {
+            upload.finish().await?;
+            Ok(size)
+        }
+        Err(e) => {
+            upload.abort().await;
+            Err(e)
+        }
+    }
+}
+
+async fn write_json_body(
+    state: &AppState,
+    upload: &mut StreamingUpload,
+    sections: Vec<(&'static str, serde_json::Value)>,
+    conversations: Vec<Conversation>,
+) -> Result<usize> {
+    let mut size = 0;
+    let mut write = async |bytes: &[u8]| -> Result<()> {
+        size += bytes.len();
+        upload.write(bytes).await
+    };
+
+    write(b"{").await?;
+    for (name, value) in &sections {
+        write(format!("\"{name}\":").as_bytes()).await?;
+        write(&serde_json::to_vec(value)?).await?;
+        write(b",").await?;
+    }
+    write(br#""conversations":["#).await?;
+    for (i, conversation) in conversations.into_iter().enumerate() {
+        if i > 0 {
+            write(b",").await?;
+        }
+        let conversation = load_conversation(state, conversation).await?;
+        write(&serde_json::to_vec(&conversation)?).await?;
+    }
+    write(b"]}").await?;
+    Ok(size)
+}
+
+/// Store a zip archive with one JSON file per category. Returns its size.
+async fn write_zip(
+    state: &AppState,
+    key: &str,
+    sections: Vec<(&'static str, serde_json::Value)>,
+    conversations: Vec<Conversation>,
+) -> Result<usize> {
+    let options = zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    let mut archive = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+
+    for (name, value) in &sections {
+        archive.start_file(format!("{name}.json"), options)?;
+        serde_json::to_writer_pretty(&mut archive, value)?;
+    }
+    archive.start_file("conversations.json", options)?;
+    archive.write_all(b"[")?;
+    for (i, conversation) in conversations.into_iter().enumerate() {
+        if i > 0 {
+            archive.write_all(b",")?;
+        }
+        let conversation = load_conversation(state, conversation).await?;
+        serde_json::to_writer_pretty(&mut archive, &conversation)?;
+    }
+    archive.write_all(b"]")?;
+
+    let bytes = archive.finish()?.into_inner();
+    let size = bytes.len();
+    state
+        .storage
+        .upload(key, bytes, ExportFormat::Zip.content_type())
+        .await?;
+    Ok(size)
+}
+
+/// Delete the files of expired exports, then their rows.
+pub async fn delete_expired(state: &AppState) -> Result<usize> {
+    let expired = state.data_export_repo.list_expired(SWEEP_BATCH).await?;
+    for export in &expired {
+        if let Some(ref key) = export.storage_key {
+            state.storage.delete(key).await?;
+        }
+        state.data_export_repo.delete(&export.id).await?;
+    }
+    Ok(expired.len())
+}
+
+/// Start the hourly sweep of expired exports.
+pub fn start(state: AppState) {
+    let stopping = state.tasks.stopping().clone();
+    let tasks = state.tasks.clone();
+    tasks.spawn(async move {
+        let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = stopping.cancelled() => break,
+            }
+            match delete_expired(&state).await {
+                Ok(count) if count > 0 => tracing::info!("Deleted {count} expired data exports"),
+                Ok(_) => {}
+                Err(e) => tracing::error!("Failed to delete expired data exports: {e:#}"),
+            }
+        }
+    });
+}
@@ -0,0 +1,45 @@
+use crate::db::models::document_event::{DocumentEventRepository, NewDocumentEvent};
+use crate::services::batch_writer::BatchQueue;
+use crate::services::tasks::BackgroundTasks;
+
+/// Events waiting to be written before new ones are dropped.
+const QUEUE_CAPACITY: usize = 10_000;
+
+/// Processing log shown on a document's detail page. Events are queued and
+/// written in the background like audit entries, so processing never waits on
+/// them and a lost event costs nothing but a line of history.
+#[derive(Clone)]
+pub struct DocumentEventLog {
+    queue: BatchQueue<NewDocumentEvent>,
+}
+
+impl DocumentEventLog {
+    pub fn start(repo: DocumentEventRepository, tasks: &BackgroundTasks) -> Self {
+        let queue = BatchQueue::start("document events", QUEUE_CAPACITY, tasks, move |batch| {
+            let repo = repo.clone();
+            async move { repo.create_batch(&batch).await }
+        });
+        Self { queue }
+    }
+
+    pub fn info(&self, document_id: &str, message: impl Into<String>) {
+        self.push(document_id, "info", message.into());
+    }
+
+    pub fn warn(&self, document_id: &str, message: impl Into<String>) {
+        self.push(document_id, "warn", message.into());
+    }
+
+    pub fn error(&self, document_id: &str, message: impl Into<String>) {
+        self.push(document_id, "error", message.into());
+    }
+
+    fn push(&self, document_id: &str, level: &'static str, message: String) {
+        self.queue.push(NewDocumentEvent {
+            document_id: document_id.to_string(),
+            level,
+            message,
+            created_at: chrono::Utc::now(),
+        });
+    }
+}
@@ -39,11 +39,10 @@ impl EmailService {
 
         let setup_link = format!("{}/setup?token={}", self.frontend_url, token);
 
-        let body = ResendRequest {
-            from: self.from_email.clone(),
-            to: vec![email.to_string()],
-            subject: "You've been invited to RAG Pipeline".to_string(),
-            html: format!(
+        self.send(
+            email,
+            "You've been invited to RAG Pipeline",
+            format!(
                 r#"<!DOCTYPE html>
 <html>
 <body style="font-family: sans-serif; max-width: 600px; margin: 0 auto; padding: 20px;">
@@ -64,6 +63,159 @@ impl EmailService {
 </body>
 </html>"#
             ),
+        )
+        .await?;
+
+        tracing::info!("Invite email sent to {email}");
+        Ok(())
+    }
+
+    /// Tell `to` that a widget visitor asked to be contacted by a person.
+    pub async fn send_handoff_notification(
+        &self,
+        to: &str,
+        widget_name: &str,
+        visitor_email: &str,
+        message: &str,
+    ) -> Result<()> {
+        if self.api_key.is_empty() {
+            tracing::warn!(
+                "Resend API key not configured, not notifying {to} of a handoff from {visitor_email}"
+            );
+            return Ok(());
+        }
+
+        let subject = format!("A visitor of {widget_name} asked for a person");
+        let admin_link = format!("{}/admin", self.frontend_url);
+        let widget_name = escape_html(widget_name);
+        let visitor_email = escape_html(visitor_email);
+        let message = escape_html(message);
+
+        self.send(
+            to,
+            &subject,
+            format!(
+                r#"<!DOCTYPE html>
+<html>
+<body style="font-family: sans-serif; max-width: 600px; margin: 0 auto; padding: 20px;">
+    <h2>New handoff request</h2>
+    <p>A visitor of the <strong>{widget_name}</strong> chat widget asked to be contacted.</p>
+    <p><strong>Email:</strong> {visitor_email}</p>
+    <p style="white-space: pre-wrap; padding: 12px; background: #f4f4f5; border-radius: 8px;">{message}</p>
+    <a href="{admin_link}"
+       style="display: inline-block; padding: 12px 24px; background: #18181b; color: #fafafa;
+              text-decoration: none; border-radius: 8px; font-weight: 600;">
+        Open Admin Panel
+    </a>
+    <p style="color: #71717a; font-size: 12px; margin-top: 40px;">
+        The full conversation is in the widget logs.
+    </p>
+</body>
+</html>"#
+            ),
+        )
+        .await?;
+
+        tracing::info!("Handoff notification sent to {to}");
+        Ok(())
+    }
+
+    /// Tell `to` that an embed key has used `percent` of a message cap.
+    pub async fn send_message_cap_alert(
+        &self,
+        to: &str,
+        widget_name: &str,
+        period: &str,
+        used: i64,
+        limit: i32,
+        percent: u8,
+    ) -> Result<()> {
+        if self.api_key.is_empty() {
+            tracing::warn!(
+                "Resend API key not configured, not notifying {to} that {widget_name} used {percent}% of its {period} messages"
+            );
+            return Ok(());
+        }
+
+        let subject = if percent >= 100 {
+            format!("{widget_name} reached its {period} message cap")
+        } else {
+            format!("{widget_name} used {percent}% of its {period} message cap")
+        };
+        let admin_link = format!("{}/admin", self.frontend_url);
+        let widget_name = escape_html(widget_name);
+        let consequence = if percent >= 100 {
+            "Visitors can't send more messages until the period ends or the cap is raised."
+        } else {
+            "Visitors are refused further messages once the cap is reached."
+        };
+
+        self.send(
+            to,
+            &subject,
+            format!(
+                r#"<!DOCTYPE html>
+<html>
+<body style="font-family: sans-serif; max-width: 600px; margin: 0 auto; padding: 20px;">
+    <h2>Widget message cap</h2>
+    <p>The <strong>{widget_name}</strong> chat widget has sent <strong>{used}</strong> of its
+       {limit} {period} messages ({percent}%).</p>
+    <p>{consequence}</p>
+    <a href="{admin_link}"
+       style="display: inline-block; padding: 12px 24px; background: #18181b; color: #fafafa;
+              text-decoration: none; border-radius: 8px; font-weight: 600;">
+        Open Admin Panel
+    </a>
+</body>
+</html>"#
+            ),
+        )
+        .await?;
+
+        tracing::info!("Message cap alert sent to {to}");
+        Ok(())
+    }
+
+    /// Send a widget visitor the Markdown transcript of their conversation.
+    pub async fn send_transcript(&self, to: &str, widget_title: &str, transcript: &str) -> Result<()> {
+        if self.api_key.is_empty() {
+            tracing::warn!("Resend API key not configured, not sending a {widget_title} transcript");
+            return Ok(());
+        }
+
+        let subject = format!("Your conversation with {widget_title}");
+        let widget_title = escape_html(widget_title);
+        let transcript = escape_html(transcript);
+
+        self.send(
+            to,
+            &subject,
+            format!(
+                r#"<!DOCTYPE html>
+<html>
+<body style="font-family: sans-serif; max-width: 600px; margin: 0 auto; padding: 20px;">
+    <h2>{widget_title}</h2>
+    <p>Here is the transcript of your conversation, as you asked.</p>
+    <p style="white-space: pre-wrap; padding: 12px; background: #f4f4f5; border-radius: 8px;">{transcript}</p>
+    <p style="color: #71717a; font-size: 12px; margin-top: 40px;">
+        You received this email because the address was entered in the {widget_title} chat.
+    </p>
+</body>
+</html>"#
+            ),
+        )
+        .await?;
+
+        tracing::info!("Transcript email sent");
+        Ok(())
+    }
+
+    async fn send(&self, to: &str, subject: &str, html: String) -> Result<()> {
+        let body = ResendRequest {
+            from: self.from_email.clone(),
+            to: vec![to.to_string()],
+            subject: subject.to_string(),
+            html,
         };
 
         let response = self
@@ -83,7 +235,76 @@ impl EmailService {
             ));
         }
 
-        tracing::info!("Invite email sent to {email}");
         Ok(())
     }
 }
+
+/// A cheap plausibility check for addresses typed by people: one `@`, a dotted
+/// domain, no whitespace. Deliverability is only known once we send.
+pub fn looks_like_email(email: &str) -> bool {
+    let Some((local, domain)) = email.split_once('@') else {
+        return false;
+    };
+    email.len() <= 254
+        && !local.is_empty()
+        && !domain.contains('@')
+        && domain.contains('.')
+        && !domain.starts_with('.')
+        && !domain.ends_with('.')
+        && !email.chars().any(char::is_whitespace)
+}
+
+/// The form account emails are stored and compared in: trimmed and lowercased,
+/// so `John@Example.com ` and `john@example.com` are the same account.
+pub fn normalize_email(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+/// Escape text from visitors before putting it into an email body.
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_looks_like_email() {
+        assert!(looks_like_email("visitor@example.com"));
+        assert!(looks_like_email("first.last+tag@mail.example.co.uk"));
+        assert!(!looks_like_email("visitor"));
+        assert!(!looks_like_email("@example.com"));
+        assert!(!looks_like_email("visitor@localhost"));
+        assert!(!looks_like_email("visitor@example."));
+        assert!(!looks_like_email("a@b@example.com"));
+        assert!(!looks_like_email("visitor @example.com"));
+    }
+
+    #[test]
+    fn test_normalize_email() {
+        assert_eq!(normalize_email("  John.Doe@Example.COM\n"), "john.doe@example.com");
+        assert_eq!(normalize_email("jürgen@BEISPIEL.de"), "jürgen@beispiel.de");
+        assert_eq!(normalize_email("visitor@example.com"), "visitor@example.com");
+    }
+
+    #[test]
+    fn test_escape_html() {
+        assert_eq!(
+            escape_html(r#"<script>alert("hi")</script> & 'more'"#),
+            "&lt;script&gt;alert(&quot;hi&quot;)&lt;/script&gt; &amp; &#39;more&#39;"
+        );
+        assert_eq!(escape_html("plain text"), "plain text");
+    }
+}
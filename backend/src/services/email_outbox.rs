@@ -0,0 +1,267 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::db::models::email_outbox::{EmailOutboxRepository, OutboxEmail};
+use crate::services::email::EmailService;
+use crate::services::tasks::BackgroundTasks;
+use crate::state::AppState;
+
+/// How often due emails are looked for.
+const SEND_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Attempts per email before it is marked failed.
+pub const MAX_ATTEMPTS: i32 = 5;
+
+/// Wait before the first retry; doubles with each failed attempt.
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(60);
+
+/// Longest wait between two attempts.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60 * 60);
+
+/// How long a claimed email is left to its sender before another pass may
+/// pick it up, e.g. after the process died mid-send.
+const SEND_LEASE: Duration = Duration::from_secs(5 * 60);
+
+/// Emails sent per pass.
+const SEND_BATCH: i64 = 50;
+
+/// Days sent emails are kept for inspection.
+pub const SENT_RETENTION_DAYS: u32 = 30;
+
+/// What an outbox email says. Stored as its `template` name and the fields
+/// as `payload`, and rendered by [`EmailService`] when it is sent.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "template", content = "payload", rename_all = "snake_case")]
+pub enum EmailMessage {
+    Invite {
+        token: String,
+    },
+    HandoffNotification {
+        widget_name: String,
+        visitor_email: String,
+        message: String,
+    },
+    MessageCapAlert {
+        widget_name: String,
+        period: String,
+        used: i64,
+        limit: i32,
+        percent: u8,
+    },
+    ConversationTranscript {
+        widget_title: String,
+        transcript: String,
+    },
+}
+
+impl EmailMessage {
+    /// Split into the `template` and `payload` columns.
+    fn into_parts(self) -> Result<(String, serde_json::Value)> {
+        let mut value = serde_json::to_value(self)?;
+        let template = value["template"].as_str().unwrap_or_default().to_string();
+        Ok((template, value["payload"].take()))
+    }
+
+    fn from_parts(template: &str, payload: &serde_json::Value) -> Result<Self> {
+        serde_json::from_value(serde_json::json!({ "template": template, "payload": payload }))
+            .with_context(|| format!("Unreadable '{template}' email"))
+    }
+}
+
+/// Outcome of one pass over the due emails.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct SendSummary {
+    pub sent: usize,
+    pub retrying: usize,
+    /// Gave up on after [`MAX_ATTEMPTS`].
+    pub failed: usize,
+}
+
+/// The one way emails leave the application: queued in `email_outbox`,
+/// sent right away, and retried with backoff by [`start`] when that fails.
+#[derive(Clone)]
+pub struct EmailOutbox {
+    repo: EmailOutboxRepository,
+    email: EmailService,
+    tasks: BackgroundTasks,
+}
+
+impl EmailOutbox {
+    pub fn new(repo: EmailOutboxRepository, email: EmailService, tasks: BackgroundTasks) -> Self {
+        Self { repo, email, tasks }
+    }
+
+    pub fn repo(&self) -> &EmailOutboxRepository {
+        &self.repo
+    }
+
+    /// Queue `message` for `to` and send it in the background. `reference_id`
+    /// ties the email to what it is about, e.g. an invite.
+    pub async fn enqueue(&self, to: &str, message: EmailMessage, reference_id: Option<&str>) -> Result<OutboxEmail> {
+        let (template, payload) = message.into_parts()?;
+        let email = self
+            .repo
+            .enqueue(to, &template, &payload, reference_id, SEND_LEASE)
+            .await?;
+
+        let outbox = self.clone();
+        let queued = email.clone();
+        self.tasks.spawn(async move {
+            if let Err(e) = outbox.attempt(&queued).await {
+                tracing::error!("Failed to record sending email {}: {e:#}", queued.id);
+            }
+        });
+        Ok(email)
+    }
+
+    /// Send the emails whose attempt is due.
+    pub async fn send_due(&self) -> Result<SendSummary> {
+        let mut summary = SendSummary::default();
+        for email in self.repo.claim_due(SEND_BATCH, SEND_LEASE).await? {
+            match self.attempt(&email).await? {
+                Attempt::Sent => summary.sent += 1,
+                Attempt::Retrying => summary.retrying += 1,
+                Attempt::Failed => summary.failed += 1,
+            }
+        }
+        Ok(summary)
+    }
+
+    /// Send one claimed email and record how it went.
+    async fn attempt(&self, email: &OutboxEmail) -> Result<Attempt> {
+        let result = match EmailMessage::from_parts(&email.template, &email.payload) {
+            Ok(message) => self.send(&email.recipient, &message).await,
+            Err(e) => Err(e),
+        };
+        let Err(e) = result else {
+            self.repo.mark_sent(&email.id).await?;
+            return Ok(Attempt::Sent);
+        };
+
+        let error = format!("{e:#}");
+        let attempts = email.attempts + 1;
+        if attempts >= MAX_ATTEMPTS {
+            tracing::warn!(
+                "Giving up on {} email {} to {} after {attempts} attempts: {error}",
+                email.template,
+                email.id,
+                email.recipient
+            );
+            self.repo.mark_failed(&email.id, &error).await?;
+            return Ok(Attempt::Failed);
+        }
+        let delay = retry_delay(attempts);
+        tracing::warn!(
+            "Sending {} email {} failed (attempt {attempts}/{MAX_ATTEMPTS}), retrying in {}s: {error}",
+            email.template,
+            email.id,
+            delay.as_secs()
+        );
+        self.repo.retry_later(&email.id, &error, delay).await?;
+        Ok(Attempt::Retrying)
+    }
+
+    async fn send(&self, to: &str, message: &EmailMessage) -> Result<()> {
+        match message {
+            EmailMessage::Invite { token } => self.email.send_invite(to, token).await,
+            EmailMessage::HandoffNotification {
+                widget_name,
+                visitor_email,
+                message,
+            } => {
+                self.email
+                    .send_handoff_notification(to, widget_name, visitor_email, message)
+                    .await
+            }
+            EmailMessage::MessageCapAlert {
+                widget_name,
+                period,
+                used,
+                limit,
+                percent,
+            } => {
+                self.email
+                    .send_message_cap_alert(to, widget_name, period, *used, *limit, *percent)
+                    .await
+            }
+            EmailMessage::ConversationTranscript {
+                widget_title,
+                transcript,
+            } => self.email.send_transcript(to, widget_title, transcript).await,
+        }
+    }
+}
+
+enum Attempt {
+    Sent,
+    Retrying,
+    Failed,
+}
+
+/// Wait before the attempt after `attempts` failed ones, up to [`MAX_RETRY_DELAY`].
+fn retry_delay(attempts: i32) -> Duration {
+    RETRY_BASE_DELAY
+        .saturating_mul(1 << (attempts - 1).clamp(0, 16))
+        .min(MAX_RETRY_DELAY)
+}
+
+/// Start the loop that sends due emails and retries failed ones.
+pub fn start(state: AppState) {
+    let stopping = state.tasks.stopping().clone();
+    state.tasks.clone().spawn(async move {
+        let mut interval = tokio::time::interval(SEND_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = stopping.cancelled() => break,
+            }
+            match state.email_outbox.send_due().await {
+                Ok(summary) if summary.sent > 0 || summary.failed > 0 => {
+                    tracing::info!(
+                        "Sent queued emails: {} sent, {} retrying, {} failed",
+                        summary.sent,
+                        summary.retrying,
+                        summary.failed
+                    );
+                }
+                Ok(_) => {}
+                Err(e) => tracing::error!("Failed to send queued emails: {e:#}"),
+            }
+        }
+        tracing::info!("Email outbox stopped");
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_delay_backs_off_to_the_cap() {
+        assert_eq!(retry_delay(1), RETRY_BASE_DELAY);
+        assert_eq!(retry_delay(2), RETRY_BASE_DELAY * 2);
+        assert_eq!(retry_delay(4), RETRY_BASE_DELAY * 8);
+        assert_eq!(retry_delay(10), MAX_RETRY_DELAY);
+        assert_eq!(retry_delay(i32::MAX), MAX_RETRY_DELAY);
+    }
+
+    #[test]
+    fn test_message_round_trips_through_template_and_payload() {
+        let message = EmailMessage::MessageCapAlert {
+            widget_name: "Docs".to_string(),
+            period: "monthly".to_string(),
+            used: 80,
+            limit: 100,
+            percent: 80,
+        };
+        let (template, payload) = message.clone().into_parts().unwrap();
+        assert_eq!(template, "message_cap_alert");
+        assert_eq!(payload["widget_name"], "Docs");
+        assert_eq!(EmailMessage::from_parts(&template, &payload).unwrap(), message);
+
+        let (template, payload) = EmailMessage::Invite { token: "t1".to_string() }.into_parts().unwrap();
+        assert_eq!((template.as_str(), payload), ("invite", serde_json::json!({ "token": "t1" })));
+        assert!(EmailMessage::from_parts("newsletter", &serde_json::json!({})).is_err());
+    }
+}
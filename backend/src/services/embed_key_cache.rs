@@ -0,0 +1,69 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+
+use crate::db::models::embed_key::{EmbedKey, EmbedKeyRepository};
+
+/// How long a looked-up embed key is reused by widget requests. Admin changes
+/// made through the API invalidate it at once; anything else, e.g. a direct
+/// database edit, takes effect within this bound.
+pub const EMBED_KEY_CACHE_TTL: Duration = Duration::from_secs(30);
+
+struct CacheEntry {
+    fetched_at: Instant,
+    key: EmbedKey,
+}
+
+/// In-memory cache of embed keys by key hash, so busy widgets don't look up
+/// their key once per request. Unknown hashes aren't cached; a new key is
+/// found as soon as it is created.
+pub struct EmbedKeyCache {
+    repo: EmbedKeyRepository,
+    ttl: Duration,
+    entries: RwLock<HashMap<String, CacheEntry>>,
+}
+
+impl EmbedKeyCache {
+    pub fn new(repo: EmbedKeyRepository, ttl: Duration) -> Self {
+        Self {
+            repo,
+            ttl,
+            entries: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// The key with `key_hash`, from the cache while it is fresh.
+    pub async fn find_by_hash(&self, key_hash: &str) -> Result<Option<EmbedKey>> {
+        if let Some(entry) = self.entries.read().await.get(key_hash)
+            && entry.fetched_at.elapsed() < self.ttl
+        {
+            return Ok(Some(entry.key.clone()));
+        }
+
+        let Some(key) = self.repo.find_by_hash(key_hash).await? else {
+            self.entries.write().await.remove(key_hash);
+            return Ok(None);
+        };
+        let mut entries = self.entries.write().await;
+        entries.retain(|_, e| e.fetched_at.elapsed() < self.ttl);
+        entries.insert(
+            key_hash.to_string(),
+            CacheEntry {
+                fetched_at: Instant::now(),
+                key: key.clone(),
+            },
+        );
+        Ok(Some(key))
+    }
+
+    /// Drop the cached copy of the key with `id` after it changed or was deleted.
+    pub async fn invalidate(&self, id: &str) {
+        self.entries.write().await.retain(|_, e| e.key.id != id);
+    }
+
+    /// Drop every cached key, e.g. after a configuration import.
+    pub async fn clear(&self) {
+        self.entries.write().await.clear();
+    }
+}
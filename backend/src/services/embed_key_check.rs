@@ -0,0 +1,182 @@
+use anyhow::Result;
+
+use crate::config::LlmConfig;
+use crate::db::models::admin_config::{AdminModel, AdminProvider};
+use crate::db::models::embed_key::EmbedKey;
+use crate::errors::AppError;
+use crate::state::AppState;
+
+/// The provider and model a widget of the key chats with: its own, or the
+/// configured defaults where it leaves them empty.
+pub fn effective_model(config: &LlmConfig, provider: &str, model: &str) -> (String, String) {
+    let provider = match provider {
+        "" => config.default_provider.clone(),
+        provider => provider.to_string(),
+    };
+    let model = match model {
+        "" => config.default_model.clone(),
+        model => model.to_string(),
+    };
+    (provider, model)
+}
+
+/// Why `provider` and `model` can't answer widget messages according to the
+/// admin catalogue; empty when they can.
+pub fn model_issues(provider: &str, model: &str, providers: &[AdminProvider], models: &[AdminModel]) -> Vec<String> {
+    let mut issues = Vec::new();
+    match providers.iter().find(|p| p.provider_id == provider) {
+        None => {
+            issues.push(format!("Provider '{provider}' is not configured"));
+            return issues;
+        }
+        Some(p) if !p.enabled => issues.push(format!("Provider '{provider}' is disabled")),
+        Some(p) if !p.supports_completion => {
+            issues.push(format!("Provider '{provider}' does not offer chat models"));
+            return issues;
+        }
+        Some(_) => {}
+    }
+
+    match models
+        .iter()
+        .find(|m| m.provider_id == provider && m.model_id == model && m.model_type == "completion")
+    {
+        None => issues.push(format!("Model '{model}' is not a chat model of provider '{provider}'")),
+        Some(m) if m.removed_at.is_some() => {
+            issues.push(format!("Model '{model}' is no longer offered by provider '{provider}'"))
+        }
+        Some(m) if !m.is_enabled => issues.push(format!("Model '{model}' of provider '{provider}' is disabled")),
+        Some(_) => {}
+    }
+    issues
+}
+
+/// Reject a provider and model an admin is about to set on an embed key
+/// unless the catalogue has them enabled. Left empty, both fall back to the
+/// defaults, which aren't the key's to check.
+pub async fn validate_model_choice(state: &AppState, provider: &str, model: &str) -> Result<(), AppError> {
+    if provider.is_empty() && model.is_empty() {
+        return Ok(());
+    }
+    let (provider, model) = effective_model(&state.config.llm, provider, model);
+    let providers = state.admin_config_repo.list_providers().await?;
+    let models = state.admin_config_repo.list_models(&provider).await?;
+    match model_issues(&provider, &model, &providers, &models).as_slice() {
+        [] => Ok(()),
+        issues => Err(AppError::Validation(issues.join("; "))),
+    }
+}
+
+/// Every embed key with what keeps its widget from answering, if anything.
+pub async fn check_all(state: &AppState) -> Result<Vec<(EmbedKey, Vec<String>)>> {
+    let keys = state.embed_key_repo.list_all().await?;
+    let providers = state.admin_config_repo.list_providers().await?;
+    let models = state.admin_config_repo.list_all_models().await?;
+    Ok(keys
+        .into_iter()
+        .map(|key| {
+            let (provider, model) = effective_model(&state.config.llm, &key.provider, &key.model);
+            let issues = model_issues(&provider, &model, &providers, &models);
+            (key, issues)
+        })
+        .collect())
+}
+
+/// Warn about active embed keys whose widgets would fail to answer, e.g.
+/// after their provider was disabled. Advisory only; keys keep working as
+/// far as the provider lets them.
+pub async fn log_issues(state: &AppState) {
+    match check_all(state).await {
+        Ok(checked) => {
+            for (key, issues) in checked.iter().filter(|(k, issues)| k.is_active && !issues.is_empty()) {
+                tracing::warn!("Embed key '{}' ({}): {}", key.name, key.key_prefix, issues.join("; "));
+            }
+        }
+        Err(e) => tracing::error!("Failed to check embed key models: {e:#}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn provider(id: &str, enabled: bool, supports_completion: bool) -> AdminProvider {
+        AdminProvider {
+            id: id.to_string(),
+            provider_id: id.to_string(),
+            display_name: id.to_string(),
+            enabled,
+            supports_completion,
+            supports_embeddings: true,
+            is_default_embedding: false,
+            created_at: Default::default(),
+        }
+    }
+
+    fn model(provider: &str, id: &str, model_type: &str) -> AdminModel {
+        AdminModel {
+            id: format!("{provider}/{id}"),
+            provider_id: provider.to_string(),
+            model_id: id.to_string(),
+            display_name: id.to_string(),
+            model_type: model_type.to_string(),
+            is_default: false,
+            created_at: Default::default(),
+            removed_at: None,
+            sort_order: 0,
+            is_enabled: true,
+        }
+    }
+
+    #[test]
+    fn test_model_issues() {
+        let providers = [
+            provider("openai", true, true),
+            provider("anthropic", false, true),
+            provider("voyage", true, false),
+        ];
+        let mut removed = model("openai", "gpt-4", "completion");
+        removed.removed_at = Some(Default::default());
+        let mut disabled = model("openai", "o1-pro", "completion");
+        disabled.is_enabled = false;
+        let models = [
+            model("openai", "gpt-4o", "completion"),
+            model("openai", "text-embedding-3-small", "embedding"),
+            model("anthropic", "claude-sonnet-4-20250514", "completion"),
+            removed,
+            disabled,
+        ];
+        let issues = |provider: &str, model: &str| model_issues(provider, model, &providers, &models);
+
+        assert!(issues("openai", "gpt-4o").is_empty());
+        assert_eq!(issues("anthropic", "claude-sonnet-4-20250514"), ["Provider 'anthropic' is disabled"]);
+        assert_eq!(
+            issues("anthropic", "gpt-4o"),
+            [
+                "Provider 'anthropic' is disabled",
+                "Model 'gpt-4o' is not a chat model of provider 'anthropic'"
+            ]
+        );
+        assert_eq!(issues("mistral", "mistral-large"), ["Provider 'mistral' is not configured"]);
+        assert_eq!(issues("voyage", "voyage-3"), ["Provider 'voyage' does not offer chat models"]);
+        assert_eq!(
+            issues("openai", "text-embedding-3-small"),
+            ["Model 'text-embedding-3-small' is not a chat model of provider 'openai'"]
+        );
+        assert_eq!(issues("openai", "gpt-4"), ["Model 'gpt-4' is no longer offered by provider 'openai'"]);
+        assert_eq!(issues("openai", "o1-pro"), ["Model 'o1-pro' of provider 'openai' is disabled"]);
+    }
+
+    #[test]
+    fn test_effective_model_falls_back_to_defaults() {
+        let mut config = crate::config::AppConfig::load().unwrap().llm;
+        config.default_provider = "openai".to_string();
+        config.default_model = "gpt-4o".to_string();
+        assert_eq!(effective_model(&config, "", ""), ("openai".to_string(), "gpt-4o".to_string()));
+        assert_eq!(
+            effective_model(&config, "anthropic", "claude-3-opus-20240229"),
+            ("anthropic".to_string(), "claude-3-opus-20240229".to_string())
+        );
+        assert_eq!(effective_model(&config, "groq", ""), ("groq".to_string(), "gpt-4o".to_string()));
+    }
+}
@@ -0,0 +1,443 @@
+use anyhow::Result;
+use std::collections::HashSet;
+use std::fmt::Display;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+use crate::config::LlmConfig;
+use crate::db::models::admin_config::AdminConfigRepository;
+use crate::db::models::settings::{ProviderCredentials, SettingsRepository};
+use crate::services::credentials::CredentialResolver;
+use crate::services::retry::{self, FailureKind, RetryPolicy};
+
+/// Texts sent to the embedding provider per request, before any adaptive shrinking.
+pub const EMBED_BATCH_SIZE: usize = 100;
+
+/// The provider and model used to embed documents and queries.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmbeddingTarget {
+    pub provider: String,
+    pub model: String,
+}
+
+/// An embedding target plus the credentials to call it with. `credentials` is
+/// `None` when no usable key exists for the provider.
+#[derive(Debug, Clone)]
+pub struct ResolvedEmbedding {
+    pub target: EmbeddingTarget,
+    pub credentials: Option<ProviderCredentials>,
+}
+
+impl ResolvedEmbedding {
+    pub fn api_key(&self) -> String {
+        self.credentials
+            .as_ref()
+            .map(|c| c.api_key.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn base_url(&self) -> Option<String> {
+        self.credentials.as_ref().and_then(|c| c.base_url.clone())
+    }
+}
+
+/// Pick the embedding target. Precedence:
+/// 1. the user's `preferred_embedding_model`, if an enabled provider offers it
+///    (the organization default provider wins when several do);
+/// 2. the admin-selected default embedding provider and its default model;
+/// 3. `llm.default_embedding_provider` / `llm.default_embedding_model`.
+///
+/// The completion provider is never consulted: it may not support embeddings.
+pub fn choose_embedding_target(
+    config: &LlmConfig,
+    admin_default: Option<&EmbeddingTarget>,
+    preferred_model: Option<&str>,
+    preferred_model_providers: &[String],
+) -> EmbeddingTarget {
+    let default = admin_default.cloned().unwrap_or_else(|| EmbeddingTarget {
+        provider: config.default_embedding_provider.clone(),
+        model: config.default_embedding_model.clone(),
+    });
+
+    if let Some(model) = preferred_model.filter(|m| !m.is_empty()) {
+        let provider = preferred_model_providers
+            .iter()
+            .find(|p| **p == default.provider)
+            .or_else(|| preferred_model_providers.first());
+        if let Some(provider) = provider {
+            return EmbeddingTarget {
+                provider: provider.clone(),
+                model: model.to_string(),
+            };
+        }
+        if model == default.model {
+            return default;
+        }
+        tracing::debug!(
+            "Preferred embedding model '{model}' is not offered by any enabled provider, using default"
+        );
+    }
+
+    default
+}
+
+/// Resolves embedding provider, model and key the same way for uploads, rescans,
+/// crawls, chat and the widget.
+#[derive(Clone)]
+pub struct EmbeddingResolver {
+    config: LlmConfig,
+    settings_repo: SettingsRepository,
+    admin_config_repo: AdminConfigRepository,
+    credentials: CredentialResolver,
+    /// Embed keys already logged as answering without knowledge base context.
+    widget_keys_without_embeddings: Arc<Mutex<HashSet<String>>>,
+}
+
+impl EmbeddingResolver {
+    pub fn new(
+        config: LlmConfig,
+        settings_repo: SettingsRepository,
+        admin_config_repo: AdminConfigRepository,
+        credentials: CredentialResolver,
+    ) -> Self {
+        Self {
+            config,
+            settings_repo,
+            admin_config_repo,
+            credentials,
+            widget_keys_without_embeddings: Arc::default(),
+        }
+    }
+
+    /// The organization-wide default target, ignoring user preferences.
+    pub async fn default_target(&self) -> Result<EmbeddingTarget> {
+        let admin_default = self.admin_config_repo.get_default_embedding().await?;
+        Ok(choose_embedding_target(&self.config, admin_default.as_ref(), None, &[]))
+    }
+
+    /// The target for a user, honouring their preferred embedding model.
+    pub async fn target_for_user(&self, user_id: &str) -> Result<EmbeddingTarget> {
+        let admin_default = self.admin_config_repo.get_default_embedding().await?;
+        let preferred_model = self
+            .settings_repo
+            .get_preferences(user_id)
+            .await?
+            .map(|p| p.preferred_embedding_model)
+            .filter(|m| !m.is_empty());
+
+        let providers = match &preferred_model {
+            Some(model) => self.admin_config_repo.find_embedding_model_providers(model).await?,
+            None => Vec::new(),
+        };
+
+        Ok(choose_embedding_target(
+            &self.config,
+            admin_default.as_ref(),
+            preferred_model.as_deref(),
+            &providers,
+        ))
+    }
+
+    /// Credentials for `target`: the user's key (or the organization key) when a
+    /// user is given, otherwise the shared organization key.
+    pub async fn credentials_for(
+        &self,
+        user_id: Option<&str>,
+        target: &EmbeddingTarget,
+    ) -> Result<Option<ProviderCredentials>> {
+        let resolved = match user_id {
+            Some(user_id) => self.credentials.resolve_for_use(user_id, &target.provider).await?,
+            None => self.credentials.resolve_shared(&target.provider).await?,
+        };
+        Ok(resolved.map(|r| r.credentials))
+    }
+
+    pub async fn resolve_for_user(&self, user_id: &str) -> Result<ResolvedEmbedding> {
+        let target = self.target_for_user(user_id).await?;
+        let credentials = self.credentials_for(Some(user_id), &target).await?;
+        Ok(ResolvedEmbedding {
+            target,
+            credentials,
+        })
+    }
+
+    /// For requests without a user (the widget).
+    pub async fn resolve_shared(&self) -> Result<ResolvedEmbedding> {
+        let target = self.default_target().await?;
+        let credentials = self.credentials_for(None, &target).await?;
+        Ok(ResolvedEmbedding {
+            target,
+            credentials,
+        })
+    }
+
+    /// Log that an embed key's widget answers without knowledge base context
+    /// because nothing can embed its queries: once per key rather than on every
+    /// message, and again if it recovers and later loses its key. Returns
+    /// whether it logged.
+    pub fn note_widget_embedding(&self, embed_key_id: &str, embedding: &ResolvedEmbedding) -> bool {
+        let mut logged = self
+            .widget_keys_without_embeddings
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        if embedding.credentials.is_some() {
+            logged.remove(embed_key_id);
+            return false;
+        }
+        if !logged.insert(embed_key_id.to_string()) {
+            return false;
+        }
+        tracing::warn!(
+            "Widget for embed key {embed_key_id} answers without knowledge base context: no API key for \
+             embedding provider '{}'. Add an organization key for it or choose another default embedding provider.",
+            embedding.target.provider
+        );
+        true
+    }
+}
+
+/// Embed `texts[start_at..]` in batches of up to `batch_size` and hand each
+/// batch to `store` (with the index of its first text) before moving on, so a
+/// later failure leaves earlier batches persisted and the work can resume.
+///
+/// Transient provider errors are retried per `policy`; a batch the provider
+/// rejects as too large is halved and tried again. Errors state how many texts
+/// were embedded and which ones failed.
+pub async fn embed_in_batches<T, E, F, Fut, S, SFut>(
+    texts: &[String],
+    start_at: usize,
+    batch_size: usize,
+    policy: RetryPolicy,
+    mut embed: F,
+    mut store: S,
+) -> Result<()>
+where
+    E: Display,
+    F: FnMut(Vec<String>) -> Fut,
+    Fut: Future<Output = Result<Vec<T>, E>>,
+    S: FnMut(usize, Vec<T>) -> SFut,
+    SFut: Future<Output = Result<()>>,
+{
+    let total = texts.len();
+    let mut batch_size = batch_size.max(1);
+    let mut start = start_at.min(total);
+
+    while start < total {
+        let end = (start + batch_size).min(total);
+        tracing::info!("Embedding chunks {}-{end} of {total}", start + 1);
+
+        let batch = &texts[start..end];
+        let embeddings = match retry::retry_with_backoff(policy, |_| embed(batch.to_vec())).await {
+            Ok(embeddings) => embeddings,
+            Err(e)
+                if batch_size > 1
+                    && retry::classify(&e.to_string()) == FailureKind::PayloadTooLarge =>
+            {
+                batch_size /= 2;
+                tracing::warn!("Embedding batch too large, retrying with {batch_size} chunks: {e}");
+                continue;
+            }
+            Err(e) => {
+                anyhow::bail!(
+                    "Embedded {start} of {total} chunks; chunks {}-{end} failed: {e}",
+                    start + 1
+                );
+            }
+        };
+
+        if let Err(e) = store(start, embeddings).await {
+            anyhow::bail!(
+                "Embedded {start} of {total} chunks; storing chunks {}-{end} failed: {e:#}",
+                start + 1
+            );
+        }
+        start = end;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{LlmBackend, RerankMode, RetrievalMode};
+
+    /// Anthropic for completions (no embeddings), OpenAI for embeddings.
+    fn config() -> LlmConfig {
+        LlmConfig {
+            default_provider: "anthropic".to_string(),
+            default_model: "claude-sonnet-4-20250514".to_string(),
+            default_embedding_provider: "openai".to_string(),
+            default_embedding_model: "text-embedding-3-small".to_string(),
+            default_system_prompt: String::new(),
+            retrieval_mode: RetrievalMode::Vector,
+            rag_top_k: 5,
+            rag_min_score: 0.0,
+            rag_max_context_chars: 12000,
+            rerank: RerankMode::None,
+            auto_title_enabled: false,
+            title_model: String::new(),
+            history_recent_messages: 20,
+            history_max_chars: 48000,
+            summary_models: Default::default(),
+            query_history_messages: 1,
+            query_include_summary: false,
+            query_rewrite_enabled: false,
+            backend: LlmBackend::Rig,
+            request_timeout_secs: 120,
+            embedding_request_timeout_secs: 60,
+            circuit_breaker_threshold: 5,
+            circuit_breaker_cooldown_secs: 30,
+            fallback_providers: Vec::new(),
+        }
+    }
+
+    fn target(provider: &str, model: &str) -> EmbeddingTarget {
+        EmbeddingTarget {
+            provider: provider.to_string(),
+            model: model.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_anthropic_completion_uses_openai_embeddings() {
+        let chosen = choose_embedding_target(&config(), None, None, &[]);
+        assert_eq!(chosen, target("openai", "text-embedding-3-small"));
+    }
+
+    #[test]
+    fn test_admin_default_overrides_config() {
+        let admin = target("mistral", "mistral-embed");
+        let chosen = choose_embedding_target(&config(), Some(&admin), None, &[]);
+        assert_eq!(chosen, admin);
+    }
+
+    #[test]
+    fn test_user_preference_uses_provider_that_offers_the_model() {
+        let chosen = choose_embedding_target(
+            &config(),
+            None,
+            Some("nomic-embed-text"),
+            &["ollama".to_string()],
+        );
+        assert_eq!(chosen, target("ollama", "nomic-embed-text"));
+    }
+
+    #[test]
+    fn test_user_preference_prefers_default_provider_when_ambiguous() {
+        let chosen = choose_embedding_target(
+            &config(),
+            None,
+            Some("text-embedding-3-large"),
+            &["together".to_string(), "openai".to_string()],
+        );
+        assert_eq!(chosen, target("openai", "text-embedding-3-large"));
+    }
+
+    #[test]
+    fn test_unknown_preferred_model_falls_back_to_default() {
+        let chosen = choose_embedding_target(&config(), None, Some("retired-embed"), &[]);
+        assert_eq!(chosen, target("openai", "text-embedding-3-small"));
+    }
+
+    fn fast_policy() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay: std::time::Duration::from_millis(1),
+            max_delay: std::time::Duration::from_millis(5),
+        }
+    }
+
+    fn texts(n: usize) -> Vec<String> {
+        (0..n).map(|i| format!("chunk {i}")).collect()
+    }
+
+    /// Runs `embed_in_batches` with an identity "embedding" and collects what was stored.
+    async fn run<F, Fut>(
+        count: usize,
+        start_at: usize,
+        batch_size: usize,
+        embed: F,
+    ) -> (Result<()>, Vec<(usize, Vec<String>)>)
+    where
+        F: FnMut(Vec<String>) -> Fut,
+        Fut: Future<Output = Result<Vec<String>, String>>,
+    {
+        let mut stored = Vec::new();
+        let result = embed_in_batches(&texts(count), start_at, batch_size, fast_policy(), embed, |start, batch| {
+            stored.push((start, batch));
+            async { Ok(()) }
+        })
+        .await;
+        (result, stored)
+    }
+
+    #[tokio::test]
+    async fn test_embed_in_batches_recovers_from_transient_errors() {
+        let mut calls = 0;
+        let (result, stored) = run(5, 0, 2, |batch| {
+            calls += 1;
+            let fail = calls == 1 || calls == 2;
+            async move {
+                if fail {
+                    Err("429 Too Many Requests".to_string())
+                } else {
+                    Ok(batch)
+                }
+            }
+        })
+        .await;
+
+        result.unwrap();
+        // Two failures, then three batches of 2, 2 and 1
+        assert_eq!(calls, 5);
+        let starts: Vec<usize> = stored.iter().map(|(start, _)| *start).collect();
+        assert_eq!(starts, vec![0, 2, 4]);
+        let all: Vec<String> = stored.into_iter().flat_map(|(_, batch)| batch).collect();
+        assert_eq!(all, texts(5));
+    }
+
+    #[tokio::test]
+    async fn test_embed_in_batches_halves_oversized_batches() {
+        let mut sizes = Vec::new();
+        let (result, stored) = run(8, 0, 8, |batch| {
+            sizes.push(batch.len());
+            async move {
+                if batch.len() > 2 {
+                    Err("413 Payload Too Large".to_string())
+                } else {
+                    Ok(batch)
+                }
+            }
+        })
+        .await;
+
+        result.unwrap();
+        assert_eq!(sizes, vec![8, 4, 2, 2, 2, 2]);
+        assert_eq!(stored.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_embed_in_batches_keeps_batches_before_a_failure() {
+        let (result, stored) = run(5, 0, 2, |batch| async move {
+            if batch[0] == "chunk 2" {
+                Err("Incorrect API key provided".to_string())
+            } else {
+                Ok(batch)
+            }
+        })
+        .await;
+
+        let err = result.unwrap_err().to_string();
+        assert!(err.contains("Embedded 2 of 5 chunks; chunks 3-4 failed"), "{err}");
+        assert_eq!(stored, vec![(0, texts(2))]);
+    }
+
+    #[tokio::test]
+    async fn test_embed_in_batches_resumes() {
+        let (result, stored) = run(5, 3, 2, |batch| async move { Ok(batch) }).await;
+
+        result.unwrap();
+        assert_eq!(stored, vec![(3, texts(5)[3..].to_vec())]);
+    }
+}
@@ -0,0 +1,446 @@
+use anyhow::Result;
+use std::ops::Range;
+
+use crate::config::LlmConfig;
+use crate::db::models::conversation::{ConversationSummary, Message};
+use crate::db::models::settings::ProviderCredentials;
+use crate::services::chunk_search;
+use crate::services::llm_provider::{ChatRequest, CompletionBackend, ModelRef};
+use crate::services::tokens::{MESSAGE_OVERHEAD_TOKENS, ModelTokens};
+use crate::state::AppState;
+
+/// Longest summary kept from the model; it is asked for far less.
+const MAX_SUMMARY_CHARS: usize = 4000;
+
+/// How much of each message the summary prompt includes.
+const MAX_EXCERPT_CHARS: usize = 1000;
+
+/// Messages folded into the summary per update. A long conversation from
+/// before summaries catches up over a few exchanges.
+const MAX_MESSAGES_PER_UPDATE: usize = 40;
+
+/// Tokens of the context window left free for the reply.
+const REPLY_RESERVE_TOKENS: usize = 1024;
+
+const SUMMARY_LABEL: &str = "\n\nConversation summary so far: ";
+
+const SUMMARY_PREAMBLE: &str = "You maintain a running summary of a chat conversation. \
+    Given the summary so far and the messages that follow it, reply with an updated summary \
+    of at most 300 words. Keep the facts, names, numbers, decisions and open questions the \
+    assistant would need to continue the conversation; drop pleasantries. Write in the \
+    language of the conversation. Reply with the summary only.";
+
+/// What of a conversation goes with a new message.
+#[derive(Debug)]
+pub struct PromptParts<'a> {
+    /// The system prompt, then the summary, then knowledge base context.
+    pub preamble: String,
+    /// The latest messages that fit, oldest first.
+    pub history: &'a [Message],
+}
+
+/// Build the preamble and history for a new message within the configured
+/// budget. The system prompt, knowledge base context and message are always
+/// sent. The summary comes next, cut to what is left, then as many of the
+/// latest `history_recent_messages` as fit, newest first.
+pub fn assemble_prompt<'a>(
+    config: &LlmConfig,
+    system_prompt: &str,
+    summary: &ConversationSummary,
+    rag_context: &str,
+    message: &str,
+    history: &'a [Message],
+) -> PromptParts<'a> {
+    let fixed = system_prompt.chars().count() + rag_context.chars().count() + message.chars().count();
+    let mut remaining = config.history_max_chars.saturating_sub(fixed);
+
+    let mut summary_part = String::new();
+    let label_chars = SUMMARY_LABEL.chars().count();
+    if !summary.text.is_empty() && remaining > label_chars {
+        let text: String = summary.text.chars().take(remaining - label_chars).collect();
+        remaining -= label_chars + text.chars().count();
+        summary_part = format!("{SUMMARY_LABEL}{text}");
+    }
+
+    let window = &history[history.len().saturating_sub(config.history_recent_messages)..];
+    let mut start = window.len();
+    while start > 0 {
+        let chars = window[start - 1].content.chars().count();
+        if chars > remaining {
+            break;
+        }
+        remaining -= chars;
+        start -= 1;
+    }
+
+    PromptParts {
+        preamble: format!("{system_prompt}{summary_part}{rag_context}"),
+        history: &window[start..],
+    }
+}
+
+/// What [`fit_context_window`] left out of a prompt.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Trimmed {
+    /// Knowledge base chunks, least relevant first.
+    pub context_chunks: usize,
+    /// History messages, oldest first.
+    pub messages: usize,
+}
+
+/// [`assemble_prompt`] with the knowledge base `context` chunks (best first),
+/// checked against the model's context window with room left for the reply.
+/// The character budget keeps most prompts well inside it; when one is still
+/// too long, chunks are dropped, least relevant first, then history, oldest
+/// first. The system prompt, summary and message are never cut.
+pub fn fit_context_window<'a>(
+    config: &LlmConfig,
+    tokens: ModelTokens<'_>,
+    system_prompt: &str,
+    summary: &ConversationSummary,
+    context: &[String],
+    message: &str,
+    history: &'a [Message],
+) -> (PromptParts<'a>, Trimmed) {
+    let budget = tokens.context_window().saturating_sub(REPLY_RESERVE_TOKENS);
+    let message_tokens = tokens.count(message) + MESSAGE_OVERHEAD_TOKENS;
+    let mut trimmed = Trimmed::default();
+    loop {
+        let kept = &context[..context.len() - trimmed.context_chunks];
+        let rag_context = chunk_search::format_rag_context(kept);
+        let mut parts = assemble_prompt(config, system_prompt, summary, &rag_context, message, history);
+        let history_tokens: Vec<usize> = parts
+            .history
+            .iter()
+            .map(|m| tokens.count(&m.content) + MESSAGE_OVERHEAD_TOKENS)
+            .collect();
+        let total = tokens.count(&parts.preamble)
+            + MESSAGE_OVERHEAD_TOKENS
+            + message_tokens
+            + history_tokens.iter().sum::<usize>();
+        if total <= budget {
+            return (parts, trimmed);
+        }
+        if !kept.is_empty() {
+            trimmed.context_chunks += 1;
+            continue;
+        }
+
+        let mut excess = total - budget;
+        while excess > 0 && trimmed.messages < history_tokens.len() {
+            excess = excess.saturating_sub(history_tokens[trimmed.messages]);
+            trimmed.messages += 1;
+        }
+        parts.history = &parts.history[trimmed.messages..];
+        return (parts, trimmed);
+    }
+}
+
+/// The cheap model that summarizes `provider`'s conversations, if one is configured.
+pub fn summary_model<'a>(config: &'a LlmConfig, provider: &str) -> Option<&'a str> {
+    config
+        .summary_models
+        .get(provider)
+        .map(String::as_str)
+        .filter(|m| !m.is_empty())
+}
+
+/// Messages the summary should take in next, out of `total`: those it doesn't
+/// cover yet that have left the recent window. `None` when it's up to date.
+fn pending_messages(summary: &ConversationSummary, total: usize, recent_messages: usize) -> Option<Range<usize>> {
+    let end = total
+        .saturating_sub(recent_messages)
+        .min(summary.message_count + MAX_MESSAGES_PER_UPDATE);
+    (end > summary.message_count).then_some(summary.message_count..end)
+}
+
+/// Ask `model` to fold `messages` into the summary so far.
+pub async fn summarize(
+    backend: &dyn CompletionBackend,
+    model: ModelRef<'_>,
+    previous: &str,
+    messages: &[Message],
+) -> Result<String> {
+    let model = backend.chat_model(model)?;
+
+    let transcript: Vec<String> = messages
+        .iter()
+        .filter_map(|m| {
+            let speaker = match m.role.as_str() {
+                "user" => "User",
+                "assistant" => "Assistant",
+                _ => return None,
+            };
+            let excerpt: String = m.content.chars().take(MAX_EXCERPT_CHARS).collect();
+            Some(format!("{speaker}: {excerpt}"))
+        })
+        .collect();
+    let previous = if previous.is_empty() { "(none yet)" } else { previous };
+    let prompt = format!(
+        "Summary so far:\n{previous}\n\nNew messages:\n{}",
+        transcript.join("\n\n")
+    );
+
+    let raw = model
+        .chat(ChatRequest {
+            preamble: SUMMARY_PREAMBLE.to_string(),
+            history: Vec::new(),
+            prompt,
+        })
+        .await
+        .map_err(|e| anyhow::anyhow!("LLM error: {e}"))?;
+    let summary: String = raw.text.trim().chars().take(MAX_SUMMARY_CHARS).collect();
+    if summary.is_empty() {
+        anyhow::bail!("Model returned an empty summary");
+    }
+    Ok(summary)
+}
+
+/// After an exchange that leaves the conversation with `total` messages, fold
+/// those that left the recent window into its summary in the background.
+/// Skipped when the provider has no summary model configured.
+pub fn update_summary_later(
+    state: &AppState,
+    conversation_id: &str,
+    provider: &str,
+    credentials: &ProviderCredentials,
+    summary: &ConversationSummary,
+    total: usize,
+) {
+    let config = &state.config.llm;
+    let Some(model) = summary_model(config, provider) else {
+        return;
+    };
+    let Some(pending) = pending_messages(summary, total, config.history_recent_messages) else {
+        return;
+    };
+
+    let (repo, backend) = (state.conversation_repo.clone(), state.completion_backend.clone());
+    let (conversation_id, provider, model) = (conversation_id.to_string(), provider.to_string(), model.to_string());
+    let (credentials, previous) = (credentials.clone(), summary.clone());
+    state.tasks.spawn(async move {
+        let result = async {
+            let messages = repo.get_messages(&conversation_id).await?;
+            let Some(messages) = messages.get(pending.clone()) else {
+                return Ok(false);
+            };
+            let model_ref = ModelRef {
+                provider: &provider,
+                model: &model,
+                api_key: &credentials.api_key,
+                base_url: credentials.base_url.as_deref(),
+            };
+            let text = summarize(backend.as_ref(), model_ref, &previous.text, messages).await?;
+            let updated = ConversationSummary {
+                text,
+                message_count: pending.end,
+            };
+            repo.update_summary(&conversation_id, &updated, previous.message_count)
+                .await
+        }
+        .await;
+        match result {
+            Ok(true) => tracing::debug!(
+                "Summarized conversation {conversation_id} through message {}",
+                pending.end
+            ),
+            Ok(false) => tracing::debug!("Conversation {conversation_id} summary changed meanwhile, skipping"),
+            Err(e) => tracing::warn!("Summarizing conversation {conversation_id} failed: {e:#}"),
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AppConfig;
+    use crate::db::models::conversation::MessageGeneration;
+
+    fn config(recent_messages: usize, max_chars: usize) -> LlmConfig {
+        let mut config = AppConfig::load().unwrap().llm;
+        config.history_recent_messages = recent_messages;
+        config.history_max_chars = max_chars;
+        config
+    }
+
+    fn messages(count: usize, len: usize) -> Vec<Message> {
+        (0..count)
+            .map(|i| Message {
+                id: i.to_string(),
+                conversation_id: "c1".to_string(),
+                role: if i % 2 == 0 { "user" } else { "assistant" }.to_string(),
+                content: format!("{i:0>len$}"),
+                created_at: Default::default(),
+                generation: MessageGeneration::default(),
+                deleted_at: None,
+            })
+            .collect()
+    }
+
+    fn summary(text: &str, message_count: usize) -> ConversationSummary {
+        ConversationSummary {
+            text: text.to_string(),
+            message_count,
+        }
+    }
+
+    fn ids(messages: &[Message]) -> Vec<&str> {
+        messages.iter().map(|m| m.id.as_str()).collect()
+    }
+
+    fn prompt_chars(parts: &PromptParts, message: &str) -> usize {
+        parts.preamble.chars().count()
+            + message.chars().count()
+            + parts.history.iter().map(|m| m.content.chars().count()).sum::<usize>()
+    }
+
+    #[test]
+    fn test_assemble_prompt_keeps_recent_messages_and_summary() {
+        let history = messages(30, 10);
+        let summary = summary("Talked about refunds.", 24);
+        let parts = assemble_prompt(&config(6, 10_000), "Be brief.", &summary, "", "Hi", &history);
+        assert_eq!(ids(parts.history), ids(&history[24..]));
+        assert_eq!(
+            parts.preamble,
+            "Be brief.\n\nConversation summary so far: Talked about refunds."
+        );
+
+        // Nothing summarized yet: just the system prompt
+        let none = ConversationSummary::default();
+        let parts = assemble_prompt(&config(6, 10_000), "Be brief.", &none, "", "Hi", &history);
+        assert_eq!(parts.preamble, "Be brief.");
+        assert_eq!(parts.history.len(), 6);
+
+        let short = messages(3, 10);
+        assert_eq!(
+            assemble_prompt(&config(6, 10_000), "", &none, "", "Hi", &short)
+                .history
+                .len(),
+            3
+        );
+    }
+
+    #[test]
+    fn test_assemble_prompt_fits_the_budget() {
+        let history = messages(40, 100);
+        let rag_context = format!("\n\nContext:\n{}", "x".repeat(500));
+        let long_summary = "s".repeat(300);
+        let assemble = |max_chars| {
+            assemble_prompt(
+                &config(20, max_chars),
+                "Be brief.",
+                &summary(&long_summary, 20),
+                &rag_context,
+                "Hi",
+                &history,
+            )
+        };
+
+        let fixed = "Be brief.".len() + rag_context.len() + "Hi".len();
+        for max_chars in [0, 50, 600, 700, 1000, 1500, 3000, 10_000] {
+            let parts = assemble(max_chars);
+            assert!(prompt_chars(&parts, "Hi") <= max_chars.max(fixed), "budget {max_chars}");
+            // The system prompt and context are always there; history is the latest messages
+            assert!(parts.preamble.starts_with("Be brief."));
+            assert!(parts.preamble.ends_with(&rag_context));
+            assert_eq!(ids(parts.history), ids(&history[history.len() - parts.history.len()..]));
+        }
+
+        // With room to spare everything is sent
+        let parts = assemble(10_000);
+        assert!(parts.preamble.contains(&long_summary));
+        assert_eq!(parts.history.len(), 20);
+
+        // Messages are dropped, oldest first, before the summary is cut
+        let parts = assemble(1000);
+        assert!(parts.preamble.contains(&long_summary));
+        assert_eq!(parts.history.len(), 1);
+        let parts = assemble(600);
+        assert!(parts.preamble.contains("so far: sss"));
+        assert!(!parts.preamble.contains(&long_summary));
+        assert!(parts.history.is_empty());
+    }
+
+    #[test]
+    fn test_fit_context_window_trims_context_then_history() {
+        // An unlisted Ollama model: an 8192 token window, four characters a token
+        let tokens = ModelTokens { provider: "ollama", model: "my-model" };
+        let none = ConversationSummary::default();
+        let chunks: Vec<String> = (0..6).map(|i| format!("{i:0>4000}")).collect();
+        let fit = |chunks: &[String], history| {
+            fit_context_window(&config(20, 1_000_000), tokens, "Be brief.", &none, chunks, "Hi", history)
+        };
+
+        // Small prompts go out whole
+        let short = messages(4, 100);
+        let (parts, trimmed) = fit(&chunks[..2], &short);
+        assert_eq!(trimmed, Trimmed::default());
+        assert!(parts.preamble.contains(&chunks[1]));
+        assert_eq!(parts.history.len(), 4);
+
+        // The least relevant chunk goes first
+        let history = messages(4, 2000);
+        let (parts, trimmed) = fit(&chunks, &history);
+        assert_eq!(trimmed, Trimmed { context_chunks: 1, messages: 0 });
+        assert!(parts.preamble.contains(&chunks[4]));
+        assert!(!parts.preamble.contains(&chunks[5]));
+        assert_eq!(parts.history.len(), 4);
+
+        // Then the oldest messages, once the context is gone
+        let history = messages(20, 2000);
+        let (parts, trimmed) = fit(&chunks, &history);
+        assert_eq!(trimmed.context_chunks, 6);
+        assert!(trimmed.messages > 0);
+        assert_eq!(parts.preamble, "Be brief.");
+        assert_eq!(ids(parts.history), ids(&history[trimmed.messages..]));
+        let total: usize = parts.history.iter().map(|m| tokens.count(&m.content) + MESSAGE_OVERHEAD_TOKENS).sum();
+        assert!(total <= 8192 - REPLY_RESERVE_TOKENS);
+    }
+
+    #[test]
+    fn test_pending_messages() {
+        // Within the recent window nothing needs summarizing
+        assert_eq!(pending_messages(&summary("", 0), 20, 20), None);
+        assert_eq!(pending_messages(&summary("", 0), 22, 20), Some(0..2));
+        assert_eq!(pending_messages(&summary("s", 2), 24, 20), Some(2..4));
+        assert_eq!(pending_messages(&summary("s", 4), 24, 20), None);
+        // A long backlog is taken in steps
+        assert_eq!(
+            pending_messages(&summary("", 0), 200, 20),
+            Some(0..MAX_MESSAGES_PER_UPDATE)
+        );
+        // A summary that covers more than the window leaves out (e.g. after raising it) stays
+        assert_eq!(pending_messages(&summary("s", 10), 24, 20), None);
+    }
+
+    #[test]
+    fn test_summary_model_requires_configuration() {
+        let mut config = config(20, 1000);
+        config
+            .summary_models
+            .insert("openai".to_string(), "gpt-4o-mini".to_string());
+        config.summary_models.insert("groq".to_string(), String::new());
+        assert_eq!(summary_model(&config, "openai"), Some("gpt-4o-mini"));
+        assert_eq!(summary_model(&config, "groq"), None);
+        assert_eq!(summary_model(&config, "ollama"), None);
+    }
+
+    #[tokio::test]
+    async fn test_summarize_sends_the_summary_and_new_messages() {
+        // The fake backend echoes the prompt back
+        let backend = crate::services::llm_provider::FakeBackend::new(8);
+        let model = ModelRef {
+            provider: "openai",
+            model: "gpt-4o-mini",
+            api_key: "",
+            base_url: None,
+        };
+        let summary = summarize(&backend, model, "Earlier: greetings.", &messages(2, 3))
+            .await
+            .unwrap();
+        assert_eq!(
+            summary,
+            "Summary so far:\nEarlier: greetings.\n\nNew messages:\nUser: 000\n\nAssistant: 001"
+        );
+    }
+}
@@ -0,0 +1,582 @@
+use anyhow::Result;
+use futures::FutureExt;
+use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{Notify, Semaphore};
+
+use crate::db::models::document::DocumentStatus;
+use crate::db::models::job::{Job, JobRepository};
+use crate::routes::documents::{delete_document_chunks, supersede_previous_versions};
+use crate::services::{audit, crawl_scheduler, data_export};
+use crate::services::embedding::EmbeddingTarget;
+use crate::state::AppState;
+
+/// Attempts before a job is dead-lettered.
+pub const MAX_ATTEMPTS: i32 = 3;
+
+/// Delay before the first retry; doubles with every further attempt.
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(30);
+
+/// How often an idle worker checks for due jobs (retries become due without a wake-up).
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How long a running job or rescan stays leased to its process without a
+/// renewal. Once it lapses, any process may take the work over.
+pub const LEASE: Duration = Duration::from_secs(60);
+
+/// How often a process renews the leases on the work it is running.
+const LEASE_RENEWAL: Duration = Duration::from_secs(20);
+
+/// Work the queue knows how to run. Stored as JSON in `jobs.payload`; keys are
+/// never included and are resolved for `user_id` when the job runs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum JobPayload {
+    /// Extract, chunk and embed an uploaded document.
+    DocumentEmbedding {
+        document_id: String,
+        user_id: String,
+        embedding_provider: String,
+        embedding_model: String,
+    },
+    /// Crawl a site and embed its pages.
+    CrawlEmbedding {
+        crawl_job_id: String,
+        user_id: String,
+        url: String,
+        crawl_type: String,
+        embedding_provider: String,
+        embedding_model: String,
+        /// Set for a scheduled re-crawl; its pages replace the schedule's last run.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        schedule_id: Option<String>,
+    },
+    /// Fetch one page and embed it, replacing what an earlier fetch stored.
+    PageEmbedding {
+        crawl_job_id: String,
+        user_id: String,
+        url: String,
+        embedding_provider: String,
+        embedding_model: String,
+    },
+    /// Assemble a user's personal data export.
+    DataExport { export_id: String, user_id: String },
+}
+
+impl JobPayload {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            JobPayload::DocumentEmbedding { .. } => "document_embedding",
+            JobPayload::CrawlEmbedding { .. } => "crawl_embedding",
+            JobPayload::PageEmbedding { .. } => "page_embedding",
+            JobPayload::DataExport { .. } => "data_export",
+        }
+    }
+}
+
+/// A failure retrying can't fix, such as a document with no extractable text.
+/// Jobs failing with it are dead-lettered straight away.
+#[derive(Debug, thiserror::Error)]
+#[error("{0}")]
+pub struct PermanentFailure(pub String);
+
+/// Backoff before retrying a job that has failed `attempts` times.
+pub fn retry_delay(attempts: i32) -> Duration {
+    let exponent = attempts.saturating_sub(1).clamp(0, 10) as u32;
+    RETRY_BASE_DELAY * 2u32.pow(exponent)
+}
+
+/// Postgres-backed queue for document and crawl embedding and data exports. Enqueueing wakes
+/// the worker immediately; retries are picked up by polling.
+#[derive(Clone)]
+pub struct JobQueue {
+    repo: JobRepository,
+    wake: Arc<Notify>,
+    owner: String,
+}
+
+impl JobQueue {
+    pub fn new(repo: JobRepository) -> Self {
+        Self {
+            repo,
+            wake: Arc::new(Notify::new()),
+            owner: uuid::Uuid::new_v4().to_string(),
+        }
+    }
+
+    pub fn repo(&self) -> &JobRepository {
+        &self.repo
+    }
+
+    /// Identifies this process on the leases it holds, unique to each start.
+    pub fn owner(&self) -> &str {
+        &self.owner
+    }
+
+    pub async fn enqueue(&self, payload: &JobPayload) -> Result<Job> {
+        let job = self
+            .repo
+            .enqueue(payload.kind(), &serde_json::to_value(payload)?, MAX_ATTEMPTS)
+            .await?;
+        tracing::info!("Enqueued {} job {}", job.kind, job.id);
+        self.wake.notify_one();
+        Ok(job)
+    }
+}
+
+/// Start the worker loop, running up to `server.worker_concurrency` jobs at once.
+/// It stops claiming jobs when shutdown starts; jobs still running when the grace
+/// period expires are released back to the queue for the next start.
+pub fn start_worker(state: AppState) {
+    let concurrency = state.config.server.worker_concurrency.max(1);
+    tracing::info!("Starting job worker (concurrency={concurrency})");
+
+    let tasks = state.tasks.clone();
+    tasks.spawn(async move {
+        let permits = Arc::new(Semaphore::new(concurrency));
+        let stopping = state.tasks.stopping().clone();
+
+        loop {
+            let permit = tokio::select! {
+                permit = permits.clone().acquire_owned() => {
+                    permit.expect("job semaphore is never closed")
+                }
+                _ = stopping.cancelled() => break,
+            };
+
+            let job = match state.jobs.repo().claim_next(state.jobs.owner(), LEASE).await {
+                Ok(Some(job)) => job,
+                Ok(None) => {
+                    drop(permit);
+                    tokio::select! {
+                        _ = state.jobs.wake.notified() => {}
+                        _ = tokio::time::sleep(POLL_INTERVAL) => {}
+                        _ = stopping.cancelled() => break,
+                    }
+                    continue;
+                }
+                Err(e) => {
+                    tracing::error!("Failed to claim job: {e:#}");
+                    drop(permit);
+                    tokio::select! {
+                        _ = tokio::time::sleep(POLL_INTERVAL) => {}
+                        _ = stopping.cancelled() => break,
+                    }
+                    continue;
+                }
+            };
+
+            let job_state = state.clone();
+            state.tasks.spawn(async move {
+                run_claimed(&job_state, job).await;
+                drop(permit);
+            });
+        }
+
+        tracing::info!("Job worker stopped");
+    });
+}
+
+async fn run_claimed(state: &AppState, job: Job) {
+    let repo = state.jobs.repo();
+
+    let payload: JobPayload = match serde_json::from_value(job.payload.clone()) {
+        Ok(payload) => payload,
+        Err(e) => {
+            let msg = format!("Invalid job payload: {e}");
+            tracing::error!("Job {}: {msg}", job.id);
+            let _ = repo.mark_dead(&job.id, &msg).await;
+            return;
+        }
+    };
+
+    tracing::info!(
+        "Job {}: running {} (attempt {}/{})",
+        job.id,
+        job.kind,
+        job.attempts,
+        job.max_attempts
+    );
+
+    let work = std::panic::AssertUnwindSafe(execute(state, &payload)).catch_unwind();
+    let renew = || repo.renew_lease(&job.id, state.jobs.owner(), LEASE);
+    let leased = async {
+        tokio::select! {
+            outcome = work => outcome,
+            never = keep_leased(format!("job {}", job.id), renew) => match never {},
+        }
+    };
+    let mut permanent = false;
+    let error = match state.tasks.run_until_aborted(leased).await {
+        Some(Ok(Ok(()))) => {
+            if let Err(e) = repo.complete(&job.id).await {
+                tracing::error!("Job {}: failed to mark completed: {e:#}", job.id);
+            }
+            tracing::info!("Job {} completed", job.id);
+            return;
+        }
+        Some(Ok(Err(e))) => {
+            permanent = e.downcast_ref::<PermanentFailure>().is_some();
+            format!("{e:#}")
+        }
+        Some(Err(_panic)) => "Internal error: job panicked".to_string(),
+        None => {
+            let _ = repo.release(&job.id).await;
+            tracing::warn!("Job {}: interrupted by shutdown, released to the queue", job.id);
+            return;
+        }
+    };
+
+    if !permanent && job.attempts < job.max_attempts {
+        let delay = retry_delay(job.attempts);
+        tracing::warn!(
+            "Job {} failed (attempt {}/{}), retrying in {}s: {error}",
+            job.id,
+            job.attempts,
+            job.max_attempts,
+            delay.as_secs()
+        );
+        let _ = repo.retry_later(&job.id, &error, delay).await;
+        if let JobPayload::DocumentEmbedding { document_id, .. } = &payload {
+            state.document_events.warn(
+                document_id,
+                format!(
+                    "Attempt {}/{} failed, retrying in {}s: {error}",
+                    job.attempts,
+                    job.max_attempts,
+                    delay.as_secs()
+                ),
+            );
+        }
+    } else {
+        tracing::error!("Job {} failed permanently: {error}", job.id);
+        let _ = repo.mark_dead(&job.id, &error).await;
+        mark_failed(state, &payload, &error).await;
+    }
+}
+
+async fn execute(state: &AppState, payload: &JobPayload) -> Result<()> {
+    match payload {
+        JobPayload::DocumentEmbedding {
+            document_id,
+            user_id,
+            embedding_provider,
+            embedding_model,
+        } => {
+            let Some(doc) = state.document_repo.find_by_id(document_id).await? else {
+                tracing::info!("Document {document_id} was deleted before processing");
+                return Ok(());
+            };
+
+            let target = EmbeddingTarget {
+                provider: embedding_provider.clone(),
+                model: embedding_model.clone(),
+            };
+            let credentials = state
+                .embedding
+                .credentials_for(Some(user_id), &target)
+                .await?
+                .unwrap_or_default();
+
+            // A retry continues after the chunks an earlier attempt already stored,
+            // as long as they were embedded with the same model
+            let resume_from = if doc.embedding_model.as_deref() == Some(embedding_model.as_str()) {
+                state.chunk_repo.resumable_prefix("document", &doc.id).await?
+            } else {
+                0
+            };
+            if resume_from == 0 {
+                delete_document_chunks(&state.vector_cleanup, &state.chunk_repo, &doc).await?;
+            }
+            // Record the model and collection up front so partially stored chunks
+            // can be found, resumed or deleted if this attempt fails
+            state
+                .document_repo
+                .update_embedding(
+                    &doc.id,
+                    embedding_model,
+                    &state.vector_service.collection_for_model(embedding_model),
+                )
+                .await?;
+
+            let processed = crate::routes::documents::process_document(
+                &state.storage,
+                &doc.minio_key,
+                &doc.id,
+                &doc.content_type,
+                &doc.original_filename,
+                &crate::routes::documents::chunk_labels(&doc),
+                &state.vector_service,
+                &state.chunk_repo,
+                state.embedding_backend.as_ref(),
+                embedding_provider,
+                embedding_model,
+                &credentials.api_key,
+                credentials.base_url.as_deref(),
+                resume_from,
+                state.config.extraction.max_table_rows,
+                state.config.features.ocr_enabled.then_some(&state.config.ocr),
+                &state.document_events,
+            )
+            .await?;
+
+            state
+                .document_repo
+                .update_embedding(&doc.id, embedding_model, &processed.collection)
+                .await?;
+            state
+                .document_repo
+                .set_extraction_fingerprint(&doc.id, &processed.fingerprint)
+                .await?;
+            if !state.document_repo.mark_ready(&doc.id).await? {
+                // A newer version finished first, so this one must not be retrieved
+                if let Some(doc) = state.document_repo.find_by_id(&doc.id).await? {
+                    delete_document_chunks(&state.vector_cleanup, &state.chunk_repo, &doc).await?;
+                }
+                state.document_events.info(&doc.id, "Processing finished, but a newer version replaced it");
+                return Ok(());
+            }
+            supersede_previous_versions(state, &doc).await?;
+            state.document_events.info(&doc.id, "Processing finished");
+            state.webhooks.emit(
+                "document.ready",
+                serde_json::json!({
+                    "document_id": doc.id,
+                    "filename": doc.original_filename,
+                    "user_id": user_id,
+                    "embedding_model": embedding_model,
+                }),
+            );
+            tracing::info!("Document {} processed successfully", doc.id);
+            Ok(())
+        }
+        JobPayload::CrawlEmbedding {
+            crawl_job_id,
+            user_id,
+            url,
+            crawl_type,
+            embedding_provider,
+            embedding_model,
+            schedule_id,
+        } => {
+            let target = EmbeddingTarget {
+                provider: embedding_provider.clone(),
+                model: embedding_model.clone(),
+            };
+            let credentials = state
+                .embedding
+                .credentials_for(Some(user_id), &target)
+                .await?
+                .unwrap_or_default();
+
+            state
+                .crawl_repo
+                .update_status(crawl_job_id, "running", None, None, None)
+                .await?;
+
+            crate::routes::crawl::run_crawl(
+                &state.crawler,
+                &state.crawl_repo,
+                crawl_job_id,
+                url,
+                crawl_type == "sitemap",
+                &state.vector_service,
+                &state.chunk_repo,
+                state.embedding_backend.as_ref(),
+                embedding_provider,
+                embedding_model,
+                &credentials.api_key,
+                credentials.base_url.as_deref(),
+            )
+            .await?;
+            if let Some(schedule_id) = schedule_id {
+                crawl_scheduler::replace_previous_run(state, schedule_id, crawl_job_id, embedding_model).await;
+            }
+            state.webhooks.emit(
+                "crawl.completed",
+                serde_json::json!({
+                    "crawl_job_id": crawl_job_id,
+                    "url": url,
+                    "crawl_type": crawl_type,
+                    "user_id": user_id,
+                }),
+            );
+            tracing::info!("Crawl job {crawl_job_id} completed");
+            Ok(())
+        }
+        JobPayload::PageEmbedding {
+            crawl_job_id,
+            user_id,
+            url,
+            embedding_provider,
+            embedding_model,
+        } => {
+            let target = EmbeddingTarget {
+                provider: embedding_provider.clone(),
+                model: embedding_model.clone(),
+            };
+            let credentials = state
+                .embedding
+                .credentials_for(Some(user_id), &target)
+                .await?
+                .unwrap_or_default();
+
+            crate::routes::crawl::run_page(
+                state,
+                crawl_job_id,
+                user_id,
+                url,
+                embedding_provider,
+                embedding_model,
+                &credentials.api_key,
+                credentials.base_url.as_deref(),
+            )
+            .await?;
+            state.webhooks.emit(
+                "crawl.completed",
+                serde_json::json!({
+                    "crawl_job_id": crawl_job_id,
+                    "url": url,
+                    "crawl_type": "page",
+                    "user_id": user_id,
+                }),
+            );
+            tracing::info!("Page job {crawl_job_id} completed");
+            Ok(())
+        }
+        JobPayload::DataExport { export_id, .. } => data_export::run(state, export_id).await,
+    }
+}
+
+/// Renew a lease through `renew` every [`LEASE_RENEWAL`]; never returns. Run it
+/// alongside the leased work so the lease lapses only if this process stops.
+pub async fn keep_leased<F, Fut>(what: String, renew: F) -> Infallible
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<bool>>,
+{
+    let mut ticks = tokio::time::interval(LEASE_RENEWAL);
+    // The first tick completes immediately, right after the lease was taken
+    ticks.tick().await;
+    loop {
+        ticks.tick().await;
+        match renew().await {
+            Ok(true) => {}
+            Ok(false) => tracing::warn!("Lease on {what} was lost; another process may take it over"),
+            Err(e) => tracing::warn!("Failed to renew the lease on {what}: {e:#}"),
+        }
+    }
+}
+
+/// Record the final failure on the document or crawl job the queue was working on.
+async fn mark_failed(state: &AppState, payload: &JobPayload, error: &str) {
+    let result = match payload {
+        JobPayload::DocumentEmbedding { document_id, user_id, .. } => {
+            state.document_events.error(document_id, format!("Failed: {error}"));
+            state.webhooks.emit(
+                "document.failed",
+                serde_json::json!({ "document_id": document_id, "user_id": user_id, "error": error }),
+            );
+            state
+                .document_repo
+                .update_status(document_id, &DocumentStatus::Failed, Some(error))
+                .await
+        }
+        JobPayload::CrawlEmbedding { crawl_job_id, user_id, url, .. }
+        | JobPayload::PageEmbedding { crawl_job_id, user_id, url, .. } => {
+            state.webhooks.emit(
+                "crawl.failed",
+                serde_json::json!({
+                    "crawl_job_id": crawl_job_id,
+                    "url": url,
+                    "user_id": user_id,
+                    "error": error,
+                }),
+            );
+            state
+                .crawl_repo
+                .update_status(crawl_job_id, "failed", None, None, Some(error))
+                .await
+        }
+        JobPayload::DataExport { export_id, user_id } => {
+            audit::log(
+                &state.audit,
+                Some(user_id),
+                "settings.export.failed",
+                Some("data_export"),
+                Some(export_id),
+                &format!("Personal data export failed: {error}"),
+                None,
+                None,
+            );
+            state.data_export_repo.mark_failed(export_id, error).await
+        }
+    };
+
+    if let Err(e) = result {
+        tracing::error!("Failed to record {} failure: {e:#}", payload.kind());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_delay_doubles() {
+        assert_eq!(retry_delay(1), Duration::from_secs(30));
+        assert_eq!(retry_delay(2), Duration::from_secs(60));
+        assert_eq!(retry_delay(3), Duration::from_secs(120));
+        // Out-of-range attempt counts stay bounded
+        assert_eq!(retry_delay(0), Duration::from_secs(30));
+        assert_eq!(retry_delay(100), retry_delay(11));
+    }
+
+    #[test]
+    fn test_payload_round_trip() {
+        let payload = JobPayload::DocumentEmbedding {
+            document_id: "doc-1".to_string(),
+            user_id: "user-1".to_string(),
+            embedding_provider: "openai".to_string(),
+            embedding_model: "text-embedding-3-small".to_string(),
+        };
+
+        let value = serde_json::to_value(&payload).unwrap();
+        assert_eq!(value["kind"], payload.kind());
+        assert_eq!(value["document_id"], "doc-1");
+        assert!(value.get("api_key").is_none());
+
+        let parsed: JobPayload = serde_json::from_value(value).unwrap();
+        assert_eq!(parsed, payload);
+    }
+
+    #[test]
+    fn test_crawl_payload_kind() {
+        let payload = JobPayload::CrawlEmbedding {
+            crawl_job_id: "crawl-1".to_string(),
+            user_id: "user-1".to_string(),
+            url: "https://example.com".to_string(),
+            crawl_type: "sitemap".to_string(),
+            embedding_provider: "openai".to_string(),
+            embedding_model: "text-embedding-3-small".to_string(),
+            schedule_id: None,
+        };
+
+        let mut value = serde_json::to_value(&payload).unwrap();
+        assert_eq!(value["kind"], "crawl_embedding");
+        // Payloads queued before scheduling existed have no schedule_id
+        assert!(value.get("schedule_id").is_none());
+        assert_eq!(serde_json::from_value::<JobPayload>(value.clone()).unwrap(), payload);
+
+        value["schedule_id"] = "schedule-1".into();
+        match serde_json::from_value::<JobPayload>(value).unwrap() {
+            JobPayload::CrawlEmbedding { schedule_id, .. } => {
+                assert_eq!(schedule_id.as_deref(), Some("schedule-1"));
+            }
+            other => panic!("expected a crawl payload, got {other:?}"),
+        }
+    }
+}
@@ -1,13 +1,34 @@
 use anyhow::{Context, Result};
+use futures::future::BoxFuture;
 use rig::client::completion::CompletionClientDyn;
 use rig::client::embeddings::EmbeddingsClientDyn;
 use rig::client::{ProviderClient, ProviderValue};
+use rig::completion::{Message, Prompt};
+use rig::embeddings::EmbeddingModelDyn;
 use rig::providers::{
     anthropic, cohere, deepseek, gemini, groq, mistral, ollama, openai, openrouter, perplexity,
     together, xai,
 };
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Arc;
+
+use crate::config::{AppConfig, LlmBackend};
+
+fn create_provider_boxed(
+    provider: &str,
+    api_key: &str,
+    base_url: Option<&str>,
+) -> Result<Box<dyn ProviderClient>> {
+    if api_key.is_empty() && requires_api_key(provider) {
+        anyhow::bail!("No API key provided for provider '{provider}'");
+    }
+
+    if let Some(base_url) = base_url.filter(|u| !u.is_empty()) {
+        return create_provider_with_base_url(provider, api_key, base_url);
+    }
 
-fn create_provider_boxed(provider: &str, api_key: &str) -> Result<Box<dyn ProviderClient>> {
     let value = ProviderValue::Simple(api_key.to_string());
 
     let boxed: Box<dyn ProviderClient> = match provider.to_lowercase().as_str() {
@@ -65,11 +86,111 @@ fn create_provider_boxed(provider: &str, api_key: &str) -> Result<Box<dyn Provid
     Ok(boxed)
 }
 
+/// Build a client pointed at a custom endpoint (remote Ollama, vLLM, LM Studio,
+/// LiteLLM and other OpenAI-compatible gateways).
+fn create_provider_with_base_url(
+    provider: &str,
+    api_key: &str,
+    base_url: &str,
+) -> Result<Box<dyn ProviderClient>> {
+    let boxed: Box<dyn ProviderClient> = match provider.to_lowercase().as_str() {
+        "openai" => openai::Client::builder(api_key).base_url(base_url).build().boxed(),
+        "anthropic" => anthropic::Client::builder(api_key)
+            .base_url(base_url)
+            .build()
+            .context("Failed to build Anthropic client")?
+            .boxed(),
+        "groq" => groq::Client::builder(api_key).base_url(base_url).build().boxed(),
+        "deepseek" => deepseek::Client::builder(api_key).base_url(base_url).build().boxed(),
+        "gemini" | "google" => gemini::Client::builder(api_key)
+            .base_url(base_url)
+            .build()
+            .context("Failed to build Gemini client")?
+            .boxed(),
+        "cohere" => cohere::Client::builder(api_key).base_url(base_url).build().boxed(),
+        "mistral" => mistral::Client::builder(api_key).base_url(base_url).build().boxed(),
+        "openrouter" => openrouter::Client::builder(api_key).base_url(base_url).build().boxed(),
+        "perplexity" => perplexity::Client::builder(api_key).base_url(base_url).build().boxed(),
+        "together" => together::Client::builder(api_key).base_url(base_url).build().boxed(),
+        "xai" => xai::Client::builder(api_key).base_url(base_url).build().boxed(),
+        "ollama" => ollama::Client::builder().base_url(base_url).build().boxed(),
+        other => return Err(anyhow::anyhow!("Unsupported provider: {other}")),
+    };
+
+    Ok(boxed)
+}
+
+/// Check that a user-supplied base URL is an absolute http(s) URL and return it
+/// without a trailing slash (clients append paths with their own `/`).
+pub fn validate_base_url(base_url: &str) -> Result<String> {
+    let trimmed = base_url.trim();
+    let parsed = url::Url::parse(trimmed).context("Base URL is not a valid URL")?;
+
+    if !matches!(parsed.scheme(), "http" | "https") {
+        anyhow::bail!("Base URL must use http or https");
+    }
+    if parsed.host_str().is_none() {
+        anyhow::bail!("Base URL must include a host");
+    }
+
+    Ok(trimmed.trim_end_matches('/').to_string())
+}
+
+/// Fail unless every address the host of `base_url` resolves to is public, so
+/// a custom endpoint can't reach the server's own network or cloud metadata
+/// (169.254.169.254). For requests made on behalf of non-admin users.
+pub async fn ensure_public_base_url(base_url: &str) -> Result<()> {
+    let parsed = url::Url::parse(base_url.trim()).context("Base URL is not a valid URL")?;
+    let addresses: Vec<IpAddr> = match parsed.host().context("Base URL must include a host")? {
+        url::Host::Ipv4(ip) => vec![ip.into()],
+        url::Host::Ipv6(ip) => vec![ip.into()],
+        url::Host::Domain(domain) => {
+            let port = parsed.port_or_known_default().unwrap_or(80);
+            tokio::net::lookup_host((domain, port))
+                .await
+                .with_context(|| format!("Could not resolve '{domain}'"))?
+                .map(|address| address.ip())
+                .collect()
+        }
+    };
+
+    if addresses.is_empty() || !addresses.iter().copied().all(is_public_ip) {
+        anyhow::bail!("Base URL must point to a public host");
+    }
+    Ok(())
+}
+
+/// Not loopback, private, link-local, shared (CGNAT) or otherwise reserved.
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            let [a, b, ..] = v4.octets();
+            !(v4.is_loopback()
+                || v4.is_private()
+                || v4.is_link_local()
+                || v4.is_unspecified()
+                || v4.is_broadcast()
+                || v4.is_documentation()
+                || a == 0
+                || (a == 100 && (b & 0xc0) == 64))
+        }
+        IpAddr::V6(v6) => {
+            if let Some(v4) = v6.to_ipv4_mapped() {
+                return is_public_ip(v4.into());
+            }
+            let first = v6.segments()[0];
+            // Unique local fc00::/7 and link-local fe80::/10
+            !(v6.is_loopback() || v6.is_unspecified() || (first & 0xfe00) == 0xfc00 || (first & 0xffc0) == 0xfe80)
+        }
+    }
+}
+
 pub fn create_completion_client(
     provider: &str,
     api_key: &str,
+    base_url: Option<&str>,
 ) -> Result<Box<dyn CompletionClientDyn>> {
-    let boxed = create_provider_boxed(provider, api_key)?;
+    let boxed = create_provider_boxed(provider, api_key, base_url)?;
     boxed
         .as_completion()
         .context(format!("Provider '{provider}' does not support completions"))
@@ -78,13 +199,323 @@ pub fn create_completion_client(
 pub fn create_embeddings_client(
     provider: &str,
     api_key: &str,
+    base_url: Option<&str>,
 ) -> Result<Box<dyn EmbeddingsClientDyn>> {
-    let boxed = create_provider_boxed(provider, api_key)?;
+    let boxed = create_provider_boxed(provider, api_key, base_url)?;
     boxed
         .as_embeddings()
         .context(format!("Provider '{provider}' does not support embeddings"))
 }
 
+/// A model to call and the credentials to call it with.
+#[derive(Debug, Clone, Copy)]
+pub struct ModelRef<'a> {
+    pub provider: &'a str,
+    pub model: &'a str,
+    pub api_key: &'a str,
+    pub base_url: Option<&'a str>,
+}
+
+/// One turn of a conversation sent to a chat model.
+#[derive(Debug, Clone)]
+pub struct ChatRequest {
+    pub preamble: String,
+    /// Earlier turns, oldest first.
+    pub history: Vec<Message>,
+    pub prompt: String,
+}
+
+/// Creates chat models. Handlers go through this rather than building rig
+/// clients, so tests can swap in [`FakeBackend`].
+pub trait CompletionBackend: Send + Sync {
+    fn chat_model(&self, model: ModelRef<'_>) -> Result<Box<dyn ChatModel>>;
+}
+
+/// A chat model's answer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChatReply {
+    pub text: String,
+    /// Tokens the call took, when the provider reports them.
+    pub usage: Option<TokenUsage>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TokenUsage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+pub trait ChatModel: Send + Sync {
+    fn chat(&self, request: ChatRequest) -> BoxFuture<'_, Result<ChatReply>>;
+}
+
+/// Creates embedding models; the embedding counterpart of [`CompletionBackend`].
+pub trait EmbeddingBackend: Send + Sync {
+    fn embedder(&self, model: ModelRef<'_>) -> Result<Box<dyn Embedder>>;
+}
+
+pub trait Embedder: Send + Sync {
+    /// One vector per text, in the order given.
+    fn embed_texts(&self, texts: Vec<String>) -> BoxFuture<'_, Result<Vec<Vec<f64>>>>;
+}
+
+/// The backend selected by `llm.backend`, for both completions and embeddings.
+pub fn backends(config: &AppConfig) -> (Arc<dyn CompletionBackend>, Arc<dyn EmbeddingBackend>) {
+    match config.llm.backend {
+        LlmBackend::Rig => (Arc::new(RigBackend), Arc::new(RigBackend)),
+        LlmBackend::Fake => {
+            let fake = FakeBackend::new(config.qdrant.vector_size as usize);
+            (Arc::new(fake.clone()), Arc::new(fake))
+        }
+    }
+}
+
+/// Calls the real providers through rig.
+pub struct RigBackend;
+
+struct RigChatModel {
+    client: Box<dyn CompletionClientDyn>,
+    model: String,
+}
+
+struct RigEmbedder {
+    model: Box<dyn EmbeddingModelDyn>,
+}
+
+impl CompletionBackend for RigBackend {
+    fn chat_model(&self, model: ModelRef<'_>) -> Result<Box<dyn ChatModel>> {
+        let client = create_completion_client(model.provider, model.api_key, model.base_url)?;
+        Ok(Box::new(RigChatModel {
+            client,
+            model: model.model.to_string(),
+        }))
+    }
+}
+
+impl ChatModel for RigChatModel {
+    fn chat(&self, request: ChatRequest) -> BoxFuture<'_, Result<ChatReply>> {
+        let agent = self.client.agent(&self.model).preamble(&request.preamble).build();
+        Box::pin(async move {
+            let mut history = request.history;
+            let response = agent
+                .prompt(request.prompt)
+                .with_history(&mut history)
+                .extended_details()
+                .await
+                .map_err(|e| anyhow::anyhow!("{e}"))?;
+            let usage = response.total_usage;
+            // Providers that report nothing leave every count at zero
+            let reported = usage.input_tokens > 0 || usage.output_tokens > 0;
+            Ok(ChatReply {
+                text: response.output,
+                usage: reported.then_some(TokenUsage {
+                    prompt_tokens: usage.input_tokens,
+                    completion_tokens: usage.output_tokens,
+                }),
+            })
+        })
+    }
+}
+
+impl EmbeddingBackend for RigBackend {
+    fn embedder(&self, model: ModelRef<'_>) -> Result<Box<dyn Embedder>> {
+        let client = create_embeddings_client(model.provider, model.api_key, model.base_url)?;
+        Ok(Box::new(RigEmbedder {
+            model: client.embedding_model(model.model),
+        }))
+    }
+}
+
+impl Embedder for RigEmbedder {
+    fn embed_texts(&self, texts: Vec<String>) -> BoxFuture<'_, Result<Vec<Vec<f64>>>> {
+        Box::pin(async move {
+            let embeddings = self
+                .model
+                .embed_texts(texts)
+                .await
+                .map_err(|e| anyhow::anyhow!("{e}"))?;
+            Ok(embeddings.into_iter().map(|e| e.vec).collect())
+        })
+    }
+}
+
+/// Deterministic stand-in for tests and local development without API keys:
+/// chat models echo the prompt, and embeddings are unit vectors derived from a
+/// hash of the text, so equal texts always get equal vectors.
+#[derive(Debug, Clone)]
+pub struct FakeBackend {
+    dimension: usize,
+    /// Providers whose chat models fail, with the error they fail with.
+    failing: HashMap<String, String>,
+}
+
+impl FakeBackend {
+    pub fn new(dimension: usize) -> Self {
+        Self {
+            dimension,
+            failing: HashMap::new(),
+        }
+    }
+
+    /// Make every chat call to `provider` fail with `error`, e.g. to simulate an outage.
+    pub fn failing(mut self, provider: &str, error: &str) -> Self {
+        self.failing.insert(provider.to_string(), error.to_string());
+        self
+    }
+
+    pub fn embed(&self, text: &str) -> Vec<f64> {
+        let seed = Sha256::digest(text.as_bytes());
+        let raw: Vec<f64> = (0..self.dimension.div_ceil(4))
+            .flat_map(|block| {
+                let digest = Sha256::new()
+                    .chain_update(seed)
+                    .chain_update((block as u64).to_le_bytes())
+                    .finalize();
+                digest
+                    .chunks(8)
+                    .map(|b| u64::from_le_bytes(b.try_into().unwrap()) as f64 / u64::MAX as f64 * 2.0 - 1.0)
+                    .collect::<Vec<_>>()
+            })
+            .take(self.dimension)
+            .collect();
+        let norm = raw.iter().map(|v| v * v).sum::<f64>().sqrt();
+        raw.into_iter().map(|v| v / norm).collect()
+    }
+}
+
+impl CompletionBackend for FakeBackend {
+    fn chat_model(&self, model: ModelRef<'_>) -> Result<Box<dyn ChatModel>> {
+        Ok(Box::new(FakeChatModel {
+            error: self.failing.get(model.provider).cloned(),
+        }))
+    }
+}
+
+struct FakeChatModel {
+    error: Option<String>,
+}
+
+impl ChatModel for FakeChatModel {
+    fn chat(&self, request: ChatRequest) -> BoxFuture<'_, Result<ChatReply>> {
+        Box::pin(async move {
+            if let Some(error) = &self.error {
+                anyhow::bail!("{error}");
+            }
+            Ok(ChatReply {
+                text: request.prompt,
+                usage: None,
+            })
+        })
+    }
+}
+
+impl EmbeddingBackend for FakeBackend {
+    fn embedder(&self, _model: ModelRef<'_>) -> Result<Box<dyn Embedder>> {
+        Ok(Box::new(self.clone()))
+    }
+}
+
+impl Embedder for FakeBackend {
+    fn embed_texts(&self, texts: Vec<String>) -> BoxFuture<'_, Result<Vec<Vec<f64>>>> {
+        Box::pin(async move { Ok(texts.iter().map(|t| self.embed(t)).collect()) })
+    }
+}
+
+/// Output dimensions of the embedding models in [`supported_providers`].
+const EMBEDDING_DIMENSIONS: &[(&str, u64)] = &[
+    ("text-embedding-3-small", 1536),
+    ("text-embedding-3-large", 3072),
+    ("text-embedding-ada-002", 1536),
+    ("text-embedding-004", 768),
+    ("embedding-001", 768),
+    ("embed-english-v3.0", 1024),
+    ("embed-multilingual-v3.0", 1024),
+    ("embed-english-light-v3.0", 384),
+    ("embed-multilingual-light-v3.0", 384),
+    ("mistral-embed", 1024),
+    ("togethercomputer/m2-bert-80M-8k-retrieval", 768),
+    ("BAAI/bge-large-en-v1.5", 1024),
+    ("BAAI/bge-base-en-v1.5", 768),
+    ("nomic-embed-text", 768),
+    ("mxbai-embed-large", 1024),
+    ("all-minilm", 384),
+    ("snowflake-arctic-embed", 1024),
+];
+
+/// Dimension of the vectors `model` produces: `overrides` first, then the
+/// built-in table. Ollama tags such as `nomic-embed-text:latest` match their base
+/// model. `None` for models we know nothing about.
+pub fn embedding_dimension(model: &str, overrides: &HashMap<String, u64>) -> Option<u64> {
+    if let Some(&dimension) = overrides.get(model) {
+        return Some(dimension);
+    }
+    let base = model.split_once(':').map_or(model, |(base, _)| base);
+    EMBEDDING_DIMENSIONS
+        .iter()
+        .find(|(id, _)| *id == model || *id == base)
+        .map(|&(_, dimension)| dimension)
+}
+
+/// Context windows in tokens of the completion models whose window differs
+/// from their provider's [`ProviderInfo::context_window`].
+const CONTEXT_WINDOWS: &[(&str, u32)] = &[
+    ("gpt-4", 8192),
+    ("gpt-3.5-turbo", 16385),
+    ("o1", 200_000),
+    ("o1-pro", 200_000),
+    ("o3-mini", 200_000),
+    ("mixtral-8x7b-32768", 32768),
+    ("gemma2-9b-it", 8192),
+    ("deepseek-r1-distill-llama-70b", 131_072),
+    ("gemini-1.5-pro", 2_097_152),
+    ("command", 4096),
+    ("command-light", 4096),
+    ("codestral-latest", 256_000),
+    ("anthropic/claude-sonnet-4", 200_000),
+    ("anthropic/claude-3.5-sonnet", 200_000),
+    ("google/gemini-2.0-flash-001", 1_048_576),
+    ("qwen/qwen-2.5-72b-instruct", 32768),
+    ("sonar-pro", 200_000),
+    ("Qwen/Qwen2.5-72B-Instruct-Turbo", 32768),
+    ("mistralai/Mixtral-8x7B-Instruct-v0.1", 32768),
+    ("llama3.1", 131_072),
+    ("llama3.2", 131_072),
+    ("mistral", 32768),
+    ("mixtral", 32768),
+    ("qwen2.5", 32768),
+    ("phi3", 4096),
+    ("deepseek-r1", 131_072),
+];
+
+/// Fallback for providers we know nothing about.
+const DEFAULT_CONTEXT_WINDOW: u32 = 8192;
+
+/// Context window in tokens of `provider`'s `model`: the built-in table first,
+/// then the provider's default. Ollama tags such as `llama3.1:8b` match their
+/// base model.
+pub fn context_window(provider: &str, model: &str) -> u32 {
+    let base = model.split_once(':').map_or(model, |(base, _)| base);
+    CONTEXT_WINDOWS
+        .iter()
+        .find(|(id, _)| *id == model || *id == base)
+        .map(|&(_, window)| window)
+        .or_else(|| {
+            supported_providers()
+                .iter()
+                .find(|p| p.id.eq_ignore_ascii_case(provider))
+                .map(|p| p.context_window)
+        })
+        .unwrap_or(DEFAULT_CONTEXT_WINDOW)
+}
+
+/// Whether `provider` needs an API key. Unknown providers are assumed to.
+pub fn requires_api_key(provider: &str) -> bool {
+    supported_providers()
+        .iter()
+        .find(|p| p.id.eq_ignore_ascii_case(provider))
+        .is_none_or(|p| p.requires_api_key)
+}
+
 pub fn supported_providers() -> Vec<ProviderInfo> {
     vec![
         ProviderInfo {
@@ -92,8 +523,10 @@ pub fn supported_providers() -> Vec<ProviderInfo> {
             name: "OpenAI",
             supports_completion: true,
             supports_embeddings: true,
+            requires_api_key: true,
             default_model: "gpt-4o",
             default_embedding_model: Some("text-embedding-3-small"),
+            context_window: 128000,
             completion_models: &[
                 ModelEntry { id: "gpt-4o", display_name: "GPT-4o" },
                 ModelEntry { id: "gpt-4o-mini", display_name: "GPT-4o Mini" },
@@ -116,8 +549,10 @@ pub fn supported_providers() -> Vec<ProviderInfo> {
             name: "Anthropic",
             supports_completion: true,
             supports_embeddings: false,
+            requires_api_key: true,
             default_model: "claude-sonnet-4-20250514",
             default_embedding_model: None,
+            context_window: 200000,
             completion_models: &[
                 ModelEntry { id: "claude-opus-4-20250514", display_name: "Claude Opus 4" },
                 ModelEntry { id: "claude-sonnet-4-20250514", display_name: "Claude Sonnet 4" },
@@ -132,8 +567,10 @@ pub fn supported_providers() -> Vec<ProviderInfo> {
             name: "Groq",
             supports_completion: true,
             supports_embeddings: false,
+            requires_api_key: true,
             default_model: "mixtral-8x7b-32768",
             default_embedding_model: None,
+            context_window: 128000,
             completion_models: &[
                 ModelEntry { id: "mixtral-8x7b-32768", display_name: "Mixtral 8x7B" },
                 ModelEntry { id: "llama-3.3-70b-versatile", display_name: "Llama 3.3 70B" },
@@ -149,8 +586,10 @@ pub fn supported_providers() -> Vec<ProviderInfo> {
             name: "DeepSeek",
             supports_completion: true,
             supports_embeddings: false,
+            requires_api_key: true,
             default_model: "deepseek-chat",
             default_embedding_model: None,
+            context_window: 64000,
             completion_models: &[
                 ModelEntry { id: "deepseek-chat", display_name: "DeepSeek Chat (V3)" },
                 ModelEntry { id: "deepseek-reasoner", display_name: "DeepSeek Reasoner (R1)" },
@@ -162,8 +601,10 @@ pub fn supported_providers() -> Vec<ProviderInfo> {
             name: "Google Gemini",
             supports_completion: true,
             supports_embeddings: true,
+            requires_api_key: true,
             default_model: "gemini-2.0-flash",
             default_embedding_model: Some("text-embedding-004"),
+            context_window: 1048576,
             completion_models: &[
                 ModelEntry { id: "gemini-2.5-pro-preview-06-05", display_name: "Gemini 2.5 Pro" },
                 ModelEntry { id: "gemini-2.5-flash-preview-05-20", display_name: "Gemini 2.5 Flash" },
@@ -182,8 +623,10 @@ pub fn supported_providers() -> Vec<ProviderInfo> {
             name: "Cohere",
             supports_completion: true,
             supports_embeddings: true,
+            requires_api_key: true,
             default_model: "command-r-plus",
             default_embedding_model: Some("embed-english-v3.0"),
+            context_window: 128000,
             completion_models: &[
                 ModelEntry { id: "command-r-plus", display_name: "Command R+" },
                 ModelEntry { id: "command-r", display_name: "Command R" },
@@ -202,8 +645,10 @@ pub fn supported_providers() -> Vec<ProviderInfo> {
             name: "Mistral",
             supports_completion: true,
             supports_embeddings: true,
+            requires_api_key: true,
             default_model: "mistral-large-latest",
             default_embedding_model: Some("mistral-embed"),
+            context_window: 128000,
             completion_models: &[
                 ModelEntry { id: "mistral-large-latest", display_name: "Mistral Large" },
                 ModelEntry { id: "mistral-medium-latest", display_name: "Mistral Medium" },
@@ -221,8 +666,10 @@ pub fn supported_providers() -> Vec<ProviderInfo> {
             name: "OpenRouter",
             supports_completion: true,
             supports_embeddings: false,
+            requires_api_key: true,
             default_model: "anthropic/claude-sonnet-4",
             default_embedding_model: None,
+            context_window: 128000,
             completion_models: &[
                 ModelEntry { id: "anthropic/claude-sonnet-4", display_name: "Claude Sonnet 4" },
                 ModelEntry { id: "anthropic/claude-3.5-sonnet", display_name: "Claude 3.5 Sonnet" },
@@ -242,8 +689,10 @@ pub fn supported_providers() -> Vec<ProviderInfo> {
             name: "Perplexity",
             supports_completion: true,
             supports_embeddings: false,
+            requires_api_key: true,
             default_model: "sonar",
             default_embedding_model: None,
+            context_window: 127072,
             completion_models: &[
                 ModelEntry { id: "sonar", display_name: "Sonar" },
                 ModelEntry { id: "sonar-pro", display_name: "Sonar Pro" },
@@ -257,8 +706,10 @@ pub fn supported_providers() -> Vec<ProviderInfo> {
             name: "Together AI",
             supports_completion: true,
             supports_embeddings: true,
+            requires_api_key: true,
             default_model: "meta-llama/Llama-3.3-70B-Instruct-Turbo",
             default_embedding_model: Some("togethercomputer/m2-bert-80M-8k-retrieval"),
+            context_window: 131072,
             completion_models: &[
                 ModelEntry { id: "meta-llama/Llama-3.3-70B-Instruct-Turbo", display_name: "Llama 3.3 70B Turbo" },
                 ModelEntry { id: "meta-llama/Meta-Llama-3.1-8B-Instruct-Turbo", display_name: "Llama 3.1 8B Turbo" },
@@ -279,8 +730,10 @@ pub fn supported_providers() -> Vec<ProviderInfo> {
             name: "xAI",
             supports_completion: true,
             supports_embeddings: false,
+            requires_api_key: true,
             default_model: "grok-3-mini",
             default_embedding_model: None,
+            context_window: 131072,
             completion_models: &[
                 ModelEntry { id: "grok-3", display_name: "Grok 3" },
                 ModelEntry { id: "grok-3-mini", display_name: "Grok 3 Mini" },
@@ -295,8 +748,10 @@ pub fn supported_providers() -> Vec<ProviderInfo> {
             name: "Ollama (Local)",
             supports_completion: true,
             supports_embeddings: true,
+            requires_api_key: false,
             default_model: "llama3.1:8b",
             default_embedding_model: Some("nomic-embed-text"),
+            context_window: 8192,
             completion_models: &[
                 ModelEntry { id: "llama3.1:8b", display_name: "Llama 3.1 8B" },
                 ModelEntry { id: "llama3.1:70b", display_name: "Llama 3.1 70B" },
@@ -328,8 +783,13 @@ pub struct ProviderInfo {
     pub name: &'static str,
     pub supports_completion: bool,
     pub supports_embeddings: bool,
+    /// False for providers that run without credentials (a local Ollama).
+    pub requires_api_key: bool,
     pub default_model: &'static str,
     pub default_embedding_model: Option<&'static str>,
+    /// Context window in tokens of the provider's models, unless
+    /// [`context_window`] knows better for a particular one.
+    pub context_window: u32,
     pub completion_models: &'static [ModelEntry],
     pub embedding_models: &'static [ModelEntry],
 }
@@ -339,3 +799,174 @@ pub struct ModelEntry {
     pub id: &'static str,
     pub display_name: &'static str,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_base_url() {
+        assert_eq!(
+            validate_base_url(" http://gpu-box:11434/ ").unwrap(),
+            "http://gpu-box:11434"
+        );
+        assert_eq!(
+            validate_base_url("https://llm.internal/v1").unwrap(),
+            "https://llm.internal/v1"
+        );
+        assert!(validate_base_url("ftp://example.com").is_err());
+        assert!(validate_base_url("localhost:11434").is_err());
+        assert!(validate_base_url("not a url").is_err());
+    }
+
+    #[test]
+    fn test_is_public_ip() {
+        for public in ["8.8.8.8", "104.18.0.1", "2606:4700::1111"] {
+            assert!(is_public_ip(public.parse().unwrap()), "{public}");
+        }
+        for internal in [
+            "127.0.0.1", "10.1.2.3", "172.16.0.1", "192.168.1.10", "169.254.169.254", "100.64.0.1", "0.0.0.0",
+            "::1", "fd00::1", "fe80::1", "::ffff:10.0.0.1",
+        ] {
+            assert!(!is_public_ip(internal.parse().unwrap()), "{internal}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ensure_public_base_url() {
+        assert!(ensure_public_base_url("https://8.8.8.8/v1").await.is_ok());
+        for url in [
+            "http://169.254.169.254/latest/meta-data",
+            "http://127.0.0.1:11434",
+            "http://[::1]:8080",
+            "http://10.0.0.5/v1",
+            "http://localhost:11434",
+        ] {
+            assert!(ensure_public_base_url(url).await.is_err(), "{url}");
+        }
+    }
+
+    #[test]
+    fn test_clients_use_base_url_override() {
+        let openai = create_provider_boxed("openai", "sk-test", Some("http://vllm:8000/v1")).unwrap();
+        assert!(format!("{openai:?}").contains("http://vllm:8000/v1"));
+
+        let ollama = create_provider_boxed("ollama", "", Some("http://gpu-box:11434")).unwrap();
+        assert!(format!("{ollama:?}").contains("http://gpu-box:11434"));
+
+        let default = create_provider_boxed("ollama", "", None).unwrap();
+        assert!(format!("{default:?}").contains("localhost:11434"));
+    }
+
+    #[test]
+    fn test_all_providers_accept_base_url() {
+        for provider in supported_providers() {
+            assert!(
+                create_provider_boxed(provider.id, "key", Some("http://localhost:9999")).is_ok(),
+                "provider {} failed to build with base_url",
+                provider.id
+            );
+        }
+        assert!(create_provider_boxed("unknown", "key", Some("http://localhost:9999")).is_err());
+    }
+
+    #[test]
+    fn test_embedding_dimension() {
+        let none = HashMap::new();
+        assert_eq!(embedding_dimension("text-embedding-3-large", &none), Some(3072));
+        assert_eq!(embedding_dimension("nomic-embed-text:latest", &none), Some(768));
+        assert_eq!(embedding_dimension("my-embedder", &none), None);
+
+        let overrides = HashMap::from([
+            ("my-embedder".to_string(), 256),
+            ("nomic-embed-text".to_string(), 512),
+        ]);
+        assert_eq!(embedding_dimension("my-embedder", &overrides), Some(256));
+        assert_eq!(embedding_dimension("nomic-embed-text", &overrides), Some(512));
+    }
+
+    #[test]
+    fn test_every_embedding_model_has_a_dimension() {
+        for provider in supported_providers() {
+            for model in provider.embedding_models {
+                assert!(
+                    embedding_dimension(model.id, &HashMap::new()).is_some(),
+                    "no dimension for {}",
+                    model.id
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_context_window() {
+        assert_eq!(context_window("openai", "gpt-4o"), 128_000);
+        assert_eq!(context_window("openai", "gpt-4"), 8192);
+        assert_eq!(context_window("ollama", "llama3.1:70b"), 131_072);
+        // Models we don't list get their provider's window
+        assert_eq!(context_window("ollama", "my-finetune:latest"), 8192);
+        assert_eq!(context_window("Anthropic", "claude-next"), 200_000);
+        assert_eq!(context_window("unknown", "model"), 8192);
+    }
+
+    #[test]
+    fn test_requires_api_key() {
+        assert!(!requires_api_key("ollama"));
+        assert!(!requires_api_key("Ollama"));
+        assert!(requires_api_key("openai"));
+        assert!(requires_api_key("unknown"));
+    }
+
+    #[tokio::test]
+    async fn test_fake_backend_is_deterministic() {
+        let fake = FakeBackend::new(8);
+        let model = ModelRef {
+            provider: "openai",
+            model: "gpt-4o",
+            api_key: "",
+            base_url: None,
+        };
+
+        let reply = fake
+            .chat_model(model)
+            .unwrap()
+            .chat(ChatRequest {
+                preamble: "Be brief.".to_string(),
+                history: vec![Message::user("Hi")],
+                prompt: "What is RAG?".to_string(),
+            })
+            .await
+            .unwrap();
+        assert_eq!(reply.text, "What is RAG?");
+        let failing = fake.clone().failing("openai", "503 Service Unavailable");
+        let request = ChatRequest {
+            preamble: String::new(),
+            history: Vec::new(),
+            prompt: "Hi".to_string(),
+        };
+        let error = failing.chat_model(model).unwrap().chat(request.clone()).await.unwrap_err();
+        assert_eq!(error.to_string(), "503 Service Unavailable");
+        let ollama = ModelRef { provider: "ollama", ..model };
+        assert_eq!(failing.chat_model(ollama).unwrap().chat(request).await.unwrap().text, "Hi");
+
+        let embedder = fake.embedder(model).unwrap();
+        let texts = vec!["alpha".to_string(), "beta".to_string(), "alpha".to_string()];
+        let vectors = embedder.embed_texts(texts).await.unwrap();
+        assert_eq!(vectors.len(), 3);
+        assert!(vectors.iter().all(|v| v.len() == 8));
+        assert_eq!(vectors[0], vectors[2]);
+        assert_ne!(vectors[0], vectors[1]);
+        let norm = vectors[1].iter().map(|v| v * v).sum::<f64>().sqrt();
+        assert!((norm - 1.0).abs() < 1e-9);
+        assert_eq!(FakeBackend::new(1536).embed("alpha").len(), 1536);
+    }
+
+    #[test]
+    fn test_empty_key_only_allowed_for_keyless_providers() {
+        assert!(create_completion_client("ollama", "", None).is_ok());
+        assert!(create_embeddings_client("ollama", "", Some("http://gpu-box:11434")).is_ok());
+
+        let err = create_completion_client("openai", "", None).err().unwrap();
+        assert!(err.to_string().contains("No API key"));
+    }
+}
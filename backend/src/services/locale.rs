@@ -0,0 +1,106 @@
+use std::collections::BTreeMap;
+
+/// Normalize a locale code such as `de`, `pt-BR` or `zh_Hant` to lowercase with
+/// `-` separators. Returns `None` unless it is a plausible BCP 47 tag: a 2–3
+/// letter language followed by optional 2–8 character alphanumeric subtags.
+pub fn normalize_locale(code: &str) -> Option<String> {
+    let code = code.trim().replace('_', "-").to_lowercase();
+    let mut parts = code.split('-');
+
+    let language = parts.next()?;
+    if !(2..=3).contains(&language.len()) || !language.chars().all(|c| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    if !parts.all(|p| (2..=8).contains(&p.len()) && p.chars().all(|c| c.is_ascii_alphanumeric())) {
+        return None;
+    }
+
+    Some(code)
+}
+
+/// Locales a visitor asked for, most preferred first: an explicit `?lang=`
+/// wins, followed by the `Accept-Language` entries in order of their q-value.
+/// Invalid codes and the `*` wildcard are skipped.
+pub fn requested_locales(lang: Option<&str>, accept_language: Option<&str>) -> Vec<String> {
+    let mut weighted: Vec<(String, f32)> = accept_language
+        .unwrap_or_default()
+        .split(',')
+        .filter_map(|entry| {
+            let mut parts = entry.split(';');
+            let code = normalize_locale(parts.next()?)?;
+            let q = parts
+                .find_map(|p| p.trim().strip_prefix("q=")?.parse::<f32>().ok())
+                .unwrap_or(1.0);
+            (q > 0.0).then_some((code, q))
+        })
+        .collect();
+    // Stable sort keeps header order among equal weights
+    weighted.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    lang.and_then(normalize_locale)
+        .into_iter()
+        .chain(weighted.into_iter().map(|(code, _)| code))
+        .collect()
+}
+
+/// The best entry of `localizations` for the requested locales. Each requested
+/// locale is tried exactly, then by its language alone (`de-at` falls back to `de`).
+/// Returns the matched key with its value.
+pub fn pick<'a, T>(
+    localizations: &'a BTreeMap<String, T>,
+    requested: &[String],
+) -> Option<(&'a str, &'a T)> {
+    requested.iter().find_map(|locale| {
+        let language = locale.split('-').next().unwrap_or(locale);
+        [locale.as_str(), language]
+            .into_iter()
+            .find_map(|code| localizations.get_key_value(code))
+            .map(|(code, value)| (code.as_str(), value))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_locale() {
+        assert_eq!(normalize_locale("de").as_deref(), Some("de"));
+        assert_eq!(normalize_locale("pt-BR").as_deref(), Some("pt-br"));
+        assert_eq!(normalize_locale("zh_Hant").as_deref(), Some("zh-hant"));
+        assert_eq!(normalize_locale("german"), None);
+        assert_eq!(normalize_locale("d"), None);
+        assert_eq!(normalize_locale("en-"), None);
+        assert_eq!(normalize_locale("*"), None);
+    }
+
+    #[test]
+    fn test_requested_locales() {
+        assert_eq!(
+            requested_locales(None, Some("fr-CH, fr;q=0.9, en;q=0.8, de;q=0.7, *;q=0.5")),
+            vec!["fr-ch", "fr", "en", "de"]
+        );
+        assert_eq!(
+            requested_locales(Some("de"), Some("en;q=0.5, es")),
+            vec!["de", "es", "en"]
+        );
+        assert_eq!(requested_locales(None, Some("en;q=0")), Vec::<String>::new());
+        assert!(requested_locales(None, None).is_empty());
+    }
+
+    #[test]
+    fn test_pick_falls_back_to_language() {
+        let localizations: BTreeMap<String, &str> =
+            [("de".to_string(), "Hallo"), ("pt-br".to_string(), "Olá")].into();
+
+        let requested = requested_locales(None, Some("de-AT, en"));
+        assert_eq!(pick(&localizations, &requested), Some(("de", &"Hallo")));
+        assert_eq!(
+            pick(&localizations, &["pt-br".to_string()]),
+            Some(("pt-br", &"Olá"))
+        );
+        // "pt" does not match the more specific "pt-br"
+        assert_eq!(pick(&localizations, &["pt".to_string()]), None);
+        assert_eq!(pick(&localizations, &["en".to_string()]), None);
+    }
+}
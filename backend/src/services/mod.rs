@@ -1,8 +1,48 @@
 pub mod audit;
 pub mod auth_service;
+pub mod batch_writer;
+pub mod chat_service;
+pub mod chunk_search;
+pub mod chunking;
+pub mod config_transfer;
+pub mod conversation_locks;
+pub mod conversation_purge;
+pub mod crawl_scheduler;
 pub mod crawler;
+pub mod data_export;
+pub mod credentials;
+pub mod document_events;
 pub mod email;
+pub mod email_outbox;
+pub mod embed_key_cache;
+pub mod embed_key_check;
+pub mod embedding;
+pub mod history;
+pub mod jobs;
 pub mod llm_provider;
+pub mod locale;
+pub mod logging;
+pub mod model_catalog;
+pub mod office_hours;
+pub mod provider_api;
+pub mod provider_failure;
+pub mod provider_guard;
+pub mod query_expansion;
+pub mod rate_limit;
+pub mod rerank;
+pub mod retry;
+pub mod scheduler;
+pub mod secrets;
+pub mod sse;
 pub mod storage;
+pub mod tasks;
 pub mod text_extract;
+pub mod titles;
+pub mod tokens;
+pub mod transcript;
+pub mod user_cache;
 pub mod vector;
+pub mod vector_cleanup;
+pub mod webhook;
+pub mod widget_avatar;
+pub mod widget_theme;
@@ -0,0 +1,212 @@
+use anyhow::Result;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+
+use crate::services::llm_provider;
+use crate::services::provider_api::{self, DiscoveredModel};
+
+/// How long a provider's live model list is reused before asking again.
+pub const MODEL_CACHE_TTL: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct LiveModel {
+    pub model_id: String,
+    pub display_name: String,
+    pub model_type: String,
+    /// True when the model is also in the built-in catalogue.
+    pub in_catalog: bool,
+}
+
+struct CacheEntry {
+    fetched_at: Instant,
+    models: Vec<LiveModel>,
+}
+
+/// In-memory cache of live model lists, keyed by provider, endpoint and key so
+/// users with different keys (and therefore different model access) don't share entries.
+pub struct ModelCatalogCache {
+    ttl: Duration,
+    entries: RwLock<HashMap<String, CacheEntry>>,
+}
+
+impl ModelCatalogCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Return the cached model list for these credentials, fetching it if missing or stale.
+    pub async fn get_or_fetch(
+        &self,
+        provider: &str,
+        api_key: &str,
+        base_url: Option<&str>,
+    ) -> Result<Vec<LiveModel>> {
+        let key = cache_key(provider, api_key, base_url);
+
+        if let Some(entry) = self.entries.read().await.get(&key)
+            && entry.fetched_at.elapsed() < self.ttl
+        {
+            return Ok(entry.models.clone());
+        }
+
+        self.refresh(provider, api_key, base_url).await
+    }
+
+    /// Fetch from the provider unconditionally and replace the cached entry.
+    pub async fn refresh(
+        &self,
+        provider: &str,
+        api_key: &str,
+        base_url: Option<&str>,
+    ) -> Result<Vec<LiveModel>> {
+        let discovered = provider_api::fetch_models(provider, api_key, base_url).await?;
+        let models = merge_with_catalog(provider, discovered);
+
+        let mut entries = self.entries.write().await;
+        entries.retain(|_, e| e.fetched_at.elapsed() < self.ttl);
+        entries.insert(
+            cache_key(provider, api_key, base_url),
+            CacheEntry {
+                fetched_at: Instant::now(),
+                models: models.clone(),
+            },
+        );
+
+        Ok(models)
+    }
+}
+
+fn cache_key(provider: &str, api_key: &str, base_url: Option<&str>) -> String {
+    let key_hash = Sha256::digest(api_key.as_bytes());
+    let key_hash: String = key_hash[..8].iter().map(|b| format!("{b:02x}")).collect();
+    format!(
+        "{}|{}|{key_hash}",
+        provider.to_lowercase(),
+        base_url.unwrap_or_default()
+    )
+}
+
+/// Attach our static display names and model types to the ids the provider
+/// reported, falling back to the provider's own name (or the id) for new models.
+pub fn merge_with_catalog(provider: &str, discovered: Vec<DiscoveredModel>) -> Vec<LiveModel> {
+    let info = llm_provider::supported_providers()
+        .into_iter()
+        .find(|p| p.id.eq_ignore_ascii_case(provider));
+
+    let mut models: Vec<LiveModel> = discovered
+        .into_iter()
+        .map(|m| {
+            let catalog_entry = info.as_ref().and_then(|info| {
+                info.completion_models
+                    .iter()
+                    .map(|e| (e, "completion"))
+                    .chain(info.embedding_models.iter().map(|e| (e, "embedding")))
+                    .find(|(e, _)| e.id == m.id)
+            });
+
+            match catalog_entry {
+                Some((entry, model_type)) => LiveModel {
+                    model_id: m.id,
+                    display_name: entry.display_name.to_string(),
+                    model_type: model_type.to_string(),
+                    in_catalog: true,
+                },
+                None => LiveModel {
+                    display_name: m.name.unwrap_or_else(|| m.id.clone()),
+                    model_id: m.id,
+                    model_type: m.model_type.to_string(),
+                    in_catalog: false,
+                },
+            }
+        })
+        .collect();
+
+    models.sort_by(|a, b| {
+        (&a.model_type, &a.display_name).cmp(&(&b.model_type, &b.display_name))
+    });
+    models.dedup_by(|a, b| a.model_id == b.model_id && a.model_type == b.model_type);
+    models
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn discovered(id: &str, name: Option<&str>, model_type: &'static str) -> DiscoveredModel {
+        DiscoveredModel {
+            id: id.to_string(),
+            name: name.map(str::to_string),
+            model_type,
+        }
+    }
+
+    #[test]
+    fn test_merge_prefers_catalog_names() {
+        let models = merge_with_catalog(
+            "openai",
+            vec![
+                discovered("gpt-4o", None, "completion"),
+                discovered("gpt-5-preview", Some("GPT-5 Preview"), "completion"),
+                discovered("text-embedding-3-small", None, "embedding"),
+                discovered("some-new-model", None, "completion"),
+            ],
+        );
+
+        let gpt4o = models.iter().find(|m| m.model_id == "gpt-4o").unwrap();
+        assert_eq!(gpt4o.display_name, "GPT-4o");
+        assert!(gpt4o.in_catalog);
+
+        let preview = models.iter().find(|m| m.model_id == "gpt-5-preview").unwrap();
+        assert_eq!(preview.display_name, "GPT-5 Preview");
+        assert!(!preview.in_catalog);
+
+        let unnamed = models.iter().find(|m| m.model_id == "some-new-model").unwrap();
+        assert_eq!(unnamed.display_name, "some-new-model");
+
+        // Completions sort before embeddings
+        assert_eq!(models.last().unwrap().model_type, "embedding");
+    }
+
+    #[test]
+    fn test_cache_key_separates_credentials() {
+        let a = cache_key("openai", "key-a", None);
+        let b = cache_key("openai", "key-b", None);
+        let c = cache_key("OpenAI", "key-a", Some("http://proxy"));
+        assert_ne!(a, b);
+        assert_ne!(a, c);
+        assert!(!a.contains("key-a"));
+        assert_eq!(a, cache_key("OPENAI", "key-a", None));
+    }
+
+    #[tokio::test]
+    async fn test_cached_entry_is_reused() {
+        let cache = ModelCatalogCache::new(MODEL_CACHE_TTL);
+        let models = vec![LiveModel {
+            model_id: "llama3".to_string(),
+            display_name: "llama3".to_string(),
+            model_type: "completion".to_string(),
+            in_catalog: false,
+        }];
+        cache.entries.write().await.insert(
+            cache_key("ollama", "", Some("http://127.0.0.1:9")),
+            CacheEntry {
+                fetched_at: Instant::now(),
+                models,
+            },
+        );
+
+        // A fresh entry is served without contacting the (unreachable) provider
+        let cached = cache
+            .get_or_fetch("ollama", "", Some("http://127.0.0.1:9"))
+            .await
+            .unwrap();
+        assert_eq!(cached[0].model_id, "llama3");
+    }
+}
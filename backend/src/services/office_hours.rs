@@ -0,0 +1,209 @@
+use anyhow::Result;
+use chrono::{DateTime, Datelike, Timelike, Utc, Weekday};
+use chrono_tz::Tz;
+use std::collections::BTreeMap;
+
+use crate::db::models::embed_key::{EmbedKey, OfficeHours, OpeningRange};
+
+/// Day keys of [`OfficeHours::days`], Monday first.
+pub const DAYS: [&str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+const MAX_RANGES_PER_DAY: usize = 8;
+const MINUTES_PER_DAY: u32 = 24 * 60;
+
+/// Check a schedule and normalize its day keys and times.
+pub fn validate(hours: OfficeHours) -> Result<OfficeHours> {
+    let timezone = hours.timezone.trim().to_string();
+    if timezone.parse::<Tz>().is_err() {
+        anyhow::bail!("Unknown time zone '{timezone}'. Use an IANA name such as 'Europe/Berlin'");
+    }
+
+    let mut days = BTreeMap::new();
+    for (day, ranges) in hours.days {
+        let key = day.trim().to_ascii_lowercase();
+        if !DAYS.contains(&key.as_str()) {
+            anyhow::bail!("Unknown day '{day}'. Use one of {}", DAYS.join(", "));
+        }
+        if ranges.len() > MAX_RANGES_PER_DAY {
+            anyhow::bail!("{key} has {} ranges; the limit is {MAX_RANGES_PER_DAY}", ranges.len());
+        }
+        let ranges = ranges
+            .into_iter()
+            .map(|range| {
+                let (start, end) = match (parse_time(&range.start), parse_time(&range.end)) {
+                    (Some(start), Some(end)) if start < MINUTES_PER_DAY => (start, end),
+                    _ => anyhow::bail!(
+                        "{key} range '{}-{}' must be HH:MM times; only the end may be 24:00",
+                        range.start,
+                        range.end
+                    ),
+                };
+                if start == end {
+                    anyhow::bail!(
+                        "{key} range '{}-{}' is empty; use 00:00-24:00 for the whole day",
+                        range.start,
+                        range.end
+                    );
+                }
+                Ok(OpeningRange {
+                    start: format_time(start),
+                    end: format_time(end),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if days.insert(key.clone(), ranges).is_some() {
+            anyhow::bail!("{key} is listed more than once");
+        }
+    }
+
+    Ok(OfficeHours { timezone, days })
+}
+
+/// Whether `now` falls in an opening range, in the schedule's time zone. A
+/// range ending before it starts is open until its end on the next day.
+/// Schedules that don't parse, which validation prevents, count as open.
+pub fn is_open(hours: &OfficeHours, now: DateTime<Utc>) -> bool {
+    let Ok(tz) = hours.timezone.parse::<Tz>() else {
+        return true;
+    };
+    let local = now.with_timezone(&tz);
+    let minute = local.hour() * 60 + local.minute();
+    let ranges = |day: Weekday| {
+        hours
+            .days
+            .get(DAYS[day.num_days_from_monday() as usize])
+            .into_iter()
+            .flatten()
+            .filter_map(|r| Some((parse_time(&r.start)?, parse_time(&r.end)?)))
+    };
+
+    let today = local.weekday();
+    ranges(today).any(|(start, end)| {
+        if start < end {
+            (start..end).contains(&minute)
+        } else {
+            minute >= start
+        }
+    }) || ranges(today.pred()).any(|(start, end)| end < start && minute < end)
+}
+
+/// Whether the key's widget is staffed at `now`; keys without office hours always are.
+pub fn is_online(key: &EmbedKey, now: DateTime<Utc>) -> bool {
+    key.office_hours.as_ref().is_none_or(|hours| is_open(hours, now))
+}
+
+/// Minutes since midnight of an `HH:MM` time, up to `24:00`.
+fn parse_time(value: &str) -> Option<u32> {
+    let (hours, minutes) = value.trim().split_once(':')?;
+    if hours.len() != 2 || minutes.len() != 2 {
+        return None;
+    }
+    let hours: u32 = hours.parse().ok()?;
+    let minutes: u32 = minutes.parse().ok()?;
+    let total = hours * 60 + minutes;
+    (minutes < 60 && total <= MINUTES_PER_DAY).then_some(total)
+}
+
+fn format_time(minutes: u32) -> String {
+    format!("{:02}:{:02}", minutes / 60, minutes % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn schedule(timezone: &str, days: &[(&str, &str, &str)]) -> OfficeHours {
+        let mut hours = OfficeHours {
+            timezone: timezone.to_string(),
+            days: BTreeMap::new(),
+        };
+        for (day, start, end) in days {
+            hours.days.entry(day.to_string()).or_default().push(OpeningRange {
+                start: start.to_string(),
+                end: end.to_string(),
+            });
+        }
+        hours
+    }
+
+    fn utc(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, m, d, h, min, 0).unwrap()
+    }
+
+    #[test]
+    fn test_validate_normalizes_days_and_rejects_bad_input() {
+        let hours = validate(schedule(" UTC ", &[("Mon", "09:00", "17:30")])).unwrap();
+        assert_eq!(hours.timezone, "UTC");
+        assert_eq!(hours.days["mon"][0].end, "17:30");
+
+        assert!(validate(schedule("Mars/Olympus", &[])).is_err());
+        assert!(validate(schedule("UTC", &[("monday", "09:00", "17:00")])).is_err());
+        assert!(validate(schedule("UTC", &[("mon", "9:00", "17:00")])).is_err());
+        assert!(validate(schedule("UTC", &[("mon", "09:60", "17:00")])).is_err());
+        assert!(validate(schedule("UTC", &[("mon", "24:00", "02:00")])).is_err());
+        assert!(validate(schedule("UTC", &[("mon", "09:00", "09:00")])).is_err());
+        assert!(validate(schedule("UTC", &[("mon", "00:00", "24:00")])).is_ok());
+
+        let mut duplicate = schedule("UTC", &[("mon", "09:00", "12:00")]);
+        duplicate.days.insert("MON".to_string(), Vec::new());
+        assert!(validate(duplicate).is_err());
+    }
+
+    #[test]
+    fn test_is_open_within_same_day_ranges() {
+        // 2026-10-16 is a Friday
+        let hours = schedule("UTC", &[("fri", "09:00", "12:00"), ("fri", "13:00", "24:00")]);
+        assert!(!is_open(&hours, utc(2026, 10, 16, 8, 59)));
+        assert!(is_open(&hours, utc(2026, 10, 16, 9, 0)));
+        assert!(!is_open(&hours, utc(2026, 10, 16, 12, 0)));
+        assert!(is_open(&hours, utc(2026, 10, 16, 23, 59)));
+        assert!(!is_open(&hours, utc(2026, 10, 17, 0, 0)));
+    }
+
+    #[test]
+    fn test_is_open_across_midnight() {
+        let hours = schedule("UTC", &[("fri", "22:00", "02:00"), ("sun", "23:00", "01:00")]);
+        assert!(!is_open(&hours, utc(2026, 10, 16, 21, 59)));
+        assert!(is_open(&hours, utc(2026, 10, 16, 23, 30)));
+        // Saturday morning belongs to Friday's range
+        assert!(is_open(&hours, utc(2026, 10, 17, 1, 59)));
+        assert!(!is_open(&hours, utc(2026, 10, 17, 2, 0)));
+        assert!(!is_open(&hours, utc(2026, 10, 17, 23, 30)));
+        // Sunday's range runs into Monday, wrapping the week
+        assert!(is_open(&hours, utc(2026, 10, 19, 0, 30)));
+        assert!(!is_open(&hours, utc(2026, 10, 19, 1, 0)));
+    }
+
+    #[test]
+    fn test_is_open_follows_daylight_saving_changes() {
+        let weekdays = [("fri", "09:00", "17:00"), ("mon", "09:00", "17:00")];
+        let hours = schedule("Europe/Berlin", &weekdays);
+        // Berlin moves to UTC+2 on 2026-03-29, so opening moves from 08:00 to 07:00 UTC
+        assert!(!is_open(&hours, utc(2026, 3, 27, 7, 30)));
+        assert!(is_open(&hours, utc(2026, 3, 27, 8, 0)));
+        assert!(is_open(&hours, utc(2026, 3, 30, 7, 30)));
+        assert!(!is_open(&hours, utc(2026, 3, 30, 15, 0)));
+        // And back to UTC+1 on 2026-10-25
+        assert!(is_open(&hours, utc(2026, 10, 23, 14, 30)));
+        assert!(is_open(&hours, utc(2026, 10, 26, 15, 30)));
+        assert!(!is_open(&hours, utc(2026, 10, 26, 16, 0)));
+    }
+
+    #[test]
+    fn test_overnight_range_on_the_night_clocks_go_back() {
+        // Sat 22:00 to Sun 02:00 local; 2026-10-25 has an extra hour at 02:00-03:00
+        let hours = schedule("Europe/Berlin", &[("sat", "22:00", "02:00")]);
+        assert!(is_open(&hours, utc(2026, 10, 24, 20, 0)));
+        // 01:30 local, still summer time
+        assert!(is_open(&hours, utc(2026, 10, 24, 23, 30)));
+        // 02:30 local summer time, then 02:30 again in winter time
+        assert!(!is_open(&hours, utc(2026, 10, 25, 0, 30)));
+        assert!(!is_open(&hours, utc(2026, 10, 25, 1, 30)));
+    }
+
+    #[test]
+    fn test_empty_schedule_is_always_closed() {
+        let hours = schedule("UTC", &[]);
+        assert!(!is_open(&hours, utc(2026, 10, 16, 12, 0)));
+    }
+}
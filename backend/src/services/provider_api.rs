@@ -0,0 +1,485 @@
+use anyhow::{Context, Result};
+use rig::completion::Prompt;
+use std::time::Duration;
+
+use crate::services::llm_provider;
+
+/// Upper bound for a connection test, including DNS, TLS and the provider's response.
+pub const CONNECTION_TEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Longest provider error message passed back to the client.
+const MAX_ERROR_CHARS: usize = 200;
+
+/// How a provider expects the API key on a plain HTTP request.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum KeyAuth {
+    Bearer,
+    /// Anthropic: `x-api-key` plus a pinned `anthropic-version`.
+    AnthropicHeader,
+    /// Gemini: `?key=` query parameter.
+    QueryParam,
+    /// Ollama has no authentication.
+    None,
+}
+
+/// A cheap, authenticated "list models" endpoint for a provider.
+#[derive(Debug, Clone, Copy)]
+struct ModelsEndpoint {
+    /// Same default base URL the rig client uses, so a `base_url` override
+    /// resolves to the same host for both.
+    default_base: &'static str,
+    path: &'static str,
+    auth: KeyAuth,
+    /// Whether the endpoint rejects a bad key (OpenRouter lists models anonymously).
+    validates_key: bool,
+}
+
+fn models_endpoint(provider: &str) -> Option<ModelsEndpoint> {
+    let mut validates_key = true;
+    let (default_base, path, auth) = match provider.to_lowercase().as_str() {
+        "openai" => ("https://api.openai.com/v1", "/models", KeyAuth::Bearer),
+        "anthropic" => ("https://api.anthropic.com", "/v1/models", KeyAuth::AnthropicHeader),
+        "gemini" | "google" => (
+            "https://generativelanguage.googleapis.com",
+            "/v1beta/models",
+            KeyAuth::QueryParam,
+        ),
+        "groq" => ("https://api.groq.com/openai/v1", "/models", KeyAuth::Bearer),
+        "mistral" => ("https://api.mistral.ai", "/v1/models", KeyAuth::Bearer),
+        "together" => ("https://api.together.xyz", "/v1/models", KeyAuth::Bearer),
+        "deepseek" => ("https://api.deepseek.com", "/models", KeyAuth::Bearer),
+        "xai" => ("https://api.x.ai", "/v1/models", KeyAuth::Bearer),
+        "cohere" => ("https://api.cohere.ai", "/v1/models", KeyAuth::Bearer),
+        "ollama" => ("http://localhost:11434", "/api/tags", KeyAuth::None),
+        "openrouter" => {
+            validates_key = false;
+            ("https://openrouter.ai/api/v1", "/models", KeyAuth::Bearer)
+        }
+        // perplexity has no models endpoint
+        _ => return None,
+    };
+
+    Some(ModelsEndpoint {
+        default_base,
+        path,
+        auth,
+        validates_key,
+    })
+}
+
+/// Build the models-listing request for `provider`, or `None` if the provider
+/// has no such endpoint and must be tested with a completion instead.
+fn models_request(
+    client: &reqwest::Client,
+    provider: &str,
+    api_key: &str,
+    base_url: Option<&str>,
+) -> Option<reqwest::RequestBuilder> {
+    let endpoint = models_endpoint(provider)?;
+    let base = base_url
+        .filter(|u| !u.is_empty())
+        .unwrap_or(endpoint.default_base)
+        .trim_end_matches('/');
+    let request = client.get(format!("{base}{}", endpoint.path));
+
+    Some(match endpoint.auth {
+        KeyAuth::Bearer => request.bearer_auth(api_key),
+        KeyAuth::AnthropicHeader => request
+            .header("x-api-key", api_key)
+            .header("anthropic-version", "2023-06-01"),
+        KeyAuth::QueryParam => request.query(&[("key", api_key)]),
+        KeyAuth::None => request,
+    })
+}
+
+/// Check that `api_key` (and optional `base_url`) can talk to `provider`.
+///
+/// Lists models where the provider supports it, otherwise sends a one-token
+/// completion to the provider's default model. Nothing is persisted. The
+/// returned error is already sanitized and safe to show to the user.
+pub async fn test_connection(provider: &str, api_key: &str, base_url: Option<&str>) -> Result<()> {
+    let result = tokio::time::timeout(
+        CONNECTION_TEST_TIMEOUT,
+        run_connection_test(provider, api_key, base_url),
+    )
+    .await
+    .unwrap_or_else(|_| {
+        Err(anyhow::anyhow!(
+            "Timed out after {}s",
+            CONNECTION_TEST_TIMEOUT.as_secs()
+        ))
+    });
+
+    result.map_err(|e| anyhow::anyhow!(sanitize_provider_error(&format!("{e:#}"), api_key)))
+}
+
+/// Client for requests to a provider's endpoints. Redirects are not followed:
+/// a base URL that passed the public-host check could otherwise bounce the
+/// request to the server's own network.
+fn http_client() -> Result<reqwest::Client> {
+    reqwest::Client::builder()
+        .timeout(CONNECTION_TEST_TIMEOUT)
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .context("Failed to build HTTP client")
+}
+
+async fn run_connection_test(provider: &str, api_key: &str, base_url: Option<&str>) -> Result<()> {
+    let client = http_client()?;
+
+    let validates_key = models_endpoint(provider).is_some_and(|e| e.validates_key);
+    if validates_key && let Some(request) = models_request(&client, provider, api_key, base_url) {
+        send_checked(request).await?;
+        return Ok(());
+    }
+
+    let default_model = llm_provider::supported_providers()
+        .into_iter()
+        .find(|p| p.id.eq_ignore_ascii_case(provider))
+        .map(|p| p.default_model)
+        .ok_or_else(|| anyhow::anyhow!("Unsupported provider: {provider}"))?;
+
+    let completion_client = llm_provider::create_completion_client(provider, api_key, base_url)?;
+    completion_client
+        .agent(default_model)
+        .max_tokens(1)
+        .build()
+        .prompt("ping")
+        .await
+        .map_err(|e| anyhow::anyhow!("{e}"))?;
+
+    Ok(())
+}
+
+/// Send a request and turn a non-2xx status into an error. Only the status is
+/// reported: the body may come from whatever host a custom base URL names.
+async fn send_checked(request: reqwest::RequestBuilder) -> Result<reqwest::Response> {
+    let response = request.send().await.context("Request failed")?;
+    let status = response.status();
+    if !status.is_success() {
+        anyhow::bail!("Provider answered {status}");
+    }
+    Ok(response)
+}
+
+// ── Live model discovery ────────────────────────────────────
+
+/// Providers whose list-models response we know how to parse.
+pub const LIVE_MODEL_PROVIDERS: &[&str] =
+    &["openai", "openrouter", "ollama", "mistral", "together"];
+
+pub fn supports_live_models(provider: &str) -> bool {
+    LIVE_MODEL_PROVIDERS.contains(&provider.to_lowercase().as_str())
+}
+
+/// A model id reported by the provider, before merging with our catalogue.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiscoveredModel {
+    pub id: String,
+    /// Name reported by the provider, if it has one.
+    pub name: Option<String>,
+    /// "completion" or "embedding".
+    pub model_type: &'static str,
+}
+
+/// Fetch the models currently available to `api_key` from the provider.
+pub async fn fetch_models(
+    provider: &str,
+    api_key: &str,
+    base_url: Option<&str>,
+) -> Result<Vec<DiscoveredModel>> {
+    if !supports_live_models(provider) {
+        anyhow::bail!("Live model discovery is not supported for provider '{provider}'");
+    }
+
+    let client = http_client()?;
+    let request = models_request(&client, provider, api_key, base_url)
+        .ok_or_else(|| anyhow::anyhow!("Provider '{provider}' has no models endpoint"))?;
+
+    let body: serde_json::Value = send_checked(request)
+        .await
+        .map_err(|e| anyhow::anyhow!(sanitize_provider_error(&format!("{e:#}"), api_key)))?
+        .json()
+        .await
+        .context("Provider returned an invalid models response")?;
+
+    Ok(parse_models_response(provider, &body))
+}
+
+/// Parse the list-models payload of a supported provider. Entries without an id are skipped.
+fn parse_models_response(provider: &str, body: &serde_json::Value) -> Vec<DiscoveredModel> {
+    let provider = provider.to_lowercase();
+    let (entries, id_field) = match provider.as_str() {
+        // {"models": [{"name": "llama3:8b", ...}]}
+        "ollama" => (body.get("models"), "name"),
+        // Together returns a bare array
+        "together" => (Some(body), "id"),
+        // OpenAI-style {"data": [{"id": ...}]}
+        _ => (body.get("data"), "id"),
+    };
+
+    let Some(entries) = entries.and_then(|e| e.as_array()) else {
+        return Vec::new();
+    };
+
+    entries
+        .iter()
+        .filter_map(|entry| {
+            let id = entry.get(id_field)?.as_str()?.to_string();
+            let name = entry
+                .get("display_name")
+                .or_else(|| entry.get("name").filter(|_| id_field != "name"))
+                .and_then(|n| n.as_str())
+                .map(str::to_string);
+            let declared_type = entry.get("type").and_then(|t| t.as_str());
+            let model_type = if declared_type == Some("embedding")
+                || id.to_lowercase().contains("embed")
+            {
+                "embedding"
+            } else {
+                "completion"
+            };
+            Some(DiscoveredModel {
+                id,
+                name,
+                model_type,
+            })
+        })
+        .collect()
+}
+
+/// Turn a raw provider error into a short message that never echoes the key.
+/// Prefers the `error.message` / `message` field of a JSON body when present.
+pub fn sanitize_provider_error(raw: &str, api_key: &str) -> String {
+    let mut message = extract_json_message(raw).unwrap_or_else(|| raw.trim().to_string());
+
+    if api_key.len() >= 4 {
+        message = message.replace(api_key, "[redacted]");
+    }
+
+    if message.chars().count() > MAX_ERROR_CHARS {
+        message = message.chars().take(MAX_ERROR_CHARS).collect::<String>() + "…";
+    }
+
+    message
+}
+
+/// Pull a human-readable message out of a JSON error body, keeping any
+/// `STATUS: ` prefix added by the caller.
+fn extract_json_message(raw: &str) -> Option<String> {
+    let start = raw.find('{')?;
+    let json: serde_json::Value = serde_json::from_str(&raw[start..]).ok()?;
+
+    let message = json
+        .pointer("/error/message")
+        .or_else(|| json.get("message"))
+        .or_else(|| json.get("error").filter(|e| e.is_string()))
+        .and_then(|m| m.as_str())?;
+
+    let prefix = raw[..start].trim();
+    Some(if prefix.is_empty() {
+        message.to_string()
+    } else {
+        format!("{prefix} {message}")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_extracts_openai_style_message() {
+        let raw = r#"401 Unauthorized: {"error":{"message":"Incorrect API key provided","type":"invalid_request_error"}}"#;
+        assert_eq!(
+            sanitize_provider_error(raw, "sk-test"),
+            "401 Unauthorized: Incorrect API key provided"
+        );
+    }
+
+    #[test]
+    fn test_sanitize_extracts_flat_message() {
+        let raw = r#"{"message":"invalid api token"}"#;
+        assert_eq!(sanitize_provider_error(raw, "abc"), "invalid api token");
+
+        let raw = r#"404 Not Found: {"error":"model not found"}"#;
+        assert_eq!(sanitize_provider_error(raw, "abc"), "404 Not Found: model not found");
+    }
+
+    #[test]
+    fn test_sanitize_redacts_key() {
+        let raw = "Incorrect API key provided: sk-secret-1234";
+        let sanitized = sanitize_provider_error(raw, "sk-secret-1234");
+        assert!(!sanitized.contains("sk-secret-1234"));
+        assert!(sanitized.contains("[redacted]"));
+    }
+
+    #[test]
+    fn test_sanitize_truncates_long_messages() {
+        let raw = "x".repeat(1000);
+        let sanitized = sanitize_provider_error(&raw, "");
+        assert_eq!(sanitized.chars().count(), MAX_ERROR_CHARS + 1);
+        assert!(sanitized.ends_with('…'));
+    }
+
+    #[test]
+    fn test_models_request_uses_base_url_override() {
+        let client = reqwest::Client::new();
+        let request = models_request(&client, "ollama", "", Some("http://gpu-box:11434/"))
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(request.url().as_str(), "http://gpu-box:11434/api/tags");
+        assert!(request.headers().get("authorization").is_none());
+    }
+
+    #[test]
+    fn test_models_request_auth_styles() {
+        let client = reqwest::Client::new();
+
+        let openai = models_request(&client, "openai", "sk-1", None).unwrap().build().unwrap();
+        assert_eq!(openai.url().as_str(), "https://api.openai.com/v1/models");
+        assert_eq!(openai.headers()["authorization"], "Bearer sk-1");
+
+        let anthropic = models_request(&client, "anthropic", "ak", None).unwrap().build().unwrap();
+        assert_eq!(anthropic.headers()["x-api-key"], "ak");
+        assert!(anthropic.headers().contains_key("anthropic-version"));
+
+        let gemini = models_request(&client, "google", "gk", None).unwrap().build().unwrap();
+        assert_eq!(gemini.url().query(), Some("key=gk"));
+    }
+
+    #[test]
+    fn test_completion_only_providers_have_no_models_request() {
+        let client = reqwest::Client::new();
+        assert!(models_request(&client, "perplexity", "k", None).is_none());
+        // OpenRouter lists models without checking the key
+        assert!(!models_endpoint("openrouter").unwrap().validates_key);
+    }
+
+    #[test]
+    fn test_parse_openai_style_models() {
+        let body = serde_json::json!({
+            "object": "list",
+            "data": [
+                {"id": "gpt-4o", "object": "model"},
+                {"id": "text-embedding-3-small", "object": "model"},
+                {"object": "model"}
+            ]
+        });
+        let models = parse_models_response("openai", &body);
+        assert_eq!(models.len(), 2);
+        assert_eq!(models[0].id, "gpt-4o");
+        assert_eq!(models[0].model_type, "completion");
+        assert_eq!(models[1].model_type, "embedding");
+    }
+
+    #[test]
+    fn test_parse_openrouter_names() {
+        let body = serde_json::json!({
+            "data": [{"id": "anthropic/claude-sonnet-4", "name": "Anthropic: Claude Sonnet 4"}]
+        });
+        let models = parse_models_response("openrouter", &body);
+        assert_eq!(models[0].name.as_deref(), Some("Anthropic: Claude Sonnet 4"));
+    }
+
+    #[test]
+    fn test_parse_ollama_tags() {
+        let body = serde_json::json!({
+            "models": [
+                {"name": "llama3.1:8b", "size": 1},
+                {"name": "nomic-embed-text:latest", "size": 2}
+            ]
+        });
+        let models = parse_models_response("ollama", &body);
+        assert_eq!(models[0].id, "llama3.1:8b");
+        assert_eq!(models[0].name, None);
+        assert_eq!(models[1].model_type, "embedding");
+    }
+
+    #[test]
+    fn test_parse_together_array() {
+        let body = serde_json::json!([
+            {"id": "meta-llama/Llama-3.3-70B-Instruct-Turbo", "type": "chat", "display_name": "Llama 3.3 70B"},
+            {"id": "BAAI/bge-large-en-v1.5", "type": "embedding"}
+        ]);
+        let models = parse_models_response("together", &body);
+        assert_eq!(models[0].name.as_deref(), Some("Llama 3.3 70B"));
+        assert_eq!(models[1].model_type, "embedding");
+        assert!(parse_models_response("together", &serde_json::json!({})).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_models_rejects_unsupported_provider() {
+        assert!(fetch_models("anthropic", "k", None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_redirects_are_not_followed() {
+        use axum::{response::Redirect, routing::get, Router};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        async fn serve(router: Router) -> String {
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let address = listener.local_addr().unwrap();
+            tokio::spawn(async move { axum::serve(listener, router).await });
+            format!("http://{address}")
+        }
+
+        // Stands in for an internal host the redirect points at
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+        let internal = serve(Router::new().route(
+            "/secret",
+            get(move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+                async { axum::Json(serde_json::json!({ "data": [{ "id": "leaked" }] })) }
+            }),
+        ))
+        .await;
+        let target = format!("{internal}/secret");
+        let redirecting = serve(Router::new().fallback(move || {
+            let target = target.clone();
+            async move { Redirect::temporary(&target) }
+        }))
+        .await;
+
+        assert!(fetch_models("openai", "sk", Some(&redirecting)).await.is_err());
+        assert!(test_connection("openai", "sk", Some(&redirecting)).await.is_err());
+        assert_eq!(hits.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn test_connection_error_is_sanitized() {
+        // Port 9 (discard) on localhost refuses connections quickly
+        let err = test_connection("openai", "sk-should-not-leak", Some("http://127.0.0.1:9"))
+            .await
+            .unwrap_err();
+        assert!(!err.to_string().contains("sk-should-not-leak"));
+    }
+
+    #[tokio::test]
+    async fn test_error_responses_report_only_the_status() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = [0u8; 1024];
+            let _ = socket.read(&mut request).await;
+            let body = r#"{"error":{"message":"internal-only data"}}"#;
+            let response = format!(
+                "HTTP/1.1 401 Unauthorized\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{body}",
+                body.len()
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+        });
+
+        let err = test_connection("openai", "sk-test", Some(&format!("http://{address}")))
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Provider answered 401 Unauthorized");
+    }
+}
@@ -0,0 +1,269 @@
+use crate::errors::AppError;
+use crate::services::chat_service::ChatChannel;
+use crate::services::llm_provider::supported_providers;
+use crate::services::provider_guard::ProviderError;
+use crate::services::retry::{self, contains_status, FailureKind};
+
+/// What went wrong with a model provider call, as far as the person chatting
+/// needs to know. The raw provider error may carry URLs, request bodies or key
+/// prefixes, so it is only ever logged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProviderFailure {
+    /// The API key was missing, wrong or revoked.
+    InvalidKey,
+    /// The provider doesn't know the configured model.
+    UnknownModel,
+    /// The provider is rate limiting us or the account is out of quota.
+    RateLimited,
+    /// The prompt doesn't fit the model's context window.
+    ContextLength,
+    /// The call took longer than the provider timeout.
+    TimedOut,
+    /// The provider kept failing and is skipped for a while.
+    CircuitOpen,
+    Other,
+}
+
+impl ProviderFailure {
+    /// Classify a failed provider call. rig folds the HTTP status and body into
+    /// the error text, so this matches on both.
+    pub fn classify(error: &anyhow::Error) -> Self {
+        match error.downcast_ref::<ProviderError>() {
+            Some(ProviderError::TimedOut { .. }) => return ProviderFailure::TimedOut,
+            Some(ProviderError::CircuitOpen { .. }) => return ProviderFailure::CircuitOpen,
+            None => {}
+        }
+        let lower = format!("{error:#}").to_lowercase();
+
+        const CONTEXT_LENGTH: &[&str] = &[
+            "context length",
+            "context_length",
+            "context window",
+            "maximum context",
+            "prompt is too long",
+            "too many tokens",
+            "reduce the length",
+        ];
+        const INVALID_KEY: &[&str] = &[
+            "invalid api key",
+            "incorrect api key",
+            "invalid_api_key",
+            "invalid x-api-key",
+            "authentication_error",
+            "unauthorized",
+            "api key not valid",
+        ];
+        const UNKNOWN_MODEL: &[&str] = &[
+            "model_not_found",
+            "model not found",
+            "does not exist",
+            "unknown model",
+            "no such model",
+            "not_found_error",
+        ];
+        const RATE_LIMITED: &[&str] = &[
+            "rate limit",
+            "rate_limit",
+            "too many requests",
+            "insufficient_quota",
+            "exceeded your current quota",
+        ];
+
+        if CONTEXT_LENGTH.iter().any(|p| lower.contains(p)) {
+            ProviderFailure::ContextLength
+        } else if contains_status(&lower, "401") || INVALID_KEY.iter().any(|p| lower.contains(p)) {
+            ProviderFailure::InvalidKey
+        } else if contains_status(&lower, "429") || RATE_LIMITED.iter().any(|p| lower.contains(p)) {
+            ProviderFailure::RateLimited
+        } else if contains_status(&lower, "404") || UNKNOWN_MODEL.iter().any(|p| lower.contains(p)) {
+            ProviderFailure::UnknownModel
+        } else {
+            ProviderFailure::Other
+        }
+    }
+
+    /// Whether another provider may answer where this one failed: on timeouts,
+    /// rate limits, server errors and while its circuit is open. A rejected
+    /// key, unknown model or too long a prompt is left for the person chatting
+    /// to fix.
+    pub fn warrants_fallback(error: &anyhow::Error) -> bool {
+        match Self::classify(error) {
+            ProviderFailure::TimedOut | ProviderFailure::RateLimited | ProviderFailure::CircuitOpen => true,
+            ProviderFailure::InvalidKey | ProviderFailure::UnknownModel | ProviderFailure::ContextLength => false,
+            ProviderFailure::Other => {
+                matches!(retry::classify(&format!("{error:#}")), FailureKind::Transient { .. })
+            }
+        }
+    }
+
+    /// What to tell whoever is chatting. Widget visitors can't change the
+    /// provider settings, so they never get told to.
+    pub fn message(self, channel: ChatChannel, provider: &str, model: &str) -> String {
+        let provider = provider_name(provider);
+        match (self, channel) {
+            (ProviderFailure::InvalidKey, ChatChannel::App) => {
+                format!("Your {provider} API key was rejected — update it in Settings.")
+            }
+            (ProviderFailure::UnknownModel, ChatChannel::App) => {
+                format!("{provider} doesn't recognize the model '{model}' — choose another model in Settings.")
+            }
+            (ProviderFailure::RateLimited, ChatChannel::App) => {
+                format!("{provider} is rate limiting requests. Please wait a moment and try again.")
+            }
+            (ProviderFailure::RateLimited, ChatChannel::Widget) => {
+                "The assistant is busy right now. Please try again in a moment.".to_string()
+            }
+            (ProviderFailure::ContextLength, ChatChannel::App) => {
+                "This conversation is too long for the model. Start a new conversation or shorten your message."
+                    .to_string()
+            }
+            (ProviderFailure::ContextLength, ChatChannel::Widget) => {
+                "Your message is too long for the assistant. Please shorten it or start a new conversation."
+                    .to_string()
+            }
+            (ProviderFailure::TimedOut, ChatChannel::App) => {
+                "The model took too long to respond. Please try again.".to_string()
+            }
+            (ProviderFailure::TimedOut, ChatChannel::Widget) => {
+                "The assistant took too long to respond. Please try again.".to_string()
+            }
+            (ProviderFailure::CircuitOpen, ChatChannel::App) => {
+                "The model provider is temporarily unavailable. Please try again in a few minutes.".to_string()
+            }
+            _ => channel.unavailable_message().to_string(),
+        }
+    }
+
+    /// The error to answer a request with when the provider call fails before
+    /// any reply is streamed. Carries only [`ProviderFailure::message`].
+    pub fn into_app_error(self, channel: ChatChannel, provider: &str, model: &str) -> AppError {
+        let message = self.message(channel, provider, model);
+        match self {
+            ProviderFailure::InvalidKey | ProviderFailure::UnknownModel | ProviderFailure::ContextLength => {
+                AppError::Validation(message)
+            }
+            _ => AppError::Unavailable(message),
+        }
+    }
+}
+
+/// Display name of a provider, e.g. `OpenAI` for `openai`.
+pub(crate) fn provider_name(provider: &str) -> String {
+    supported_providers()
+        .iter()
+        .find(|p| p.id.eq_ignore_ascii_case(provider))
+        .map_or_else(|| provider.to_string(), |p| p.name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn classify(message: &str) -> ProviderFailure {
+        ProviderFailure::classify(&anyhow::anyhow!("{message}"))
+    }
+
+    #[test]
+    fn test_classify_provider_errors() {
+        let cases = [
+            (
+                r#"CompletionError: ProviderError: {"error":{"message":"Incorrect API key provided: sk-abc1****wxyz. You can find your API key at https://platform.openai.com/account/api-keys.","type":"invalid_request_error","code":"invalid_api_key"}}"#,
+                ProviderFailure::InvalidKey,
+            ),
+            (
+                r#"HttpError: 401 Unauthorized {"type":"error","error":{"type":"authentication_error","message":"invalid x-api-key"}}"#,
+                ProviderFailure::InvalidKey,
+            ),
+            (
+                r#"ProviderError: {"error":{"message":"The model `gpt-5-turbo` does not exist or you do not have access to it.","code":"model_not_found"}}"#,
+                ProviderFailure::UnknownModel,
+            ),
+            (r#"status 404: {"error":"model 'llama9' not found, try pulling it first"}"#, ProviderFailure::UnknownModel),
+            (
+                "429 Too Many Requests: Rate limit reached for gpt-4o in organization org-123 on tokens per min",
+                ProviderFailure::RateLimited,
+            ),
+            (
+                r#"{"error":{"message":"You exceeded your current quota, please check your plan and billing details.","code":"insufficient_quota"}}"#,
+                ProviderFailure::RateLimited,
+            ),
+            (
+                r#"400 Bad Request: {"error":{"message":"This model's maximum context length is 8192 tokens. However, your messages resulted in 9001 tokens.","code":"context_length_exceeded"}}"#,
+                ProviderFailure::ContextLength,
+            ),
+            (
+                r#"{"type":"error","error":{"type":"invalid_request_error","message":"prompt is too long: 210000 tokens > 200000 maximum"}}"#,
+                ProviderFailure::ContextLength,
+            ),
+            ("500 Internal Server Error", ProviderFailure::Other),
+            ("Limit 14010 tokens used", ProviderFailure::Other),
+        ];
+        for (message, expected) in cases {
+            assert_eq!(classify(message), expected, "{message}");
+        }
+    }
+
+    #[test]
+    fn test_only_outages_warrant_a_fallback() {
+        let falls_back = |message: &str| ProviderFailure::warrants_fallback(&anyhow::anyhow!("{message}"));
+        assert!(falls_back("503 Service Unavailable"));
+        assert!(falls_back("HttpError: 502 Bad Gateway"));
+        assert!(falls_back("429 Too Many Requests"));
+        assert!(falls_back(r#"{"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#));
+        assert!(ProviderFailure::warrants_fallback(&anyhow::Error::new(ProviderError::TimedOut {
+            provider: "openai".to_string(),
+            secs: 120,
+        })));
+
+        assert!(!falls_back(r#"401 {"error":{"code":"invalid_api_key"}}"#));
+        assert!(!falls_back(r#"{"error":{"code":"model_not_found"}}"#));
+        assert!(!falls_back("This model's maximum context length is 8192 tokens"));
+        assert!(!falls_back("400 Bad Request: invalid temperature"));
+    }
+
+    #[test]
+    fn test_guard_errors_distinguish_timeouts() {
+        let timed_out = anyhow::Error::new(ProviderError::TimedOut {
+            provider: "ollama".to_string(),
+            secs: 120,
+        });
+        assert_eq!(ProviderFailure::classify(&timed_out), ProviderFailure::TimedOut);
+        assert_eq!(
+            ProviderFailure::TimedOut.message(ChatChannel::App, "ollama", "llama3"),
+            "The model took too long to respond. Please try again."
+        );
+
+        let open = anyhow::Error::new(ProviderError::CircuitOpen {
+            provider: "openai".to_string(),
+        });
+        assert_eq!(ProviderFailure::classify(&open), ProviderFailure::CircuitOpen);
+        assert!(ProviderFailure::CircuitOpen
+            .message(ChatChannel::App, "openai", "gpt-4o")
+            .contains("temporarily unavailable"));
+        assert_eq!(
+            ProviderFailure::CircuitOpen.message(ChatChannel::Widget, "openai", "gpt-4o"),
+            ChatChannel::Widget.unavailable_message()
+        );
+    }
+
+    #[test]
+    fn test_messages_never_echo_the_provider_error() {
+        let raw = r#"401 {"error":{"message":"Incorrect API key provided: sk-abc1****wxyz","code":"invalid_api_key"}}"#;
+        let failure = classify(raw);
+        let app = failure.message(ChatChannel::App, "openai", "gpt-4o");
+        assert_eq!(app, "Your OpenAI API key was rejected — update it in Settings.");
+        let widget = failure.message(ChatChannel::Widget, "openai", "gpt-4o");
+        assert!(!widget.contains("Settings") && !widget.contains("sk-"));
+
+        assert!(ProviderFailure::UnknownModel
+            .message(ChatChannel::App, "anthropic", "claude-9")
+            .starts_with("Anthropic doesn't recognize the model 'claude-9'"));
+        assert!(ProviderFailure::RateLimited
+            .message(ChatChannel::App, "custom-llm", "m")
+            .starts_with("custom-llm is rate limiting"));
+        assert_eq!(
+            ProviderFailure::Other.message(ChatChannel::App, "openai", "gpt-4o"),
+            "The model failed to respond. Please try again."
+        );
+    }
+}
@@ -0,0 +1,394 @@
+use anyhow::Result;
+use futures::future::BoxFuture;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::services::llm_provider::{
+    ChatModel, ChatReply, ChatRequest, CompletionBackend, Embedder, EmbeddingBackend, ModelRef,
+};
+use crate::services::retry::{self, FailureKind};
+
+/// A provider call stopped by the guard rather than answered by the provider.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ProviderError {
+    #[error("Request to provider '{provider}' timed out after {secs}s")]
+    TimedOut { provider: String, secs: u64 },
+
+    #[error("Provider '{provider}' is temporarily unavailable")]
+    CircuitOpen { provider: String },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Calls go through.
+    Closed,
+    /// Calls fail fast until the cooldown passes.
+    Open,
+    /// One call is probing whether the provider recovered.
+    HalfOpen,
+}
+
+#[derive(Debug)]
+struct Circuit {
+    state: CircuitState,
+    consecutive_failures: u32,
+    /// When the circuit opened or its latest probe started.
+    since: Instant,
+    times_opened: u64,
+}
+
+/// Per-provider circuit breakers: after `threshold` consecutive failures a
+/// provider's calls fail fast for `cooldown`, then a single call probes it and
+/// either closes the circuit or opens it for another cooldown.
+#[derive(Clone)]
+pub struct CircuitBreakers {
+    circuits: Arc<Mutex<HashMap<String, Circuit>>>,
+    threshold: u32,
+    cooldown: Duration,
+}
+
+impl CircuitBreakers {
+    /// A `threshold` of 0 never opens a circuit.
+    pub fn new(threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            circuits: Arc::new(Mutex::new(HashMap::new())),
+            threshold,
+            cooldown,
+        }
+    }
+
+    pub fn state(&self, provider: &str) -> CircuitState {
+        let circuits = self.circuits.lock().unwrap();
+        circuits.get(provider).map_or(CircuitState::Closed, |c| c.state)
+    }
+
+    /// Whether a call to `provider` may go ahead. Once the cooldown has passed an
+    /// open circuit lets one call through as a probe.
+    pub fn allow(&self, provider: &str) -> Result<(), ProviderError> {
+        let mut circuits = self.circuits.lock().unwrap();
+        let Some(circuit) = circuits.get_mut(provider) else {
+            return Ok(());
+        };
+        match circuit.state {
+            CircuitState::Closed => Ok(()),
+            _ if circuit.since.elapsed() >= self.cooldown => {
+                if circuit.state == CircuitState::Open {
+                    tracing::info!("Circuit for provider '{provider}' half-open, probing it");
+                }
+                circuit.state = CircuitState::HalfOpen;
+                circuit.since = Instant::now();
+                Ok(())
+            }
+            _ => Err(ProviderError::CircuitOpen {
+                provider: provider.to_string(),
+            }),
+        }
+    }
+
+    pub fn record_success(&self, provider: &str) {
+        let mut circuits = self.circuits.lock().unwrap();
+        let Some(circuit) = circuits.get_mut(provider) else {
+            return;
+        };
+        if circuit.state != CircuitState::Closed {
+            tracing::info!("Circuit for provider '{provider}' closed, it is responding again");
+        }
+        circuit.state = CircuitState::Closed;
+        circuit.consecutive_failures = 0;
+    }
+
+    /// Run a call to `provider` that isn't made through a guarded backend, such
+    /// as a rerank request, with the same deadline and circuit breaker.
+    pub async fn guard<T>(&self, provider: &str, timeout: Duration, call: impl Future<Output = Result<T>>) -> Result<T> {
+        let guard = Guard { breakers: self.clone(), key: provider.to_lowercase(), timeout };
+        guard.run(call).await
+    }
+
+    pub fn record_failure(&self, provider: &str) {
+        if self.threshold == 0 {
+            return;
+        }
+        let mut circuits = self.circuits.lock().unwrap();
+        let circuit = circuits.entry(provider.to_string()).or_insert_with(|| Circuit {
+            state: CircuitState::Closed,
+            consecutive_failures: 0,
+            since: Instant::now(),
+            times_opened: 0,
+        });
+        circuit.consecutive_failures += 1;
+
+        let reopen = match circuit.state {
+            CircuitState::HalfOpen => true,
+            CircuitState::Closed => circuit.consecutive_failures >= self.threshold,
+            // A call started before the circuit opened
+            CircuitState::Open => false,
+        };
+        if reopen {
+            circuit.state = CircuitState::Open;
+            circuit.since = Instant::now();
+            circuit.times_opened += 1;
+            tracing::warn!(
+                "Circuit for provider '{provider}' opened after {} consecutive failures \
+                 (opened {} times since startup); failing its calls for {}s",
+                circuit.consecutive_failures,
+                circuit.times_opened,
+                self.cooldown.as_secs()
+            );
+        }
+    }
+}
+
+/// Circuit a model's calls count against: the provider, or the provider at a
+/// custom endpoint so one hung Ollama host doesn't block another.
+fn circuit_key(model: &ModelRef<'_>) -> String {
+    match model.base_url.filter(|u| !u.is_empty()) {
+        Some(base_url) => format!("{} ({base_url})", model.provider.to_lowercase()),
+        None => model.provider.to_lowercase(),
+    }
+}
+
+#[derive(Clone)]
+struct Guard {
+    breakers: CircuitBreakers,
+    key: String,
+    timeout: Duration,
+}
+
+impl Guard {
+    /// Run `call` with the deadline and record how it went. Errors the provider
+    /// answered with, such as a bad key, show it is up and count as success.
+    async fn run<T>(&self, call: impl Future<Output = Result<T>>) -> Result<T> {
+        self.breakers.allow(&self.key)?;
+
+        let outcome = match tokio::time::timeout(self.timeout, call).await {
+            Ok(outcome) => outcome,
+            Err(_) => Err(ProviderError::TimedOut {
+                provider: self.key.clone(),
+                secs: self.timeout.as_secs(),
+            }
+            .into()),
+        };
+
+        match &outcome {
+            Err(e) if matches!(retry::classify(&format!("{e:#}")), FailureKind::Transient { .. }) => {
+                self.breakers.record_failure(&self.key)
+            }
+            _ => self.breakers.record_success(&self.key),
+        }
+        outcome
+    }
+}
+
+/// Wraps a backend so every call has a deadline and goes through its
+/// provider's circuit breaker.
+pub struct GuardedBackend<B: ?Sized> {
+    inner: Arc<B>,
+    breakers: CircuitBreakers,
+    timeout: Duration,
+}
+
+impl<B: ?Sized> GuardedBackend<B> {
+    pub fn new(inner: Arc<B>, breakers: CircuitBreakers, timeout: Duration) -> Self {
+        Self {
+            inner,
+            breakers,
+            timeout,
+        }
+    }
+
+    fn guard(&self, model: &ModelRef<'_>) -> Guard {
+        Guard {
+            breakers: self.breakers.clone(),
+            key: circuit_key(model),
+            timeout: self.timeout,
+        }
+    }
+}
+
+struct GuardedChatModel {
+    inner: Box<dyn ChatModel>,
+    guard: Guard,
+}
+
+struct GuardedEmbedder {
+    inner: Box<dyn Embedder>,
+    guard: Guard,
+}
+
+impl CompletionBackend for GuardedBackend<dyn CompletionBackend> {
+    fn chat_model(&self, model: ModelRef<'_>) -> Result<Box<dyn ChatModel>> {
+        Ok(Box::new(GuardedChatModel {
+            inner: self.inner.chat_model(model)?,
+            guard: self.guard(&model),
+        }))
+    }
+}
+
+impl ChatModel for GuardedChatModel {
+    fn chat(&self, request: ChatRequest) -> BoxFuture<'_, Result<ChatReply>> {
+        Box::pin(self.guard.run(self.inner.chat(request)))
+    }
+}
+
+impl EmbeddingBackend for GuardedBackend<dyn EmbeddingBackend> {
+    fn embedder(&self, model: ModelRef<'_>) -> Result<Box<dyn Embedder>> {
+        Ok(Box::new(GuardedEmbedder {
+            inner: self.inner.embedder(model)?,
+            guard: self.guard(&model),
+        }))
+    }
+}
+
+impl Embedder for GuardedEmbedder {
+    fn embed_texts(&self, texts: Vec<String>) -> BoxFuture<'_, Result<Vec<Vec<f64>>>> {
+        Box::pin(self.guard.run(self.inner.embed_texts(texts)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Chat models that answer with `reply`, or never when it is `None`.
+    struct ScriptedBackend {
+        reply: Option<Result<String, String>>,
+    }
+
+    impl CompletionBackend for ScriptedBackend {
+        fn chat_model(&self, _model: ModelRef<'_>) -> Result<Box<dyn ChatModel>> {
+            Ok(Box::new(ScriptedBackend {
+                reply: self.reply.clone(),
+            }))
+        }
+    }
+
+    impl ChatModel for ScriptedBackend {
+        fn chat(&self, _request: ChatRequest) -> BoxFuture<'_, Result<ChatReply>> {
+            Box::pin(async move {
+                match &self.reply {
+                    Some(Ok(text)) => Ok(ChatReply {
+                        text: text.clone(),
+                        usage: None,
+                    }),
+                    Some(Err(e)) => Err(anyhow::anyhow!(e.clone())),
+                    None => std::future::pending().await,
+                }
+            })
+        }
+    }
+
+    fn backend(reply: Option<Result<&str, &str>>, breakers: &CircuitBreakers) -> GuardedBackend<dyn CompletionBackend> {
+        let reply = reply.map(|r| r.map(str::to_string).map_err(str::to_string));
+        GuardedBackend::new(
+            Arc::new(ScriptedBackend { reply }) as Arc<dyn CompletionBackend>,
+            breakers.clone(),
+            Duration::from_secs(120),
+        )
+    }
+
+    async fn chat(backend: &GuardedBackend<dyn CompletionBackend>, provider: &str) -> Result<String> {
+        let model = backend.chat_model(ModelRef {
+            provider,
+            model: "gpt-4o",
+            api_key: "sk-test",
+            base_url: None,
+        })?;
+        model
+            .chat(ChatRequest {
+                preamble: String::new(),
+                history: Vec::new(),
+                prompt: "Hi".to_string(),
+            })
+            .await
+            .map(|reply| reply.text)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_hung_call_times_out() {
+        let breakers = CircuitBreakers::new(5, Duration::from_secs(30));
+        let error = chat(&backend(None, &breakers), "ollama").await.unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<ProviderError>(),
+            Some(&ProviderError::TimedOut {
+                provider: "ollama".to_string(),
+                secs: 120
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_circuit_opens_after_consecutive_failures() {
+        let breakers = CircuitBreakers::new(2, Duration::from_secs(30));
+        let failing = backend(Some(Err("HTTP 503 Service Unavailable")), &breakers);
+        let working = backend(Some(Ok("Hello")), &breakers);
+
+        assert!(chat(&failing, "openai").await.is_err());
+        assert_eq!(breakers.state("openai"), CircuitState::Closed);
+        assert!(chat(&failing, "openai").await.is_err());
+        assert_eq!(breakers.state("openai"), CircuitState::Open);
+
+        // Fails fast without calling the provider, and other providers are unaffected
+        let error = chat(&working, "openai").await.unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<ProviderError>(),
+            Some(ProviderError::CircuitOpen { .. })
+        ));
+        assert_eq!(chat(&working, "anthropic").await.unwrap(), "Hello");
+    }
+
+    #[tokio::test]
+    async fn test_probe_after_cooldown_closes_or_reopens() {
+        let breakers = CircuitBreakers::new(1, Duration::ZERO);
+        let failing = backend(Some(Err("error sending request: connection refused")), &breakers);
+        let working = backend(Some(Ok("Hello")), &breakers);
+
+        assert!(chat(&failing, "openai").await.is_err());
+        assert_eq!(breakers.state("openai"), CircuitState::Open);
+
+        // A failed probe reopens the circuit
+        assert!(chat(&failing, "openai").await.is_err());
+        assert_eq!(breakers.state("openai"), CircuitState::Open);
+
+        assert_eq!(chat(&working, "openai").await.unwrap(), "Hello");
+        assert_eq!(breakers.state("openai"), CircuitState::Closed);
+    }
+
+    #[test]
+    fn test_half_open_circuit_admits_one_probe_per_cooldown() {
+        let breakers = CircuitBreakers::new(1, Duration::from_secs(30));
+        breakers.record_failure("openai");
+        breakers.circuits.lock().unwrap().get_mut("openai").unwrap().since -= Duration::from_secs(30);
+
+        assert!(breakers.allow("openai").is_ok());
+        assert_eq!(breakers.state("openai"), CircuitState::HalfOpen);
+        assert!(breakers.allow("openai").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_provider_rejections_do_not_open_the_circuit() {
+        let breakers = CircuitBreakers::new(1, Duration::from_secs(30));
+        let rejecting = backend(Some(Err("HTTP 401: invalid api key")), &breakers);
+
+        assert!(chat(&rejecting, "openai").await.is_err());
+        assert!(chat(&rejecting, "openai").await.is_err());
+        assert_eq!(breakers.state("openai"), CircuitState::Closed);
+    }
+
+    #[test]
+    fn test_custom_endpoints_have_their_own_circuit() {
+        let model = |base_url| ModelRef {
+            provider: "Ollama",
+            model: "llama3",
+            api_key: "",
+            base_url,
+        };
+        assert_eq!(circuit_key(&model(None)), "ollama");
+        assert_eq!(circuit_key(&model(Some(""))), "ollama");
+        assert_eq!(
+            circuit_key(&model(Some("http://gpu-1:11434"))),
+            "ollama (http://gpu-1:11434)"
+        );
+    }
+}
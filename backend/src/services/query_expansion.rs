@@ -0,0 +1,177 @@
+use anyhow::Result;
+
+use crate::db::models::conversation::{ConversationSummary, Message};
+use crate::services::llm_provider::{ChatRequest, CompletionBackend, ModelRef};
+
+/// How much of each earlier message or summary goes into a query.
+const MAX_EXCERPT_CHARS: usize = 500;
+
+/// Latest messages of the conversation the rewrite prompt includes.
+const REWRITE_HISTORY_MESSAGES: usize = 6;
+
+/// Longest rewritten query kept from the model.
+const MAX_QUERY_CHARS: usize = 1000;
+
+const REWRITE_PREAMBLE: &str = "You rewrite the latest message of a chat conversation into a \
+    standalone search query for a knowledge base. Resolve references like \"it\", \"that one\" \
+    or \"the second option\" using the conversation, keep names, numbers and product terms, \
+    and write in the language of the latest message. Reply with the query only.";
+
+fn excerpt(text: &str) -> String {
+    text.trim().chars().take(MAX_EXCERPT_CHARS).collect()
+}
+
+/// The text to search the knowledge base with for `message`: the message,
+/// preceded by the last `previous_messages` user messages of `history` and,
+/// with `include_summary`, the start of the conversation's summary.
+pub fn expanded_query(
+    message: &str,
+    history: &[Message],
+    summary: &ConversationSummary,
+    previous_messages: usize,
+    include_summary: bool,
+) -> String {
+    let mut parts = Vec::new();
+    if include_summary && !summary.text.trim().is_empty() {
+        parts.push(excerpt(&summary.text));
+    }
+    let mut previous: Vec<String> = history
+        .iter()
+        .rev()
+        .filter(|m| m.role == "user")
+        .take(previous_messages)
+        .map(|m| excerpt(&m.content))
+        .filter(|m| !m.is_empty())
+        .collect();
+    previous.reverse();
+    parts.extend(previous);
+    parts.push(message.to_string());
+    parts.join("\n")
+}
+
+/// Ask `model` to turn `message` into a question that makes sense without
+/// the conversation before it, given the summary and latest messages.
+pub async fn rewrite_query(
+    backend: &dyn CompletionBackend,
+    model: ModelRef<'_>,
+    message: &str,
+    history: &[Message],
+    summary: &ConversationSummary,
+) -> Result<String> {
+    let model = backend.chat_model(model)?;
+
+    let mut prompt = String::new();
+    if !summary.text.is_empty() {
+        prompt.push_str(&format!("Conversation summary: {}\n\n", excerpt(&summary.text)));
+    }
+    for m in &history[history.len().saturating_sub(REWRITE_HISTORY_MESSAGES)..] {
+        let speaker = match m.role.as_str() {
+            "user" => "User",
+            "assistant" => "Assistant",
+            _ => continue,
+        };
+        prompt.push_str(&format!("{speaker}: {}\n\n", excerpt(&m.content)));
+    }
+    prompt.push_str(&format!("Latest message: {message}"));
+
+    let raw = model
+        .chat(ChatRequest {
+            preamble: REWRITE_PREAMBLE.to_string(),
+            history: Vec::new(),
+            prompt,
+        })
+        .await?;
+    let query: String = raw.text.trim().chars().take(MAX_QUERY_CHARS).collect();
+    if query.is_empty() {
+        anyhow::bail!("Model returned an empty query");
+    }
+    Ok(query)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::models::conversation::MessageGeneration;
+
+    fn message(role: &str, content: &str) -> Message {
+        Message {
+            id: String::new(),
+            conversation_id: "c1".to_string(),
+            role: role.to_string(),
+            content: content.to_string(),
+            created_at: Default::default(),
+            generation: MessageGeneration::default(),
+            deleted_at: None,
+        }
+    }
+
+    fn summary(text: &str) -> ConversationSummary {
+        ConversationSummary {
+            text: text.to_string(),
+            message_count: 2,
+        }
+    }
+
+    fn history() -> Vec<Message> {
+        vec![
+            message("user", "Which plans do you offer?"),
+            message("assistant", "Basic and Pro."),
+            message("user", "How much is Pro?"),
+            message("assistant", "20 EUR a month."),
+        ]
+    }
+
+    #[test]
+    fn test_expanded_query_prepends_previous_user_messages() {
+        let query = "what about the second option?";
+        assert_eq!(
+            expanded_query(query, &history(), &summary(""), 1, false),
+            "How much is Pro?\nwhat about the second option?"
+        );
+        assert_eq!(
+            expanded_query(query, &history(), &summary(""), 5, false),
+            "Which plans do you offer?\nHow much is Pro?\nwhat about the second option?"
+        );
+        assert_eq!(expanded_query(query, &history(), &summary(""), 0, false), query);
+        assert_eq!(expanded_query(query, &[], &summary(""), 3, true), query);
+    }
+
+    #[test]
+    fn test_expanded_query_includes_summary_when_enabled() {
+        let summary = summary("The user compares subscription plans.");
+        assert_eq!(
+            expanded_query("and yearly?", &history(), &summary, 1, true),
+            "The user compares subscription plans.\nHow much is Pro?\nand yearly?"
+        );
+        assert_eq!(
+            expanded_query("and yearly?", &history(), &summary, 1, false),
+            "How much is Pro?\nand yearly?"
+        );
+    }
+
+    #[test]
+    fn test_expanded_query_truncates_long_messages() {
+        let long = "x".repeat(MAX_EXCERPT_CHARS * 2);
+        let query = expanded_query("next?", &[message("user", &long)], &summary(""), 1, false);
+        assert_eq!(query.chars().count(), MAX_EXCERPT_CHARS + "\nnext?".len());
+    }
+
+    #[tokio::test]
+    async fn test_rewrite_query_sends_the_conversation() {
+        // The fake backend echoes the prompt back
+        let backend = crate::services::llm_provider::FakeBackend::new(8);
+        let model = ModelRef {
+            provider: "openai",
+            model: "gpt-4o-mini",
+            api_key: "",
+            base_url: None,
+        };
+        let query = rewrite_query(&backend, model, "and yearly?", &history()[2..], &summary("Plans."))
+            .await
+            .unwrap();
+        assert_eq!(
+            query,
+            "Conversation summary: Plans.\n\nUser: How much is Pro?\n\nAssistant: 20 EUR a month.\n\nLatest message: and yearly?"
+        );
+    }
+}
@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Tracked keys past which expired windows are swept out on the next request.
+const SWEEP_THRESHOLD: usize = 10_000;
+
+/// In-memory fixed-window limiter for unauthenticated endpoints, keyed by
+/// something like the client IP. Counts are per process, so with several
+/// instances each one allows the full limit.
+#[derive(Clone)]
+pub struct RateLimiter<K> {
+    windows: Arc<Mutex<HashMap<K, (Instant, u32)>>>,
+    limit: u32,
+    window: Duration,
+}
+
+impl<K: Eq + Hash> RateLimiter<K> {
+    /// Allow `limit` requests per key in every `window`; 0 allows none.
+    pub fn new(limit: u32, window: Duration) -> Self {
+        Self {
+            windows: Arc::default(),
+            limit,
+            window,
+        }
+    }
+
+    /// Count a request from `key`; false once its window's limit is reached.
+    pub fn allow(&self, key: K) -> bool {
+        self.allow_at(key, Instant::now())
+    }
+
+    fn allow_at(&self, key: K, now: Instant) -> bool {
+        let mut windows = self.windows.lock().unwrap_or_else(|e| e.into_inner());
+        if windows.len() >= SWEEP_THRESHOLD {
+            windows.retain(|_, (start, _)| now.duration_since(*start) < self.window);
+        }
+
+        let (start, count) = windows.entry(key).or_insert((now, 0));
+        if now.duration_since(*start) >= self.window {
+            *start = now;
+            *count = 0;
+        }
+        if *count >= self.limit {
+            return false;
+        }
+        *count += 1;
+        true
+    }
+}
+
+/// In-memory token bucket limiter for authenticated requests, keyed by user.
+/// Each key may burst up to the per-minute limit, then gets tokens back at an
+/// even rate. Like [`RateLimiter`], counts are per process.
+#[derive(Clone)]
+pub struct TokenBucketLimiter<K> {
+    buckets: Arc<Mutex<HashMap<K, (Instant, f64)>>>,
+    capacity: f64,
+    /// Tokens regained per second.
+    refill_rate: f64,
+}
+
+impl<K: Eq + Hash> TokenBucketLimiter<K> {
+    /// Allow `per_minute` requests per key; 0 turns the limit off.
+    pub fn per_minute(per_minute: u32) -> Self {
+        Self {
+            buckets: Arc::default(),
+            capacity: per_minute as f64,
+            refill_rate: per_minute as f64 / 60.0,
+        }
+    }
+
+    /// Take a token for a request from `key`, or say how long until one is back.
+    pub fn acquire(&self, key: K) -> Result<(), Duration> {
+        self.acquire_at(key, Instant::now())
+    }
+
+    fn acquire_at(&self, key: K, now: Instant) -> Result<(), Duration> {
+        if self.capacity == 0.0 {
+            return Ok(());
+        }
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        if buckets.len() >= SWEEP_THRESHOLD {
+            // A bucket that has refilled is the same as no bucket
+            buckets.retain(|_, (updated, tokens)| self.refilled(*tokens, now.duration_since(*updated)) < self.capacity);
+        }
+
+        let (updated, tokens) = buckets.entry(key).or_insert((now, self.capacity));
+        *tokens = self.refilled(*tokens, now.saturating_duration_since(*updated));
+        *updated = now;
+        if *tokens < 1.0 {
+            return Err(Duration::from_secs_f64((1.0 - *tokens) / self.refill_rate));
+        }
+        *tokens -= 1.0;
+        Ok(())
+    }
+
+    fn refilled(&self, tokens: f64, elapsed: Duration) -> f64 {
+        (tokens + elapsed.as_secs_f64() * self.refill_rate).min(self.capacity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limit_resets_with_the_window() {
+        let limiter = RateLimiter::new(2, Duration::from_secs(60));
+        let start = Instant::now();
+        assert!(limiter.allow_at("a", start));
+        assert!(limiter.allow_at("a", start));
+        assert!(!limiter.allow_at("a", start + Duration::from_secs(30)));
+        // Other keys have their own budget
+        assert!(limiter.allow_at("b", start));
+        assert!(limiter.allow_at("a", start + Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn test_token_bucket_bursts_then_refills_evenly() {
+        let limiter = TokenBucketLimiter::per_minute(3);
+        let start = Instant::now();
+        for _ in 0..3 {
+            assert_eq!(limiter.acquire_at("a", start), Ok(()));
+        }
+        // One token comes back every 20 seconds
+        assert_eq!(limiter.acquire_at("a", start), Err(Duration::from_secs(20)));
+        assert_eq!(
+            limiter.acquire_at("a", start + Duration::from_secs(15)),
+            Err(Duration::from_secs(5))
+        );
+        assert_eq!(limiter.acquire_at("a", start + Duration::from_secs(20)), Ok(()));
+        assert!(limiter.acquire_at("a", start + Duration::from_secs(20)).is_err());
+        // Other keys have their own bucket
+        assert_eq!(limiter.acquire_at("b", start), Ok(()));
+        // Idle time refills up to the burst, not beyond
+        let later = start + Duration::from_secs(3600);
+        for _ in 0..3 {
+            assert_eq!(limiter.acquire_at("a", later), Ok(()));
+        }
+        assert!(limiter.acquire_at("a", later).is_err());
+    }
+
+    #[test]
+    fn test_token_bucket_zero_is_unlimited() {
+        let limiter = TokenBucketLimiter::per_minute(0);
+        let start = Instant::now();
+        for _ in 0..1000 {
+            assert_eq!(limiter.acquire_at("a", start), Ok(()));
+        }
+    }
+}
@@ -0,0 +1,348 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+use crate::config::RerankMode;
+use crate::services::provider_guard::CircuitBreakers;
+use crate::services::vector::SearchResult;
+
+/// How many candidates to retrieve before reranking down to `top_k`.
+pub const RERANK_CANDIDATES: u64 = 20;
+
+const COHERE_RERANK_URL: &str = "https://api.cohere.com/v2/rerank";
+const COHERE_RERANK_MODEL: &str = "rerank-v3.5";
+/// Reranking runs before every reply, so a slow Cohere falls back to the
+/// lexical scorer rather than holding the reply up.
+const COHERE_RERANK_TIMEOUT: Duration = Duration::from_secs(5);
+
+// BM25 parameters
+const BM25_K1: f32 = 1.2;
+const BM25_B: f32 = 0.75;
+
+#[derive(Clone)]
+pub struct RerankService {
+    mode: RerankMode,
+    client: reqwest::Client,
+    breakers: CircuitBreakers,
+    endpoint: String,
+    timeout: Duration,
+}
+
+#[derive(Serialize)]
+struct CohereRerankRequest<'a> {
+    model: &'a str,
+    query: &'a str,
+    documents: Vec<&'a str>,
+    top_n: usize,
+}
+
+#[derive(Deserialize)]
+struct CohereRerankResponse {
+    results: Vec<CohereRerankResult>,
+}
+
+#[derive(Deserialize)]
+struct CohereRerankResult {
+    index: usize,
+    relevance_score: f32,
+}
+
+impl RerankService {
+    /// Cohere calls go through `breakers`, the same circuits as chat and embedding calls.
+    pub fn new(mode: RerankMode, breakers: CircuitBreakers) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(COHERE_RERANK_TIMEOUT)
+            .build()
+            .expect("Failed to build rerank HTTP client");
+        Self {
+            mode,
+            client,
+            breakers,
+            endpoint: COHERE_RERANK_URL.to_string(),
+            timeout: COHERE_RERANK_TIMEOUT,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.mode != RerankMode::None
+    }
+
+    pub fn needs_cohere_key(&self) -> bool {
+        self.mode == RerankMode::Cohere
+    }
+
+    /// Number of candidates to fetch from retrieval so the reranker has something to reorder.
+    pub fn candidate_count(&self, top_k: u64) -> u64 {
+        if self.is_enabled() {
+            top_k.max(RERANK_CANDIDATES)
+        } else {
+            top_k
+        }
+    }
+
+    /// Reorder `candidates` by relevance to `query` and keep the best `top_k`.
+    /// Cohere reranking falls back to the lexical scorer when no key is available
+    /// or the request fails, so retrieval never breaks because of the reranker.
+    pub async fn rerank(
+        &self,
+        query: &str,
+        candidates: Vec<SearchResult>,
+        top_k: usize,
+        cohere_api_key: Option<&str>,
+    ) -> Vec<SearchResult> {
+        if candidates.is_empty() {
+            return candidates;
+        }
+
+        let started = Instant::now();
+        let count = candidates.len();
+
+        let (method, mut ranked) = match (self.mode, cohere_api_key) {
+            (RerankMode::None, _) => return truncate(candidates, top_k),
+            (RerankMode::Lexical, _) => ("lexical", rerank_lexical(query, candidates)),
+            (RerankMode::Cohere, Some(api_key)) => {
+                match self.rerank_cohere(api_key, query, &candidates).await {
+                    Ok(ranked) => ("cohere", ranked),
+                    Err(e) => {
+                        tracing::warn!("Cohere rerank failed, falling back to lexical: {e:#}");
+                        ("lexical", rerank_lexical(query, candidates))
+                    }
+                }
+            }
+            (RerankMode::Cohere, None) => {
+                tracing::warn!("Cohere rerank enabled but no cohere API key configured, using lexical");
+                ("lexical", rerank_lexical(query, candidates))
+            }
+        };
+
+        ranked.truncate(top_k);
+
+        tracing::info!(
+            "Reranked {count} candidates with {method} in {}ms",
+            started.elapsed().as_millis()
+        );
+
+        ranked
+    }
+
+    async fn rerank_cohere(
+        &self,
+        api_key: &str,
+        query: &str,
+        candidates: &[SearchResult],
+    ) -> Result<Vec<SearchResult>> {
+        let body = CohereRerankRequest {
+            model: COHERE_RERANK_MODEL,
+            query,
+            documents: candidates.iter().map(|c| c.content.as_str()).collect(),
+            top_n: candidates.len(),
+        };
+
+        let call = async {
+            let response = self
+                .client
+                .post(&self.endpoint)
+                .bearer_auth(api_key)
+                .json(&body)
+                .send()
+                .await
+                .context("Failed to call Cohere rerank")?;
+
+            if !response.status().is_success() {
+                let status = response.status();
+                let text = response.text().await.unwrap_or_default();
+                anyhow::bail!("Cohere rerank returned {status}: {text}");
+            }
+
+            response
+                .json::<CohereRerankResponse>()
+                .await
+                .context("Failed to parse Cohere rerank response")
+        };
+        let parsed = self.breakers.guard("cohere", self.timeout, call).await?;
+
+        Ok(parsed
+            .results
+            .into_iter()
+            .filter_map(|r| {
+                candidates.get(r.index).map(|c| SearchResult {
+                    score: r.relevance_score,
+                    ..c.clone()
+                })
+            })
+            .collect())
+    }
+}
+
+fn truncate(mut results: Vec<SearchResult>, top_k: usize) -> Vec<SearchResult> {
+    results.truncate(top_k);
+    results
+}
+
+fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|t| !t.is_empty())
+        .map(|t| t.to_lowercase())
+        .collect()
+}
+
+/// BM25-style scoring of each candidate against the query, using the candidate
+/// set itself for document frequencies. Ties keep the original retrieval order.
+pub fn rerank_lexical(query: &str, candidates: Vec<SearchResult>) -> Vec<SearchResult> {
+    let query_terms: HashSet<String> = tokenize(query).into_iter().collect();
+    let docs: Vec<Vec<String>> = candidates.iter().map(|c| tokenize(&c.content)).collect();
+
+    let n = docs.len() as f32;
+    let avg_len = docs.iter().map(|d| d.len()).sum::<usize>() as f32 / n.max(1.0);
+
+    let mut doc_freq: HashMap<&str, usize> = HashMap::new();
+    for doc in &docs {
+        let unique: HashSet<&str> = doc.iter().map(|t| t.as_str()).collect();
+        for term in unique {
+            if query_terms.contains(term) {
+                *doc_freq.entry(term).or_default() += 1;
+            }
+        }
+    }
+
+    let scores: Vec<f32> = docs
+        .iter()
+        .map(|doc| {
+            let len = doc.len() as f32;
+            query_terms
+                .iter()
+                .map(|term| {
+                    let tf = doc.iter().filter(|t| *t == term).count() as f32;
+                    if tf == 0.0 {
+                        return 0.0;
+                    }
+                    let df = *doc_freq.get(term.as_str()).unwrap_or(&0) as f32;
+                    let idf = (1.0 + (n - df + 0.5) / (df + 0.5)).ln();
+                    let norm = BM25_K1 * (1.0 - BM25_B + BM25_B * len / avg_len.max(1.0));
+                    idf * tf * (BM25_K1 + 1.0) / (tf + norm)
+                })
+                .sum()
+        })
+        .collect();
+
+    let mut ranked: Vec<(usize, f32, SearchResult)> = candidates
+        .into_iter()
+        .enumerate()
+        .map(|(i, c)| (i, scores[i], c))
+        .collect();
+
+    ranked.sort_by(|a, b| {
+        b.1.partial_cmp(&a.1)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then(a.0.cmp(&b.0))
+    });
+
+    ranked
+        .into_iter()
+        .map(|(_, score, c)| SearchResult { score, ..c })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::provider_guard::CircuitState;
+
+    fn no_breakers() -> CircuitBreakers {
+        CircuitBreakers::new(0, Duration::ZERO)
+    }
+
+    fn candidate(id: &str, content: &str) -> SearchResult {
+        SearchResult {
+            point_id: id.to_string(),
+            score: 0.5,
+            vector_score: Some(0.5),
+            content: content.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_lexical_promotes_matching_chunk() {
+        let candidates = vec![
+            candidate("a", "General information about our shipping policy."),
+            candidate("b", "Returns are accepted within thirty days."),
+            candidate("c", "Error ERR-4021 means the payment gateway timed out."),
+        ];
+
+        let ranked = rerank_lexical("what does ERR-4021 mean", candidates);
+
+        assert_eq!(ranked[0].point_id, "c");
+        assert!(ranked[0].score > ranked[1].score);
+        assert_eq!(ranked[0].vector_score, Some(0.5));
+    }
+
+    #[test]
+    fn test_lexical_keeps_order_on_ties() {
+        let candidates = vec![
+            candidate("a", "alpha"),
+            candidate("b", "beta"),
+            candidate("c", "gamma"),
+        ];
+
+        let ranked = rerank_lexical("unrelated", candidates);
+        let ids: Vec<&str> = ranked.iter().map(|r| r.point_id.as_str()).collect();
+        assert_eq!(ids, vec!["a", "b", "c"]);
+    }
+
+    #[tokio::test]
+    async fn test_rerank_truncates_to_top_k() {
+        let service = RerankService::new(RerankMode::Lexical, no_breakers());
+        let candidates = vec![
+            candidate("a", "nothing here"),
+            candidate("b", "refund policy details"),
+            candidate("c", "more unrelated text"),
+        ];
+
+        let ranked = service.rerank("refund policy", candidates, 1, None).await;
+        assert_eq!(ranked.len(), 1);
+        assert_eq!(ranked[0].point_id, "b");
+    }
+
+    #[test]
+    fn test_candidate_count() {
+        assert_eq!(RerankService::new(RerankMode::None, no_breakers()).candidate_count(5), 5);
+        assert_eq!(RerankService::new(RerankMode::Lexical, no_breakers()).candidate_count(5), 20);
+        assert_eq!(RerankService::new(RerankMode::Lexical, no_breakers()).candidate_count(30), 30);
+    }
+
+    #[tokio::test]
+    async fn test_hanging_cohere_falls_back_and_opens_the_circuit() {
+        // Accepts connections and never answers
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut held = Vec::new();
+            while let Ok((socket, _)) = listener.accept().await {
+                held.push(socket);
+            }
+        });
+
+        let breakers = CircuitBreakers::new(2, Duration::from_secs(60));
+        let service = RerankService {
+            endpoint: format!("http://{address}/v2/rerank"),
+            timeout: Duration::from_millis(200),
+            ..RerankService::new(RerankMode::Cohere, breakers.clone())
+        };
+        let candidates = || vec![candidate("a", "shipping costs"), candidate("b", "refund policy")];
+
+        for _ in 0..2 {
+            let started = Instant::now();
+            let ranked = service.rerank("refund policy", candidates(), 2, Some("co-key")).await;
+            assert!(started.elapsed() < Duration::from_secs(2));
+            assert_eq!(ranked[0].point_id, "b");
+        }
+        assert_eq!(breakers.state("cohere"), CircuitState::Open);
+
+        // Open: Cohere isn't called at all
+        let started = Instant::now();
+        let ranked = service.rerank("refund policy", candidates(), 2, Some("co-key")).await;
+        assert!(started.elapsed() < Duration::from_millis(100));
+        assert_eq!(ranked[0].point_id, "b");
+    }
+}
@@ -0,0 +1,270 @@
+use rand::Rng;
+use std::fmt::Display;
+use std::future::Future;
+use std::time::Duration;
+
+/// How often and how patiently to retry a provider call.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Exponential backoff with jitter before retry number `attempt` (1-based).
+    /// A provider-supplied `Retry-After` wins when present, capped at `max_delay`.
+    pub(crate) fn delay(&self, attempt: u32, retry_after: Option<Duration>) -> Duration {
+        if let Some(retry_after) = retry_after {
+            return retry_after.min(self.max_delay);
+        }
+        let exponential = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_delay);
+        // Between half and all of the exponential delay, so concurrent workers spread out
+        exponential.mul_f64(rand::rng().random_range(0.5..=1.0))
+    }
+}
+
+/// How a failed provider call should be handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureKind {
+    /// Rate limits, timeouts and server errors: worth trying again.
+    Transient { retry_after: Option<Duration> },
+    /// The request was too big; retrying it unchanged won't help, but a smaller one may.
+    PayloadTooLarge,
+    /// Anything else (bad key, unknown model, ...).
+    Permanent,
+}
+
+/// Classify a provider error from its message. rig folds HTTP status and body
+/// into the error text, so this is the only signal available.
+pub fn classify(message: &str) -> FailureKind {
+    let lower = message.to_lowercase();
+
+    const TRANSIENT_CODES: &[&str] = &["429", "500", "502", "503", "504"];
+    const TRANSIENT: &[&str] = &[
+        "rate limit",
+        "rate_limit",
+        "too many requests",
+        "overloaded",
+        "internal server error",
+        "bad gateway",
+        "service unavailable",
+        "temporarily",
+        "timed out",
+        "timeout",
+        "connection",
+        "error sending request",
+    ];
+    if TRANSIENT_CODES.iter().any(|c| contains_status(&lower, c))
+        || TRANSIENT.iter().any(|p| lower.contains(p))
+    {
+        return FailureKind::Transient {
+            retry_after: parse_retry_after(&lower),
+        };
+    }
+
+    const TOO_LARGE: &[&str] = &[
+        "too large",
+        "too long",
+        "too many inputs",
+        "maximum context length",
+        "max_tokens_per_request",
+        "tokens per request",
+        "batch size",
+    ];
+    if contains_status(&lower, "413") || TOO_LARGE.iter().any(|p| lower.contains(p)) {
+        return FailureKind::PayloadTooLarge;
+    }
+
+    FailureKind::Permanent
+}
+
+/// True when `code` appears as a standalone number, so "503" matches
+/// "status code 503" but not "Limit 1503000".
+pub(crate) fn contains_status(lower: &str, code: &str) -> bool {
+    lower.match_indices(code).any(|(i, _)| {
+        let before = lower[..i].chars().next_back();
+        let after = lower[i + code.len()..].chars().next();
+        !before.is_some_and(|c| c.is_ascii_digit()) && !after.is_some_and(|c| c.is_ascii_digit())
+    })
+}
+
+/// Find a retry hint such as `Retry-After: 3`, `retry after 2 seconds` or
+/// OpenAI's `Please try again in 1.5s` / `in 250ms`.
+fn parse_retry_after(lower: &str) -> Option<Duration> {
+    ["retry-after:", "retry after", "try again in"]
+        .iter()
+        .find_map(|marker| {
+            let rest = lower[lower.find(marker)? + marker.len()..].trim_start();
+            let end = rest
+                .find(|c: char| !c.is_ascii_digit() && c != '.')
+                .unwrap_or(rest.len());
+            let value: f64 = rest[..end].parse().ok()?;
+            let seconds = if rest[end..].starts_with("ms") {
+                value / 1000.0
+            } else {
+                value
+            };
+            Duration::try_from_secs_f64(seconds).ok()
+        })
+}
+
+/// Run `op` until it succeeds, fails with a non-transient error, or
+/// `policy.max_attempts` is reached. `op` receives the 1-based attempt number.
+pub async fn retry_with_backoff<T, E, F, Fut>(policy: RetryPolicy, mut op: F) -> Result<T, E>
+where
+    E: Display,
+    F: FnMut(u32) -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let mut attempt = 1;
+    loop {
+        let error = match op(attempt).await {
+            Ok(value) => return Ok(value),
+            Err(e) => e,
+        };
+
+        let FailureKind::Transient { retry_after } = classify(&error.to_string()) else {
+            return Err(error);
+        };
+        if attempt >= policy.max_attempts {
+            return Err(error);
+        }
+
+        let delay = policy.delay(attempt, retry_after);
+        tracing::warn!(
+            "Attempt {attempt}/{} failed, retrying in {}ms: {error}",
+            policy.max_attempts,
+            delay.as_millis()
+        );
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    fn fast_policy() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(5),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fails_twice_then_succeeds() {
+        let calls = Cell::new(0);
+        let result: Result<&str, String> = retry_with_backoff(fast_policy(), |attempt| {
+            calls.set(calls.get() + 1);
+            async move {
+                if attempt < 3 {
+                    Err("429 Too Many Requests".to_string())
+                } else {
+                    Ok("embedded")
+                }
+            }
+        })
+        .await;
+
+        assert_eq!(result.unwrap(), "embedded");
+        assert_eq!(calls.get(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_max_attempts() {
+        let calls = Cell::new(0);
+        let result: Result<(), String> = retry_with_backoff(fast_policy(), |_| {
+            calls.set(calls.get() + 1);
+            async { Err("503 Service Unavailable".to_string()) }
+        })
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(calls.get(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_permanent_errors_are_not_retried() {
+        let calls = Cell::new(0);
+        let result: Result<(), String> = retry_with_backoff(fast_policy(), |_| {
+            calls.set(calls.get() + 1);
+            async { Err("Incorrect API key provided".to_string()) }
+        })
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(
+            classify("ProviderError: Rate limit reached. Please try again in 1.5s."),
+            FailureKind::Transient {
+                retry_after: Some(Duration::from_millis(1500))
+            }
+        );
+        assert_eq!(
+            classify("HttpError: Http client error: error sending request: connection reset"),
+            FailureKind::Transient { retry_after: None }
+        );
+        assert_eq!(
+            classify("ProviderError: Requested 400000 tokens, max 300000 tokens per request"),
+            FailureKind::PayloadTooLarge
+        );
+        assert_eq!(
+            classify("ProviderError: Incorrect API key provided"),
+            FailureKind::Permanent
+        );
+        assert_eq!(
+            classify("Invalid status code 413 Payload Too Large"),
+            FailureKind::PayloadTooLarge
+        );
+        // Status codes only count as whole numbers
+        assert_eq!(
+            classify("ProviderError: input has 15000 tokens, exceeds limit"),
+            FailureKind::Permanent
+        );
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        assert_eq!(parse_retry_after("retry-after: 3"), Some(Duration::from_secs(3)));
+        assert_eq!(
+            parse_retry_after("please try again in 250ms"),
+            Some(Duration::from_millis(250))
+        );
+        assert_eq!(parse_retry_after("rate limited"), None);
+    }
+
+    #[test]
+    fn test_delay_is_bounded() {
+        let policy = RetryPolicy::default();
+        for attempt in 1..10 {
+            let delay = policy.delay(attempt, None);
+            assert!(delay <= policy.max_delay);
+            assert!(delay >= policy.base_delay / 2);
+        }
+        assert_eq!(
+            policy.delay(1, Some(Duration::from_secs(120))),
+            policy.max_delay
+        );
+    }
+}
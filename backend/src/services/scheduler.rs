@@ -0,0 +1,193 @@
+use anyhow::Result;
+use futures::FutureExt;
+use rand::Rng;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use crate::state::AppState;
+
+type JobFuture = Pin<Box<dyn Future<Output = Result<()>> + Send>>;
+type JobFn = Arc<dyn Fn(AppState) -> JobFuture + Send + Sync>;
+
+/// A periodic job registered with the [`Scheduler`].
+pub struct ScheduledJob {
+    name: String,
+    interval: Duration,
+    run: JobFn,
+    running: AtomicBool,
+}
+
+impl ScheduledJob {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    /// Mark the job running, unless it already is.
+    fn claim(&self) -> bool {
+        !self.running.swap(true, Ordering::SeqCst)
+    }
+}
+
+/// Periodic maintenance work. Jobs are registered by name at startup and run
+/// by [`start`] every interval; each run is recorded in `scheduled_job_runs`,
+/// and a failing or panicking run doesn't stop the next one.
+#[derive(Clone, Default)]
+pub struct Scheduler {
+    jobs: Arc<RwLock<Vec<Arc<ScheduledJob>>>>,
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register `run` to be called every `interval` as `name`, replacing a
+    /// job registered under the same name.
+    pub fn register<F, Fut>(&self, name: &str, interval: Duration, run: F)
+    where
+        F: Fn(AppState) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        let job = Arc::new(ScheduledJob {
+            name: name.to_string(),
+            interval,
+            run: Arc::new(move |state| Box::pin(run(state))),
+            running: AtomicBool::new(false),
+        });
+        let mut jobs = self.jobs.write().unwrap_or_else(|e| e.into_inner());
+        jobs.retain(|j| j.name != name);
+        jobs.push(job);
+    }
+
+    /// Registered jobs, in registration order.
+    pub fn jobs(&self) -> Vec<Arc<ScheduledJob>> {
+        self.jobs.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    pub fn find(&self, name: &str) -> Option<Arc<ScheduledJob>> {
+        self.jobs().into_iter().find(|j| j.name == name)
+    }
+}
+
+/// Start running `job` in the background now. Returns false, without starting
+/// it, if a run is already in progress.
+pub fn run_now(state: &AppState, job: Arc<ScheduledJob>) -> bool {
+    if !job.claim() {
+        return false;
+    }
+    let state = state.clone();
+    state.tasks.clone().spawn(async move { execute(&state, &job).await });
+    true
+}
+
+/// Run a claimed job once, record how it went and release it.
+async fn execute(state: &AppState, job: &ScheduledJob) {
+    let repo = &state.scheduled_job_run_repo;
+    if let Err(e) = repo.record_start(&job.name).await {
+        tracing::error!("Failed to record start of scheduled job {}: {e:#}", job.name);
+    }
+
+    let started = Instant::now();
+    let outcome = AssertUnwindSafe((job.run)(state.clone())).catch_unwind().await;
+    let duration = started.elapsed();
+    let error = match outcome {
+        Ok(Ok(())) => None,
+        Ok(Err(e)) => Some(format!("{e:#}")),
+        Err(panic) => Some(format!("Panicked: {}", panic_message(panic.as_ref()))),
+    };
+    match &error {
+        Some(error) => tracing::error!(
+            "Scheduled job {} failed after {}ms: {error}",
+            job.name,
+            duration.as_millis()
+        ),
+        None => tracing::debug!("Scheduled job {} finished in {}ms", job.name, duration.as_millis()),
+    }
+
+    if let Err(e) = repo.record_finish(&job.name, duration, error.as_deref()).await {
+        tracing::error!("Failed to record run of scheduled job {}: {e:#}", job.name);
+    }
+    job.running.store(false, Ordering::SeqCst);
+}
+
+fn panic_message(panic: &(dyn std::any::Any + Send)) -> &str {
+    panic
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown panic")
+}
+
+/// A random delay of up to `max_secs`.
+fn jitter(max_secs: u64) -> Duration {
+    if max_secs == 0 {
+        return Duration::ZERO;
+    }
+    Duration::from_millis(rand::rng().random_range(0..=max_secs * 1000))
+}
+
+/// Start a loop for every enabled job. Each runs shortly after startup, then
+/// every interval; a run still in progress when the next is due is skipped.
+pub fn start(state: AppState) {
+    let config = &state.config.scheduler;
+    for job in state.scheduler.jobs() {
+        if !config.is_enabled(&job.name) {
+            tracing::info!("Scheduled job {} is disabled", job.name);
+            continue;
+        }
+
+        let state = state.clone();
+        let jitter_secs = config.jitter_secs;
+        let stopping = state.tasks.stopping().clone();
+        state.tasks.clone().spawn(async move {
+            let mut delay = jitter(jitter_secs);
+            loop {
+                tokio::select! {
+                    _ = tokio::time::sleep(delay) => {}
+                    _ = stopping.cancelled() => break,
+                }
+                if job.claim() {
+                    execute(&state, &job).await;
+                } else {
+                    tracing::info!("Scheduled job {} is still running, skipping this run", job.name);
+                }
+                delay = job.interval + jitter(jitter_secs);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_jitter_stays_within_bound() {
+        assert_eq!(jitter(0), Duration::ZERO);
+        for _ in 0..100 {
+            assert!(jitter(2) <= Duration::from_secs(2));
+        }
+    }
+
+    #[test]
+    fn test_panic_message_reads_str_and_string_payloads() {
+        let payload: Box<dyn std::any::Any + Send> = Box::new("boom");
+        assert_eq!(panic_message(payload.as_ref()), "boom");
+        let payload: Box<dyn std::any::Any + Send> = Box::new(format!("code {}", 7));
+        assert_eq!(panic_message(payload.as_ref()), "code 7");
+        let payload: Box<dyn std::any::Any + Send> = Box::new(7);
+        assert_eq!(panic_message(payload.as_ref()), "unknown panic");
+    }
+}
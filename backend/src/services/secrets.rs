@@ -0,0 +1,120 @@
+use anyhow::{Context, Result};
+use rand::Rng;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+
+/// Prefix on stored values so the format can change later without guessing.
+const PREFIX: &str = "enc:v1:";
+
+/// AES-256-GCM encryption for secrets at rest (organization API keys).
+///
+/// The key is derived from a configured secret, so changing that secret makes
+/// previously stored values unreadable.
+#[derive(Clone)]
+pub struct SecretCipher {
+    key: Arc<LessSafeKey>,
+}
+
+impl SecretCipher {
+    pub fn new(secret: &str) -> Self {
+        let mut hasher = Sha256::new();
+        hasher.update(b"rag-pipeline:secrets:");
+        hasher.update(secret.as_bytes());
+        let key = hasher.finalize();
+
+        let key = UnboundKey::new(&AES_256_GCM, &key)
+            .expect("SHA-256 output is a valid AES-256 key");
+        Self {
+            key: Arc::new(LessSafeKey::new(key)),
+        }
+    }
+
+    pub fn encrypt(&self, plaintext: &str) -> Result<String> {
+        let mut nonce_bytes = [0u8; NONCE_LEN];
+        rand::rng().fill(&mut nonce_bytes);
+
+        let mut ciphertext = plaintext.as_bytes().to_vec();
+        self.key
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce_bytes),
+                Aad::empty(),
+                &mut ciphertext,
+            )
+            .map_err(|_| anyhow::anyhow!("Failed to encrypt secret"))?;
+
+        Ok(format!("{PREFIX}{}{}", to_hex(&nonce_bytes), to_hex(&ciphertext)))
+    }
+
+    pub fn decrypt(&self, stored: &str) -> Result<String> {
+        let hex = stored
+            .strip_prefix(PREFIX)
+            .context("Stored secret has an unknown format")?;
+        let bytes = from_hex(hex).context("Stored secret is not valid hex")?;
+        if bytes.len() <= NONCE_LEN {
+            anyhow::bail!("Stored secret is too short");
+        }
+
+        let (nonce, ciphertext) = bytes.split_at(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(nonce)
+            .map_err(|_| anyhow::anyhow!("Stored secret has an invalid nonce"))?;
+        let mut ciphertext = ciphertext.to_vec();
+        let plaintext = self
+            .key
+            .open_in_place(nonce, Aad::empty(), &mut ciphertext)
+            .map_err(|_| {
+                anyhow::anyhow!("Failed to decrypt secret (was the encryption key changed?)")
+            })?;
+
+        String::from_utf8(plaintext.to_vec()).context("Decrypted secret is not valid UTF-8")
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let cipher = SecretCipher::new("test-secret");
+        let stored = cipher.encrypt("sk-live-1234").unwrap();
+
+        assert!(stored.starts_with(PREFIX));
+        assert!(!stored.contains("sk-live-1234"));
+        assert_eq!(cipher.decrypt(&stored).unwrap(), "sk-live-1234");
+    }
+
+    #[test]
+    fn test_nonce_is_random() {
+        let cipher = SecretCipher::new("test-secret");
+        assert_ne!(cipher.encrypt("same").unwrap(), cipher.encrypt("same").unwrap());
+    }
+
+    #[test]
+    fn test_wrong_secret_fails() {
+        let stored = SecretCipher::new("secret-a").encrypt("sk-live-1234").unwrap();
+        assert!(SecretCipher::new("secret-b").decrypt(&stored).is_err());
+    }
+
+    #[test]
+    fn test_rejects_malformed_values() {
+        let cipher = SecretCipher::new("test-secret");
+        assert!(cipher.decrypt("sk-plaintext").is_err());
+        assert!(cipher.decrypt("enc:v1:zz").is_err());
+        assert!(cipher.decrypt("enc:v1:00ff").is_err());
+    }
+}
@@ -0,0 +1,147 @@
+use axum::response::sse::{Event, KeepAlive};
+use futures::stream::{self, Stream, StreamExt};
+use std::convert::Infallible;
+use std::future::Future;
+use std::time::Duration;
+
+/// How often a comment ping is sent on an otherwise idle stream.
+pub const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Delay between tokens when replaying a complete reply as a stream.
+const TOKEN_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Data of the `done` event, kept for clients predating named events.
+pub const LEGACY_DONE_MARKER: &str = "[DONE]";
+
+pub fn keep_alive() -> KeepAlive {
+    KeepAlive::new().interval(KEEP_ALIVE_INTERVAL).text("ping")
+}
+
+pub fn token_event(text: &str) -> Event {
+    Event::default().event("token").data(text)
+}
+
+pub fn warning_event(message: &str) -> Event {
+    Event::default()
+        .event("warning")
+        .data(serde_json::json!({ "warning": message }).to_string())
+}
+
+pub fn notice_event(message: &str) -> Event {
+    Event::default()
+        .event("notice")
+        .data(serde_json::json!({ "notice": message }).to_string())
+}
+
+pub fn error_event(message: &str) -> Event {
+    Event::default()
+        .event("error")
+        .data(serde_json::json!({ "error": message }).to_string())
+}
+
+pub fn done_event() -> Event {
+    Event::default().event("done").data(LEGACY_DONE_MARKER)
+}
+
+/// A finished reply, with notes on how it was produced for whoever is chatting.
+#[derive(Debug, Default)]
+pub struct Reply {
+    pub text: String,
+    pub notices: Vec<String>,
+}
+
+impl From<String> for Reply {
+    fn from(text: String) -> Self {
+        Self {
+            text,
+            notices: Vec::new(),
+        }
+    }
+}
+
+/// Stream the reply produced by `reply` as the chat event contract:
+///
+/// - `event: warning` carries `{"warning": "..."}`; `warnings` are sent first.
+/// - `event: notice` carries `{"notice": "..."}`, something worth knowing
+///   about the reply, e.g. that another provider answered it.
+/// - `event: token` carries a piece of the reply as plain text.
+/// - `event: done` ends a successful reply. Its data is the legacy `[DONE]`
+///   marker so clients that only read `data:` lines keep working.
+/// - `event: error` carries `{"error": "..."}` instead, when `reply` fails.
+///
+/// Only keep-alive pings are sent until `reply` resolves.
+pub fn reply_stream<F, R>(
+    warnings: Vec<String>,
+    reply: F,
+) -> impl Stream<Item = Result<Event, Infallible>>
+where
+    F: Future<Output = Result<R, String>> + Send + 'static,
+    R: Into<Reply>,
+{
+    let warnings = stream::iter(warnings).map(|w| Ok(warning_event(&w)));
+    warnings.chain(stream::once(reply).flat_map(|result| {
+        let events: Vec<Event> = match result.map(Into::into) {
+            Ok(Reply { text, notices }) => notices
+                .iter()
+                .map(|n| notice_event(n))
+                .chain(text.split_inclusive(' ').map(token_event))
+                .chain(std::iter::once(done_event()))
+                .collect(),
+            Err(message) => vec![error_event(&message)],
+        };
+        tokio_stream::StreamExt::throttle(stream::iter(events), TOKEN_INTERVAL).map(Ok)
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::response::IntoResponse;
+    use axum::response::sse::Sse;
+
+    async fn render(warnings: Vec<String>, result: Result<String, String>) -> String {
+        let response = Sse::new(reply_stream(warnings, async move { result })).into_response();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_reply_stream_events() {
+        let body = render(Vec::new(), Ok("Hello there".to_string())).await;
+        assert_eq!(
+            body,
+            "event: token\ndata: Hello \n\nevent: token\ndata: there\n\nevent: done\ndata: [DONE]\n\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reply_stream_error() {
+        let body = render(Vec::new(), Err("Model unavailable".to_string())).await;
+        assert_eq!(body, "event: error\ndata: {\"error\":\"Model unavailable\"}\n\n");
+    }
+
+    #[tokio::test]
+    async fn test_reply_stream_notices_come_before_tokens() {
+        let reply = Reply {
+            text: "Hi".to_string(),
+            notices: vec!["Anthropic answered".to_string()],
+        };
+        let response = Sse::new(reply_stream(Vec::new(), async move { Ok(reply) })).into_response();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(
+            String::from_utf8(body.to_vec()).unwrap(),
+            "event: notice\ndata: {\"notice\":\"Anthropic answered\"}\n\n\
+             event: token\ndata: Hi\n\nevent: done\ndata: [DONE]\n\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reply_stream_warnings_come_first() {
+        let body = render(vec!["Using the default model".to_string()], Ok("Hi".to_string())).await;
+        assert_eq!(
+            body,
+            "event: warning\ndata: {\"warning\":\"Using the default model\"}\n\n\
+             event: token\ndata: Hi\n\nevent: done\ndata: [DONE]\n\n"
+        );
+    }
+}
@@ -2,10 +2,15 @@ use anyhow::{Context, Result};
 use aws_credential_types::Credentials;
 use aws_sdk_s3::config::{BehaviorVersion, Region};
 use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::types::{CompletedMultipartUpload, CompletedPart};
 use aws_sdk_s3::Client;
 
 use crate::config::MinioConfig;
 
+/// Size of each part in a streaming upload. S3 requires at least 5 MiB for
+/// every part but the last; this also bounds the memory one upload holds.
+const PART_SIZE: usize = 8 * 1024 * 1024;
+
 #[derive(Clone)]
 pub struct StorageService {
     client: Client,
@@ -14,6 +19,13 @@ pub struct StorageService {
 
 impl StorageService {
     pub async fn new(config: &MinioConfig) -> Result<Self> {
+        let service = Self::connect_lazy(config);
+        service.ensure_bucket_exists().await?;
+        Ok(service)
+    }
+
+    /// Build the client without contacting MinIO or creating the bucket.
+    pub fn connect_lazy(config: &MinioConfig) -> Self {
         let creds = Credentials::new(
             &config.access_key,
             &config.secret_key,
@@ -30,16 +42,10 @@ impl StorageService {
             .force_path_style(true)
             .build();
 
-        let client = Client::from_conf(s3_config);
-
-        let service = Self {
-            client,
+        Self {
+            client: Client::from_conf(s3_config),
             bucket: config.bucket_name.clone(),
-        };
-
-        service.ensure_bucket_exists().await?;
-
-        Ok(service)
+        }
     }
 
     async fn ensure_bucket_exists(&self) -> Result<()> {
@@ -82,6 +88,20 @@ impl StorageService {
         Ok(())
     }
 
+    /// Start an upload whose content arrives in pieces. Nothing is sent until
+    /// the first part fills up; files smaller than one part become a single
+    /// `put_object` in [`StreamingUpload::finish`].
+    pub fn start_upload(&self, key: &str, content_type: &str) -> StreamingUpload {
+        StreamingUpload {
+            storage: self.clone(),
+            key: key.to_string(),
+            content_type: content_type.to_string(),
+            upload_id: None,
+            parts: Vec::new(),
+            buffer: Vec::with_capacity(PART_SIZE),
+        }
+    }
+
     pub async fn download(&self, key: &str) -> Result<Vec<u8>> {
         let resp = self
             .client
@@ -119,3 +139,120 @@ impl StorageService {
         format!("users/{user_id}/{document_id}/{filename}")
     }
 }
+
+/// An in-progress upload fed piece by piece, held in memory one part at a time.
+/// Call [`finish`](Self::finish) to store the object or [`abort`](Self::abort)
+/// to discard the parts already sent.
+pub struct StreamingUpload {
+    storage: StorageService,
+    key: String,
+    content_type: String,
+    upload_id: Option<String>,
+    parts: Vec<CompletedPart>,
+    buffer: Vec<u8>,
+}
+
+impl StreamingUpload {
+    pub async fn write(&mut self, mut data: &[u8]) -> Result<()> {
+        while !data.is_empty() {
+            let take = (PART_SIZE - self.buffer.len()).min(data.len());
+            self.buffer.extend_from_slice(&data[..take]);
+            data = &data[take..];
+
+            if self.buffer.len() == PART_SIZE {
+                self.flush_part().await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn flush_part(&mut self) -> Result<()> {
+        let client = &self.storage.client;
+        let upload_id = match &self.upload_id {
+            Some(id) => id.clone(),
+            None => {
+                let created = client
+                    .create_multipart_upload()
+                    .bucket(&self.storage.bucket)
+                    .key(&self.key)
+                    .content_type(&self.content_type)
+                    .send()
+                    .await
+                    .context("Failed to start multipart upload to MinIO")?;
+                let id = created
+                    .upload_id()
+                    .context("MinIO returned no multipart upload id")?
+                    .to_string();
+                self.upload_id = Some(id.clone());
+                id
+            }
+        };
+
+        let part_number = self.parts.len() as i32 + 1;
+        let body = std::mem::replace(&mut self.buffer, Vec::with_capacity(PART_SIZE));
+        let uploaded = client
+            .upload_part()
+            .bucket(&self.storage.bucket)
+            .key(&self.key)
+            .upload_id(&upload_id)
+            .part_number(part_number)
+            .body(ByteStream::from(body))
+            .send()
+            .await
+            .with_context(|| format!("Failed to upload part {part_number} to MinIO"))?;
+
+        self.parts.push(
+            CompletedPart::builder()
+                .part_number(part_number)
+                .set_e_tag(uploaded.e_tag().map(str::to_string))
+                .build(),
+        );
+        Ok(())
+    }
+
+    pub async fn finish(mut self) -> Result<()> {
+        let Some(upload_id) = self.upload_id.clone() else {
+            let data = std::mem::take(&mut self.buffer);
+            return self.storage.upload(&self.key, data, &self.content_type).await;
+        };
+
+        if !self.buffer.is_empty() {
+            self.flush_part().await?;
+        }
+        self.storage
+            .client
+            .complete_multipart_upload()
+            .bucket(&self.storage.bucket)
+            .key(&self.key)
+            .upload_id(upload_id)
+            .multipart_upload(
+                CompletedMultipartUpload::builder()
+                    .set_parts(Some(std::mem::take(&mut self.parts)))
+                    .build(),
+            )
+            .send()
+            .await
+            .context("Failed to complete multipart upload to MinIO")?;
+
+        Ok(())
+    }
+
+    /// Discard the upload. Failures are logged: MinIO expires abandoned parts anyway.
+    pub async fn abort(self) {
+        let Some(upload_id) = self.upload_id else {
+            return;
+        };
+        if let Err(e) = self
+            .storage
+            .client
+            .abort_multipart_upload()
+            .bucket(&self.storage.bucket)
+            .key(&self.key)
+            .upload_id(upload_id)
+            .send()
+            .await
+        {
+            tracing::warn!("Failed to abort multipart upload of {}: {e}", self.key);
+        }
+    }
+}
@@ -0,0 +1,161 @@
+use std::future::Future;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::TaskTracker;
+
+/// Error recorded on documents and crawl jobs interrupted by a shutdown.
+pub const SHUTDOWN_ERROR: &str = "Interrupted by server shutdown; retry to process again";
+
+/// Error recorded on work whose process stopped before finishing it, i.e. lost to a crash.
+pub const RESTART_ERROR: &str = "Interrupted by a server restart; retry to process again";
+
+/// How long aborted tasks get to record their failure before the process exits.
+const ABORT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Registry for background work (document processing, crawls, periodic loops and
+/// fire-and-forget writes) so shutdown can wait for it instead of dropping it.
+///
+/// Shutdown happens in two steps: `stopping` is cancelled as soon as a signal
+/// arrives so loops exit and nothing new starts, then `aborted` is cancelled once
+/// the grace period runs out so in-flight work can record a failure and return.
+#[derive(Clone, Default)]
+pub struct BackgroundTasks {
+    tracker: TaskTracker,
+    stopping: CancellationToken,
+    aborted: CancellationToken,
+}
+
+impl BackgroundTasks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn spawn<F>(&self, task: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.tracker.spawn(task);
+    }
+
+    /// Cancelled when shutdown starts. Periodic loops select on this.
+    pub fn stopping(&self) -> &CancellationToken {
+        &self.stopping
+    }
+
+    /// Run `work` to completion, or return `None` if the shutdown grace period
+    /// expires first. The caller is expected to mark its record failed.
+    pub async fn run_until_aborted<F: Future>(&self, work: F) -> Option<F::Output> {
+        self.aborted.run_until_cancelled(work).await
+    }
+
+    /// Stop accepting work, wait up to `grace` for tracked tasks to finish, then
+    /// abort the rest and give them a moment to record their failure.
+    pub async fn shutdown(&self, grace: Duration) {
+        self.stopping.cancel();
+        self.tracker.close();
+
+        let running = self.tracker.len();
+        if running > 0 {
+            tracing::info!("Waiting up to {}s for {running} background tasks", grace.as_secs());
+        }
+
+        if tokio::time::timeout(grace, self.tracker.wait()).await.is_ok() {
+            return;
+        }
+
+        tracing::warn!(
+            "Grace period expired, aborting {} background tasks",
+            self.tracker.len()
+        );
+        self.aborted.cancel();
+        if tokio::time::timeout(ABORT_TIMEOUT, self.tracker.wait()).await.is_err() {
+            tracing::error!(
+                "{} background tasks did not stop after being aborted",
+                self.tracker.len()
+            );
+        }
+    }
+}
+
+/// Resolves when the process receives SIGINT (Ctrl+C) or SIGTERM.
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!("Failed to listen for Ctrl+C: {e}");
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                tracing::error!("Failed to listen for SIGTERM: {e}");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+
+    tracing::info!("Shutdown signal received");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    #[tokio::test]
+    async fn test_shutdown_waits_for_short_tasks() {
+        let tasks = BackgroundTasks::new();
+        let finished = Arc::new(AtomicBool::new(false));
+
+        let flag = finished.clone();
+        tasks.spawn(async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            flag.store(true, Ordering::SeqCst);
+        });
+
+        tasks.shutdown(Duration::from_secs(5)).await;
+        assert!(finished.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_long_tasks_are_aborted_after_grace_period() {
+        let tasks = BackgroundTasks::new();
+        let recorded_failure = Arc::new(AtomicBool::new(false));
+
+        let handle = tasks.clone();
+        let flag = recorded_failure.clone();
+        tasks.spawn(async move {
+            let work = tokio::time::sleep(Duration::from_secs(3600));
+            if handle.run_until_aborted(work).await.is_none() {
+                flag.store(true, Ordering::SeqCst);
+            }
+        });
+
+        tasks.shutdown(Duration::from_millis(20)).await;
+        assert!(recorded_failure.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_stopping_is_cancelled_immediately() {
+        let tasks = BackgroundTasks::new();
+        let stopping = tasks.stopping().clone();
+        assert!(!stopping.is_cancelled());
+
+        tasks.shutdown(Duration::from_secs(1)).await;
+        assert!(stopping.is_cancelled());
+    }
+}
@@ -1,5 +1,7 @@
 use anyhow::{Context, Result};
 
+use crate::config::OcrConfig;
+
 /// Supported MIME types for document upload.
 pub const SUPPORTED_MIME_TYPES: &[&str] = &[
     "application/pdf",
@@ -29,13 +31,126 @@ pub fn is_supported(content_type: &str, filename: &str) -> bool {
         .unwrap_or(false)
 }
 
+/// How many leading bytes [`sniff_content_type`] needs to identify a file.
+pub const SNIFF_LEN: usize = 8 * 1024;
+
+const PDF: &str = "application/pdf";
+const DOCX: &str = "application/vnd.openxmlformats-officedocument.wordprocessingml.document";
+const XLSX: &str = "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet";
+const XLS: &str = "application/vnd.ms-excel";
+
+/// Text types a UTF-8 file may legitimately be declared as.
+const TEXT_MIME_TYPES: &[&str] = &["text/plain", "text/markdown", "text/csv", "text/xml", "application/xml"];
+
+/// Identify a file from its first bytes (at least [`SNIFF_LEN`] when available).
+/// The content decides the family (PDF, Office, text, ...); the declared type and
+/// extension only pick between types the content can't tell apart, such as CSV
+/// vs Markdown. Unsupported formats are reported by their own MIME type so
+/// callers can name them.
+pub fn sniff_content_type(head: &[u8], declared: &str, filename: &str) -> &'static str {
+    let ext = extension_from_filename(filename).unwrap_or_default();
+
+    if head.starts_with(b"%PDF-") {
+        return PDF;
+    }
+
+    // DOCX and XLSX are zip archives; their part names sit near the start
+    if head.starts_with(b"PK\x03\x04") {
+        if contains(head, b"word/") {
+            return DOCX;
+        }
+        if contains(head, b"xl/") {
+            return XLSX;
+        }
+        return match (declared, ext.as_str()) {
+            (DOCX, _) | (_, "docx") => DOCX,
+            (XLSX, _) | (_, "xlsx") => XLSX,
+            _ => "application/zip",
+        };
+    }
+
+    // Legacy Office (OLE compound file): only spreadsheets are supported
+    if head.starts_with(&[0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1]) {
+        return if declared == XLS || ext == "xls" {
+            XLS
+        } else {
+            "application/x-ole-storage"
+        };
+    }
+
+    const BINARY_SIGNATURES: &[(&[u8], &str)] = &[
+        (b"\x89PNG\r\n\x1a\n", "image/png"),
+        (b"\xFF\xD8\xFF", "image/jpeg"),
+        (b"GIF8", "image/gif"),
+        (b"MZ", "application/x-msdownload"),
+        (b"\x7FELF", "application/x-executable"),
+        (b"\x1F\x8B", "application/gzip"),
+    ];
+    if let Some((_, mime)) = BINARY_SIGNATURES.iter().find(|(magic, _)| head.starts_with(magic)) {
+        return mime;
+    }
+
+    if !looks_like_text(head) {
+        return "application/octet-stream";
+    }
+
+    if let Some(declared) = TEXT_MIME_TYPES.iter().find(|t| **t == declared) {
+        return declared;
+    }
+    match ext.as_str() {
+        "csv" => "text/csv",
+        "md" => "text/markdown",
+        "xml" => "application/xml",
+        _ if String::from_utf8_lossy(head)
+            .trim_start_matches('\u{feff}')
+            .trim_start()
+            .starts_with("<?xml") =>
+        {
+            "application/xml"
+        }
+        _ => "text/plain",
+    }
+}
+
+/// True if `mime` is a type [`extract_text`] can handle.
+pub fn is_supported_type(mime: &str) -> bool {
+    mime != "application/octet-stream" && SUPPORTED_MIME_TYPES.contains(&mime)
+}
+
+/// Text in any encoding [`decode_text`] handles: anything with a UTF-16 BOM, or
+/// bytes free of the control characters binary formats are full of. This admits
+/// legacy single-byte encodings such as Windows-1252, not just UTF-8.
+fn looks_like_text(head: &[u8]) -> bool {
+    if head.starts_with(&[0xFF, 0xFE]) || head.starts_with(&[0xFE, 0xFF]) {
+        return true;
+    }
+    // Tab, LF, FF, CR and ESC occur in text; other C0 controls don't
+    !head
+        .iter()
+        .any(|&b| b < 0x20 && !matches!(b, b'\t' | b'\n' | 0x0C | b'\r' | 0x1B))
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.windows(needle.len()).any(|w| w == needle)
+}
+
 /// Extract text from file bytes, routing to the correct extractor.
 ///
 /// CPU-bound extractors (PDF, DOCX, XLSX) are run on a blocking thread pool
-/// via `spawn_blocking` so they don't stall the async runtime.
-pub async fn extract_text(bytes: &[u8], content_type: &str, filename: &str) -> Result<String> {
+/// via `spawn_blocking` so they don't stall the async runtime. CSV files and
+/// spreadsheet sheets stop after `max_table_rows` rows.
+pub async fn extract_text(
+    bytes: &[u8],
+    content_type: &str,
+    filename: &str,
+    max_table_rows: usize,
+) -> Result<String> {
     let ext = extension_from_filename(filename).unwrap_or_default();
 
+    // Route by content, so documents stored with a wrong type still extract
+    let head = &bytes[..bytes.len().min(SNIFF_LEN)];
+    let content_type = sniff_content_type(head, content_type, filename);
+
     // Determine if this needs blocking extraction
     let needs_blocking = matches!(
         content_type,
@@ -55,7 +170,7 @@ pub async fn extract_text(bytes: &[u8], content_type: &str, filename: &str) -> R
 
         let handle = tokio::task::spawn_blocking(move || {
             tracing::info!("extract_text: spawn_blocking thread started for '{fname}'");
-            let result = extract_text_sync(&bytes, &ct, &ext);
+            let result = extract_text_sync(&bytes, &ct, &ext, max_table_rows);
             match &result {
                 Ok(text) => tracing::info!("extract_text: '{fname}' extraction succeeded, {} chars", text.len()),
                 Err(e) => tracing::error!("extract_text: '{fname}' extraction failed: {e:#}"),
@@ -69,30 +184,30 @@ pub async fn extract_text(bytes: &[u8], content_type: &str, filename: &str) -> R
             Err(_) => anyhow::bail!("Text extraction timed out after 120s for '{filename}'"),
         }
     } else {
-        extract_text_sync(bytes, content_type, &ext)
+        extract_text_sync(bytes, content_type, &ext, max_table_rows)
     }
 }
 
 /// Synchronous text extraction — called directly for lightweight formats,
 /// or via `spawn_blocking` for CPU-heavy ones (PDF, DOCX, XLSX).
-fn extract_text_sync(bytes: &[u8], content_type: &str, ext: &str) -> Result<String> {
+fn extract_text_sync(bytes: &[u8], content_type: &str, ext: &str, max_table_rows: usize) -> Result<String> {
     match content_type {
         "application/pdf" => extract_pdf(bytes),
         "application/vnd.openxmlformats-officedocument.wordprocessingml.document" => {
             extract_docx(bytes)
         }
         "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"
-        | "application/vnd.ms-excel" => extract_xlsx(bytes),
+        | "application/vnd.ms-excel" => extract_xlsx(bytes, max_table_rows),
         "text/xml" | "application/xml" => extract_xml(bytes),
-        "text/csv" => extract_csv(bytes),
+        "text/csv" => extract_csv(bytes, max_table_rows),
         "text/plain" | "text/markdown" => extract_plaintext(bytes),
         // Fallback: detect by extension
         _ => match ext {
             "pdf" => extract_pdf(bytes),
             "docx" => extract_docx(bytes),
-            "xlsx" | "xls" => extract_xlsx(bytes),
+            "xlsx" | "xls" => extract_xlsx(bytes, max_table_rows),
             "xml" => extract_xml(bytes),
-            "csv" => extract_csv(bytes),
+            "csv" => extract_csv(bytes, max_table_rows),
             "txt" | "md" => extract_plaintext(bytes),
             _ => Err(anyhow::anyhow!(
                 "Unsupported file type: {content_type} (ext: {ext})"
@@ -118,13 +233,10 @@ fn extract_pdf(bytes: &[u8]) -> Result<String> {
 }
 
 fn extract_pdf_pdftotext(bytes: &[u8]) -> Result<String> {
-    use std::io::Write;
     use std::process::Command;
 
     // Write bytes to a temp file (pdftotext reads from file)
-    let mut tmp = tempfile::NamedTempFile::new().context("Failed to create temp file")?;
-    tmp.write_all(bytes).context("Failed to write PDF to temp file")?;
-    tmp.flush()?;
+    let tmp = write_temp_pdf(bytes)?;
 
     let output = Command::new("pdftotext")
         .arg("-layout")
@@ -141,6 +253,96 @@ fn extract_pdf_pdftotext(bytes: &[u8]) -> Result<String> {
     String::from_utf8(output.stdout).context("pdftotext output is not valid UTF-8")
 }
 
+/// Below this many non-whitespace characters per page a PDF is treated as scanned.
+const MIN_CHARS_PER_PAGE: usize = 16;
+
+/// True when the text extracted from a `pages`-page PDF is too sparse to be
+/// anything but stray marks around page images.
+pub fn looks_like_scanned_pdf(text: &str, pages: usize) -> bool {
+    let chars = text.chars().filter(|c| !c.is_whitespace()).count();
+    chars < MIN_CHARS_PER_PAGE * pages.max(1)
+}
+
+/// Page count from poppler's `pdfinfo`, or `None` if it isn't available.
+pub async fn pdf_page_count(bytes: &[u8]) -> Option<usize> {
+    let tmp = write_temp_pdf(bytes).ok()?;
+    let output = tokio::process::Command::new("pdfinfo")
+        .arg(tmp.path())
+        .output()
+        .await
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find_map(|line| line.strip_prefix("Pages:")?.trim().parse().ok())
+}
+
+/// OCR a scanned PDF: render each page with `pdftoppm` and recognize it with
+/// `tesseract`. Stops after `config.max_pages`; a page that exceeds
+/// `config.page_timeout_secs` fails the whole document.
+pub async fn ocr_pdf(bytes: &[u8], pages: usize, config: &OcrConfig) -> Result<String> {
+    use tokio::process::Command;
+
+    let tmp = write_temp_pdf(bytes)?;
+    let dir = tempfile::tempdir().context("Failed to create OCR temp dir")?;
+    let page_timeout = std::time::Duration::from_secs(config.page_timeout_secs);
+    let pages = pages.min(config.max_pages);
+
+    let mut text = String::new();
+    for page in 1..=pages {
+        let image = dir.path().join(format!("page-{page}"));
+        let page_text = async {
+            let rendered = Command::new("pdftoppm")
+                .args(["-r", "300", "-png", "-singlefile", "-f", &page.to_string(), "-l", &page.to_string()])
+                .arg(tmp.path())
+                .arg(&image)
+                .kill_on_drop(true)
+                .output()
+                .await
+                .context("Failed to run pdftoppm — is poppler-utils installed?")?;
+            if !rendered.status.success() {
+                let stderr = String::from_utf8_lossy(&rendered.stderr);
+                anyhow::bail!("pdftoppm exited with {}: {stderr}", rendered.status);
+            }
+
+            let recognized = Command::new("tesseract")
+                .arg(image.with_extension("png"))
+                .args(["stdout", "-l", &config.language])
+                .kill_on_drop(true)
+                .output()
+                .await
+                .context("Failed to run tesseract — is tesseract-ocr installed?")?;
+            if !recognized.status.success() {
+                let stderr = String::from_utf8_lossy(&recognized.stderr);
+                anyhow::bail!("tesseract exited with {}: {stderr}", recognized.status);
+            }
+            Ok(String::from_utf8_lossy(&recognized.stdout).into_owned())
+        };
+
+        let page_text = tokio::time::timeout(page_timeout, page_text)
+            .await
+            .map_err(|_| anyhow::anyhow!("OCR of page {page} timed out after {}s", page_timeout.as_secs()))?
+            .with_context(|| format!("OCR of page {page} failed"))?;
+        text.push_str(&page_text);
+        text.push('\n');
+        let _ = std::fs::remove_file(image.with_extension("png"));
+    }
+
+    tracing::info!("OCR extracted {} chars from {pages} pages", text.len());
+    Ok(text)
+}
+
+fn write_temp_pdf(bytes: &[u8]) -> Result<tempfile::NamedTempFile> {
+    use std::io::Write;
+
+    let mut tmp = tempfile::NamedTempFile::new().context("Failed to create temp file")?;
+    tmp.write_all(bytes).context("Failed to write PDF to temp file")?;
+    tmp.flush()?;
+    Ok(tmp)
+}
+
 fn extract_docx(bytes: &[u8]) -> Result<String> {
     let doc = docx_rs::read_docx(bytes).map_err(|e| anyhow::anyhow!("Failed to read DOCX: {e}"))?;
 
@@ -192,7 +394,7 @@ fn collect_docx_text(child: &docx_rs::DocumentChild, out: &mut String) {
     }
 }
 
-fn extract_xlsx(bytes: &[u8]) -> Result<String> {
+fn extract_xlsx(bytes: &[u8], max_rows: usize) -> Result<String> {
     use calamine::{Reader, open_workbook_auto_from_rs};
     use std::io::Cursor;
 
@@ -205,13 +407,21 @@ fn extract_xlsx(bytes: &[u8]) -> Result<String> {
 
     for name in sheet_names {
         if let Ok(range) = workbook.worksheet_range(&name) {
-            for row in range.rows() {
-                let cells: Vec<String> = row
-                    .iter()
-                    .map(|cell| format!("{cell}"))
-                    .collect();
-                text.push_str(&cells.join("\t"));
-                text.push('\n');
+            let total = range.height();
+            let rows = range
+                .rows()
+                .take(max_rows)
+                .map(|row| row.iter().map(|cell| cell.to_string()).collect())
+                .collect();
+
+            let table = format_table(rows);
+            if table.is_empty() {
+                continue;
+            }
+            text.push_str(&format!("Sheet: {name}\n"));
+            text.push_str(&table);
+            if total > max_rows {
+                text.push_str(&truncation_notice(max_rows, &format!("sheet '{name}'")));
             }
             text.push('\n');
         }
@@ -256,25 +466,109 @@ fn extract_xml(bytes: &[u8]) -> Result<String> {
     Ok(text)
 }
 
-fn extract_csv(bytes: &[u8]) -> Result<String> {
+fn extract_csv(bytes: &[u8], max_rows: usize) -> Result<String> {
+    let decoded = decode_text(bytes);
     let mut reader = csv::ReaderBuilder::new()
         .flexible(true)
-        .from_reader(bytes);
-
-    let mut text = String::new();
+        .has_headers(false)
+        .from_reader(decoded.as_bytes());
 
+    let mut rows = Vec::new();
+    let mut truncated = false;
     for result in reader.records() {
+        if rows.len() == max_rows {
+            truncated = true;
+            break;
+        }
         let record = result.context("Failed to parse CSV row")?;
-        let row: Vec<&str> = record.iter().collect();
-        text.push_str(&row.join(" "));
-        text.push('\n');
+        rows.push(record.iter().map(str::to_string).collect());
     }
 
+    let mut text = format_table(rows);
+    if truncated {
+        text.push_str(&truncation_notice(max_rows, "file"));
+    }
     Ok(text)
 }
 
+/// Render table rows as one line each, labelling every cell with its column
+/// header ("region: east; revenue: 42") so a row still makes sense when it is
+/// retrieved on its own. The first row is used as the header when it looks like
+/// one. Empty cells are left out, which also drops empty rows and columns.
+fn format_table(mut rows: Vec<Vec<String>>) -> String {
+    for row in &mut rows {
+        for cell in row.iter_mut() {
+            *cell = cell.trim().to_string();
+        }
+    }
+    rows.retain(|row| row.iter().any(|cell| !cell.is_empty()));
+    if rows.is_empty() {
+        return String::new();
+    }
+
+    let header = if rows.len() > 1 && looks_like_header(&rows[0]) {
+        Some(rows.remove(0))
+    } else {
+        None
+    };
+
+    let mut text = String::new();
+    for row in &rows {
+        let cells: Vec<String> = row
+            .iter()
+            .enumerate()
+            .filter(|(_, cell)| !cell.is_empty())
+            .map(|(i, cell)| {
+                match header.as_ref().and_then(|h| h.get(i)).filter(|h| !h.is_empty()) {
+                    Some(name) => format!("{name}: {cell}"),
+                    None => cell.clone(),
+                }
+            })
+            .collect();
+        text.push_str(&cells.join("; "));
+        text.push('\n');
+    }
+    text
+}
+
+/// A header row has distinct, non-numeric labels.
+fn looks_like_header(row: &[String]) -> bool {
+    let labels: Vec<&str> = row.iter().map(String::as_str).filter(|c| !c.is_empty()).collect();
+    let distinct: std::collections::HashSet<&str> = labels.iter().copied().collect();
+    !labels.is_empty()
+        && distinct.len() == labels.len()
+        && labels.iter().all(|label| label.parse::<f64>().is_err())
+}
+
+/// Appended to extracted tables that hit the row limit, so users can tell from
+/// the document text that not everything was indexed.
+fn truncation_notice(max_rows: usize, what: &str) -> String {
+    tracing::warn!("extract_text: {what} truncated to {max_rows} rows");
+    format!("[Truncated: only the first {max_rows} rows of this {what} were extracted]\n")
+}
+
 fn extract_plaintext(bytes: &[u8]) -> Result<String> {
-    String::from_utf8(bytes.to_vec()).context("File is not valid UTF-8 text")
+    Ok(decode_text(bytes))
+}
+
+/// Decode text to UTF-8, dropping any BOM. A BOM decides the encoding when
+/// present; otherwise valid UTF-8 is used as is and anything else is read as
+/// Windows-1252, the usual encoding of spreadsheet and legacy text exports and
+/// a superset of Latin-1. Bytes that don't decode become U+FFFD.
+fn decode_text(bytes: &[u8]) -> String {
+    let (encoding, body) = match encoding_rs::Encoding::for_bom(bytes) {
+        Some((encoding, bom_len)) => (encoding, &bytes[bom_len..]),
+        None if std::str::from_utf8(bytes).is_ok() => (encoding_rs::UTF_8, bytes),
+        None => (encoding_rs::WINDOWS_1252, bytes),
+    };
+
+    let (text, had_errors) = encoding.decode_without_bom_handling(body);
+    if had_errors {
+        tracing::warn!("extract_text: decoded as {} with invalid bytes replaced", encoding.name());
+    } else {
+        tracing::info!("extract_text: decoded as {}", encoding.name());
+    }
+    text.into_owned()
 }
 
 fn extension_from_filename(filename: &str) -> Option<String> {
@@ -284,38 +578,39 @@ fn extension_from_filename(filename: &str) -> Option<String> {
         .map(|e| e.to_lowercase())
 }
 
-/// Split text into overlapping word chunks for embedding.
-pub fn chunk_text(text: &str, chunk_size: usize, overlap: usize) -> Vec<String> {
-    if text.is_empty() {
-        return Vec::new();
-    }
-
-    let words: Vec<&str> = text.split_whitespace().collect();
-    if words.is_empty() {
-        return Vec::new();
-    }
-
+/// Split row-per-line table text (see [`format_table`]) into chunks of whole
+/// rows, about `chunk_size` words each. A row is never split, so one longer
+/// than `chunk_size` becomes a chunk of its own.
+pub fn chunk_rows(text: &str, chunk_size: usize) -> Vec<String> {
     let mut chunks = Vec::new();
-    let mut start = 0;
-
-    while start < words.len() {
-        let end = (start + chunk_size).min(words.len());
-        let chunk = words[start..end].join(" ");
-
-        if !chunk.trim().is_empty() {
-            chunks.push(chunk);
+    let mut current = String::new();
+    let mut words = 0;
+
+    for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        let line_words = line.split_whitespace().count();
+        if words > 0 && words + line_words > chunk_size {
+            chunks.push(std::mem::take(&mut current));
+            words = 0;
         }
-
-        if end >= words.len() {
-            break;
+        if !current.is_empty() {
+            current.push('\n');
         }
-
-        start += chunk_size - overlap;
+        current.push_str(line);
+        words += line_words;
+    }
+    if !current.is_empty() {
+        chunks.push(current);
     }
 
     chunks
 }
 
+/// Whether extracted text of this type is one table row per line and should be
+/// chunked with [`chunk_rows`].
+pub fn is_tabular(content_type: &str) -> bool {
+    matches!(content_type, "text/csv" | XLSX | XLS)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -328,27 +623,116 @@ mod tests {
         assert!(!is_supported("application/octet-stream", "image.png"));
     }
 
-    #[tokio::test]
-    async fn test_extract_plaintext() {
-        let bytes = b"Hello world\nThis is a test";
-        let result = extract_text(bytes, "text/plain", "test.txt").await.unwrap();
-        assert_eq!(result, "Hello world\nThis is a test");
+    #[test]
+    fn test_sniff_content_type() {
+        // Content wins over the declared type
+        assert_eq!(sniff_content_type(b"%PDF-1.7\n...", "text/plain", "notes.txt"), PDF);
+        assert_eq!(
+            sniff_content_type(b"PK\x03\x04....[Content_Types].xml....word/document.xml", "application/octet-stream", "report"),
+            DOCX
+        );
+        assert_eq!(
+            sniff_content_type(b"PK\x03\x04....xl/workbook.xml", "application/pdf", "sheet.pdf"),
+            XLSX
+        );
+        assert_eq!(
+            sniff_content_type(b"MZ\x90\x00\x03\x00", "application/pdf", "invoice.pdf"),
+            "application/x-msdownload"
+        );
+        assert_eq!(sniff_content_type(b"\x00\x01\x02", "text/plain", "a.txt"), "application/octet-stream");
+
+        // Declared type and extension pick between text types
+        assert_eq!(sniff_content_type(b"a,b\n1,2\n", "text/csv", "data.txt"), "text/csv");
+        assert_eq!(sniff_content_type(b"# Title\n", "application/octet-stream", "README.md"), "text/markdown");
+        assert_eq!(sniff_content_type(b"<?xml version=\"1.0\"?><a/>", "application/octet-stream", "feed"), "application/xml");
+        assert_eq!(sniff_content_type(b"hello", "application/pdf", "fake.pdf"), "text/plain");
+
+        // A multi-byte character cut off by the sniff window is still text
+        assert_eq!(sniff_content_type(&"caf\u{e9}".as_bytes()[..4], "text/plain", "a.txt"), "text/plain");
     }
 
     #[test]
-    fn test_chunk_text_basic() {
-        let text = (0..100).map(|i| format!("word{i}")).collect::<Vec<_>>().join(" ");
-        let chunks = chunk_text(&text, 30, 5);
-        assert!(!chunks.is_empty());
-        for chunk in &chunks {
-            let word_count = chunk.split_whitespace().count();
-            assert!(word_count <= 30);
-        }
+    fn test_looks_like_scanned_pdf() {
+        let text_pdf = "Quarterly report\n\nRevenue grew 12% year over year, driven by new customers.\n\x0c\
+                        Outlook\n\nWe expect growth to continue through the next two quarters.\n\x0c";
+        assert!(!looks_like_scanned_pdf(text_pdf, 2));
+
+        // pdftotext on a scanned PDF yields only form feeds and whitespace
+        assert!(looks_like_scanned_pdf("\x0c\n\x0c\n\x0c", 3));
+        assert!(looks_like_scanned_pdf("", 1));
+        // A page number per page is still scanned
+        assert!(looks_like_scanned_pdf("1\x0c2\x0c3\x0c", 3));
+        // Unknown page count counts as one page
+        assert!(!looks_like_scanned_pdf(text_pdf, 0));
+    }
+
+    #[tokio::test]
+    async fn test_extract_routes_by_content() {
+        let bytes = b"Plain text uploaded as a PDF";
+        let result = extract_text(bytes, "application/pdf", "mislabeled.pdf", 1000).await.unwrap();
+        assert_eq!(result, "Plain text uploaded as a PDF");
+    }
+
+    #[tokio::test]
+    async fn test_extract_windows_1252() {
+        // "Café,Crème brûlée" as exported by Excel on Windows
+        let bytes = b"Dish,Price\nCaf\xe9,3\nCr\xe8me br\xfbl\xe9e,7\n";
+        assert_eq!(sniff_content_type(bytes, "text/csv", "menu.csv"), "text/csv");
+
+        let csv = extract_text(bytes, "text/csv", "menu.csv", 1000).await.unwrap();
+        assert_eq!(csv, "Dish: Caf\u{e9}; Price: 3\nDish: Cr\u{e8}me br\u{fb}l\u{e9}e; Price: 7\n");
+
+        let text = extract_text(b"na\xefve r\xe9sum\xe9", "text/plain", "cv.txt", 1000).await.unwrap();
+        assert_eq!(text, "na\u{ef}ve r\u{e9}sum\u{e9}");
+    }
+
+    #[tokio::test]
+    async fn test_extract_csv_with_headers() {
+        let bytes = b"id,customer,,region\n42,acme,,east\n,,,\n43,globex,,\n";
+        let text = extract_text(bytes, "text/csv", "accounts.csv", 1000).await.unwrap();
+        assert_eq!(text, "id: 42; customer: acme; region: east\nid: 43; customer: globex\n");
+    }
+
+    #[tokio::test]
+    async fn test_extract_csv_without_headers() {
+        let bytes = b"42,acme,east\n43,globex,west\n";
+        let text = extract_text(bytes, "text/csv", "accounts.csv", 1000).await.unwrap();
+        assert_eq!(text, "42; acme; east\n43; globex; west\n");
+    }
+
+    #[tokio::test]
+    async fn test_extract_csv_truncates() {
+        let bytes: String = std::iter::once("n\n".to_string())
+            .chain((0..10).map(|i| format!("{i}\n")))
+            .collect();
+        let text = extract_text(bytes.as_bytes(), "text/csv", "big.csv", 5).await.unwrap();
+        assert_eq!(text.lines().filter(|l| l.starts_with("n: ")).count(), 4);
+        assert!(text.ends_with("[Truncated: only the first 5 rows of this file were extracted]\n"));
     }
 
     #[test]
-    fn test_chunk_text_empty() {
-        assert!(chunk_text("", 30, 5).is_empty());
-        assert!(chunk_text("   ", 30, 5).is_empty());
+    fn test_chunk_rows_keeps_rows_whole() {
+        let text = "a: 1; b: 2\nc: 3; d: 4\n\ne: 5; f: 6\n";
+        // Each row is 4 words; two rows fit in a chunk of 8
+        assert_eq!(chunk_rows(text, 8), vec!["a: 1; b: 2\nc: 3; d: 4", "e: 5; f: 6"]);
+        // A row longer than the chunk size is kept intact
+        assert_eq!(chunk_rows(text, 3).len(), 3);
+        assert!(chunk_rows("", 8).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_extract_strips_bom() {
+        let utf8 = extract_text(b"\xEF\xBB\xBFhello", "text/plain", "a.txt", 1000).await.unwrap();
+        assert_eq!(utf8, "hello");
+
+        let utf16 = extract_text(b"\xFF\xFEh\x00i\x00", "text/plain", "a.txt", 1000).await.unwrap();
+        assert_eq!(utf16, "hi");
+    }
+
+    #[tokio::test]
+    async fn test_extract_plaintext() {
+        let bytes = b"Hello world\nThis is a test";
+        let result = extract_text(bytes, "text/plain", "test.txt", 1000).await.unwrap();
+        assert_eq!(result, "Hello world\nThis is a test");
     }
 }
@@ -0,0 +1,106 @@
+use anyhow::Result;
+
+use crate::services::llm_provider::{ChatRequest, CompletionBackend, ModelRef};
+
+/// Title given to new conversations until one is generated or set.
+pub const DEFAULT_TITLE: &str = "New Chat";
+
+/// Longest title kept from the model; anything longer is cut at a word boundary.
+const MAX_TITLE_CHARS: usize = 80;
+
+/// How much of each message the title prompt includes.
+const MAX_EXCERPT_CHARS: usize = 1000;
+
+const TITLE_PREAMBLE: &str = "You write short titles for chat conversations. \
+    Reply with a 4 to 8 word title that summarizes the topic of the exchange, \
+    in the language of the user's message. Reply with the title only: \
+    no quotes, no trailing punctuation, no prefix like \"Title:\".";
+
+/// The title used until a generated one arrives, and if generation fails:
+/// the start of the user's first message.
+pub fn fallback_title(message: &str) -> String {
+    message.chars().take(50).collect()
+}
+
+/// Ask `model` for a short title summarizing the first exchange of a conversation.
+pub async fn generate_title(
+    backend: &dyn CompletionBackend,
+    model: ModelRef<'_>,
+    user_message: &str,
+    assistant_reply: &str,
+) -> Result<String> {
+    let model = backend.chat_model(model)?;
+
+    let excerpt = |text: &str| text.chars().take(MAX_EXCERPT_CHARS).collect::<String>();
+    let prompt = format!(
+        "User: {}\n\nAssistant: {}",
+        excerpt(user_message),
+        excerpt(assistant_reply)
+    );
+
+    let raw = model
+        .chat(ChatRequest {
+            preamble: TITLE_PREAMBLE.to_string(),
+            history: Vec::new(),
+            prompt,
+        })
+        .await
+        .map_err(|e| anyhow::anyhow!("LLM error: {e}"))?;
+    clean_title(&raw.text).ok_or_else(|| anyhow::anyhow!("Model returned an empty title"))
+}
+
+/// Strip the quoting, prefixes and punctuation models add despite instructions.
+fn clean_title(raw: &str) -> Option<String> {
+    let line = raw.lines().map(str::trim).find(|l| !l.is_empty())?;
+    let line = ["Title:", "title:", "TITLE:"]
+        .iter()
+        .find_map(|prefix| line.strip_prefix(prefix))
+        .unwrap_or(line);
+    let title = line
+        .trim()
+        .trim_matches(|c: char| matches!(c, '"' | '\'' | '*' | '#' | '`' | '“' | '”'))
+        .trim_end_matches(['.', '!', ':'])
+        .trim();
+    if title.is_empty() {
+        return None;
+    }
+
+    if title.chars().count() <= MAX_TITLE_CHARS {
+        return Some(title.to_string());
+    }
+    let cut: String = title.chars().take(MAX_TITLE_CHARS).collect();
+    let cut = cut.rsplit_once(' ').map_or(cut.as_str(), |(head, _)| head);
+    Some(cut.trim_end().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clean_title() {
+        assert_eq!(
+            clean_title("\"Configuring SSO for the Admin Panel.\"").as_deref(),
+            Some("Configuring SSO for the Admin Panel")
+        );
+        assert_eq!(
+            clean_title("Title: **Refund policy for annual plans**\n\nHope this helps!").as_deref(),
+            Some("Refund policy for annual plans")
+        );
+        assert_eq!(clean_title("  \n \"\" "), None);
+
+        let long = "word ".repeat(40);
+        let title = clean_title(&long).unwrap();
+        assert!(title.chars().count() <= MAX_TITLE_CHARS);
+        assert!(title.ends_with("word"));
+    }
+
+    #[test]
+    fn test_fallback_title() {
+        let message = "hey quick question about the thing we discussed yesterday";
+        assert_eq!(
+            fallback_title(message),
+            "hey quick question about the thing we discussed ye"
+        );
+    }
+}
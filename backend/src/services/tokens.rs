@@ -0,0 +1,89 @@
+use serde::Serialize;
+use tiktoken_rs::tokenizer::{Tokenizer, get_tokenizer};
+use tiktoken_rs::{CoreBPE, cl100k_base_singleton, o200k_base_singleton};
+
+use crate::services::llm_provider;
+
+/// How a token count was arrived at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum EstimationMethod {
+    /// The model's own tokenizer: exact for OpenAI models.
+    Tiktoken,
+    /// Four characters to a token, close enough for other providers.
+    Heuristic,
+}
+
+/// Tokens added to each chat message by the role and separators around it.
+pub const MESSAGE_OVERHEAD_TOKENS: usize = 4;
+
+/// Estimate how many tokens `text` is for `provider`'s `model`.
+pub fn estimate(provider: &str, model: &str, text: &str) -> (usize, EstimationMethod) {
+    match openai_tokenizer(provider, model) {
+        Some(bpe) => (bpe.encode_ordinary(text).len(), EstimationMethod::Tiktoken),
+        None => (text.chars().count().div_ceil(4), EstimationMethod::Heuristic),
+    }
+}
+
+/// Like [`estimate`], the count alone.
+pub fn estimate_tokens(provider: &str, model: &str, text: &str) -> usize {
+    estimate(provider, model, text).0
+}
+
+/// Token counting for one model.
+#[derive(Debug, Clone, Copy)]
+pub struct ModelTokens<'a> {
+    pub provider: &'a str,
+    pub model: &'a str,
+}
+
+impl ModelTokens<'_> {
+    pub fn count(&self, text: &str) -> usize {
+        estimate_tokens(self.provider, self.model, text)
+    }
+
+    pub fn context_window(&self) -> usize {
+        llm_provider::context_window(self.provider, self.model) as usize
+    }
+}
+
+/// The tokenizer of an OpenAI model, whether called directly or through
+/// OpenRouter. Models tiktoken doesn't know yet are taken to use the newest
+/// encoding.
+fn openai_tokenizer(provider: &str, model: &str) -> Option<&'static CoreBPE> {
+    let model = match provider.to_ascii_lowercase().as_str() {
+        "openai" => model,
+        "openrouter" => model.strip_prefix("openai/")?,
+        _ => return None,
+    };
+    Some(match get_tokenizer(model) {
+        Some(Tokenizer::Cl100kBase) => cl100k_base_singleton(),
+        _ => o200k_base_singleton(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_openai_models_are_tokenized() {
+        let text = "Refunds are processed within five business days.";
+        let (tokens, method) = estimate("openai", "gpt-4o", text);
+        assert_eq!(method, EstimationMethod::Tiktoken);
+        assert!(tokens > 5 && tokens < 15, "{tokens} tokens");
+
+        assert_eq!(estimate("openrouter", "openai/gpt-4o", text), (tokens, EstimationMethod::Tiktoken));
+        assert_eq!(estimate("openai", "gpt-3.5-turbo", text).1, EstimationMethod::Tiktoken);
+        assert_eq!(estimate("openai", "gpt-4o", ""), (0, EstimationMethod::Tiktoken));
+    }
+
+    #[test]
+    fn test_other_models_use_the_heuristic() {
+        assert_eq!(estimate("anthropic", "claude-sonnet-4-20250514", "abcdefghi"), (3, EstimationMethod::Heuristic));
+        assert_eq!(estimate("openrouter", "anthropic/claude-sonnet-4", "abcd"), (1, EstimationMethod::Heuristic));
+        // Characters, not bytes
+        assert_eq!(estimate_tokens("ollama", "llama3.1:8b", "ééééé"), 2);
+    }
+}
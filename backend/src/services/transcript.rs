@@ -0,0 +1,53 @@
+use crate::db::models::conversation::Message;
+use crate::db::timestamp;
+
+/// A conversation as Markdown: the title as a heading, then every message
+/// under its author and time. Deleted messages are left out.
+pub fn render_markdown(title: &str, messages: &[Message], assistant_name: &str) -> String {
+    let mut out = format!("# {}\n", title.trim());
+    for message in messages.iter().filter(|m| m.deleted_at.is_none()) {
+        let author = match message.role.as_str() {
+            "user" => "You",
+            "assistant" => assistant_name,
+            other => other,
+        };
+        out.push_str(&format!("\n**{author}** · {}\n\n", timestamp::format(&message.created_at)));
+        out.push_str(message.content.trim());
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::models::conversation::MessageGeneration;
+    use chrono::{TimeZone, Utc};
+
+    fn message(role: &str, content: &str, deleted: bool) -> Message {
+        Message {
+            id: "m".to_string(),
+            conversation_id: "c".to_string(),
+            role: role.to_string(),
+            content: content.to_string(),
+            created_at: Utc.with_ymd_and_hms(2026, 10, 17, 9, 30, 0).unwrap(),
+            generation: MessageGeneration::default(),
+            deleted_at: deleted.then(|| Utc.with_ymd_and_hms(2026, 10, 17, 9, 31, 0).unwrap()),
+        }
+    }
+
+    #[test]
+    fn test_render_markdown_labels_authors_and_skips_deleted() {
+        let messages = [
+            message("user", "Opening hours?", false),
+            message("user", "", true),
+            message("assistant", " 9 to 5. \n", false),
+        ];
+        assert_eq!(
+            render_markdown("Widget chat", &messages, "Acme Help"),
+            "# Widget chat\n\n\
+             **You** · 2026-10-17T09:30:00.000Z\n\nOpening hours?\n\n\
+             **Acme Help** · 2026-10-17T09:30:00.000Z\n\n9 to 5.\n"
+        );
+    }
+}
@@ -0,0 +1,63 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+
+use crate::db::models::user::{User, UserRepository};
+
+/// How long a user looked up by the auth middleware is reused. Role changes and
+/// deletions made through the API invalidate it at once; anything else, e.g. a
+/// direct database edit, takes effect within this bound.
+pub const USER_CACHE_TTL: Duration = Duration::from_secs(30);
+
+struct CacheEntry {
+    fetched_at: Instant,
+    user: User,
+}
+
+/// In-memory cache of users by ID, so authenticated requests don't each load
+/// their user. Unknown IDs aren't cached.
+pub struct UserCache {
+    repo: UserRepository,
+    ttl: Duration,
+    entries: RwLock<HashMap<String, CacheEntry>>,
+}
+
+impl UserCache {
+    pub fn new(repo: UserRepository, ttl: Duration) -> Self {
+        Self {
+            repo,
+            ttl,
+            entries: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// The user with `id`, from the cache while it is fresh.
+    pub async fn find_by_id(&self, id: &str) -> Result<Option<User>> {
+        if let Some(entry) = self.entries.read().await.get(id)
+            && entry.fetched_at.elapsed() < self.ttl
+        {
+            return Ok(Some(entry.user.clone()));
+        }
+
+        let Some(user) = self.repo.find_by_id(id).await? else {
+            self.entries.write().await.remove(id);
+            return Ok(None);
+        };
+        let mut entries = self.entries.write().await;
+        entries.retain(|_, e| e.fetched_at.elapsed() < self.ttl);
+        entries.insert(
+            id.to_string(),
+            CacheEntry {
+                fetched_at: Instant::now(),
+                user: user.clone(),
+            },
+        );
+        Ok(Some(user))
+    }
+
+    /// Drop the cached copy of the user with `id` after it changed or was deleted.
+    pub async fn invalidate(&self, id: &str) {
+        self.entries.write().await.remove(id);
+    }
+}
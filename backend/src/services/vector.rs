@@ -1,74 +1,349 @@
 use anyhow::{Context, Result};
 use qdrant_client::qdrant::{
-    CreateCollectionBuilder, DeletePointsBuilder, Distance, PointStruct, QueryPointsBuilder,
-    UpsertPointsBuilder, VectorParamsBuilder,
+    Condition, CreateCollectionBuilder, DeletePointsBuilder, Distance, Filter, PointStruct,
+    PointsIdsList, QueryPointsBuilder, SetPayloadPointsBuilder, UpsertPointsBuilder,
+    VectorParamsBuilder,
 };
-use qdrant_client::Qdrant;
+use qdrant_client::qdrant::Value;
+use qdrant_client::{Qdrant, QdrantError};
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+use tokio::sync::RwLock;
 
 use crate::config::QdrantConfig;
+use crate::services::llm_provider;
+use crate::services::retry::RetryPolicy;
 
+/// How long [`VectorService::check_health`] waits for Qdrant to answer.
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Attempts per upsert batch while Qdrant is unavailable.
+const UPSERT_POLICY: RetryPolicy = RetryPolicy {
+    max_attempts: 4,
+    base_delay: Duration::from_millis(500),
+    max_delay: Duration::from_secs(10),
+};
+
+/// A failed Qdrant request, split by whether trying again later can help.
+#[derive(Debug, thiserror::Error)]
+pub enum VectorError {
+    /// Qdrant couldn't be reached, timed out or failed internally.
+    #[error("{0} (vector store unavailable)")]
+    Unavailable(String),
+    /// Qdrant rejected the request, e.g. for a missing collection.
+    #[error("{0}")]
+    BadRequest(String),
+}
+
+impl VectorError {
+    fn from_qdrant(context: &str, error: QdrantError) -> Self {
+        use tonic::Code;
+
+        let unavailable = match &error {
+            QdrantError::ResponseError { status } => matches!(
+                status.code(),
+                Code::Unavailable
+                    | Code::DeadlineExceeded
+                    | Code::Cancelled
+                    | Code::Aborted
+                    | Code::Internal
+                    | Code::Unknown
+            ),
+            QdrantError::ResourceExhaustedError { .. } | QdrantError::Io(_) => true,
+            _ => false,
+        };
+        let message = format!("{context}: {error}");
+        if unavailable {
+            VectorError::Unavailable(message)
+        } else {
+            VectorError::BadRequest(message)
+        }
+    }
+
+    pub fn is_unavailable(&self) -> bool {
+        matches!(self, VectorError::Unavailable(_))
+    }
+}
+
+/// Fail when the embedding model's dimension (`expected`), the configured
+/// `vector_size` and the existing collection's dimension (`actual`) disagree.
+/// Unknown values are skipped, so an unknown model or a new collection only
+/// compares the other two.
+fn check_vector_size(
+    model: &str,
+    expected: Option<u64>,
+    configured: u64,
+    collection: &str,
+    actual: Option<u64>,
+) -> Result<()> {
+    if expected.is_none() {
+        tracing::warn!(
+            "Dimension of embedding model '{model}' is unknown; add it to qdrant.embedding_dimensions to validate qdrant.vector_size"
+        );
+    }
+    let mismatched = expected.is_some_and(|e| e != configured) || actual.is_some_and(|a| a != configured);
+    if !mismatched {
+        return Ok(());
+    }
+
+    let describe = |dimension: Option<u64>| dimension.map_or("an unknown number of".to_string(), |d| d.to_string());
+    let collection_size = actual.map_or("does not exist yet".to_string(), |a| format!("holds {a}-dimensional vectors"));
+    let fix = match (expected, actual) {
+        (Some(e), Some(a)) if a != e => format!(
+            "Set qdrant.vector_size to {e} and either point qdrant.collection_name at a new collection or delete '{collection}' and reprocess your documents"
+        ),
+        (Some(e), _) => format!("Set qdrant.vector_size (APP__QDRANT__VECTOR_SIZE) to {e}"),
+        (None, Some(a)) => format!(
+            "Set qdrant.vector_size to {a} to match the collection, or add '{model}' to qdrant.embedding_dimensions"
+        ),
+        (None, None) => unreachable!("a mismatch needs a known dimension"),
+    };
+
+    anyhow::bail!(
+        "Vector size mismatch: embedding model '{model}' produces {} dimensions, qdrant.vector_size is {configured}, \
+         and collection '{collection}' {collection_size}. {fix}. Set qdrant.skip_vector_validation or pass \
+         --skip-vector-validation to start anyway.",
+        describe(expected)
+    )
+}
+
+/// Attach the operation that failed to a Qdrant error and classify it.
+trait VectorContext<T> {
+    fn vector_context(self, context: &str) -> Result<T, VectorError>;
+}
+
+impl<T> VectorContext<T> for Result<T, QdrantError> {
+    fn vector_context(self, context: &str) -> Result<T, VectorError> {
+        self.map_err(|e| VectorError::from_qdrant(context, e))
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct SearchResult {
     pub point_id: String,
+    /// Ranking score: cosine similarity for vector search, RRF score for hybrid search.
     pub score: f32,
+    /// Raw cosine similarity, when the chunk was returned by the vector query.
+    pub vector_score: Option<f32>,
     pub content: String,
 }
 
+/// Labels of the document a chunk came from, stored in each point's payload so
+/// searches can be narrowed by them.
+#[derive(Debug, Clone, Default)]
+pub struct ChunkLabels {
+    pub tags: Vec<String>,
+    pub collection_id: Option<String>,
+}
+
+impl ChunkLabels {
+    fn payload(&self) -> HashMap<String, Value> {
+        [
+            ("tags".to_string(), Value::from(self.tags.clone())),
+            // An unset collection is stored as null so relabelling clears it
+            ("collection_id".to_string(), Value::from(serde_json::json!(self.collection_id))),
+        ]
+        .into()
+    }
+}
+
+/// Narrows a search. The default matches every chunk.
+#[derive(Debug, Clone, Default)]
+pub struct SearchFilter {
+    /// Chunks carrying any of these tags; empty matches all.
+    pub tags: Vec<String>,
+    /// Only chunks of documents in this collection.
+    pub collection_id: Option<String>,
+}
+
+impl SearchFilter {
+    fn to_qdrant(&self) -> Option<Filter> {
+        let mut conditions = Vec::new();
+        if !self.tags.is_empty() {
+            conditions.push(Condition::matches("tags", self.tags.clone()));
+        }
+        if let Some(ref collection_id) = self.collection_id {
+            conditions.push(Condition::matches("collection_id", collection_id.clone()));
+        }
+        (!conditions.is_empty()).then(|| Filter::must(conditions))
+    }
+}
+
+/// Manages one Qdrant collection per embedding model. The configured default
+/// embedding model keeps the base collection name so existing indexes stay in
+/// place; any other model gets its own `{base}_{model_slug}` collection.
 pub struct VectorService {
     client: Qdrant,
     collection_name: String,
+    default_embedding_model: String,
     vector_size: u64,
+    upsert_batch_size: usize,
+    max_payload_content_chars: usize,
+    upsert_policy: RetryPolicy,
+    known_collections: RwLock<HashSet<String>>,
 }
 
 impl VectorService {
-    pub async fn new(config: &QdrantConfig) -> Result<Self> {
+    /// Connect and create the default collection, first checking that
+    /// `vector_size` fits the default embedding model and any existing collection
+    /// unless `skip_vector_validation` is set.
+    pub async fn new(config: &QdrantConfig, default_embedding_model: &str) -> Result<Self> {
+        let service = Self::connect_lazy(config, default_embedding_model)?;
+        if config.skip_vector_validation {
+            tracing::warn!("Vector size validation skipped; a wrong qdrant.vector_size will fail uploads");
+        } else {
+            let expected = llm_provider::embedding_dimension(default_embedding_model, &config.embedding_dimensions);
+            let actual = service.collection_vector_size(&service.collection_name).await?;
+            check_vector_size(
+                default_embedding_model,
+                expected,
+                service.vector_size,
+                &service.collection_name,
+                actual,
+            )?;
+        }
+        service
+            .ensure_collection(&service.collection_name, service.vector_size)
+            .await?;
+
+        Ok(service)
+    }
+
+    /// Build the service without contacting Qdrant; the default collection is
+    /// not created, and an unreachable server only surfaces on first use.
+    pub fn connect_lazy(config: &QdrantConfig, default_embedding_model: &str) -> Result<Self> {
         let client = Qdrant::from_url(&config.url)
             .build()
             .context("Failed to connect to Qdrant")?;
 
-        let service = Self {
+        Ok(Self {
             client,
             collection_name: config.collection_name.clone(),
+            default_embedding_model: default_embedding_model.to_string(),
             vector_size: config.vector_size,
-        };
+            upsert_batch_size: config.upsert_batch_size.max(1),
+            max_payload_content_chars: config.max_payload_content_chars,
+            upsert_policy: UPSERT_POLICY,
+            known_collections: RwLock::new(HashSet::new()),
+        })
+    }
+
+    /// Use a different retry policy for upserts, e.g. a fast one in tests.
+    pub fn with_upsert_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.upsert_policy = policy;
+        self
+    }
 
-        service.ensure_collection().await?;
+    /// Collection used for the configured default embedding model.
+    pub fn default_collection(&self) -> &str {
+        &self.collection_name
+    }
 
-        Ok(service)
+    pub fn default_embedding_model(&self) -> &str {
+        &self.default_embedding_model
+    }
+
+    /// Collection name holding vectors produced by `model`.
+    pub fn collection_for_model(&self, model: &str) -> String {
+        if model.is_empty() || model == self.default_embedding_model {
+            self.collection_name.clone()
+        } else {
+            format!("{}_{}", self.collection_name, model_slug(model))
+        }
+    }
+
+    /// Ensure the collection for `model` exists, creating it with `vector_size`
+    /// dimensions if needed. Returns the collection name.
+    pub async fn ensure_model_collection(&self, model: &str, vector_size: u64) -> Result<String, VectorError> {
+        let name = self.collection_for_model(model);
+        self.ensure_collection(&name, vector_size).await?;
+        Ok(name)
     }
 
-    async fn ensure_collection(&self) -> Result<()> {
+    /// Check that Qdrant answers, bypassing the cache of known collections. Meant
+    /// to be cheap enough to call before accepting work that needs the store.
+    pub async fn check_health(&self) -> Result<(), VectorError> {
+        match tokio::time::timeout(HEALTH_CHECK_TIMEOUT, self.client.collection_exists(&self.collection_name)).await {
+            Ok(result) => result.map(|_| ()).vector_context("Failed to reach Qdrant"),
+            Err(_) => Err(VectorError::Unavailable(format!(
+                "Qdrant did not answer within {} ms",
+                HEALTH_CHECK_TIMEOUT.as_millis()
+            ))),
+        }
+    }
+
+    pub async fn collection_exists(&self, name: &str) -> Result<bool, VectorError> {
+        if self.known_collections.read().await.contains(name) {
+            return Ok(true);
+        }
+
         let exists = self
             .client
-            .collection_exists(&self.collection_name)
+            .collection_exists(name)
             .await
-            .context("Failed to check Qdrant collection")?;
+            .vector_context("Failed to check Qdrant collection")?;
+
+        if exists {
+            self.known_collections.write().await.insert(name.to_string());
+        }
+
+        Ok(exists)
+    }
+
+    /// Dimension of the vectors stored in collection `name`, or `None` when it
+    /// doesn't exist. Collections with named vectors report the first one.
+    pub async fn collection_vector_size(&self, name: &str) -> Result<Option<u64>, VectorError> {
+        use qdrant_client::qdrant::vectors_config::Config;
 
-        if !exists {
+        if !self.collection_exists(name).await? {
+            return Ok(None);
+        }
+        let info = self
+            .client
+            .collection_info(name)
+            .await
+            .vector_context("Failed to read Qdrant collection info")?;
+
+        let vectors = info
+            .result
+            .and_then(|r| r.config)
+            .and_then(|c| c.params)
+            .and_then(|p| p.vectors_config)
+            .and_then(|v| v.config);
+        Ok(match vectors {
+            Some(Config::Params(params)) => Some(params.size),
+            Some(Config::ParamsMap(map)) => map.map.values().next().map(|p| p.size),
+            None => None,
+        })
+    }
+
+    async fn ensure_collection(&self, name: &str, vector_size: u64) -> Result<(), VectorError> {
+        if !self.collection_exists(name).await? {
             self.client
                 .create_collection(
-                    CreateCollectionBuilder::new(&self.collection_name)
-                        .vectors_config(VectorParamsBuilder::new(
-                            self.vector_size,
-                            Distance::Cosine,
-                        )),
+                    CreateCollectionBuilder::new(name)
+                        .vectors_config(VectorParamsBuilder::new(vector_size, Distance::Cosine)),
                 )
                 .await
-                .context("Failed to create Qdrant collection")?;
+                .vector_context("Failed to create Qdrant collection")?;
 
-            tracing::info!(
-                "Created Qdrant collection '{}' (vector_size={})",
-                self.collection_name,
-                self.vector_size
-            );
+            tracing::info!("Created Qdrant collection '{name}' (vector_size={vector_size})");
+
+            self.known_collections.write().await.insert(name.to_string());
         }
 
         Ok(())
     }
 
+    /// Store chunks with their content and the labels of the document they came
+    /// from, `upsert_batch_size` points per request. Each batch is written
+    /// durably before the next is sent and retried while Qdrant is unavailable.
     pub async fn upsert_chunks(
         &self,
+        collection: &str,
         chunks: Vec<(String, Vec<f64>, String)>, // (point_id, embedding, content)
-    ) -> Result<()> {
+        labels: &ChunkLabels,
+    ) -> Result<(), VectorError> {
         if chunks.is_empty() {
             return Ok(());
         }
@@ -76,11 +351,9 @@ impl VectorService {
         let points: Vec<PointStruct> = chunks
             .into_iter()
             .map(|(id, embedding, content)| {
-                let payload: std::collections::HashMap<String, qdrant_client::qdrant::Value> = [(
-                    "content".to_string(),
-                    qdrant_client::qdrant::Value::from(content),
-                )]
-                .into();
+                let mut payload = labels.payload();
+                let content = truncate_chars(content, self.max_payload_content_chars);
+                payload.insert("content".to_string(), Value::from(content));
 
                 // Qdrant expects f32 vectors
                 let embedding_f32: Vec<f32> = embedding.iter().map(|&v| v as f32).collect();
@@ -89,30 +362,97 @@ impl VectorService {
             })
             .collect();
 
+        let total = points.len();
+        let batches = total.div_ceil(self.upsert_batch_size);
+        for (i, batch) in points.chunks(self.upsert_batch_size).enumerate() {
+            self.upsert_batch(collection, batch).await?;
+            tracing::info!(
+                "Upserted batch {}/{batches} ({} of {total} points) into '{collection}'",
+                i + 1,
+                i * self.upsert_batch_size + batch.len()
+            );
+        }
+
+        Ok(())
+    }
+
+    async fn upsert_batch(&self, collection: &str, batch: &[PointStruct]) -> Result<(), VectorError> {
+        let policy = self.upsert_policy;
+        let mut attempt = 1;
+        loop {
+            let error = match self
+                .client
+                .upsert_points(UpsertPointsBuilder::new(collection, batch.to_vec()).wait(true))
+                .await
+                .vector_context("Failed to upsert points to Qdrant")
+            {
+                Ok(_) => return Ok(()),
+                Err(e) => e,
+            };
+            if !error.is_unavailable() || attempt >= policy.max_attempts {
+                return Err(error);
+            }
+
+            let delay = policy.delay(attempt, None);
+            tracing::warn!(
+                "Upsert attempt {attempt}/{} failed, retrying in {}ms: {error}",
+                policy.max_attempts,
+                delay.as_millis()
+            );
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// Replace the labels of existing points, e.g. after a document is retagged or
+    /// moved to another collection.
+    pub async fn set_labels(
+        &self,
+        collection: &str,
+        point_ids: Vec<String>,
+        labels: &ChunkLabels,
+    ) -> Result<(), VectorError> {
+        if point_ids.is_empty() {
+            return Ok(());
+        }
+
+        let ids: Vec<qdrant_client::qdrant::PointId> = point_ids
+            .into_iter()
+            .map(qdrant_client::qdrant::PointId::from)
+            .collect();
         self.client
-            .upsert_points(UpsertPointsBuilder::new(&self.collection_name, points))
+            .set_payload(
+                SetPayloadPointsBuilder::new(collection, labels.payload())
+                    .points_selector(PointsIdsList { ids })
+                    .wait(true),
+            )
             .await
-            .context("Failed to upsert points to Qdrant")?;
+            .vector_context("Failed to update labels in Qdrant")?;
 
         Ok(())
     }
 
+    /// Nearest chunks to `query_embedding` among those matching `filter`.
     pub async fn search(
         &self,
+        collection: &str,
         query_embedding: Vec<f64>,
         top_k: u64,
-    ) -> Result<Vec<SearchResult>> {
+        filter: &SearchFilter,
+    ) -> Result<Vec<SearchResult>, VectorError> {
         let query_f32: Vec<f32> = query_embedding.iter().map(|&v| v as f32).collect();
+        let mut query = QueryPointsBuilder::new(collection)
+            .query(query_f32)
+            .limit(top_k)
+            .with_payload(true);
+        if let Some(filter) = filter.to_qdrant() {
+            query = query.filter(filter);
+        }
         let response = self
             .client
-            .query(
-                QueryPointsBuilder::new(&self.collection_name)
-                    .query(query_f32)
-                    .limit(top_k)
-                    .with_payload(true),
-            )
+            .query(query)
             .await
-            .context("Failed to search Qdrant")?;
+            .vector_context("Failed to search Qdrant")?;
 
         let results = response
             .result
@@ -146,6 +486,7 @@ impl VectorService {
                 SearchResult {
                     point_id,
                     score: point.score,
+                    vector_score: Some(point.score),
                     content,
                 }
             })
@@ -154,21 +495,143 @@ impl VectorService {
         Ok(results)
     }
 
-    pub async fn delete_points(&self, point_ids: Vec<String>) -> Result<()> {
+    pub async fn delete_points(&self, collection: &str, point_ids: Vec<String>) -> Result<(), VectorError> {
         if point_ids.is_empty() {
             return Ok(());
         }
 
         let ids: Vec<qdrant_client::qdrant::PointId> = point_ids
             .into_iter()
-            .map(|id| qdrant_client::qdrant::PointId::from(id))
+            .map(qdrant_client::qdrant::PointId::from)
             .collect();
 
         self.client
-            .delete_points(DeletePointsBuilder::new(&self.collection_name).points(ids))
+            .delete_points(DeletePointsBuilder::new(collection).points(ids))
             .await
-            .context("Failed to delete points from Qdrant")?;
+            .vector_context("Failed to delete points from Qdrant")?;
 
         Ok(())
     }
 }
+
+/// The first `max_chars` characters of `content`.
+fn truncate_chars(mut content: String, max_chars: usize) -> String {
+    if let Some((end, _)) = content.char_indices().nth(max_chars) {
+        content.truncate(end);
+    }
+    content
+}
+
+/// Reduce a model id such as `BAAI/bge-large-en-v1.5` to a collection-safe
+/// suffix (`baai_bge_large_en_v1_5`).
+fn model_slug(model: &str) -> String {
+    let mut slug = String::with_capacity(model.len());
+    for c in model.chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.ends_with('_') {
+            slug.push('_');
+        }
+    }
+    slug.trim_matches('_').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_model_slug() {
+        assert_eq!(model_slug("text-embedding-3-large"), "text_embedding_3_large");
+        assert_eq!(model_slug("BAAI/bge-large-en-v1.5"), "baai_bge_large_en_v1_5");
+        assert_eq!(model_slug("nomic-embed-text:latest"), "nomic_embed_text_latest");
+        assert_eq!(model_slug("--odd//name--"), "odd_name");
+    }
+
+    /// A service whose Qdrant URL points at a port nothing listens on.
+    fn unreachable_service() -> VectorService {
+        let config = QdrantConfig {
+            url: "http://127.0.0.1:1".to_string(),
+            collection_name: "rag_vectors".to_string(),
+            vector_size: 4,
+            embedding_dimensions: HashMap::new(),
+            skip_vector_validation: false,
+            upsert_batch_size: 2,
+            max_payload_content_chars: 100,
+        };
+        VectorService::connect_lazy(&config, "nomic-embed-text")
+            .unwrap()
+            .with_upsert_retry_policy(RetryPolicy {
+                max_attempts: 2,
+                base_delay: Duration::from_millis(1),
+                max_delay: Duration::from_millis(1),
+            })
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_qdrant_is_unavailable() {
+        let service = unreachable_service();
+
+        let health = service.check_health().await.unwrap_err();
+        assert!(health.is_unavailable(), "{health}");
+        let exists = service.collection_exists("rag_vectors").await.unwrap_err();
+        assert!(exists.is_unavailable(), "{exists}");
+        let delete = service
+            .delete_points("rag_vectors", vec![uuid::Uuid::new_v4().to_string()])
+            .await
+            .unwrap_err();
+        assert!(delete.is_unavailable(), "{delete}");
+        let chunks = (0..3)
+            .map(|i| (uuid::Uuid::new_v4().to_string(), vec![0.0; 4], format!("chunk {i}")))
+            .collect();
+        let upsert = service
+            .upsert_chunks("rag_vectors", chunks, &ChunkLabels::default())
+            .await
+            .unwrap_err();
+        assert!(upsert.is_unavailable(), "{upsert}");
+    }
+
+    #[test]
+    fn test_truncate_chars() {
+        assert_eq!(truncate_chars("héllo wörld".to_string(), 7), "héllo w");
+        assert_eq!(truncate_chars("short".to_string(), 100), "short");
+        assert_eq!(truncate_chars("ünïcode".to_string(), 0), "");
+    }
+
+    #[test]
+    fn test_check_vector_size() {
+        assert!(check_vector_size("text-embedding-3-small", Some(1536), 1536, "rag_vectors", Some(1536)).is_ok());
+        assert!(check_vector_size("text-embedding-3-small", Some(1536), 1536, "rag_vectors", None).is_ok());
+        assert!(check_vector_size("my-embedder", None, 1536, "rag_vectors", None).is_ok());
+
+        let error = check_vector_size("text-embedding-3-large", Some(3072), 1536, "rag_vectors", None)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("produces 3072 dimensions"), "{error}");
+        assert!(error.contains("qdrant.vector_size is 1536"), "{error}");
+        assert!(error.contains("does not exist yet"), "{error}");
+
+        let error = check_vector_size("nomic-embed-text", Some(768), 768, "rag_vectors", Some(1536))
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("768 dimensions"), "{error}");
+        assert!(error.contains("holds 1536-dimensional vectors"), "{error}");
+        assert!(error.contains("delete 'rag_vectors'"), "{error}");
+
+        let error = check_vector_size("my-embedder", None, 768, "rag_vectors", Some(1024))
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("produces an unknown number of dimensions"), "{error}");
+        assert!(error.contains("Set qdrant.vector_size to 1024"), "{error}");
+    }
+
+    #[test]
+    fn test_rejected_request_is_bad_request() {
+        let error = QdrantError::ResponseError {
+            status: tonic::Status::not_found("Collection `missing` doesn't exist!"),
+        };
+        let error = VectorError::from_qdrant("Failed to delete points from Qdrant", error);
+        assert!(!error.is_unavailable());
+        assert!(error.to_string().starts_with("Failed to delete points from Qdrant: "), "{error}");
+    }
+}
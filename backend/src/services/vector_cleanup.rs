@@ -0,0 +1,147 @@
+use anyhow::Result;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::db::models::pending_vector_deletion::PendingVectorDeletionRepository;
+use crate::services::vector::{VectorError, VectorService};
+use crate::state::AppState;
+
+/// How often queued deletions are retried.
+const RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Longest wait between retries of one deletion while Qdrant stays down.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60 * 60);
+
+/// Queued deletions retried per pass.
+const RETRY_BATCH: i64 = 100;
+
+/// Deletes vectors whose chunk rows are already gone. Points Qdrant doesn't
+/// delete are queued in `pending_vector_deletions` and retried by [`start`].
+#[derive(Clone)]
+pub struct VectorCleanup {
+    vector_service: Arc<VectorService>,
+    pending: PendingVectorDeletionRepository,
+}
+
+/// Outcome of one pass over the queued deletions.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct RetrySummary {
+    pub deleted: usize,
+    /// Dropped because Qdrant rejected them, e.g. for a removed collection.
+    pub dropped: usize,
+    pub remaining: usize,
+}
+
+impl VectorCleanup {
+    pub fn new(vector_service: Arc<VectorService>, pending: PendingVectorDeletionRepository) -> Self {
+        Self { vector_service, pending }
+    }
+
+    pub fn vector_service(&self) -> &VectorService {
+        &self.vector_service
+    }
+
+    /// Delete points, queueing them for a retry if Qdrant fails. The error is
+    /// still returned so callers can log what the vectors belonged to.
+    pub async fn delete_points(&self, collection: &str, point_ids: Vec<String>) -> Result<(), VectorError> {
+        if point_ids.is_empty() {
+            return Ok(());
+        }
+        let Err(e) = self.vector_service.delete_points(collection, point_ids.clone()).await else {
+            return Ok(());
+        };
+        if let Err(queue_error) = self.pending.enqueue(collection, &point_ids, &e.to_string()).await {
+            tracing::error!(
+                "Failed to queue {} vectors in '{collection}' for deletion, they are orphaned: {queue_error:#}",
+                point_ids.len()
+            );
+        }
+        Err(e)
+    }
+
+    /// Retry the deletions that are due. Stops at the first one Qdrant is
+    /// unavailable for, since the rest would fail the same way.
+    pub async fn retry_pending(&self) -> Result<RetrySummary> {
+        let due = self.pending.list_due(RETRY_BATCH).await?;
+        let mut summary = RetrySummary {
+            remaining: due.len(),
+            ..Default::default()
+        };
+        for deletion in due {
+            match self
+                .vector_service
+                .delete_points(&deletion.collection, deletion.point_ids.clone())
+                .await
+            {
+                Ok(()) => {
+                    self.pending.remove(deletion.id).await?;
+                    summary.deleted += 1;
+                }
+                Err(e) if e.is_unavailable() => {
+                    let delay = retry_delay(deletion.attempts);
+                    self.pending.retry_later(deletion.id, &e.to_string(), delay).await?;
+                    break;
+                }
+                Err(e) => {
+                    tracing::warn!(
+                        "Dropping queued deletion of {} vectors in '{}': {e}",
+                        deletion.point_ids.len(),
+                        deletion.collection
+                    );
+                    self.pending.remove(deletion.id).await?;
+                    summary.dropped += 1;
+                }
+            }
+            summary.remaining -= 1;
+        }
+        Ok(summary)
+    }
+}
+
+/// Doubles with each failed retry, up to [`MAX_RETRY_DELAY`].
+fn retry_delay(attempts: i32) -> Duration {
+    RETRY_INTERVAL
+        .saturating_mul(1 << attempts.clamp(0, 16))
+        .min(MAX_RETRY_DELAY)
+}
+
+/// Start the loop that retries queued vector deletions.
+pub fn start(state: AppState) {
+    let stopping = state.tasks.stopping().clone();
+    state.tasks.clone().spawn(async move {
+        let mut interval = tokio::time::interval(RETRY_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = stopping.cancelled() => break,
+            }
+            match state.vector_cleanup.retry_pending().await {
+                Ok(summary) if summary.deleted > 0 || summary.dropped > 0 => {
+                    tracing::info!(
+                        "Retried queued vector deletions: {} deleted, {} dropped, {} still pending",
+                        summary.deleted,
+                        summary.dropped,
+                        summary.remaining
+                    );
+                }
+                Ok(_) => {}
+                Err(e) => tracing::error!("Failed to retry queued vector deletions: {e:#}"),
+            }
+        }
+        tracing::info!("Vector deletion retries stopped");
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_delay_backs_off_to_the_cap() {
+        assert_eq!(retry_delay(0), RETRY_INTERVAL);
+        assert_eq!(retry_delay(1), RETRY_INTERVAL * 2);
+        assert_eq!(retry_delay(3), RETRY_INTERVAL * 8);
+        assert_eq!(retry_delay(10), MAX_RETRY_DELAY);
+        assert_eq!(retry_delay(i32::MAX), MAX_RETRY_DELAY);
+    }
+}
@@ -0,0 +1,215 @@
+use anyhow::Result;
+use ring::hmac;
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+use crate::db::models::webhook::{NewWebhookDelivery, WebhookRepository, WebhookTarget};
+use crate::services::retry::RetryPolicy;
+use crate::services::tasks::BackgroundTasks;
+use chrono::{DateTime, Utc};
+
+/// Events a webhook can subscribe to.
+pub const WEBHOOK_EVENTS: &[&str] = &[
+    "document.ready",
+    "document.failed",
+    "crawl.completed",
+    "crawl.failed",
+    "embed_key.created",
+    "embed_key.deleted",
+];
+
+/// Carries `sha256=<hex HMAC-SHA256 of the body>`, keyed with the webhook's secret.
+pub const SIGNATURE_HEADER: &str = "X-Webhook-Signature";
+pub const EVENT_HEADER: &str = "X-Webhook-Event";
+/// Identifies the event; retries of it reuse the same value.
+pub const DELIVERY_HEADER: &str = "X-Webhook-Delivery";
+
+/// How long a receiver gets to answer one attempt.
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Attempts per event, with backoff between them, while the receiver fails
+/// with a server error or can't be reached.
+const DELIVERY_POLICY: RetryPolicy = RetryPolicy {
+    max_attempts: 5,
+    base_delay: Duration::from_secs(2),
+    max_delay: Duration::from_secs(60),
+};
+
+/// The JSON body POSTed to webhooks.
+#[derive(Serialize)]
+struct WebhookPayload<'a> {
+    id: &'a str,
+    event: &'a str,
+    #[serde(with = "crate::db::timestamp")]
+    created_at: DateTime<Utc>,
+    data: &'a serde_json::Value,
+}
+
+/// Sends events to the webhooks subscribed to them, recording every attempt
+/// in `webhook_deliveries`.
+#[derive(Clone)]
+pub struct WebhookDispatcher {
+    repo: WebhookRepository,
+    client: reqwest::Client,
+    tasks: BackgroundTasks,
+    policy: RetryPolicy,
+}
+
+impl WebhookDispatcher {
+    pub fn new(repo: WebhookRepository, tasks: BackgroundTasks) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(DELIVERY_TIMEOUT)
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .expect("Failed to build webhook HTTP client");
+        Self {
+            repo,
+            client,
+            tasks,
+            policy: DELIVERY_POLICY,
+        }
+    }
+
+    /// Use a different retry policy, e.g. a fast one in tests.
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn repo(&self) -> &WebhookRepository {
+        &self.repo
+    }
+
+    /// Deliver `event` in the background so the caller never waits on receivers.
+    pub fn emit(&self, event: &'static str, data: serde_json::Value) {
+        let dispatcher = self.clone();
+        self.tasks.spawn(async move {
+            let work = dispatcher.dispatch(event, &data);
+            match dispatcher.tasks.run_until_aborted(work).await {
+                Some(Err(e)) => tracing::error!("Failed to dispatch webhook event {event}: {e:#}"),
+                None => tracing::warn!("Webhook event {event} abandoned by shutdown"),
+                Some(Ok(())) => {}
+            }
+        });
+    }
+
+    /// Deliver `event` to every subscribed webhook, retrying each until it
+    /// succeeds or runs out of attempts.
+    pub async fn dispatch(&self, event: &str, data: &serde_json::Value) -> Result<()> {
+        let targets = self.repo.targets_for(event).await?;
+        if targets.is_empty() {
+            return Ok(());
+        }
+
+        let delivery_id = uuid::Uuid::new_v4().to_string();
+        let body = serde_json::to_vec(&WebhookPayload {
+            id: &delivery_id,
+            event,
+            created_at: chrono::Utc::now(),
+            data,
+        })?;
+
+        let deliveries = targets
+            .iter()
+            .map(|target| self.deliver(target, &delivery_id, event, &body));
+        futures::future::join_all(deliveries).await;
+        Ok(())
+    }
+
+    async fn deliver(&self, target: &WebhookTarget, delivery_id: &str, event: &str, body: &[u8]) {
+        let signature = sign(&target.secret, body);
+        let mut attempt = 1;
+        loop {
+            let started = Instant::now();
+            let result = self
+                .client
+                .post(&target.url)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .header(EVENT_HEADER, event)
+                .header(DELIVERY_HEADER, delivery_id)
+                .header(SIGNATURE_HEADER, &signature)
+                .body(body.to_vec())
+                .send()
+                .await;
+            let duration_ms = started.elapsed().as_millis() as i64;
+
+            let (status_code, error, retryable) = match &result {
+                Ok(response) if response.status().is_success() => (Some(response.status()), None, false),
+                Ok(response) => (
+                    Some(response.status()),
+                    Some(format!("Receiver answered {}", response.status())),
+                    response.status().is_server_error(),
+                ),
+                Err(e) => (None, Some(format!("Request failed: {e}")), true),
+            };
+
+            let recorded = self
+                .repo
+                .record_delivery(&NewWebhookDelivery {
+                    webhook_id: &target.id,
+                    delivery_id,
+                    event,
+                    attempt,
+                    status_code: status_code.map(|s| s.as_u16()),
+                    success: error.is_none(),
+                    error: error.as_deref(),
+                    duration_ms,
+                })
+                .await;
+            if let Err(e) = recorded {
+                tracing::error!("Failed to record delivery to webhook {}: {e:#}", target.id);
+            }
+
+            let Some(error) = error else {
+                return;
+            };
+            if !retryable || attempt >= self.policy.max_attempts {
+                tracing::warn!(
+                    "Giving up on {event} for webhook {} after {attempt} attempts: {error}",
+                    target.id
+                );
+                return;
+            }
+
+            let delay = self.policy.delay(attempt, None);
+            tracing::warn!(
+                "Webhook {} attempt {attempt}/{} failed, retrying in {}ms: {error}",
+                target.id,
+                self.policy.max_attempts,
+                delay.as_millis()
+            );
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+}
+
+/// `sha256=` followed by the hex HMAC-SHA256 of `body` keyed with `secret`.
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+    let tag = hmac::sign(&key, body);
+    let hex: String = tag.as_ref().iter().map(|b| format!("{b:02x}")).collect();
+    format!("sha256={hex}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_matches_known_hmac() {
+        // RFC 4231 test case 2
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn test_signature_depends_on_secret_and_body() {
+        let body = br#"{"event":"document.ready"}"#;
+        assert_eq!(sign("secret", body), sign("secret", body));
+        assert_ne!(sign("secret", body), sign("other", body));
+        assert_ne!(sign("secret", body), sign("secret", b"{}"));
+    }
+}
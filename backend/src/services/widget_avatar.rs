@@ -0,0 +1,249 @@
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::reader::Reader;
+use quick_xml::writer::Writer;
+
+/// Largest avatar image an admin may upload.
+pub const MAX_AVATAR_BYTES: usize = 512 * 1024;
+
+/// Storage prefix for avatar images, kept apart from users' documents.
+const STORAGE_PREFIX: &str = "widget-avatars";
+
+/// Elements an avatar SVG may use: shapes, text, gradients and filters. Anything
+/// else, including `script`, `style`, `image` and `foreignObject`, is removed
+/// with its content.
+const SVG_ELEMENTS: &[&str] = &[
+    "svg", "g", "defs", "symbol", "use", "title", "desc", "path", "rect", "circle", "ellipse", "line",
+    "polyline", "polygon", "text", "tspan", "linearGradient", "radialGradient", "stop", "clipPath", "mask",
+    "pattern", "marker", "filter", "feBlend", "feColorMatrix", "feComposite", "feDropShadow", "feFlood",
+    "feGaussianBlur", "feMerge", "feMergeNode", "feOffset",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AvatarFormat {
+    Png,
+    Jpeg,
+    Svg,
+}
+
+impl AvatarFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            AvatarFormat::Png => "image/png",
+            AvatarFormat::Jpeg => "image/jpeg",
+            AvatarFormat::Svg => "image/svg+xml",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            AvatarFormat::Png => "png",
+            AvatarFormat::Jpeg => "jpg",
+            AvatarFormat::Svg => "svg",
+        }
+    }
+
+    fn from_content_type(content_type: &str) -> Option<Self> {
+        match content_type.split(';').next().unwrap_or("").trim() {
+            "image/png" => Some(AvatarFormat::Png),
+            "image/jpeg" | "image/jpg" => Some(AvatarFormat::Jpeg),
+            "image/svg+xml" => Some(AvatarFormat::Svg),
+            _ => None,
+        }
+    }
+
+    /// The format of a stored avatar, from its storage key.
+    pub fn from_storage_key(key: &str) -> Option<Self> {
+        match key.rsplit_once('.')?.1 {
+            "png" => Some(AvatarFormat::Png),
+            "jpg" => Some(AvatarFormat::Jpeg),
+            "svg" => Some(AvatarFormat::Svg),
+            _ => None,
+        }
+    }
+}
+
+/// Check an uploaded avatar against its declared content type and return the
+/// bytes to store. SVGs are rewritten without scripts, event handlers and
+/// external references.
+pub fn prepare(content_type: &str, data: &[u8]) -> Result<(AvatarFormat, Vec<u8>), String> {
+    let format = AvatarFormat::from_content_type(content_type)
+        .ok_or_else(|| "Avatar must be a PNG, JPEG or SVG image".to_string())?;
+    if data.is_empty() {
+        return Err("Avatar image is empty".to_string());
+    }
+    if data.len() > MAX_AVATAR_BYTES {
+        return Err(format!("Avatar image exceeds {} KB", MAX_AVATAR_BYTES / 1024));
+    }
+
+    let matches = match format {
+        AvatarFormat::Png => data.starts_with(b"\x89PNG\r\n\x1a\n"),
+        AvatarFormat::Jpeg => data.starts_with(&[0xff, 0xd8, 0xff]),
+        AvatarFormat::Svg => return Ok((format, sanitize_svg(data)?)),
+    };
+    if !matches {
+        return Err(format!("File content is not a valid {} image", format.extension().to_uppercase()));
+    }
+    Ok((format, data.to_vec()))
+}
+
+/// Where a new avatar of the key is stored. Every upload gets its own object,
+/// so the URL changes and caches never serve the old image.
+pub fn storage_key(embed_key_id: &str, format: AvatarFormat) -> String {
+    format!(
+        "{STORAGE_PREFIX}/{embed_key_id}/{}.{}",
+        uuid::Uuid::new_v4().simple(),
+        format.extension()
+    )
+}
+
+/// Identifies one upload of an avatar: its file name without the extension.
+pub fn version(avatar_key: &str) -> &str {
+    let file = avatar_key.rsplit('/').next().unwrap_or(avatar_key);
+    file.split('.').next().unwrap_or(file)
+}
+
+/// Public URL of a stored avatar, relative to the API server. It names the
+/// key by its prefix, so the widget never needs the raw key to show it.
+pub fn url(key_prefix: &str, avatar_key: &str) -> String {
+    format!("/api/widget/avatar/{key_prefix}?v={}", version(avatar_key))
+}
+
+fn is_safe_attribute(name: &str, value: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    let value = value.to_ascii_lowercase();
+    if name.starts_with("on") || value.contains("javascript:") {
+        return false;
+    }
+    if name == "href" || name == "xlink:href" {
+        return value.trim_start().starts_with('#');
+    }
+    // Paint servers and clip paths may point into the document, nowhere else
+    value
+        .match_indices("url(")
+        .all(|(i, m)| value[i + m.len()..].trim_start_matches([' ', '\'', '"']).starts_with('#'))
+}
+
+fn sanitize_element(element: &BytesStart) -> Result<BytesStart<'static>, String> {
+    let name = String::from_utf8_lossy(element.name().as_ref()).into_owned();
+    let mut clean = BytesStart::new(name);
+    for attribute in element.attributes() {
+        let attribute = attribute.map_err(|e| format!("Invalid SVG attribute: {e}"))?;
+        let name = String::from_utf8_lossy(attribute.key.as_ref()).into_owned();
+        let value = attribute
+            .unescape_value()
+            .map_err(|e| format!("Invalid SVG attribute value: {e}"))?;
+        if is_safe_attribute(&name, &value) {
+            clean.push_attribute((name.as_str(), value.as_ref()));
+        }
+    }
+    Ok(clean)
+}
+
+/// Rewrite an SVG keeping only [`SVG_ELEMENTS`] and attributes that can't run
+/// script or load anything from elsewhere.
+pub fn sanitize_svg(data: &[u8]) -> Result<Vec<u8>, String> {
+    let mut reader = Reader::from_reader(data);
+    let mut writer = Writer::new(Vec::new());
+    let mut buf = Vec::new();
+    let mut skip_depth = 0usize;
+    let mut has_root = false;
+
+    loop {
+        let event = reader.read_event_into(&mut buf).map_err(|e| format!("Invalid SVG: {e}"))?;
+        let write = match event {
+            Event::Eof => break,
+            Event::Start(_) if skip_depth > 0 => {
+                skip_depth += 1;
+                None
+            }
+            Event::End(_) if skip_depth > 0 => {
+                skip_depth -= 1;
+                None
+            }
+            _ if skip_depth > 0 => None,
+            Event::Start(ref e) | Event::Empty(ref e) => {
+                let name = e.name();
+                let allowed = SVG_ELEMENTS.iter().any(|n| n.as_bytes() == name.as_ref());
+                if !has_root && name.as_ref() != b"svg" {
+                    return Err("File is not an SVG image".to_string());
+                }
+                has_root = true;
+                match (allowed, &event) {
+                    (true, Event::Start(e)) => Some(Event::Start(sanitize_element(e)?)),
+                    (true, _) => Some(Event::Empty(sanitize_element(e)?)),
+                    (false, Event::Start(_)) => {
+                        skip_depth = 1;
+                        None
+                    }
+                    (false, _) => None,
+                }
+            }
+            Event::End(e) => Some(Event::End(e.into_owned())),
+            Event::Text(e) if has_root => Some(Event::Text(e.into_owned())),
+            Event::Decl(e) => Some(Event::Decl(e.into_owned())),
+            // Comments, doctypes (and their entities), CDATA and processing instructions
+            _ => None,
+        };
+        if let Some(event) = write {
+            writer.write_event(event).map_err(|e| format!("Failed to write SVG: {e}"))?;
+        }
+        buf.clear();
+    }
+
+    if !has_root {
+        return Err("File is not an SVG image".to_string());
+    }
+    Ok(writer.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sanitized(svg: &str) -> String {
+        String::from_utf8(sanitize_svg(svg.as_bytes()).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_sanitize_svg_keeps_drawing() {
+        let svg = r##"<?xml version="1.0"?><svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 10 10"><defs><linearGradient id="g"><stop offset="0" stop-color="#fff"/></linearGradient></defs><circle cx="5" cy="5" r="4" fill="url(#g)"/><text x="1">A &amp; B</text></svg>"##;
+        assert_eq!(sanitized(svg), svg);
+    }
+
+    #[test]
+    fn test_sanitize_svg_removes_active_content() {
+        let svg = r##"<svg onload="alert(1)"><script>alert(1)</script><foreignObject><div><b>x</b></div></foreignObject><a href="javascript:alert(1)"><rect/></a><use href="https://evil.example/x.svg#a" xlink:href="#local"/><rect style="fill: url( 'https://evil.example/p')" ONCLICK="x()"/><!-- note --><image href="data:image/png;base64,AA"/></svg>"##;
+        assert_eq!(sanitized(svg), r##"<svg><use xlink:href="#local"/><rect/></svg>"##);
+    }
+
+    #[test]
+    fn test_sanitize_svg_rejects_other_documents() {
+        assert!(sanitize_svg(b"<html><svg/></html>").is_err());
+        assert!(sanitize_svg(b"not xml at all").is_err());
+        assert!(sanitize_svg(b"<svg><g></svg>").is_err());
+    }
+
+    #[test]
+    fn test_prepare_checks_content_against_type() {
+        let png = b"\x89PNG\r\n\x1a\nrest".to_vec();
+        assert_eq!(prepare("image/png", &png).unwrap(), (AvatarFormat::Png, png.clone()));
+        assert!(prepare("image/jpeg", &png).is_err());
+        assert!(prepare("image/gif", b"GIF89a").is_err());
+        assert!(prepare("image/png", &[]).is_err());
+        let mut large = png.clone();
+        large.resize(MAX_AVATAR_BYTES + 1, 0);
+        assert!(prepare("image/png", &large).is_err());
+        assert_eq!(prepare("image/jpg", &[0xff, 0xd8, 0xff, 0xe0]).unwrap().0, AvatarFormat::Jpeg);
+        assert_eq!(prepare("image/svg+xml", b"<svg/>").unwrap(), (AvatarFormat::Svg, b"<svg/>".to_vec()));
+    }
+
+    #[test]
+    fn test_url_changes_with_each_upload() {
+        let key = storage_key("key-1", AvatarFormat::Svg);
+        assert!(key.starts_with("widget-avatars/key-1/") && key.ends_with(".svg"));
+        assert_eq!(AvatarFormat::from_storage_key(&key), Some(AvatarFormat::Svg));
+        let version = key.trim_start_matches("widget-avatars/key-1/").trim_end_matches(".svg");
+        assert_eq!(url("ek_12345678", &key), format!("/api/widget/avatar/ek_12345678?v={version}"));
+        assert_ne!(storage_key("key-1", AvatarFormat::Svg), key);
+    }
+}
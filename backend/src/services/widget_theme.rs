@@ -0,0 +1,302 @@
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::db::models::embed_key::{WidgetTheme, WidgetThemeColors};
+
+/// Largest theme an admin may submit, measured as JSON.
+pub const MAX_THEME_BYTES: usize = 4096;
+const MAX_BORDER_RADIUS: u32 = 32;
+const MAX_COLOR_CHARS: usize = 64;
+const MAX_FONT_FAMILY_CHARS: usize = 200;
+
+pub const DEFAULT_PRIMARY_COLOR: &str = "#2563eb";
+const DEFAULT_BORDER_RADIUS: u32 = 12;
+const DEFAULT_FONT_FAMILY: &str = r#"-apple-system, BlinkMacSystemFont, "Segoe UI", Roboto, sans-serif"#;
+
+/// Colors a scheme uses where the theme sets none. The primary color and the
+/// user bubble, which defaults to it, come from the embed key instead.
+struct SchemeDefaults {
+    background: &'static str,
+    text: &'static str,
+    user_text: &'static str,
+    assistant_bubble: &'static str,
+    assistant_text: &'static str,
+}
+
+const LIGHT_DEFAULTS: SchemeDefaults = SchemeDefaults {
+    background: "#ffffff",
+    text: "#333333",
+    user_text: "#ffffff",
+    assistant_bubble: "#f1f3f5",
+    assistant_text: "#333333",
+};
+
+const DARK_DEFAULTS: SchemeDefaults = SchemeDefaults {
+    background: "#1f2937",
+    text: "#f3f4f6",
+    user_text: "#ffffff",
+    assistant_bubble: "#374151",
+    assistant_text: "#f3f4f6",
+};
+
+/// A theme with every value filled in, as the widget applies it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ResolvedWidgetTheme {
+    pub light: ResolvedThemeColors,
+    /// Used when the visitor's system prefers a dark color scheme; `None` keeps
+    /// the light colors.
+    pub dark: Option<ResolvedThemeColors>,
+    /// Corner radius of the panel and message bubbles, in pixels.
+    pub border_radius: u32,
+    pub font_family: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ResolvedThemeColors {
+    pub primary: String,
+    pub background: String,
+    pub text: String,
+    pub user_bubble: String,
+    pub user_text: String,
+    pub assistant_bubble: String,
+    pub assistant_text: String,
+}
+
+/// A `#rgb`, `#rgba`, `#rrggbb` or `#rrggbbaa` hex color, or an `rgb()`,
+/// `rgba()`, `hsl()` or `hsla()` color with plain numeric arguments. Nothing
+/// else is accepted, so a valid color can't carry other CSS with it.
+pub fn is_valid_color(value: &str) -> bool {
+    if value.len() > MAX_COLOR_CHARS {
+        return false;
+    }
+    if let Some(hex) = value.strip_prefix('#') {
+        return matches!(hex.len(), 3 | 4 | 6 | 8) && hex.chars().all(|c| c.is_ascii_hexdigit());
+    }
+    let Some((function, args)) = value.split_once('(') else {
+        return false;
+    };
+    let Some(args) = args.strip_suffix(')') else {
+        return false;
+    };
+    matches!(function.to_ascii_lowercase().as_str(), "rgb" | "rgba" | "hsl" | "hsla")
+        && args.chars().any(|c| c.is_ascii_digit())
+        && args.chars().all(|c| c.is_ascii_digit() || " ,./%".contains(c))
+}
+
+/// A comma-separated list of font families, each a bare name or one quoted
+/// with `"` or `'`. Names are limited to letters, digits, spaces, `-` and `_`.
+pub fn is_valid_font_family(value: &str) -> bool {
+    value.len() <= MAX_FONT_FAMILY_CHARS
+        && value.split(',').all(|family| {
+            let family = family.trim();
+            let name = match family.chars().next() {
+                Some(quote @ ('"' | '\'')) => match family[1..].strip_suffix(quote) {
+                    Some(name) => name,
+                    None => return false,
+                },
+                _ => family,
+            };
+            !name.trim().is_empty()
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, ' ' | '-' | '_'))
+        })
+}
+
+/// Check a theme submitted by an admin, trimming values and dropping blank ones
+/// so they fall back to the defaults.
+pub fn validate(theme: WidgetTheme) -> Result<WidgetTheme> {
+    let size = serde_json::to_vec(&theme)?.len();
+    if size > MAX_THEME_BYTES {
+        anyhow::bail!("Theme is {size} bytes; the limit is {MAX_THEME_BYTES}");
+    }
+
+    if let Some(radius) = theme.border_radius
+        && radius > MAX_BORDER_RADIUS
+    {
+        anyhow::bail!("Theme border_radius must be at most {MAX_BORDER_RADIUS} pixels");
+    }
+    let font_family = theme
+        .font_family
+        .map(|f| f.trim().to_string())
+        .filter(|f| !f.is_empty());
+    if let Some(ref font_family) = font_family
+        && !is_valid_font_family(font_family)
+    {
+        anyhow::bail!(
+            "Theme font_family must be a comma-separated list of font names such as 'Inter, sans-serif'"
+        );
+    }
+
+    Ok(WidgetTheme {
+        light: validate_colors(theme.light, "light")?,
+        dark: theme.dark.map(|dark| validate_colors(dark, "dark")).transpose()?,
+        border_radius: theme.border_radius,
+        font_family,
+    })
+}
+
+fn validate_colors(colors: WidgetThemeColors, scheme: &str) -> Result<WidgetThemeColors> {
+    let check = |name: &str, value: Option<String>| -> Result<Option<String>> {
+        let Some(value) = value.map(|v| v.trim().to_string()).filter(|v| !v.is_empty()) else {
+            return Ok(None);
+        };
+        if !is_valid_color(&value) {
+            anyhow::bail!(
+                "Theme color {scheme}.{name} '{value}' is not a hex, rgb() or hsl() color"
+            );
+        }
+        Ok(Some(value))
+    };
+    Ok(WidgetThemeColors {
+        primary: check("primary", colors.primary)?,
+        background: check("background", colors.background)?,
+        text: check("text", colors.text)?,
+        user_bubble: check("user_bubble", colors.user_bubble)?,
+        user_text: check("user_text", colors.user_text)?,
+        assistant_bubble: check("assistant_bubble", colors.assistant_bubble)?,
+        assistant_text: check("assistant_text", colors.assistant_text)?,
+    })
+}
+
+/// Fill in a key's theme with the defaults. `primary_color` is the key's
+/// shorthand for the light primary color. Values that aren't valid, such as
+/// ones stored before validation existed, are replaced by the defaults too.
+pub fn resolve(theme: &WidgetTheme, primary_color: &str) -> ResolvedWidgetTheme {
+    let primary_color = if is_valid_color(primary_color) {
+        primary_color
+    } else {
+        DEFAULT_PRIMARY_COLOR
+    };
+    let light = resolve_colors(&theme.light, &LIGHT_DEFAULTS, primary_color);
+    let dark = theme
+        .dark
+        .as_ref()
+        .map(|dark| resolve_colors(dark, &DARK_DEFAULTS, &light.primary));
+
+    ResolvedWidgetTheme {
+        dark,
+        light,
+        border_radius: theme
+            .border_radius
+            .filter(|r| *r <= MAX_BORDER_RADIUS)
+            .unwrap_or(DEFAULT_BORDER_RADIUS),
+        font_family: theme
+            .font_family
+            .clone()
+            .filter(|f| is_valid_font_family(f))
+            .unwrap_or_else(|| DEFAULT_FONT_FAMILY.to_string()),
+    }
+}
+
+fn resolve_colors(colors: &WidgetThemeColors, defaults: &SchemeDefaults, primary: &str) -> ResolvedThemeColors {
+    let pick = |value: &Option<String>, default: &str| {
+        value
+            .as_deref()
+            .filter(|c| is_valid_color(c))
+            .unwrap_or(default)
+            .to_string()
+    };
+    let primary = pick(&colors.primary, primary);
+    ResolvedThemeColors {
+        user_bubble: pick(&colors.user_bubble, &primary),
+        background: pick(&colors.background, defaults.background),
+        text: pick(&colors.text, defaults.text),
+        user_text: pick(&colors.user_text, defaults.user_text),
+        assistant_bubble: pick(&colors.assistant_bubble, defaults.assistant_bubble),
+        assistant_text: pick(&colors.assistant_text, defaults.assistant_text),
+        primary,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_valid_color() {
+        for color in ["#fff", "#ffff", "#1a2B3c", "#1a2b3c80", "rgb(10, 20, 30)", "rgba(0 0 0 / 50%)", "hsl(210, 50%, 40%)"] {
+            assert!(is_valid_color(color), "{color}");
+        }
+        for color in [
+            "",
+            "red",
+            "#ggg",
+            "#12345",
+            "rgb()",
+            "url(x)",
+            "rgb(0,0,0);background:url(x)",
+            "#fff;}",
+            "rgb(0, 0, 0) }",
+            "expression(alert(1))",
+        ] {
+            assert!(!is_valid_color(color), "{color}");
+        }
+    }
+
+    #[test]
+    fn test_is_valid_font_family() {
+        assert!(is_valid_font_family("Inter, sans-serif"));
+        assert!(is_valid_font_family(r#""Segoe UI", 'Open Sans', system-ui"#));
+        assert!(!is_valid_font_family(""));
+        assert!(!is_valid_font_family("Inter,"));
+        assert!(!is_valid_font_family(r#""Inter"#));
+        assert!(!is_valid_font_family("Inter; color: red"));
+        assert!(!is_valid_font_family("Inter}</style><script>"));
+        assert!(!is_valid_font_family(r#""a\" b""#));
+    }
+
+    #[test]
+    fn test_validate_trims_and_rejects() {
+        let theme: WidgetTheme = serde_json::from_value(serde_json::json!({
+            "light": { "background": " #fafafa ", "text": "" },
+            "font_family": "  Inter, sans-serif ",
+            "border_radius": 8
+        }))
+        .unwrap();
+        let theme = validate(theme).unwrap();
+        assert_eq!(theme.light.background.as_deref(), Some("#fafafa"));
+        assert_eq!(theme.light.text, None);
+        assert_eq!(theme.font_family.as_deref(), Some("Inter, sans-serif"));
+
+        let invalid = |json: serde_json::Value| validate(serde_json::from_value(json).unwrap()).is_err();
+        assert!(invalid(serde_json::json!({ "dark": { "text": "white;}" } })));
+        assert!(invalid(serde_json::json!({ "border_radius": 100 })));
+        assert!(invalid(serde_json::json!({ "font_family": "x".repeat(MAX_THEME_BYTES) })));
+
+        // Unknown fields are refused rather than silently stored
+        assert!(serde_json::from_value::<WidgetTheme>(serde_json::json!({ "background": "#fff" })).is_err());
+    }
+
+    #[test]
+    fn test_resolve_fills_in_defaults() {
+        let resolved = resolve(&WidgetTheme::default(), "#ff0000");
+        assert_eq!(resolved.light.primary, "#ff0000");
+        assert_eq!(resolved.light.user_bubble, "#ff0000");
+        assert_eq!(resolved.light.background, LIGHT_DEFAULTS.background);
+        assert_eq!(resolved.dark, None);
+        assert_eq!(resolved.border_radius, DEFAULT_BORDER_RADIUS);
+
+        // The dark scheme inherits the primary color unless it sets its own
+        let theme: WidgetTheme = serde_json::from_value(serde_json::json!({
+            "light": { "primary": "#00ff00" },
+            "dark": { "assistant_bubble": "#111111" }
+        }))
+        .unwrap();
+        let dark = resolve(&theme, "#ff0000").dark.unwrap();
+        assert_eq!(dark.primary, "#00ff00");
+        assert_eq!(dark.assistant_bubble, "#111111");
+        assert_eq!(dark.background, DARK_DEFAULTS.background);
+
+        // Stored values that don't validate never reach the widget
+        let theme = WidgetTheme {
+            font_family: Some("x; }".to_string()),
+            ..Default::default()
+        };
+        let resolved = resolve(&theme, "red;}");
+        assert_eq!(resolved.light.primary, DEFAULT_PRIMARY_COLOR);
+        assert_eq!(resolved.font_family, DEFAULT_FONT_FAMILY);
+    }
+}
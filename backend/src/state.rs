@@ -1,41 +1,131 @@
 use crate::config::AppConfig;
+use crate::db::models::admin_api_key::AdminApiKeyRepository;
 use crate::db::models::admin_config::AdminConfigRepository;
 use crate::db::models::audit_log::AuditLogRepository;
+use crate::db::models::collection::CollectionRepository;
 use crate::db::models::conversation::ConversationRepository;
+use crate::db::models::conversation_share::ConversationShareRepository;
 use crate::db::models::crawl_job::CrawlJobRepository;
+use crate::db::models::crawl_schedule::CrawlScheduleRepository;
+use crate::db::models::data_export::DataExportRepository;
 use crate::db::models::document::DocumentRepository;
 use crate::db::models::document_chunk::DocumentChunkRepository;
+use crate::db::models::document_event::DocumentEventRepository;
+use crate::db::models::email_outbox::EmailOutboxRepository;
 use crate::db::models::embed_key::EmbedKeyRepository;
+use crate::db::models::impersonation::ImpersonationRepository;
 use crate::db::models::invite::InviteRepository;
+use crate::db::models::job::JobRepository;
+use crate::db::models::org_settings::OrgSettingsRepository;
+use crate::db::models::pending_vector_deletion::PendingVectorDeletionRepository;
+use crate::db::models::rescan_run::RescanRunRepository;
+use crate::db::models::scheduled_job_run::ScheduledJobRunRepository;
 use crate::db::models::settings::SettingsRepository;
 use crate::db::models::user::UserRepository;
+use crate::db::models::web_page::WebPageRepository;
+use crate::db::models::webhook::WebhookRepository;
+use crate::db::models::widget_handoff::WidgetHandoffRepository;
 use crate::db::models::widget_session::WidgetSessionRepository;
+use crate::services::audit::AuditQueue;
+use crate::services::chunk_search::ChunkSearchService;
+use crate::services::conversation_locks::ConversationLocks;
 use crate::services::crawler::CrawlerService;
+use crate::services::credentials::CredentialResolver;
+use crate::services::document_events::DocumentEventLog;
 use crate::services::email::EmailService;
+use crate::services::email_outbox::EmailOutbox;
+use crate::services::embedding::EmbeddingResolver;
+use crate::services::jobs::JobQueue;
+use crate::services::llm_provider::{self, CompletionBackend, EmbeddingBackend};
+use crate::services::embed_key_cache::{EmbedKeyCache, EMBED_KEY_CACHE_TTL};
+use crate::services::user_cache::{UserCache, USER_CACHE_TTL};
+use crate::services::model_catalog::{ModelCatalogCache, MODEL_CACHE_TTL};
+use crate::services::provider_guard::{CircuitBreakers, GuardedBackend};
+use crate::services::rate_limit::{RateLimiter, TokenBucketLimiter};
+use crate::services::rerank::RerankService;
+use crate::services::scheduler::Scheduler;
+use crate::services::secrets::SecretCipher;
 use crate::services::storage::StorageService;
+use crate::services::tasks::BackgroundTasks;
 use crate::services::vector::VectorService;
+use crate::services::vector_cleanup::VectorCleanup;
+use crate::services::webhook::WebhookDispatcher;
 use sqlx::PgPool;
+use std::net::IpAddr;
 use std::sync::Arc;
+use std::time::Duration;
 
 #[derive(Clone)]
 pub struct AppState {
     pub config: Arc<AppConfig>,
     pub db: PgPool,
     pub user_repo: UserRepository,
+    /// Users looked up by the auth middleware; see [`AppState::invalidate_user`].
+    pub user_cache: Arc<UserCache>,
     pub invite_repo: InviteRepository,
+    pub impersonation_repo: ImpersonationRepository,
     pub document_repo: DocumentRepository,
+    pub document_event_repo: DocumentEventRepository,
+    pub document_events: DocumentEventLog,
     pub settings_repo: SettingsRepository,
     pub crawl_repo: CrawlJobRepository,
+    pub crawl_schedule_repo: CrawlScheduleRepository,
+    pub web_page_repo: WebPageRepository,
     pub admin_config_repo: AdminConfigRepository,
+    pub admin_api_key_repo: AdminApiKeyRepository,
     pub conversation_repo: ConversationRepository,
+    pub share_repo: ConversationShareRepository,
     pub audit_log_repo: AuditLogRepository,
+    pub audit: AuditQueue,
+    pub collection_repo: CollectionRepository,
     pub chunk_repo: DocumentChunkRepository,
     pub embed_key_repo: EmbedKeyRepository,
+    /// Embed keys looked up by widget requests; see [`AppState::invalidate_embed_key`].
+    pub embed_key_cache: Arc<EmbedKeyCache>,
     pub widget_session_repo: WidgetSessionRepository,
+    pub widget_handoff_repo: WidgetHandoffRepository,
+    pub data_export_repo: DataExportRepository,
+    pub rescan_run_repo: RescanRunRepository,
+    pub scheduled_job_run_repo: ScheduledJobRunRepository,
+    pub org_settings_repo: OrgSettingsRepository,
+    pub credentials: CredentialResolver,
+    pub embedding: EmbeddingResolver,
+    pub completion_backend: Arc<dyn CompletionBackend>,
+    pub embedding_backend: Arc<dyn EmbeddingBackend>,
+    /// Shared by both backends, which fail fast while a provider's circuit is open.
+    pub provider_breakers: CircuitBreakers,
     pub storage: StorageService,
     pub crawler: Arc<CrawlerService>,
     pub vector_service: Arc<VectorService>,
-    pub email: EmailService,
+    /// Deletes vectors, queueing any Qdrant couldn't delete for a retry.
+    pub vector_cleanup: VectorCleanup,
+    pub chunk_search: Arc<ChunkSearchService>,
+    pub reranker: RerankService,
+    pub model_catalog: Arc<ModelCatalogCache>,
+    /// Queues, sends and retries every email the application sends.
+    pub email_outbox: EmailOutbox,
+    /// Limits requests to shared conversation links per client IP.
+    pub share_limiter: RateLimiter<IpAddr>,
+    /// Limits widget conversation deletions per (embed key, session).
+    pub widget_delete_limiter: RateLimiter<(String, String)>,
+    /// Limits transcript emails per (embed key, session).
+    pub transcript_email_limiter: RateLimiter<(String, String)>,
+    /// Limits transcript emails per client IP, across sessions.
+    pub transcript_email_ip_limiter: RateLimiter<IpAddr>,
+    /// Limits transcript emails per embed key, across all its visitors.
+    pub transcript_email_key_limiter: RateLimiter<String>,
+    /// Limits each user's requests to the authenticated API.
+    pub api_limiter: TokenBucketLimiter<String>,
+    /// Limits each user's requests to endpoints that call a model.
+    pub llm_limiter: TokenBucketLimiter<String>,
+    /// Notifies admin-registered webhooks of pipeline and admin events.
+    pub webhooks: WebhookDispatcher,
+    pub tasks: BackgroundTasks,
+    pub jobs: JobQueue,
+    /// Periodic maintenance jobs, registered at startup.
+    pub scheduler: Scheduler,
+    /// Conversations with a reply being generated, so sends to one serialize.
+    pub conversation_locks: ConversationLocks,
 }
 
 impl AppState {
@@ -45,38 +135,161 @@ impl AppState {
         storage: StorageService,
         vector_service: VectorService,
     ) -> Self {
+        let tasks = BackgroundTasks::new();
         let user_repo = UserRepository::new(db.clone());
+        let user_cache = Arc::new(UserCache::new(user_repo.clone(), USER_CACHE_TTL));
         let invite_repo = InviteRepository::new(db.clone());
+        let impersonation_repo = ImpersonationRepository::new(db.clone());
         let document_repo = DocumentRepository::new(db.clone());
+        let document_event_repo = DocumentEventRepository::new(db.clone());
+        let document_events = DocumentEventLog::start(document_event_repo.clone(), &tasks);
         let settings_repo = SettingsRepository::new(db.clone());
         let crawl_repo = CrawlJobRepository::new(db.clone());
+        let crawl_schedule_repo = CrawlScheduleRepository::new(db.clone());
+        let web_page_repo = WebPageRepository::new(db.clone());
         let admin_config_repo = AdminConfigRepository::new(db.clone());
+        let cipher = SecretCipher::new(config.auth.encryption_secret());
+        let admin_api_key_repo = AdminApiKeyRepository::new(db.clone(), cipher.clone());
         let conversation_repo = ConversationRepository::new(db.clone());
+        let share_repo = ConversationShareRepository::new(db.clone());
         let audit_log_repo = AuditLogRepository::new(db.clone());
+        let audit = AuditQueue::start(audit_log_repo.clone(), &tasks);
+        let collection_repo = CollectionRepository::new(db.clone());
         let chunk_repo = DocumentChunkRepository::new(db.clone());
         let embed_key_repo = EmbedKeyRepository::new(db.clone());
+        let embed_key_cache = Arc::new(EmbedKeyCache::new(embed_key_repo.clone(), EMBED_KEY_CACHE_TTL));
         let widget_session_repo = WidgetSessionRepository::new(db.clone());
+        let widget_handoff_repo = WidgetHandoffRepository::new(db.clone());
+        let data_export_repo = DataExportRepository::new(db.clone());
+        let rescan_run_repo = RescanRunRepository::new(db.clone());
+        let scheduled_job_run_repo = ScheduledJobRunRepository::new(db.clone());
+        let org_settings_repo = OrgSettingsRepository::new(db.clone());
+        let credentials = CredentialResolver::new(
+            settings_repo.clone(),
+            admin_api_key_repo.clone(),
+            user_repo.clone(),
+            config.features.allow_shared_api_keys,
+            tasks.clone(),
+        );
+        let embedding = EmbeddingResolver::new(
+            config.llm.clone(),
+            settings_repo.clone(),
+            admin_config_repo.clone(),
+            credentials.clone(),
+        );
+        let provider_breakers = CircuitBreakers::new(
+            config.llm.circuit_breaker_threshold,
+            Duration::from_secs(config.llm.circuit_breaker_cooldown_secs),
+        );
+        let (completion_backend, embedding_backend) = llm_provider::backends(&config);
+        let completion_backend: Arc<dyn CompletionBackend> = Arc::new(GuardedBackend::new(
+            completion_backend,
+            provider_breakers.clone(),
+            Duration::from_secs(config.llm.request_timeout_secs),
+        ));
+        let embedding_backend: Arc<dyn EmbeddingBackend> = Arc::new(GuardedBackend::new(
+            embedding_backend,
+            provider_breakers.clone(),
+            Duration::from_secs(config.llm.embedding_request_timeout_secs),
+        ));
         let crawler = Arc::new(CrawlerService::new(&config.crawler));
-        let email = EmailService::new(&config.resend);
+        let email_outbox = EmailOutbox::new(
+            EmailOutboxRepository::new(db.clone()),
+            EmailService::new(&config.resend),
+            tasks.clone(),
+        );
+        let share_limiter = RateLimiter::new(config.sharing.public_requests_per_minute, Duration::from_secs(60));
+        let widget_delete_limiter =
+            RateLimiter::new(config.widget.delete_requests_per_minute, Duration::from_secs(60));
+        const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+        let transcript_email_limiter = RateLimiter::new(config.widget.transcript_emails_per_day, DAY);
+        let transcript_email_ip_limiter = RateLimiter::new(config.widget.transcript_emails_per_ip_per_day, DAY);
+        let transcript_email_key_limiter =
+            RateLimiter::new(config.widget.transcript_emails_per_embed_key_per_day, DAY);
+        let api_limiter = TokenBucketLimiter::per_minute(config.rate_limit.requests_per_minute);
+        let llm_limiter = TokenBucketLimiter::per_minute(config.rate_limit.llm_requests_per_minute);
+        let vector_service = Arc::new(vector_service);
+        let vector_cleanup = VectorCleanup::new(
+            vector_service.clone(),
+            PendingVectorDeletionRepository::new(db.clone()),
+        );
+        let chunk_search = Arc::new(ChunkSearchService::new(
+            vector_service.clone(),
+            chunk_repo.clone(),
+            config.llm.retrieval_mode,
+        ));
+        let reranker = RerankService::new(config.llm.rerank, provider_breakers.clone());
+        let model_catalog = Arc::new(ModelCatalogCache::new(MODEL_CACHE_TTL));
+        let jobs = JobQueue::new(JobRepository::new(db.clone()));
+        let webhooks = WebhookDispatcher::new(WebhookRepository::new(db.clone(), cipher), tasks.clone());
 
         Self {
             config: Arc::new(config),
             db,
             user_repo,
+            user_cache,
             invite_repo,
+            impersonation_repo,
             document_repo,
+            document_event_repo,
+            document_events,
             settings_repo,
             crawl_repo,
+            crawl_schedule_repo,
+            web_page_repo,
             admin_config_repo,
+            admin_api_key_repo,
             conversation_repo,
+            share_repo,
             audit_log_repo,
+            audit,
+            collection_repo,
             chunk_repo,
             embed_key_repo,
+            embed_key_cache,
             widget_session_repo,
+            widget_handoff_repo,
+            data_export_repo,
+            rescan_run_repo,
+            scheduled_job_run_repo,
+            org_settings_repo,
+            credentials,
+            embedding,
+            completion_backend,
+            embedding_backend,
+            provider_breakers,
             storage,
             crawler,
-            vector_service: Arc::new(vector_service),
-            email,
+            vector_service,
+            vector_cleanup,
+            chunk_search,
+            reranker,
+            model_catalog,
+            email_outbox,
+            share_limiter,
+            widget_delete_limiter,
+            transcript_email_limiter,
+            transcript_email_ip_limiter,
+            transcript_email_key_limiter,
+            api_limiter,
+            llm_limiter,
+            webhooks,
+            tasks,
+            jobs,
+            scheduler: Scheduler::new(),
+            conversation_locks: ConversationLocks::new(),
         }
     }
+
+    /// Make authenticated requests see a user's new role (or deletion) right
+    /// away instead of after the cache expires.
+    pub async fn invalidate_user(&self, id: &str) {
+        self.user_cache.invalidate(id).await;
+    }
+
+    /// Make widget requests see an embed key's change (or deletion) right away
+    /// instead of after the cache expires. Call after every write to a key.
+    pub async fn invalidate_embed_key(&self, id: &str) {
+        self.embed_key_cache.invalidate(id).await;
+    }
 }
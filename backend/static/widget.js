@@ -7,6 +7,9 @@
   var EMBED_KEY = script.getAttribute("data-key");
   var SERVER = script.getAttribute("data-server") || new URL(script.src).origin;
   var POSITION = script.getAttribute("data-position") || "bottom-right";
+  // Visitor locale for localized texts; the browser's Accept-Language is used otherwise
+  var LANG =
+    script.getAttribute("data-lang") || document.documentElement.lang || "";
 
   if (!EMBED_KEY) {
     console.error("[RAG Widget] Missing data-key attribute");
@@ -43,6 +46,10 @@
     sessionStorage.setItem("rag_widget_conv_" + EMBED_KEY, id);
   }
 
+  function clearConversationId() {
+    sessionStorage.removeItem("rag_widget_conv_" + EMBED_KEY);
+  }
+
   var SESSION_ID = getSessionId();
 
   // API helpers
@@ -54,6 +61,10 @@
     };
   }
 
+  function withLang(path) {
+    return LANG ? path + "?lang=" + encodeURIComponent(LANG) : path;
+  }
+
   function apiFetch(path, opts) {
     opts = opts || {};
     opts.headers = apiHeaders();
@@ -64,39 +75,57 @@
   var config = {
     widget_title: "Chat",
     primary_color: "#2563eb",
+    theme: null,
     greeting_message: "Hello! How can I help you?",
     custom_css: "",
+    handoff_enabled: false,
+    avatar_url: null,
+    rate_limit: null,
+    messages_used: 0,
+    is_online: true,
+    offline_message: "",
+    offline_behavior: "notice",
   };
   var messages = [];
   var isOpen = false;
   var isLoading = false;
   var isRateLimited = false;
+  var isOffline = false;
+  var offlineShown = false;
   var widgetEl, chatPanel, msgList, inputArea, inputField, sendBtn, bubble;
 
   // CSS styles
   var styles =
     '\
-    :host { all: initial; font-family: -apple-system, BlinkMacSystemFont, "Segoe UI", Roboto, sans-serif; font-size: 14px; }\
+    :host { all: initial; font-family: var(--rag-font-family); font-size: 14px; }\
     * { box-sizing: border-box; margin: 0; padding: 0; }\
-    .rag-bubble { position: fixed; width: 56px; height: 56px; border-radius: 50%; cursor: pointer; display: flex; align-items: center; justify-content: center; box-shadow: 0 4px 12px rgba(0,0,0,0.15); z-index: 99999; transition: transform 0.2s; }\
+    .rag-bubble { position: fixed; width: 56px; height: 56px; border-radius: 50%; background: var(--rag-primary); cursor: pointer; display: flex; align-items: center; justify-content: center; box-shadow: 0 4px 12px rgba(0,0,0,0.15); z-index: 99999; transition: transform 0.2s; }\
     .rag-bubble:hover { transform: scale(1.1); }\
     .rag-bubble svg { width: 28px; height: 28px; fill: white; }\
-    .rag-panel { position: fixed; width: 380px; max-width: calc(100vw - 32px); height: 520px; max-height: calc(100vh - 100px); border-radius: 12px; box-shadow: 0 8px 32px rgba(0,0,0,0.2); display: none; flex-direction: column; z-index: 99999; background: #fff; overflow: hidden; }\
+    .rag-bubble .rag-avatar { width: 100%; height: 100%; border-radius: 50%; object-fit: cover; }\
+    .rag-panel { position: fixed; width: 380px; max-width: calc(100vw - 32px); height: 520px; max-height: calc(100vh - 100px); border-radius: var(--rag-radius); box-shadow: 0 8px 32px rgba(0,0,0,0.2); display: none; flex-direction: column; z-index: 99999; background: var(--rag-background); color: var(--rag-text); overflow: hidden; }\
     .rag-panel.open { display: flex; }\
-    .rag-header { padding: 16px; color: white; display: flex; align-items: center; justify-content: space-between; flex-shrink: 0; }\
+    .rag-header { padding: 16px; background: var(--rag-primary); color: white; display: flex; align-items: center; justify-content: space-between; flex-shrink: 0; }\
     .rag-header-title { font-size: 16px; font-weight: 600; }\
     .rag-header-close { background: none; border: none; color: white; font-size: 20px; cursor: pointer; padding: 4px 8px; border-radius: 4px; }\
     .rag-header-close:hover { background: rgba(255,255,255,0.2); }\
     .rag-messages { flex: 1; overflow-y: auto; padding: 16px; display: flex; flex-direction: column; gap: 8px; }\
-    .rag-msg { max-width: 85%; padding: 10px 14px; border-radius: 12px; line-height: 1.4; word-wrap: break-word; overflow-wrap: anywhere; }\
-    .rag-msg-user { align-self: flex-end; color: white; border-bottom-right-radius: 4px; white-space: pre-wrap; }\
-    .rag-msg-assistant { align-self: flex-start; background: #f1f3f5; color: #333; border-bottom-left-radius: 4px; white-space: normal; }\
-    .rag-msg-greeting { align-self: flex-start; background: #f1f3f5; color: #333; border-bottom-left-radius: 4px; white-space: normal; }\
+    .rag-msg { max-width: 85%; padding: 10px 14px; border-radius: var(--rag-radius); line-height: 1.4; word-wrap: break-word; overflow-wrap: anywhere; }\
+    .rag-msg-user { align-self: flex-end; background: var(--rag-user-bubble); color: var(--rag-user-text); border-bottom-right-radius: 4px; white-space: pre-wrap; }\
+    .rag-msg-assistant { align-self: flex-start; background: var(--rag-assistant-bubble); color: var(--rag-assistant-text); border-bottom-left-radius: 4px; white-space: normal; }\
+    .rag-msg-greeting { align-self: flex-start; background: var(--rag-assistant-bubble); color: var(--rag-assistant-text); border-bottom-left-radius: 4px; white-space: normal; }\
     .rag-msg-system { align-self: center; color: #888; font-size: 12px; padding: 8px; }\
+    .rag-header-actions { display: flex; align-items: center; gap: 4px; }\
+    .rag-header-handoff, .rag-header-clear { background: none; border: 1px solid rgba(255,255,255,0.6); color: white; font-size: 12px; cursor: pointer; padding: 4px 8px; border-radius: 4px; font-family: inherit; }\
+    .rag-header-handoff:hover, .rag-header-clear:hover { background: rgba(255,255,255,0.2); }\
+    .rag-handoff { align-self: stretch; display: flex; flex-direction: column; gap: 6px; padding: 12px; border: 1px solid #e5e7eb; border-radius: 8px; background: #fafafa; }\
+    .rag-handoff-label { font-size: 13px; color: var(--rag-text); }\
+    .rag-handoff input, .rag-handoff textarea { border: 1px solid #d1d5db; border-radius: 6px; padding: 8px; font-size: 13px; font-family: inherit; }\
+    .rag-handoff textarea { resize: vertical; min-height: 60px; }\
     .rag-input-area { display: flex; padding: 12px; border-top: 1px solid #e5e7eb; gap: 8px; flex-shrink: 0; }\
     .rag-input { flex: 1; border: 1px solid #d1d5db; border-radius: 8px; padding: 10px 12px; font-size: 14px; font-family: inherit; resize: none; outline: none; max-height: 80px; }\
-    .rag-input:focus { border-color: #2563eb; }\
-    .rag-send { border: none; border-radius: 8px; color: white; padding: 10px 16px; cursor: pointer; font-size: 14px; font-weight: 500; }\
+    .rag-input:focus { border-color: var(--rag-primary); }\
+    .rag-send { border: none; border-radius: 8px; background: var(--rag-primary); color: white; padding: 10px 16px; cursor: pointer; font-size: 14px; font-weight: 500; }\
     .rag-send:disabled { opacity: 0.5; cursor: not-allowed; }\
     .rag-typing { display: flex; gap: 4px; padding: 10px 14px; align-self: flex-start; }\
     .rag-typing span { width: 6px; height: 6px; background: #aaa; border-radius: 50%; animation: rag-bounce 1.4s infinite ease-in-out; }\
@@ -168,7 +197,7 @@
     var shadow = host.attachShadow({ mode: "closed" });
 
     var styleEl = document.createElement("style");
-    styleEl.textContent = styles + mdStyles;
+    styleEl.textContent = themeStyles() + styles + mdStyles;
     shadow.appendChild(styleEl);
 
     // Inject custom CSS from config (after base styles so it can override)
@@ -186,8 +215,16 @@
     // Bubble
     bubble = document.createElement("div");
     bubble.className = "rag-bubble";
-    bubble.innerHTML =
-      '<svg viewBox="0 0 24 24"><path d="M20 2H4c-1.1 0-2 .9-2 2v18l4-4h14c1.1 0 2-.9 2-2V4c0-1.1-.9-2-2-2z"/></svg>';
+    if (config.avatar_url) {
+      var avatar = document.createElement("img");
+      avatar.className = "rag-avatar";
+      avatar.src = SERVER + config.avatar_url;
+      avatar.alt = "";
+      bubble.appendChild(avatar);
+    } else {
+      bubble.innerHTML =
+        '<svg viewBox="0 0 24 24"><path d="M20 2H4c-1.1 0-2 .9-2 2v18l4-4h14c1.1 0 2-.9 2-2V4c0-1.1-.9-2-2-2z"/></svg>';
+    }
     bubble.onclick = togglePanel;
     widgetEl.appendChild(bubble);
 
@@ -206,8 +243,24 @@
     closeBtn.className = "rag-header-close";
     closeBtn.textContent = "\u00d7";
     closeBtn.onclick = togglePanel;
+    var actions = document.createElement("div");
+    actions.className = "rag-header-actions";
+    if (config.handoff_enabled) {
+      var handoffBtn = document.createElement("button");
+      handoffBtn.className = "rag-header-handoff";
+      handoffBtn.textContent = "Talk to a person";
+      handoffBtn.onclick = showHandoffForm;
+      actions.appendChild(handoffBtn);
+    }
+    var clearBtn = document.createElement("button");
+    clearBtn.className = "rag-header-clear";
+    clearBtn.textContent = "Clear";
+    clearBtn.title = "Delete this chat history";
+    clearBtn.onclick = clearHistory;
+    actions.appendChild(clearBtn);
+    actions.appendChild(closeBtn);
     header.appendChild(title);
-    header.appendChild(closeBtn);
+    header.appendChild(actions);
     chatPanel.appendChild(header);
 
     // Messages
@@ -238,20 +291,58 @@
     inputArea.appendChild(inputField);
     inputArea.appendChild(sendBtn);
     chatPanel.appendChild(inputArea);
+  }
 
-    applyColors();
+  // Theme values end up inside a <style> element. The server only stores plain
+  // colors and font names, but anything that could close the declaration or
+  // the element is dropped here too.
+  function cssValue(value, fallback) {
+    if (typeof value === "string" && value && !/[;{}<>\\\n\r]|url\(|@import|expression/i.test(value)) {
+      return value;
+    }
+    return fallback;
   }
 
-  function applyColors() {
-    var c = config.primary_color;
-    bubble.style.background = c;
-    chatPanel.querySelector(".rag-header").style.background = c;
-    sendBtn.style.background = c;
-    // Update user message colors
-    var userMsgs = msgList.querySelectorAll(".rag-msg-user");
-    for (var i = 0; i < userMsgs.length; i++) {
-      userMsgs[i].style.background = c;
+  function themeColorVars(colors, fallback) {
+    colors = colors || {};
+    var primary = cssValue(colors.primary, fallback.primary);
+    return (
+      "--rag-primary: " + primary + ";" +
+      "--rag-background: " + cssValue(colors.background, fallback.background) + ";" +
+      "--rag-text: " + cssValue(colors.text, fallback.text) + ";" +
+      "--rag-user-bubble: " + cssValue(colors.user_bubble, primary) + ";" +
+      "--rag-user-text: " + cssValue(colors.user_text, fallback.user_text) + ";" +
+      "--rag-assistant-bubble: " + cssValue(colors.assistant_bubble, fallback.assistant_bubble) + ";" +
+      "--rag-assistant-text: " + cssValue(colors.assistant_text, fallback.assistant_text) + ";"
+    );
+  }
+
+  function themeStyles() {
+    // Servers that predate themes only send primary_color
+    var theme = config.theme || {};
+    var light = {
+      primary: cssValue(config.primary_color, "#2563eb"),
+      background: "#ffffff",
+      text: "#333333",
+      user_text: "#ffffff",
+      assistant_bubble: "#f1f3f5",
+      assistant_text: "#333333",
+    };
+    var radius = parseInt(theme.border_radius, 10);
+    var css =
+      ":host {" +
+      themeColorVars(theme.light, light) +
+      "--rag-radius: " + (radius >= 0 && radius <= 32 ? radius : 12) + "px;" +
+      "--rag-font-family: " +
+      cssValue(theme.font_family, '-apple-system, BlinkMacSystemFont, "Segoe UI", Roboto, sans-serif') +
+      "; }";
+    if (theme.dark) {
+      css +=
+        "@media (prefers-color-scheme: dark) { :host {" +
+        themeColorVars(theme.dark, light) +
+        "} }";
     }
+    return css;
   }
 
   function togglePanel() {
@@ -261,6 +352,9 @@
     if (isOpen && messages.length === 0) {
       addMessage("assistant", config.greeting_message, "rag-msg-greeting");
     }
+    if (isOpen && !config.is_online && !offlineShown) {
+      showOffline(config.offline_message);
+    }
     if (isOpen) {
       inputField.focus();
     }
@@ -273,7 +367,6 @@
       (extraClass || (role === "user" ? "rag-msg-user" : "rag-msg-assistant"));
     if (role === "user") {
       msg.textContent = content;
-      msg.style.background = config.primary_color;
     } else {
       msg.innerHTML = simpleMarkdown(content);
     }
@@ -306,12 +399,46 @@
     msgList.scrollTop = msgList.scrollHeight;
   }
 
+  function showRateLimited(text) {
+    isRateLimited = true;
+    showSystemMessage(text || "Message limit reached for this session.");
+    inputField.disabled = true;
+    sendBtn.disabled = true;
+  }
+
+  // Outside office hours: with offline_behavior "block" the input stays
+  // disabled and visitors are offered the handoff form instead
+  function showOffline(text) {
+    offlineShown = true;
+    showSystemMessage(text || config.offline_message);
+    if (config.offline_behavior === "block") {
+      isOffline = true;
+      inputField.disabled = true;
+      sendBtn.disabled = true;
+      if (config.handoff_enabled) showHandoffForm();
+    }
+  }
+
   function setInputEnabled(enabled) {
     inputField.disabled = !enabled;
     sendBtn.disabled = !enabled;
     isLoading = !enabled;
   }
 
+  async function clearHistory() {
+    if (isLoading) return;
+    try {
+      var res = await apiFetch("/api/widget/conversations", { method: "DELETE" });
+      if (!res.ok) throw new Error("Failed to clear history");
+      clearConversationId();
+      messages = [];
+      msgList.innerHTML = "";
+      addMessage("assistant", config.greeting_message, "rag-msg-greeting");
+    } catch (e) {
+      showSystemMessage("Could not clear the chat history. Please try again.");
+    }
+  }
+
   async function ensureConversation() {
     var convId = getConversationId();
     if (convId) return convId;
@@ -328,7 +455,7 @@
   }
 
   async function sendMessage() {
-    if (isLoading || isRateLimited) return;
+    if (isLoading || isRateLimited || isOffline) return;
 
     var text = inputField.value.trim();
     if (!text) return;
@@ -342,7 +469,7 @@
       var typing = addTypingIndicator();
 
       var res = await fetch(
-        SERVER + "/api/widget/conversations/" + convId + "/messages",
+        SERVER + withLang("/api/widget/conversations/" + convId + "/messages"),
         {
           method: "POST",
           headers: apiHeaders(),
@@ -352,10 +479,11 @@
 
       if (res.status === 429) {
         removeTypingIndicator();
-        isRateLimited = true;
-        showSystemMessage("Message limit reached for this session.");
-        inputField.disabled = true;
-        sendBtn.disabled = true;
+        // quota_exceeded: the whole widget is out of messages, not just this session
+        var limitData = await res.json().catch(function () {
+          return {};
+        });
+        showRateLimited(limitData.code === "quota_exceeded" ? limitData.error : null);
         return;
       }
 
@@ -363,6 +491,13 @@
         removeTypingIndicator();
         try {
           var errData = await res.json();
+          if (errData.code === "widget_offline") {
+            // Office hours ended while the widget was open
+            config.offline_behavior = "block";
+            isLoading = false;
+            showOffline(errData.error);
+            return;
+          }
           showSystemMessage(
             errData.error || "Something went wrong. Please try again.",
           );
@@ -378,10 +513,12 @@
       var decoder = new TextDecoder();
       var assistantContent = "";
       var assistantMsg = null;
+      // Events are `token` (reply text), `done` and `error` ({"error": "..."});
+      // the typing indicator stays up until the first of them arrives
+      var eventType = "message";
+      var finished = false;
 
-      removeTypingIndicator();
-
-      while (true) {
+      while (!finished) {
         var result = await reader.read();
         if (result.done) break;
 
@@ -390,12 +527,36 @@
 
         for (var i = 0; i < lines.length; i++) {
           var line = lines[i].replace(/^\s+/, "");
+          if (line === "") {
+            eventType = "message";
+            continue;
+          }
+          if (line.startsWith("event:")) {
+            eventType = line.substring(6).trim();
+            continue;
+          }
           if (!line.startsWith("data:")) continue;
           var data = line.substring(5);
           // Strip single leading space after "data:" per SSE spec, preserve all other whitespace
           if (data.charAt(0) === " ") data = data.substring(1);
 
-          if (data.trim() === "[DONE]") break;
+          removeTypingIndicator();
+          if (eventType === "done" || data.trim() === "[DONE]") {
+            finished = true;
+            break;
+          }
+          if (eventType === "warning") continue;
+          if (eventType === "error") {
+            var message = "Something went wrong. Please try again.";
+            try {
+              message = JSON.parse(data).error || message;
+            } catch (e) {
+              // Keep the generic message
+            }
+            showSystemMessage(message);
+            finished = true;
+            break;
+          }
 
           assistantContent += data;
 
@@ -412,6 +573,7 @@
           msgList.scrollTop = msgList.scrollHeight;
         }
       }
+      removeTypingIndicator();
 
       if (assistantContent) {
         // Render markdown now that streaming is complete
@@ -430,16 +592,86 @@
     inputField.focus();
   }
 
+  // Ask for an email address so a person can follow up on the conversation
+  function showHandoffForm() {
+    if (msgList.querySelector(".rag-handoff")) return;
+
+    var form = document.createElement("form");
+    form.className = "rag-handoff";
+    var label = document.createElement("div");
+    label.className = "rag-handoff-label";
+    label.textContent = "Leave your email and we'll get back to you.";
+    var email = document.createElement("input");
+    email.type = "email";
+    email.required = true;
+    email.placeholder = "you@example.com";
+    var note = document.createElement("textarea");
+    note.placeholder = "What do you need help with? (optional)";
+    var submit = document.createElement("button");
+    submit.type = "submit";
+    submit.className = "rag-send";
+    submit.textContent = "Request a follow-up";
+
+    form.appendChild(label);
+    form.appendChild(email);
+    form.appendChild(note);
+    form.appendChild(submit);
+    form.onsubmit = async function (e) {
+      e.preventDefault();
+      submit.disabled = true;
+      try {
+        var convId = await ensureConversation();
+        var res = await apiFetch(
+          "/api/widget/conversations/" + convId + "/handoff",
+          {
+            method: "POST",
+            body: JSON.stringify({ email: email.value, message: note.value }),
+          },
+        );
+        if (res.ok) {
+          form.remove();
+          showSystemMessage("Thanks! Someone will contact you by email.");
+          return;
+        }
+        var err = await res.json().catch(function () {
+          return {};
+        });
+        showSystemMessage(
+          res.status === 429
+            ? "You have already asked for a follow-up. We'll be in touch."
+            : err.error || "Could not send your request. Please try again.",
+        );
+      } catch (e) {
+        showSystemMessage("Could not send your request. Please try again.");
+      }
+      submit.disabled = false;
+    };
+
+    msgList.appendChild(form);
+    msgList.scrollTop = msgList.scrollHeight;
+    email.focus();
+  }
+
   async function loadConfig() {
     try {
-      var res = await apiFetch("/api/widget/config");
+      var res = await apiFetch(withLang("/api/widget/config"));
       if (res.ok) {
         var data = await res.json();
         config.widget_title = data.widget_title || config.widget_title;
         config.primary_color = data.primary_color || config.primary_color;
+        config.theme = data.theme || null;
         config.greeting_message =
           data.greeting_message || config.greeting_message;
         config.custom_css = data.custom_css || "";
+        config.handoff_enabled = !!data.handoff_enabled;
+        config.avatar_url = data.avatar_url || null;
+        config.is_online = data.is_online !== false;
+        config.offline_message = data.offline_message || "";
+        config.offline_behavior = data.offline_behavior || "notice";
+        if (typeof data.rate_limit === "number") {
+          config.rate_limit = data.rate_limit;
+          config.messages_used = data.messages_used_in_window || 0;
+        }
       }
     } catch (e) {
       console.warn("[RAG Widget] Failed to load config", e);
@@ -472,6 +704,9 @@
     await loadConfig();
     createWidget();
     await loadHistory();
+    if (config.rate_limit !== null && config.messages_used >= config.rate_limit) {
+      showRateLimited();
+    }
   }
 
   if (document.readyState === "loading") {
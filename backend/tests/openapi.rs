@@ -0,0 +1,90 @@
+//! Checks that every route registered in `main.rs` and `routes/mod.rs` is
+//! documented in the OpenAPI spec, so new handlers can't silently go missing
+//! from it. Run with:
+//!
+//! ```sh
+//! cargo test --features openapi --test openapi
+//! ```
+#![cfg(feature = "openapi")]
+
+use rag_backend::openapi::ApiDoc;
+use utoipa::OpenApi;
+
+/// The files that register routes.
+const ROUTER_SOURCES: &[&str] = &[include_str!("../src/main.rs"), include_str!("../src/routes/mod.rs")];
+
+/// Routes served outside the documented API.
+const UNDOCUMENTED: &[&str] = &["/api/openapi.json", "/api/docs"];
+
+const METHODS: &[&str] = &["get", "post", "put", "patch", "delete"];
+
+/// `(path, methods)` for each `.route("...", ...)` call in [`ROUTER_SOURCES`].
+fn registered_routes() -> Vec<(String, Vec<&'static str>)> {
+    ROUTER_SOURCES
+        .iter()
+        .flat_map(|source| source.split(".route(").skip(1))
+        .filter_map(|call| {
+            let call = call.trim_start();
+            let path = call.strip_prefix('"')?.split('"').next()?.to_string();
+            // The method router ends where the next chained builder call starts
+            let handlers = call.split(".layer(").next().unwrap_or(call);
+            let is_ident = |b: u8| b.is_ascii_alphanumeric() || b == b'_';
+            let methods = METHODS
+                .iter()
+                .copied()
+                .filter(|m| {
+                    handlers
+                        .match_indices(&format!("{m}("))
+                        .any(|(i, _)| i == 0 || !is_ident(handlers.as_bytes()[i - 1]))
+                })
+                .collect();
+            Some((path, methods))
+        })
+        .collect()
+}
+
+#[test]
+fn routes_parse_from_router_sources() {
+    let routes = registered_routes();
+    assert!(routes.len() > 40, "expected to find the router, got {routes:?}");
+    let (_, methods) = routes
+        .iter()
+        .find(|(path, _)| path == "/api/conversations/{id}")
+        .expect("conversation route is registered");
+    assert_eq!(methods, &["get", "patch", "delete"]);
+}
+
+#[test]
+fn every_registered_route_is_documented() {
+    let spec = ApiDoc::openapi();
+    let mut missing = Vec::new();
+
+    for (path, methods) in registered_routes() {
+        if UNDOCUMENTED.contains(&path.as_str()) {
+            continue;
+        }
+        let Some(item) = spec.paths.paths.get(&path) else {
+            missing.push(format!("{path} (all methods)"));
+            continue;
+        };
+        for method in methods {
+            let documented = match method {
+                "get" => item.get.is_some(),
+                "post" => item.post.is_some(),
+                "put" => item.put.is_some(),
+                "patch" => item.patch.is_some(),
+                "delete" => item.delete.is_some(),
+                _ => unreachable!(),
+            };
+            if !documented {
+                missing.push(format!("{} {path}", method.to_uppercase()));
+            }
+        }
+    }
+
+    assert!(
+        missing.is_empty(),
+        "Routes missing from the OpenAPI spec (add a utoipa::path and list them in ApiDoc):\n{}",
+        missing.join("\n")
+    );
+}
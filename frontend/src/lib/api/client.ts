@@ -104,6 +104,7 @@ export const api = {
   stream: async function* (
     endpoint: string,
     data: unknown,
+    onWarning?: (message: string) => void,
   ): AsyncGenerator<string> {
     const token = getToken();
 
@@ -131,18 +132,42 @@ export const api = {
     if (!reader) throw new Error("No response body");
 
     const decoder = new TextDecoder();
+    // Events are `warning` ({"warning": "..."}), `token` (reply text), `done`
+    // and `error` ({"error": "..."})
+    let eventType = "message";
 
     while (true) {
       const { done, value } = await reader.read();
       if (done) break;
 
-      const chunk = decoder.decode(value);
+      const chunk = decoder.decode(value, { stream: true });
       const lines = chunk.split("\n");
 
       for (const line of lines) {
-        if (line.startsWith("data: ")) {
+        if (line === "") {
+          eventType = "message";
+        } else if (line.startsWith("event: ")) {
+          eventType = line.slice(7);
+        } else if (line.startsWith("data: ")) {
           const data = line.slice(6);
-          if (data === "[DONE]") return;
+          if (eventType === "done" || data === "[DONE]") return;
+          if (eventType === "warning") {
+            try {
+              onWarning?.(JSON.parse(data).warning);
+            } catch {
+              // Ignore malformed warnings
+            }
+            continue;
+          }
+          if (eventType === "error") {
+            let message = "Failed to get response";
+            try {
+              message = JSON.parse(data).error || message;
+            } catch {
+              // Keep the generic message
+            }
+            throw new Error(message);
+          }
           yield data;
         }
       }
@@ -34,12 +34,31 @@ export interface Document {
   error_message: string | null;
   created_at: string;
   processed_at: string | null;
+  tags: string[];
+  collection_id: string | null;
+}
+
+/** One step of a document's processing log. */
+export interface DocumentEvent {
+  id: number;
+  document_id: string;
+  level: "info" | "warn" | "error";
+  message: string;
+  created_at: string;
+}
+
+export interface Collection {
+  id: string;
+  name: string;
+  owner_id: string;
+  document_count: number;
+  created_at: string;
 }
 
 export interface CrawlJob {
   id: string;
   url: string;
-  crawl_type: "sitemap" | "full";
+  crawl_type: "sitemap" | "full" | "page";
   status: "pending" | "running" | "completed" | "failed";
   pages_found: number;
   pages_processed: number;
@@ -49,6 +68,18 @@ export interface CrawlJob {
   completed_at: string | null;
 }
 
+export interface CrawlSchedule {
+  id: string;
+  user_id: string;
+  url: string;
+  crawl_type: "sitemap" | "full";
+  frequency: "daily" | "weekly";
+  next_run_at: string;
+  last_job_id: string | null;
+  created_at: string;
+  updated_at: string;
+}
+
 export interface LlmPreferences {
   preferred_provider: string;
   preferred_model: string;
@@ -59,6 +90,8 @@ export interface LlmPreferences {
 export interface ApiKey {
   id: string;
   provider: string;
+  key_preview: string;
+  last_used_at: string | null;
   created_at: string;
 }
 
@@ -66,8 +99,10 @@ export interface Conversation {
   id: string;
   user_id: string;
   title: string;
+  collection_id: string | null;
   created_at: string;
   updated_at: string;
+  archived_at?: string;
 }
 
 export interface Message {
@@ -78,10 +113,46 @@ export interface Message {
   created_at: string;
 }
 
+export interface ConversationSettings {
+  provider: string | null;
+  model: string | null;
+  system_prompt: string | null;
+}
+
+export interface ConversationChatSettings {
+  overrides: ConversationSettings;
+  effective: {
+    provider: string;
+    model: string;
+    system_prompt: string;
+  };
+  warnings: string[];
+}
+
 export interface ConversationWithMessages extends Conversation {
+  settings: ConversationChatSettings;
   messages: Message[];
 }
 
+export interface CreateShareResponse {
+  share: {
+    id: string;
+    conversation_id: string;
+    expires_at: string;
+    revoked: boolean;
+    created_at: string;
+  };
+  url: string;
+  token: string;
+}
+
+/** A conversation opened through a public share link. */
+export interface SharedConversation {
+  title: string;
+  created_at: string;
+  messages: { role: "user" | "assistant"; content: string; created_at: string }[];
+}
+
 export interface AdminProvider {
   id: string;
   provider_id: string;
@@ -100,6 +171,9 @@ export interface AdminModel {
   model_type: "completion" | "embedding";
   is_default: boolean;
   created_at: string;
+  removed_at: string | null;
+  sort_order: number;
+  is_enabled: boolean;
 }
 
 export interface ConversationLog {
@@ -111,6 +185,7 @@ export interface ConversationLog {
   message_count: number;
   created_at: string;
   updated_at: string;
+  deleted_at?: string;
 }
 
 export interface LogsResponse {
@@ -129,6 +204,7 @@ export interface WidgetConversationLog {
   message_count: number;
   created_at: string;
   updated_at: string;
+  deleted_at?: string;
 }
 
 export interface WidgetLogsResponse {
@@ -156,10 +232,18 @@ export interface EmbedKey {
   rate_limit: number;
   widget_title: string;
   primary_color: string;
+  theme: WidgetTheme;
   greeting_message: string;
   provider: string;
   model: string;
   custom_css: string;
+  handoff_enabled: boolean;
+  handoff_notification_email: string | null;
+  transcript_email_enabled: boolean;
+  office_hours: OfficeHours | null;
+  offline_message: string;
+  offline_behavior: 'notice' | 'block';
+  collection_id: string | null;
   is_active: boolean;
   total_conversations: number;
   total_messages: number;
@@ -167,6 +251,42 @@ export interface EmbedKey {
   updated_at: string;
 }
 
+export interface WidgetThemeColors {
+  primary?: string;
+  background?: string;
+  text?: string;
+  user_bubble?: string;
+  user_text?: string;
+  assistant_bubble?: string;
+  assistant_text?: string;
+}
+
+export interface WidgetTheme {
+  light: WidgetThemeColors;
+  dark?: WidgetThemeColors | null;
+  border_radius?: number | null;
+  font_family?: string | null;
+}
+
+export interface OfficeHours {
+  timezone: string;
+  /** Keyed by `mon` to `sun`; a range ending before it starts runs past midnight. */
+  days: Record<string, { start: string; end: string }[]>;
+}
+
+export interface WidgetHandoff {
+  id: string;
+  conversation_id: string;
+  embed_key_id: string;
+  embed_key_name: string;
+  session_id: string;
+  email: string;
+  message: string;
+  status: 'open' | 'closed';
+  created_at: string;
+  updated_at: string;
+}
+
 export interface CreateEmbedKeyResponse {
   embed_key: EmbedKey;
   raw_key: string;